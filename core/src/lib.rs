@@ -0,0 +1,213 @@
+//! The membership test of fractal-slicer's subdivision rules and the digit
+//! math under it, without `std` or an allocator, so a cell can be tested
+//! on embedded targets and in GPU shaders as well as in the slicer.
+//!
+//! A rule is its bases, its [`Split`] and a keep-mask lookup: `keep(level,
+//! index)` says whether subdivision step `level`, from 1 at the coarsest,
+//! keeps the subcell with flat index `index`, digits from [`decompose`].
+//! Rules of up to [`MAX_DIMS`] axes are supported, scratch space living on
+//! the stack.
+
+#![no_std]
+
+/// Most axes a rule may have.
+pub const MAX_DIMS: usize = 16;
+
+/// How a cell is divided into the subcells the keep-mask refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Split {
+    /// `bases[i]` equal parts along axis `i`; each kept subcell is a copy
+    /// one level down.
+    Uniform,
+    /// Three parts per axis in the ratio `1 : √2 - 1 : 1`, on sides that
+    /// follow the Pell numbers 1, 2, 5, 12, 29, … so the ratio stays exact
+    /// in whole cells. Subcells with only outer digits hold a copy one level
+    /// down; the rest hold a copy two levels down, pushed against the outer
+    /// faces. The depth-`d` lattice is `pell(d + 1)` cells wide.
+    Pell,
+    /// Octahedral copies: each kept digit tuple `t` places a copy one level
+    /// down centred `(t - 1) * 2^(d-1)` cells from the centre of a depth-`d`
+    /// lattice `2^(d+1) - 1` cells wide. Copies are bounded by L1 balls, so
+    /// they never overlap as long as no two kept tuples differ by one in a
+    /// single digit.
+    Flake,
+}
+
+/// Writes the digits of a flat subcell index into `digits`, least
+/// significant axis first, axis `i` in base `bases[i]`.
+pub fn decompose(mut index: usize, bases: &[u32], digits: &mut [u32]) {
+    for (d, &base) in digits.iter_mut().zip(bases) {
+        *d = (index % base as usize) as u32;
+        index /= base as usize;
+    }
+}
+
+/// Inverse of [`decompose`].
+pub fn compose(digits: &[u32], bases: &[u32]) -> usize {
+    digits
+        .iter()
+        .zip(bases)
+        .rev()
+        .fold(0, |acc, (&d, &base)| acc * base as usize + d as usize)
+}
+
+/// The Pell numbers 0, 1, 2, 5, 12, 29, …, with `pell(n) = 0` for `n <= 0`,
+/// saturating at `usize::MAX`.
+pub fn pell(n: i64) -> usize {
+    let (mut a, mut b) = (0usize, 1usize);
+    for _ in 0..n.max(0) {
+        if a == usize::MAX {
+            break;
+        }
+        (a, b) = (b, b.saturating_mul(2).saturating_add(a));
+    }
+    a
+}
+
+/// The Pell subcell of the depth-`depth` lattice containing `c` along
+/// one axis: its digit, the depth of the copy it holds if `c` falls in
+/// that copy, and `c` relative to the copy.
+pub fn pell_digit(c: usize, depth: i64, inner: bool) -> (u32, Option<(i64, usize)>) {
+    let (side, outer_side) = (pell(depth + 1), pell(depth));
+    let (piece_depth, piece) = if inner {
+        (depth - 2, pell(depth - 1))
+    } else {
+        (depth - 1, outer_side)
+    };
+    let middle = outer_side + pell(depth - 1);
+    if c < outer_side {
+        (0, (c < piece).then_some((piece_depth, c)))
+    } else if c < middle {
+        (1, Some((piece_depth, c - outer_side)))
+    } else {
+        (
+            2,
+            (c >= side - piece).then(|| (piece_depth, c - (side - piece))),
+        )
+    }
+}
+
+/// Membership test for the cell at `coords` in the depth-`depth` lattice
+/// of the rule with `bases`, `split` and keep-mask lookup `keep`.
+///
+/// For a uniform split, walks the digits of every coordinate, in its
+/// axis's base, from the least significant level upwards and rejects the
+/// cell as soon as one level removes it.
+pub fn is_solid(
+    bases: &[u32],
+    split: Split,
+    coords: &[usize],
+    depth: u32,
+    keep: impl Fn(u32, usize) -> bool,
+) -> bool {
+    assert!(
+        bases.len() <= MAX_DIMS,
+        "a rule has at most {MAX_DIMS} axes"
+    );
+    debug_assert_eq!(coords.len(), bases.len());
+    match split {
+        Split::Uniform => {}
+        Split::Pell => return is_solid_pell(bases, coords, depth, keep),
+        Split::Flake => return is_solid_flake(bases, coords, depth, keep),
+    }
+    let mut rest = [0; MAX_DIMS];
+    rest[..coords.len()].copy_from_slice(coords);
+    for level in (1..=depth).rev() {
+        let mut index = 0;
+        let mut stride = 1;
+        for (c, &base) in rest.iter_mut().zip(bases) {
+            let base = base as usize;
+            index += *c % base * stride;
+            stride *= base;
+            *c /= base;
+        }
+        if !keep(level, index) {
+            return false;
+        }
+    }
+    true
+}
+
+/// [`is_solid`] for the Pell split: descends into the copy holding the
+/// cell until it reaches a single cell or falls in a gap.
+fn is_solid_pell(
+    bases: &[u32],
+    coords: &[usize],
+    depth: u32,
+    keep: impl Fn(u32, usize) -> bool,
+) -> bool {
+    let dims = coords.len();
+    let mut local = [0; MAX_DIMS];
+    local[..dims].copy_from_slice(coords);
+    let local = &mut local[..dims];
+    let mut digits = [0; MAX_DIMS];
+    let digits = &mut digits[..dims];
+    let mut depth = depth as i64;
+    while depth > 0 {
+        let outer_side = pell(depth);
+        for (digit, &c) in digits.iter_mut().zip(local.iter()) {
+            *digit = if c < outer_side {
+                0
+            } else if c < outer_side + pell(depth - 1) {
+                1
+            } else {
+                2
+            };
+        }
+        if !keep(1, compose(digits, bases)) {
+            return false;
+        }
+        let inner = digits.contains(&1);
+        let mut next_depth = depth;
+        for c in local.iter_mut() {
+            let (_, piece) = pell_digit(*c, depth, inner);
+            let Some((d, offset)) = piece else {
+                return false;
+            };
+            (next_depth, *c) = (d, offset);
+        }
+        depth = next_depth;
+    }
+    depth == 0
+}
+
+/// [`is_solid`] for the flake split: descends into the one copy whose L1
+/// ball holds the cell until it reaches the centre cell of a depth-0 copy
+/// or falls between copies.
+fn is_solid_flake(
+    bases: &[u32],
+    coords: &[usize],
+    depth: u32,
+    keep: impl Fn(u32, usize) -> bool,
+) -> bool {
+    let dims = coords.len();
+    let subcells: usize = bases.iter().map(|&b| b as usize).product();
+    let centre = (1i64 << depth) - 1;
+    let mut r = [0; MAX_DIMS];
+    for (r, &c) in r.iter_mut().zip(coords) {
+        *r = c as i64 - centre;
+    }
+    let r = &mut r[..dims];
+    let mut digits = [0; MAX_DIMS];
+    let digits = &mut digits[..dims];
+    for level in (1..=depth).rev() {
+        let half = 1i64 << (level - 1);
+        let distance = |digits: &[u32], r: &[i64]| -> i64 {
+            r.iter()
+                .zip(digits)
+                .map(|(&c, &d)| (c - (d as i64 - 1) * half).abs())
+                .sum()
+        };
+        let found = (0..subcells).filter(|&i| keep(1, i)).any(|index| {
+            decompose(index, bases, digits);
+            distance(digits, r) < half
+        });
+        if !found {
+            return false;
+        }
+        for (c, &d) in r.iter_mut().zip(digits.iter()) {
+            *c -= (d as i64 - 1) * half;
+        }
+    }
+    r.iter().all(|&c| c == 0)
+}
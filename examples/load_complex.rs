@@ -0,0 +1,103 @@
+//! Reads a binary cell complex written by `fractal-slicer generate --dims 4
+//! --complex cells.cx4`, checks that its cells and facets point at each
+//! other, and prints its counts.
+//!
+//! cargo run --example load_complex -- cells.cx4
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: load_complex FILE.cx4");
+        return ExitCode::FAILURE;
+    };
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match load(&bytes) {
+        Ok(complex) => {
+            let boundary = complex
+                .facets
+                .iter()
+                .filter(|facet| facet[9..].contains(&u32::MAX))
+                .count();
+            println!(
+                "shape {:?}: {} vertices, {} facets ({boundary} on the boundary), {} cells",
+                complex.shape,
+                complex.vertices.len(),
+                complex.facets.len(),
+                complex.cells.len()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {path}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+struct Complex {
+    shape: [u32; 4],
+    vertices: Vec<[u32; 4]>,
+    /// Axis, 8 vertices, and the cells below and above.
+    facets: Vec<[u32; 11]>,
+    /// Position, 16 vertices and 8 facets.
+    cells: Vec<[u32; 28]>,
+}
+
+fn load(bytes: &[u8]) -> Result<Complex, String> {
+    let header = bytes.get(..48).ok_or("file too short")?;
+    if &header[..4] != b"FS4C" || header[4] != 1 {
+        return Err("not a version 1 cell complex".into());
+    }
+    let u64_at = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap()) as usize;
+    let counts = [u64_at(24), u64_at(32), u64_at(40)];
+    let values: Vec<u32> = bytes[8..]
+        .chunks_exact(4)
+        .map(|value| u32::from_le_bytes(value.try_into().unwrap()))
+        .collect();
+    let body = &values[10..];
+    if body.len() != counts[0] * 4 + counts[1] * 11 + counts[2] * 28 {
+        return Err("cell data does not match the header".into());
+    }
+    let (vertices, rest) = body.split_at(counts[0] * 4);
+    let (facets, cells) = rest.split_at(counts[1] * 11);
+    let complex = Complex {
+        shape: values[..4].try_into().unwrap(),
+        vertices: vertices
+            .chunks_exact(4)
+            .map(|v| v.try_into().unwrap())
+            .collect(),
+        facets: facets
+            .chunks_exact(11)
+            .map(|f| f.try_into().unwrap())
+            .collect(),
+        cells: cells
+            .chunks_exact(28)
+            .map(|c| c.try_into().unwrap())
+            .collect(),
+    };
+    // Each cell is above its low facets and below its high ones, and the
+    // vertices of its facets are among its own.
+    for (index, cell) in complex.cells.iter().enumerate() {
+        for (f, &facet) in cell[20..].iter().enumerate() {
+            let facet = complex
+                .facets
+                .get(facet as usize)
+                .ok_or("a cell names a missing facet")?;
+            let side = 10 - f % 2;
+            if facet[0] as usize != f / 2 || facet[side] != index as u32 {
+                return Err(format!("cell {index} and its facet {f} disagree"));
+            }
+            if !facet[1..9].iter().all(|v| cell[4..20].contains(v)) {
+                return Err(format!("facet {f} of cell {index} has a foreign vertex"));
+            }
+        }
+    }
+    Ok(complex)
+}
@@ -0,0 +1,98 @@
+//! Reads a `.inst` file written by `fractal-slicer generate -o cells.inst`
+//! and prints its instance count and bounds, as a starting point for
+//! uploading the instances to a GPU buffer.
+//!
+//! cargo run --example load_instances -- cells.inst
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: load_instances FILE.inst");
+        return ExitCode::FAILURE;
+    };
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("error: {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match load(&bytes) {
+        Ok(instances) => {
+            let mut low = [f32::INFINITY; 3];
+            let mut high = [f32::NEG_INFINITY; 3];
+            for [x, y, z, _] in &instances {
+                for (a, c) in [x, y, z].into_iter().enumerate() {
+                    low[a] = low[a].min(*c);
+                    high[a] = high[a].max(*c);
+                }
+            }
+            println!(
+                "{} instances, centres from {low:?} to {high:?}",
+                instances.len()
+            );
+            if let Some(first) = instances.first() {
+                println!("first: centre {:?}, edge {}", &first[..3], first[3]);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {path}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The instances as centre x, y, z and edge length, widened to `f32`.
+fn load(bytes: &[u8]) -> Result<Vec<[f32; 4]>, String> {
+    let header = bytes.get(..16).ok_or("file too short")?;
+    if &header[..4] != b"FSIN" || header[4] != 1 {
+        return Err("not a version 1 instance file".into());
+    }
+    let width = header[5] as usize;
+    let quantized = header[6] == 1;
+    let count = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+    let mut body = &bytes[16..];
+    // Quantized values are scaled back as `offset + step * q`.
+    let mut dequantize = [[0.0; 2]; 4];
+    if quantized {
+        let floats = body.get(..32).ok_or("file too short")?;
+        for (i, float) in floats.chunks_exact(4).enumerate() {
+            dequantize[i % 4][i / 4] = f32::from_le_bytes(float.try_into().unwrap());
+        }
+        body = &body[32..];
+    }
+    let valid = matches!((width, quantized), (2, _) | (4, false));
+    if !valid || body.len() != count * 4 * width {
+        return Err("instance data does not match the header".into());
+    }
+    let values: Vec<f32> = body
+        .chunks_exact(width)
+        .enumerate()
+        .map(|(i, value)| match width {
+            2 if quantized => {
+                let [offset, step] = dequantize[i % 4];
+                offset + step * u16::from_le_bytes([value[0], value[1]]) as f32
+            }
+            2 => f16_to_f32(u16::from_le_bytes([value[0], value[1]])),
+            _ => f32::from_le_bytes(value.try_into().unwrap()),
+        })
+        .collect();
+    Ok(values
+        .chunks_exact(4)
+        .map(|v| [v[0], v[1], v[2], v[3]])
+        .collect())
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
@@ -0,0 +1,63 @@
+//! Reads one z layer of a compressed NIfTI volume written by
+//! `fractal-slicer generate -o sponge.nii.zst`, decompressing only the
+//! frames the layer lies in, and prints how many of its cells are filled.
+//!
+//! cargo run --example read_slice -- sponge.nii.zst 13
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::process::ExitCode;
+
+use fractal_slicer_4_d::seekable::SeekableReader;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [path, z] = args.as_slice() else {
+        eprintln!("usage: read_slice FILE.nii.zst Z");
+        return ExitCode::FAILURE;
+    };
+    let Ok(z) = z.parse::<usize>() else {
+        eprintln!("error: `{z}` is not a layer index");
+        return ExitCode::FAILURE;
+    };
+    match read_slice(path, z) {
+        Ok((filled, total)) => {
+            println!("layer {z}: {filled} of {total} cells filled");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {path}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// The filled and total cell counts of layer `z`.
+fn read_slice(path: &str, z: usize) -> Result<(usize, usize), Box<dyn std::error::Error>> {
+    let mut volume = SeekableReader::new(File::open(path)?)?;
+    let mut header = [0; 352];
+    volume.read_exact(&mut header)?;
+    let short = |offset: usize| i16::from_le_bytes([header[offset], header[offset + 1]]);
+    let [nx, ny, nz] = [42, 44, 46].map(|offset| short(offset) as usize);
+    if z >= nz {
+        return Err(format!("layer {z} is outside the volume (depth {nz})").into());
+    }
+    // Occupancy bytes, or signed distances as `f32`, filled at or below 0.
+    let width = match short(70) {
+        2 => 1,
+        16 => 4,
+        datatype => return Err(format!("unexpected datatype {datatype}").into()),
+    };
+    let offset = f32::from_le_bytes(header[108..112].try_into().unwrap()) as u64;
+    let mut layer = vec![0; nx * ny * width];
+    volume.seek(SeekFrom::Start(offset + (z * layer.len()) as u64))?;
+    volume.read_exact(&mut layer)?;
+    let filled = match width {
+        1 => layer.iter().filter(|&&v| v != 0).count(),
+        _ => layer
+            .chunks_exact(4)
+            .filter(|v| f32::from_le_bytes((*v).try_into().unwrap()) <= 0.0)
+            .count(),
+    };
+    Ok((filled, nx * ny))
+}
@@ -0,0 +1,24 @@
+//! Job manifests, as batch files and the server hand them over: parsing
+//! and validating must reject bad input without panicking or running
+//! away.
+
+#![no_main]
+
+use fractal_slicer_4_d::job::Job;
+use fractal_slicer_4_d::rule::expected_cells;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(job) = serde_json::from_slice::<Job>(data) else {
+        return;
+    };
+    if job.validate().is_err() {
+        return;
+    }
+    if let Ok(rule) = job.rule() {
+        for level in 1..=job.depth.min(64) {
+            expected_cells(&rule, level);
+            rule.volume(level);
+        }
+    }
+});
@@ -0,0 +1,18 @@
+//! Imported models: the STL and OBJ readers must reject malformed files
+//! without panicking or running away.
+
+#![no_main]
+
+use fractal_slicer_4_d::import::{bounds, parse_obj, parse_stl};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mesh) = parse_stl(data, "fuzz.stl") {
+        bounds(&mesh);
+    }
+    if let Ok(text) = std::str::from_utf8(data) {
+        if let Ok(mesh) = parse_obj(text, "fuzz.obj") {
+            bounds(&mesh);
+        }
+    }
+});
@@ -0,0 +1,23 @@
+//! Custom rule files, as `rule test` reads them: parsing, building the
+//! rule and its closed-form counts must reject bad input without
+//! panicking or running away.
+
+#![no_main]
+
+use fractal_slicer_4_d::rule::expected_cells;
+use fractal_slicer_4_d::rule_test::RuleTest;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(test) = serde_json::from_slice::<RuleTest>(data) else {
+        return;
+    };
+    let Ok(rule) = test.custom.rule(test.custom.bases.len()) else {
+        return;
+    };
+    for level in 1..=test.depth.min(64) {
+        rule.survivors_at(level);
+        expected_cells(&rule, level);
+        rule.volume(level);
+    }
+});
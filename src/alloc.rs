@@ -0,0 +1,129 @@
+//! Memory accounting: the process's resident memory, and with the
+//! `alloc-stats` feature a counting global allocator whose totals job
+//! reports break down per stage.
+//!
+//! The counts are process-wide, so jobs run side by side in a batch count
+//! each other's allocations.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+/// The system allocator, counting what passes through it.
+pub struct Counting;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+static LIVE: AtomicU64 = AtomicU64::new(0);
+static PEAK: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "alloc-stats")]
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+impl Counting {
+    fn add(size: usize) {
+        let size = size as u64;
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(size, Ordering::Relaxed);
+        let live = LIVE.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn remove(size: usize) {
+        LIVE.fetch_sub(size as u64, Ordering::Relaxed);
+    }
+}
+
+// SAFETY: every call is passed straight to the system allocator.
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            Counting::add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Counting::add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        Counting::remove(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            Counting::remove(layout.size());
+            Counting::add(new_size);
+        }
+        new
+    }
+}
+
+/// Allocations made during one stage of a job.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Allocations {
+    pub count: u64,
+    pub bytes: u64,
+    /// Most bytes allocated at once during the stage, counting those
+    /// allocated before it.
+    pub peak_bytes: u64,
+}
+
+/// Measures the allocations from here to [`Stage::finish`]; None without
+/// the `alloc-stats` feature.
+pub(crate) fn start() -> Option<Stage> {
+    cfg!(feature = "alloc-stats").then(|| {
+        let live = LIVE.load(Ordering::Relaxed);
+        // The peak so far is another stage's; this one's starts from here.
+        PEAK.store(live, Ordering::Relaxed);
+        Stage {
+            count: ALLOCATIONS.load(Ordering::Relaxed),
+            bytes: ALLOCATED.load(Ordering::Relaxed),
+        }
+    })
+}
+
+/// The allocation totals at the start of a stage.
+pub(crate) struct Stage {
+    count: u64,
+    bytes: u64,
+}
+
+impl Stage {
+    pub(crate) fn finish(self) -> Allocations {
+        Allocations {
+            count: ALLOCATIONS.load(Ordering::Relaxed) - self.count,
+            bytes: ALLOCATED.load(Ordering::Relaxed) - self.bytes,
+            peak_bytes: PEAK.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The process's resident and peak resident memory in bytes, where the
+/// system reports them.
+pub fn resident() -> Option<(u64, u64)> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib = |key: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(key))
+            .and_then(|rest| {
+                rest.trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+    };
+    Some((kib("VmRSS:")? * 1024, kib("VmHWM:")? * 1024))
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::cancel::CancelToken;
+use crate::distance::squared_distances;
+use crate::error::{Error, Result};
+use crate::job::Job;
+use crate::lattice::{Boundary, Lattice};
+use crate::volume::MappedVolume;
+
+/// Pore-space statistics of a generated lattice, as used when treating the
+/// fractal as a porous medium.
+#[derive(Clone, Debug, Serialize)]
+pub struct Analysis {
+    pub job: String,
+    pub shape: Vec<usize>,
+    /// Fraction of cells that are empty.
+    pub porosity: f64,
+    pub empty_cells: usize,
+    /// Number of face-connected pores.
+    pub pores: usize,
+    /// Pore-size distribution from the distance transform of the empty
+    /// space: entry `r` counts empty cells whose nearest filled cell is at
+    /// least `r` and less than `r + 1` cells away.
+    pub pore_radii: Vec<u64>,
+    /// Distance from the most open empty cell to the nearest filled one,
+    /// in cells; infinite when no cell is filled.
+    pub max_pore_radius: f64,
+    /// Per axis, whether one pore touches both opposite faces.
+    pub percolates: Vec<bool>,
+}
+
+/// Boxes of a lattice's box-counting estimate of its dimension: at each
+/// box side, from one cell growing by a constant factor, the boxes that
+/// hold a filled cell, with the least-squares line through the log of the
+/// count against the log of one over the side, whose slope is the
+/// estimate.
+#[derive(Clone, Debug, Serialize)]
+pub struct BoxCounting {
+    pub job: String,
+    pub sides: Vec<usize>,
+    pub boxes: Vec<usize>,
+    /// The slope of the fitted line.
+    pub dimension: f64,
+    /// The fitted line's log count at a side of one cell.
+    pub intercept: f64,
+}
+
+impl Job {
+    /// Generates the job's lattice and counts its boxes, growing by the
+    /// rule's base for rules split evenly and equally along every axis,
+    /// so the estimate of a self-similar fractal is exact, and by 2
+    /// otherwise.
+    pub fn box_counting(&self, cancel: &CancelToken) -> Result<BoxCounting> {
+        self.validate()?;
+        if self.surface()?.is_some() {
+            return Err(Error::InvalidJob(format!(
+                "surface fractal `{}` has no lattice to count boxes of",
+                self.fractal
+            )));
+        }
+        let factor = self.levels()?.map_or(2, |levels| levels.base);
+        let name = self.display_name();
+        match self.dims {
+            3 => box_counting(&name, &self.generate::<3>(cancel)?, factor, cancel),
+            _ => box_counting(&name, &self.generate::<4>(cancel)?, factor, cancel),
+        }
+    }
+
+    /// Generates the job's 4D lattice and counts the filled cells of each
+    /// w slice, the cross-section's volume in cells along the sweep.
+    pub fn cross_sections(&self, cancel: &CancelToken) -> Result<Vec<usize>> {
+        self.validate()?;
+        if self.dims != 4 || self.surface()?.is_some() {
+            return Err(Error::InvalidJob(
+                "cross-sections along w need a 4D lattice".into(),
+            ));
+        }
+        let lattice = self.generate::<4>(cancel)?;
+        (0..lattice.shape()[3])
+            .map(|w| {
+                cancel.check()?;
+                Ok(lattice.slice_w(w).count())
+            })
+            .collect()
+    }
+
+    /// Generates the job's lattice and measures its pore space.
+    pub fn analyze(&self, cancel: &CancelToken) -> Result<Analysis> {
+        self.validate()?;
+        if self.surface()?.is_some() {
+            return Err(Error::InvalidJob(format!(
+                "surface fractal `{}` has no lattice to analyze",
+                self.fractal
+            )));
+        }
+        match self.dims {
+            3 => analyze(&self.display_name(), &self.generate::<3>(cancel)?, cancel),
+            _ => analyze(&self.display_name(), &self.generate::<4>(cancel)?, cancel),
+        }
+    }
+}
+
+/// Measures the pore space of a mapped volume, or of the box from `low` up
+/// to `high` in it, reading only the cells of the box.
+pub fn analyze_volume(
+    volume: &MappedVolume,
+    region: Option<([usize; 3], [usize; 3])>,
+    cancel: &CancelToken,
+) -> Result<Analysis> {
+    let name = volume.path().display().to_string();
+    match region {
+        Some((low, high)) => {
+            let name = format!("{name} [{low:?}..{high:?}]");
+            analyze(&name, &volume.region(low, high), cancel)
+        }
+        None => analyze(&name, &volume.to_lattice(), cancel),
+    }
+}
+
+#[tracing::instrument(name = "analyze", skip_all, fields(cells = lattice.len()))]
+fn analyze<const D: usize>(
+    name: &str,
+    lattice: &Lattice<D>,
+    cancel: &CancelToken,
+) -> Result<Analysis> {
+    let pores = lattice.complement();
+    let empty_cells = pores.count();
+    let distances = squared_distances(lattice, true, cancel)?;
+    let mut pore_radii = Vec::new();
+    let mut max_pore_radius: f64 = 0.0;
+    for p in pores.iter() {
+        let radius = distances[lattice.index(p)].sqrt();
+        max_pore_radius = max_pore_radius.max(radius);
+        if radius.is_finite() {
+            let bin = radius as usize;
+            if pore_radii.len() <= bin {
+                pore_radii.resize(bin + 1, 0);
+            }
+            pore_radii[bin] += 1;
+        }
+    }
+    cancel.check()?;
+    let components = pores.components(Boundary::Open);
+    let shape = lattice.shape();
+    let percolates = (0..D)
+        .map(|axis| {
+            let mut touches = vec![[false; 2]; components.sizes.len() + 1];
+            for p in pores.iter() {
+                let label = components.labels[pores.index(p)] as usize;
+                touches[label][0] |= p[axis] == 0;
+                touches[label][1] |= p[axis] == shape[axis] - 1;
+            }
+            touches.iter().any(|&[first, last]| first && last)
+        })
+        .collect();
+    Ok(Analysis {
+        job: name.to_string(),
+        shape: shape.to_vec(),
+        porosity: empty_cells as f64 / lattice.len().max(1) as f64,
+        empty_cells,
+        pores: components.sizes.len(),
+        pore_radii,
+        max_pore_radius: if empty_cells == 0 {
+            0.0
+        } else {
+            max_pore_radius
+        },
+        percolates,
+    })
+}
+
+/// Counts the boxes of `lattice` holding a filled cell at sides from one
+/// cell up to the whole lattice, each `factor` times the last.
+#[tracing::instrument(name = "box_counting", skip_all, fields(cells = lattice.len()))]
+pub fn box_counting<const D: usize>(
+    name: &str,
+    lattice: &Lattice<D>,
+    factor: usize,
+    cancel: &CancelToken,
+) -> Result<BoxCounting> {
+    let shape = lattice.shape();
+    let largest = shape.into_iter().max().unwrap_or(1);
+    let mut sides = Vec::new();
+    let mut boxes = Vec::new();
+    let mut side = 1;
+    loop {
+        cancel.check()?;
+        sides.push(side);
+        boxes.push(coarsen(lattice, side).count());
+        if side >= largest {
+            break;
+        }
+        side = (side * factor.max(2)).min(largest);
+    }
+    let points: Vec<(f64, f64)> = sides
+        .iter()
+        .zip(&boxes)
+        .filter(|(_, &count)| count > 0)
+        .map(|(&side, &count)| (-(side as f64).ln(), (count as f64).ln()))
+        .collect();
+    let n = points.len() as f64;
+    let (sx, sy) = points
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (mx, my) = (sx / n.max(1.0), sy / n.max(1.0));
+    let (sxy, sxx) = points.iter().fold((0.0, 0.0), |(sxy, sxx), (x, y)| {
+        (sxy + (x - mx) * (y - my), sxx + (x - mx) * (x - mx))
+    });
+    let dimension = if sxx > 0.0 { sxy / sxx } else { 0.0 };
+    Ok(BoxCounting {
+        job: name.to_string(),
+        sides,
+        boxes,
+        dimension,
+        intercept: my - dimension * mx,
+    })
+}
+
+/// The boxes of side `side` cells holding a filled cell of `lattice`, one
+/// cell per box.
+pub(crate) fn coarsen<const D: usize>(lattice: &Lattice<D>, side: usize) -> Lattice<D> {
+    let mut coarse = Lattice::new(lattice.shape().map(|n| n.div_ceil(side)));
+    for p in lattice.iter() {
+        coarse.set(p.map(|c| c / side), true);
+    }
+    coarse
+}
+
+impl fmt::Display for BoxCounting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "box counting {}:", self.job)?;
+        writeln!(f, "  dimension: {:.4}", self.dimension)?;
+        writeln!(f, "  {:>8} {:>16}", "side", "boxes")?;
+        for (side, boxes) in self.sides.iter().zip(&self.boxes) {
+            writeln!(f, "  {:>8} {:>16}", side, boxes)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shape: Vec<String> = self.shape.iter().map(usize::to_string).collect();
+        writeln!(f, "analysis {}: {} cells", self.job, shape.join("×"))?;
+        writeln!(
+            f,
+            "  porosity: {:.4} ({} empty cells in {} pores)",
+            self.porosity, self.empty_cells, self.pores
+        )?;
+        let axes = ["x", "y", "z", "w"];
+        let percolation: Vec<String> = self
+            .percolates
+            .iter()
+            .zip(axes)
+            .map(|(&yes, axis)| format!("{axis} {}", if yes { "yes" } else { "no" }))
+            .collect();
+        writeln!(f, "  percolates: {}", percolation.join(", "))?;
+        writeln!(
+            f,
+            "  largest pore radius: {:.2} cells",
+            self.max_pore_radius
+        )?;
+        writeln!(f, "  {:>6} {:>16}", "radius", "cells")?;
+        for (radius, &cells) in self.pore_radii.iter().enumerate() {
+            if cells > 0 {
+                writeln!(f, "  {:>6} {:>16}", radius, cells)?;
+            }
+        }
+        Ok(())
+    }
+}
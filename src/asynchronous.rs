@@ -0,0 +1,194 @@
+//! An async facade over generation and slicing, behind the `async`
+//! feature, so servers and batch orchestrators can be written as ordinary
+//! tokio code: the work runs on the runtime's blocking threads, and
+//! callers await its result or stream a lattice's slabs or a 4D fractal's
+//! slices as they are made, without stalling their executor.
+//!
+//! Dropping a future or stream cancels its work at the next check, so
+//! `tokio::time::timeout` and `select!` stop generation as they would any
+//! other future. Every function must be called within a tokio runtime.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::job::{Job, JobReport};
+use crate::lattice::{Lattice3, Lattice4};
+use crate::rule::Rule;
+use crate::sweep::Sweep;
+
+/// Slabs or slices waiting to be taken from a stream before its producer
+/// blocks.
+const BUFFERED: usize = 2;
+
+/// Cancels the token when dropped, unless disarmed first.
+struct CancelOnDrop(Option<CancelToken>);
+
+impl CancelOnDrop {
+    fn new(cancel: &CancelToken) -> Self {
+        CancelOnDrop(Some(cancel.clone()))
+    }
+
+    fn disarm(mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if let Some(cancel) = &self.0 {
+            cancel.cancel();
+        }
+    }
+}
+
+/// Runs `work` on a blocking thread with a token cancelled if the returned
+/// future is dropped first.
+async fn blocking<T: Send + 'static>(
+    work: impl FnOnce(&CancelToken) -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let cancel = CancelToken::new();
+    let guard = CancelOnDrop::new(&cancel);
+    let result = tokio::task::spawn_blocking(move || work(&cancel)).await;
+    guard.disarm();
+    match result {
+        Ok(result) => result,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Runs the job as [`Job::run_cancellable`] does.
+pub async fn generate_async(job: Job) -> Result<JobReport> {
+    blocking(move |cancel| job.run_cancellable(cancel)).await
+}
+
+/// The slice at `w` of the depth-`depth` fractal of the 4D `rule`, from
+/// its digits where the rule allows and otherwise from its generated
+/// hypercube.
+pub async fn slice_async(rule: Rule, depth: u32, w: usize) -> Result<Lattice3> {
+    blocking(move |cancel| slicer(&rule, depth, &[w], cancel)?(w)).await
+}
+
+/// The slices at each of `ws`, in order, as they are made; see
+/// [`slice_async`].
+pub fn slices_async(rule: Rule, depth: u32, ws: Vec<usize>) -> Chunks<(usize, Lattice3)> {
+    Chunks::spawn(move |cancel, send| {
+        let mut slice = slicer(&rule, depth, &ws, cancel)?;
+        for &w in &ws {
+            if !send(slice(w).map(|slice| (w, slice))) {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// A slab of a 3D lattice: its cells from `z` up.
+#[derive(Clone, Debug)]
+pub struct Slab {
+    pub z: usize,
+    pub cells: Lattice3,
+}
+
+/// The depth-`depth` lattice of the 3D `rule` in slabs of `layers` z
+/// layers, bottom up, generated as they are taken so the whole lattice is
+/// never held.
+pub fn chunks_async(rule: Rule, depth: u32, layers: usize) -> Chunks<Slab> {
+    Chunks::spawn(move |cancel, send| {
+        if rule.dims() != 3 {
+            return Err(Error::InvalidJob("slabs are cut from 3D rules only".into()));
+        }
+        let [nx, ny, nz] = std::array::from_fn(|axis| rule.side_along(axis, depth));
+        for z in (0..nz).step_by(layers.max(1)) {
+            let size = [nx, ny, layers.max(1).min(nz - z)];
+            let cells = Lattice3::from_fn(size, cancel, |[x, y, dz]| {
+                rule.is_solid(&[x, y, z + dz], depth)
+            });
+            if !send(cells.map(|cells| Slab { z, cells })) {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Makes the slices of `rule` at `ws`: from a sweep of its digits where
+/// the rule allows, and otherwise from its generated hypercube.
+fn slicer<'a>(
+    rule: &Rule,
+    depth: u32,
+    ws: &[usize],
+    cancel: &'a CancelToken,
+) -> Result<Box<dyn FnMut(usize) -> Result<Lattice3> + 'a>> {
+    if rule.dims() != 4 {
+        return Err(Error::InvalidJob(
+            "slices are cut from 4D rules only".into(),
+        ));
+    }
+    let side = rule.side(depth);
+    if let Some(&w) = ws.iter().find(|&&w| w >= side) {
+        return Err(Error::InvalidJob(format!(
+            "w={w} is outside the lattice (side {side})"
+        )));
+    }
+    Ok(match Sweep::new(rule, depth, ws, cancel)? {
+        Some(mut sweep) => Box::new(move |w| sweep.slice(w, cancel)),
+        None => {
+            let lattice = Lattice4::generate_recursive(rule, depth, cancel)?;
+            Box::new(move |w| Ok(lattice.slice_w(w)))
+        }
+    })
+}
+
+/// A stream of the results of work running on a blocking thread, which
+/// makes at most a couple ahead of the reader; dropping it cancels the
+/// work. The work's error, if any, is the stream's last item.
+pub struct Chunks<T> {
+    receiver: mpsc::Receiver<Result<T>>,
+    _cancel: CancelOnDrop,
+}
+
+impl<T: Send + 'static> Chunks<T> {
+    /// Runs `work`, which hands each item to its sender and stops once
+    /// the sender returns false, the stream being gone.
+    fn spawn(
+        work: impl FnOnce(&CancelToken, &mut dyn FnMut(Result<T>) -> bool) -> Result<()>
+            + Send
+            + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(BUFFERED);
+        let cancel = CancelToken::new();
+        let guard = CancelOnDrop::new(&cancel);
+        tokio::task::spawn_blocking(move || {
+            let mut send = |item: Result<T>| {
+                let failed = item.is_err();
+                sender.blocking_send(item).is_ok() && !failed
+            };
+            if let Err(e) = work(&cancel, &mut send) {
+                // Nobody is left to tell once the stream is dropped.
+                let _ = sender.blocking_send(Err(e));
+            }
+        });
+        Chunks {
+            receiver,
+            _cancel: guard,
+        }
+    }
+
+    /// The next item, or None once the work has finished.
+    pub async fn next(&mut self) -> Option<Result<T>> {
+        self.receiver.recv().await
+    }
+}
+
+impl<T> Stream for Chunks<T> {
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<T>>> {
+        self.receiver.poll_recv(cx)
+    }
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::job::{Job, JobReport};
+
+/// A list of jobs to run in one batch.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub jobs: Vec<Job>,
+}
+
+impl Manifest {
+    /// Reads a JSON manifest. Relative paths are resolved against the
+    /// manifest's directory, see [`Job::rebase`], so a manifest runs the
+    /// same from anywhere.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let mut manifest: Manifest = serde_json::from_str(&text)?;
+        if let Some(dir) = path.parent() {
+            for job in &mut manifest.jobs {
+                job.rebase(dir);
+            }
+        }
+        Ok(manifest)
+    }
+}
+
+/// Runs `jobs` on a pool of at most `workers` threads and returns their
+/// results in manifest order. A failing job does not stop the others, but
+/// once `cancel` fires running jobs stop and the rest report cancellation.
+pub fn run_batch(jobs: &[Job], workers: usize, cancel: &CancelToken) -> Vec<Result<JobReport>> {
+    let workers = workers.clamp(1, jobs.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..jobs.len()).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(job) = jobs.get(index) else { break };
+                let result = job.run_cancellable(cancel);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every job is run"))
+        .collect()
+}
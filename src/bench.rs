@@ -0,0 +1,276 @@
+//! A standard benchmark of the ways a rule fractal can be generated, run on
+//! the user's machine to pick the fastest for their fractals and to send
+//! along with performance reports.
+//!
+//! Every backend builds the same lattice, so their times compare directly.
+//! Generation runs on the CPU only; there is no GPU backend to measure.
+//! Every backend also decides membership on integer digits, with no
+//! floating-point variant, so there is no float against integer axis.
+
+use std::fmt;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::Lattice;
+use crate::rule::Rule;
+
+/// A way of generating a rule fractal's lattice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    // Plain text, as clap prints these in `bench --help`.
+    /// Tests every cell's digits against the rule.
+    BruteForce,
+    /// Copies the levels below into each kept subcell, as generate does.
+    Recursive,
+    /// Tests one fundamental domain of the rule's symmetries and mirrors
+    /// it, as generate --symmetric does.
+    Symmetric,
+    /// Tests every cell on every core, NUMA node by node, as generate
+    /// --numa does. Needs the numa feature.
+    Numa,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 4] = [
+        Backend::BruteForce,
+        Backend::Recursive,
+        Backend::Symmetric,
+        Backend::Numa,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::BruteForce => "brute-force",
+            Backend::Recursive => "recursive",
+            Backend::Symmetric => "symmetric",
+            Backend::Numa => "numa",
+        }
+    }
+
+    /// Generates the lattice and returns its filled cells.
+    fn generate(self, rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<usize> {
+        match rule.dims() {
+            3 => self.generate_dims::<3>(rule, depth, cancel),
+            _ => self.generate_dims::<4>(rule, depth, cancel),
+        }
+    }
+
+    fn generate_dims<const D: usize>(
+        self,
+        rule: &Rule,
+        depth: u32,
+        cancel: &CancelToken,
+    ) -> Result<usize> {
+        let lattice: Lattice<D> = match self {
+            Backend::BruteForce => Lattice::generate_cancellable(rule, depth, cancel)?,
+            Backend::Recursive => Lattice::generate_recursive(rule, depth, cancel)?,
+            Backend::Symmetric => Lattice::generate_symmetric(rule, depth, cancel)?,
+            Backend::Numa => Lattice::generate_numa(rule, depth, cancel)?,
+        };
+        Ok(lattice.count())
+    }
+}
+
+/// The matrix of fractals, depths and backends to time.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bench {
+    /// A built-in rule.
+    #[serde(default = "default_fractal")]
+    pub fractal: String,
+    /// Dimensions to run the rule in, 3 or 4.
+    #[serde(default = "default_dims")]
+    pub dims: Vec<usize>,
+    /// Every depth from 1 is run whose lattice has at most this many
+    /// cells, so the matrix is the same on every machine.
+    #[serde(default = "default_max_cells")]
+    pub max_cells: u64,
+    #[serde(default = "default_backends")]
+    pub backends: Vec<Backend>,
+    /// Runs of each entry, of which the fastest is reported.
+    #[serde(default = "default_repeat")]
+    pub repeat: usize,
+}
+
+fn default_fractal() -> String {
+    "menger".into()
+}
+
+fn default_dims() -> Vec<usize> {
+    vec![3, 4]
+}
+
+fn default_max_cells() -> u64 {
+    50_000_000
+}
+
+/// Every backend this build has.
+fn default_backends() -> Vec<Backend> {
+    Backend::ALL
+        .into_iter()
+        .filter(|&backend| backend != Backend::Numa || cfg!(feature = "numa"))
+        .collect()
+}
+
+fn default_repeat() -> usize {
+    3
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Bench {
+            fractal: default_fractal(),
+            dims: default_dims(),
+            max_cells: default_max_cells(),
+            backends: default_backends(),
+            repeat: default_repeat(),
+        }
+    }
+}
+
+/// The machine a benchmark ran on and its timings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub version: String,
+    pub os: String,
+    pub arch: String,
+    pub threads: usize,
+    /// Whether the slicer was built optimized, without which its timings
+    /// say little.
+    pub release: bool,
+    pub runs: Vec<BenchRun>,
+}
+
+/// The fastest run of one backend on one fractal.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BenchRun {
+    pub fractal: String,
+    pub dims: usize,
+    pub depth: u32,
+    pub backend: Backend,
+    pub cells: u64,
+    pub filled: usize,
+    pub seconds: f64,
+    /// Brute force's time over this one's, when brute force was run.
+    pub speedup: Option<f64>,
+}
+
+impl Bench {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(dims) = self.dims.iter().find(|&&dims| !(3..=4).contains(&dims)) {
+            return Err(Error::InvalidJob(format!(
+                "benchmarks run in 3 or 4 dimensions, not {dims}"
+            )));
+        }
+        if self.backends.is_empty() || self.repeat == 0 {
+            return Err(Error::InvalidJob(
+                "a benchmark needs a backend and at least one run".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Times every backend on every depth of the fractal that fits.
+    #[tracing::instrument(name = "bench", skip_all, fields(fractal = %self.fractal))]
+    pub fn run(&self, cancel: &CancelToken) -> Result<BenchReport> {
+        self.validate()?;
+        let mut runs = Vec::new();
+        for &dims in &self.dims {
+            let rule = Rule::by_name(&self.fractal, dims).ok_or_else(|| {
+                Error::InvalidJob(format!("`{}` is not a built-in rule", self.fractal))
+            })?;
+            let depths = (1..).take_while(|&depth| rule.volume(depth) <= self.max_cells);
+            for depth in depths {
+                let first = runs.len();
+                for &backend in &self.backends {
+                    let mut seconds = f64::INFINITY;
+                    let mut filled = 0;
+                    for _ in 0..self.repeat {
+                        let start = Instant::now();
+                        filled = backend.generate(&rule, depth, cancel)?;
+                        seconds = seconds.min(start.elapsed().as_secs_f64());
+                    }
+                    runs.push(BenchRun {
+                        fractal: self.fractal.clone(),
+                        dims,
+                        depth,
+                        backend,
+                        cells: rule.volume(depth),
+                        filled,
+                        seconds,
+                        speedup: None,
+                    });
+                }
+                let brute = runs[first..]
+                    .iter()
+                    .find(|run| run.backend == Backend::BruteForce)
+                    .map(|run| run.seconds);
+                for run in &mut runs[first..] {
+                    run.speedup = brute.map(|brute| brute / run.seconds.max(1e-9));
+                    tracing::info!(
+                        dims,
+                        depth,
+                        backend = run.backend.name(),
+                        seconds = run.seconds,
+                        "benchmarked"
+                    );
+                }
+            }
+        }
+        Ok(BenchReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            release: !cfg!(debug_assertions),
+            runs,
+        })
+    }
+}
+
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "fractal-slicer {} on {}-{}, {} thread{}{}",
+            self.version,
+            self.arch,
+            self.os,
+            self.threads,
+            if self.threads == 1 { "" } else { "s" },
+            if self.release {
+                ""
+            } else {
+                ", unoptimized build"
+            }
+        )?;
+        writeln!(
+            f,
+            "  fractal   dims  depth  backend            cells       filled    seconds  Mcells/s  speedup"
+        )?;
+        for run in &self.runs {
+            let speedup = match run.speedup {
+                Some(speedup) => format!("{speedup:.2}x"),
+                None => "-".into(),
+            };
+            writeln!(
+                f,
+                "  {:<8}  {:>4}  {:>5}  {:<12}  {:>11}  {:>11}  {:>9.3}  {:>8.1}  {:>7}",
+                run.fractal,
+                run.dims,
+                run.depth,
+                run.backend.name(),
+                run.cells,
+                run.filled,
+                run.seconds,
+                run.cells as f64 / run.seconds.max(1e-9) / 1e6,
+                speedup
+            )?;
+        }
+        Ok(())
+    }
+}
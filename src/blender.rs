@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use crate::rule::{Rule, Split};
+
+/// The add-on's source, with `@…@` placeholders filled by [`addon`].
+const TEMPLATE: &str = include_str!("blender_addon.py");
+
+/// The source of a Blender add-on that runs `program` from a sidebar panel
+/// and imports what it writes, with a material per recursion depth.
+///
+/// The add-on calls `generate --blender --json` and reads the written
+/// files from the summary, so it stays in step with this version's command
+/// line. Its fractal list is the built-in rules; faces are keyed to depth
+/// by the coarsest subdivision grid they lie on, which is exact for rules
+/// split evenly along every axis, and those rules alone get materials.
+pub fn addon(program: &Path) -> String {
+    let fractals: Vec<String> = Rule::NAMES
+        .iter()
+        .filter_map(|&name| Rule::by_name(name, 3))
+        .map(|rule| {
+            let base = match rule.split() {
+                Split::Uniform if rule.is_isotropic() => rule.bases()[0].to_string(),
+                _ => "None".to_string(),
+            };
+            format!("({}, {base})", python_string(rule.name()))
+        })
+        .collect();
+    TEMPLATE
+        .replace("@VERSION@", &env!("CARGO_PKG_VERSION").replace('.', ", "))
+        .replace("@PROGRAM@", &python_string(&program.to_string_lossy()))
+        .replace("@FRACTALS@", &format!("[{}]", fractals.join(", ")))
+}
+
+/// A Python string literal; JSON's escapes are all valid Python.
+fn python_string(text: &str) -> String {
+    serde_json::to_string(text).expect("strings serialize")
+}
@@ -0,0 +1,178 @@
+# Blender add-on for fractal-slicer, written by `fractal-slicer blender-addon`.
+# Install it from Edit > Preferences > Add-ons > Install.
+
+bl_info = {
+    "name": "Fractal Slicer",
+    "author": "fractal-slicer",
+    "version": (@VERSION@),
+    "blender": (3, 6, 0),
+    "location": "View3D > Sidebar > Fractal",
+    "description": "Generates fractal sponges and slices of 4D ones",
+    "category": "Add Mesh",
+}
+
+import colorsys
+import json
+import os
+import subprocess
+import tempfile
+
+import bpy
+
+# The fractal-slicer executable that wrote this add-on.
+PROGRAM = @PROGRAM@
+
+# Built-in rules, with the parts per axis when every face lies on the grid
+# of the subdivision level that exposed it, otherwise None.
+FRACTALS = @FRACTALS@
+
+
+def face_level(plane, base, depth):
+    """The coarsest subdivision level whose grid contains the plane."""
+    level = depth
+    while level > 0 and plane % base == 0:
+        plane //= base
+        level -= 1
+    return level
+
+
+def depth_material(level, depth):
+    """A material per level, shading from blue at the outer faces to red."""
+    name = f"Fractal depth {level}"
+    material = bpy.data.materials.get(name)
+    if material is None:
+        material = bpy.data.materials.new(name)
+        hue = 0.66 * (1 - level / max(depth, 1))
+        colour = (*colorsys.hsv_to_rgb(hue, 0.6, 0.9), 1.0)
+        material.diffuse_color = colour
+        material.use_nodes = True
+        shader = material.node_tree.nodes.get("Principled BSDF")
+        if shader is not None:
+            shader.inputs["Base Color"].default_value = colour
+    return material
+
+
+def assign_depth_materials(obj, base, depth):
+    mesh = obj.data
+    mesh.materials.clear()
+    for level in range(depth + 1):
+        mesh.materials.append(depth_material(level, depth))
+    # Imported meshes sit one unit a cell, so world planes are cell indices.
+    matrix = obj.matrix_world
+    rotation = matrix.to_3x3()
+    for polygon in mesh.polygons:
+        centre = matrix @ polygon.center
+        normal = rotation @ polygon.normal
+        axis = max(range(3), key=lambda a: abs(normal[a]))
+        polygon.material_index = face_level(round(centre[axis]), base, depth)
+
+
+class FractalSlicerSettings(bpy.types.PropertyGroup):
+    program: bpy.props.StringProperty(
+        name="Program", subtype="FILE_PATH", default=PROGRAM
+    )
+    fractal: bpy.props.EnumProperty(
+        name="Fractal",
+        items=[(name, name.capitalize(), "") for name, _ in FRACTALS],
+    )
+    dims: bpy.props.IntProperty(name="Dimensions", default=3, min=3, max=4)
+    depth: bpy.props.IntProperty(name="Depth", default=2, min=0, max=5)
+    slice: bpy.props.IntProperty(
+        name="Slice", description="w index of the 4D slice", default=0, min=0
+    )
+    depth_materials: bpy.props.BoolProperty(
+        name="Depth Materials",
+        description="One material per recursion level",
+        default=True,
+    )
+
+
+class FRACTAL_SLICER_OT_generate(bpy.types.Operator):
+    """Generate the fractal and import it"""
+
+    bl_idname = "fractal_slicer.generate"
+    bl_label = "Generate Fractal"
+    bl_options = {"REGISTER", "UNDO"}
+
+    def execute(self, context):
+        settings = context.scene.fractal_slicer
+        directory = tempfile.mkdtemp(prefix="fractal-slicer-")
+        command = [
+            bpy.path.abspath(settings.program),
+            "--json",
+            "generate",
+            "--blender",
+            "--fractal",
+            settings.fractal,
+            "--dims",
+            str(settings.dims),
+            "--depth",
+            str(settings.depth),
+            "--output",
+            os.path.join(directory, f"{settings.fractal}.glb"),
+        ]
+        if settings.dims == 4:
+            command += ["--slice", str(settings.slice)]
+        try:
+            result = subprocess.run(command, capture_output=True, text=True)
+        except OSError as error:
+            self.report({"ERROR"}, f"cannot run {settings.program}: {error}")
+            return {"CANCELLED"}
+        try:
+            job = json.loads(result.stdout)["jobs"][0]
+        except (ValueError, KeyError, IndexError):
+            self.report({"ERROR"}, result.stderr.strip() or "no summary printed")
+            return {"CANCELLED"}
+        if job["status"] != "ok":
+            self.report({"ERROR"}, job.get("error", job["status"]))
+            return {"CANCELLED"}
+
+        base = dict(FRACTALS)[settings.fractal]
+        for artifact in job["artifacts"]:
+            bpy.ops.import_scene.gltf(filepath=artifact["path"])
+            if settings.depth_materials and base is not None:
+                for obj in context.selected_objects:
+                    if obj.type == "MESH":
+                        assign_depth_materials(obj, base, settings.depth)
+        return {"FINISHED"}
+
+
+class VIEW3D_PT_fractal_slicer(bpy.types.Panel):
+    bl_label = "Fractal Slicer"
+    bl_space_type = "VIEW_3D"
+    bl_region_type = "UI"
+    bl_category = "Fractal"
+
+    def draw(self, context):
+        settings = context.scene.fractal_slicer
+        layout = self.layout
+        layout.prop(settings, "program")
+        layout.prop(settings, "fractal")
+        layout.prop(settings, "dims")
+        layout.prop(settings, "depth")
+        row = layout.row()
+        row.enabled = settings.dims == 4
+        row.prop(settings, "slice")
+        layout.prop(settings, "depth_materials")
+        layout.operator(FRACTAL_SLICER_OT_generate.bl_idname)
+
+
+CLASSES = (FractalSlicerSettings, FRACTAL_SLICER_OT_generate, VIEW3D_PT_fractal_slicer)
+
+
+def register():
+    for cls in CLASSES:
+        bpy.utils.register_class(cls)
+    bpy.types.Scene.fractal_slicer = bpy.props.PointerProperty(
+        type=FractalSlicerSettings
+    )
+
+
+def unregister():
+    del bpy.types.Scene.fractal_slicer
+    for cls in reversed(CLASSES):
+        bpy.utils.unregister_class(cls)
+
+
+if __name__ == "__main__":
+    register()
@@ -0,0 +1,438 @@
+use crate::mesh::{cross, dot, normalize, sub, Mesh};
+
+/// A bounding volume hierarchy over a mesh's triangles, answering ray
+/// queries in logarithmic time for renderers that embed the geometry, and
+/// nearest-point queries for measuring distances to it.
+///
+/// Built with the surface area heuristic over binned triangle centroids.
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    /// Triangle corners, reordered so every leaf holds a contiguous run.
+    triangles: Vec<[[f64; 3]; 3]>,
+    /// Each reordered triangle's index in [`Mesh::triangles`].
+    indices: Vec<usize>,
+}
+
+/// A half-line from `origin` along `direction`, up to `max` times the
+/// direction's length.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: [f64; 3],
+    pub direction: [f64; 3],
+    pub max: f64,
+}
+
+/// Where a ray meets a triangle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    /// Distance along the ray, in multiples of its direction.
+    pub t: f64,
+    /// The triangle's index in [`Mesh::triangles`].
+    pub triangle: usize,
+    /// Weights of the triangle's second and third corners at the hit; the
+    /// first corner's is one minus their sum.
+    pub barycentric: [f64; 2],
+    /// The triangle's unit normal, following its winding.
+    pub normal: [f64; 3],
+}
+
+/// The point of a mesh nearest a query point.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Closest {
+    pub point: [f64; 3],
+    pub distance: f64,
+    /// The triangle's index in [`Mesh::triangles`].
+    pub triangle: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    bounds: Bounds,
+    /// For leaves the first triangle, otherwise the first child; the
+    /// second child follows it.
+    first: usize,
+    /// Triangles in a leaf; 0 for inner nodes.
+    count: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bounds {
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+/// Largest number of triangles left in one leaf.
+const LEAF_SIZE: usize = 4;
+/// Centroid bins the surface area heuristic chooses splits between.
+const BINS: usize = 12;
+/// Hits closer than this are taken to be the surface the ray leaves.
+const EPSILON: f64 = 1e-9;
+
+impl Bvh {
+    pub fn build(mesh: &Mesh) -> Self {
+        let corners: Vec<[[f64; 3]; 3]> = mesh
+            .triangles()
+            .iter()
+            .map(|t| t.map(|v| mesh.vertices[v as usize]))
+            .collect();
+        let centroids: Vec<[f64; 3]> = corners
+            .iter()
+            .map(|c| std::array::from_fn(|a| (c[0][a] + c[1][a] + c[2][a]) / 3.0))
+            .collect();
+        let mut order: Vec<usize> = (0..corners.len()).collect();
+        let mut nodes = vec![Node {
+            bounds: Bounds::of(&corners, &order),
+            first: 0,
+            count: order.len(),
+        }];
+        // Split nodes until no split pays; siblings are always adjacent.
+        let mut pending = vec![0];
+        while let Some(index) = pending.pop() {
+            let Node { first, count, .. } = nodes[index];
+            let Some(mid) = split(&corners, &centroids, &mut order[first..first + count]) else {
+                continue;
+            };
+            let children = nodes.len();
+            for (start, len) in [(first, mid), (first + mid, count - mid)] {
+                nodes.push(Node {
+                    bounds: Bounds::of(&corners, &order[start..start + len]),
+                    first: start,
+                    count: len,
+                });
+            }
+            nodes[index].first = children;
+            nodes[index].count = 0;
+            pending.extend([children, children + 1]);
+        }
+        Bvh {
+            nodes,
+            triangles: order.iter().map(|&i| corners[i]).collect(),
+            indices: order,
+        }
+    }
+
+    /// Number of triangles.
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// The lowest and highest corners of the mesh's bounding box.
+    pub fn bounds(&self) -> Option<([f64; 3], [f64; 3])> {
+        (!self.is_empty()).then(|| (self.nodes[0].bounds.min, self.nodes[0].bounds.max))
+    }
+
+    /// The nearest triangle along the ray, if any lies within its length.
+    pub fn intersect(&self, ray: &Ray) -> Option<RayHit> {
+        let mut nearest: Option<RayHit> = None;
+        self.traverse(ray, |bvh, i, max| {
+            let hit = bvh.hit_triangle(i, ray, max)?;
+            nearest = Some(hit);
+            Some(hit.t)
+        });
+        nearest
+    }
+
+    /// Whether any triangle lies along the ray within its length, as for
+    /// shadow rays.
+    pub fn occluded(&self, ray: &Ray) -> bool {
+        let mut found = false;
+        self.traverse(ray, |bvh, i, max| {
+            found = bvh.hit_triangle(i, ray, max).is_some();
+            // Stop at the first hit by shortening the ray to nothing.
+            found.then_some(0.0)
+        });
+        found
+    }
+
+    /// The point of the mesh nearest `p`, visiting boxes nearest first and
+    /// skipping those farther than the best point found.
+    pub fn closest(&self, p: [f64; 3]) -> Option<Closest> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut best: Option<(f64, [f64; 3], usize)> = None;
+        let mut stack = vec![(self.nodes[0].bounds.distance_squared(p), 0)];
+        while let Some((near, index)) = stack.pop() {
+            if best.is_some_and(|(d, ..)| near >= d) {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                for i in node.first..node.first + node.count {
+                    let q = closest_on_triangle(p, self.triangles[i]);
+                    let d = dot(sub(p, q), sub(p, q));
+                    if best.is_none_or(|(best, ..)| d < best) {
+                        best = Some((d, q, self.indices[i]));
+                    }
+                }
+                continue;
+            }
+            let (a, b) = (node.first, node.first + 1);
+            let (da, db) = (
+                self.nodes[a].bounds.distance_squared(p),
+                self.nodes[b].bounds.distance_squared(p),
+            );
+            if da < db {
+                stack.extend([(db, b), (da, a)]);
+            } else {
+                stack.extend([(da, a), (db, b)]);
+            }
+        }
+        best.map(|(d, point, triangle)| Closest {
+            point,
+            distance: d.sqrt(),
+            triangle,
+        })
+    }
+
+    /// Visits the triangles whose boxes the ray meets, nearer children
+    /// first. `visit` returns a new, shorter length when it finds a hit.
+    fn traverse(&self, ray: &Ray, mut visit: impl FnMut(&Self, usize, f64) -> Option<f64>) {
+        if self.is_empty() {
+            return;
+        }
+        let inverse = ray.direction.map(|d| 1.0 / d);
+        let mut max = ray.max;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.bounds.entry(ray.origin, inverse, max).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for i in node.first..node.first + node.count {
+                    if let Some(t) = visit(self, i, max) {
+                        max = t;
+                        if max <= 0.0 {
+                            return;
+                        }
+                    }
+                }
+                continue;
+            }
+            let (a, b) = (node.first, node.first + 1);
+            let near = |i: usize| self.nodes[i].bounds.entry(ray.origin, inverse, max);
+            match (near(a), near(b)) {
+                (Some(ta), Some(tb)) if tb < ta => stack.extend([a, b]),
+                (Some(_), Some(_)) => stack.extend([b, a]),
+                (Some(_), None) => stack.push(a),
+                (None, Some(_)) => stack.push(b),
+                (None, None) => {}
+            }
+        }
+    }
+
+    /// Möller–Trumbore intersection with the reordered triangle `i`.
+    fn hit_triangle(&self, i: usize, ray: &Ray, max: f64) -> Option<RayHit> {
+        let [p0, p1, p2] = self.triangles[i];
+        let (e1, e2) = (sub(p1, p0), sub(p2, p0));
+        let p = cross(ray.direction, e2);
+        let determinant = dot(e1, p);
+        if determinant.abs() < f64::MIN_POSITIVE {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let s = sub(ray.origin, p0);
+        let u = dot(s, p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = cross(s, e1);
+        let v = dot(ray.direction, q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = dot(e2, q) * inverse;
+        if t <= EPSILON || t >= max {
+            return None;
+        }
+        Some(RayHit {
+            t,
+            triangle: self.indices[i],
+            barycentric: [u, v],
+            normal: normalize(cross(e1, e2)),
+        })
+    }
+}
+
+impl Bounds {
+    fn empty() -> Self {
+        Bounds {
+            min: [f64::INFINITY; 3],
+            max: [f64::NEG_INFINITY; 3],
+        }
+    }
+
+    fn of(corners: &[[[f64; 3]; 3]], triangles: &[usize]) -> Self {
+        let mut bounds = Bounds::empty();
+        for &t in triangles {
+            for p in corners[t] {
+                bounds.grow(p);
+            }
+        }
+        bounds
+    }
+
+    fn grow(&mut self, p: [f64; 3]) {
+        self.min = std::array::from_fn(|a| self.min[a].min(p[a]));
+        self.max = std::array::from_fn(|a| self.max[a].max(p[a]));
+    }
+
+    fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: std::array::from_fn(|a| self.min[a].min(other.min[a])),
+            max: std::array::from_fn(|a| self.max[a].max(other.max[a])),
+        }
+    }
+
+    fn area(&self) -> f64 {
+        let d: [f64; 3] = std::array::from_fn(|a| (self.max[a] - self.min[a]).max(0.0));
+        2.0 * (d[0] * d[1] + d[1] * d[2] + d[2] * d[0])
+    }
+
+    /// Squared distance from `p` to the nearest point of the box, 0 inside.
+    fn distance_squared(&self, p: [f64; 3]) -> f64 {
+        (0..3)
+            .map(|a| {
+                (self.min[a] - p[a])
+                    .max(p[a] - self.max[a])
+                    .max(0.0)
+                    .powi(2)
+            })
+            .sum()
+    }
+
+    /// Where a ray with the given inverse direction enters the box, if it
+    /// does before `max`.
+    fn entry(&self, origin: [f64; 3], inverse: [f64; 3], max: f64) -> Option<f64> {
+        let (mut near, mut far) = (0.0_f64, max);
+        for a in 0..3 {
+            let t0 = (self.min[a] - origin[a]) * inverse[a];
+            let t1 = (self.max[a] - origin[a]) * inverse[a];
+            // NaN from a zero direction on the slab's plane keeps the
+            // bounds unchanged.
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+/// Partitions `triangles` at the cheapest binned split along the axis
+/// their centroids spread furthest, returning the size of the first part,
+/// or `None` when splitting costs more than a leaf.
+fn split(
+    corners: &[[[f64; 3]; 3]],
+    centroids: &[[f64; 3]],
+    triangles: &mut [usize],
+) -> Option<usize> {
+    if triangles.len() <= LEAF_SIZE {
+        return None;
+    }
+    let mut spread = Bounds::empty();
+    for &t in triangles.iter() {
+        spread.grow(centroids[t]);
+    }
+    let axis = (0..3)
+        .max_by(|&a, &b| {
+            let extent = |axis: usize| spread.max[axis] - spread.min[axis];
+            extent(a).total_cmp(&extent(b))
+        })
+        .expect("three axes");
+    let (low, extent) = (spread.min[axis], spread.max[axis] - spread.min[axis]);
+    if extent <= 0.0 {
+        return None;
+    }
+    let bin =
+        |t: usize| (((centroids[t][axis] - low) / extent * BINS as f64) as usize).min(BINS - 1);
+    let mut bins = [(Bounds::empty(), 0usize); BINS];
+    for &t in triangles.iter() {
+        let (bounds, count) = &mut bins[bin(t)];
+        for p in corners[t] {
+            bounds.grow(p);
+        }
+        *count += 1;
+    }
+    // Cost of splitting after each bin: area times count on either side.
+    let mut best = (f64::INFINITY, 0);
+    for cut in 1..BINS {
+        let side = |range: &[(Bounds, usize)]| {
+            range
+                .iter()
+                .fold((Bounds::empty(), 0), |(b, n), (bounds, count)| {
+                    (b.union(bounds), n + count)
+                })
+        };
+        let (left, nl) = side(&bins[..cut]);
+        let (right, nr) = side(&bins[cut..]);
+        if nl == 0 || nr == 0 {
+            continue;
+        }
+        let cost = left.area() * nl as f64 + right.area() * nr as f64;
+        if cost < best.0 {
+            best = (cost, cut);
+        }
+    }
+    let leaf_cost = Bounds::of(corners, triangles).area() * triangles.len() as f64;
+    if best.0 >= leaf_cost && triangles.len() <= 4 * LEAF_SIZE {
+        return None;
+    }
+    let mut mid = 0;
+    for i in 0..triangles.len() {
+        if bin(triangles[i]) < best.1 {
+            triangles.swap(i, mid);
+            mid += 1;
+        }
+    }
+    (mid > 0 && mid < triangles.len()).then_some(mid)
+}
+
+/// The point of triangle `[a, b, c]` nearest `p`, by the Voronoi region of
+/// the triangle `p` lies in (Ericson, Real-Time Collision Detection 5.1.5).
+fn closest_on_triangle(p: [f64; 3], [a, b, c]: [[f64; 3]; 3]) -> [f64; 3] {
+    let along = |from: [f64; 3], edge: [f64; 3], t: f64| -> [f64; 3] {
+        std::array::from_fn(|i| from[i] + edge[i] * t)
+    };
+    let (ab, ac, ap) = (sub(b, a), sub(c, a), sub(p, a));
+    let (d1, d2) = (dot(ab, ap), dot(ac, ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+    let bp = sub(p, b);
+    let (d3, d4) = (dot(ab, bp), dot(ac, bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return along(a, ab, d1 / (d1 - d3));
+    }
+    let cp = sub(p, c);
+    let (d5, d6) = (dot(ab, cp), dot(ac, cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return along(a, ac, d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return along(b, sub(c, b), (d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denominator = va + vb + vc;
+    if denominator.abs() < f64::MIN_POSITIVE {
+        // Degenerate: the nearest of its corners.
+        return [a, b, c]
+            .into_iter()
+            .min_by(|x, y| dot(sub(p, *x), sub(p, *x)).total_cmp(&dot(sub(p, *y), sub(p, *y))))
+            .unwrap_or(a);
+    }
+    let (v, w) = (vb / denominator, vc / denominator);
+    std::array::from_fn(|i| a[i] + ab[i] * v + ac[i] * w)
+}
@@ -0,0 +1,130 @@
+//! Resuming runs that stopped early.
+//!
+//! Every output is written atomically, so a cancelled or failed job leaves
+//! only finished files behind. A [`Checkpoint`] records which of them make
+//! up each completed unit of work, such as a w-slice, a 3D output or a
+//! Zarr chunk, and is saved beside the job's first output when the run
+//! stops. Rerunning the same job reuses every unit whose files are still
+//! as recorded, and deletes the checkpoint once the run finishes.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::export::{write_file_atomically, Artifact, HashingWriter};
+use crate::store;
+
+/// The units of a run completed so far. Clones share the record; the
+/// default records nothing and reuses nothing.
+#[derive(Clone, Debug, Default)]
+pub struct Checkpoint {
+    state: Option<Arc<Mutex<State>>>,
+}
+
+#[derive(Debug)]
+struct State {
+    path: PathBuf,
+    manifest: Manifest,
+}
+
+/// The file a checkpoint is saved as.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// The job checkpointed, which a rerun must match exactly.
+    job: serde_json::Value,
+    /// The files of each completed unit, by the unit's name.
+    units: BTreeMap<String, Vec<Artifact>>,
+}
+
+impl Checkpoint {
+    /// The checkpoint of `job` saved at `path`, holding the units an
+    /// earlier run of the same job completed. A checkpoint of another job,
+    /// or one that cannot be read, is started afresh.
+    pub fn open(path: PathBuf, job: &impl Serialize) -> Result<Self> {
+        let job = serde_json::to_value(job)?;
+        let units = std::fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Manifest>(&bytes).ok())
+            .filter(|manifest| manifest.job == job)
+            .map(|manifest| manifest.units)
+            .unwrap_or_default();
+        if !units.is_empty() {
+            tracing::info!(path = %path.display(), units = units.len(), "resuming from checkpoint");
+        }
+        Ok(Checkpoint {
+            state: Some(Arc::new(Mutex::new(State {
+                path,
+                manifest: Manifest { job, units },
+            }))),
+        })
+    }
+
+    /// Where the checkpoint of a job writing `output` is saved:
+    /// `.<name>.checkpoint.json` beside it. Objects in a bucket get none.
+    pub fn path_for(output: &Path) -> Option<PathBuf> {
+        if store::object_url(output).is_some() {
+            return None;
+        }
+        let name = output.file_name()?.to_string_lossy();
+        Some(output.with_file_name(format!(".{name}.checkpoint.json")))
+    }
+
+    /// The files of `unit`, from an earlier run if it completed the unit
+    /// and they are unchanged, or else from `run`, recording them.
+    pub fn unit(
+        &self,
+        unit: &str,
+        run: impl FnOnce() -> Result<Vec<Artifact>>,
+    ) -> Result<Vec<Artifact>> {
+        let Some(state) = &self.state else {
+            return run();
+        };
+        let done = state.lock().unwrap().manifest.units.get(unit).cloned();
+        if let Some(artifacts) = done.filter(|artifacts| artifacts.iter().all(unchanged)) {
+            tracing::debug!(unit, "reused from checkpoint");
+            return Ok(artifacts);
+        }
+        let artifacts = run()?;
+        state
+            .lock()
+            .unwrap()
+            .manifest
+            .units
+            .insert(unit.to_string(), artifacts.clone());
+        Ok(artifacts)
+    }
+
+    /// Saves the units completed so far, so a rerun resumes after them.
+    pub fn save(&self) -> Result<()> {
+        let Some(state) = &self.state else {
+            return Ok(());
+        };
+        let state = state.lock().unwrap();
+        let json = serde_json::to_vec_pretty(&state.manifest)?;
+        write_file_atomically(&state.path, |out| Ok(io::Write::write_all(out, &json)?))?;
+        Ok(())
+    }
+
+    /// Deletes the checkpoint of a finished run.
+    pub fn remove(&self) {
+        if let Some(state) = &self.state {
+            let _ = std::fs::remove_file(&state.lock().unwrap().path);
+        }
+    }
+}
+
+/// Whether the file `artifact` describes still holds what was written.
+fn unchanged(artifact: &Artifact) -> bool {
+    let Ok(mut file) = File::open(&artifact.path) else {
+        return false;
+    };
+    let mut hashed = HashingWriter::new(io::sink());
+    io::copy(&mut file, &mut hashed).is_ok()
+        && hashed.bytes() == artifact.bytes
+        && hashed.hex_digest() == artifact.sha256
+}
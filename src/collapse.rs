@@ -0,0 +1,339 @@
+//! Physics drops: every filled cell of a lattice as a rigid cube falling
+//! under gravity onto a ground plane, simulated with rapier, with each
+//! frame's positions and orientations written as a USD point cache.
+//!
+//! The cache is a `.usda` `PointInstancer` instancing one cube per cell,
+//! its `positions` and `orientations` sampled at every frame, so Blender
+//! and other USD importers play the collapse back without simulating it
+//! themselves. Lengths are in metres, z up.
+
+use std::io::Write;
+use std::path::Path;
+
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, Artifact};
+use crate::job::Job;
+use crate::lattice::Lattice3;
+use crate::random::Rng;
+
+/// How the cubes are dropped and the collapse recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Collapse {
+    /// Frames recorded, the first before anything moves.
+    #[serde(default = "default_frames")]
+    pub frames: u32,
+    #[serde(default = "default_fps")]
+    pub fps: f64,
+    /// Simulation steps between frames.
+    #[serde(default = "default_substeps")]
+    pub substeps: u32,
+    /// Side of a cell, in metres.
+    #[serde(default = "default_cell_size")]
+    pub cell_size: f64,
+    /// How far above the ground the lattice's underside starts, in
+    /// metres.
+    #[serde(default)]
+    pub height: f64,
+    /// Each cube starts with a random velocity of up to this many metres
+    /// a second, so a lattice dropped whole breaks apart.
+    #[serde(default)]
+    pub scatter: f64,
+    #[serde(default = "default_restitution")]
+    pub restitution: f64,
+    #[serde(default = "default_friction")]
+    pub friction: f64,
+    /// Seeds the starting velocities.
+    #[serde(default)]
+    pub seed: u64,
+}
+
+fn default_frames() -> u32 {
+    120
+}
+
+fn default_fps() -> f64 {
+    24.0
+}
+
+fn default_substeps() -> u32 {
+    4
+}
+
+fn default_cell_size() -> f64 {
+    0.1
+}
+
+fn default_restitution() -> f64 {
+    0.2
+}
+
+fn default_friction() -> f64 {
+    0.6
+}
+
+impl Default for Collapse {
+    fn default() -> Self {
+        Collapse {
+            frames: default_frames(),
+            fps: default_fps(),
+            substeps: default_substeps(),
+            cell_size: default_cell_size(),
+            height: 0.0,
+            scatter: 0.0,
+            restitution: default_restitution(),
+            friction: default_friction(),
+            seed: 0,
+        }
+    }
+}
+
+/// The cubes' placements, frame by frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trajectories {
+    /// Side of every cube, in metres.
+    pub cell_size: f64,
+    pub fps: f64,
+    /// Per frame, the centre of every cube, in the order of the
+    /// lattice's filled cells.
+    pub positions: Vec<Vec<[f32; 3]>>,
+    /// Per frame, the rotation of every cube as a unit quaternion, real
+    /// part first.
+    pub orientations: Vec<Vec<[f32; 4]>>,
+}
+
+impl Collapse {
+    pub fn validate(&self) -> Result<()> {
+        if self.frames == 0 || self.substeps == 0 {
+            return Err(Error::InvalidJob(
+                "a collapse needs frames and simulation steps".into(),
+            ));
+        }
+        let positive = |value: f64| value > 0.0 && value.is_finite();
+        if !positive(self.fps) || !positive(self.cell_size) {
+            return Err(Error::InvalidJob(
+                "frame rate and cell size must be positive".into(),
+            ));
+        }
+        let non_negative = |value: f64| value >= 0.0 && value.is_finite();
+        if ![self.height, self.scatter, self.restitution, self.friction]
+            .into_iter()
+            .all(non_negative)
+        {
+            return Err(Error::InvalidJob(
+                "height, scatter, restitution and friction must not be negative".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Drops the filled cells of `lattice` onto the ground, recording
+    /// every frame.
+    #[tracing::instrument(name = "collapse", skip_all, fields(cells = lattice.count()))]
+    pub fn simulate(&self, lattice: &Lattice3, cancel: &CancelToken) -> Result<Trajectories> {
+        self.validate()?;
+        let size = self.cell_size as Real;
+        let half = size / 2.0;
+        let mut bodies = RigidBodySet::new();
+        let mut colliders = ColliderSet::new();
+        colliders.insert(
+            ColliderBuilder::halfspace(Vector::z_axis())
+                .friction(self.friction as Real)
+                .build(),
+        );
+        let mut rng = Rng::new(self.seed, 0);
+        let mut handles = Vec::with_capacity(lattice.count());
+        for cell in lattice.iter() {
+            let centre = cell.map(|c| c as Real * size + half);
+            // Uniform in the ball, by rejection.
+            let velocity = loop {
+                let v: [f64; 3] = std::array::from_fn(|_| rng.unit() * 2.0 - 1.0);
+                if v.iter().map(|c| c * c).sum::<f64>() <= 1.0 {
+                    break v.map(|c| (c * self.scatter) as Real);
+                }
+            };
+            let body = RigidBodyBuilder::dynamic()
+                .translation(vector![
+                    centre[0],
+                    centre[1],
+                    centre[2] + self.height as Real
+                ])
+                .linvel(vector![velocity[0], velocity[1], velocity[2]])
+                .build();
+            let handle = bodies.insert(body);
+            let collider = ColliderBuilder::cuboid(half, half, half)
+                .restitution(self.restitution as Real)
+                .friction(self.friction as Real)
+                .build();
+            colliders.insert_with_parent(collider, handle, &mut bodies);
+            handles.push(handle);
+        }
+        let parameters = IntegrationParameters {
+            dt: (1.0 / (self.fps * self.substeps as f64)) as Real,
+            ..IntegrationParameters::default()
+        };
+        let gravity = vector![0.0, 0.0, -9.81];
+        let mut pipeline = PhysicsPipeline::new();
+        let mut islands = IslandManager::new();
+        let mut broad_phase = DefaultBroadPhase::new();
+        let mut narrow_phase = NarrowPhase::new();
+        let mut impulse_joints = ImpulseJointSet::new();
+        let mut multibody_joints = MultibodyJointSet::new();
+        let mut ccd_solver = CCDSolver::new();
+        let mut trajectories = Trajectories {
+            cell_size: self.cell_size,
+            fps: self.fps,
+            positions: Vec::new(),
+            orientations: Vec::new(),
+        };
+        for frame in 0..self.frames {
+            cancel.check()?;
+            if frame > 0 {
+                for _ in 0..self.substeps {
+                    pipeline.step(
+                        &gravity,
+                        &parameters,
+                        &mut islands,
+                        &mut broad_phase,
+                        &mut narrow_phase,
+                        &mut bodies,
+                        &mut colliders,
+                        &mut impulse_joints,
+                        &mut multibody_joints,
+                        &mut ccd_solver,
+                        None,
+                        &(),
+                        &(),
+                    );
+                }
+            }
+            let placed = handles.iter().map(|&handle| &bodies[handle]);
+            trajectories.positions.push(
+                placed
+                    .clone()
+                    .map(|body| {
+                        let t = body.translation();
+                        [t.x, t.y, t.z]
+                    })
+                    .collect(),
+            );
+            trajectories.orientations.push(
+                placed
+                    .map(|body| {
+                        let q = body.rotation();
+                        [q.w, q.i, q.j, q.k]
+                    })
+                    .collect(),
+            );
+        }
+        Ok(trajectories)
+    }
+}
+
+impl Trajectories {
+    /// Writes the trajectories as a USD point cache: a `.usda` layer with
+    /// a `PointInstancer` of cubes whose positions and orientations are
+    /// sampled at every frame.
+    pub fn write_usda(&self, out: &mut impl Write) -> Result<()> {
+        let frames = self.positions.len();
+        let cubes = self.positions.first().map_or(0, Vec::len);
+        writeln!(out, "#usda 1.0")?;
+        writeln!(out, "(")?;
+        writeln!(out, "    defaultPrim = \"Collapse\"")?;
+        writeln!(out, "    startTimeCode = 0")?;
+        writeln!(out, "    endTimeCode = {}", frames.saturating_sub(1))?;
+        writeln!(out, "    framesPerSecond = {}", self.fps)?;
+        writeln!(out, "    timeCodesPerSecond = {}", self.fps)?;
+        writeln!(out, "    metersPerUnit = 1")?;
+        writeln!(out, "    upAxis = \"Z\"")?;
+        writeln!(out, ")")?;
+        writeln!(out)?;
+        writeln!(out, "def PointInstancer \"Collapse\"")?;
+        writeln!(out, "{{")?;
+        writeln!(out, "    rel prototypes = [</Collapse/Prototypes/Cube>]")?;
+        write!(out, "    int[] protoIndices = [")?;
+        for i in 0..cubes {
+            write!(out, "{}0", if i > 0 { ", " } else { "" })?;
+        }
+        writeln!(out, "]")?;
+        writeln!(out, "    point3f[] positions.timeSamples = {{")?;
+        for (frame, positions) in self.positions.iter().enumerate() {
+            write!(out, "        {frame}: [")?;
+            for (i, [x, y, z]) in positions.iter().enumerate() {
+                let comma = if i > 0 { ", " } else { "" };
+                write!(out, "{comma}({x}, {y}, {z})")?;
+            }
+            writeln!(out, "],")?;
+        }
+        writeln!(out, "    }}")?;
+        writeln!(out, "    quath[] orientations.timeSamples = {{")?;
+        for (frame, orientations) in self.orientations.iter().enumerate() {
+            write!(out, "        {frame}: [")?;
+            for (i, [w, x, y, z]) in orientations.iter().enumerate() {
+                let comma = if i > 0 { ", " } else { "" };
+                write!(out, "{comma}({w}, {x}, {y}, {z})")?;
+            }
+            writeln!(out, "],")?;
+        }
+        writeln!(out, "    }}")?;
+        writeln!(out)?;
+        writeln!(out, "    def Scope \"Prototypes\"")?;
+        writeln!(out, "    {{")?;
+        writeln!(out, "        def Cube \"Cube\"")?;
+        writeln!(out, "        {{")?;
+        writeln!(out, "            double size = {}", self.cell_size)?;
+        writeln!(out, "        }}")?;
+        writeln!(out, "    }}")?;
+        writeln!(out, "}}")?;
+        Ok(())
+    }
+}
+
+impl Job {
+    /// Drops the cells of the job's lattice as `collapse` describes and
+    /// writes the point cache to the `.usda` file at `path`. 4D fractals
+    /// are dropped at their w slice `w`, the middle one when none is
+    /// given.
+    pub fn collapse(
+        &self,
+        collapse: &Collapse,
+        w: Option<usize>,
+        path: &Path,
+        cancel: &CancelToken,
+    ) -> Result<Artifact> {
+        self.validate()?;
+        collapse.validate()?;
+        if !path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("usda"))
+        {
+            return Err(Error::UnknownFormat(path.to_path_buf()));
+        }
+        if self.surface()?.is_some() {
+            return Err(Error::InvalidJob(format!(
+                "surface fractal `{}` has no cells to drop",
+                self.fractal
+            )));
+        }
+        let lattice = match self.dims {
+            3 => self.generate::<3>(cancel)?,
+            _ => {
+                let lattice = self.generate::<4>(cancel)?;
+                let side = lattice.shape()[3];
+                let w = w.unwrap_or(side / 2);
+                if w >= side {
+                    return Err(Error::InvalidJob(format!(
+                        "slice w={w} is outside the lattice (side {side})"
+                    )));
+                }
+                lattice.slice_w(w)
+            }
+        };
+        let trajectories = collapse.simulate(&lattice, cancel)?;
+        write_file_atomically(path, |out| trajectories.write_usda(out))
+    }
+}
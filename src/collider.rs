@@ -0,0 +1,75 @@
+use rapier3d::prelude::*;
+
+use crate::lattice::{Boundary, Lattice3};
+use crate::mesh::{build_indexed_mesh, greedy_blocks, FaceKind};
+
+/// How the lattice is turned into collision geometry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColliderShape {
+    /// A compound of cuboids covering the filled cells. Solid, cheap to
+    /// query, and robust for fast-moving bodies.
+    Cuboids,
+    /// A triangle mesh of the culled surface. Exact but hollow.
+    TriMesh,
+}
+
+/// Builds a single collider for the lattice, with each cell `cell_size` wide
+/// and the lattice's minimum corner at the origin. An empty lattice has no
+/// shape to collide with and fails with
+/// [`TriMeshBuilderError::EmptyIndices`], whichever `shape` is asked for.
+pub fn build_collider(
+    lattice: &Lattice3,
+    shape: ColliderShape,
+    cell_size: Real,
+) -> Result<Collider, TriMeshBuilderError> {
+    // rapier's compounds panic when given no shapes.
+    if lattice.count() == 0 {
+        return Err(TriMeshBuilderError::EmptyIndices);
+    }
+    let builder = match shape {
+        ColliderShape::Cuboids => ColliderBuilder::compound(cuboids(lattice, cell_size)),
+        ColliderShape::TriMesh => {
+            let (vertices, indices) = surface_trimesh(lattice, cell_size);
+            ColliderBuilder::trimesh(vertices, indices)?
+        }
+    };
+    Ok(builder.build())
+}
+
+/// Builds a collider set holding the lattice's collider, ready to hand to a
+/// rapier physics pipeline. The set of an empty lattice is empty.
+pub fn build_collider_set(
+    lattice: &Lattice3,
+    shape: ColliderShape,
+    cell_size: Real,
+) -> Result<ColliderSet, TriMeshBuilderError> {
+    let mut set = ColliderSet::new();
+    if lattice.count() > 0 {
+        set.insert(build_collider(lattice, shape, cell_size)?);
+    }
+    Ok(set)
+}
+
+fn cuboids(lattice: &Lattice3, cell_size: Real) -> Vec<(Isometry<Real>, SharedShape)> {
+    greedy_blocks(lattice)
+        .into_iter()
+        .map(|block| {
+            let half = |axis: usize| (block.max[axis] - block.min[axis]) as Real * cell_size / 2.0;
+            let centre = |axis: usize| block.min[axis] as Real * cell_size + half(axis);
+            (
+                Isometry::translation(centre(0), centre(1), centre(2)),
+                SharedShape::cuboid(half(0), half(1), half(2)),
+            )
+        })
+        .collect()
+}
+
+fn surface_trimesh(lattice: &Lattice3, cell_size: Real) -> (Vec<Point<Real>>, Vec<[u32; 3]>) {
+    let mesh = build_indexed_mesh(lattice, FaceKind::Triangles, Boundary::Open);
+    let vertices = mesh
+        .vertices
+        .iter()
+        .map(|v| Point::from(v.map(|c| c as Real * cell_size)))
+        .collect();
+    (vertices, mesh.triangles())
+}
@@ -0,0 +1,301 @@
+//! Colour maps: palettes such as viridis and magma, or gradients of your
+//! own, keyed to an attribute of the cell behind each face, namely the
+//! level it was cut at, the slice's place along w, how enclosed it is, or
+//! the connected piece it belongs to.
+//!
+//! The renderer and `.glb` export colour faces through the same
+//! [`Coloring`], so a render and the model it was made from match.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lattice::{Boundary, Lattice3};
+use crate::mesh::{cross, sub, Mesh, Polygons, Scalar};
+use crate::render::Levels;
+
+/// Evenly spaced sRGB stops of matplotlib's viridis.
+const VIRIDIS: [u32; 9] = [
+    0x440154, 0x472d7b, 0x3b528b, 0x2c728e, 0x21918c, 0x28ae80, 0x5ec962, 0xaddc30, 0xfde725,
+];
+
+/// Evenly spaced sRGB stops of matplotlib's magma.
+const MAGMA: [u32; 9] = [
+    0x000004, 0x1c1044, 0x4f127b, 0x812581, 0xb5367a, 0xe55064, 0xfb8761, 0xfec287, 0xfcfdbf,
+];
+
+/// A run of colours from 0 to 1.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    /// Dark blue through green to yellow, perceptually uniform.
+    #[default]
+    Viridis,
+    /// Black through purple and orange to pale yellow, perceptually
+    /// uniform.
+    Magma,
+    /// Evenly spaced sRGB stops, each channel 0 to 1, blended linearly.
+    Gradient(Vec<[f64; 3]>),
+}
+
+impl Palette {
+    /// The linear RGB colour at `t`, clamped to 0 to 1.
+    pub fn color(&self, t: f64) -> [f64; 3] {
+        let hex = |stops: &[u32]| -> Vec<[f64; 3]> {
+            stops
+                .iter()
+                .map(|stop| [16, 8, 0].map(|shift| (stop >> shift & 0xff) as f64 / 255.0))
+                .collect()
+        };
+        let stops = match self {
+            Palette::Viridis => hex(&VIRIDIS),
+            Palette::Magma => hex(&MAGMA),
+            Palette::Gradient(stops) => stops.clone(),
+        };
+        let Some(&last) = stops.last() else {
+            return [1.0; 3];
+        };
+        let along = if t.is_nan() {
+            0.0
+        } else {
+            t.clamp(0.0, 1.0) * (stops.len() - 1) as f64
+        };
+        let i = along.floor() as usize;
+        let srgb = match stops.get(i + 1) {
+            Some(next) => {
+                let f = along - i as f64;
+                std::array::from_fn(|c| stops[i][c] + (next[c] - stops[i][c]) * f)
+            }
+            None => last,
+        };
+        srgb.map(linear)
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// `viridis`, `magma`, or two or more comma-separated `#rrggbb` stops.
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text {
+            "viridis" => return Ok(Palette::Viridis),
+            "magma" => return Ok(Palette::Magma),
+            _ => {}
+        }
+        let stops = text
+            .split(',')
+            .map(|stop| {
+                let digits = stop.trim().trim_start_matches('#');
+                match (digits.len(), u32::from_str_radix(digits, 16)) {
+                    (6, Ok(rgb)) => {
+                        Ok([16, 8, 0].map(|shift| (rgb >> shift & 0xff) as f64 / 255.0))
+                    }
+                    _ => Err(format!(
+                        "unknown palette `{text}`; use viridis, magma or #rrggbb stops"
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        match stops.len() {
+            ..=1 => Err(format!("gradient `{text}` needs at least two stops")),
+            _ => Ok(Palette::Gradient(stops)),
+        }
+    }
+}
+
+/// What about a face's cell picks its colour.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Attribute {
+    /// The level the face was first cut at, over the depth, for rules
+    /// split evenly along every axis; 0 for other fractals.
+    #[default]
+    Depth,
+    /// The slice's place along w, 0 at the first slice and 1 at the last;
+    /// 0 for 3D fractals.
+    W,
+    /// Ambient occlusion: the share of the eight cells around the one in
+    /// front of the face, in its layer, that are filled, so faces deep in
+    /// crevices take the top of the palette.
+    Occlusion,
+    /// The face-connected piece of the lattice, spread over the palette
+    /// so neighbouring labels differ.
+    Component,
+}
+
+/// A palette keyed to an attribute.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorMap {
+    #[serde(default)]
+    pub attribute: Attribute,
+    #[serde(default)]
+    pub palette: Palette,
+}
+
+impl FromStr for ColorMap {
+    type Err = String;
+
+    /// `attribute[:palette]`, e.g. `depth`, `occlusion:magma` or
+    /// `component:#000000,#ff8800`.
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (attribute, palette) = match text.split_once(':') {
+            Some((attribute, palette)) => (attribute, palette.parse()?),
+            None => (text, Palette::default()),
+        };
+        let attribute = <Attribute as clap::ValueEnum>::from_str(attribute, true)
+            .map_err(|_| format!("unknown colour attribute `{attribute}`"))?;
+        Ok(ColorMap { attribute, palette })
+    }
+}
+
+/// A colour map with what its attributes read besides the lattice.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Colors {
+    pub map: ColorMap,
+    /// The subdivision `depth` colours by, when the fractal has one.
+    pub levels: Option<Levels>,
+    /// The slice's place along w, 0 to 1, that `w` colours by.
+    pub w: f64,
+}
+
+/// A colour map applied to the faces of one lattice.
+pub struct Coloring<'a> {
+    colors: Colors,
+    lattice: &'a Lattice3,
+    /// Per flat index, the component of each filled cell, when the
+    /// attribute reads them.
+    labels: Vec<u32>,
+}
+
+impl<'a> Coloring<'a> {
+    /// Colours the faces of `lattice`, whose cells join across `boundary`
+    /// into components.
+    pub fn new(colors: Colors, lattice: &'a Lattice3, boundary: Boundary) -> Self {
+        let labels = match colors.map.attribute {
+            Attribute::Component => lattice.components(boundary).labels,
+            _ => Vec::new(),
+        };
+        Coloring {
+            colors,
+            lattice,
+            labels,
+        }
+    }
+
+    /// `mesh`, a surface of the lattice in cell units, with a colour per
+    /// vertex, as the faces' colours give it for any [`FaceColors`].
+    pub fn mesh<S: Scalar>(&self, mesh: &Mesh<S>) -> (Mesh<S>, Vec<[f64; 3]>) {
+        (self as &dyn FaceColors).mesh(mesh, self.lattice.shape())
+    }
+}
+
+impl FaceColors for Coloring<'_> {
+    fn face(&self, cell: [usize; 3], axis: usize, positive: bool) -> [f64; 3] {
+        let t = match self.colors.map.attribute {
+            Attribute::Depth => self.colors.levels.map_or(0.0, |levels| {
+                let plane = cell[axis] + positive as usize;
+                levels.of(plane) as f64 / levels.depth.max(1) as f64
+            }),
+            Attribute::W => self.colors.w,
+            Attribute::Occlusion => {
+                let mut front = cell.map(|c| c as i64);
+                front[axis] += if positive { 1 } else { -1 };
+                let [u, v] = [(axis + 1) % 3, (axis + 2) % 3];
+                let mut filled = 0;
+                for du in -1..=1 {
+                    for dv in -1..=1 {
+                        let mut p = front;
+                        p[u] += du;
+                        p[v] += dv;
+                        filled += ((du, dv) != (0, 0) && self.lattice.get_signed(p)) as u32;
+                    }
+                }
+                filled as f64 / 8.0
+            }
+            Attribute::Component => {
+                let label = self.labels[self.lattice.index(cell)];
+                // Multiples of the golden ratio's fraction never bunch up.
+                (label.saturating_sub(1) as f64 * 0.618_033_988_75).fract()
+            }
+        };
+        self.colors.map.palette.color(t)
+    }
+}
+
+/// Colours of the faces of a lattice's cells, as a [`Coloring`] or
+/// [`Materials`](crate::materials::Materials) gives them.
+pub trait FaceColors: Sync {
+    /// The linear RGB colour of the face of `cell` looking along `axis`,
+    /// or against it unless `positive`.
+    fn face(&self, cell: [usize; 3], axis: usize, positive: bool) -> [f64; 3];
+}
+
+impl dyn FaceColors + '_ {
+    /// `mesh`, a surface of a lattice of `shape` in cell units, with a
+    /// colour per vertex. Vertices shared by faces of different colours
+    /// are split so every face keeps its own; each face is coloured as the
+    /// face of the cell just behind its centre, looking along its normal's
+    /// main axis.
+    pub fn mesh<S: Scalar>(&self, mesh: &Mesh<S>, shape: [usize; 3]) -> (Mesh<S>, Vec<[f64; 3]>) {
+        let mut vertices = Vec::new();
+        let mut colors = Vec::new();
+        let mut split = HashMap::new();
+        let mut recolor = |face: &[u32]| -> Vec<u32> {
+            let corners: Vec<[f64; 3]> = face.iter().map(|&i| mesh.vertex(i)).collect();
+            let normal = cross(sub(corners[1], corners[0]), sub(corners[2], corners[0]));
+            let axis = (0..3)
+                .max_by(|&a, &b| normal[a].abs().total_cmp(&normal[b].abs()))
+                .unwrap_or(0);
+            let positive = normal[axis] > 0.0;
+            let centre: [f64; 3] = std::array::from_fn(|a| {
+                corners.iter().map(|p| p[a]).sum::<f64>() / corners.len() as f64
+            });
+            let cell = std::array::from_fn(|a| {
+                let c = if a == axis {
+                    centre[a].round() - positive as u8 as f64
+                } else {
+                    centre[a].floor()
+                };
+                (c.max(0.0) as usize).min(shape[a].saturating_sub(1))
+            });
+            let color = self.face(cell, axis, positive);
+            face.iter()
+                .map(|&i| {
+                    *split
+                        .entry((i, color.map(f64::to_bits)))
+                        .or_insert_with(|| {
+                            vertices.push(mesh.vertices[i as usize]);
+                            colors.push(color);
+                            vertices.len() as u32 - 1
+                        })
+                })
+                .collect()
+        };
+        let faces = match &mesh.faces {
+            Polygons::Quads(quads) => Polygons::Quads(
+                quads
+                    .iter()
+                    .map(|quad| recolor(quad).try_into().expect("four corners"))
+                    .collect(),
+            ),
+            Polygons::Triangles(triangles) => Polygons::Triangles(
+                triangles
+                    .iter()
+                    .map(|triangle| recolor(triangle).try_into().expect("three corners"))
+                    .collect(),
+            ),
+        };
+        (Mesh { vertices, faces }, colors)
+    }
+}
+
+/// An sRGB channel, 0 to 1, in linear light.
+fn linear(srgb: f64) -> f64 {
+    if srgb <= 0.040_45 {
+        srgb / 12.92
+    } else {
+        ((srgb + 0.055) / 1.055).powf(2.4)
+    }
+}
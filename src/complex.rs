@@ -0,0 +1,246 @@
+//! The raw 4D structure of a hypersponge as a cell complex: every kept
+//! tesseract cell with its 16 corner vertices and 8 cube facets, each
+//! vertex and facet stored once and shared by the cells that meet there,
+//! for 4D tools that want the cells rather than 3D slices of them.
+//!
+//! Coordinates are whole lattice units, a cell spanning `position` to
+//! `position + 1` on every axis; the job's transforms are 3D and are not
+//! applied. Corner `k` of a cell is offset by 1 along axis `i` when bit
+//! `i` of `k` is set, x being bit 0 and w bit 3. Facet `2 * i` of a cell
+//! is its low side along axis `i` and facet `2 * i + 1` its high side. A
+//! facet's corners run over the other three axes in the same way, the
+//! lowest of them as bit 0.
+//!
+//! A `.json` path holds one object:
+//!
+//! ```json
+//! {
+//!   "format": "fractal-slicer-cell-complex",
+//!   "version": 1,
+//!   "shape": [27, 27, 27, 27],
+//!   "vertices": [[0, 0, 0, 0], ...],
+//!   "facets": [{"axis": 0, "vertices": [0, 1, ...], "cells": [null, 0]}, ...],
+//!   "cells": [{"position": [0, 0, 0, 0], "vertices": [0, ...], "facets": [0, ...]}, ...]
+//! }
+//! ```
+//!
+//! where a facet's `cells` are the cells below and above it along its
+//! axis, null for none, so a facet with a null is on the boundary. Any
+//! other path holds the same in little-endian binary:
+//!
+//! | offset | size | field                                             |
+//! |--------|------|---------------------------------------------------|
+//! | 0      | 4    | magic `FS4C`                                      |
+//! | 4      | 1    | version, 1                                        |
+//! | 5      | 3    | zero                                              |
+//! | 8      | 16   | shape, four `u32`                                 |
+//! | 24     | 24   | vertex, facet and cell counts, `u64` each         |
+//! | 48     |      | per vertex: x, y, z, w, `u32` each                |
+//! |        |      | per facet: axis, 8 vertices, cell below and above |
+//! |        |      | per cell: position, 16 vertices, 8 facets         |
+//!
+//! with every facet and cell field a `u32`, and `u32::MAX` for no cell.
+//!
+//! `examples/load_complex.rs` reads the binary file back.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::export::{write_file_atomically, Artifact};
+use crate::lattice::Lattice4;
+
+pub const MAGIC: [u8; 4] = *b"FS4C";
+pub const VERSION: u8 = 1;
+
+/// How many cells are added or written between cancellation checks.
+const CANCEL_INTERVAL: usize = 4096;
+
+/// The kept cells of a 4D lattice with their shared vertices and facets.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CellComplex {
+    pub shape: [usize; 4],
+    pub vertices: Vec<[u32; 4]>,
+    pub facets: Vec<Facet>,
+    pub cells: Vec<Cell>,
+}
+
+/// A cube facet, normal to `axis`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Facet {
+    pub axis: u8,
+    pub vertices: [u32; 8],
+    /// The cells below and above the facet along its axis.
+    pub cells: [Option<u32>; 2],
+}
+
+/// A kept tesseract cell.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Cell {
+    pub position: [u32; 4],
+    pub vertices: [u32; 16],
+    pub facets: [u32; 8],
+}
+
+impl CellComplex {
+    /// The complex of the filled cells of `lattice`, cells in index order
+    /// and vertices and facets in the order the cells first reach them.
+    pub fn new(lattice: &Lattice4, cancel: &CancelToken) -> Result<Self> {
+        let mut complex = CellComplex {
+            shape: lattice.shape(),
+            ..CellComplex::default()
+        };
+        let mut vertices: HashMap<[usize; 4], u32> = HashMap::new();
+        // Facets by their lowest corner and axis.
+        let mut facets: HashMap<([usize; 4], usize), u32> = HashMap::new();
+        for (index, p) in lattice.iter().enumerate() {
+            if index % CANCEL_INTERVAL == 0 {
+                cancel.check()?;
+            }
+            let mut vertex = |corner: [usize; 4]| {
+                let count = vertices.len() as u32;
+                *vertices.entry(corner).or_insert_with(|| {
+                    complex.vertices.push(corner.map(|c| c as u32));
+                    count
+                })
+            };
+            let corners: [u32; 16] =
+                std::array::from_fn(|k| vertex(std::array::from_fn(|i| p[i] + (k >> i & 1))));
+            let cell = index as u32;
+            let cell_facets = std::array::from_fn(|f| {
+                let (axis, high) = (f / 2, f % 2 == 1);
+                let mut low = p;
+                low[axis] += high as usize;
+                let count = facets.len() as u32;
+                let facet = *facets.entry((low, axis)).or_insert_with(|| {
+                    let others: Vec<usize> = (0..4).filter(|&i| i != axis).collect();
+                    let offset = |k: usize| -> usize {
+                        others
+                            .iter()
+                            .enumerate()
+                            .map(|(j, &i)| (k >> j & 1) << i)
+                            .sum()
+                    };
+                    complex.facets.push(Facet {
+                        axis: axis as u8,
+                        vertices: std::array::from_fn(|k| {
+                            corners[offset(k) | (high as usize) << axis]
+                        }),
+                        cells: [None, None],
+                    });
+                    count
+                });
+                // The cell is above its low facet and below its high one.
+                complex.facets[facet as usize].cells[!high as usize] = Some(cell);
+                facet
+            });
+            complex.cells.push(Cell {
+                position: p.map(|c| c as u32),
+                vertices: corners,
+                facets: cell_facets,
+            });
+        }
+        Ok(complex)
+    }
+
+    /// Writes the complex as the JSON object described above.
+    pub fn write_json(&self, out: &mut impl Write) -> Result<()> {
+        #[derive(Serialize)]
+        struct Document<'a> {
+            format: &'static str,
+            version: u8,
+            #[serde(flatten)]
+            complex: &'a CellComplex,
+        }
+        let document = Document {
+            format: "fractal-slicer-cell-complex",
+            version: VERSION,
+            complex: self,
+        };
+        serde_json::to_writer(&mut *out, &document)?;
+        out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Writes the complex in the binary layout described above.
+    pub fn write_binary(&self, out: &mut impl Write, cancel: &CancelToken) -> Result<()> {
+        let u32s = |out: &mut dyn Write, values: &[u32]| -> Result<()> {
+            for value in values {
+                out.write_all(&value.to_le_bytes())?;
+            }
+            Ok(())
+        };
+        out.write_all(&MAGIC)?;
+        out.write_all(&[VERSION, 0, 0, 0])?;
+        u32s(out, &self.shape.map(|side| side as u32))?;
+        for count in [self.vertices.len(), self.facets.len(), self.cells.len()] {
+            out.write_all(&(count as u64).to_le_bytes())?;
+        }
+        for (i, vertex) in self.vertices.iter().enumerate() {
+            if i % CANCEL_INTERVAL == 0 {
+                cancel.check()?;
+            }
+            u32s(out, vertex)?;
+        }
+        for (i, facet) in self.facets.iter().enumerate() {
+            if i % CANCEL_INTERVAL == 0 {
+                cancel.check()?;
+            }
+            u32s(out, &[facet.axis as u32])?;
+            u32s(out, &facet.vertices)?;
+            u32s(out, &facet.cells.map(|cell| cell.unwrap_or(u32::MAX)))?;
+        }
+        for (i, cell) in self.cells.iter().enumerate() {
+            if i % CANCEL_INTERVAL == 0 {
+                cancel.check()?;
+            }
+            u32s(out, &cell.position)?;
+            u32s(out, &cell.vertices)?;
+            u32s(out, &cell.facets)?;
+        }
+        Ok(())
+    }
+
+    /// Upper bound on the bytes of a complex of `cells` cells, in JSON
+    /// when `json`: every vertex and facet of each cell its own.
+    pub fn max_bytes(cells: u64, json: bool) -> u64 {
+        // A vertex, facet and cell of binary are 16, 44 and 112 bytes; in
+        // JSON each number takes up to 11 bytes with its comma.
+        let per_cell: u64 = if json {
+            16 * 4 * 11 + 8 * 11 * 11 + 28 * 11 + 128
+        } else {
+            16 * 16 + 8 * 44 + 112
+        };
+        cells.saturating_mul(per_cell).saturating_add(64)
+    }
+}
+
+/// Whether `path` names a JSON complex rather than a binary one.
+pub fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+/// Writes the cell complex of `lattice` to `path`, as JSON for a `.json`
+/// path and binary otherwise.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn export_complex(lattice: &Lattice4, path: &Path, cancel: &CancelToken) -> Result<Artifact> {
+    let complex = CellComplex::new(lattice, cancel)?;
+    tracing::info!(
+        vertices = complex.vertices.len(),
+        facets = complex.facets.len(),
+        cells = complex.cells.len(),
+        "cell complex built"
+    );
+    write_file_atomically(path, |out| {
+        if is_json(path) {
+            complex.write_json(out)
+        } else {
+            complex.write_binary(out, cancel)
+        }
+    })
+}
@@ -0,0 +1,194 @@
+/// `data` wrapped in a zlib stream, as PNG image data is.
+pub(crate) fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    deflate(data, &mut out);
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+/// `data` wrapped in a gzip member, as NBT files and Zarr chunks are.
+pub(crate) fn gzip(data: &[u8]) -> Vec<u8> {
+    // No timestamp, no flags, unknown operating system.
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    deflate(data, &mut out);
+    out.extend_from_slice(&crc32(data).to_le_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out
+}
+
+/// Shortest and longest back-references, and how far back they reach.
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const WINDOW: usize = 1 << 15;
+
+/// Candidate matches tried per position; enough for the long runs and
+/// repeats of voxel data.
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Appends `data` as one deflate block with the fixed Huffman codes,
+/// repeats replaced by greedy back-references found through hash chains.
+fn deflate(data: &[u8], out: &mut Vec<u8>) {
+    let mut bits = Bits {
+        out,
+        value: 0,
+        count: 0,
+    };
+    // Final block, fixed codes.
+    bits.push(0b011, 3);
+    let hash = |i: usize| {
+        let key = (data[i] as usize) << 16 | (data[i + 1] as usize) << 8 | data[i + 2] as usize;
+        key.wrapping_mul(2_654_435_761) >> 17 & (WINDOW - 1)
+    };
+    // The latest position with each hash, and the one before each position
+    // with the same hash; `usize::MAX` for none.
+    let mut head = vec![usize::MAX; WINDOW];
+    let mut previous = vec![usize::MAX; WINDOW];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            previous[i % WINDOW] = head[h];
+            head[h] = i;
+        }
+    };
+    let mut i = 0;
+    while i < data.len() {
+        let mut best = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let limit = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(i)];
+            for _ in 0..MAX_CHAIN {
+                if candidate == usize::MAX || i - candidate > WINDOW - 1 {
+                    break;
+                }
+                let length = (0..limit)
+                    .take_while(|&k| data[candidate + k] == data[i + k])
+                    .count();
+                if length > best.0 {
+                    best = (length, i - candidate);
+                    if length == limit {
+                        break;
+                    }
+                }
+                let next = previous[candidate % WINDOW];
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+            }
+        }
+        let (length, distance) = best;
+        if length >= MIN_MATCH {
+            let code = LENGTH_BASE.partition_point(|&base| base as usize <= length) - 1;
+            bits.literal(257 + code as u16);
+            bits.push(
+                (length - LENGTH_BASE[code] as usize) as u32,
+                LENGTH_EXTRA[code],
+            );
+            let code = DISTANCE_BASE.partition_point(|&base| base as usize <= distance) - 1;
+            bits.push_reversed(code as u32, 5);
+            bits.push(
+                (distance - DISTANCE_BASE[code] as usize) as u32,
+                DISTANCE_EXTRA[code],
+            );
+            for k in i..i + length {
+                insert(k, &mut head, &mut previous);
+            }
+            i += length;
+        } else {
+            bits.literal(data[i] as u16);
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    bits.literal(256);
+    bits.flush();
+}
+
+/// A deflate bit stream, filled from the least significant bit of each
+/// byte.
+struct Bits<'a> {
+    out: &'a mut Vec<u8>,
+    value: u64,
+    count: u8,
+}
+
+impl Bits<'_> {
+    fn push(&mut self, value: u32, count: u8) {
+        self.value |= (value as u64) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.value as u8);
+            self.value >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are stored from their most significant bit.
+    fn push_reversed(&mut self, code: u32, count: u8) {
+        self.push(code.reverse_bits() >> (32 - count), count);
+    }
+
+    /// A literal, length or end-of-block symbol in the fixed code.
+    fn literal(&mut self, symbol: u16) {
+        let symbol = symbol as u32;
+        match symbol {
+            0..=143 => self.push_reversed(0x30 + symbol, 8),
+            144..=255 => self.push_reversed(0x190 + symbol - 144, 9),
+            256..=279 => self.push_reversed(symbol - 256, 7),
+            _ => self.push_reversed(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.value as u8);
+        }
+        self.value = 0;
+        self.count = 0;
+    }
+}
+
+/// The CRC-32 of PNG chunks and gzip members.
+pub(crate) fn crc32<'a>(bytes: impl IntoIterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+/// The Adler-32 checksum ending a zlib stream.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in bytes.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
@@ -0,0 +1,375 @@
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::distance::signed_distances;
+use crate::error::{Error, Result};
+use crate::image::{write_png, Layer};
+use crate::lattice::Lattice3;
+use crate::random::Rng;
+use crate::rule::Rule;
+use crate::store::{self, Sink};
+
+/// A randomized training set for 3D machine learning: slices of built-in
+/// 4D fractals at random depths, slice positions and rotations, each
+/// resampled to one grid and written as a depth image, a signed distance
+/// volume and its parameter labels.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dataset {
+    pub samples: usize,
+    /// Rules samples are drawn from.
+    #[serde(default = "default_fractals")]
+    pub fractals: Vec<String>,
+    #[serde(default = "default_min_depth")]
+    pub min_depth: u32,
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    /// Side of every sample's grid, in cells. Slices are resampled to it
+    /// by nearest cell, so sides larger than it lose detail.
+    #[serde(default = "default_resolution")]
+    pub resolution: usize,
+    /// Fraction of the samples held out for validation.
+    #[serde(default = "default_val_fraction")]
+    pub val_fraction: f64,
+    /// The same seed and settings always give the same dataset.
+    #[serde(default)]
+    pub seed: u64,
+}
+
+fn default_fractals() -> Vec<String> {
+    Rule::NAMES.iter().map(|name| name.to_string()).collect()
+}
+
+fn default_min_depth() -> u32 {
+    1
+}
+
+fn default_max_depth() -> u32 {
+    3
+}
+
+fn default_resolution() -> usize {
+    64
+}
+
+fn default_val_fraction() -> f64 {
+    0.1
+}
+
+/// One sample's files and labels, as listed in `train.jsonl` and
+/// `val.jsonl`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub id: String,
+    pub split: Subset,
+    pub fractal: String,
+    pub depth: u32,
+    /// The w slice of the depth-`depth` hypersponge.
+    pub w: usize,
+    /// Maps the sample's centred x, y, z onto the slice's: one of the 24
+    /// rotations of the cube, so resampling is exact.
+    pub rotation: [[i32; 3]; 3],
+    /// Fraction of the sample's cells that are filled.
+    pub filled: f64,
+    /// 8-bit depth image seen from +z, relative to the dataset directory.
+    pub image: PathBuf,
+    /// `float32` signed distance volume in z, y, x order, relative to the
+    /// dataset directory.
+    pub sdf: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Subset {
+    Train,
+    Val,
+}
+
+/// What [`Dataset::generate`] wrote.
+#[derive(Clone, Debug, Serialize)]
+pub struct DatasetReport {
+    pub dir: PathBuf,
+    pub train: usize,
+    pub val: usize,
+}
+
+impl Dataset {
+    pub fn validate(&self) -> Result<()> {
+        if self.samples == 0 {
+            return Err(Error::InvalidJob("a dataset needs samples".into()));
+        }
+        if self.fractals.is_empty() {
+            return Err(Error::InvalidJob("a dataset needs fractals".into()));
+        }
+        for fractal in &self.fractals {
+            if Rule::by_name(fractal, 4).is_none() {
+                return Err(Error::UnknownFractal(fractal.clone()));
+            }
+        }
+        if self.min_depth == 0 || self.min_depth > self.max_depth {
+            return Err(Error::InvalidJob(format!(
+                "depths {}..={} are not a range from 1",
+                self.min_depth, self.max_depth
+            )));
+        }
+        if self.resolution == 0 {
+            return Err(Error::InvalidJob(
+                "dataset resolution must be at least 1 cell".into(),
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.val_fraction) {
+            return Err(Error::InvalidJob(
+                "validation fraction must be between 0 and 1".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The labels of every sample, in order. Exactly the validation
+    /// fraction of them, rounded, is held out, chosen at random.
+    pub fn samples(&self) -> Vec<Sample> {
+        let mut order: Vec<usize> = (0..self.samples).collect();
+        let mut rng = Rng::new(self.seed, 0);
+        for i in (1..order.len()).rev() {
+            order.swap(i, rng.below(i + 1));
+        }
+        let held_out = (self.samples as f64 * self.val_fraction).round() as usize;
+        let mut subsets = vec![Subset::Train; self.samples];
+        for &i in &order[..held_out] {
+            subsets[i] = Subset::Val;
+        }
+        let width = self.samples.saturating_sub(1).to_string().len().max(5);
+        subsets
+            .into_iter()
+            .enumerate()
+            .map(|(index, split)| {
+                // Each sample draws from its own stream, so it does not
+                // change with the sample count.
+                let mut rng = Rng::new(self.seed, index as u64 + 1);
+                let fractal = self.fractals[rng.below(self.fractals.len())].clone();
+                let depths = (self.max_depth - self.min_depth) as usize + 1;
+                let depth = self.min_depth + rng.below(depths) as u32;
+                let rule = Rule::by_name(&fractal, 4).expect("fractals are validated");
+                let w = rng.below(rule.side_along(3, depth));
+                let rotation = ROTATIONS[rng.below(ROTATIONS.len())];
+                let id = format!("sample_{index:0width$}");
+                Sample {
+                    image: Path::new("images").join(format!("{id}.png")),
+                    sdf: Path::new("sdf").join(format!("{id}.npy")),
+                    id,
+                    split,
+                    fractal,
+                    depth,
+                    w,
+                    rotation,
+                    filled: 0.0,
+                }
+            })
+            .collect()
+    }
+
+    /// Renders every sample into `dir` on at most `workers` threads, then
+    /// writes `train.jsonl`, `val.jsonl` and the settings as
+    /// `dataset.json`.
+    #[tracing::instrument(name = "dataset", skip_all, fields(samples = self.samples))]
+    pub fn generate(
+        &self,
+        dir: &Path,
+        workers: usize,
+        cancel: &CancelToken,
+    ) -> Result<DatasetReport> {
+        self.validate()?;
+        let samples = self.samples();
+        let workers = workers.clamp(1, samples.len());
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new((0..samples.len()).map(|_| None).collect::<Vec<_>>());
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(sample) = samples.get(index) else {
+                        break;
+                    };
+                    let result = self.render(sample, dir, cancel);
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+        let samples = results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .map(|result| result.expect("every sample is rendered"))
+            .collect::<Result<Vec<_>>>()?;
+        for subset in [Subset::Train, Subset::Val] {
+            let name = match subset {
+                Subset::Train => "train.jsonl",
+                Subset::Val => "val.jsonl",
+            };
+            let mut out = store::create(&dir.join(name))?;
+            for sample in samples.iter().filter(|s| s.split == subset) {
+                serde_json::to_writer(&mut out, sample)?;
+                writeln!(out)?;
+            }
+            out.finish()?;
+        }
+        let mut out = store::create(&dir.join("dataset.json"))?;
+        serde_json::to_writer_pretty(&mut out, self)?;
+        writeln!(out)?;
+        out.finish()?;
+        let val = samples.iter().filter(|s| s.split == Subset::Val).count();
+        Ok(DatasetReport {
+            dir: dir.to_path_buf(),
+            train: samples.len() - val,
+            val,
+        })
+    }
+
+    /// Resamples one sample's slice and writes its image and volume.
+    fn render(&self, sample: &Sample, dir: &Path, cancel: &CancelToken) -> Result<Sample> {
+        let rule = Rule::by_name(&sample.fractal, 4).expect("fractals are validated");
+        let n = self.resolution;
+        let sides: [usize; 3] = std::array::from_fn(|axis| rule.side_along(axis, sample.depth));
+        let lattice = Lattice3::from_fn([n; 3], cancel, |p| {
+            // Rotate the cell centre about the middle of the grid.
+            let centred = p.map(|c| (c as f64 + 0.5) / n as f64 - 0.5);
+            let mut coords = [0, 0, 0, sample.w];
+            for (axis, row) in sample.rotation.iter().enumerate() {
+                let u: f64 = row.iter().zip(centred).map(|(&r, c)| r as f64 * c).sum();
+                let cell = ((u + 0.5) * sides[axis] as f64) as usize;
+                coords[axis] = cell.min(sides[axis] - 1);
+            }
+            rule.is_solid(&coords, sample.depth)
+        })?;
+        let distances = signed_distances(&lattice, cancel)?;
+        write_sample(&dir.join(&sample.image), |out| {
+            write_png(&depth_image(&lattice), out)
+        })?;
+        write_sample(&dir.join(&sample.sdf), |out| {
+            write_npy(&distances, [n; 3], out)
+        })?;
+        tracing::info!(id = %sample.id, fractal = %sample.fractal, depth = sample.depth, "sample written");
+        Ok(Sample {
+            filled: lattice.count() as f64 / lattice.len() as f64,
+            ..sample.clone()
+        })
+    }
+}
+
+fn write_sample(path: &Path, write: impl FnOnce(&mut Box<dyn Sink>) -> Result<()>) -> Result<()> {
+    let mut out = store::create(path)?;
+    match write(&mut out) {
+        Ok(()) => out.finish(),
+        Err(e) => {
+            out.abort();
+            Err(e)
+        }
+    }
+}
+
+/// The lattice seen from +z: each pixel is the height of the topmost
+/// filled cell in its column, brighter when higher, and 0 where the column
+/// is empty. Rows run along y as in z image stacks.
+fn depth_image(lattice: &Lattice3) -> Layer {
+    let [nx, ny, nz] = lattice.shape();
+    let mut samples = Vec::with_capacity(nx * ny);
+    for y in 0..ny {
+        for x in 0..nx {
+            let top = (0..nz).rev().find(|&z| lattice.get([x, y, z]));
+            samples.push(top.map_or(0, |z| (255 * (z + 1) / nz) as u16));
+        }
+    }
+    Layer {
+        width: nx,
+        height: ny,
+        bits: 8,
+        samples,
+    }
+}
+
+/// Writes a NumPy `.npy` array of little-endian `float32`, its axes the
+/// reverse of `shape`, which runs fastest first as lattice indices do.
+fn write_npy(values: &[f64], shape: [usize; 3], out: &mut impl Write) -> Result<()> {
+    let [nx, ny, nz] = shape;
+    let mut header =
+        format!("{{'descr': '<f4', 'fortran_order': False, 'shape': ({nz}, {ny}, {nx}), }}");
+    // The magic, version and length take 10 bytes; the header is padded
+    // so the data starts on a 64-byte boundary.
+    while (10 + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+    out.write_all(b"\x93NUMPY\x01\x00")?;
+    out.write_all(&(header.len() as u16).to_le_bytes())?;
+    out.write_all(header.as_bytes())?;
+    let data: Vec<u8> = values
+        .iter()
+        .flat_map(|&v| (v as f32).to_le_bytes())
+        .collect();
+    out.write_all(&data)?;
+    Ok(())
+}
+
+/// The rotations of the cube: signed permutation matrices of determinant 1.
+const ROTATIONS: [[[i32; 3]; 3]; 24] = rotations();
+
+const fn rotations() -> [[[i32; 3]; 3]; 24] {
+    const PERMUTATIONS: [([usize; 3], i32); 6] = [
+        ([0, 1, 2], 1),
+        ([1, 2, 0], 1),
+        ([2, 0, 1], 1),
+        ([0, 2, 1], -1),
+        ([2, 1, 0], -1),
+        ([1, 0, 2], -1),
+    ];
+    let mut rotations = [[[0; 3]; 3]; 24];
+    let mut count = 0;
+    let mut p = 0;
+    while p < 6 {
+        let (columns, parity) = PERMUTATIONS[p];
+        let mut signs = 0;
+        while signs < 8 {
+            if sign(signs, 0) * sign(signs, 1) * sign(signs, 2) == parity {
+                let mut matrix = [[0; 3]; 3];
+                let mut row = 0;
+                while row < 3 {
+                    matrix[row][columns[row]] = sign(signs, row);
+                    row += 1;
+                }
+                rotations[count] = matrix;
+                count += 1;
+            }
+            signs += 1;
+        }
+        p += 1;
+    }
+    rotations
+}
+
+/// -1 where bit `bit` of `signs` is set, 1 otherwise.
+const fn sign(signs: usize, bit: usize) -> i32 {
+    if signs >> bit & 1 == 1 {
+        -1
+    } else {
+        1
+    }
+}
+
+impl fmt::Display for DatasetReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "wrote {} samples to {}: {} train, {} val",
+            self.train + self.val,
+            self.dir.display(),
+            self.train,
+            self.val
+        )
+    }
+}
@@ -0,0 +1,272 @@
+//! How far one mesh strays from another: the Hausdorff and Chamfer
+//! distances between their surfaces, to measure what decimation, marching
+//! cubes or quantization changed against the exact cube mesh.
+//!
+//! Distances are taken from points of each mesh, its vertices and points
+//! drawn evenly over its area, to the nearest point of the other's
+//! surface, so a vertex lying on the other surface counts as no error
+//! even where the other has no vertex. Each direction is summarized on its
+//! own; the Hausdorff distance is the larger maximum and the Chamfer
+//! distance the sum of the two means. Lengths are in the meshes' units.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::bvh::Bvh;
+use crate::cancel::CancelToken;
+use crate::color::Palette;
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, write_glb, Artifact, ExportOptions};
+use crate::mesh::{cross, sub, Mesh, Polygons};
+use crate::random::Rng;
+use crate::transform::Affine;
+
+/// Points measured between cancellation checks.
+const CANCEL_INTERVAL: usize = 4096;
+
+/// The distances between a reference mesh and one measured against it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Deviation {
+    pub reference: String,
+    pub measured: String,
+    /// Diagonal of the reference's bounding box, to scale the distances
+    /// by.
+    pub diagonal: f64,
+    /// From the measured mesh's points to the reference's surface.
+    pub forward: DistanceStats,
+    /// From the reference's points to the measured mesh's surface.
+    pub backward: DistanceStats,
+    pub hausdorff: f64,
+    pub chamfer: f64,
+    /// The measured mesh with its vertices' errors, if written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<Artifact>,
+}
+
+/// One direction's distances.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct DistanceStats {
+    pub points: usize,
+    pub mean: f64,
+    pub rms: f64,
+    /// The 95th percentile.
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl DistanceStats {
+    pub fn of(distances: &[f64]) -> Self {
+        if distances.is_empty() {
+            return DistanceStats {
+                points: 0,
+                mean: 0.0,
+                rms: 0.0,
+                p95: 0.0,
+                max: 0.0,
+            };
+        }
+        let n = distances.len() as f64;
+        let mut sorted = distances.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let rank = ((0.95 * n).ceil() as usize).clamp(1, sorted.len());
+        DistanceStats {
+            points: distances.len(),
+            mean: distances.iter().sum::<f64>() / n,
+            rms: (distances.iter().map(|d| d * d).sum::<f64>() / n).sqrt(),
+            p95: sorted[rank - 1],
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// Measures `measured` against `reference`, from their vertices and
+/// `samples` points drawn over each. Returns the distances with the error
+/// of each of the measured mesh's vertices, its distance to the reference.
+pub fn deviation(
+    (reference_name, measured_name): (String, String),
+    reference: &Mesh,
+    measured: &Mesh,
+    samples: usize,
+    cancel: &CancelToken,
+) -> Result<(Deviation, Vec<f64>)> {
+    if reference.face_count() == 0 || measured.face_count() == 0 {
+        return Err(Error::InvalidJob(
+            "cannot measure the distance to a mesh without faces".into(),
+        ));
+    }
+    let (to_reference, to_measured) = (Bvh::build(reference), Bvh::build(measured));
+    let forward = surface_distances(measured, &to_reference, samples, cancel)?;
+    let backward = surface_distances(reference, &to_measured, samples, cancel)?;
+    let (forward_stats, backward_stats) =
+        (DistanceStats::of(&forward), DistanceStats::of(&backward));
+    let diagonal = to_reference.bounds().map_or(0.0, |(low, high)| {
+        let d = sub(high, low);
+        (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+    });
+    let vertex_errors = forward[..measured.vertices.len()].to_vec();
+    let deviation = Deviation {
+        reference: reference_name,
+        measured: measured_name,
+        diagonal,
+        forward: forward_stats,
+        backward: backward_stats,
+        hausdorff: forward_stats.max.max(backward_stats.max),
+        chamfer: forward_stats.mean + backward_stats.mean,
+        errors: None,
+    };
+    Ok((deviation, vertex_errors))
+}
+
+/// Distances from the vertices of `from`, then from `samples` points drawn
+/// over its area, to the surface in `to`.
+pub fn surface_distances(
+    from: &Mesh,
+    to: &Bvh,
+    samples: usize,
+    cancel: &CancelToken,
+) -> Result<Vec<f64>> {
+    let points = from
+        .vertices
+        .iter()
+        .copied()
+        .chain(sample_surface(from, samples));
+    let mut distances = Vec::with_capacity(from.vertices.len() + samples);
+    for (i, p) in points.enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        distances.push(to.closest(p).map_or(f64::INFINITY, |c| c.distance));
+    }
+    Ok(distances)
+}
+
+/// `count` points spread evenly over the area of `mesh`, the same ones
+/// every run.
+pub fn sample_surface(mesh: &Mesh, count: usize) -> impl Iterator<Item = [f64; 3]> + '_ {
+    let triangles = mesh.triangles();
+    let mut total = 0.0;
+    let cumulative: Vec<f64> = triangles
+        .iter()
+        .map(|t| {
+            let [a, b, c] = t.map(|v| mesh.vertex(v));
+            let n = cross(sub(b, a), sub(c, a));
+            total += (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt() / 2.0;
+            total
+        })
+        .collect();
+    let mut rng = Rng::new(0, 0);
+    let count = if total > 0.0 { count } else { 0 };
+    (0..count).map(move |_| {
+        let at = rng.unit() * total;
+        let i = cumulative
+            .partition_point(|&area| area <= at)
+            .min(triangles.len() - 1);
+        let [a, b, c] = triangles[i].map(|v| mesh.vertex(v));
+        // Uniform over the triangle by folding the unit square.
+        let (mut u, mut v) = (rng.unit(), rng.unit());
+        if u + v > 1.0 {
+            (u, v) = (1.0 - u, 1.0 - v);
+        }
+        std::array::from_fn(|k| a[k] + (b[k] - a[k]) * u + (c[k] - a[k]) * v)
+    })
+}
+
+/// Writes `mesh` with `errors`, one per vertex, to `path`: a `.glb` with
+/// each vertex coloured by `palette` from no error to the largest, or a
+/// legacy `.vtk` polygon mesh with the errors as its `error` point scalars.
+pub fn write_errors(
+    mesh: &Mesh,
+    errors: &[f64],
+    path: &Path,
+    palette: &Palette,
+    cancel: &CancelToken,
+) -> Result<Artifact> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("glb") => {
+            let largest = errors.iter().copied().fold(0.0, f64::max);
+            let colors: Vec<[f64; 3]> = errors
+                .iter()
+                .map(|&e| palette.color(if largest > 0.0 { e / largest } else { 0.0 }))
+                .collect();
+            let options = ExportOptions::default();
+            write_file_atomically(path, |out| {
+                write_glb(
+                    mesh,
+                    Some(&colors),
+                    &[Affine::default()],
+                    &options,
+                    out,
+                    cancel,
+                )
+            })
+        }
+        Some("vtk") => write_file_atomically(path, |out| write_vtk_errors(mesh, errors, out)),
+        _ => Err(Error::UnknownFormat(path.to_path_buf())),
+    }
+}
+
+/// Writes `mesh` as ASCII legacy VTK polygon data with `errors` as its
+/// vertices' `error` scalars.
+pub fn write_vtk_errors(mesh: &Mesh, errors: &[f64], out: &mut impl Write) -> Result<()> {
+    writeln!(out, "# vtk DataFile Version 3.0")?;
+    writeln!(out, "distance from each vertex to the reference surface")?;
+    writeln!(out, "ASCII")?;
+    writeln!(out, "DATASET POLYDATA")?;
+    writeln!(out, "POINTS {} double", mesh.vertices.len())?;
+    for [x, y, z] in &mesh.vertices {
+        writeln!(out, "{x} {y} {z}")?;
+    }
+    match &mesh.faces {
+        Polygons::Quads(quads) => {
+            writeln!(out, "POLYGONS {} {}", quads.len(), quads.len() * 5)?;
+            for [a, b, c, d] in quads {
+                writeln!(out, "4 {a} {b} {c} {d}")?;
+            }
+        }
+        Polygons::Triangles(triangles) => {
+            writeln!(out, "POLYGONS {} {}", triangles.len(), triangles.len() * 4)?;
+            for [a, b, c] in triangles {
+                writeln!(out, "3 {a} {b} {c}")?;
+            }
+        }
+    }
+    writeln!(out, "POINT_DATA {}", errors.len())?;
+    writeln!(out, "SCALARS error double 1")?;
+    writeln!(out, "LOOKUP_TABLE default")?;
+    for error in errors {
+        writeln!(out, "{error}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Deviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "deviation of {} from {}:", self.measured, self.reference)?;
+        writeln!(f, "  hausdorff: {:.6}", self.hausdorff)?;
+        writeln!(f, "  chamfer: {:.6}", self.chamfer)?;
+        writeln!(f, "  reference diagonal: {:.6}", self.diagonal)?;
+        writeln!(
+            f,
+            "  {:<10} {:>8} {:>12} {:>12} {:>12} {:>12}",
+            "direction", "points", "mean", "rms", "p95", "max"
+        )?;
+        for (direction, stats) in [("forward", &self.forward), ("backward", &self.backward)] {
+            writeln!(
+                f,
+                "  {:<10} {:>8} {:>12.6} {:>12.6} {:>12.6} {:>12.6}",
+                direction, stats.points, stats.mean, stats.rms, stats.p95, stats.max
+            )?;
+        }
+        if let Some(errors) = &self.errors {
+            writeln!(f, "  vertex errors: {}", errors.path.display())?;
+        }
+        Ok(())
+    }
+}
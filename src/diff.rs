@@ -0,0 +1,335 @@
+//! Comparisons of two lattices of the same shape, such as two rules or one
+//! job generated by two backends: how much they overlap, how far apart
+//! their surfaces are, how many blocks of each level they disagree on, and
+//! a voxel diff marking the cells each has that the other lacks.
+
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::analysis::coarsen;
+use crate::cancel::CancelToken;
+use crate::distance::squared_distances;
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, Artifact};
+use crate::job::Job;
+use crate::lattice::{Lattice, Lattice3};
+use crate::volume::MappedVolume;
+
+/// Labels of the voxel diff's cells.
+const NEITHER: u8 = 0;
+const BOTH: u8 = 1;
+const ONLY_FIRST: u8 = 2;
+const ONLY_SECOND: u8 = 3;
+
+/// One side of a comparison.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    /// The lattice a job generates, read from its JSON.
+    Job(Box<Job>),
+    /// A `.nii` volume written by `generate`.
+    Volume(PathBuf),
+}
+
+/// How two lattices differ.
+#[derive(Clone, Debug, Serialize)]
+pub struct LatticeDiff {
+    pub first: String,
+    pub second: String,
+    pub shape: Vec<usize>,
+    pub first_cells: usize,
+    pub second_cells: usize,
+    /// Cells filled in both.
+    pub both: usize,
+    pub only_first: usize,
+    pub only_second: usize,
+    /// Cells filled in both over cells filled in either; 1 for two empty
+    /// lattices.
+    pub jaccard: f64,
+    /// Farthest any surface cell of either lies from the other's surface,
+    /// in cells; infinite when only one has a surface.
+    pub hausdorff: f64,
+    /// Per level, coarsest first.
+    pub levels: Vec<LevelDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub voxels: Option<Artifact>,
+}
+
+/// The blocks of one level filled in either lattice, a block being filled
+/// when any of its cells is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct LevelDiff {
+    pub level: u32,
+    /// Side of a block, in cells.
+    pub side: usize,
+    pub first: usize,
+    pub second: usize,
+    /// Blocks filled in one lattice but not the other.
+    pub disagreeing: usize,
+}
+
+impl Source {
+    /// Reads a `.json` job or opens a `.nii` volume.
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if extension.eq_ignore_ascii_case("json") {
+            let job: Job = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            job.validate()?;
+            if job.surface()?.is_some() {
+                return Err(Error::InvalidJob(format!(
+                    "surface fractal `{}` has no lattice to compare",
+                    job.fractal
+                )));
+            }
+            Ok(Source::Job(Box::new(job)))
+        } else if extension.eq_ignore_ascii_case("nii") {
+            Ok(Source::Volume(path.to_path_buf()))
+        } else {
+            Err(Error::UnknownFormat(path.to_path_buf()))
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Source::Job(job) => job.display_name(),
+            Source::Volume(path) => path.display().to_string(),
+        }
+    }
+
+    fn dims(&self) -> usize {
+        match self {
+            Source::Job(job) => job.dims,
+            Source::Volume(_) => 3,
+        }
+    }
+
+    /// The factor between the sides of one level's blocks and the next's:
+    /// the base of a rule split evenly and equally along every axis, and 2
+    /// for anything else.
+    fn base(&self) -> Result<usize> {
+        Ok(match self {
+            Source::Job(job) => job.levels()?.map_or(2, |levels| levels.base),
+            Source::Volume(_) => 2,
+        })
+    }
+
+    /// The 3D lattice, the w slice `w` of a 4D job's or the middle one
+    /// when none is given.
+    fn lattice3(&self, w: Option<usize>, cancel: &CancelToken) -> Result<Lattice3> {
+        match self {
+            Source::Volume(path) => Ok(MappedVolume::open(path)?.to_lattice()),
+            Source::Job(job) if job.dims == 3 => job.generate::<3>(cancel),
+            Source::Job(job) => {
+                let lattice = job.generate::<4>(cancel)?;
+                let side = lattice.shape()[3];
+                let w = w.unwrap_or(side / 2);
+                if w >= side {
+                    return Err(Error::InvalidJob(format!(
+                        "slice w={w} is outside the lattice (side {side})"
+                    )));
+                }
+                Ok(lattice.slice_w(w))
+            }
+        }
+    }
+}
+
+/// Compares the lattices of `first` and `second`, and writes the voxel
+/// diff to `voxels` if given. Two 4D jobs are compared whole unless `w`
+/// picks a slice; otherwise 4D jobs are compared at their w slice `w`, the
+/// middle one when none is given, as are voxel diffs, which are 3D.
+pub fn compare(
+    first: &Source,
+    second: &Source,
+    w: Option<usize>,
+    voxels: Option<&Path>,
+    cancel: &CancelToken,
+) -> Result<LatticeDiff> {
+    let base = first.base()?;
+    let names = (first.name(), second.name());
+    if first.dims() == 4 && second.dims() == 4 && w.is_none() && voxels.is_none() {
+        let (Source::Job(a), Source::Job(b)) = (first, second) else {
+            unreachable!("only jobs are 4D")
+        };
+        let (a, b) = (a.generate::<4>(cancel)?, b.generate::<4>(cancel)?);
+        return diff(names, &a, &b, base, cancel);
+    }
+    let a = first.lattice3(w, cancel)?;
+    let b = second.lattice3(w, cancel)?;
+    let mut report = diff(names, &a, &b, base, cancel)?;
+    if let Some(path) = voxels {
+        report.voxels = Some(write_file_atomically(path, |out| {
+            write_voxel_diff(&a, &b, out, cancel)
+        })?);
+    }
+    Ok(report)
+}
+
+/// Compares `first` and `second`, which must have the same shape, with
+/// levels of blocks `base` times larger than the last.
+#[tracing::instrument(name = "diff", skip_all, fields(cells = first.len()))]
+pub fn diff<const D: usize>(
+    (first_name, second_name): (String, String),
+    first: &Lattice<D>,
+    second: &Lattice<D>,
+    base: usize,
+    cancel: &CancelToken,
+) -> Result<LatticeDiff> {
+    let shape = first.shape();
+    if second.shape() != shape {
+        return Err(Error::InvalidJob(format!(
+            "cannot compare lattices of shapes {:?} and {:?}",
+            shape,
+            second.shape()
+        )));
+    }
+    let both = first.iter().filter(|&p| second.get(p)).count();
+    let (first_cells, second_cells) = (first.count(), second.count());
+    let either = first_cells + second_cells - both;
+    let hausdorff = {
+        let (a, b) = (surface(first), surface(second));
+        let to_a = squared_distances(&a, true, cancel)?;
+        let to_b = squared_distances(&b, true, cancel)?;
+        let farthest = |from: &Lattice<D>, to: &[f64]| {
+            from.iter().map(|p| to[from.index(p)]).fold(0.0, f64::max)
+        };
+        farthest(&a, &to_b).max(farthest(&b, &to_a)).sqrt()
+    };
+    let largest = shape.into_iter().max().unwrap_or(1);
+    let mut sides = vec![1];
+    while sides[sides.len() - 1] < largest {
+        sides.push(sides[sides.len() - 1] * base.max(2));
+    }
+    let mut levels = Vec::with_capacity(sides.len());
+    for (level, &side) in sides.iter().rev().enumerate() {
+        cancel.check()?;
+        let (a, b) = (coarsen(first, side), coarsen(second, side));
+        let common = a.iter().filter(|&p| b.get(p)).count();
+        let (first, second) = (a.count(), b.count());
+        levels.push(LevelDiff {
+            level: level as u32,
+            side,
+            first,
+            second,
+            disagreeing: first + second - 2 * common,
+        });
+    }
+    Ok(LatticeDiff {
+        first: first_name,
+        second: second_name,
+        shape: shape.to_vec(),
+        first_cells,
+        second_cells,
+        both,
+        only_first: first_cells - both,
+        only_second: second_cells - both,
+        jaccard: match either {
+            0 => 1.0,
+            either => both as f64 / either as f64,
+        },
+        hausdorff,
+        levels,
+        voxels: None,
+    })
+}
+
+/// The filled cells of `lattice` with a face open to an empty cell or the
+/// outside.
+fn surface<const D: usize>(lattice: &Lattice<D>) -> Lattice<D> {
+    let mut surface = Lattice::new(lattice.shape());
+    for p in lattice.iter() {
+        let exposed = (0..D).any(|axis| {
+            [-1, 1].into_iter().any(|step| {
+                let mut q = p.map(|c| c as i64);
+                q[axis] += step;
+                !lattice.get_signed(q)
+            })
+        });
+        if exposed {
+            surface.set(p, true);
+        }
+    }
+    surface
+}
+
+/// Writes a VTK XML image with one `diff` byte per cell of `first` and
+/// `second`: 0 where neither is filled, 1 where both are, 2 where only
+/// the first is and 3 where only the second is, so thresholding above 1
+/// shows their disagreements.
+pub fn write_voxel_diff(
+    first: &Lattice3,
+    second: &Lattice3,
+    out: &mut impl Write,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let [nx, ny, nz] = first.shape();
+    writeln!(out, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        out,
+        r#"<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" header_type="UInt64">"#
+    )?;
+    let extent = format!("0 {nx} 0 {ny} 0 {nz}");
+    writeln!(
+        out,
+        r#"  <ImageData WholeExtent="{extent}" Origin="0 0 0" Spacing="1 1 1">"#
+    )?;
+    writeln!(out, r#"    <Piece Extent="{extent}">"#)?;
+    writeln!(out, r#"      <CellData Scalars="diff">"#)?;
+    write!(
+        out,
+        r#"        <DataArray type="UInt8" Name="diff" format="ascii">"#
+    )?;
+    for index in 0..first.len() {
+        if index % (nx * ny).max(1) == 0 {
+            cancel.check()?;
+            writeln!(out)?;
+        }
+        let p = first.position(index);
+        let label = match (first.get(p), second.get(p)) {
+            (false, false) => NEITHER,
+            (true, true) => BOTH,
+            (true, false) => ONLY_FIRST,
+            (false, true) => ONLY_SECOND,
+        };
+        write!(out, "{label} ")?;
+    }
+    writeln!(out)?;
+    writeln!(out, "        </DataArray>")?;
+    writeln!(out, "      </CellData>")?;
+    writeln!(out, "    </Piece>")?;
+    writeln!(out, "  </ImageData>")?;
+    writeln!(out, "</VTKFile>")?;
+    Ok(())
+}
+
+impl fmt::Display for LatticeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "diff {} against {}:", self.first, self.second)?;
+        writeln!(f, "  shape: {:?}", self.shape)?;
+        writeln!(
+            f,
+            "  cells: {} against {}, {} in both, {} only in the first, {} only in the second",
+            self.first_cells, self.second_cells, self.both, self.only_first, self.only_second
+        )?;
+        writeln!(f, "  jaccard: {:.6}", self.jaccard)?;
+        writeln!(f, "  hausdorff: {:.3} cells", self.hausdorff)?;
+        writeln!(
+            f,
+            "  {:>5} {:>8} {:>12} {:>12} {:>12}",
+            "level", "side", "first", "second", "disagreeing"
+        )?;
+        for level in &self.levels {
+            writeln!(
+                f,
+                "  {:>5} {:>8} {:>12} {:>12} {:>12}",
+                level.level, level.side, level.first, level.second, level.disagreeing
+            )?;
+        }
+        if let Some(voxels) = &self.voxels {
+            writeln!(f, "  voxel diff: {}", voxels.path.display())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::lattice::{Lattice, Lattice3};
+use crate::mesh::{contour, FaceKind, Mesh};
+
+/// Stand-in for infinity while transforming, so the envelope arithmetic
+/// stays finite; far beyond any squared distance a lattice can hold.
+const FAR: f64 = 1e30;
+
+/// Squared Euclidean distance, in cells, from every cell to the centre of
+/// the nearest cell whose state is `target`, by flat index. Cells that are
+/// `target` themselves get 0; with no such cell anywhere, every distance
+/// is infinite. Outside the lattice counts as neither state.
+///
+/// Exact, in time linear in the number of cells: the lower envelope of
+/// parabolas (Felzenszwalb and Huttenlocher) one axis at a time.
+#[tracing::instrument(name = "distance", skip_all, fields(cells = lattice.len()))]
+pub fn squared_distances<const D: usize>(
+    lattice: &Lattice<D>,
+    target: bool,
+    cancel: &CancelToken,
+) -> Result<Vec<f64>> {
+    let shape = lattice.shape();
+    let mut field: Vec<f64> = (0..lattice.len())
+        .map(|index| {
+            if lattice.get(lattice.position(index)) == target {
+                0.0
+            } else {
+                FAR
+            }
+        })
+        .collect();
+    let longest = shape.iter().copied().max().unwrap_or(0);
+    let mut line = vec![0.0; longest];
+    let mut out = vec![0.0; longest];
+    let mut parabolas = vec![0; longest];
+    let mut bounds = vec![0.0; longest + 1];
+    let mut stride = 1;
+    for &n in shape.iter() {
+        cancel.check()?;
+        for start in 0..lattice.len() {
+            if (start / stride) % n != 0 {
+                continue;
+            }
+            for (i, value) in line[..n].iter_mut().enumerate() {
+                *value = field[start + i * stride];
+            }
+            envelope(&line[..n], &mut out[..n], &mut parabolas, &mut bounds);
+            for (i, &value) in out[..n].iter().enumerate() {
+                field[start + i * stride] = value;
+            }
+        }
+        stride *= n;
+    }
+    for value in &mut field {
+        if *value >= FAR / 2.0 {
+            *value = f64::INFINITY;
+        }
+    }
+    Ok(field)
+}
+
+/// Signed distance, in cells, from every cell centre to the surface
+/// between filled and empty cells, by flat index: positive in empty cells,
+/// negative in filled ones. The surface is taken to lie half a cell short
+/// of the nearest cell of the other state, which is exact along the axes.
+pub fn signed_distances<const D: usize>(
+    lattice: &Lattice<D>,
+    cancel: &CancelToken,
+) -> Result<Vec<f64>> {
+    let to_filled = squared_distances(lattice, true, cancel)?;
+    let to_empty = squared_distances(lattice, false, cancel)?;
+    Ok(to_filled
+        .iter()
+        .zip(&to_empty)
+        .map(|(&filled, &empty)| {
+            if filled == 0.0 {
+                0.5 - empty.sqrt()
+            } else {
+                filled.sqrt() - 0.5
+            }
+        })
+        .collect())
+}
+
+/// A smooth surface `offset` cells outside the lattice's cube surface: a
+/// positive offset dilates the solid, a negative one erodes it, and zero
+/// rounds off its corners. Clipped to the lattice's bounds.
+#[tracing::instrument(name = "offset", skip_all, fields(offset = offset))]
+pub fn offset_surface(
+    lattice: &Lattice3,
+    offset: f64,
+    kind: FaceKind,
+    cancel: &CancelToken,
+) -> Result<Mesh> {
+    let field = signed_distances(lattice, cancel)?;
+    cancel.check()?;
+    Ok(contour(&field, lattice.shape(), offset, kind))
+}
+
+/// One-dimensional squared distance transform of the sampled function `f`.
+fn envelope(f: &[f64], out: &mut [f64], parabolas: &mut [usize], bounds: &mut [f64]) {
+    let n = f.len();
+    if n == 0 {
+        return;
+    }
+    let key = |q: usize| f[q] + (q * q) as f64;
+    let intersection = |q: usize, v: usize| (key(q) - key(v)) / (2 * (q - v)) as f64;
+    let mut k = 0;
+    parabolas[0] = 0;
+    bounds[0] = f64::NEG_INFINITY;
+    bounds[1] = f64::INFINITY;
+    for q in 1..n {
+        // bounds[0] is -inf, so the search always stops by k == 0.
+        let mut s = intersection(q, parabolas[k]);
+        while s <= bounds[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+        k += 1;
+        parabolas[k] = q;
+        bounds[k] = s;
+        bounds[k + 1] = f64::INFINITY;
+    }
+    let mut k = 0;
+    for (q, value) in out.iter_mut().enumerate() {
+        while bounds[k + 1] < q as f64 {
+            k += 1;
+        }
+        let v = parabolas[k];
+        let d = q as f64 - v as f64;
+        *value = d * d + f[v];
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors raised while running jobs and writing their outputs.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    UnknownFractal(String),
+    UnknownFormat(PathBuf),
+    InvalidJob(String),
+    Cancelled,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {e}"),
+            Error::Json(e) => write!(f, "invalid json: {e}"),
+            Error::UnknownFractal(name) => write!(
+                f,
+                "unknown fractal `{name}`; expected one of {}, {}, {}, {}, hybrid, custom, implicit, script, import, infill or lsystem",
+                crate::rule::Rule::NAMES.join(", "),
+                crate::escape::EscapeTime::NAMES.join(", "),
+                crate::sdf::DistanceField::NAMES.join(", "),
+                crate::lsystem::Surface::NAMES.join(", ")
+            ),
+            Error::UnknownFormat(path) => {
+                write!(f, "cannot infer output format of `{}`", path.display())
+            }
+            Error::InvalidJob(reason) => write!(f, "invalid job: {reason}"),
+            Error::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
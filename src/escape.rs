@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::lattice::Lattice;
+
+/// Which quaternion set an [`EscapeTime`] fractal samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EscapeSet {
+    /// Points `q` whose orbit under `q² + c` stays bounded.
+    Julia,
+    /// Points `c` for which the orbit of 0 under `q² + c` stays bounded.
+    Mandelbrot,
+}
+
+/// Where and how finely an escape-time fractal is sampled.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sampling {
+    /// The Julia constant; ignored by the Mandelbrot set.
+    #[serde(default = "default_c")]
+    pub c: [f64; 4],
+    /// Cells along each axis.
+    #[serde(default = "default_resolution")]
+    pub resolution: usize,
+    /// Centre of the sampled box, as `[x, y, z, w]`.
+    #[serde(default)]
+    pub center: [f64; 4],
+    /// Half the width of the sampled box; by default one enclosing the
+    /// fractal.
+    #[serde(default)]
+    pub radius: Option<f64>,
+}
+
+fn default_c() -> [f64; 4] {
+    [-0.2, 0.8, 0.0, 0.0]
+}
+
+fn default_resolution() -> usize {
+    64
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling {
+            c: default_c(),
+            resolution: default_resolution(),
+            center: [0.0; 4],
+            radius: None,
+        }
+    }
+}
+
+impl Sampling {
+    /// The centre of the cell at `p` in a box of half-width `radius`. A 3D
+    /// lattice samples the hyperplane through the centre's w.
+    pub fn cell_centre<const D: usize>(&self, p: [usize; D], radius: f64) -> [f64; 4] {
+        let size = 2.0 * radius / self.resolution as f64;
+        std::array::from_fn(|axis| match p.get(axis) {
+            Some(&i) => self.center[axis] - radius + (i as f64 + 0.5) * size,
+            None => self.center[axis],
+        })
+    }
+}
+
+/// A quaternion Julia or Mandelbrot set, voxelized into the same lattices
+/// subdivision rules produce so slicing, meshing and export apply as is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscapeTime {
+    pub set: EscapeSet,
+    pub sampling: Sampling,
+}
+
+/// Squared radius beyond which an orbit is known to escape.
+const BAILOUT: f64 = 4.0;
+
+impl EscapeTime {
+    /// Names accepted by [`EscapeTime::by_name`].
+    pub const NAMES: &'static [&'static str] = &["julia", "mandelbrot"];
+
+    pub fn by_name(name: &str, sampling: Sampling) -> Option<Self> {
+        let set = match name {
+            "julia" => EscapeSet::Julia,
+            "mandelbrot" => EscapeSet::Mandelbrot,
+            _ => return None,
+        };
+        Some(EscapeTime { set, sampling })
+    }
+
+    /// Whether the orbit starting from `point` is still bounded after
+    /// `iterations` steps.
+    pub fn contains(&self, point: [f64; 4], iterations: u32) -> bool {
+        let (mut q, c) = match self.set {
+            EscapeSet::Julia => (point, self.sampling.c),
+            EscapeSet::Mandelbrot => ([0.0; 4], point),
+        };
+        for _ in 0..iterations {
+            let [a, b, cc, d] = q;
+            q = [
+                a * a - b * b - cc * cc - d * d + c[0],
+                2.0 * a * b + c[1],
+                2.0 * a * cc + c[2],
+                2.0 * a * d + c[3],
+            ];
+            if q.iter().map(|x| x * x).sum::<f64>() > BAILOUT {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Half the width of the sampled box, 1.5 unless set.
+    pub fn radius(&self) -> f64 {
+        self.sampling.radius.unwrap_or(1.5)
+    }
+
+    /// Samples every cell centre with `iterations` steps per orbit.
+    #[tracing::instrument(name = "generate", skip_all, fields(set = ?self.set, iterations = iterations))]
+    pub fn voxelize<const D: usize>(
+        &self,
+        iterations: u32,
+        cancel: &CancelToken,
+    ) -> Result<Lattice<D>> {
+        Lattice::from_fn([self.sampling.resolution; D], cancel, |p| {
+            self.contains(self.sampling.cell_centre(p, self.radius()), iterations)
+        })
+    }
+}
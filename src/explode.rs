@@ -0,0 +1,149 @@
+//! Exploded views: each level-`k` block of a rule fractal pushed out from
+//! the centre, so the sponge's parts stand apart as in the familiar
+//! figure.
+//!
+//! A block at level `k` is one of the `base^k` pieces each axis is cut
+//! into by the first `k` subdivisions. Its cells keep their places within
+//! it while its centre moves to `factor` times further from the fractal's,
+//! plus where it was: 0 leaves the fractal whole and 1 doubles every
+//! block's distance from the centre. Faces between filled cells of two
+//! blocks, hidden in the whole fractal, are kept since the blocks part.
+//!
+//! With several frames the factor rises from 0 to its full value, one
+//! mesh per frame, for an animation of the explosion.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::lattice::Lattice3;
+use crate::mesh::{Face, FaceKind, Mesh, Polygons};
+
+/// How far a rule fractal's blocks are pushed apart.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Explode {
+    /// The level whose blocks move apart, 1 for the coarsest.
+    pub level: u32,
+    /// How far each block moves, in multiples of its distance from the
+    /// centre.
+    pub factor: f64,
+    /// Meshes written, with the factor rising evenly from 0 in the first
+    /// to `factor` in the last; one writes only the full explosion.
+    #[serde(default = "default_frames")]
+    pub frames: u32,
+}
+
+fn default_frames() -> u32 {
+    1
+}
+
+impl Explode {
+    /// Checks the factor and frame count, and the level against the
+    /// fractal's `depth`.
+    pub fn validate(&self, depth: u32) -> Result<()> {
+        if !(1..=depth).contains(&self.level) {
+            return Err(Error::InvalidJob(format!(
+                "explode level {} is outside 1 to the depth {depth}",
+                self.level
+            )));
+        }
+        if !(self.factor >= 0.0 && self.factor.is_finite()) {
+            return Err(Error::InvalidJob(
+                "explode factor must be finite and not negative".into(),
+            ));
+        }
+        if self.frames == 0 {
+            return Err(Error::InvalidJob("an explosion needs a frame".into()));
+        }
+        Ok(())
+    }
+
+    /// The factor of frame `frame`.
+    pub fn factor_at(&self, frame: u32) -> f64 {
+        match self.frames {
+            1 => self.factor,
+            frames => self.factor * frame as f64 / (frames - 1) as f64,
+        }
+    }
+
+    /// The surface of `lattice`, the depth-`depth` fractal of a rule split
+    /// evenly along axes of `bases`, with its level blocks pushed apart
+    /// by `factor`, with faces of `kind`. Vertices are in cells, as for the
+    /// whole lattice.
+    pub fn mesh(
+        &self,
+        lattice: &Lattice3,
+        bases: &[u32],
+        depth: u32,
+        factor: f64,
+        kind: FaceKind,
+    ) -> Mesh {
+        let shape = lattice.shape();
+        let block: [usize; 3] =
+            std::array::from_fn(|axis| (bases[axis] as usize).pow(depth - self.level));
+        let offset = |cell: [usize; 3]| -> [f64; 3] {
+            std::array::from_fn(|axis| {
+                let centre = (cell[axis] / block[axis]) as f64 + 0.5;
+                (centre * block[axis] as f64 - shape[axis] as f64 / 2.0) * factor
+            })
+        };
+        let mut vertices = Vec::new();
+        let mut lookup = HashMap::new();
+        let mut quads = Vec::new();
+        for cell in lattice.iter() {
+            let which: [usize; 3] = std::array::from_fn(|axis| cell[axis] / block[axis]);
+            let shift = offset(cell);
+            for axis in 0..3 {
+                for positive in [false, true] {
+                    let mut q = cell.map(|c| c as i64);
+                    q[axis] += if positive { 1 } else { -1 };
+                    let apart = q[axis] < 0 || q[axis] as usize / block[axis] != which[axis];
+                    if lattice.get_signed(q) && !apart {
+                        continue;
+                    }
+                    let face = Face {
+                        cell,
+                        axis,
+                        positive,
+                    };
+                    quads.push(face.lattice_corners().map(|corner| {
+                        *lookup.entry((which, corner)).or_insert_with(|| {
+                            vertices.push(std::array::from_fn(|a| corner[a] as f64 + shift[a]));
+                            (vertices.len() - 1) as u32
+                        })
+                    }));
+                }
+            }
+        }
+        let mut mesh = Mesh {
+            vertices,
+            faces: Polygons::Quads(quads),
+        };
+        if kind == FaceKind::Triangles {
+            mesh.faces = Polygons::Triangles(mesh.triangles());
+        }
+        mesh
+    }
+}
+
+impl FromStr for Explode {
+    type Err = String;
+
+    /// `level:factor[:frames]`, e.g. `1:0.5` or `2:1:60`.
+    fn from_str(text: &str) -> std::result::Result<Self, String> {
+        let parts: Vec<&str> = text.split(':').collect();
+        let (level, factor, frames) = match parts.as_slice() {
+            [level, factor] => (level, factor, "1"),
+            [level, factor, frames] => (level, factor, *frames),
+            _ => return Err("expected level:factor[:frames]".into()),
+        };
+        Ok(Explode {
+            level: level.trim().parse().map_err(|e| format!("level: {e}"))?,
+            factor: factor.trim().parse().map_err(|e| format!("factor: {e}"))?,
+            frames: frames.trim().parse().map_err(|e| format!("frames: {e}"))?,
+        })
+    }
+}
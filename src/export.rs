@@ -0,0 +1,1410 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::cancel::CancelToken;
+use crate::checkpoint::Checkpoint;
+use crate::color::{Coloring, Colors, FaceColors};
+use crate::distance::{offset_surface, signed_distances};
+use crate::error::{Error, Result};
+use crate::image::{layer_path, write_png, write_tiff, ImageStack, ImageValues};
+use crate::instances;
+use crate::lattice::{Boundary, Lattice3};
+use crate::materials::Materials;
+use crate::mesh::{
+    build_indexed_mesh_as, cross, dot, normalize, repair, simplify, sub, validate, FaceKind, Mesh,
+    Normals, Polygons, Scalar, Simplify,
+};
+use crate::plugin::{Exporter, Exporters};
+use crate::ranks::{RankReport, Ranks};
+use crate::schematic::{piece_path, Schematic};
+use crate::seekable::{self, SeekableWriter};
+use crate::store::{self, Sink};
+use crate::texture::Texture;
+use crate::tiling::Tiling;
+use crate::transform::{Affine, Axis, Transform};
+use crate::zarr::{chunk_distances, chunk_key, Zarr};
+
+pub mod meshopt;
+mod streaming;
+
+pub use streaming::{export_streaming, slab_layers, streaming_bytes};
+
+/// Output file formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Wavefront OBJ with one quad per exposed cell face.
+    Obj,
+    /// Binary STL with two triangles per exposed cell face.
+    Stl,
+    /// Binary glTF with the surface stored once and placed by one node per
+    /// tile.
+    Glb,
+    /// Legacy VTK structured points holding the signed distance field of
+    /// the lattice rather than a mesh, for volume tools such as ParaView.
+    Vtk,
+    /// NIfTI-1 volume of the cells' occupancy or signed distance, with the
+    /// job's transforms as its voxel-to-world matrix, for medical imaging
+    /// tools.
+    Nifti,
+    /// Sponge schematic of Minecraft blocks, split into several files when
+    /// the lattice is too large for one.
+    Schem,
+    /// DirectDraw Surface 3D texture of the cells' occupancy or signed
+    /// distance, for raymarching in shaders.
+    Dds,
+    /// Zarr v3 array of the cells' occupancy or signed distance: a
+    /// directory of compressed chunks, for cloud-native analysis.
+    Zarr,
+    /// A binary list of cube instances, a position and size per filled
+    /// cell, for instanced rendering; see [`crate::instances`].
+    Instances,
+    /// A stack of grayscale PNG images, one file per layer of cells.
+    Png,
+    /// A stack of grayscale TIFF images, one file per layer of cells.
+    Tiff,
+}
+
+impl Format {
+    /// Infers the format from a path's extension, or for a compressed
+    /// `.zst` path the extension before it.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        seekable::inner_path(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(Format::from_extension)
+            .ok_or_else(|| Error::UnknownFormat(path.to_path_buf()))
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "obj" => Some(Format::Obj),
+            "stl" => Some(Format::Stl),
+            "glb" => Some(Format::Glb),
+            "vtk" => Some(Format::Vtk),
+            "nii" => Some(Format::Nifti),
+            "schem" => Some(Format::Schem),
+            "dds" => Some(Format::Dds),
+            "zarr" => Some(Format::Zarr),
+            "inst" => Some(Format::Instances),
+            "png" => Some(Format::Png),
+            "tif" | "tiff" => Some(Format::Tiff),
+            _ => None,
+        }
+    }
+
+    /// MIME type used when serving the format over HTTP.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Obj => "model/obj",
+            Format::Stl => "model/stl",
+            Format::Glb => "model/gltf-binary",
+            Format::Vtk | Format::Nifti | Format::Schem | Format::Zarr | Format::Instances => {
+                "application/octet-stream"
+            }
+            Format::Dds => "image/vnd-ms.dds",
+            Format::Png => "image/png",
+            Format::Tiff => "image/tiff",
+        }
+    }
+
+    /// Whether the format writes a stack of files through
+    /// [`export_stack`] rather than one file.
+    pub fn is_image_stack(self) -> bool {
+        matches!(self, Format::Png | Format::Tiff)
+    }
+
+    /// Whether the format holds a value per cell rather than a mesh.
+    pub fn is_volume(self) -> bool {
+        matches!(
+            self,
+            Format::Vtk
+                | Format::Nifti
+                | Format::Schem
+                | Format::Dds
+                | Format::Zarr
+                | Format::Instances
+        ) || self.is_image_stack()
+    }
+}
+
+/// Settings shared by every format.
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    /// Applied to the mesh, after tiling, before writing.
+    pub transform: Affine,
+    /// Copies of the mesh to write. glTF instances a single mesh; other
+    /// formats get the copies merged into one.
+    pub tiling: Tiling,
+    /// Normals written to formats that carry them. STL always stores its
+    /// own per-triangle normals.
+    pub normals: Normals,
+    /// Reduce the triangle count before writing.
+    pub simplify: Simplify,
+    /// Run [`repair`] on the mesh before writing and log what the final
+    /// validation found.
+    pub repair: bool,
+    /// How faces on the lattice's outer walls are culled.
+    pub boundary: Boundary,
+    /// Write the smooth surface this many cells outside the cube surface
+    /// instead of the cube faces; see [`offset_surface`].
+    pub offset: Option<f64>,
+    /// How image stacks slice the lattice, and what they, NIfTI volumes
+    /// and 3D textures hold.
+    pub images: ImageStack,
+    /// The block and piece size of Minecraft schematics.
+    pub schematic: Schematic,
+    /// Axis, unit and tangent conventions of glTF files.
+    pub gltf: Gltf,
+    /// Compression of 3D textures.
+    pub texture: Texture,
+    /// Chunking of Zarr arrays.
+    pub zarr: Zarr,
+    /// How `.glb` and `.inst` outputs store coordinates.
+    pub precision: Precision,
+    /// The precision meshes are built and transformed in.
+    pub coordinates: Coordinates,
+    /// Scale each object into the unit cube before the transform; see
+    /// [`ExportOptions::transform_for`].
+    pub normalize: bool,
+    /// Where outputs sit relative to the origin.
+    pub domain: Domain,
+    /// Exporters for extensions no built-in format claims.
+    pub exporters: Exporters,
+    /// Colour the vertices of `.glb` outputs of lattices by their cells;
+    /// see [`Coloring::mesh`].
+    pub colors: Option<Colors>,
+    /// Colour the vertices of `.glb` outputs of lattices by their cells'
+    /// materials, in place of `colors`.
+    pub materials: Option<Materials>,
+    /// Records the Zarr chunks written, and skips those an earlier run of
+    /// the job completed.
+    pub checkpoint: Checkpoint,
+}
+
+/// How `.glb` and `.inst` outputs store coordinates; the smaller
+/// encodings cut files for web delivery at a bounded error. Other formats
+/// keep their own precision.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// 32-bit floats.
+    #[default]
+    F32,
+    /// 16-bit floats, which GPUs read natively. They keep 11 significant
+    /// bits, so a value is off by at most 1/2048 of its magnitude: a
+    /// hundredth of a cell at 20 cells from the origin, a whole cell past
+    /// 2048. Instance lists only, as glTF has no half-float positions.
+    F16,
+    /// 16-bit integers spread over the bounding box and scaled back by the
+    /// reader, off by at most 1/131070 of the box's longest side, whatever
+    /// the distance from the origin. glTF files use the
+    /// `KHR_mesh_quantization` extension, may snap to a coarser grid with
+    /// [`Gltf::position_bits`], and store normals and tangents as integers
+    /// too.
+    Q16,
+}
+
+impl ExportOptions {
+    /// The transform of an object `extent` across in lattice units, from
+    /// the origin: [`ExportOptions::transform`], after moving the object's
+    /// centre to the origin in the [`Domain::Centered`] domain, and after
+    /// scaling by the longest side into `[0, 1]^3`, or `[-1/2, 1/2]^3`
+    /// centred, if `normalize` asks, so a depth-2 and a depth-5 sponge
+    /// overlay. Shorter sides are centred in the cube. Tiled copies are
+    /// placed before it, so each copy is a unit across.
+    pub fn transform_for(&self, extent: [f64; 3]) -> Affine {
+        let longest = extent.into_iter().fold(0.0, f64::max);
+        let normalize = self.normalize && longest > 0.0 && longest.is_finite();
+        let mut transforms = Vec::new();
+        match self.domain {
+            Domain::Centered => {
+                transforms.push(Transform::Translate(extent.map(|side| -side / 2.0)))
+            }
+            Domain::Positive if normalize => transforms.push(Transform::Translate(
+                extent.map(|side| (longest - side) / 2.0),
+            )),
+            Domain::Positive => {}
+        }
+        if normalize {
+            transforms.push(Transform::Scale(1.0 / longest));
+        }
+        if transforms.is_empty() {
+            self.transform
+        } else {
+            self.transform.then(&Affine::from_transforms(&transforms))
+        }
+    }
+}
+
+/// Where a lattice's cells sit in output coordinates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Domain {
+    /// From the origin to the lattice's side along each axis, cell `i`
+    /// from `i` to `i + 1`.
+    #[default]
+    Positive,
+    /// From `-side / 2` to `side / 2`, centred on the origin as renderers
+    /// and physics engines expect. The cell at centred index `c` (see
+    /// [`Rule::is_solid_centered`](crate::rule::Rule::is_solid_centered))
+    /// spans `c - 1/2` to `c + 1/2` along odd sides, so a base-3 sponge's
+    /// middle cell surrounds the origin, and `c` to `c + 1` along even ones.
+    Centered,
+}
+
+/// The precision of vertex coordinates while a lattice's surface is built,
+/// tiled and transformed, before the format's own precision applies.
+///
+/// `f32` halves the memory of vertices for meshes that are only viewed,
+/// and changes nothing for OBJ, STL and glTF files of untransformed
+/// lattices, whose integer corners it holds exactly. Transformed vertices
+/// are rounded to `f32` after each placement, off by about 2^-24 of their
+/// magnitude. Simplification, repair and offset surfaces still work in
+/// `f64`, on a copy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Coordinates {
+    #[default]
+    F64,
+    F32,
+}
+
+/// Conventions for `.glb` outputs, so game engines import them without
+/// turning or rescaling.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Gltf {
+    /// The axis that points up in the file. glTF, and the Unity and Unreal
+    /// importers with it, expect `y`; `z` keeps the job's own axes.
+    #[serde(default)]
+    pub up: Up,
+    /// Metres per output unit, since glTF measures in metres: 0.001 when
+    /// the job's transforms produce millimetres.
+    #[serde(default = "default_unit_scale")]
+    pub unit_scale: f64,
+    /// Write a tangent per vertex, for normal-mapped materials. Needs
+    /// smooth normals.
+    #[serde(default)]
+    pub tangents: bool,
+    /// Scale the whole file so its longest side is this many metres,
+    /// centred over the origin and standing on the floor, as XR viewers
+    /// place models; `unit_scale` is then ignored.
+    #[serde(default)]
+    pub fit: Option<f64>,
+    /// Compress the geometry with `EXT_meshopt_compression`, which
+    /// three.js, Babylon.js and other web viewers decode as they load.
+    /// Quantized positions and normals, with few bits, compress furthest.
+    #[serde(default)]
+    pub meshopt: bool,
+    /// Bits of the grid `q16` positions snap to, 1 to 16. Every bit fewer
+    /// doubles the error bound and leaves meshopt smaller deltas to store.
+    #[serde(default = "default_bits")]
+    pub position_bits: u32,
+    /// Bits of `q16` normals and tangents: 16, or 8 for a byte each, which
+    /// turns them by up to half a degree.
+    #[serde(default = "default_bits")]
+    pub normal_bits: u32,
+}
+
+/// The up axis of a glTF file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Up {
+    Y,
+    #[default]
+    Z,
+}
+
+fn default_unit_scale() -> f64 {
+    1.0
+}
+
+fn default_bits() -> u32 {
+    16
+}
+
+impl Default for Gltf {
+    fn default() -> Self {
+        Gltf {
+            up: Up::default(),
+            unit_scale: default_unit_scale(),
+            tangents: false,
+            fit: None,
+            meshopt: false,
+            position_bits: default_bits(),
+            normal_bits: default_bits(),
+        }
+    }
+}
+
+impl Gltf {
+    /// Takes the job's z-up output units to the file's axes and metres.
+    /// `bounds` are the lowest and highest corners of everything written,
+    /// in output units, which `fit` scales and places.
+    pub fn root(&self, bounds: ([f64; 3], [f64; 3])) -> Affine {
+        let (min, max) = bounds;
+        let longest = (0..3).map(|a| max[a] - min[a]).fold(0.0, f64::max);
+        let mut transforms = match self.fit {
+            // An empty mesh has no box to fit.
+            Some(fit) if longest > 0.0 && longest.is_finite() => vec![
+                Transform::Translate([-(min[0] + max[0]) / 2.0, -(min[1] + max[1]) / 2.0, -min[2]]),
+                Transform::Scale(fit / longest),
+            ],
+            Some(_) => vec![],
+            None => vec![Transform::Scale(self.unit_scale)],
+        };
+        if self.up == Up::Y {
+            transforms.push(Transform::Rotate {
+                axis: Axis::X,
+                degrees: -90.0,
+            });
+        }
+        Affine::from_transforms(&transforms)
+    }
+}
+
+/// A file written by an export.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artifact {
+    pub path: PathBuf,
+    pub bytes: u64,
+    /// Hex-encoded SHA-256 of the file contents.
+    pub sha256: String,
+}
+
+/// Writes the culled surface of `lattice` to `path` and describes the file
+/// written. A path no built-in format claims goes to its exporter in
+/// `options`.
+///
+/// The file is written under a temporary name and only renamed into place
+/// once complete, so a cancelled or failed export never leaves a truncated
+/// file behind.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn export(
+    lattice: &Lattice3,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Artifact> {
+    if let Some(exporter) = options.exporters.find(path) {
+        return write_compressed(path, |out| {
+            write_with(exporter, lattice, out, options, cancel)
+        });
+    }
+    write_atomically(path, |format, mut out| {
+        write(lattice, format, &mut out, options, cancel)
+    })
+}
+
+/// Writes `lattice` as a stack of PNG or TIFF images, one per layer of
+/// cells along the stack's axis, numbered as [`layer_path`] describes. Each
+/// image is written atomically; tiling and transforms are ignored.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn export_stack(
+    lattice: &Lattice3,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Vec<Artifact>> {
+    let layers = options.images.layers(lattice, cancel)?;
+    let count = layers.len();
+    layers
+        .enumerate()
+        .map(|(i, layer)| {
+            cancel.check()?;
+            write_atomically(
+                &layer_path(path, i, count),
+                |format, mut out| match format {
+                    Format::Png => write_png(&layer, &mut out),
+                    Format::Tiff => write_tiff(&layer, &mut out),
+                    _ => Err(Error::InvalidJob(format!(
+                        "`{}` is not an image stack",
+                        path.display()
+                    ))),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Writes `lattice` as Sponge schematics, one per piece of at most the
+/// schematic's size, each written atomically; see
+/// [`crate::schematic::piece_path`] for their names. Tiling and transforms
+/// are ignored.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn export_schematic(
+    lattice: &Lattice3,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Vec<Artifact>> {
+    let schematic = &options.schematic;
+    let pieces = schematic.pieces(lattice.shape())?;
+    pieces
+        .iter()
+        .map(|piece| {
+            write_atomically(&piece_path(path, piece, pieces.len()), |_, mut out| {
+                schematic.write(lattice, piece, &mut out, cancel)
+            })
+        })
+        .collect()
+}
+
+/// Writes `lattice` as a Zarr v3 array in the directory `path`: its
+/// `zarr.json`, then every chunk, each written atomically and recorded in
+/// the options' checkpoint. Tiling and transforms are ignored.
+#[tracing::instrument(skip_all, fields(path = %path.display()))]
+pub fn export_zarr(
+    lattice: &Lattice3,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Vec<Artifact>> {
+    let zarr = &options.zarr;
+    zarr.validate()?;
+    let values = options.images.values;
+    let metadata = zarr.metadata(lattice.shape(), values);
+    let distances = chunk_distances(lattice, values, cancel)?;
+    let mut artifacts = vec![write_file_atomically(&path.join("zarr.json"), |out| {
+        Ok(out.write_all(metadata.as_bytes())?)
+    })?];
+    for chunk in zarr.chunks(lattice.shape()) {
+        let path = path.join(chunk_key(chunk));
+        artifacts.extend(options.checkpoint.unit(&path.to_string_lossy(), || {
+            Ok(vec![write_file_atomically(&path, |out| {
+                zarr.write_chunk(lattice, [0; 3], distances.as_deref(), chunk, out, cancel)
+            })?])
+        })?);
+    }
+    Ok(artifacts)
+}
+
+/// Like [`export_zarr`], for the part of an array of `shape` cells from z
+/// `z0` on that one of `ranks` writes: its rows of chunks, the array's
+/// `zarr.json` from rank 0, and the rank's counts in `ranks/`; see
+/// [`crate::ranks`]. Only occupancy is stored, since distances need the
+/// whole lattice.
+#[tracing::instrument(skip_all, fields(path = %path.display(), rank = ranks.rank))]
+pub fn export_zarr_part(
+    part: &Lattice3,
+    shape: [usize; 3],
+    z0: usize,
+    ranks: Ranks,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Vec<Artifact>> {
+    let zarr = &options.zarr;
+    zarr.validate()?;
+    if !z0.is_multiple_of(zarr.chunk) {
+        return Err(Error::InvalidJob(format!(
+            "a rank's part of `{}` must start on a chunk boundary",
+            path.display()
+        )));
+    }
+    let mut artifacts = Vec::new();
+    if ranks.rank == 0 {
+        let metadata = zarr.metadata(shape, ImageValues::Occupancy);
+        artifacts.push(write_file_atomically(&path.join("zarr.json"), |out| {
+            Ok(out.write_all(metadata.as_bytes())?)
+        })?);
+    }
+    let rows = z0 / zarr.chunk..(z0 + part.shape()[2]).div_ceil(zarr.chunk);
+    let chunks: Vec<_> = zarr
+        .chunks(shape)
+        .into_iter()
+        .filter(|chunk| rows.contains(&chunk[0]))
+        .collect();
+    for &chunk in &chunks {
+        let path = path.join(chunk_key(chunk));
+        artifacts.extend(options.checkpoint.unit(&path.to_string_lossy(), || {
+            Ok(vec![write_file_atomically(&path, |out| {
+                zarr.write_chunk(part, [0, 0, z0], None, chunk, out, cancel)
+            })?])
+        })?);
+    }
+    let report = RankReport {
+        rank: ranks.rank,
+        size: ranks.size,
+        cells: part.count() as u64,
+        chunks: chunks.len(),
+    };
+    let json = serde_json::to_string_pretty(&report)?;
+    artifacts.push(write_file_atomically(
+        &path.join("ranks").join(format!("{}.json", ranks.rank)),
+        |out| Ok(out.write_all(json.as_bytes())?),
+    )?);
+    Ok(artifacts)
+}
+
+/// Like [`export`], for a mesh built some other way such as a surface
+/// fractal. `extent` is the object's size, which tiling spaces copies by.
+pub fn export_mesh(
+    mesh: &Mesh,
+    extent: [f64; 3],
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Artifact> {
+    if let Some(exporter) = options.exporters.find(path) {
+        return write_compressed(path, |out| {
+            write_mesh_with(exporter, mesh.clone(), extent, out, options, cancel)
+        });
+    }
+    write_atomically(path, |format, mut out| {
+        write_mesh(mesh.clone(), extent, format, &mut out, options, cancel)
+    })
+}
+
+/// Writes the file or object through `write`, publishing it only once
+/// complete; see [`store::create`]. A `.zst` path is compressed in the
+/// seekable format; see [`crate::seekable`].
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(Format, &mut dyn Write) -> Result<()>,
+) -> Result<Artifact> {
+    let format = Format::from_path(path)?;
+    write_compressed(path, |out| write(format, out))
+}
+
+/// Like [`write_atomically`], for formats outside [`Format`].
+fn write_compressed(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<Artifact> {
+    write_file_atomically(path, |out| {
+        if seekable::is_compressed(path) {
+            let mut compressed = SeekableWriter::new(out);
+            write(&mut compressed)?;
+            compressed.finish()?;
+            Ok(())
+        } else {
+            write(out)
+        }
+    })
+}
+
+/// Like [`write_atomically`], for files whose format is not in their name.
+pub(crate) fn write_file_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut HashingWriter<Box<dyn Sink>>) -> Result<()>,
+) -> Result<Artifact> {
+    let mut out = HashingWriter::new(store::create(path)?);
+    if let Err(e) = write(&mut out) {
+        out.inner.abort();
+        return Err(e);
+    }
+    let artifact = Artifact {
+        path: path.to_path_buf(),
+        bytes: out.bytes,
+        sha256: out.hex_digest(),
+    };
+    out.inner.finish()?;
+    Ok(artifact)
+}
+
+/// Counts and hashes everything written through it.
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        HashingWriter {
+            inner,
+            hasher: Sha256::new(),
+            bytes: 0,
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Hex-encoded SHA-256 of the bytes written so far.
+    pub fn hex_digest(&self) -> String {
+        hex(&self.hasher.clone().finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Lower-case hex encoding.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Writes the culled surface of `lattice` in `format` to any writer, or
+/// its cells for the volume formats.
+pub fn write(
+    lattice: &Lattice3,
+    format: Format,
+    out: &mut impl Write,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let kind = match format {
+        Format::Obj => FaceKind::Quads,
+        Format::Stl | Format::Glb => FaceKind::Triangles,
+        Format::Vtk => return write_vtk(lattice, out, options, cancel),
+        Format::Nifti => return write_nifti(lattice, out, options, cancel),
+        Format::Dds => {
+            return options
+                .texture
+                .write_dds(lattice, options.images.values, out, cancel)
+        }
+        Format::Instances => return instances::write(lattice, options, out, cancel),
+        Format::Schem => {
+            let pieces = options.schematic.pieces(lattice.shape())?;
+            return match pieces.as_slice() {
+                [piece] => options.schematic.write(lattice, piece, out, cancel),
+                _ => Err(Error::InvalidJob(
+                    "the lattice needs several schematics; see export_schematic".into(),
+                )),
+            };
+        }
+        Format::Zarr => {
+            return Err(Error::InvalidJob(
+                "a Zarr array spans a directory of chunks; see export_zarr".into(),
+            ))
+        }
+        Format::Png | Format::Tiff => {
+            return Err(Error::InvalidJob(
+                "an image stack spans one file per layer; see export_stack".into(),
+            ))
+        }
+    };
+    let extent = lattice.shape().map(|side| side as f64);
+    let coloring = match (&options.materials, &options.colors, format) {
+        (None, Some(colors), Format::Glb) => {
+            Some(Coloring::new(colors.clone(), lattice, options.boundary))
+        }
+        _ => None,
+    };
+    let faces = match (&options.materials, &coloring, format) {
+        (Some(materials), _, Format::Glb) => Some(materials as &dyn FaceColors),
+        (_, Some(coloring), _) => Some(coloring as &dyn FaceColors),
+        _ => None,
+    };
+    let coloring = faces.map(|faces| (faces, lattice.shape()));
+    match options.coordinates {
+        Coordinates::F64 => {
+            let mesh: Mesh = surface(lattice, kind, options, cancel)?;
+            write_colored_mesh(mesh, extent, format, out, options, coloring, cancel)
+        }
+        Coordinates::F32 => {
+            let mesh: Mesh<f32> = surface(lattice, kind, options, cancel)?;
+            write_colored_mesh(mesh, extent, format, out, options, coloring, cancel)
+        }
+    }
+}
+
+/// The culled surface of `lattice`, or the offset surface `options` asks
+/// for.
+fn surface<S: Scalar>(
+    lattice: &Lattice3,
+    kind: FaceKind,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Mesh<S>> {
+    Ok(match options.offset {
+        Some(offset) => offset_surface(lattice, offset, kind, cancel)?.cast(),
+        None => build_indexed_mesh_as(lattice, kind, options.boundary),
+    })
+}
+
+/// Like [`write`], through `exporter`: the cells of `lattice` if it writes
+/// lattices, otherwise its surface as for STL.
+fn write_with(
+    exporter: &dyn Exporter,
+    lattice: &Lattice3,
+    out: &mut dyn Write,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<()> {
+    if exporter.writes_lattices() {
+        return exporter.write_lattice(lattice, out, cancel);
+    }
+    let mesh: Mesh = surface(lattice, FaceKind::Triangles, options, cancel)?;
+    let extent = lattice.shape().map(|side| side as f64);
+    write_mesh_with(exporter, mesh, extent, out, options, cancel)
+}
+
+/// Like [`write_mesh`], through `exporter`.
+fn write_mesh_with(
+    exporter: &dyn Exporter,
+    mut mesh: Mesh,
+    extent: [f64; 3],
+    out: &mut dyn Write,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<()> {
+    mesh = simplify_and_repair(mesh, options);
+    if !options.tiling.is_single() {
+        mesh = mesh.tile(&options.tiling.instances(extent));
+    }
+    mesh.transform(&options.transform_for(extent));
+    exporter.write_mesh(&mesh, out, cancel)
+}
+
+/// Writes an already built mesh in `format`, applying the mesh stages of
+/// `options`: simplification, repair, tiling and the transform.
+pub fn write_mesh<S: Scalar>(
+    mesh: Mesh<S>,
+    extent: [f64; 3],
+    format: Format,
+    out: &mut impl Write,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<()> {
+    write_colored_mesh(mesh, extent, format, out, options, None, cancel)
+}
+
+/// Like [`write_mesh`], colouring the vertices of `.glb` outputs with
+/// `coloring`'s face colours, of the lattice of the shape given that the
+/// mesh is the surface of.
+fn write_colored_mesh<S: Scalar>(
+    mut mesh: Mesh<S>,
+    extent: [f64; 3],
+    format: Format,
+    out: &mut impl Write,
+    options: &ExportOptions,
+    coloring: Option<(&dyn FaceColors, [usize; 3])>,
+    cancel: &CancelToken,
+) -> Result<()> {
+    if format.is_volume() {
+        return Err(Error::InvalidJob(
+            "a volume or image stack needs a lattice, not a mesh".into(),
+        ));
+    }
+    mesh = simplify_and_repair(mesh, options);
+    let placements = options.tiling.instances(extent);
+    let transform = options.transform_for(extent);
+    if format == Format::Glb {
+        let nodes: Vec<Affine> = placements
+            .iter()
+            .map(|placement| transform.then(placement))
+            .collect();
+        let root = options.gltf.root(placed_bounds(&mesh, &nodes));
+        let nodes: Vec<Affine> = nodes.iter().map(|node| root.then(node)).collect();
+        let (mesh, colors) = match coloring {
+            Some((faces, shape)) => {
+                let (mesh, colors) = faces.mesh(&mesh, shape);
+                (mesh, Some(colors))
+            }
+            None => (mesh, None),
+        };
+        return write_glb(&mesh, colors.as_deref(), &nodes, options, out, cancel);
+    }
+    if !options.tiling.is_single() {
+        mesh = mesh.tile(&placements);
+    }
+    mesh.transform(&transform);
+    match format {
+        Format::Obj => write_obj(&mesh, options.normals, out, cancel),
+        Format::Stl => write_stl(&mesh, out, cancel),
+        Format::Glb
+        | Format::Vtk
+        | Format::Nifti
+        | Format::Schem
+        | Format::Dds
+        | Format::Zarr
+        | Format::Instances
+        | Format::Png
+        | Format::Tiff => {
+            unreachable!("handled above")
+        }
+    }
+}
+
+/// Applies the simplification and repair `options` ask for, in `f64`.
+fn simplify_and_repair<S: Scalar>(mesh: Mesh<S>, options: &ExportOptions) -> Mesh<S> {
+    if options.simplify == Simplify::default() && !options.repair {
+        return mesh;
+    }
+    let mut mesh: Mesh = mesh.cast();
+    if options.simplify != Simplify::default() {
+        let report = simplify(&mut mesh, &options.simplify);
+        tracing::info!(?report, "mesh simplified");
+    }
+    if options.repair {
+        let repaired = repair(&mut mesh);
+        let report = validate(&mesh);
+        tracing::info!(
+            ?repaired,
+            watertight = report.is_watertight(),
+            ?report,
+            "mesh repaired"
+        );
+    }
+    mesh.cast()
+}
+
+/// The box around every copy of `mesh` placed by `nodes`, from the
+/// corners of the mesh's own box.
+fn placed_bounds<S: Scalar>(mesh: &Mesh<S>, nodes: &[Affine]) -> ([f64; 3], [f64; 3]) {
+    let mut low = [f64::INFINITY; 3];
+    let mut high = [f64::NEG_INFINITY; 3];
+    for v in &mesh.vertices {
+        for a in 0..3 {
+            low[a] = low[a].min(v[a].to_f64());
+            high[a] = high[a].max(v[a].to_f64());
+        }
+    }
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for node in nodes {
+        for corner in 0..8 {
+            let p = node.apply(std::array::from_fn(|a| match corner >> a & 1 {
+                0 => low[a],
+                _ => high[a],
+            }));
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+    }
+    (min, max)
+}
+
+/// How many vertices or faces are written between cancellation checks.
+const CANCEL_INTERVAL: usize = 4096;
+
+/// Writes an indexed mesh as Wavefront OBJ, keeping quads as quads.
+///
+/// Face normals are deduplicated, so cube surfaces need only six `vn`
+/// lines; smooth normals are written one per vertex.
+pub fn write_obj<S: Scalar>(
+    mesh: &Mesh<S>,
+    normals: Normals,
+    out: &mut impl Write,
+    cancel: &CancelToken,
+) -> Result<()> {
+    for (i, [x, y, z]) in mesh.vertices.iter().enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        writeln!(out, "v {x} {y} {z}")?;
+    }
+    // The normal index of each face corner, or None without normals.
+    let normal_index: Box<dyn Fn(usize, u32) -> Option<usize>> = match normals {
+        Normals::None => Box::new(|_, _| None),
+        Normals::Face => {
+            let mut unique = Vec::new();
+            let mut lookup = HashMap::new();
+            let per_face: Vec<usize> = mesh
+                .face_normals()
+                .into_iter()
+                .map(|n| {
+                    *lookup.entry(n.map(f64::to_bits)).or_insert_with(|| {
+                        unique.push(n);
+                        unique.len() - 1
+                    })
+                })
+                .collect();
+            write_vn(&unique, out)?;
+            Box::new(move |face, _| Some(per_face[face]))
+        }
+        Normals::Smooth => {
+            write_vn(&mesh.vertex_normals(), out)?;
+            Box::new(|_, vertex| Some(vertex as usize))
+        }
+    };
+    let polygons: Box<dyn Iterator<Item = &[u32]>> = match &mesh.faces {
+        Polygons::Quads(quads) => Box::new(quads.iter().map(|q| q.as_slice())),
+        Polygons::Triangles(triangles) => Box::new(triangles.iter().map(|t| t.as_slice())),
+    };
+    for (i, polygon) in polygons.enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        write!(out, "f")?;
+        for &v in polygon {
+            match normal_index(i, v) {
+                Some(n) => write!(out, " {}//{}", v + 1, n + 1)?,
+                None => write!(out, " {}", v + 1)?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_vn(normals: &[[f64; 3]], out: &mut impl Write) -> Result<()> {
+    for [x, y, z] in normals {
+        writeln!(out, "vn {x} {y} {z}")?;
+    }
+    Ok(())
+}
+
+/// Writes a mesh as binary STL, triangulating quads.
+pub fn write_stl<S: Scalar>(
+    mesh: &Mesh<S>,
+    out: &mut impl Write,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let triangles = mesh.triangles();
+    out.write_all(&[0; 80])?;
+    out.write_all(&(triangles.len() as u32).to_le_bytes())?;
+    for (i, triangle) in triangles.iter().enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        let corners = triangle.map(|v| mesh.vertex(v));
+        let normal = normalize(cross(
+            sub(corners[1], corners[0]),
+            sub(corners[2], corners[0]),
+        ));
+        for value in normal.into_iter().chain(corners.into_iter().flatten()) {
+            out.write_all(&(value as f32).to_le_bytes())?;
+        }
+        out.write_all(&[0; 2])?;
+    }
+    Ok(())
+}
+
+/// Writes the signed distance field of `lattice` (see [`signed_distances`])
+/// as a legacy binary VTK volume, one big-endian float per cell centre,
+/// about the origin in the [`Domain::Centered`] domain. Tiling and
+/// transforms apply to meshes only and are ignored.
+pub fn write_vtk(
+    lattice: &Lattice3,
+    out: &mut impl Write,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let field = signed_distances(lattice, cancel)?;
+    let [nx, ny, nz] = lattice.shape();
+    let [ox, oy, oz] = lattice.shape().map(|side| match options.domain {
+        Domain::Positive => 0.5,
+        Domain::Centered => 0.5 - side as f64 / 2.0,
+    });
+    writeln!(out, "# vtk DataFile Version 3.0")?;
+    writeln!(out, "signed distance to the fractal surface, in cells")?;
+    writeln!(out, "BINARY")?;
+    writeln!(out, "DATASET STRUCTURED_POINTS")?;
+    writeln!(out, "DIMENSIONS {nx} {ny} {nz}")?;
+    writeln!(out, "ORIGIN {ox} {oy} {oz}")?;
+    writeln!(out, "SPACING 1 1 1")?;
+    writeln!(out, "POINT_DATA {}", field.len())?;
+    writeln!(out, "SCALARS distance float 1")?;
+    writeln!(out, "LOOKUP_TABLE default")?;
+    for (i, value) in field.iter().enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        out.write_all(&(*value as f32).to_be_bytes())?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Writes `lattice` as a single-file NIfTI-1 volume: unsigned bytes, 1
+/// for filled cells, or for [`ImageValues::Distance`] floats holding the
+/// signed distance (see [`signed_distances`]) in output units.
+///
+/// The voxel-to-world matrix is the export transform with voxels at cell
+/// centres, so the volume lines up with meshes of the same job; spacing is
+/// in millimetres. Tiling is ignored.
+pub fn write_nifti(
+    lattice: &Lattice3,
+    out: &mut impl Write,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let [nx, ny, nz] = lattice.shape();
+    let transform = options.transform_for(lattice.shape().map(|side| side as f64));
+    let to_world = transform
+        .then(&Affine::from(Transform::Translate([0.5; 3])))
+        .rows();
+    let spacing = [0, 1, 2].map(|c| (0..3).map(|r| to_world[r][c].powi(2)).sum::<f64>().sqrt());
+    let distance = options.images.values == ImageValues::Distance;
+    let (datatype, bitpix): (i16, i16) = if distance { (16, 32) } else { (2, 8) };
+    let mut header = [0u8; 352];
+    let mut put = |offset: usize, bytes: &[u8]| {
+        header[offset..offset + bytes.len()].copy_from_slice(bytes);
+    };
+    put(0, &348i32.to_le_bytes());
+    // Dimensions, the first being their count.
+    for (i, n) in [3, nx, ny, nz, 1, 1, 1, 1].into_iter().enumerate() {
+        put(40 + 2 * i, &(n as i16).to_le_bytes());
+    }
+    put(70, &datatype.to_le_bytes());
+    put(72, &bitpix.to_le_bytes());
+    // Voxel sizes, after qfac.
+    for (i, d) in [1.0, spacing[0], spacing[1], spacing[2], 1.0]
+        .into_iter()
+        .enumerate()
+    {
+        put(76 + 4 * i, &(d as f32).to_le_bytes());
+    }
+    put(108, &352f32.to_le_bytes());
+    put(112, &1f32.to_le_bytes());
+    // Millimetres.
+    put(123, &[2]);
+    let description = if distance {
+        "signed distance to the fractal surface"
+    } else {
+        "fractal occupancy"
+    };
+    put(148, description.as_bytes());
+    // Only the general affine, NIFTI_XFORM_SCANNER_ANAT.
+    put(254, &1i16.to_le_bytes());
+    for (r, row) in to_world.iter().enumerate() {
+        for (c, value) in row.iter().enumerate() {
+            put(280 + 16 * r + 4 * c, &(*value as f32).to_le_bytes());
+        }
+    }
+    put(344, b"n+1\0");
+    out.write_all(&header)?;
+    if distance {
+        let scale = transform.scale_factor();
+        for (i, value) in signed_distances(lattice, cancel)?.iter().enumerate() {
+            if i % CANCEL_INTERVAL == 0 {
+                cancel.check()?;
+            }
+            out.write_all(&((value * scale) as f32).to_le_bytes())?;
+        }
+    } else {
+        for i in 0..lattice.len() {
+            if i % CANCEL_INTERVAL == 0 {
+                cancel.check()?;
+            }
+            out.write_all(&[lattice.get(lattice.position(i)) as u8])?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes a mesh as binary glTF 2.0, with one node per entry of `nodes`
+/// sharing the mesh, in the normals, conventions and precision `options`
+/// ask for, and with `colors` as its vertices' linear RGB `COLOR_0`.
+///
+/// Without smooth normals no `NORMAL` attribute is written, and viewers
+/// shade flat as the glTF specification requires. Nodes that mirror are
+/// left to the viewer, which reverses their winding.
+///
+/// With [`Precision::Q16`] positions are stored as grid steps from the
+/// mesh's lowest corner, and every node scales and moves them back. The
+/// step is the same along every axis, so the nodes stay uniform and the
+/// normals keep their directions. `Precision::F16` is rejected.
+///
+/// With [`Gltf::meshopt`] every buffer view is compressed into the file's
+/// buffer and decoded into a second one, which has no data of its own.
+pub fn write_glb<S: Scalar>(
+    mesh: &Mesh<S>,
+    colors: Option<&[[f64; 3]]>,
+    nodes: &[Affine],
+    options: &ExportOptions,
+    out: &mut impl Write,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let (normals, gltf) = (options.normals, &options.gltf);
+    let quantized = match options.precision {
+        Precision::F32 => false,
+        Precision::F16 => {
+            return Err(Error::InvalidJob(
+                "glTF cannot store f16 positions; use q16".into(),
+            ))
+        }
+        Precision::Q16 => true,
+    };
+    let mut low = [f64::INFINITY; 3];
+    let mut high = [f64::NEG_INFINITY; 3];
+    for vertex in &mesh.vertices {
+        for ((low, high), value) in low.iter_mut().zip(&mut high).zip(vertex.map(S::to_f64)) {
+            *low = low.min(value);
+            *high = high.max(value);
+        }
+    }
+    if mesh.vertices.is_empty() {
+        (low, high) = ([0.0; 3], [0.0; 3]);
+    }
+    let step = match (0..3).map(|a| high[a] - low[a]).fold(0.0, f64::max) {
+        side if side > 0.0 => side / ((1 << gltf.position_bits) - 1) as f64,
+        _ => 1.0,
+    };
+
+    let triangles = mesh.triangles();
+    let mut bin = Vec::new();
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for (i, vertex) in mesh.vertices.iter().enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        for (axis, value) in vertex.map(S::to_f64).into_iter().enumerate() {
+            let value = if quantized {
+                ((value - low[axis]) / step).round()
+            } else {
+                value as f32 as f64
+            };
+            min[axis] = min[axis].min(value);
+            max[axis] = max[axis].max(value);
+            if quantized {
+                bin.extend_from_slice(&(value as u16).to_le_bytes());
+            } else {
+                bin.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+        if quantized {
+            bin.extend_from_slice(&[0; 2]);
+        }
+    }
+    let positions_len = bin.len();
+    for (i, &index) in triangles.iter().flatten().enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        bin.extend_from_slice(&index.to_le_bytes());
+    }
+    let indices_len = bin.len() - positions_len;
+
+    let bound = |values: [f64; 3]| {
+        if quantized {
+            serde_json::json!(values.map(|v| v as u16))
+        } else {
+            serde_json::json!(values.map(|v| v as f32))
+        }
+    };
+    let mut attributes = serde_json::json!({ "POSITION": 0 });
+    let mut buffer_views = vec![
+        serde_json::json!({ "buffer": 0, "byteOffset": 0, "byteLength": positions_len, "target": 34962 }),
+        serde_json::json!({ "buffer": 0, "byteOffset": positions_len, "byteLength": indices_len, "target": 34963 }),
+    ];
+    let mut accessors = vec![
+        serde_json::json!({
+            "bufferView": 0,
+            "componentType": if quantized { 5123 } else { 5126 },
+            "count": mesh.vertices.len(),
+            "type": "VEC3",
+            "min": bound(min),
+            "max": bound(max),
+        }),
+        serde_json::json!({
+            "bufferView": 1,
+            "componentType": 5125,
+            "count": triangles.len() * 3,
+            "type": "SCALAR",
+        }),
+    ];
+    // Bytes per component of normals and tangents.
+    let unit = match (quantized, gltf.normal_bits) {
+        (false, _) => 4,
+        (true, ..=8) => 1,
+        (true, _) => 2,
+    };
+    let unit_type = match unit {
+        1 => 5120,
+        2 => 5122,
+        _ => 5126,
+    };
+    if normals == Normals::Smooth {
+        let vertex_normals = mesh.vertex_normals();
+        let offset = bin.len();
+        for normal in &vertex_normals {
+            write_unit(&mut bin, normal, unit);
+            bin.resize(bin.len().next_multiple_of(4), 0);
+        }
+        attributes["NORMAL"] = serde_json::json!(accessors.len());
+        buffer_views.push(serde_json::json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bin.len() - offset,
+            "target": 34962,
+        }));
+        accessors.push(serde_json::json!({
+            "bufferView": buffer_views.len() - 1,
+            "componentType": unit_type,
+            "count": mesh.vertices.len(),
+            "type": "VEC3",
+        }));
+        if quantized {
+            buffer_views.last_mut().unwrap()["byteStride"] = (4 * unit).into();
+            accessors.last_mut().unwrap()["normalized"] = true.into();
+        }
+        if gltf.tangents {
+            let offset = bin.len();
+            for normal in &vertex_normals {
+                write_unit(&mut bin, &tangent(*normal), unit);
+            }
+            attributes["TANGENT"] = serde_json::json!(accessors.len());
+            buffer_views.push(serde_json::json!({
+                "buffer": 0,
+                "byteOffset": offset,
+                "byteLength": bin.len() - offset,
+                "target": 34962,
+            }));
+            accessors.push(serde_json::json!({
+                "bufferView": buffer_views.len() - 1,
+                "componentType": unit_type,
+                "count": mesh.vertices.len(),
+                "type": "VEC4",
+            }));
+            if quantized {
+                accessors.last_mut().unwrap()["normalized"] = true.into();
+            }
+        }
+    }
+    if let Some(colors) = colors {
+        let offset = bin.len();
+        for color in colors {
+            for channel in color {
+                bin.extend_from_slice(&(*channel as f32).to_le_bytes());
+            }
+        }
+        attributes["COLOR_0"] = serde_json::json!(accessors.len());
+        buffer_views.push(serde_json::json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bin.len() - offset,
+            "target": 34962,
+        }));
+        accessors.push(serde_json::json!({
+            "bufferView": buffer_views.len() - 1,
+            "componentType": 5126,
+            "count": colors.len(),
+            "type": "VEC3",
+        }));
+    }
+    if quantized {
+        buffer_views[0]["byteStride"] = 8.into();
+    }
+    // Scales and moves the grid steps back to the mesh's coordinates.
+    let dequantize = if quantized {
+        Affine::from_transforms(&[Transform::Scale(step), Transform::Translate(low)])
+    } else {
+        Affine::IDENTITY
+    };
+    let nodes: Vec<_> = nodes
+        .iter()
+        .map(|node| node.then(&dequantize))
+        .map(|node| {
+            if node == Affine::IDENTITY {
+                serde_json::json!({ "mesh": 0 })
+            } else {
+                serde_json::json!({ "mesh": 0, "matrix": node.to_column_major() })
+            }
+        })
+        .collect();
+    let mut buffers = vec![serde_json::json!({ "byteLength": bin.len() })];
+    let mut extensions = Vec::new();
+    if quantized {
+        extensions.push("KHR_mesh_quantization");
+    }
+    if gltf.meshopt && !triangles.is_empty() {
+        cancel.check()?;
+        let mut compressed = Vec::new();
+        // Views and accessors pair up one to one.
+        for (view, accessor) in buffer_views.iter_mut().zip(&accessors) {
+            let start = view["byteOffset"].as_u64().unwrap_or(0) as usize;
+            let data = &bin[start..start + view["byteLength"].as_u64().unwrap_or(0) as usize];
+            let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+            let (encoded, stride, mode) = if view["target"] == 34963 {
+                (
+                    meshopt::encode_triangles(&triangles.concat()),
+                    4,
+                    "TRIANGLES",
+                )
+            } else {
+                let stride = data.len() / count;
+                (meshopt::encode_vertices(data, stride), stride, "ATTRIBUTES")
+            };
+            view["buffer"] = 1.into();
+            view["extensions"] = serde_json::json!({
+                "EXT_meshopt_compression": {
+                    "buffer": 0,
+                    "byteOffset": compressed.len(),
+                    "byteLength": encoded.len(),
+                    "byteStride": stride,
+                    "count": count,
+                    "mode": mode,
+                },
+            });
+            compressed.extend_from_slice(&encoded);
+            compressed.resize(compressed.len().next_multiple_of(4), 0);
+        }
+        buffers = vec![
+            serde_json::json!({ "byteLength": compressed.len() }),
+            serde_json::json!({
+                "byteLength": bin.len(),
+                "extensions": { "EXT_meshopt_compression": { "fallback": true } },
+            }),
+        ];
+        bin = compressed;
+        extensions.push("EXT_meshopt_compression");
+    }
+    let mut document = serde_json::json!({
+        "asset": { "version": "2.0", "generator": "fractal-slicer" },
+        "scene": 0,
+        "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+        "nodes": nodes,
+        "meshes": [{ "primitives": [{ "attributes": attributes, "indices": 1, "mode": 4 }] }],
+        "buffers": buffers,
+        "bufferViews": buffer_views,
+        "accessors": accessors,
+    });
+    if !extensions.is_empty() {
+        document["extensionsUsed"] = serde_json::json!(extensions);
+        document["extensionsRequired"] = serde_json::json!(extensions);
+    }
+
+    let mut json = serde_json::to_vec(&document)?;
+    json.resize(json.len().next_multiple_of(4), b' ');
+    bin.resize(bin.len().next_multiple_of(4), 0);
+    let total = 12 + 8 + json.len() + 8 + bin.len();
+    out.write_all(b"glTF")?;
+    out.write_all(&2u32.to_le_bytes())?;
+    out.write_all(&(total as u32).to_le_bytes())?;
+    out.write_all(&(json.len() as u32).to_le_bytes())?;
+    out.write_all(b"JSON")?;
+    out.write_all(&json)?;
+    out.write_all(&(bin.len() as u32).to_le_bytes())?;
+    out.write_all(b"BIN\0")?;
+    out.write_all(&bin)?;
+    Ok(())
+}
+
+/// Writes the components of a unit vector as `f32`, or with 1 or 2
+/// `bytes` as normalized `i8` or `i16`.
+fn write_unit(bin: &mut Vec<u8>, vector: &[f64], bytes: usize) {
+    for &value in vector {
+        match bytes {
+            1 => bin.push((value * 127.0).round() as i8 as u8),
+            2 => bin.extend_from_slice(&((value * 32767.0).round() as i16).to_le_bytes()),
+            _ => bin.extend_from_slice(&(value as f32).to_le_bytes()),
+        }
+    }
+}
+
+/// A unit tangent perpendicular to `normal`, with handedness 1. The mesh
+/// has no texture coordinates to align it with, so it only has to be
+/// consistent: the x axis, or y near x, projected onto the surface.
+fn tangent(normal: [f64; 3]) -> [f64; 4] {
+    let axis = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let along = dot(axis, normal);
+    let [x, y, z] = normalize(std::array::from_fn(|i| axis[i] - along * normal[i]));
+    [x, y, z, 1.0]
+}
@@ -0,0 +1,248 @@
+//! Encoders for the `EXT_meshopt_compression` glTF extension: the
+//! attribute bitstream, version 0, and the triangle bitstream, version 1,
+//! that meshoptimizer's decoders read in web viewers.
+
+const VERTEX_HEADER: u8 = 0xa0;
+const INDEX_HEADER: u8 = 0xe1;
+
+/// Bytes of vertex data a block of the attribute stream may hold.
+const BLOCK_BYTES: usize = 8192;
+const BLOCK_MAX_VERTICES: usize = 256;
+/// Values whose deltas share a bit width.
+const GROUP: usize = 16;
+/// The padding after the blocks, which also carries the first vertex.
+const TAIL: usize = 32;
+
+/// Pairs of vertex FIFO codes a single triangle code byte can stand for;
+/// the decoder reads the table from the end of the stream.
+const CODE_AUX_TABLE: [u8; 16] = [
+    0x00, 0x76, 0x87, 0x56, 0x67, 0x78, 0xa9, 0x86, 0x65, 0x89, 0x68, 0x98, 0x01, 0x69, 0, 0,
+];
+
+/// Encodes `data`, vertices of `stride` bytes, as the `ATTRIBUTES` mode
+/// stores them: per byte of the vertex, the zigzagged deltas between
+/// successive vertices, packed in groups of 16 at the fewest bits that
+/// hold most of them. `stride` must be a multiple of 4, at most 256.
+pub fn encode_vertices(data: &[u8], stride: usize) -> Vec<u8> {
+    debug_assert!(stride.is_multiple_of(4) && stride <= 256);
+    let block = ((BLOCK_BYTES / stride) & !(GROUP - 1)).min(BLOCK_MAX_VERTICES);
+    let first = &data[..stride.min(data.len())];
+    let mut out = vec![VERTEX_HEADER];
+    let mut last = first.to_vec();
+    last.resize(stride, 0);
+    for vertices in data.chunks(block * stride) {
+        let count = vertices.len() / stride;
+        let mut deltas = [0; BLOCK_MAX_VERTICES];
+        for (k, previous) in last.iter_mut().enumerate() {
+            for (delta, vertex) in deltas.iter_mut().zip(vertices.chunks_exact(stride)) {
+                *delta = zigzag(vertex[k].wrapping_sub(*previous));
+                *previous = vertex[k];
+            }
+            encode_bytes(&mut out, &deltas[..count.next_multiple_of(GROUP)]);
+        }
+    }
+    out.resize(out.len() + TAIL.saturating_sub(stride), 0);
+    out.extend_from_slice(first);
+    out.resize(out.len() + stride - first.len(), 0);
+    out
+}
+
+fn zigzag(v: u8) -> u8 {
+    ((v as i8 >> 7) as u8) ^ (v << 1)
+}
+
+/// Writes groups of 16 bytes behind a header of two bits per group: the
+/// base-2 logarithm of the bits each value gets, with values that do not
+/// fit escaped to a whole byte after the group.
+fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    let header = out.len();
+    out.resize(header + (bytes.len() / GROUP).div_ceil(4), 0);
+    for (i, group) in bytes.chunks_exact(GROUP).enumerate() {
+        let mut best = (8, GROUP);
+        for bits in [1, 2, 4] {
+            if let Some(size) = group_size(group, bits).filter(|&size| size < best.1) {
+                best = (bits, size);
+            }
+        }
+        let bits = best.0;
+        out[header + i / 4] |= (bits.trailing_zeros() as u8) << (i % 4 * 2);
+        match bits {
+            1 => {}
+            8 => out.extend_from_slice(group),
+            _ => {
+                let sentinel = (1u8 << bits) - 1;
+                for values in group.chunks_exact(8 / bits) {
+                    out.push(
+                        values
+                            .iter()
+                            .fold(0, |byte, &v| (byte << bits) | v.min(sentinel)),
+                    );
+                }
+                out.extend(group.iter().filter(|&&v| v >= sentinel));
+            }
+        }
+    }
+}
+
+/// The bytes `group` takes at `bits` a value; one bit means all zeros.
+fn group_size(group: &[u8], bits: usize) -> Option<usize> {
+    if bits == 1 {
+        return group.iter().all(|&v| v == 0).then_some(0);
+    }
+    let sentinel = (1u8 << bits) - 1;
+    Some(GROUP * bits / 8 + group.iter().filter(|&&v| v >= sentinel).count())
+}
+
+/// Recently seen edges and vertices, which later triangles refer to by
+/// their age instead of repeating their indices.
+struct Fifo {
+    edges: [[u32; 2]; 16],
+    edge_offset: usize,
+    vertices: [u32; 16],
+    vertex_offset: usize,
+}
+
+impl Fifo {
+    /// The age of the edge of the triangle found in the FIFO, and which of
+    /// the triangle's edges it is.
+    fn edge(&self, [a, b, c]: [u32; 3]) -> Option<(usize, usize)> {
+        (0..16).find_map(|age| {
+            let edge = self.edges[(self.edge_offset + 15 - age) & 15];
+            [[a, b], [b, c], [c, a]]
+                .iter()
+                .position(|&e| e == edge)
+                .map(|rotation| (age, rotation))
+        })
+    }
+
+    fn vertex(&self, v: u32) -> Option<usize> {
+        (0..16).find(|age| self.vertices[(self.vertex_offset + 15 - age) & 15] == v)
+    }
+
+    fn push_edge(&mut self, a: u32, b: u32) {
+        self.edges[self.edge_offset] = [a, b];
+        self.edge_offset = (self.edge_offset + 1) & 15;
+    }
+
+    fn push_vertex(&mut self, v: u32) {
+        self.vertices[self.vertex_offset] = v;
+        self.vertex_offset = (self.vertex_offset + 1) & 15;
+    }
+}
+
+/// Encodes triangle `indices` as the `TRIANGLES` mode stores them: a code
+/// byte per triangle naming a recent edge and vertex, or the next unseen
+/// vertex, with the rest of the indices as varint deltas after the codes.
+pub fn encode_triangles(indices: &[u32]) -> Vec<u8> {
+    let triangles = indices.len() / 3;
+    let mut codes = Vec::with_capacity(triangles + 1);
+    let mut data = Vec::new();
+    codes.push(INDEX_HEADER);
+    let mut fifo = Fifo {
+        edges: [[u32::MAX; 2]; 16],
+        edge_offset: 0,
+        vertices: [u32::MAX; 16],
+        vertex_offset: 0,
+    };
+    let mut next = 0;
+    let mut last = 0;
+    for triangle in indices.chunks_exact(3) {
+        let triangle = [triangle[0], triangle[1], triangle[2]];
+        if let Some((age, rotation)) = fifo.edge(triangle).filter(|&(age, _)| age < 15) {
+            let [a, b, c] = std::array::from_fn(|i| triangle[(i + rotation) % 3]);
+            // The third vertex: recent, next, one either side of the last
+            // free index, or free.
+            let fec = match fifo.vertex(c) {
+                Some(fc @ 1..13) => fc,
+                _ if c == next => {
+                    next += 1;
+                    0
+                }
+                _ if c.wrapping_add(1) == last => 13,
+                _ if c == last.wrapping_add(1) => 14,
+                _ => 15,
+            };
+            codes.push((age << 4 | fec) as u8);
+            match fec {
+                13 | 14 => last = c,
+                15 => encode_free(&mut data, c, &mut last),
+                _ => {}
+            }
+            if fec == 0 || fec >= 13 {
+                fifo.push_vertex(c);
+            }
+            fifo.push_edge(c, b);
+            fifo.push_edge(a, c);
+        } else {
+            let [_, b0, c0] = triangle;
+            let rotation = if b0 == next {
+                1
+            } else if c0 == next {
+                2
+            } else {
+                0
+            };
+            let [a, b, c] = std::array::from_fn(|i| triangle[(i + rotation) % 3]);
+            let (fb, fc) = (fifo.vertex(b), fifo.vertex(c));
+            let fea = fresh(a, None, &mut next);
+            let feb = fresh(b, fb, &mut next);
+            let fec = fresh(c, fc, &mut next);
+            let aux = (feb << 4 | fec) as u8;
+            match CODE_AUX_TABLE[..14].iter().position(|&code| code == aux) {
+                Some(i) if fea == 0 => codes.push(0xf0 | i as u8),
+                _ => {
+                    codes.push(0xfe | (fea == 15) as u8);
+                    data.push(aux);
+                }
+            }
+            for (v, fe) in [(a, fea), (b, feb), (c, fec)] {
+                if fe == 15 {
+                    encode_free(&mut data, v, &mut last);
+                }
+            }
+            for (v, fe) in [(a, fea), (b, feb), (c, fec)] {
+                if fe == 0 || fe == 15 {
+                    fifo.push_vertex(v);
+                }
+            }
+            fifo.push_edge(b, a);
+            fifo.push_edge(c, b);
+            fifo.push_edge(a, c);
+        }
+    }
+    codes.extend_from_slice(&data);
+    codes.extend_from_slice(&CODE_AUX_TABLE);
+    codes
+}
+
+/// The code of a vertex of a triangle no edge in the FIFO starts: one
+/// more than its age in the vertex FIFO, 0 for the next vertex, or 15
+/// for a free index.
+fn fresh(v: u32, recent: Option<usize>, next: &mut u32) -> usize {
+    match recent {
+        Some(age @ 0..14) => age + 1,
+        _ if v == *next => {
+            *next += 1;
+            0
+        }
+        _ => 15,
+    }
+}
+
+/// Appends a free index as the zigzagged delta from the last one.
+fn encode_free(data: &mut Vec<u8>, v: u32, last: &mut u32) {
+    let delta = v.wrapping_sub(*last);
+    encode_varint(data, (delta << 1) ^ ((delta as i32 >> 31) as u32));
+    *last = v;
+}
+
+/// Seven bits a byte, low first, with the high bit marking more.
+fn encode_varint(out: &mut Vec<u8>, mut v: u32) {
+    loop {
+        out.push((v & 127) as u8 | if v > 127 { 128 } else { 0 });
+        v >>= 7;
+        if v == 0 {
+            break;
+        }
+    }
+}
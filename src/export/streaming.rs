@@ -0,0 +1,362 @@
+//! Out-of-core meshing of lattices too large to hold, such as Menger
+//! sponges of depth 7 or 8: the cells are generated a slab of z layers at
+//! a time, inside a ghost border of the neighbouring cells that culls the
+//! faces on the slab's walls, and the culled faces are written as soon as
+//! they are found.
+//!
+//! Vertices on the plane between two slabs are numbered once and shared
+//! by the faces on both sides, so the mesh has no seams, and numbering
+//! matches [`build_indexed_mesh`](crate::mesh::build_indexed_mesh). Memory
+//! is a few slabs of bits and the vertex indices of one slab's planes,
+//! whatever the depth.
+//!
+//! Slabs are generated on their own threads while the surface of earlier
+//! ones is written, so generation hides the disk's latency and writing
+//! the cost of testing cells. The generation threads take slabs in turn
+//! and each hands its slabs over one at a time, so the file is written in
+//! order and at most two slabs per thread wait to be written.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+
+use memmap2::Mmap;
+
+use super::{write_atomically, Artifact, ExportOptions, Format};
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::Lattice3;
+use crate::mesh::{chunk_faces, cross, normalize, sub, Normals};
+use crate::transform::Affine;
+
+/// Receives the surface a slab at a time: every vertex at its first use,
+/// then each face with its vertices' indices and positions.
+trait FaceSink {
+    fn vertex(&mut self, position: [f64; 3]) -> Result<()>;
+    fn quad(&mut self, indices: [u64; 4], corners: [[f64; 3]; 4]) -> Result<()>;
+}
+
+/// Cells a slab of layers holds at most when [`slab_layers`] picks its
+/// depth, a few megabytes of bits and vertex indices.
+const SLAB_CELLS: usize = 1 << 22;
+
+/// The layers per slab for a lattice of `shape`: as many as fit in
+/// [`SLAB_CELLS`], and at least one.
+pub fn slab_layers([nx, ny, nz]: [usize; 3]) -> usize {
+    (SLAB_CELLS / (nx * ny).max(1)).clamp(1, nz.max(1))
+}
+
+/// Threads generating slabs while the calling thread writes them: every
+/// core but the writer's, and at least one.
+fn generators() -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .saturating_sub(1)
+        .max(1)
+}
+
+/// Bytes held at once while streaming a lattice of `shape` in slabs of
+/// `layers`: the bits, with their ghost border, of the slabs being
+/// generated, waiting and written, and the vertex indices of the planes
+/// between the written slab's layers.
+pub fn streaming_bytes([nx, ny, _]: [usize; 3], layers: usize) -> u64 {
+    let bits = (nx + 2) as u64 * (ny + 2) as u64 * (layers + 2) as u64;
+    let slabs = 2 * generators() as u64 + 1;
+    bits.div_ceil(8) * slabs + (nx + 1) as u64 * (ny + 1) as u64 * (layers + 1) as u64 * 8
+}
+
+/// Writes the culled surface of the lattice of `shape` whose cells
+/// `solid` accepts to a `.obj` or `.stl` `path`, without building the
+/// lattice or the mesh, generating `layers` layers at a time and applying
+/// the boundary, normals and transform of `options`. The file is the one
+/// [`export`](super::export) writes for the whole lattice, whatever the
+/// slab depth.
+pub fn export_streaming(
+    shape: [usize; 3],
+    solid: impl Fn([usize; 3]) -> bool + Sync,
+    layers: usize,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Artifact> {
+    let mirrored = options.transform.is_mirroring();
+    write_atomically(path, |format, out| match format {
+        Format::Obj => {
+            let mut obj = ObjWriter {
+                out,
+                normals: options.normals,
+                unique: HashMap::new(),
+                mirrored,
+            };
+            walk_surface(shape, &solid, layers, options, cancel, &mut obj)
+        }
+        Format::Stl => {
+            let mut stl = StlWriter {
+                triangles: 0,
+                spill: Spill::new()?,
+                mirrored,
+            };
+            walk_surface(shape, &solid, layers, options, cancel, &mut stl)?;
+            stl.finish(out)
+        }
+        _ => Err(Error::InvalidJob(format!(
+            "`{}` cannot be written out of core; use .obj or .stl",
+            path.display()
+        ))),
+    })
+}
+
+/// Generates the lattice a slab of layers at a time and hands its culled
+/// faces to `sink` in the order [`surface_faces`](crate::mesh::surface_faces)
+/// finds them.
+fn walk_surface(
+    shape: [usize; 3],
+    solid: &(impl Fn([usize; 3]) -> bool + Sync),
+    layers: usize,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+    sink: &mut impl FaceSink,
+) -> Result<()> {
+    let [nx, ny, nz] = shape;
+    let layers = layers.max(1);
+    let transform = options.transform_for(shape.map(|side| side as f64));
+    let boundary = options.boundary;
+    let starts: Vec<usize> = (0..nz).step_by(layers).collect();
+    let threads = generators();
+    std::thread::scope(|scope| {
+        // Slab `i` comes from thread `i % threads`, which sends it, or the
+        // error that stopped it, and waits while the one before is unread.
+        let slabs: Vec<Receiver<Result<Lattice3>>> = (0..threads)
+            .map(|t| {
+                let (sender, receiver) = mpsc::sync_channel(1);
+                let starts = &starts;
+                scope.spawn(move || {
+                    for &z in starts.iter().skip(t).step_by(threads) {
+                        let slab = Lattice3::chunk_from_fn(
+                            shape,
+                            [0, 0, z],
+                            [nx, ny, layers.min(nz - z)],
+                            boundary,
+                            cancel,
+                            solid,
+                        );
+                        let failed = slab.is_err();
+                        // The writer is gone once it fails.
+                        if sender.send(slab).is_err() || failed {
+                            break;
+                        }
+                    }
+                });
+                receiver
+            })
+            .collect();
+        let slabs = starts.iter().enumerate().map(|(i, &z)| {
+            let slab = slabs[i % threads]
+                .recv()
+                .expect("generation threads send every slab or stop at an error");
+            slab.map(|slab| (z, slab))
+        });
+        write_slabs(shape, layers, &transform, slabs, cancel, sink)
+    })
+}
+
+/// Numbers the vertices of each slab's culled faces, in order, and hands
+/// them and the faces to `sink`.
+fn write_slabs(
+    [nx, ny, nz]: [usize; 3],
+    layers: usize,
+    transform: &Affine,
+    slabs: impl Iterator<Item = Result<(usize, Lattice3)>>,
+    cancel: &CancelToken,
+    sink: &mut impl FaceSink,
+) -> Result<()> {
+    // Vertex indices on each plane between the slab's layers, x fastest;
+    // `u64::MAX` where none is numbered yet. The top plane carries over
+    // as the next slab's bottom one.
+    let mut planes = vec![vec![u64::MAX; (nx + 1) * (ny + 1)]; layers.min(nz) + 1];
+    let mut next = 0;
+    for slab in slabs {
+        cancel.check()?;
+        let (z, slab) = slab?;
+        tracing::debug!(done = z, total = nz, "layers meshed");
+        let depth = layers.min(nz - z);
+        for face in chunk_faces(&slab, [0, 0, z]) {
+            let corners = face.lattice_corners();
+            let mut indices = [0; 4];
+            for (index, corner) in indices.iter_mut().zip(corners) {
+                let slot = &mut planes[corner[2] - z][corner[1] * (nx + 1) + corner[0]];
+                if *slot == u64::MAX {
+                    *slot = next;
+                    next += 1;
+                    sink.vertex(transform.apply(corner.map(|c| c as f64)))?;
+                }
+                *index = *slot;
+            }
+            let corners = corners.map(|corner| transform.apply(corner.map(|c| c as f64)));
+            sink.quad(indices, corners)?;
+        }
+        planes.swap(0, depth);
+        for plane in &mut planes[1..] {
+            plane.fill(u64::MAX);
+        }
+    }
+    Ok(())
+}
+
+/// Streams OBJ with vertices and face normals written just before the
+/// first face using them, keeping quads as quads.
+struct ObjWriter<W> {
+    out: W,
+    normals: Normals,
+    /// The index of every face normal written, by its bits.
+    unique: HashMap<[u64; 3], usize>,
+    /// Whether the transform mirrors, so faces are reversed to keep
+    /// pointing outwards.
+    mirrored: bool,
+}
+
+impl<W: Write> FaceSink for ObjWriter<W> {
+    fn vertex(&mut self, [x, y, z]: [f64; 3]) -> Result<()> {
+        writeln!(self.out, "v {x} {y} {z}")?;
+        Ok(())
+    }
+
+    fn quad(&mut self, mut indices: [u64; 4], mut corners: [[f64; 3]; 4]) -> Result<()> {
+        if self.mirrored {
+            indices.reverse();
+            corners.reverse();
+        }
+        let normal = match self.normals {
+            Normals::None => None,
+            // Rejected by job validation, as they need every face around
+            // a vertex.
+            Normals::Smooth => {
+                return Err(Error::InvalidJob(
+                    "smooth normals cannot be written out of core".into(),
+                ))
+            }
+            Normals::Face => {
+                let [x, y, z] = normalize(cross(
+                    sub(corners[1], corners[0]),
+                    sub(corners[2], corners[0]),
+                ));
+                let count = self.unique.len();
+                let index = *self
+                    .unique
+                    .entry([x, y, z].map(f64::to_bits))
+                    .or_insert(count);
+                if index == count {
+                    writeln!(self.out, "vn {x} {y} {z}")?;
+                }
+                Some(index)
+            }
+        };
+        write!(self.out, "f")?;
+        for v in indices {
+            match normal {
+                Some(n) => write!(self.out, " {}//{}", v + 1, n + 1)?,
+                None => write!(self.out, " {}", v + 1)?,
+            }
+        }
+        writeln!(self.out)?;
+        Ok(())
+    }
+}
+
+/// Collects binary STL triangles in a spill file until their count,
+/// which leads the file, is known.
+struct StlWriter {
+    triangles: u64,
+    spill: Spill,
+    mirrored: bool,
+}
+
+impl FaceSink for StlWriter {
+    fn vertex(&mut self, _: [f64; 3]) -> Result<()> {
+        Ok(())
+    }
+
+    fn quad(&mut self, _: [u64; 4], corners: [[f64; 3]; 4]) -> Result<()> {
+        for triangle in [[0, 1, 2], [0, 2, 3]] {
+            let mut triangle = triangle.map(|i| corners[i]);
+            if self.mirrored {
+                triangle.reverse();
+            }
+            let normal = normalize(cross(
+                sub(triangle[1], triangle[0]),
+                sub(triangle[2], triangle[0]),
+            ));
+            for value in normal.into_iter().chain(triangle.into_iter().flatten()) {
+                self.spill.out.write_all(&(value as f32).to_le_bytes())?;
+            }
+            self.spill.out.write_all(&[0; 2])?;
+        }
+        self.triangles += 2;
+        Ok(())
+    }
+}
+
+impl StlWriter {
+    fn finish(mut self, out: &mut dyn Write) -> Result<()> {
+        let triangles = u32::try_from(self.triangles).map_err(|_| {
+            Error::InvalidJob(format!(
+                "{} triangles are too many for STL; write .obj",
+                self.triangles
+            ))
+        })?;
+        out.write_all(&[0; 80])?;
+        out.write_all(&triangles.to_le_bytes())?;
+        self.spill.copy_to(out)
+    }
+}
+
+/// A temporary file holding what must be written after a header that is
+/// only known at the end, removed when dropped.
+struct Spill {
+    path: PathBuf,
+    out: BufWriter<File>,
+}
+
+impl Spill {
+    fn new() -> Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "fractal-slicer-{}-{}.spill",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Spill {
+            path,
+            out: BufWriter::new(file),
+        })
+    }
+
+    /// Copies everything written to `out` through a memory map, so the
+    /// spill is paged in as it is copied rather than read into memory.
+    fn copy_to(&mut self, out: &mut dyn Write) -> Result<()> {
+        self.out.flush()?;
+        let file = self.out.get_ref();
+        if file.metadata()?.len() == 0 {
+            return Ok(());
+        }
+        // SAFETY: the file was created by this process under a fresh name
+        // and is only read while mapped.
+        let map = unsafe { Mmap::map(file)? };
+        out.write_all(&map)?;
+        Ok(())
+    }
+}
+
+impl Drop for Spill {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
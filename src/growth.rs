@@ -0,0 +1,138 @@
+//! Growth animations: a rule fractal morphing from one depth to the next,
+//! the cells the last level removes shrinking away while the rest stay.
+//!
+//! Every cell of the coarser fractal is split into its children, as
+//! [`Rule::children`] numbers them; those the finer fractal keeps are its
+//! surface, and each of the others is a cube shrinking about its own
+//! centre, from full size in the first frame to nothing in the last.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::{Boundary, Lattice, Lattice3};
+use crate::mesh::{build_indexed_mesh, Face, FaceKind, Mesh, Polygons};
+use crate::rule::{Rule, Split};
+
+/// How a job animates its fractal's growth into its depth.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Grow {
+    /// Meshes written, the first of the fractal one level short of the
+    /// job's depth and the last of it at the depth.
+    pub frames: u32,
+}
+
+impl Grow {
+    pub fn validate(&self, depth: u32) -> Result<()> {
+        if depth == 0 {
+            return Err(Error::InvalidJob(
+                "growth needs a depth of at least 1 to grow into".into(),
+            ));
+        }
+        if self.frames < 2 {
+            return Err(Error::InvalidJob(
+                "a growth animation needs at least two frames".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// How far frame `frame` is through the growth, 0 to 1.
+    pub fn progress(&self, frame: u32) -> f64 {
+        frame as f64 / (self.frames - 1).max(1) as f64
+    }
+}
+
+/// The cells of a 3D rule fractal at one depth, split into those the
+/// next level keeps and those it removes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Growth {
+    /// The fractal at the finer depth.
+    kept: Lattice3,
+    /// Children of the coarser fractal's cells the finer one removes, in
+    /// cells of the finer lattice.
+    removed: Vec<[usize; 3]>,
+}
+
+impl Growth {
+    /// The step from depth `depth - 1` to `depth` of the 3D fractal of
+    /// `rule`, which must be split evenly.
+    pub fn new(rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<Self> {
+        if rule.dims() != 3 || rule.split() != Split::Uniform || depth == 0 {
+            return Err(Error::InvalidJob(
+                "growth needs a 3D rule split evenly and a depth of at least 1".into(),
+            ));
+        }
+        let kept: Lattice3 = Lattice::generate_recursive(rule, depth, cancel)?;
+        let parents: Lattice3 = Lattice::generate_recursive(rule, depth - 1, cancel)?;
+        let mut removed = Vec::new();
+        for parent in parents.iter() {
+            cancel.check()?;
+            for child in rule.children(&parent) {
+                let child = [child[0], child[1], child[2]];
+                if !kept.get(child) {
+                    removed.push(child);
+                }
+            }
+        }
+        Ok(Growth { kept, removed })
+    }
+
+    /// The fractal at the finer depth.
+    pub fn kept(&self) -> &Lattice3 {
+        &self.kept
+    }
+
+    /// The cells the finer depth removes.
+    pub fn removed(&self) -> &[[usize; 3]] {
+        &self.removed
+    }
+
+    /// The frame `progress` of the way through the growth, 0 to 1, with
+    /// faces of `kind`: the finer fractal's surface, and every removed
+    /// cell as a whole cube scaled by `1 - progress` about its centre.
+    /// Vertices are in cells of the finer lattice.
+    pub fn mesh(&self, progress: f64, kind: FaceKind) -> Mesh {
+        let mut mesh = build_indexed_mesh(&self.kept, FaceKind::Quads, Boundary::Open);
+        let scale = 1.0 - progress.clamp(0.0, 1.0);
+        let Polygons::Quads(quads) = &mut mesh.faces else {
+            unreachable!("built as quads")
+        };
+        if scale > 0.0 {
+            for &cell in &self.removed {
+                let base = mesh.vertices.len() as u32;
+                let mut corners = Vec::new();
+                for axis in 0..3 {
+                    for positive in [false, true] {
+                        let face = Face {
+                            cell,
+                            axis,
+                            positive,
+                        };
+                        quads.push(face.lattice_corners().map(|corner| {
+                            let index = corners
+                                .iter()
+                                .position(|&known| known == corner)
+                                .unwrap_or_else(|| {
+                                    corners.push(corner);
+                                    corners.len() - 1
+                                });
+                            base + index as u32
+                        }));
+                    }
+                }
+                mesh.vertices.extend(corners.iter().map(|corner| {
+                    std::array::from_fn(|a| {
+                        let centre = cell[a] as f64 + 0.5;
+                        centre + (corner[a] as f64 - centre) * scale
+                    })
+                }));
+            }
+        }
+        if kind == FaceKind::Triangles {
+            mesh.faces = Polygons::Triangles(mesh.triangles());
+        }
+        mesh
+    }
+}
@@ -0,0 +1,345 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::compress::{crc32, zlib};
+use crate::distance::signed_distances;
+use crate::error::Result;
+use crate::lattice::Lattice3;
+use crate::transform::Axis;
+
+/// How a lattice is cut into grayscale images, as read by micro-CT and
+/// ImageJ/Fiji workflows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageStack {
+    /// The axis layers are stacked along; one image per cell along it.
+    #[serde(default = "default_axis")]
+    pub axis: Axis,
+    #[serde(default)]
+    pub values: ImageValues,
+}
+
+fn default_axis() -> Axis {
+    Axis::Z
+}
+
+impl Default for ImageStack {
+    fn default() -> Self {
+        ImageStack {
+            axis: default_axis(),
+            values: ImageValues::default(),
+        }
+    }
+}
+
+/// What each pixel of an image stack holds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageValues {
+    /// 8-bit: 255 for filled cells, 0 for empty ones.
+    #[default]
+    Occupancy,
+    /// 16-bit: 32768 minus 256 times the signed distance in cells, so
+    /// filled cells are brighter and the surface lies at 32768.
+    Distance,
+}
+
+/// One grayscale image of a stack.
+pub struct Layer {
+    pub width: usize,
+    pub height: usize,
+    /// 8 or 16.
+    pub bits: u8,
+    /// Row by row from the top, each `width` long.
+    pub samples: Vec<u16>,
+}
+
+impl ImageStack {
+    /// The lattice's layers along the stack's axis, in order. Layers
+    /// across x show (y, z) and layers across y show (x, z); layers
+    /// across z show (x, y). Rows run along the lower axis of the two.
+    pub fn layers(
+        &self,
+        lattice: &Lattice3,
+        cancel: &CancelToken,
+    ) -> Result<impl ExactSizeIterator<Item = Layer>> {
+        let (bits, values) = match self.values {
+            ImageValues::Occupancy => (
+                8,
+                (0..lattice.len())
+                    .map(|i| {
+                        if lattice.get(lattice.position(i)) {
+                            255
+                        } else {
+                            0
+                        }
+                    })
+                    .collect::<Vec<u16>>(),
+            ),
+            ImageValues::Distance => (
+                16,
+                signed_distances(lattice, cancel)?
+                    .iter()
+                    .map(|d| (32768.0 - 256.0 * d).round().clamp(0.0, 65535.0) as u16)
+                    .collect(),
+            ),
+        };
+        let shape = lattice.shape();
+        let index = move |p: [usize; 3]| p[0] + shape[0] * (p[1] + shape[1] * p[2]);
+        let axis = self.axis.index();
+        let [across, down] = match axis {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        Ok((0..shape[axis]).map(move |k| {
+            let mut samples = Vec::with_capacity(shape[across] * shape[down]);
+            for row in 0..shape[down] {
+                for column in 0..shape[across] {
+                    let mut p = [0; 3];
+                    p[axis] = k;
+                    p[across] = column;
+                    p[down] = row;
+                    samples.push(values[index(p)]);
+                }
+            }
+            Layer {
+                width: shape[across],
+                height: shape[down],
+                bits,
+                samples,
+            }
+        }))
+    }
+}
+
+/// The file layer `index` of `count` is written to: a `{i}` in `path` is
+/// replaced by the zero-padded index, otherwise it is appended to the
+/// file name, so the files sort in stack order.
+pub fn layer_path(path: &Path, index: usize, count: usize) -> PathBuf {
+    let width = count.saturating_sub(1).to_string().len();
+    let number = format!("{index:0width$}");
+    let text = path.to_string_lossy();
+    if text.contains("{i}") {
+        return PathBuf::from(text.replace("{i}", &number));
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_{number}");
+    if let Some(extension) = path.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// The layer's samples as bytes, 16-bit ones in the given byte order.
+fn sample_bytes(layer: &Layer, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+    match layer.bits {
+        8 => layer.samples.iter().map(|&s| s as u8).collect(),
+        _ => layer.samples.iter().flat_map(|&s| to_bytes(s)).collect(),
+    }
+}
+
+/// Writes a grayscale PNG, unfiltered.
+pub fn write_png(layer: &Layer, out: &mut impl Write) -> Result<()> {
+    let pixels = sample_bytes(layer, u16::to_be_bytes);
+    encode_png(layer.width, layer.height, layer.bits, 0, &pixels, out)
+}
+
+/// Writes an 8-bit RGB PNG, unfiltered, of `pixels` row by row from the
+/// top.
+pub fn write_rgb_png(
+    width: usize,
+    height: usize,
+    pixels: &[[u8; 3]],
+    out: &mut impl Write,
+) -> Result<()> {
+    encode_png(width, height, 8, 2, pixels.as_flattened(), out)
+}
+
+/// Writes a PNG of `height` rows of `pixels`, in the given bit depth and
+/// color type.
+fn encode_png(
+    width: usize,
+    height: usize,
+    bits: u8,
+    color: u8,
+    pixels: &[u8],
+    out: &mut impl Write,
+) -> Result<()> {
+    let row = pixels.len() / height.max(1);
+    // Every row starts with filter type 0, none.
+    let mut raw = Vec::with_capacity(pixels.len() + height);
+    for line in pixels.chunks(row.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth, color type, deflate, no filtering, not interlaced.
+    header.extend_from_slice(&[bits, color, 0, 0, 0]);
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_chunk(out, b"IHDR", &header)?;
+    write_chunk(out, b"IDAT", &zlib(&raw))?;
+    write_chunk(out, b"IEND", &[])?;
+    Ok(())
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let crc = crc32(kind.iter().chain(data));
+    out.write_all(&crc.to_be_bytes())?;
+    Ok(())
+}
+
+/// Writes an uncompressed little-endian grayscale TIFF, one strip.
+pub fn write_tiff(layer: &Layer, out: &mut impl Write) -> Result<()> {
+    let pixels = sample_bytes(layer, u16::to_le_bytes);
+    const SHORT: u16 = 3;
+    const LONG: u16 = 4;
+    const ENTRIES: usize = 9;
+    // The header, then the directory, then the pixels.
+    let data_offset = (8 + 2 + ENTRIES * 12 + 4) as u32;
+    let entries: [(u16, u16, u32); ENTRIES] = [
+        (256, LONG, layer.width as u32),         // ImageWidth
+        (257, LONG, layer.height as u32),        // ImageLength
+        (258, SHORT, layer.bits as u32),         // BitsPerSample
+        (259, SHORT, 1),                         // Compression: none
+        (262, SHORT, 1),                         // PhotometricInterpretation: black is zero
+        (273, LONG, data_offset),                // StripOffsets
+        (277, SHORT, 1),                         // SamplesPerPixel
+        (278, LONG, layer.height.max(1) as u32), // RowsPerStrip
+        (279, LONG, pixels.len() as u32),        // StripByteCounts
+    ];
+    out.write_all(b"II*\0")?;
+    out.write_all(&8u32.to_le_bytes())?;
+    out.write_all(&(entries.len() as u16).to_le_bytes())?;
+    for (tag, kind, value) in entries {
+        out.write_all(&tag.to_le_bytes())?;
+        out.write_all(&kind.to_le_bytes())?;
+        out.write_all(&1u32.to_le_bytes())?;
+        // Values shorter than four bytes sit at the start of the field.
+        match kind {
+            SHORT => {
+                out.write_all(&(value as u16).to_le_bytes())?;
+                out.write_all(&[0, 0])?;
+            }
+            _ => out.write_all(&value.to_le_bytes())?,
+        }
+    }
+    // No further images.
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&pixels)?;
+    Ok(())
+}
+
+/// One channel of an OpenEXR image, row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExrChannel {
+    pub name: String,
+    pub samples: ExrSamples,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExrSamples {
+    Uint(Vec<u32>),
+    Float(Vec<f32>),
+}
+
+impl ExrSamples {
+    /// The pixel type code in the channel list.
+    fn pixel_type(&self) -> i32 {
+        match self {
+            ExrSamples::Uint(_) => 0,
+            ExrSamples::Float(_) => 2,
+        }
+    }
+
+    /// Appends the little-endian bytes of row `y`, `width` samples long.
+    fn write_row(&self, y: usize, width: usize, out: &mut Vec<u8>) {
+        let range = y * width..(y + 1) * width;
+        match self {
+            ExrSamples::Uint(samples) => {
+                out.extend(samples[range].iter().flat_map(|s| s.to_le_bytes()))
+            }
+            ExrSamples::Float(samples) => {
+                out.extend(samples[range].iter().flat_map(|s| s.to_le_bytes()))
+            }
+        }
+    }
+}
+
+/// Writes an uncompressed single-part scanline OpenEXR image, as Nuke,
+/// Blender's compositor and OpenImageIO read.
+pub fn write_exr(
+    width: usize,
+    height: usize,
+    channels: &[ExrChannel],
+    out: &mut impl Write,
+) -> Result<()> {
+    // Readers expect channels sorted by name, in the list and the rows.
+    let mut channels: Vec<&ExrChannel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut list = Vec::new();
+    for channel in &channels {
+        list.extend_from_slice(channel.name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&channel.samples.pixel_type().to_le_bytes());
+        // Not perceptually linear, three reserved bytes, no subsampling.
+        list.extend_from_slice(&[0; 4]);
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    let attributes: [(&str, &str, &[u8]); 8] = [
+        ("channels", "chlist", &list),
+        ("compression", "compression", &[0]),
+        ("dataWindow", "box2i", &window),
+        ("displayWindow", "box2i", &window),
+        ("lineOrder", "lineOrder", &[0]),
+        ("pixelAspectRatio", "float", &1f32.to_le_bytes()),
+        ("screenWindowCenter", "v2f", &[0; 8]),
+        ("screenWindowWidth", "float", &1f32.to_le_bytes()),
+    ];
+    let mut header = Vec::new();
+    // Magic number, then version 2 of a single-part scanline file.
+    header.extend_from_slice(&20000630u32.to_le_bytes());
+    header.extend_from_slice(&2u32.to_le_bytes());
+    for (name, kind, value) in attributes {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    }
+    header.push(0);
+    out.write_all(&header)?;
+    // Uncompressed files hold one row per chunk, each led by its y and
+    // size, found through a table of their offsets.
+    let row_bytes = channels.len() * width * 4;
+    let first = (header.len() + height * 8) as u64;
+    for y in 0..height as u64 {
+        out.write_all(&(first + y * (8 + row_bytes as u64)).to_le_bytes())?;
+    }
+    let mut row = Vec::with_capacity(8 + row_bytes);
+    for y in 0..height {
+        row.clear();
+        row.extend_from_slice(&(y as i32).to_le_bytes());
+        row.extend_from_slice(&(row_bytes as i32).to_le_bytes());
+        for channel in &channels {
+            channel.samples.write_row(y, width, &mut row);
+        }
+        out.write_all(&row)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,619 @@
+//! Implicit solids and escape-time fractals written as small programs, so
+//! shapes beyond the built-in ones can be voxelized without writing Rust.
+//!
+//! A program is a list of statements ending in an expression; a cell is
+//! filled when the expression is at most 0 at its centre. The centre is in
+//! the variables `x`, `y`, `z` and `w`. Statements are
+//!
+//! * `name = expr;`, which assigns a variable, new or not;
+//! * `repeat { ... }`, which runs its statements as many times as the job's
+//!   depth, or `repeat 3 { ... }` a fixed number of times;
+//! * `escape if expr;`, which leaves the cell empty when `expr` is not 0.
+//!
+//! Expressions have numbers, variables, `pi` and `tau`, the operators
+//! `+ - * / % ^`, comparisons and `&& || !` giving 1 or 0, and the
+//! functions `abs sqrt exp ln sin cos tan asin acos atan sinh cosh tanh
+//! floor ceil fract` of one argument, `atan2 min max pow hypot mod` of two
+//! and `clamp` of three. `#` starts a comment. A unit ball is
+//! `x*x + y*y + z*z - 1`, and a slice of the Mandelbrot set
+//!
+//! ```text
+//! a = 0; b = 0;
+//! repeat {
+//!     t = a*a - b*b + x;
+//!     b = 2*a*b + y;
+//!     a = t;
+//!     escape if a*a + b*b > 4;
+//! }
+//! -1
+//! ```
+//!
+//! Programs are parsed once and compiled to closures over a slot per
+//! variable, which the voxelizer calls for every cell.
+
+use std::cell::Cell;
+use std::fmt;
+use std::sync::Arc;
+
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{char, digit1, multispace1, not_line_ending};
+use nom::combinator::{all_consuming, cut, map, map_res, not, opt, recognize, value};
+use nom::multi::{many0, separated_list0};
+use nom::number::complete::recognize_float;
+use nom::sequence::{delimited, pair, preceded, terminated};
+use nom::{IResult, Parser};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::escape::Sampling;
+use crate::lattice::Lattice;
+
+/// Variables holding the cell centre, in the first slots.
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
+
+/// Named constants, read when no variable of the name is assigned.
+const CONSTANTS: [(&str, f64); 2] = [("pi", std::f64::consts::PI), ("tau", std::f64::consts::TAU)];
+
+/// Words that cannot name variables.
+const KEYWORDS: [&str; 3] = ["repeat", "escape", "if"];
+
+/// Deepest nesting of brackets, unary operators, exponents and `repeat`
+/// blocks, so that a pathological program fails to parse instead of
+/// overflowing the stack when parsed, compiled or run.
+const MAX_NESTING: usize = 64;
+
+thread_local! {
+    static NESTING: Cell<usize> = const { Cell::new(0) };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f64),
+    Name(String),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    /// Operands of one precedence level, applied left to right; flat so
+    /// that long sums nest no deeper than short ones.
+    Chain(Box<Expr>, Vec<(BinaryOp, Expr)>),
+    Power(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Statement {
+    Assign(String, Expr),
+    /// A fixed count, or the job's depth when None.
+    Repeat(Option<u32>, Vec<Statement>),
+    Escape(Expr),
+}
+
+type PResult<'a, T> = IResult<&'a str, T>;
+
+/// Runs `parser` one level deeper, failing past [`MAX_NESTING`].
+fn nested<'a, T>(input: &'a str, parser: impl FnOnce(&'a str) -> PResult<'a, T>) -> PResult<'a, T> {
+    let depth = NESTING.get() + 1;
+    if depth > MAX_NESTING {
+        return Err(nom::Err::Failure(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::TooLarge,
+        )));
+    }
+    NESTING.set(depth);
+    let result = parser(input);
+    NESTING.set(depth - 1);
+    result
+}
+
+/// Whitespace and `#` comments.
+fn space(input: &str) -> PResult<'_, ()> {
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value((), pair(char('#'), not_line_ending)),
+        ))),
+    )
+    .parse(input)
+}
+
+fn token<'a>(
+    text: &'static str,
+) -> impl Parser<&'a str, Output = &'a str, Error = nom::error::Error<&'a str>> {
+    preceded(space, tag(text))
+}
+
+fn identifier(input: &str) -> PResult<'_, &str> {
+    preceded(
+        space,
+        recognize(pair(
+            take_while1(|c: char| c.is_ascii_alphabetic() || c == '_'),
+            take_while(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+        )),
+    )
+    .parse(input)
+}
+
+fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> PResult<'a, ()> {
+    move |input| {
+        let (rest, name) = identifier(input)?;
+        if name == word {
+            Ok((rest, ()))
+        } else {
+            Err(nom::Err::Error(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::Tag,
+            )))
+        }
+    }
+}
+
+/// A variable or function name: an identifier that is not a keyword.
+fn name(input: &str) -> PResult<'_, String> {
+    let (rest, name) = identifier(input)?;
+    if KEYWORDS.contains(&name) {
+        Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Tag,
+        )))
+    } else {
+        Ok((rest, name.to_string()))
+    }
+}
+
+fn atom(input: &str) -> PResult<'_, Expr> {
+    alt((
+        // Not nom's `double`, which would read `inf` and `nan` as numbers
+        // and the start of names such as `inside` with them.
+        map_res(preceded(space, recognize_float), |n: &str| {
+            n.parse().map(Expr::Number)
+        }),
+        map(
+            pair(
+                name,
+                opt(delimited(
+                    token("("),
+                    cut(separated_list0(token(","), expression)),
+                    cut(token(")")),
+                )),
+            ),
+            |(name, args)| match args {
+                Some(args) => Expr::Call(name, args),
+                None => Expr::Name(name),
+            },
+        ),
+        delimited(token("("), cut(expression), cut(token(")"))),
+    ))
+    .parse(input)
+}
+
+/// `^`, right associative and binding tighter than unary minus, so that
+/// `-x^2` is `-(x^2)`.
+fn power(input: &str) -> PResult<'_, Expr> {
+    let (rest, base) = atom(input)?;
+    match preceded(token("^"), cut(unary)).parse(rest) {
+        Ok((rest, exponent)) => Ok((rest, Expr::Power(Box::new(base), Box::new(exponent)))),
+        Err(nom::Err::Error(_)) => Ok((rest, base)),
+        Err(e) => Err(e),
+    }
+}
+
+fn unary(input: &str) -> PResult<'_, Expr> {
+    nested(input, |input| {
+        alt((
+            map(preceded(token("-"), cut(unary)), |e| {
+                Expr::Negate(Box::new(e))
+            }),
+            map(preceded(token("!"), cut(unary)), |e| Expr::Not(Box::new(e))),
+            power,
+        ))
+        .parse(input)
+    })
+}
+
+/// One left-associative level of binary operators over `operand`. Longer
+/// operators come first in `ops` so that `<=` is not read as `<`.
+fn binary<'a>(
+    input: &'a str,
+    ops: &[(&'static str, BinaryOp)],
+    operand: fn(&'a str) -> PResult<'a, Expr>,
+) -> PResult<'a, Expr> {
+    let (mut input, first) = operand(input)?;
+    let mut rest = Vec::new();
+    'operators: loop {
+        for &(text, op) in ops {
+            if let Ok((after, _)) = token(text).parse(input) {
+                let (after, rhs) = cut(operand).parse(after)?;
+                rest.push((op, rhs));
+                input = after;
+                continue 'operators;
+            }
+        }
+        return Ok(if rest.is_empty() {
+            (input, first)
+        } else {
+            (input, Expr::Chain(Box::new(first), rest))
+        });
+    }
+}
+
+fn product(input: &str) -> PResult<'_, Expr> {
+    use BinaryOp::*;
+    binary(
+        input,
+        &[("*", Multiply), ("/", Divide), ("%", Remainder)],
+        unary,
+    )
+}
+
+fn sum(input: &str) -> PResult<'_, Expr> {
+    binary(
+        input,
+        &[("+", BinaryOp::Add), ("-", BinaryOp::Subtract)],
+        product,
+    )
+}
+
+fn comparison(input: &str) -> PResult<'_, Expr> {
+    use BinaryOp::*;
+    let ops = [
+        ("<=", LessEqual),
+        (">=", GreaterEqual),
+        ("==", Equal),
+        ("!=", NotEqual),
+        ("<", Less),
+        (">", Greater),
+    ];
+    binary(input, &ops, sum)
+}
+
+fn conjunction(input: &str) -> PResult<'_, Expr> {
+    binary(input, &[("&&", BinaryOp::And)], comparison)
+}
+
+fn expression(input: &str) -> PResult<'_, Expr> {
+    binary(input, &[("||", BinaryOp::Or)], conjunction)
+}
+
+fn statement(input: &str) -> PResult<'_, Statement> {
+    nested(input, statement_at)
+}
+
+fn statement_at(input: &str) -> PResult<'_, Statement> {
+    alt((
+        map(
+            preceded(
+                keyword("repeat"),
+                cut(pair(
+                    opt(preceded(space, digit1)),
+                    delimited(token("{"), many0(statement), token("}")),
+                )),
+            ),
+            |(count, body): (Option<&str>, _)| {
+                Statement::Repeat(count.map(|n| n.parse().unwrap_or(u32::MAX)), body)
+            },
+        ),
+        map(
+            preceded(
+                keyword("escape"),
+                cut(delimited(keyword("if"), expression, token(";"))),
+            ),
+            Statement::Escape,
+        ),
+        map(
+            pair(
+                terminated(name, pair(token("="), not(char('=')))),
+                cut(terminated(expression, token(";"))),
+            ),
+            |(name, e)| Statement::Assign(name, e),
+        ),
+    ))
+    .parse(input)
+}
+
+fn program(input: &str) -> PResult<'_, (Vec<Statement>, Expr)> {
+    all_consuming(terminated(
+        pair(many0(statement), cut(expression)),
+        cut(pair(opt(token(";")), space)),
+    ))
+    .parse(input)
+}
+
+/// Evaluates an expression over the variable slots.
+type Value = Box<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+/// Runs a statement over the variable slots with the repeat count; false
+/// once the program escapes.
+type Step = Box<dyn Fn(&mut [f64], u32) -> bool + Send + Sync>;
+
+/// Resolves names to slots as statements are compiled in order.
+struct Compiler {
+    variables: Vec<String>,
+}
+
+fn truth(b: bool) -> f64 {
+    b as u8 as f64
+}
+
+/// `a op b`, evaluating `b` only if `&&` or `||` need it.
+fn apply(op: BinaryOp, a: f64, b: impl FnOnce() -> f64) -> f64 {
+    match op {
+        BinaryOp::Or => truth(a != 0.0 || b() != 0.0),
+        BinaryOp::And => truth(a != 0.0 && b() != 0.0),
+        BinaryOp::Less => truth(a < b()),
+        BinaryOp::LessEqual => truth(a <= b()),
+        BinaryOp::Greater => truth(a > b()),
+        BinaryOp::GreaterEqual => truth(a >= b()),
+        BinaryOp::Equal => truth(a == b()),
+        BinaryOp::NotEqual => truth(a != b()),
+        BinaryOp::Add => a + b(),
+        BinaryOp::Subtract => a - b(),
+        BinaryOp::Multiply => a * b(),
+        BinaryOp::Divide => a / b(),
+        BinaryOp::Remainder => a.rem_euclid(b()),
+    }
+}
+
+impl Compiler {
+    fn slot(&self, name: &str) -> Option<usize> {
+        self.variables.iter().position(|v| v == name)
+    }
+
+    fn expression(&self, e: &Expr) -> Result<Value> {
+        Ok(match e {
+            &Expr::Number(n) => Box::new(move |_| n),
+            Expr::Name(name) => match self.slot(name) {
+                Some(slot) => Box::new(move |vars| vars[slot]),
+                None => match CONSTANTS.iter().find(|(c, _)| c == name) {
+                    Some(&(_, n)) => Box::new(move |_| n),
+                    None => return Err(invalid(format!("`{name}` is used before it is assigned"))),
+                },
+            },
+            Expr::Negate(e) => {
+                let e = self.expression(e)?;
+                Box::new(move |vars| -e(vars))
+            }
+            Expr::Not(e) => {
+                let e = self.expression(e)?;
+                Box::new(move |vars| truth(e(vars) == 0.0))
+            }
+            Expr::Chain(first, rest) => {
+                let first = self.expression(first)?;
+                let rest = rest
+                    .iter()
+                    .map(|(op, e)| Ok((*op, self.expression(e)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                Box::new(move |v| {
+                    rest.iter()
+                        .fold(first(v), |a, (op, b)| apply(*op, a, || b(v)))
+                })
+            }
+            Expr::Power(a, b) => {
+                let (a, b) = (self.expression(a)?, self.expression(b)?);
+                Box::new(move |v| a(v).powf(b(v)))
+            }
+            Expr::Call(function, args) => self.call(function, args)?,
+        })
+    }
+
+    fn call(&self, function: &str, args: &[Expr]) -> Result<Value> {
+        let args = args
+            .iter()
+            .map(|arg| self.expression(arg))
+            .collect::<Result<Vec<_>>>()?;
+        let unary: Option<fn(f64) -> f64> = match function {
+            "abs" => Some(f64::abs),
+            "sqrt" => Some(f64::sqrt),
+            "exp" => Some(f64::exp),
+            "ln" => Some(f64::ln),
+            "sin" => Some(f64::sin),
+            "cos" => Some(f64::cos),
+            "tan" => Some(f64::tan),
+            "asin" => Some(f64::asin),
+            "acos" => Some(f64::acos),
+            "atan" => Some(f64::atan),
+            "sinh" => Some(f64::sinh),
+            "cosh" => Some(f64::cosh),
+            "tanh" => Some(f64::tanh),
+            "floor" => Some(f64::floor),
+            "ceil" => Some(f64::ceil),
+            "fract" => Some(|x| x - x.floor()),
+            _ => None,
+        };
+        let binary: Option<fn(f64, f64) -> f64> = match function {
+            "atan2" => Some(f64::atan2),
+            "min" => Some(f64::min),
+            "max" => Some(f64::max),
+            "pow" => Some(f64::powf),
+            "hypot" => Some(f64::hypot),
+            "mod" => Some(f64::rem_euclid),
+            _ => None,
+        };
+        let arity = match (unary, binary, function) {
+            (Some(_), _, _) => 1,
+            (_, Some(_), _) => 2,
+            (_, _, "clamp") => 3,
+            _ => return Err(invalid(format!("unknown function `{function}`"))),
+        };
+        if args.len() != arity {
+            return Err(invalid(format!(
+                "`{function}` takes {arity} argument{}, got {}",
+                if arity == 1 { "" } else { "s" },
+                args.len()
+            )));
+        }
+        let mut args = args.into_iter();
+        let mut next = || args.next().unwrap();
+        Ok(match (unary, binary) {
+            (Some(f), _) => {
+                let a = next();
+                Box::new(move |v| f(a(v)))
+            }
+            (_, Some(f)) => {
+                let (a, b) = (next(), next());
+                Box::new(move |v| f(a(v), b(v)))
+            }
+            _ => {
+                let (x, lo, hi) = (next(), next(), next());
+                Box::new(move |v| x(v).max(lo(v)).min(hi(v)))
+            }
+        })
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Result<Vec<Step>> {
+        statements.iter().map(|s| self.statement(s)).collect()
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<Step> {
+        Ok(match statement {
+            Statement::Assign(name, e) => {
+                let e = self.expression(e)?;
+                let slot = match self.slot(name) {
+                    Some(slot) => slot,
+                    None => {
+                        self.variables.push(name.clone());
+                        self.variables.len() - 1
+                    }
+                };
+                Box::new(move |vars, _| {
+                    vars[slot] = e(vars);
+                    true
+                })
+            }
+            Statement::Repeat(count, body) => {
+                let body = self.statements(body)?;
+                let count = *count;
+                Box::new(move |vars, depth| {
+                    (0..count.unwrap_or(depth)).all(|_| body.iter().all(|step| step(vars, depth)))
+                })
+            }
+            Statement::Escape(e) => {
+                let e = self.expression(e)?;
+                Box::new(move |vars, _| e(vars) == 0.0)
+            }
+        })
+    }
+}
+
+fn invalid(reason: String) -> Error {
+    Error::InvalidJob(format!("implicit program: {reason}"))
+}
+
+/// A parsed and compiled program.
+#[derive(Clone)]
+pub struct Program {
+    source: String,
+    slots: usize,
+    steps: Arc<[Step]>,
+    result: Arc<Value>,
+}
+
+impl fmt::Debug for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Program").field(&self.source).finish()
+    }
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Program {
+    /// Parses and compiles `source`, with the line and column of the first
+    /// syntax error.
+    pub fn parse(source: &str) -> Result<Self> {
+        let (statements, result) = match program(source) {
+            Ok((_, parsed)) => parsed,
+            Err(nom::Err::Error(e) | nom::Err::Failure(e))
+                if e.code == nom::error::ErrorKind::TooLarge =>
+            {
+                return Err(invalid(format!("nested more than {MAX_NESTING} deep")));
+            }
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                let offset = source.len() - e.input.len();
+                let line = source[..offset].matches('\n').count() + 1;
+                let column = offset - source[..offset].rfind('\n').map_or(0, |i| i + 1) + 1;
+                let near = e.input.trim_start().lines().next().unwrap_or("");
+                let near: String = near.chars().take(16).collect();
+                return Err(invalid(if near.is_empty() {
+                    format!("unexpected end at line {line}, column {column}")
+                } else {
+                    format!("syntax error at line {line}, column {column}, near `{near}`")
+                }));
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers"),
+        };
+        let mut compiler = Compiler {
+            variables: AXES.iter().map(|axis| axis.to_string()).collect(),
+        };
+        let steps = compiler.statements(&statements)?;
+        let result = compiler.expression(&result)?;
+        Ok(Program {
+            source: source.to_string(),
+            slots: compiler.variables.len(),
+            steps: steps.into(),
+            result: Arc::new(result),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The program's final value at `point`, with `repeat` blocks run
+    /// `depth` times, or None if it escaped.
+    pub fn eval(&self, point: [f64; 4], depth: u32) -> Option<f64> {
+        let mut vars = vec![0.0; self.slots];
+        vars[..4].copy_from_slice(&point);
+        let finished = self.steps.iter().all(|step| step(&mut vars, depth));
+        finished.then(|| (self.result)(&vars))
+    }
+}
+
+/// A solid or escape-time fractal given by a [`Program`], voxelized into the
+/// same lattices subdivision rules produce.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Implicit {
+    pub program: Program,
+    pub sampling: Sampling,
+}
+
+impl Implicit {
+    /// Half the width of the sampled box, 1.5 unless set.
+    pub fn radius(&self) -> f64 {
+        self.sampling.radius.unwrap_or(1.5)
+    }
+
+    /// Whether the program keeps `point` with `repeat` blocks run `depth`
+    /// times; NaN results count as outside.
+    pub fn contains(&self, point: [f64; 4], depth: u32) -> bool {
+        self.program.eval(point, depth).is_some_and(|v| v <= 0.0)
+    }
+
+    /// Runs the program at every cell centre.
+    #[tracing::instrument(name = "generate", skip_all, fields(depth = depth))]
+    pub fn voxelize<const D: usize>(&self, depth: u32, cancel: &CancelToken) -> Result<Lattice<D>> {
+        Lattice::from_fn([self.sampling.resolution; D], cancel, |p| {
+            self.contains(self.sampling.cell_centre(p, self.radius()), depth)
+        })
+    }
+}
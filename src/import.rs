@@ -0,0 +1,388 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::{Boundary, Lattice3};
+use crate::mesh::{Mesh, Polygons};
+use crate::transform::{Affine, Transform};
+
+/// An external model voxelized into a lattice: fractal `import`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Import {
+    /// An STL or OBJ model.
+    pub model: PathBuf,
+    /// Model units per lattice cell.
+    pub cell_size: f64,
+    #[serde(default)]
+    pub voxelizer: Voxelizer,
+}
+
+/// How a model's inside is told from its outside.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Voxelizer {
+    /// Fills cells whose centres lie inside, counting surface crossings
+    /// along z. Exact for watertight models.
+    #[default]
+    Parity,
+    /// Fills the cells the surface passes through and everything the
+    /// outside cannot reach from the grid's walls. Tolerates holes and
+    /// self-intersections smaller than a cell, and keeps walls thinner
+    /// than one.
+    Fill,
+}
+
+/// Most cells a model's grid may have, 2048³: a gigabyte of bits.
+const MAX_GRID_CELLS: u64 = 1 << 33;
+
+/// A loaded model and the grid laid over it.
+pub struct ModelGrid {
+    pub shape: [usize; 3],
+    /// The model-space corner of cell (0, 0, 0).
+    pub origin: [f64; 3],
+    mesh: Mesh,
+}
+
+impl Import {
+    /// Reads the model and lays a grid of `cell_size` cells over its
+    /// bounding box.
+    pub fn grid(&self) -> Result<ModelGrid> {
+        if !(self.cell_size > 0.0 && self.cell_size.is_finite()) {
+            return Err(Error::InvalidJob("model cell size must be positive".into()));
+        }
+        let mesh = read_mesh(&self.model)?;
+        if mesh.face_count() == 0 {
+            return Err(Error::InvalidJob(format!(
+                "model `{}` has no triangles",
+                self.model.display()
+            )));
+        }
+        if mesh.vertices.iter().flatten().any(|c| !c.is_finite()) {
+            return Err(Error::InvalidJob(format!(
+                "model `{}` has a vertex that is not a finite number",
+                self.model.display()
+            )));
+        }
+        let (min, max) = bounds(&mesh);
+        let sides: [f64; 3] =
+            std::array::from_fn(|axis| ((max[axis] - min[axis]) / self.cell_size).ceil().max(1.0));
+        // Infinite when a side overflows, which is refused as well.
+        let cells: f64 = sides.iter().product();
+        if cells > MAX_GRID_CELLS as f64 {
+            return Err(Error::InvalidJob(format!(
+                "model `{}` is too large for cells of {}: its grid would have more \
+                 than the {MAX_GRID_CELLS} cells allowed",
+                self.model.display(),
+                self.cell_size
+            )));
+        }
+        let shape = sides.map(|side| side as usize);
+        Ok(ModelGrid {
+            shape,
+            origin: min,
+            mesh,
+        })
+    }
+
+    /// Places lattice cells over the model, so outputs line up with it.
+    pub fn placement(&self, grid: &ModelGrid) -> Affine {
+        Affine::from_transforms(&[
+            Transform::Scale(self.cell_size),
+            Transform::Translate(grid.origin),
+        ])
+    }
+
+    /// The cells of `grid` inside the model.
+    pub fn voxelize(&self, grid: &ModelGrid, cancel: &CancelToken) -> Result<Lattice3> {
+        let method = match self.voxelizer {
+            Voxelizer::Parity => voxelize,
+            Voxelizer::Fill => voxelize_fill,
+        };
+        method(&grid.mesh, grid.shape, grid.origin, self.cell_size, cancel)
+    }
+}
+
+/// Reads an STL or OBJ model, by extension.
+pub fn read_mesh(path: &Path) -> Result<Mesh> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("stl") => read_stl(path),
+        Some("obj") => read_obj(path),
+        _ => Err(Error::InvalidJob(format!(
+            "cannot read `{}`: expected an .stl or .obj model",
+            path.display()
+        ))),
+    }
+}
+
+/// Reads a binary or ASCII STL file, welding corners with identical
+/// coordinates into shared vertices.
+pub fn read_stl(path: &Path) -> Result<Mesh> {
+    parse_stl(&std::fs::read(path)?, &path.display().to_string())
+}
+
+/// Parses the bytes of an STL file, naming it `name` in errors.
+pub fn parse_stl(bytes: &[u8], name: &str) -> Result<Mesh> {
+    let invalid = |reason: &str| Error::InvalidJob(format!("cannot read `{name}`: {reason}"));
+    let binary_len = bytes
+        .get(80..84)
+        .map(|count| 84 + 50 * u32::from_le_bytes(count.try_into().unwrap()) as usize);
+    let mut corners = Vec::new();
+    if binary_len == Some(bytes.len()) {
+        for record in bytes[84..].chunks_exact(50) {
+            let value =
+                |i: usize| f32::from_le_bytes(record[i * 4..i * 4 + 4].try_into().unwrap()) as f64;
+            // Skip the stored normal, which is recomputed from the winding.
+            for corner in 1..4 {
+                corners.push(std::array::from_fn(|axis| value(corner * 3 + axis)));
+            }
+        }
+    } else {
+        let text = std::str::from_utf8(bytes).map_err(|_| invalid("not an STL file"))?;
+        if !text.trim_start().starts_with("solid") {
+            return Err(invalid("not an STL file"));
+        }
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            if words.next() != Some("vertex") {
+                continue;
+            }
+            let mut corner = [0.0; 3];
+            for c in &mut corner {
+                *c = words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .ok_or_else(|| invalid("malformed vertex"))?;
+            }
+            corners.push(corner);
+        }
+        if corners.len() % 3 != 0 {
+            return Err(invalid("facets must have three vertices"));
+        }
+    }
+    Ok(weld(&corners))
+}
+
+/// Reads the vertices and faces of a Wavefront OBJ file, splitting
+/// polygons into fans of triangles. Texture coordinates, normals, groups
+/// and materials are ignored.
+pub fn read_obj(path: &Path) -> Result<Mesh> {
+    parse_obj(&std::fs::read_to_string(path)?, &path.display().to_string())
+}
+
+/// Parses the text of an OBJ file, naming it `name` in errors.
+pub fn parse_obj(text: &str, name: &str) -> Result<Mesh> {
+    let invalid = |line: usize, reason: &str| {
+        Error::InvalidJob(format!("cannot read `{name}`: line {}: {reason}", line + 1))
+    };
+    let mut vertices: Vec<[f64; 3]> = Vec::new();
+    let mut corners = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                let mut vertex = [0.0; 3];
+                for c in &mut vertex {
+                    *c = words
+                        .next()
+                        .and_then(|w| w.parse().ok())
+                        .ok_or_else(|| invalid(number, "malformed vertex"))?;
+                }
+                vertices.push(vertex);
+            }
+            Some("f") => {
+                // Indices count from 1, or back from the latest vertex when
+                // negative.
+                let face = words
+                    .map(|word| {
+                        let index: i64 = word
+                            .split('/')
+                            .next()
+                            .and_then(|i| i.parse().ok())
+                            .ok_or_else(|| invalid(number, "malformed face"))?;
+                        let index = match index {
+                            i if i > 0 => i - 1,
+                            i => vertices.len() as i64 + i,
+                        };
+                        usize::try_from(index)
+                            .ok()
+                            .and_then(|i| vertices.get(i).copied())
+                            .ok_or_else(|| invalid(number, "face refers to a missing vertex"))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if face.len() < 3 {
+                    return Err(invalid(number, "faces must have at least three vertices"));
+                }
+                for pair in face[1..].windows(2) {
+                    corners.extend([face[0], pair[0], pair[1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(weld(&corners))
+}
+
+/// Builds an indexed triangle mesh from a flat list of triangle corners.
+fn weld(corners: &[[f64; 3]]) -> Mesh {
+    let mut index = HashMap::new();
+    let mut vertices = Vec::new();
+    let mut vertex = |p: [f64; 3]| {
+        *index.entry(p.map(f64::to_bits)).or_insert_with(|| {
+            vertices.push(p);
+            vertices.len() as u32 - 1
+        })
+    };
+    let triangles = corners
+        .chunks_exact(3)
+        .map(|t| [vertex(t[0]), vertex(t[1]), vertex(t[2])])
+        .collect();
+    Mesh {
+        vertices,
+        faces: Polygons::Triangles(triangles),
+    }
+}
+
+/// The smallest and largest corner of the mesh's bounding box.
+pub fn bounds(mesh: &Mesh) -> ([f64; 3], [f64; 3]) {
+    let mut min = [f64::INFINITY; 3];
+    let mut max = [f64::NEG_INFINITY; 3];
+    for v in &mesh.vertices {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(v[axis]);
+            max[axis] = max[axis].max(v[axis]);
+        }
+    }
+    (min, max)
+}
+
+/// Fills the cells of a `shape` lattice, cell `p` spanning `origin + p *
+/// cell_size` onwards, whose centres lie inside the closed mesh.
+///
+/// Casts a ray along z through every column of cell centres and fills
+/// between alternate surface crossings, so the mesh must be watertight.
+/// Rays are nudged off the cell centres by a tiny amount so that they do
+/// not pass exactly through the shared edges of grid-aligned models.
+#[tracing::instrument(name = "voxelize", skip_all, fields(triangles = mesh.face_count()))]
+pub fn voxelize(
+    mesh: &Mesh,
+    shape: [usize; 3],
+    origin: [f64; 3],
+    cell_size: f64,
+    cancel: &CancelToken,
+) -> Result<Lattice3> {
+    let [nx, ny, nz] = shape;
+    let nudge = [1.234_567e-7, 2.345_678e-7];
+    let mut columns: Vec<Vec<f64>> = vec![Vec::new(); nx * ny];
+    for (i, triangle) in mesh.triangles().iter().enumerate() {
+        if i % 4096 == 0 {
+            cancel.check()?;
+        }
+        // Corners in cell units.
+        let [a, b, c] = triangle.map(|v| {
+            let p = mesh.vertices[v as usize];
+            std::array::from_fn::<f64, 3, _>(|axis| (p[axis] - origin[axis]) / cell_size)
+        });
+        let area = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+        if area == 0.0 {
+            continue;
+        }
+        let column_range = |axis: usize, extent: usize| {
+            let low = a[axis].min(b[axis]).min(c[axis]) - 0.5;
+            let high = a[axis].max(b[axis]).max(c[axis]) - 0.5;
+            let low = low.ceil().max(0.0) as usize;
+            let high = (high.floor() + 1.0).clamp(0.0, extent as f64) as usize;
+            low..high
+        };
+        for y in column_range(1, ny) {
+            for x in column_range(0, nx) {
+                let p = [x as f64 + 0.5 + nudge[0], y as f64 + 0.5 + nudge[1]];
+                // Barycentric weight of the corner opposite edge `st`.
+                let edge = |s: [f64; 3], t: [f64; 3]| {
+                    ((s[0] - p[0]) * (t[1] - p[1]) - (s[1] - p[1]) * (t[0] - p[0])) / area
+                };
+                let (wa, wb, wc) = (edge(b, c), edge(c, a), edge(a, b));
+                if wa < 0.0 || wb < 0.0 || wc < 0.0 {
+                    continue;
+                }
+                columns[x + nx * y].push(wa * a[2] + wb * b[2] + wc * c[2]);
+            }
+        }
+    }
+    let mut lattice = Lattice3::new(shape);
+    for (i, crossings) in columns.iter_mut().enumerate() {
+        if i % 4096 == 0 {
+            cancel.check()?;
+        }
+        crossings.sort_by(f64::total_cmp);
+        let (x, y) = (i % nx, i / nx);
+        for pair in crossings.chunks_exact(2) {
+            let low = (pair[0] - 0.5).ceil().max(0.0) as usize;
+            let high = ((pair[1] - 0.5).floor() + 1.0).clamp(0.0, nz as f64) as usize;
+            for z in low..high {
+                lattice.set([x, y, z], true);
+            }
+        }
+    }
+    Ok(lattice)
+}
+
+/// Like [`voxelize`], but fills every cell the surface passes through,
+/// then every cell the outside cannot reach from the grid's walls by face
+/// steps. Gaps in the mesh narrower than a cell do not let the outside in.
+#[tracing::instrument(name = "voxelize", skip_all, fields(triangles = mesh.face_count()))]
+pub fn voxelize_fill(
+    mesh: &Mesh,
+    shape: [usize; 3],
+    origin: [f64; 3],
+    cell_size: f64,
+    cancel: &CancelToken,
+) -> Result<Lattice3> {
+    let mut surface = Lattice3::new(shape);
+    for (i, triangle) in mesh.triangles().iter().enumerate() {
+        if i % 4096 == 0 {
+            cancel.check()?;
+        }
+        let [a, b, c] = triangle.map(|v| {
+            let p = mesh.vertices[v as usize];
+            std::array::from_fn::<f64, 3, _>(|axis| (p[axis] - origin[axis]) / cell_size)
+        });
+        // Sample the triangle under half a cell apart, so the cells hit
+        // join up corner to corner and leave no face-wide gap.
+        let longest = [(a, b), (b, c), (c, a)]
+            .iter()
+            .map(|(s, t)| (0..3).map(|axis| (t[axis] - s[axis]).powi(2)).sum::<f64>())
+            .fold(0.0, f64::max)
+            .sqrt();
+        let steps = (2.0 * longest).ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            for j in 0..=steps - i {
+                let (u, v) = (i as f64 / steps as f64, j as f64 / steps as f64);
+                let cell = std::array::from_fn(|axis| {
+                    let p = a[axis] + u * (b[axis] - a[axis]) + v * (c[axis] - a[axis]);
+                    (p.floor().max(0.0) as usize).min(shape[axis] - 1)
+                });
+                surface.set(cell, true);
+            }
+        }
+    }
+    cancel.check()?;
+    let empty = surface.complement();
+    let components = empty.components(Boundary::Open);
+    let on_wall = |p: [usize; 3]| (0..3).any(|axis| p[axis] == 0 || p[axis] + 1 == shape[axis]);
+    let outside: HashSet<u32> = empty
+        .iter()
+        .filter(|&p| on_wall(p))
+        .map(|p| components.labels[empty.index(p)])
+        .collect();
+    Lattice3::from_fn(shape, cancel, |p| {
+        !outside.contains(&components.labels[empty.index(p)])
+    })
+}
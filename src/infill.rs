@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::import::{Import, ModelGrid, Voxelizer};
+use crate::lattice::{Boundary, Lattice3};
+use crate::morphology::{erode, Element, Morphology, Operation};
+use crate::rule::Rule;
+use crate::transform::Affine;
+
+/// Fills an external model with a fractal: the model's interior is
+/// voxelized and intersected with the rule's lattice, repeated as often as
+/// needed, under a solid skin.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Infill {
+    /// An STL or OBJ model.
+    pub model: PathBuf,
+    /// The built-in 3D rule filling the model.
+    #[serde(default = "default_rule")]
+    pub rule: String,
+    /// Model units per lattice cell.
+    pub cell_size: f64,
+    /// Thickness of the solid skin kept under the model's surface, in
+    /// cells; zero exposes the fractal at the surface.
+    #[serde(default = "default_shell")]
+    pub shell: u32,
+    #[serde(default)]
+    pub voxelizer: Voxelizer,
+}
+
+fn default_rule() -> String {
+    "menger".to_string()
+}
+
+fn default_shell() -> u32 {
+    1
+}
+
+impl Infill {
+    pub fn rule(&self) -> Result<Rule> {
+        Rule::by_name(&self.rule, 3).ok_or_else(|| Error::UnknownFractal(self.rule.clone()))
+    }
+
+    /// The model, voxelized as it is filled.
+    pub fn import(&self) -> Import {
+        Import {
+            model: self.model.clone(),
+            cell_size: self.cell_size,
+            voxelizer: self.voxelizer,
+        }
+    }
+
+    /// Reads the model and lays a grid of `cell_size` cells over its
+    /// bounding box.
+    pub fn grid(&self) -> Result<ModelGrid> {
+        self.import().grid()
+    }
+
+    /// Places lattice cells over the model, so outputs line up with it.
+    pub fn placement(&self, grid: &ModelGrid) -> Affine {
+        self.import().placement(grid)
+    }
+
+    /// The infilled model, with the rule at `depth` repeating every
+    /// `rule.side(depth)` cells from the grid's corner.
+    #[tracing::instrument(name = "infill", skip_all, fields(rule = %self.rule, depth = depth))]
+    pub fn generate(&self, grid: &ModelGrid, depth: u32, cancel: &CancelToken) -> Result<Lattice3> {
+        let rule = self.rule()?;
+        let pattern = Lattice3::generate_cancellable(&rule, depth, cancel)?;
+        let model = self.import().voxelize(grid, cancel)?;
+        let core = match self.shell {
+            0 => model.clone(),
+            shell => {
+                let cube = Morphology::new(Operation::Erode, Element::Cube, shell).offsets::<3>();
+                erode(&model, &cube, Boundary::Open, cancel)?
+            }
+        };
+        let period = pattern.shape();
+        Lattice3::from_fn(grid.shape, cancel, |p| {
+            model.get(p) && (!core.get(p) || pattern.get(std::array::from_fn(|a| p[a] % period[a])))
+        })
+    }
+}
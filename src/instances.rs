@@ -0,0 +1,147 @@
+//! A bare binary list of cube instances, one per filled cell, for
+//! renderers that draw the cells as instanced cubes without a mesh format
+//! in between.
+//!
+//! All values are little-endian:
+//!
+//! | offset | size | field                                           |
+//! |--------|------|-------------------------------------------------|
+//! | 0      | 4    | magic `FSIN`                                    |
+//! | 4      | 1    | version, 1                                      |
+//! | 5      | 1    | bytes per value: 4 for `f32`, 2 for `f16`/`u16` |
+//! | 6      | 1    | 1 when values are quantized, else 0             |
+//! | 7      | 1    | zero                                            |
+//! | 8      | 8    | instance count, `u64`                           |
+//! | 16     | 32   | quantized only: offset and step of each value   |
+//! |        |      | per instance: centre x, y, z and edge length    |
+//!
+//! Quantized values are `u16`, and value `i` of an instance is
+//! `offset[i] + step[i] * q` with both `f32`.
+//!
+//! `examples/load_instances.rs` reads the file back.
+
+use std::io::Write;
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::export::{ExportOptions, Precision};
+use crate::lattice::Lattice3;
+
+pub const MAGIC: [u8; 4] = *b"FSIN";
+pub const VERSION: u8 = 1;
+
+/// How many cells are written between cancellation checks.
+const CANCEL_INTERVAL: usize = 4096;
+
+/// Writes a cube per filled cell of `lattice`, for every tiled copy,
+/// placed by the export transform, at the options' precision. The edge
+/// length is the placement's uniform scale; rotation and shear are not
+/// kept.
+pub fn write(
+    lattice: &Lattice3,
+    options: &ExportOptions,
+    out: &mut impl Write,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let extent = lattice.shape().map(|side| side as f64);
+    let transform = options.transform_for(extent);
+    let placements: Vec<_> = options
+        .tiling
+        .instances(extent)
+        .iter()
+        .map(|instance| transform.then(instance))
+        .collect();
+    let instances = || {
+        placements.iter().flat_map(|placement| {
+            let scale = placement.scale_factor();
+            lattice.iter().map(move |cell| {
+                let [x, y, z] = placement.apply(cell.map(|c| c as f64 + 0.5));
+                [x, y, z, scale]
+            })
+        })
+    };
+    let count = lattice.count() * placements.len();
+    let (width, quantized) = match options.precision {
+        Precision::F32 => (4, false),
+        Precision::F16 => (2, false),
+        Precision::Q16 => (2, true),
+    };
+    out.write_all(&MAGIC)?;
+    out.write_all(&[VERSION, width, quantized as u8, 0])?;
+    out.write_all(&(count as u64).to_le_bytes())?;
+    // Each value's range spread over the 16-bit grid; a value that never
+    // changes gets step 0.
+    let (mut offset, mut step) = ([0.0; 4], [0.0; 4]);
+    if quantized && count > 0 {
+        let mut high = [f64::NEG_INFINITY; 4];
+        offset = [f64::INFINITY; 4];
+        for (i, instance) in instances().enumerate() {
+            if i % CANCEL_INTERVAL == 0 {
+                cancel.check()?;
+            }
+            for ((low, high), value) in offset.iter_mut().zip(&mut high).zip(instance) {
+                *low = low.min(value);
+                *high = high.max(value);
+            }
+        }
+        step = std::array::from_fn(|a| (high[a] - offset[a]) / 65535.0);
+    }
+    if quantized {
+        for value in offset.iter().chain(&step) {
+            out.write_all(&(*value as f32).to_le_bytes())?;
+        }
+    }
+    for (i, instance) in instances().enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        for (a, value) in instance.into_iter().enumerate() {
+            match options.precision {
+                Precision::F32 => out.write_all(&(value as f32).to_le_bytes())?,
+                Precision::F16 => out.write_all(&f16_bits(value as f32).to_le_bytes())?,
+                Precision::Q16 => {
+                    let q = if step[a] > 0.0 {
+                        ((value - offset[a]) / step[a]).round() as u16
+                    } else {
+                        0
+                    };
+                    out.write_all(&q.to_le_bytes())?
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The IEEE 754 half-precision bits nearest `value`, ties to even;
+/// overflow gives infinity.
+pub fn f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let biased = exponent - 127 + 15;
+    if biased >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // Drop `shift` low bits of the significand, rounding to nearest even;
+    // a carry out of the mantissa rightly bumps the exponent.
+    let round = |significand: u32, shift: u32| {
+        let kept = significand >> shift;
+        let rest = significand & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        kept + (rest > halfway || (rest == halfway && kept & 1 == 1)) as u32
+    };
+    if biased <= 0 {
+        if biased < -10 {
+            return sign;
+        }
+        // Subnormal: the implicit bit becomes explicit.
+        return sign | round(mantissa | 0x80_0000, (14 - biased) as u32) as u16;
+    }
+    sign | round(((biased as u32) << 23) | mantissa, 13) as u16
+}
@@ -0,0 +1,1809 @@
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::alloc::{self, Allocations};
+use crate::cancel::CancelToken;
+use crate::checkpoint::Checkpoint;
+use crate::color::{ColorMap, Colors};
+use crate::complex::export_complex;
+use crate::distance::offset_surface;
+use crate::error::{Error, Result};
+use crate::escape::{EscapeTime, Sampling};
+use crate::explode::Explode;
+use crate::export::{
+    export, export_mesh, export_schematic, export_stack, export_streaming, export_zarr,
+    export_zarr_part, slab_layers, Artifact, Coordinates, Domain, ExportOptions, Format, Gltf,
+    Precision,
+};
+use crate::growth::{Grow, Growth};
+use crate::image::{ImageStack, ImageValues};
+use crate::implicit::{Implicit, Program};
+use crate::import::Import;
+use crate::infill::Infill;
+use crate::lattice::{Boundary, Lattice, Lattice3};
+use crate::lsystem::{LSystem, Surface};
+use crate::materials::{MaterialRule, Materials};
+use crate::mesh::{build_indexed_mesh, FaceKind, Mesh, Normals, PolyMesh, Simplify};
+use crate::monitor::Monitor;
+use crate::morphology::Morphology;
+use crate::orientation::{Orient, Orientation};
+use crate::plugin::Exporters;
+use crate::printability::{Printability, ThinFeatures};
+use crate::ranks::{all_reduce, Ranks};
+use crate::rule::{expected_cells, AxisRule, Rule, RuleCombinator, Split, MAX_DIMS};
+use crate::schematic::Schematic;
+use crate::script::ScriptRule;
+use crate::sdf::{DistanceField, EstimatorParams};
+use crate::seekable;
+use crate::slice::{PlaneCut, Section};
+use crate::sonify::{SliceStats, Sonify};
+use crate::sweep::Sweep;
+use crate::texture::Texture;
+use crate::tiling::Tiling;
+use crate::transform::{Affine, Transform};
+use crate::zarr::Zarr;
+
+/// One generation run: which fractal to build, which slices to take, and
+/// where to write them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// A built-in rule or surface fractal, or `lsystem` for the system
+    /// given in `lsystem`.
+    #[serde(default = "default_fractal")]
+    pub fractal: String,
+    #[serde(default)]
+    pub lsystem: Option<LSystem>,
+    /// The rules combined by fractal `hybrid`.
+    #[serde(default)]
+    pub combine: Option<RuleCombinator>,
+    /// The per-axis rule of fractal `custom`.
+    #[serde(default)]
+    pub custom: Option<AxisRule>,
+    /// The model and rule of fractal `infill`.
+    #[serde(default)]
+    pub infill: Option<Infill>,
+    /// The script of fractal `script`.
+    #[serde(default)]
+    pub script: Option<ScriptRule>,
+    /// The model voxelized by fractal `import`.
+    #[serde(default)]
+    pub import: Option<Import>,
+    /// The program of fractal `implicit`, in the language described in
+    /// [`crate::implicit`].
+    #[serde(default)]
+    pub implicit: Option<String>,
+    /// Sampling for the escape-time, distance-estimated and implicit
+    /// fractals.
+    #[serde(default)]
+    pub sampling: Option<Sampling>,
+    /// Shape of the distance-estimated fractals `mandelbulb` and
+    /// `mandelbox`.
+    #[serde(default)]
+    pub estimator: Option<EstimatorParams>,
+    #[serde(default = "default_dims")]
+    pub dims: usize,
+    /// Subdivision levels or rewrites; iterations per orbit for
+    /// escape-time and distance-estimated fractals.
+    pub depth: u32,
+    /// The w indices to slice a 4D fractal at; empty means every slice.
+    #[serde(default)]
+    pub slices: Vec<usize>,
+    /// The w indices `[w0, w1]` of a slab of a 4D fractal to project into
+    /// one 3D lattice, keeping every cell with a filled cell above it in
+    /// the slab, instead of slicing at single w.
+    #[serde(default)]
+    pub slab: Option<[usize; 2]>,
+    #[serde(default)]
+    pub transforms: Vec<Transform>,
+    /// Copies of the output laid out in a grid.
+    #[serde(default)]
+    pub tiling: Tiling,
+    /// Normals written to formats that support them.
+    #[serde(default)]
+    pub normals: Normals,
+    /// How `.glb` and `.inst` outputs store coordinates.
+    #[serde(default)]
+    pub precision: Precision,
+    /// The precision meshes are built and transformed in.
+    #[serde(default)]
+    pub coordinates: Coordinates,
+    /// Scale outputs into the unit cube, so those of different depths
+    /// overlay; see [`ExportOptions::transform_for`].
+    #[serde(default)]
+    pub normalize: bool,
+    /// Where outputs sit relative to the origin.
+    #[serde(default)]
+    pub domain: Domain,
+    /// Triangle budget and error bound for mesh simplification.
+    #[serde(default)]
+    pub simplify: Simplify,
+    /// Repair and validate meshes before writing them.
+    #[serde(default)]
+    pub repair: bool,
+    /// How the lattice's outer walls are treated when culling faces.
+    #[serde(default)]
+    pub boundary: Boundary,
+    /// Morphology applied in order to each 3D lattice before it is
+    /// meshed, such as a dilation to thicken struts for printing.
+    #[serde(default)]
+    pub morphology: Vec<Morphology>,
+    /// Check each 3D lattice for struts and walls too thin to print, and
+    /// optionally thicken them, after morphology.
+    #[serde(default)]
+    pub printability: Option<Printability>,
+    /// Rotate each output to the orientation needing the least support
+    /// when printed.
+    #[serde(default)]
+    pub orient: Option<Orient>,
+    /// Write smooth surfaces this many cells outside the cube surface:
+    /// positive dilates, negative erodes.
+    #[serde(default)]
+    pub offset: Option<f64>,
+    /// The axis and values of `.png` and `.tiff` image stack outputs, and
+    /// the values of `.nii` volumes and `.dds` textures.
+    #[serde(default)]
+    pub images: ImageStack,
+    /// The block and piece size of `.schem` outputs.
+    #[serde(default)]
+    pub schematic: Schematic,
+    /// Up axis, units and tangents of `.glb` outputs.
+    #[serde(default)]
+    pub gltf: Gltf,
+    /// Vertex colours of `.glb` outputs, and face colours of renders whose
+    /// scene sets none, from a palette keyed to a cell attribute.
+    #[serde(default)]
+    pub color: Option<ColorMap>,
+    /// Materials assigned to the cells of a rule fractal split evenly,
+    /// colouring `.glb` outputs and renders; an output with `{material}`
+    /// in its path is written once per material. See [`crate::materials`].
+    #[serde(default)]
+    pub materials: Vec<MaterialRule>,
+    /// Compression of `.dds` 3D texture outputs.
+    #[serde(default)]
+    pub texture: Texture,
+    /// Chunk size of `.zarr` outputs.
+    #[serde(default)]
+    pub zarr: Zarr,
+    /// Write `.vti` snapshots while the fractal is generated.
+    #[serde(default)]
+    pub monitor: Option<Monitor>,
+    /// Mesh a slab of layers at a time without building the lattice, for
+    /// rule fractals too deep to hold in memory; see [`export_streaming`].
+    #[serde(default)]
+    pub out_of_core: bool,
+    /// Generate one fundamental domain of the rule's symmetries and
+    /// mirror it; see [`Lattice::generate_symmetric`].
+    #[serde(default)]
+    pub symmetric: bool,
+    /// Generate on every core, NUMA node by node; see
+    /// [`Lattice::generate_numa`].
+    #[serde(default)]
+    pub numa: bool,
+    /// Generate and write only this rank's part of the job's Zarr
+    /// outputs, one of several processes sharing it; see
+    /// [`crate::ranks`].
+    #[serde(default)]
+    pub ranks: Option<Ranks>,
+    /// Cut a 4D rule fractal exactly along this hyperplane into a mesh,
+    /// instead of slicing it at w layers.
+    #[serde(default)]
+    pub section: Option<Section>,
+    /// Cut a 3D rule fractal exactly along this plane, keeping what lies
+    /// behind it, into a mesh capped where it cuts the cells.
+    #[serde(default)]
+    pub cut: Option<PlaneCut>,
+    /// Push the level blocks of a 3D rule fractal apart into an exploded
+    /// mesh, or a run of them with a `{frame}` in the output paths.
+    #[serde(default)]
+    pub explode: Option<Explode>,
+    /// Animate a 3D rule fractal growing from one level short of its depth
+    /// into it, one mesh per frame at `{frame}` in the output paths.
+    #[serde(default)]
+    pub grow: Option<Grow>,
+    /// Play the w slices of a 4D fractal's sweep as sound, one per frame,
+    /// into a WAV file; see [`crate::sonify`].
+    #[serde(default)]
+    pub sonify: Option<Sonify>,
+    /// Also write the kept cells of a 4D fractal, with their vertices and
+    /// facets, to this path; see [`CellComplex`](crate::complex::CellComplex).
+    #[serde(default)]
+    pub complex: Option<PathBuf>,
+    /// Shared libraries whose exporters write the outputs no built-in
+    /// format claims; see [`crate::plugin`].
+    #[serde(default)]
+    pub plugins: Vec<PathBuf>,
+    /// Output paths, with the format taken from the extension. A `{w}` in
+    /// the path is replaced by the slice index.
+    pub outputs: Vec<PathBuf>,
+}
+
+fn default_fractal() -> String {
+    "menger".to_string()
+}
+
+fn default_dims() -> usize {
+    4
+}
+
+/// Cells kept and removed by one subdivision level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct LevelStats {
+    pub level: u32,
+    pub kept: u64,
+    pub removed: u64,
+}
+
+/// Wall time spent in one stage of a job.
+#[derive(Clone, Debug, Serialize)]
+pub struct StageTiming {
+    pub stage: &'static str,
+    #[serde(serialize_with = "as_seconds")]
+    pub elapsed: Duration,
+    /// What the stage allocated, with the `alloc-stats` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allocations: Option<Allocations>,
+}
+
+/// What a finished job produced.
+#[derive(Clone, Debug, Serialize)]
+pub struct JobReport {
+    pub name: String,
+    /// Filled cells, or triangles for surface fractals.
+    pub cells: usize,
+    pub surface: bool,
+    pub slices: usize,
+    pub levels: Vec<LevelStats>,
+    pub stages: Vec<StageTiming>,
+    /// Printability findings, one per lattice checked.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub thin_features: Vec<ThinFeatures>,
+    /// Orientations chosen, one per mesh oriented.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub orientations: Vec<Orientation>,
+    pub artifacts: Vec<Artifact>,
+    #[serde(serialize_with = "as_seconds")]
+    pub elapsed: Duration,
+    /// Peak resident memory of the process so far, in bytes, with the
+    /// `alloc-stats` feature.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_rss: Option<u64>,
+}
+
+fn as_seconds<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+impl Job {
+    /// The job's name, or one derived from its parameters.
+    pub fn display_name(&self) -> String {
+        self.name
+            .clone()
+            .unwrap_or_else(|| format!("{}-{}d-n{}", self.fractal, self.dims, self.depth))
+    }
+
+    pub fn rule(&self) -> Result<Rule> {
+        if self.combine.is_some() && self.fractal != "hybrid" {
+            return Err(Error::InvalidJob(
+                "a `combine` definition needs fractal `hybrid`".into(),
+            ));
+        }
+        if self.custom.is_some() && self.fractal != "custom" {
+            return Err(Error::InvalidJob(
+                "a `custom` definition needs fractal `custom`".into(),
+            ));
+        }
+        match self.fractal.as_str() {
+            "hybrid" => match &self.combine {
+                Some(combine) => combine.rule(self.dims),
+                None => Err(Error::InvalidJob(
+                    "fractal `hybrid` needs a `combine` definition".into(),
+                )),
+            },
+            "custom" => match &self.custom {
+                Some(custom) => custom.rule(self.dims),
+                None => Err(Error::InvalidJob(
+                    "fractal `custom` needs a `custom` definition".into(),
+                )),
+            },
+            name => {
+                Rule::by_name(name, self.dims).ok_or_else(|| Error::UnknownFractal(name.into()))
+            }
+        }
+    }
+
+    /// The surface fractal the job builds, or None for a lattice rule.
+    pub fn surface(&self) -> Result<Option<Surface>> {
+        match (self.fractal.as_str(), &self.lsystem) {
+            ("lsystem", Some(system)) => Ok(Some(Surface::Turtle {
+                name: "lsystem".to_string(),
+                system: system.clone(),
+            })),
+            ("lsystem", None) => Err(Error::InvalidJob(
+                "fractal `lsystem` needs an `lsystem` definition".into(),
+            )),
+            (_, Some(_)) => Err(Error::InvalidJob(
+                "an `lsystem` definition needs fractal `lsystem`".into(),
+            )),
+            (name, None) => Ok(Surface::by_name(name)),
+        }
+    }
+
+    /// The model the job fills, or None for other fractals.
+    pub fn infill(&self) -> Result<Option<&Infill>> {
+        match (self.fractal.as_str(), &self.infill) {
+            ("infill", Some(infill)) => Ok(Some(infill)),
+            ("infill", None) => Err(Error::InvalidJob(
+                "fractal `infill` needs an `infill` definition".into(),
+            )),
+            (_, Some(_)) => Err(Error::InvalidJob(
+                "an `infill` definition needs fractal `infill`".into(),
+            )),
+            (_, None) => Ok(None),
+        }
+    }
+
+    /// The model the job voxelizes, or None for other fractals.
+    pub fn import(&self) -> Result<Option<&Import>> {
+        match (self.fractal.as_str(), &self.import) {
+            ("import", Some(import)) => Ok(Some(import)),
+            ("import", None) => Err(Error::InvalidJob(
+                "fractal `import` needs an `import` definition".into(),
+            )),
+            (_, Some(_)) => Err(Error::InvalidJob(
+                "an `import` definition needs fractal `import`".into(),
+            )),
+            (_, None) => Ok(None),
+        }
+    }
+
+    /// The scripted rule the job runs, or None for other fractals.
+    pub fn script(&self) -> Result<Option<&ScriptRule>> {
+        match (self.fractal.as_str(), &self.script) {
+            ("script", Some(script)) => Ok(Some(script)),
+            ("script", None) => Err(Error::InvalidJob(
+                "fractal `script` needs a `script` definition".into(),
+            )),
+            (_, Some(_)) => Err(Error::InvalidJob(
+                "a `script` definition needs fractal `script`".into(),
+            )),
+            (_, None) => Ok(None),
+        }
+    }
+
+    /// The external model the job's lattice is laid over, imported or
+    /// infilled.
+    pub(crate) fn model(&self) -> Result<Option<Import>> {
+        Ok(match (self.import()?, self.infill()?) {
+            (Some(import), _) => Some(import.clone()),
+            (_, Some(infill)) => Some(infill.import()),
+            (None, None) => None,
+        })
+    }
+
+    /// The escape-time set the job samples, or None for other fractals.
+    pub fn escape_time(&self) -> Option<EscapeTime> {
+        EscapeTime::by_name(&self.fractal, self.sampling.clone().unwrap_or_default())
+    }
+
+    /// The distance-estimated fractal the job samples, or None for other
+    /// fractals.
+    pub fn distance_field(&self) -> Result<Option<DistanceField>> {
+        let params = self.estimator.unwrap_or_default();
+        let sampling = self.sampling.clone().unwrap_or_default();
+        let field = DistanceField::by_name(&self.fractal, params, sampling);
+        if field.is_none() && self.estimator.is_some() {
+            return Err(Error::InvalidJob(
+                "`estimator` needs a distance-estimated fractal (mandelbulb or mandelbox)".into(),
+            ));
+        }
+        Ok(field)
+    }
+
+    /// The implicit fractal the job samples, compiled, or None for other
+    /// fractals.
+    pub fn implicit(&self) -> Result<Option<Implicit>> {
+        match (self.fractal.as_str(), &self.implicit) {
+            ("implicit", Some(source)) => Ok(Some(Implicit {
+                program: Program::parse(source)?,
+                sampling: self.sampling.clone().unwrap_or_default(),
+            })),
+            ("implicit", None) => Err(Error::InvalidJob(
+                "fractal `implicit` needs an `implicit` program".into(),
+            )),
+            (_, Some(_)) => Err(Error::InvalidJob(
+                "an `implicit` program needs fractal `implicit`".into(),
+            )),
+            (_, None) => Ok(None),
+        }
+    }
+
+    /// Whether the job's lattice is subdivided by its [`Rule`], rather
+    /// than a surface, sampled, a model or scripted.
+    pub(crate) fn is_rule(&self) -> Result<bool> {
+        Ok(self.surface()?.is_none()
+            && !self.is_sampled()?
+            && self.model()?.is_none()
+            && self.script()?.is_none())
+    }
+
+    /// Whether the job's lattice is sampled rather than subdivided.
+    pub(crate) fn is_sampled(&self) -> Result<bool> {
+        Ok(self.escape_time().is_some()
+            || self.distance_field()?.is_some()
+            || self.implicit()?.is_some())
+    }
+
+    /// Checks the parameters that can be checked without generating.
+    pub fn validate(&self) -> Result<()> {
+        // Before any rule is built, which takes time exponential in dims.
+        if !(3..=4).contains(&self.dims) {
+            return Err(Error::InvalidJob(format!(
+                "dims must be 3 or 4, got {}",
+                self.dims
+            )));
+        }
+        if self.surface()?.is_some() {
+            if self.dims != 3 {
+                return Err(Error::InvalidJob(format!(
+                    "surface fractal `{}` is 3D; set dims to 3",
+                    self.fractal
+                )));
+            }
+            if self.offset.is_some()
+                || !self.morphology.is_empty()
+                || self.printability.is_some()
+                || self.outputs.iter().any(|path| is_volume(path))
+            {
+                return Err(Error::InvalidJob(format!(
+                    "surface fractal `{}` has no lattice to offset, reshape or write as a volume",
+                    self.fractal
+                )));
+            }
+        } else if let Some(model) = self.model()? {
+            if self.dims != 3 {
+                return Err(Error::InvalidJob(format!(
+                    "fractal `{}` is 3D; set dims to 3",
+                    self.fractal
+                )));
+            }
+            if !(model.cell_size > 0.0 && model.cell_size.is_finite()) {
+                return Err(Error::InvalidJob("model cell size must be positive".into()));
+            }
+            if let Some(infill) = self.infill()? {
+                infill.rule()?;
+            }
+        } else if self.is_sampled()? {
+            let sampling = self.sampling.clone().unwrap_or_default();
+            if sampling.resolution == 0 || sampling.radius.is_some_and(|r| r <= 0.0) {
+                return Err(Error::InvalidJob(
+                    "sampling resolution and radius must be positive".into(),
+                ));
+            }
+            if self.distance_field()?.is_some() && self.dims != 3 {
+                return Err(Error::InvalidJob(format!(
+                    "distance-estimated fractal `{}` is 3D; set dims to 3",
+                    self.fractal
+                )));
+            }
+        } else if let Some(script) = self.script()? {
+            script.bases(self.dims)?;
+        } else {
+            self.rule()?;
+        }
+        if self.combine.is_some() || self.custom.is_some() {
+            self.rule()?;
+        }
+        if self.sampling.is_some() && !self.is_sampled()? {
+            return Err(Error::InvalidJob(
+                "`sampling` needs an escape-time, distance-estimated or implicit fractal".into(),
+            ));
+        }
+        if self.dims == 3 && (!self.slices.is_empty() || self.slab.is_some()) {
+            return Err(Error::InvalidJob("3D fractals cannot be sliced".into()));
+        }
+        if let Some([w0, w1]) = self.slab {
+            if !self.slices.is_empty() {
+                return Err(Error::InvalidJob(
+                    "a slab replaces the w slices; give one or the other".into(),
+                ));
+            }
+            if w0 > w1 {
+                return Err(Error::InvalidJob(format!(
+                    "slab w={w0}..{w1} ends before it starts"
+                )));
+            }
+        }
+        if self.morphology.iter().any(|step| step.radius == 0) {
+            return Err(Error::InvalidJob(
+                "structuring element radius must be at least 1".into(),
+            ));
+        }
+        if self
+            .printability
+            .is_some_and(|p| !(p.min_thickness > 0.0 && p.min_thickness.is_finite()))
+        {
+            return Err(Error::InvalidJob(
+                "minimum thickness must be positive".into(),
+            ));
+        }
+        if self
+            .orient
+            .is_some_and(|orient| !(0.0..90.0).contains(&orient.max_overhang))
+        {
+            return Err(Error::InvalidJob(
+                "max overhang must be at least 0 and under 90 degrees".into(),
+            ));
+        }
+        if self.offset.is_some_and(|offset| !offset.is_finite()) {
+            return Err(Error::InvalidJob("offset must be finite".into()));
+        }
+        if self
+            .outputs
+            .iter()
+            .any(|path| matches!(Format::from_path(path), Ok(Format::Schem)))
+        {
+            self.schematic.block_id()?;
+            self.schematic.pieces([1; 3])?;
+        }
+        if self.texture.bc4
+            && self.images.values == ImageValues::Distance
+            && self
+                .outputs
+                .iter()
+                .any(|path| matches!(Format::from_path(path), Ok(Format::Dds)))
+        {
+            return Err(Error::InvalidJob(
+                "BC4 compresses occupancy textures only, not distances".into(),
+            ));
+        }
+        if !(self.gltf.unit_scale > 0.0 && self.gltf.unit_scale.is_finite()) {
+            return Err(Error::InvalidJob("glTF unit scale must be positive".into()));
+        }
+        if self
+            .gltf
+            .fit
+            .is_some_and(|fit| !(fit > 0.0 && fit.is_finite()))
+        {
+            return Err(Error::InvalidJob("glTF fit size must be positive".into()));
+        }
+        if !(1..=16).contains(&self.gltf.position_bits) {
+            return Err(Error::InvalidJob(
+                "glTF position bits must be 1 to 16".into(),
+            ));
+        }
+        if ![8, 16].contains(&self.gltf.normal_bits) {
+            return Err(Error::InvalidJob("glTF normal bits must be 8 or 16".into()));
+        }
+        if self.gltf.tangents && self.normals != Normals::Smooth {
+            return Err(Error::InvalidJob(
+                "glTF tangents need smooth normals".into(),
+            ));
+        }
+        if self.precision == Precision::F16
+            && self
+                .outputs
+                .iter()
+                .any(|path| matches!(Format::from_path(path), Ok(Format::Glb)))
+        {
+            return Err(Error::InvalidJob(
+                "glTF cannot store f16 positions; use q16".into(),
+            ));
+        }
+        if (self.normalize || self.domain != Domain::Positive) && self.model()?.is_some() {
+            return Err(Error::InvalidJob(
+                "outputs of an imported or infilled model keep its coordinates; \
+                 drop normalize and the centred domain"
+                    .into(),
+            ));
+        }
+        if let Some(path) = self.outputs.iter().find(|path| {
+            seekable::is_compressed(path)
+                && Format::from_path(path).is_ok_and(|format| {
+                    format.is_image_stack() || matches!(format, Format::Schem | Format::Zarr)
+                })
+        }) {
+            return Err(Error::InvalidJob(format!(
+                "`{}` cannot be zstd-compressed",
+                path.display()
+            )));
+        }
+        self.zarr.validate()?;
+        if let Some(monitor) = &self.monitor {
+            if !self.is_rule()? {
+                return Err(Error::InvalidJob(
+                    "only subdivision rule fractals can be monitored".into(),
+                ));
+            }
+            monitor.validate()?;
+        }
+        if self.out_of_core {
+            self.validate_out_of_core()?;
+        }
+        if let Some(ranks) = &self.ranks {
+            ranks.validate()?;
+            self.validate_ranked()?;
+        }
+        if self.symmetric && !self.is_rule()? {
+            return Err(Error::InvalidJob(
+                "only subdivision rule fractals can be generated symmetrically".into(),
+            ));
+        }
+        if let Some(section) = &self.section {
+            section.validate()?;
+            self.validate_exact(4, "a section")?;
+        }
+        if let Some(cut) = &self.cut {
+            cut.validate()?;
+            self.validate_exact(3, "a plane cut")?;
+        }
+        if let Some(explode) = &self.explode {
+            explode.validate(self.depth)?;
+            self.validate_animated("an exploded view")?;
+        }
+        if let Some(sonify) = &self.sonify {
+            sonify.validate()?;
+            if self.dims != 4
+                || self.out_of_core
+                || self.ranks.is_some()
+                || self.section.is_some()
+                || self.surface()?.is_some()
+            {
+                return Err(Error::InvalidJob(
+                    "sonification needs the w slices of a 4D lattice, written in core".into(),
+                ));
+            }
+        }
+        if let Some(grow) = &self.grow {
+            grow.validate(self.depth)?;
+            self.validate_animated("growth")?;
+            if self.explode.is_some() || !self.morphology.is_empty() {
+                return Err(Error::InvalidJob(
+                    "growth cannot be exploded or reshaped by morphology".into(),
+                ));
+            }
+        }
+        if self.complex.is_some()
+            && (self.dims != 4
+                || self.out_of_core
+                || self.section.is_some()
+                || self.surface()?.is_some())
+        {
+            return Err(Error::InvalidJob(
+                "a cell complex needs a generated 4D lattice; it cannot be written out of core or with a section".into(),
+            ));
+        }
+        if self.symmetric && (self.monitor.is_some() || self.out_of_core) {
+            return Err(Error::InvalidJob(
+                "symmetric generation cannot be monitored or run out of core".into(),
+            ));
+        }
+        if self.numa && !self.is_rule()? {
+            return Err(Error::InvalidJob(
+                "only subdivision rule fractals can be generated NUMA-aware".into(),
+            ));
+        }
+        if self.numa && (self.symmetric || self.monitor.is_some() || self.out_of_core) {
+            return Err(Error::InvalidJob(
+                "NUMA-aware generation cannot be symmetric, monitored or run out of core".into(),
+            ));
+        }
+        if !self.materials.is_empty() {
+            self.validate_materials()?;
+        } else if let Some(path) = self
+            .outputs
+            .iter()
+            .find(|path| path.to_string_lossy().contains("{material}"))
+        {
+            return Err(Error::InvalidJob(format!(
+                "`{}` is written per material, but the job has none",
+                path.display()
+            )));
+        }
+        if self.tiling.count.contains(&0) {
+            return Err(Error::InvalidJob("tiling counts must be at least 1".into()));
+        }
+        if self.dims == 3 && self.tiling.count[3] != 1 {
+            return Err(Error::InvalidJob("3D fractals cannot be tiled in w".into()));
+        }
+        Ok(())
+    }
+
+    /// Checks that an exact cut, `what`, is of a `dims`-dimensional rule
+    /// fractal and asks for nothing but meshes of it.
+    fn validate_exact(&self, dims: usize, what: &str) -> Result<()> {
+        if self.dims != dims || !self.is_rule()? {
+            return Err(Error::InvalidJob(format!(
+                "only {dims}D subdivision rule fractals can be cut by {what}"
+            )));
+        }
+        if self.section.is_some() && self.cut.is_some() {
+            return Err(Error::InvalidJob(
+                "a job takes a section or a plane cut, not both".into(),
+            ));
+        }
+        if !self.slices.is_empty()
+            || self.slab.is_some()
+            || !self.morphology.is_empty()
+            || self.printability.is_some()
+            || self.offset.is_some()
+            || self.monitor.is_some()
+            || self.out_of_core
+            || self.symmetric
+            || self.numa
+        {
+            return Err(Error::InvalidJob(format!(
+                "{what} is a mesh; it cannot be combined with w slices, morphology, printability, offsets, monitoring, out-of-core or symmetric or NUMA-aware generation"
+            )));
+        }
+        if let Some(path) = self.outputs.iter().find(|path| is_volume(path)) {
+            return Err(Error::InvalidJob(format!(
+                "`{}` needs a lattice; write {what} as a mesh",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that an animation or view, `what`, is of a 3D rule fractal
+    /// split evenly, written only as meshes of its cells.
+    fn validate_animated(&self, what: &str) -> Result<()> {
+        if self.dims != 3 || !self.is_rule()? || self.rule()?.split() != Split::Uniform {
+            return Err(Error::InvalidJob(format!(
+                "{what} needs a 3D subdivision rule fractal split evenly"
+            )));
+        }
+        if self.section.is_some()
+            || self.cut.is_some()
+            || self.offset.is_some()
+            || self.orient.is_some()
+            || self.out_of_core
+            || self.ranks.is_some()
+            || !self.materials.is_empty()
+        {
+            return Err(Error::InvalidJob(format!(
+                "{what} cannot be cut, offset, oriented, coloured by materials, or written out of core or by ranks"
+            )));
+        }
+        if let Some(path) = self.outputs.iter().find(|path| is_volume(path)) {
+            return Err(Error::InvalidJob(format!(
+                "`{}` needs a lattice; write {what} as a mesh",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the job's material rules fit its rule and that its
+    /// cells reach the outputs whole, for the rules to pick them by.
+    fn validate_materials(&self) -> Result<()> {
+        if !self.is_rule()? || self.rule()?.split() != Split::Uniform {
+            return Err(Error::InvalidJob(
+                "materials need a subdivision rule fractal split evenly".into(),
+            ));
+        }
+        if self.color.is_some() {
+            return Err(Error::InvalidJob(
+                "a job colours its cells by a colour map or by materials, not both".into(),
+            ));
+        }
+        if self.out_of_core
+            || self.ranks.is_some()
+            || self.section.is_some()
+            || self.cut.is_some()
+            || self.offset.is_some()
+        {
+            return Err(Error::InvalidJob(
+                "materials need the lattice's cells; they cannot be written out of core, by ranks, cut or offset".into(),
+            ));
+        }
+        self.materials()?;
+        Ok(())
+    }
+
+    fn validate_out_of_core(&self) -> Result<()> {
+        if !self.is_rule()? {
+            return Err(Error::InvalidJob(
+                "only subdivision rule fractals can be meshed out of core".into(),
+            ));
+        }
+        if !self.morphology.is_empty()
+            || self.printability.is_some()
+            || self.orient.is_some()
+            || self.offset.is_some()
+            || self.monitor.is_some()
+        {
+            return Err(Error::InvalidJob(
+                "morphology, printability, orientation, offsets and monitoring need the whole lattice, not out of core".into(),
+            ));
+        }
+        if self.simplify != Simplify::default() || self.repair || !self.tiling.is_single() {
+            return Err(Error::InvalidJob(
+                "simplification, repair and tiling need the whole mesh, not out of core".into(),
+            ));
+        }
+        if self.normals == Normals::Smooth {
+            return Err(Error::InvalidJob(
+                "smooth normals cannot be written out of core".into(),
+            ));
+        }
+        if let Some(path) = self
+            .outputs
+            .iter()
+            .find(|path| !matches!(Format::from_path(path), Ok(Format::Obj | Format::Stl)))
+        {
+            return Err(Error::InvalidJob(format!(
+                "`{}` cannot be written out of core; use .obj or .stl",
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the job can be split between ranks: a rule fractal
+    /// written to Zarr arrays of its occupancy, with nothing that needs
+    /// the whole lattice.
+    fn validate_ranked(&self) -> Result<()> {
+        if !self.is_rule()? || self.section.is_some() || self.cut.is_some() {
+            return Err(Error::InvalidJob(
+                "only subdivision rule fractals' lattices can be split between ranks".into(),
+            ));
+        }
+        if !self.morphology.is_empty()
+            || self.printability.is_some()
+            || self.offset.is_some()
+            || self.monitor.is_some()
+            || self.out_of_core
+            || self.symmetric
+            || self.numa
+            || self.complex.is_some()
+            || !self.tiling.is_single()
+        {
+            return Err(Error::InvalidJob(
+                "morphology, printability, offsets, monitoring, tiling, cell complexes and other generators need the whole lattice, not a rank's part".into(),
+            ));
+        }
+        if let Some(path) = self
+            .outputs
+            .iter()
+            .find(|path| !matches!(Format::from_path(path), Ok(Format::Zarr)))
+        {
+            return Err(Error::InvalidJob(format!(
+                "`{}` cannot be written in parts by ranks; use .zarr",
+                path.display()
+            )));
+        }
+        if self.images.values != ImageValues::Occupancy {
+            return Err(Error::InvalidJob(
+                "ranks write occupancy; distances need the whole lattice".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn run(&self) -> Result<JobReport> {
+        self.run_cancellable(&CancelToken::new())
+    }
+
+    /// Runs the job, stopping with [`Error::Cancelled`] at the next check
+    /// after `cancel` fires. Outputs already completed are kept.
+    #[tracing::instrument(name = "job", skip_all, fields(job = %self.display_name()))]
+    pub fn run_cancellable(&self, cancel: &CancelToken) -> Result<JobReport> {
+        self.validate()?;
+        if let Some(surface) = self.surface()? {
+            return self.run_mesh(|cancel| surface.build(self.depth, cancel), cancel);
+        }
+        if let Some(section) = &self.section {
+            let rule = self.rule()?;
+            return self.run_mesh(
+                |cancel| exact_mesh(section.cells(&rule, self.depth, cancel)?),
+                cancel,
+            );
+        }
+        if let Some(cut) = &self.cut {
+            let rule = self.rule()?;
+            return self.run_mesh(
+                |cancel| exact_mesh(cut.cells(&rule, self.depth, cancel)?),
+                cancel,
+            );
+        }
+        if let Some(explode) = &self.explode {
+            let rule = self.rule()?;
+            return self.run_frames(
+                explode.frames,
+                |cancel| {
+                    let lattice = self.morph(self.generate::<3>(cancel)?, cancel)?;
+                    let cells = lattice.count();
+                    Ok((lattice, cells))
+                },
+                |lattice, frame, kind| {
+                    let factor = explode.factor_at(frame);
+                    explode.mesh(lattice, rule.bases(), self.depth, factor, kind)
+                },
+                cancel,
+            );
+        }
+        if let Some(grow) = &self.grow {
+            let rule = self.rule()?;
+            return self.run_frames(
+                grow.frames,
+                |cancel| {
+                    let growth = Growth::new(&rule, self.depth, cancel)?;
+                    let cells = growth.kept().count();
+                    Ok((growth, cells))
+                },
+                |growth, frame, kind| growth.mesh(grow.progress(frame), kind),
+                cancel,
+            );
+        }
+        if self.out_of_core {
+            return self.run_out_of_core(cancel);
+        }
+        if let Some(ranks) = self.ranks {
+            return self.run_ranked(ranks, cancel);
+        }
+        let checkpoint = match self.outputs.first().and_then(|o| Checkpoint::path_for(o)) {
+            Some(path) => Checkpoint::open(path, self)?,
+            None => Checkpoint::default(),
+        };
+        let result = self.run_lattice(&checkpoint, cancel);
+        if result.is_ok() {
+            checkpoint.remove();
+        } else if let Err(e) = checkpoint.save() {
+            tracing::warn!("cannot save checkpoint: {e}");
+        }
+        result
+    }
+
+    /// Runs a job generating a lattice, reusing the slices, outputs and
+    /// Zarr chunks `checkpoint` holds and recording those it completes.
+    /// Resumed slices add no thin features or orientations to the report.
+    fn run_lattice(&self, checkpoint: &Checkpoint, cancel: &CancelToken) -> Result<JobReport> {
+        let start = Instant::now();
+        let mut options = self.export_options();
+        options.checkpoint = checkpoint.clone();
+        options.exporters = self.exporters()?;
+        if let Some(model) = self.model()? {
+            options.transform = options.transform.then(&model.placement(&model.grid()?));
+        }
+        let mut timer = StageTimer::default();
+        let mut artifacts = Vec::new();
+        let mut thin_features = Vec::new();
+        let mut orientations = Vec::new();
+        let (cells, slices) = if self.dims == 3 {
+            let lattice = timer.time("generate", || self.generate::<3>(cancel))?;
+            let cells = lattice.count();
+            let lattice = timer.time_if(!self.morphology.is_empty(), "morphology", || {
+                self.morph(lattice, cancel)
+            })?;
+            let lattice = timer.time_if(self.printability.is_some(), "printability", || {
+                self.check_printability(lattice, None, &mut thin_features, cancel)
+            })?;
+            let options = timer.time_if(self.orient.is_some(), "orient", || {
+                self.orient_lattice(&lattice, None, &options, &mut orientations, cancel)
+            })?;
+            for output in &self.outputs {
+                artifacts.extend(checkpoint.unit(&output.to_string_lossy(), || {
+                    timer.time("export", || {
+                        export_materials(&lattice, output, &options, cancel)
+                    })
+                })?);
+            }
+            (cells, 1)
+        } else {
+            let mut slicer = timer.time("generate", || self.slicer(cancel))?;
+            if let (Some(path), Slicer::Lattice(lattice)) = (&self.complex, &slicer) {
+                artifacts.extend(checkpoint.unit(&path.to_string_lossy(), || {
+                    Ok(vec![timer.time("export", || {
+                        export_complex(lattice, path, cancel)
+                    })?])
+                })?);
+            }
+            let side = slicer.side();
+            let slices = self.slice_indices(side)?;
+            let mut sounds = Vec::new();
+            for &w in &slices {
+                // A sonified sweep needs every slice's statistics, so only
+                // the slice's Zarr chunks are reused.
+                let unit = self.sonify.is_none().then(|| format!("w{w}"));
+                let mut export_slice = || {
+                    let slice =
+                        timer.time("slice", || slicer.slab(self.slab_ws(w), side, cancel))?;
+                    let slice = timer.time_if(!self.morphology.is_empty(), "morphology", || {
+                        self.morph(slice, cancel)
+                    })?;
+                    let slice =
+                        timer.time_if(self.printability.is_some(), "printability", || {
+                            self.check_printability(slice, Some(w), &mut thin_features, cancel)
+                        })?;
+                    let mut options = timer.time_if(self.orient.is_some(), "orient", || {
+                        self.orient_lattice(&slice, Some(w), &options, &mut orientations, cancel)
+                    })?;
+                    if let Some(colors) = &mut options.colors {
+                        colors.w = (w % side) as f64 / (side - 1).max(1) as f64;
+                    }
+                    if self.sonify.is_some() {
+                        sounds.push(
+                            timer.time("sonify", || SliceStats::measure(&slice, w, self.boundary)),
+                        );
+                    }
+                    let mut written = Vec::new();
+                    for output in &self.outputs {
+                        let path = slice_path(output, w, slices.len() > 1);
+                        written.extend(timer.time("export", || {
+                            export_materials(&slice, &path, &options, cancel)
+                        })?);
+                    }
+                    Ok(written)
+                };
+                artifacts.extend(match unit {
+                    Some(unit) => checkpoint.unit(&unit, export_slice)?,
+                    None => export_slice()?,
+                });
+            }
+            if let Some(sonify) = &self.sonify {
+                artifacts.push(timer.time("sonify", || sonify.write(&sounds))?);
+            }
+            (slicer.cells(), slices.len())
+        };
+        let report = JobReport {
+            name: self.display_name(),
+            cells,
+            surface: false,
+            slices,
+            levels: if self.is_rule()? {
+                level_stats(&self.rule()?, self.depth)
+            } else {
+                Vec::new()
+            },
+            stages: timer.stages,
+            thin_features,
+            orientations,
+            artifacts,
+            elapsed: start.elapsed(),
+            peak_rss: peak_rss(),
+        };
+        tracing::info!(
+            cells,
+            slices,
+            files = report.artifacts.len(),
+            "job finished"
+        );
+        Ok(report)
+    }
+
+    /// Generates the job's lattice from its rule, sampled set, implicit
+    /// program, script, imported model or infilled model.
+    pub(crate) fn generate<const D: usize>(&self, cancel: &CancelToken) -> Result<Lattice<D>> {
+        let model = match (self.import()?, self.infill()?) {
+            (Some(import), _) => Some(import.voxelize(&import.grid()?, cancel)?),
+            (_, Some(infill)) => Some(infill.generate(&infill.grid()?, self.depth, cancel)?),
+            (None, None) => None,
+        };
+        if let Some(filled) = model {
+            let shape = std::array::from_fn(|axis| filled.shape().get(axis).copied().unwrap_or(1));
+            return Lattice::from_fn(shape, cancel, |p| filled.get([p[0], p[1], p[2]]));
+        }
+        if let Some(set) = self.escape_time() {
+            return set.voxelize(self.depth, cancel);
+        }
+        if let Some(field) = self.distance_field()? {
+            return field.voxelize(self.depth, cancel);
+        }
+        if let Some(implicit) = self.implicit()? {
+            return implicit.voxelize(self.depth, cancel);
+        }
+        if let Some(script) = self.script()? {
+            return script.generate(self.depth, cancel);
+        }
+        let rule = self.rule()?;
+        match &self.monitor {
+            Some(monitor) => {
+                let mut snapshots = monitor.start();
+                Lattice::generate_observed(&rule, self.depth, cancel, |lattice, done| {
+                    snapshots.observe(lattice, done)
+                })
+            }
+            None if self.symmetric => Lattice::generate_symmetric(&rule, self.depth, cancel),
+            None if self.numa => Lattice::generate_numa(&rule, self.depth, cancel),
+            None => Lattice::generate_recursive(&rule, self.depth, cancel),
+        }
+    }
+
+    /// Where a 4D job's slices come from: a rule fractal's digits when the
+    /// rule allows, so sweeping w never builds the hypercube, and the
+    /// generated lattice otherwise.
+    fn slicer(&self, cancel: &CancelToken) -> Result<Slicer> {
+        if self.is_rule()?
+            && self.monitor.is_none()
+            && !self.symmetric
+            && !self.numa
+            && self.complex.is_none()
+        {
+            let rule = self.rule()?;
+            let side = rule.side_along(3, self.depth);
+            let ws: Vec<usize> = self
+                .slice_indices(side)?
+                .into_iter()
+                .flat_map(|w| self.slab_ws(w))
+                .map(|w| w % side)
+                .collect();
+            if let Some(sweep) = Sweep::new(&rule, self.depth, &ws, cancel)? {
+                return Ok(Slicer::Sweep(
+                    sweep,
+                    expected_cells(&rule, self.depth) as usize,
+                ));
+            }
+        }
+        Ok(Slicer::Lattice(self.generate::<4>(cancel)?))
+    }
+
+    /// Applies the job's morphology to one 3D lattice.
+    fn morph(&self, mut lattice: Lattice3, cancel: &CancelToken) -> Result<Lattice3> {
+        for step in &self.morphology {
+            lattice = step.apply(&lattice, self.boundary, cancel)?;
+        }
+        Ok(lattice)
+    }
+
+    /// Runs the job's printability check, if any, on one 3D lattice, with
+    /// cells sized by the job's transforms.
+    fn check_printability(
+        &self,
+        lattice: Lattice3,
+        slice: Option<usize>,
+        found: &mut Vec<ThinFeatures>,
+        cancel: &CancelToken,
+    ) -> Result<Lattice3> {
+        let Some(printability) = &self.printability else {
+            return Ok(lattice);
+        };
+        let cell_size = Affine::from_transforms(&self.transforms).scale_factor()
+            * self.model()?.map_or(1.0, |model| model.cell_size);
+        let (lattice, features) =
+            printability.check(lattice, cell_size, self.boundary, slice, cancel)?;
+        found.push(features);
+        Ok(lattice)
+    }
+
+    /// The export options with the rotation that best orients the mesh
+    /// of `lattice` added, if the job orients its outputs.
+    fn orient_lattice(
+        &self,
+        lattice: &Lattice3,
+        slice: Option<usize>,
+        options: &ExportOptions,
+        found: &mut Vec<Orientation>,
+        cancel: &CancelToken,
+    ) -> Result<ExportOptions> {
+        if self.orient.is_none() {
+            return Ok(options.clone());
+        }
+        let mesh = match options.offset {
+            Some(offset) => offset_surface(lattice, offset, FaceKind::Triangles, cancel)?,
+            None => build_indexed_mesh(lattice, FaceKind::Triangles, options.boundary),
+        };
+        self.orient_mesh(mesh, slice, options, found, cancel)
+    }
+
+    /// Like [`Job::orient_lattice`], for an already built mesh.
+    fn orient_mesh(
+        &self,
+        mut mesh: Mesh,
+        slice: Option<usize>,
+        options: &ExportOptions,
+        found: &mut Vec<Orientation>,
+        cancel: &CancelToken,
+    ) -> Result<ExportOptions> {
+        let Some(orient) = &self.orient else {
+            return Ok(options.clone());
+        };
+        mesh.transform(&options.transform);
+        let orientation = orient.search(&mesh, slice, cancel)?;
+        let mut options = options.clone();
+        options.transform = orientation.affine().then(&options.transform);
+        found.push(orientation);
+        Ok(options)
+    }
+
+    /// Runs a job whose outputs are a mesh `build` makes rather than a
+    /// lattice: a surface fractal or an exact cut.
+    fn run_mesh(
+        &self,
+        build: impl FnOnce(&CancelToken) -> Result<Mesh>,
+        cancel: &CancelToken,
+    ) -> Result<JobReport> {
+        let start = Instant::now();
+        let mut options = self.export_options();
+        options.exporters = self.exporters()?;
+        let mut timer = StageTimer::default();
+        let mesh = timer.time("generate", || build(cancel))?;
+        let extent = mesh.extent();
+        let mut orientations = Vec::new();
+        let options = timer.time_if(self.orient.is_some(), "orient", || {
+            self.orient_mesh(mesh.clone(), None, &options, &mut orientations, cancel)
+        })?;
+        let mut artifacts = Vec::new();
+        for output in &self.outputs {
+            artifacts.push(timer.time("export", || {
+                export_mesh(&mesh, extent, output, &options, cancel)
+            })?);
+        }
+        let triangles = mesh.face_count();
+        tracing::info!(triangles, files = artifacts.len(), "job finished");
+        Ok(JobReport {
+            name: self.display_name(),
+            cells: triangles,
+            surface: true,
+            slices: 1,
+            levels: Vec::new(),
+            stages: timer.stages,
+            thin_features: Vec::new(),
+            orientations,
+            artifacts,
+            elapsed: start.elapsed(),
+            peak_rss: peak_rss(),
+        })
+    }
+
+    /// Writes a mesh of what `build` generates to every output, once per
+    /// frame of `frames`, as `mesh` makes it from the frame and the face
+    /// kind the output takes. `build` also gives the cells generated.
+    fn run_frames<T>(
+        &self,
+        frames: u32,
+        build: impl FnOnce(&CancelToken) -> Result<(T, usize)>,
+        mesh: impl Fn(&T, u32, FaceKind) -> Mesh,
+        cancel: &CancelToken,
+    ) -> Result<JobReport> {
+        let start = Instant::now();
+        let mut options = self.export_options();
+        options.exporters = self.exporters()?;
+        let rule = self.rule()?;
+        let mut timer = StageTimer::default();
+        let (subject, cells) = timer.time("generate", || build(cancel))?;
+        let extent = std::array::from_fn(|axis| rule.side_along(axis, self.depth) as f64);
+        let mut artifacts = Vec::new();
+        for frame in 0..frames {
+            for output in &self.outputs {
+                let kind = match Format::from_path(output) {
+                    Ok(Format::Obj) => FaceKind::Quads,
+                    _ => FaceKind::Triangles,
+                };
+                let mesh = timer.time("mesh", || mesh(&subject, frame, kind));
+                let path = frame_path(output, frame, frames > 1);
+                artifacts.push(timer.time("export", || {
+                    export_mesh(&mesh, extent, &path, &options, cancel)
+                })?);
+            }
+            cancel.check()?;
+        }
+        tracing::info!(cells, frames, files = artifacts.len(), "job finished");
+        Ok(JobReport {
+            name: self.display_name(),
+            cells,
+            surface: false,
+            slices: 1,
+            levels: level_stats(&rule, self.depth),
+            stages: timer.stages,
+            thin_features: Vec::new(),
+            orientations: Vec::new(),
+            artifacts,
+            elapsed: start.elapsed(),
+            peak_rss: peak_rss(),
+        })
+    }
+
+    /// Meshes every output a slab of layers at a time, for 4D rules slice
+    /// by slice, testing cells against the rule rather than generating
+    /// the lattice.
+    fn run_out_of_core(&self, cancel: &CancelToken) -> Result<JobReport> {
+        let start = Instant::now();
+        let options = self.export_options();
+        let rule = self.rule()?;
+        let solid = self.cell_test(&rule);
+        let shape = std::array::from_fn(|axis| rule.side_along(axis, self.depth));
+        let mut timer = StageTimer::default();
+        let mut artifacts = Vec::new();
+        let slices = match self.dims {
+            3 => {
+                for output in &self.outputs {
+                    artifacts.push(timer.time("export", || {
+                        export_streaming(
+                            shape,
+                            |p| solid(&p),
+                            slab_layers(shape),
+                            output,
+                            &options,
+                            cancel,
+                        )
+                    })?);
+                }
+                1
+            }
+            _ => {
+                let slices = self.slice_indices(rule.side_along(3, self.depth))?;
+                for &w in &slices {
+                    for output in &self.outputs {
+                        let path = slice_path(output, w, slices.len() > 1);
+                        artifacts.push(timer.time("export", || {
+                            export_streaming(
+                                shape,
+                                |[x, y, z]| self.slab_ws(w).any(|w| solid(&[x, y, z, w])),
+                                slab_layers(shape),
+                                &path,
+                                &options,
+                                cancel,
+                            )
+                        })?);
+                    }
+                }
+                slices.len()
+            }
+        };
+        let cells = expected_cells(&rule, self.depth) as usize;
+        tracing::info!(cells, slices, files = artifacts.len(), "job finished");
+        Ok(JobReport {
+            name: self.display_name(),
+            cells,
+            surface: false,
+            slices,
+            levels: level_stats(&rule, self.depth),
+            stages: timer.stages,
+            thin_features: Vec::new(),
+            orientations: Vec::new(),
+            artifacts,
+            elapsed: start.elapsed(),
+            peak_rss: peak_rss(),
+        })
+    }
+
+    /// Generates this rank's rows of chunks of the lattice, or of each
+    /// slice, and writes them to every output; see [`crate::ranks`].
+    fn run_ranked(&self, ranks: Ranks, cancel: &CancelToken) -> Result<JobReport> {
+        let start = Instant::now();
+        let options = self.export_options();
+        let rule = self.rule()?;
+        let solid = self.cell_test(&rule);
+        let shape = std::array::from_fn(|axis| rule.side_along(axis, self.depth));
+        let chunk = options.zarr.chunk;
+        let rows = ranks.share(shape[2].div_ceil(chunk));
+        let z0 = (rows.start * chunk).min(shape[2]);
+        let part = [shape[0], shape[1], (rows.end * chunk).min(shape[2]) - z0];
+        let slices: Vec<Option<usize>> = match self.dims {
+            3 => vec![None],
+            _ => self
+                .slice_indices(rule.side_along(3, self.depth))?
+                .into_iter()
+                .map(Some)
+                .collect(),
+        };
+        let mut timer = StageTimer::default();
+        let mut artifacts = Vec::new();
+        let mut cells = 0;
+        for &w in &slices {
+            let lattice = timer.time("generate", || {
+                Lattice3::from_fn(part, cancel, |[x, y, z]| match w {
+                    Some(w) => self.slab_ws(w).any(|w| solid(&[x, y, z + z0, w])),
+                    None => solid(&[x, y, z + z0]),
+                })
+            })?;
+            cells += lattice.count();
+            for output in &self.outputs {
+                let path = match w {
+                    Some(w) => slice_path(output, w, slices.len() > 1),
+                    None => output.clone(),
+                };
+                artifacts.extend(timer.time("export", || {
+                    export_zarr_part(&lattice, shape, z0, ranks, &path, &options, cancel)
+                })?);
+            }
+        }
+        tracing::info!(
+            rank = ranks.rank,
+            cells,
+            files = artifacts.len(),
+            "rank finished"
+        );
+        let chunks = options
+            .zarr
+            .chunks(shape)
+            .into_iter()
+            .filter(|chunk| rows.contains(&chunk[0]))
+            .count()
+            * self.outputs.len()
+            * slices.len();
+        if let Some(totals) = all_reduce(cells as u64, chunks) {
+            if ranks.rank == 0 {
+                tracing::info!(
+                    ranks = totals.ranks,
+                    cells = totals.cells,
+                    chunks = totals.chunks,
+                    "every rank finished"
+                );
+            }
+        }
+        Ok(JobReport {
+            name: format!("{} rank {}/{}", self.display_name(), ranks.rank, ranks.size),
+            cells,
+            surface: false,
+            slices: slices.len(),
+            levels: Vec::new(),
+            stages: timer.stages,
+            thin_features: Vec::new(),
+            orientations: Vec::new(),
+            artifacts,
+            elapsed: start.elapsed(),
+            peak_rss: peak_rss(),
+        })
+    }
+
+    /// The export settings of the job, without its plugins' exporters;
+    /// see [`Job::exporters`].
+    pub fn export_options(&self) -> ExportOptions {
+        ExportOptions {
+            transform: Affine::from_transforms(&self.transforms),
+            tiling: self.tiling.clone(),
+            normals: self.normals,
+            simplify: self.simplify,
+            repair: self.repair,
+            boundary: self.boundary,
+            offset: self.offset,
+            images: self.images,
+            schematic: self.schematic.clone(),
+            gltf: self.gltf,
+            texture: self.texture,
+            zarr: self.zarr,
+            precision: self.precision,
+            coordinates: self.coordinates,
+            normalize: self.normalize,
+            domain: self.domain,
+            exporters: Exporters::default(),
+            colors: self.color.clone().map(|map| Colors {
+                map,
+                levels: self.levels().unwrap_or_default(),
+                w: 0.0,
+            }),
+            materials: self.materials().unwrap_or_default(),
+            checkpoint: Checkpoint::default(),
+        }
+    }
+
+    /// The job's material rules bound to its rule, if it has any.
+    pub fn materials(&self) -> Result<Option<Materials>> {
+        if self.materials.is_empty() {
+            return Ok(None);
+        }
+        let rule = self.rule()?;
+        Materials::new(&self.materials, rule.bases(), self.depth).map(Some)
+    }
+
+    /// The exporters of the job's plugins.
+    pub fn exporters(&self) -> Result<Exporters> {
+        let mut exporters = Exporters::default();
+        for library in &self.plugins {
+            exporters.load(library)?;
+        }
+        Ok(exporters)
+    }
+
+    /// The files the job reads besides its definition: its script, the
+    /// model it imports or fills and its exporter plugins.
+    pub fn inputs(&self) -> Vec<PathBuf> {
+        let script = self.script.as_ref().map(|script| &script.file);
+        let import = self.import.as_ref().map(|import| &import.model);
+        let infill = self.infill.as_ref().map(|infill| &infill.model);
+        [script, import, infill]
+            .into_iter()
+            .flatten()
+            .chain(&self.plugins)
+            .cloned()
+            .collect()
+    }
+
+    /// Resolves the job's relative paths, the files it reads and writes
+    /// alike, against `dir`.
+    pub fn rebase(&mut self, dir: &Path) {
+        let rebase = |path: &mut PathBuf| {
+            if path.is_relative() {
+                *path = dir.join(&*path);
+            }
+        };
+        self.outputs.iter_mut().for_each(rebase);
+        if let Some(infill) = &mut self.infill {
+            rebase(&mut infill.model);
+        }
+        if let Some(import) = &mut self.import {
+            rebase(&mut import.model);
+        }
+        if let Some(monitor) = &mut self.monitor {
+            rebase(&mut monitor.path);
+        }
+        if let Some(complex) = &mut self.complex {
+            rebase(complex);
+        }
+        if let Some(script) = &mut self.script {
+            rebase(&mut script.file);
+        }
+        self.plugins.iter_mut().for_each(rebase);
+        if let Some(sonify) = &mut self.sonify {
+            rebase(&mut sonify.path);
+        }
+    }
+
+    /// The w indices to slice at, which may run past `side` into further
+    /// copies when the job is tiled in w; a slab's first.
+    pub(crate) fn slice_indices(&self, side: usize) -> Result<Vec<usize>> {
+        let side = side * self.tiling.count[3];
+        if let Some([w0, w1]) = self.slab {
+            if w1 >= side {
+                return Err(Error::InvalidJob(format!(
+                    "slab w={w0}..{w1} runs outside the lattice (side {side})"
+                )));
+            }
+            return Ok(vec![w0]);
+        }
+        if self.slices.is_empty() {
+            return Ok((0..side).collect());
+        }
+        if let Some(&w) = self.slices.iter().find(|&&w| w >= side) {
+            return Err(Error::InvalidJob(format!(
+                "slice w={w} is outside the lattice (side {side})"
+            )));
+        }
+        Ok(self.slices.clone())
+    }
+
+    /// The membership test of `rule` by lattice index, in the job's
+    /// domain: about the centre, by balanced digits, when centred.
+    fn cell_test<'a>(&'a self, rule: &'a Rule) -> impl Fn(&[usize]) -> bool + Sync + 'a {
+        let center = rule.center(self.depth);
+        move |p| match self.domain {
+            Domain::Positive => rule.is_solid(p, self.depth),
+            Domain::Centered => {
+                let mut centered = [0; MAX_DIMS];
+                for ((c, &p), &center) in centered.iter_mut().zip(p).zip(&center) {
+                    *c = p as i64 - center as i64;
+                }
+                rule.is_solid_centered(&centered[..p.len()], self.depth)
+            }
+        }
+    }
+
+    /// The w indices projected into the slice at `w`: the job's slab, or
+    /// `w` alone.
+    pub(crate) fn slab_ws(&self, w: usize) -> RangeInclusive<usize> {
+        match self.slab {
+            Some([w0, w1]) => w0..=w1,
+            None => w..=w,
+        }
+    }
+}
+
+/// The source of a 4D job's slices; see [`Job::slicer`].
+enum Slicer {
+    Lattice(Lattice<4>),
+    /// A planned sweep, with the cells of the whole hypercube.
+    Sweep(Sweep, usize),
+}
+
+impl Slicer {
+    fn side(&self) -> usize {
+        match self {
+            Slicer::Lattice(lattice) => lattice.shape()[3],
+            Slicer::Sweep(sweep, _) => sweep.side(),
+        }
+    }
+
+    fn cells(&self) -> usize {
+        match self {
+            Slicer::Lattice(lattice) => lattice.count(),
+            Slicer::Sweep(_, cells) => *cells,
+        }
+    }
+
+    /// The projection of the slices at `ws`, which may run past `side`
+    /// into further copies.
+    fn slab(
+        &mut self,
+        ws: RangeInclusive<usize>,
+        side: usize,
+        cancel: &CancelToken,
+    ) -> Result<Lattice3> {
+        match self {
+            Slicer::Lattice(lattice) => {
+                let (first, last) = (ws.start() % side, ws.end() % side);
+                Ok(if ws.end() - ws.start() + 1 >= side {
+                    lattice.slab_w(0..=side - 1)
+                } else if first <= last {
+                    lattice.slab_w(first..=last)
+                } else {
+                    // Wrapping from one copy into the next.
+                    let mut slab = lattice.slab_w(first..=side - 1);
+                    slab.union_with(&lattice.slab_w(0..=last));
+                    slab
+                })
+            }
+            Slicer::Sweep(sweep, _) => {
+                // Slices of w with the same key are the same.
+                let mut keys = HashSet::new();
+                let mut slab: Option<Lattice3> = None;
+                for w in ws.map(|w| w % side) {
+                    if !keys.insert(sweep.key(w)) {
+                        continue;
+                    }
+                    let slice = sweep.slice(w, cancel)?;
+                    match &mut slab {
+                        Some(slab) => slab.union_with(&slice),
+                        None => slab = Some(slice),
+                    }
+                }
+                Ok(slab.expect("a slab has a slice"))
+            }
+        }
+    }
+}
+
+/// The surface of an exact cut's cells, logging their volume and area.
+fn exact_mesh(cells: PolyMesh) -> Result<Mesh> {
+    tracing::info!(
+        cells = cells.cell_count(),
+        volume = cells.volume(),
+        area = cells.area(),
+        "cut cells"
+    );
+    Ok(cells.surface())
+}
+
+/// Whether `path` names a volume or image stack rather than a mesh.
+fn is_volume(path: &Path) -> bool {
+    Format::from_path(path).is_ok_and(Format::is_volume)
+}
+
+/// Writes `lattice` to `path`: one file, one per layer for an image stack
+/// or one per piece of a large schematic.
+fn export_lattice(
+    lattice: &Lattice3,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Vec<Artifact>> {
+    match Format::from_path(path) {
+        Ok(Format::Schem) => export_schematic(lattice, path, options, cancel),
+        Ok(Format::Zarr) => export_zarr(lattice, path, options, cancel),
+        Ok(format) if format.is_image_stack() => export_stack(lattice, path, options, cancel),
+        _ => Ok(vec![export(lattice, path, options, cancel)?]),
+    }
+}
+
+/// Exports `lattice` to `path`, or, when the path has a `{material}`, the
+/// cells of each of the options' materials to the path naming it.
+fn export_materials(
+    lattice: &Lattice3,
+    path: &Path,
+    options: &ExportOptions,
+    cancel: &CancelToken,
+) -> Result<Vec<Artifact>> {
+    let text = path.to_string_lossy();
+    let Some(materials) = options
+        .materials
+        .as_ref()
+        .filter(|_| text.contains("{material}"))
+    else {
+        return export_lattice(lattice, path, options, cancel);
+    };
+    let mut artifacts = Vec::new();
+    for (name, part) in materials.names().zip(materials.split(lattice)) {
+        let path = PathBuf::from(text.replace("{material}", name));
+        artifacts.extend(export_lattice(&part, &path, options, cancel)?);
+    }
+    Ok(artifacts)
+}
+
+/// Per-level kept and removed counts, from the rule alone.
+pub fn level_stats(rule: &Rule, depth: u32) -> Vec<LevelStats> {
+    (1..=depth)
+        .map(|level| LevelStats {
+            level,
+            kept: expected_cells(rule, level),
+            removed: rule.removed(level),
+        })
+        .collect()
+}
+
+/// Accumulates wall time per named stage, in first-use order.
+#[derive(Default)]
+struct StageTimer {
+    stages: Vec<StageTiming>,
+}
+
+impl StageTimer {
+    fn time<T>(&mut self, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let counting = alloc::start();
+        let value = f();
+        let elapsed = start.elapsed();
+        let allocations = counting.map(alloc::Stage::finish);
+        match self.stages.iter_mut().find(|s| s.stage == stage) {
+            Some(timing) => {
+                timing.elapsed += elapsed;
+                if let (Some(total), Some(more)) = (&mut timing.allocations, allocations) {
+                    total.count += more.count;
+                    total.bytes += more.bytes;
+                    total.peak_bytes = total.peak_bytes.max(more.peak_bytes);
+                }
+            }
+            None => self.stages.push(StageTiming {
+                stage,
+                elapsed,
+                allocations,
+            }),
+        }
+        value
+    }
+
+    /// Like [`StageTimer::time`], leaving stages that are not `enabled`
+    /// out of the report.
+    fn time_if<T>(&mut self, enabled: bool, stage: &'static str, f: impl FnOnce() -> T) -> T {
+        if enabled {
+            self.time(stage, f)
+        } else {
+            f()
+        }
+    }
+}
+
+/// The process's peak resident memory, reported with the `alloc-stats`
+/// feature.
+fn peak_rss() -> Option<u64> {
+    if cfg!(feature = "alloc-stats") {
+        alloc::resident().map(|(_, peak)| peak)
+    } else {
+        None
+    }
+}
+
+/// Substitutes `{w}` in `path`, or appends `_w<index>` to the file stem when
+/// several slices share an output without a placeholder.
+pub(crate) fn slice_path(path: &Path, w: usize, many: bool) -> PathBuf {
+    let text = path.to_string_lossy();
+    if text.contains("{w}") {
+        return PathBuf::from(text.replace("{w}", &w.to_string()));
+    }
+    if !many {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_w{w}");
+    if let Some(extension) = path.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// Substitutes `{frame}` in `path`, or appends `_f<frame>` to the file
+/// stem when several frames share an output without a placeholder.
+pub(crate) fn frame_path(path: &Path, frame: u32, many: bool) -> PathBuf {
+    let text = path.to_string_lossy();
+    if text.contains("{frame}") {
+        return PathBuf::from(text.replace("{frame}", &frame.to_string()));
+    }
+    if !many {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_f{frame}");
+    if let Some(extension) = path.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
@@ -0,0 +1,479 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::numa;
+use crate::rule::{decompose, expected_cells, Rule};
+use crate::symmetry::Domain;
+
+/// How positions outside a lattice are read.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    /// Everything outside is empty.
+    #[default]
+    Open,
+    /// The lattice repeats, as if it were a torus.
+    Periodic,
+    /// The lattice is reflected at each face, so the cell just outside
+    /// reads as the one just inside.
+    Mirrored,
+}
+
+impl Boundary {
+    /// Maps coordinate `c` onto an axis of length `extent`, or None if it
+    /// falls outside an open boundary.
+    pub fn wrap(self, c: i64, extent: usize) -> Option<usize> {
+        let n = extent as i64;
+        if (0..n).contains(&c) {
+            return Some(c as usize);
+        }
+        match self {
+            Boundary::Open => None,
+            Boundary::Periodic => Some(c.rem_euclid(n) as usize),
+            Boundary::Mirrored => {
+                let m = c.rem_euclid(2 * n);
+                Some(if m < n { m } else { 2 * n - 1 - m } as usize)
+            }
+        }
+    }
+}
+
+/// Face-connected components of a lattice's filled cells.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Components {
+    /// Per flat index: 0 for empty cells, `i + 1` for cells of component
+    /// `i`.
+    pub labels: Vec<u32>,
+    /// Cells in each component, in order of each one's first cell.
+    pub sizes: Vec<usize>,
+}
+
+/// A dense `D`-dimensional occupancy grid stored as a bitset.
+///
+/// Cells are addressed by their integer coordinates, x fastest. Coordinates
+/// outside the grid read as empty.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Lattice<const D: usize> {
+    shape: [usize; D],
+    bits: Vec<u64>,
+}
+
+/// The 3D lattices produced by slicing and consumed by meshing and export.
+pub type Lattice3 = Lattice<3>;
+/// The 4D hypersponge lattices produced by generation.
+pub type Lattice4 = Lattice<4>;
+
+impl<const D: usize> Lattice<D> {
+    /// Creates an empty lattice with the given extent per axis.
+    pub fn new(shape: [usize; D]) -> Self {
+        let len: usize = shape.iter().product();
+        Lattice {
+            shape,
+            bits: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    /// Generates the depth-`depth` fractal of `rule` by testing every cell;
+    /// see [`Lattice::generate_recursive`] for a faster way.
+    pub fn generate(rule: &Rule, depth: u32) -> Self {
+        Lattice::generate_cancellable(rule, depth, &CancelToken::new())
+            .expect("a fresh token is never cancelled")
+    }
+
+    /// Like [`Lattice::generate`], polling `cancel` between blocks of cells.
+    pub fn generate_cancellable(rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<Self> {
+        Lattice::generate_observed(rule, depth, cancel, |_, _| Ok(()))
+    }
+
+    /// Like [`Lattice::generate_cancellable`], showing the partly generated
+    /// lattice to `observe` as [`Lattice::from_fn_observed`] does.
+    #[tracing::instrument(name = "generate", skip_all, fields(rule = %rule.name(), depth = depth))]
+    pub fn generate_observed(
+        rule: &Rule,
+        depth: u32,
+        cancel: &CancelToken,
+        observe: impl FnMut(&Self, usize) -> Result<()>,
+    ) -> Result<Self> {
+        assert_eq!(rule.dims(), D, "rule dimension does not match lattice");
+        let shape = std::array::from_fn(|axis| rule.side_along(axis, depth));
+        Lattice::from_fn_observed(shape, cancel, |p| rule.is_solid(&p, depth), observe)
+    }
+
+    /// Like [`Lattice::generate_cancellable`], building the fractal from
+    /// the bottom up instead of testing every cell: each level is made of
+    /// copies of the levels below, one per kept subcell, so the work goes
+    /// with the filled cells rather than the whole lattice.
+    #[tracing::instrument(name = "generate_recursive", skip_all, fields(rule = %rule.name(), depth = depth))]
+    pub fn generate_recursive(rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<Self> {
+        assert_eq!(rule.dims(), D, "rule dimension does not match lattice");
+        // The lattices one and two levels below the one being built, the
+        // empty one of depth -1 below a single cell.
+        let mut below = (Lattice::new([1; D]), Lattice::new([0; D]));
+        below.0.set([0; D], true);
+        let mut digits = [0; D];
+        let mut offset = [0; D];
+        // A level's work goes with the cells it places, so progress is
+        // counted in cells.
+        let total = (1..=depth)
+            .map(|level| expected_cells(rule, level))
+            .fold(0, u64::saturating_add);
+        let mut done = 0u64;
+        for level in 1..=depth {
+            let shape = std::array::from_fn(|axis| rule.side_along(axis, level));
+            let mut lattice = Lattice::new(shape);
+            // Built bottom up, so the finest mask comes first.
+            let mask = rule.mask(depth + 1 - level);
+            for index in (0..mask.len()).filter(|&index| mask[index]) {
+                cancel.check()?;
+                decompose(index, rule.bases(), &mut digits);
+                let copy = match rule.copy_placement(&digits, level, &mut offset) {
+                    1 => &below.0,
+                    _ => &below.1,
+                };
+                for p in copy.iter() {
+                    lattice.set(std::array::from_fn(|axis| offset[axis] + p[axis]), true);
+                }
+            }
+            below = (lattice, below.0);
+            done = done.saturating_add(expected_cells(rule, level));
+            tracing::debug!(done, total, "cells built");
+        }
+        Ok(below.0)
+    }
+
+    /// Like [`Lattice::generate_cancellable`], testing only the cells of
+    /// one fundamental domain of the rule's symmetries, a 48th of the
+    /// Menger sponge, and mirroring them into the rest.
+    #[tracing::instrument(name = "generate_symmetric", skip_all, fields(rule = %rule.name(), depth = depth))]
+    pub fn generate_symmetric(rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<Self> {
+        assert_eq!(rule.dims(), D, "rule dimension does not match lattice");
+        let shape = std::array::from_fn(|axis| rule.side_along(axis, depth));
+        let domain = Domain::<D>::new(&rule.symmetries());
+        let part = Lattice::from_fn(domain.folded(shape), cancel, |q| {
+            domain.contains(q) && rule.is_solid(&q, depth)
+        })?;
+        Lattice::from_fn(shape, cancel, |p| part.get(domain.canonical(p, shape)))
+    }
+
+    /// Like [`Lattice::generate_cancellable`], on every core and placed in
+    /// memory NUMA node by node, as [`crate::numa`] describes; needs the
+    /// `numa` feature.
+    #[tracing::instrument(name = "generate_numa", skip_all, fields(rule = %rule.name(), depth = depth))]
+    pub fn generate_numa(rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<Self> {
+        assert_eq!(rule.dims(), D, "rule dimension does not match lattice");
+        let nodes = numa::nodes()?;
+        let shape: [usize; D] = std::array::from_fn(|axis| rule.side_along(axis, depth));
+        // The first two levels' branches along the last axis, so that
+        // there are enough to share out between nodes.
+        let branches = rule.bases()[D - 1].pow(2.min(depth)) as usize;
+        Lattice::from_fn_partitioned(shape, branches, &nodes, cancel, |p| {
+            rule.is_solid(&p, depth)
+        })
+    }
+
+    /// Like [`Lattice::from_fn`], on threads pinned to `nodes`, each
+    /// writing its own run of the lattice's words, split as
+    /// [`numa::partition`] does.
+    pub fn from_fn_partitioned(
+        shape: [usize; D],
+        branches: usize,
+        nodes: &[numa::Node],
+        cancel: &CancelToken,
+        solid: impl Fn([usize; D]) -> bool + Sync,
+    ) -> Result<Self> {
+        let mut lattice = Lattice::new(shape);
+        let len = lattice.len();
+        let parts = numa::partition(lattice.bits.len(), branches, nodes);
+        let position = |mut index: usize| {
+            let mut p = [0; D];
+            for (c, &extent) in p.iter_mut().zip(&shape) {
+                *c = index % extent;
+                index /= extent;
+            }
+            p
+        };
+        std::thread::scope(|scope| {
+            let mut rest = lattice.bits.as_mut_slice();
+            let mut threads = Vec::new();
+            for (words, node) in parts {
+                let (part, tail) = std::mem::take(&mut rest).split_at_mut(words.len());
+                rest = tail;
+                let (solid, position) = (&solid, &position);
+                threads.push(scope.spawn(move || -> Result<()> {
+                    numa::pin(&nodes[node]);
+                    for (offset, word) in part.iter_mut().enumerate() {
+                        let first = (words.start + offset) * 64;
+                        if offset % 1024 == 0 {
+                            cancel.check()?;
+                        }
+                        for bit in 0..64.min(len - first) {
+                            if solid(position(first + bit)) {
+                                *word |= 1 << bit;
+                            }
+                        }
+                    }
+                    Ok(())
+                }));
+            }
+            threads
+                .into_iter()
+                .try_for_each(|thread| thread.join().expect("generation threads do not panic"))
+        })?;
+        Ok(lattice)
+    }
+
+    /// Fills a lattice of the given shape with the cells `solid` accepts,
+    /// polling `cancel` between blocks of cells.
+    pub fn from_fn(
+        shape: [usize; D],
+        cancel: &CancelToken,
+        solid: impl Fn([usize; D]) -> bool,
+    ) -> Result<Self> {
+        Lattice::from_fn_observed(shape, cancel, solid, |_, _| Ok(()))
+    }
+
+    /// The box of `size` cells from `low` in the lattice of `shape` whose
+    /// cells `solid` accepts, inside a one-cell ghost border holding the
+    /// neighbouring cells as `boundary` reads them; see
+    /// [`chunk_faces`](crate::mesh::chunk_faces).
+    pub fn chunk_from_fn(
+        shape: [usize; D],
+        low: [usize; D],
+        size: [usize; D],
+        boundary: Boundary,
+        cancel: &CancelToken,
+        solid: impl Fn([usize; D]) -> bool,
+    ) -> Result<Self> {
+        let ghosted = std::array::from_fn(|axis| size[axis] + 2);
+        Lattice::from_fn(ghosted, cancel, |g| {
+            let mut p = [0; D];
+            for axis in 0..D {
+                let c = low[axis] as i64 + g[axis] as i64 - 1;
+                match boundary.wrap(c, shape[axis]) {
+                    Some(c) => p[axis] = c,
+                    None => return false,
+                }
+            }
+            solid(p)
+        })
+    }
+
+    /// Like [`Lattice::from_fn`], also handing the lattice and the number
+    /// of cells filled in so far, in flat index order, to `observe` between
+    /// blocks of cells and once complete.
+    pub fn from_fn_observed(
+        shape: [usize; D],
+        cancel: &CancelToken,
+        solid: impl Fn([usize; D]) -> bool,
+        mut observe: impl FnMut(&Self, usize) -> Result<()>,
+    ) -> Result<Self> {
+        let mut lattice = Lattice::new(shape);
+        for index in 0..lattice.len() {
+            if index % (1 << 16) == 0 {
+                cancel.check()?;
+                observe(&lattice, index)?;
+                tracing::trace!(done = index, total = lattice.len(), "cells tested");
+            }
+            if solid(lattice.position(index)) {
+                lattice.bits[index / 64] |= 1 << (index % 64);
+            }
+        }
+        observe(&lattice, lattice.len())?;
+        Ok(lattice)
+    }
+
+    pub fn shape(&self) -> [usize; D] {
+        self.shape
+    }
+
+    /// Total number of cells, filled or not.
+    pub fn len(&self) -> usize {
+        self.shape.iter().product()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Flat index of an in-bounds position.
+    pub fn index(&self, p: [usize; D]) -> usize {
+        let mut index = 0;
+        for axis in (0..D).rev() {
+            debug_assert!(p[axis] < self.shape[axis]);
+            index = index * self.shape[axis] + p[axis];
+        }
+        index
+    }
+
+    /// Position of a flat index.
+    pub fn position(&self, mut index: usize) -> [usize; D] {
+        let mut p = [0; D];
+        for (c, &extent) in p.iter_mut().zip(&self.shape) {
+            *c = index % extent;
+            index /= extent;
+        }
+        p
+    }
+
+    pub fn contains(&self, p: [i64; D]) -> bool {
+        (0..D).all(|axis| p[axis] >= 0 && (p[axis] as usize) < self.shape[axis])
+    }
+
+    pub fn get(&self, p: [usize; D]) -> bool {
+        let index = self.index(p);
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Like [`Lattice::get`] but accepts out-of-bounds (including negative)
+    /// positions, which read as empty.
+    pub fn get_signed(&self, p: [i64; D]) -> bool {
+        self.contains(p) && self.get(p.map(|c| c as usize))
+    }
+
+    /// Maps a possibly out-of-bounds position into the lattice under
+    /// `boundary`.
+    pub fn wrap(&self, p: [i64; D], boundary: Boundary) -> Option<[usize; D]> {
+        let mut q = [0; D];
+        for axis in 0..D {
+            q[axis] = boundary.wrap(p[axis], self.shape[axis])?;
+        }
+        Some(q)
+    }
+
+    /// Like [`Lattice::get_signed`], reading outside positions under
+    /// `boundary`.
+    pub fn get_bounded(&self, p: [i64; D], boundary: Boundary) -> bool {
+        self.wrap(p, boundary).is_some_and(|q| self.get(q))
+    }
+
+    /// The in-lattice positions sharing a face with `p` under `boundary`.
+    /// Wrapped neighbours may repeat or be `p` itself on small or mirrored
+    /// lattices.
+    pub fn face_neighbours(
+        &self,
+        p: [usize; D],
+        boundary: Boundary,
+    ) -> impl Iterator<Item = [usize; D]> + '_ {
+        let p = p.map(|c| c as i64);
+        (0..2 * D).filter_map(move |i| {
+            let mut q = p;
+            q[i / 2] += if i % 2 == 0 { -1 } else { 1 };
+            self.wrap(q, boundary)
+        })
+    }
+
+    /// Labels the face-connected components of the filled cells, joining
+    /// cells across the boundary when it wraps.
+    pub fn components(&self, boundary: Boundary) -> Components {
+        let mut labels = vec![0u32; self.len()];
+        let mut sizes = Vec::new();
+        let mut stack = Vec::new();
+        for start in self.iter() {
+            if labels[self.index(start)] != 0 {
+                continue;
+            }
+            sizes.push(0);
+            let label = sizes.len() as u32;
+            labels[self.index(start)] = label;
+            stack.push(start);
+            while let Some(p) = stack.pop() {
+                sizes[label as usize - 1] += 1;
+                for q in self.face_neighbours(p, boundary) {
+                    let index = self.index(q);
+                    if self.get(q) && labels[index] == 0 {
+                        labels[index] = label;
+                        stack.push(q);
+                    }
+                }
+            }
+        }
+        Components { labels, sizes }
+    }
+
+    pub fn set(&mut self, p: [usize; D], value: bool) {
+        let index = self.index(p);
+        if value {
+            self.bits[index / 64] |= 1 << (index % 64);
+        } else {
+            self.bits[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    /// The lattice with filled and empty cells swapped.
+    pub fn complement(&self) -> Self {
+        let mut complement = Lattice {
+            shape: self.shape,
+            bits: self.bits.iter().map(|w| !w).collect(),
+        };
+        let tail = self.len() % 64;
+        if let (Some(last), true) = (complement.bits.last_mut(), tail != 0) {
+            *last &= (1 << tail) - 1;
+        }
+        complement
+    }
+
+    /// Fills every cell filled in `other`, a lattice of the same shape.
+    pub fn union_with(&mut self, other: &Self) {
+        assert_eq!(self.shape, other.shape, "lattices differ in shape");
+        for (word, &other) in self.bits.iter_mut().zip(&other.bits) {
+            *word |= other;
+        }
+    }
+
+    /// Number of filled cells.
+    pub fn count(&self) -> usize {
+        self.bits.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Iterates over the positions of all filled cells in index order.
+    pub fn iter(&self) -> impl Iterator<Item = [usize; D]> + '_ {
+        self.bits
+            .iter()
+            .enumerate()
+            .flat_map(move |(word_index, &word)| {
+                let mut word = word;
+                std::iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some(self.position(word_index * 64 + bit))
+                })
+            })
+    }
+}
+
+impl Lattice4 {
+    /// The 3D cross-section of cells whose w coordinate equals `w`.
+    pub fn slice_w(&self, w: usize) -> Lattice3 {
+        let [sx, sy, sz, sw] = self.shape;
+        assert!(w < sw, "slice w={w} is outside the lattice");
+        let mut slice = Lattice3::new([sx, sy, sz]);
+        for z in 0..sz {
+            for y in 0..sy {
+                for x in 0..sx {
+                    if self.get([x, y, z, w]) {
+                        slice.set([x, y, z], true);
+                    }
+                }
+            }
+        }
+        slice
+    }
+
+    /// The 3D projection of the slab of cells whose w coordinate is in
+    /// `ws`: a cell is filled when any cell above it in the slab is.
+    pub fn slab_w(&self, ws: RangeInclusive<usize>) -> Lattice3 {
+        let mut slab = Lattice3::new([self.shape[0], self.shape[1], self.shape[2]]);
+        for w in ws {
+            slab.union_with(&self.slice_w(w));
+        }
+        slab
+    }
+}
@@ -0,0 +1,81 @@
+//! Generates 4D fractals and slices them into sets of 3D objects.
+
+pub mod alloc;
+pub mod analysis;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+pub mod batch;
+pub mod bench;
+pub mod blender;
+pub mod bvh;
+pub mod cancel;
+pub mod checkpoint;
+#[cfg(feature = "rapier")]
+pub mod collapse;
+#[cfg(feature = "rapier")]
+pub mod collider;
+pub mod color;
+pub mod complex;
+mod compress;
+pub mod dataset;
+pub mod deviation;
+pub mod diff;
+pub mod distance;
+pub mod error;
+pub mod escape;
+pub mod explode;
+pub mod export;
+pub mod growth;
+pub mod image;
+pub mod implicit;
+pub mod import;
+pub mod infill;
+pub mod instances;
+pub mod job;
+pub mod lattice;
+pub mod lsystem;
+pub mod materials;
+pub mod mesh;
+pub mod metrics;
+pub mod monitor;
+pub mod morphology;
+#[cfg(feature = "evcxr")]
+pub mod notebook;
+pub mod numa;
+pub mod orientation;
+pub mod plan;
+#[cfg(feature = "plots")]
+pub mod plot;
+pub mod plugin;
+pub mod preset;
+pub mod printability;
+pub mod provenance;
+mod random;
+pub mod ranks;
+pub mod removal;
+pub mod render;
+pub mod report;
+pub mod rule;
+pub mod rule_test;
+pub mod schematic;
+pub mod script;
+pub mod sdf;
+pub mod seekable;
+pub mod server;
+pub mod shader;
+pub mod slice;
+pub mod sonify;
+pub mod store;
+pub mod sweep;
+pub mod symmetry;
+pub mod texture;
+pub mod tiling;
+pub mod transform;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+pub mod volume;
+pub mod watch;
+pub mod web_demo;
+pub mod zarr;
@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::mesh::{cross, dot, normalize, sub, Mesh, Polygons};
+
+/// A surface fractal built by rewriting rather than by subdividing a
+/// lattice. Surfaces are always 3D and come out as triangle meshes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Surface {
+    /// The 3D Koch surface: starting from a tetrahedron, every triangle is
+    /// split into four and the middle one raised into a tetrahedral bump.
+    Koch,
+    /// The paths an L-system's turtle draws, as square tubes.
+    Turtle { name: String, system: LSystem },
+}
+
+impl Surface {
+    /// Names accepted by [`Surface::by_name`].
+    pub const NAMES: &'static [&'static str] = &["koch-surface", "carpet", "koch-curve"];
+
+    /// Looks up a built-in surface fractal by name.
+    pub fn by_name(name: &str) -> Option<Self> {
+        let system = match name {
+            "koch-surface" => return Some(Surface::Koch),
+            // Traces the Sierpinski carpet, the face of the Menger sponge.
+            "carpet" => LSystem::new("F", &[('F', "F+F-F-F-G+F+F+F-F"), ('G', "GGG")], 90.0),
+            "koch-curve" => LSystem::new("F--F--F", &[('F', "F+F--F+F")], 60.0),
+            _ => return None,
+        };
+        Some(Surface::Turtle {
+            name: name.to_string(),
+            system,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Surface::Koch => "koch-surface",
+            Surface::Turtle { name, .. } => name,
+        }
+    }
+
+    /// Number of triangles [`Surface::build`] produces, without building.
+    pub fn triangles(&self, iterations: u32) -> u64 {
+        match self {
+            Surface::Koch => 4 * 6u64.saturating_pow(iterations),
+            Surface::Turtle { system, .. } => system
+                .symbol_counts(iterations)
+                .get(&'F')
+                .map_or(0, |&f| f.saturating_mul(TUBE_TRIANGLES)),
+        }
+    }
+
+    /// Builds the mesh after `iterations` rewrites, one unit per step for
+    /// turtle paths and on a unit-edge seed for the Koch surface.
+    pub fn build(&self, iterations: u32, cancel: &CancelToken) -> Result<Mesh> {
+        match self {
+            Surface::Koch => koch_surface(iterations, cancel),
+            Surface::Turtle { system, .. } => {
+                let program = system.expand(iterations, cancel)?;
+                Ok(tubes(&system.draw(&program), system.thickness))
+            }
+        }
+    }
+}
+
+/// A Lindenmayer system drawn by a 3D turtle.
+///
+/// `F` draws a step forward; `f` and `G` move without drawing. `+`/`-` yaw,
+/// `&`/`^` pitch and `\`/`/` roll by `angle`, `|` turns around, and `[`/`]`
+/// save and restore the turtle. Other symbols only take part in rewriting.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LSystem {
+    pub axiom: String,
+    /// The replacement for each symbol; symbols without one are kept.
+    pub rules: BTreeMap<char, String>,
+    /// Turning angle, in degrees.
+    pub angle: f64,
+    /// Width of the tubes drawn along the path, in steps.
+    #[serde(default = "default_thickness")]
+    pub thickness: f64,
+}
+
+fn default_thickness() -> f64 {
+    0.25
+}
+
+/// Triangles in the box drawn for one step.
+const TUBE_TRIANGLES: u64 = 12;
+
+/// Symbols rewritten between cancellation checks.
+const CANCEL_INTERVAL: usize = 1 << 16;
+
+impl LSystem {
+    pub fn new(axiom: &str, rules: &[(char, &str)], angle: f64) -> Self {
+        LSystem {
+            axiom: axiom.to_string(),
+            rules: rules.iter().map(|&(c, s)| (c, s.to_string())).collect(),
+            angle,
+            thickness: default_thickness(),
+        }
+    }
+
+    /// The program after `iterations` rewrites of the axiom.
+    pub fn expand(&self, iterations: u32, cancel: &CancelToken) -> Result<String> {
+        let mut current = self.axiom.clone();
+        for _ in 0..iterations {
+            let mut next = String::with_capacity(current.len() * 2);
+            for (i, symbol) in current.chars().enumerate() {
+                if i % CANCEL_INTERVAL == 0 {
+                    cancel.check()?;
+                }
+                match self.rules.get(&symbol) {
+                    Some(replacement) => next.push_str(replacement),
+                    None => next.push(symbol),
+                }
+            }
+            current = next;
+        }
+        Ok(current)
+    }
+
+    /// How often each symbol occurs after `iterations` rewrites, worked out
+    /// from the rules without expanding.
+    pub fn symbol_counts(&self, iterations: u32) -> HashMap<char, u64> {
+        let mut counts: HashMap<char, u64> = HashMap::new();
+        for symbol in self.axiom.chars() {
+            *counts.entry(symbol).or_default() += 1;
+        }
+        for _ in 0..iterations {
+            let mut next: HashMap<char, u64> = HashMap::new();
+            for (&symbol, &count) in &counts {
+                match self.rules.get(&symbol) {
+                    Some(replacement) => {
+                        for produced in replacement.chars() {
+                            let entry = next.entry(produced).or_default();
+                            *entry = entry.saturating_add(count);
+                        }
+                    }
+                    None => {
+                        let entry = next.entry(symbol).or_default();
+                        *entry = entry.saturating_add(count);
+                    }
+                }
+            }
+            counts = next;
+        }
+        counts
+    }
+
+    /// Runs the turtle over `program` and returns the segments drawn, each
+    /// with the turtle's up vector at the time.
+    pub fn draw(&self, program: &str) -> Vec<Segment> {
+        let angle = self.angle.to_radians();
+        let mut turtle = Turtle {
+            position: [0.0; 3],
+            heading: [1.0, 0.0, 0.0],
+            left: [0.0, 1.0, 0.0],
+            up: [0.0, 0.0, 1.0],
+        };
+        let mut stack = Vec::new();
+        let mut segments = Vec::new();
+        for symbol in program.chars() {
+            match symbol {
+                'F' => {
+                    let start = turtle.position;
+                    turtle.advance();
+                    segments.push(Segment {
+                        start,
+                        end: turtle.position,
+                        up: turtle.up,
+                    });
+                }
+                'f' | 'G' => turtle.advance(),
+                '+' => turtle.yaw(angle),
+                '-' => turtle.yaw(-angle),
+                '&' => turtle.pitch(angle),
+                '^' => turtle.pitch(-angle),
+                '\\' => turtle.roll(angle),
+                '/' => turtle.roll(-angle),
+                '|' => turtle.yaw(std::f64::consts::PI),
+                '[' => stack.push(turtle),
+                ']' => turtle = stack.pop().unwrap_or(turtle),
+                _ => {}
+            }
+        }
+        segments
+    }
+}
+
+/// One step drawn by the turtle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Segment {
+    pub start: [f64; 3],
+    pub end: [f64; 3],
+    pub up: [f64; 3],
+}
+
+#[derive(Clone, Copy)]
+struct Turtle {
+    position: [f64; 3],
+    heading: [f64; 3],
+    left: [f64; 3],
+    up: [f64; 3],
+}
+
+impl Turtle {
+    fn advance(&mut self) {
+        self.position = std::array::from_fn(|i| self.position[i] + self.heading[i]);
+    }
+
+    fn yaw(&mut self, angle: f64) {
+        (self.heading, self.left) = rotate_pair(self.heading, self.left, angle);
+    }
+
+    fn pitch(&mut self, angle: f64) {
+        (self.heading, self.up) = rotate_pair(self.heading, self.up, -angle);
+    }
+
+    fn roll(&mut self, angle: f64) {
+        (self.left, self.up) = rotate_pair(self.left, self.up, angle);
+    }
+}
+
+/// Rotates the orthonormal pair `(a, b)` by `angle` in their plane, from
+/// `a` towards `b`.
+fn rotate_pair(a: [f64; 3], b: [f64; 3], angle: f64) -> ([f64; 3], [f64; 3]) {
+    let (sin, cos) = angle.sin_cos();
+    (
+        std::array::from_fn(|i| a[i] * cos + b[i] * sin),
+        std::array::from_fn(|i| b[i] * cos - a[i] * sin),
+    )
+}
+
+/// A closed box of square cross-section `thickness` around every segment,
+/// lengthened by half the thickness at each end so joints close. Boxes
+/// overlap where the path turns.
+pub fn tubes(segments: &[Segment], thickness: f64) -> Mesh {
+    let half = thickness / 2.0;
+    let mut vertices = Vec::with_capacity(segments.len() * 8);
+    let mut triangles = Vec::with_capacity(segments.len() * 12);
+    for segment in segments {
+        let axis = normalize(sub(segment.end, segment.start));
+        let up = normalize(segment.up);
+        let side = cross(axis, up);
+        let base = vertices.len() as u32;
+        for (end, along) in [(segment.start, -half), (segment.end, half)] {
+            for (s, u) in [(-half, -half), (half, -half), (half, half), (-half, half)] {
+                vertices.push(std::array::from_fn(|i| {
+                    end[i] + axis[i] * along + side[i] * s + up[i] * u
+                }));
+            }
+        }
+        // Corners 0-3 at the start and 4-7 at the end, clockwise about the
+        // axis; quads wound outwards, split in two.
+        let quads = [
+            [0, 1, 2, 3],
+            [4, 7, 6, 5],
+            [0, 4, 5, 1],
+            [1, 5, 6, 2],
+            [2, 6, 7, 3],
+            [3, 7, 4, 0],
+        ];
+        for q in quads {
+            let q = q.map(|v| base + v);
+            triangles.push([q[0], q[1], q[2]]);
+            triangles.push([q[0], q[2], q[3]]);
+        }
+    }
+    Mesh {
+        vertices,
+        faces: Polygons::Triangles(triangles),
+    }
+}
+
+/// The Koch surface on a unit-edge regular tetrahedron.
+fn koch_surface(iterations: u32, cancel: &CancelToken) -> Result<Mesh> {
+    let s = std::f64::consts::FRAC_1_SQRT_2 / 2.0;
+    let mut triangles: Vec<[[f64; 3]; 3]> = {
+        let a = [s, s, s];
+        let b = [s, -s, -s];
+        let c = [-s, s, -s];
+        let d = [-s, -s, s];
+        vec![[a, b, c], [a, c, d], [a, d, b], [b, d, c]]
+    };
+    let mid = |p: [f64; 3], q: [f64; 3]| std::array::from_fn(|i| (p[i] + q[i]) / 2.0);
+    for _ in 0..iterations {
+        cancel.check()?;
+        let mut next = Vec::with_capacity(triangles.len() * 6);
+        for [a, b, c] in triangles {
+            let (ab, bc, ca) = (mid(a, b), mid(b, c), mid(c, a));
+            let edge = dot(sub(bc, ab), sub(bc, ab)).sqrt();
+            let normal = normalize(cross(sub(b, a), sub(c, a)));
+            let height = edge * (2.0f64 / 3.0).sqrt();
+            let apex: [f64; 3] =
+                std::array::from_fn(|i| (ab[i] + bc[i] + ca[i]) / 3.0 + normal[i] * height);
+            next.extend([
+                [a, ab, ca],
+                [ab, b, bc],
+                [ca, bc, c],
+                [ab, bc, apex],
+                [bc, ca, apex],
+                [ca, ab, apex],
+            ]);
+        }
+        triangles = next;
+    }
+    // Shared corners are computed identically from both sides, so exact
+    // deduplication welds the surface.
+    let mut index = HashMap::new();
+    let mut vertices = Vec::new();
+    let faces = triangles
+        .iter()
+        .map(|triangle| {
+            triangle.map(|p| {
+                *index.entry(p.map(f64::to_bits)).or_insert_with(|| {
+                    vertices.push(p);
+                    vertices.len() as u32 - 1
+                })
+            })
+        })
+        .collect();
+    Ok(Mesh {
+        vertices,
+        faces: Polygons::Triangles(faces),
+    })
+}
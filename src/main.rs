@@ -1,3 +1,2140 @@
-fn main() {
-    println!("Hello, world!");
+use std::io::IsTerminal;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+use fractal_slicer_4_d::analysis::{analyze_volume, Analysis};
+use fractal_slicer_4_d::batch::{run_batch, Manifest};
+use fractal_slicer_4_d::bench::{Backend, Bench};
+use fractal_slicer_4_d::blender::addon;
+use fractal_slicer_4_d::cancel::CancelToken;
+#[cfg(feature = "rapier")]
+use fractal_slicer_4_d::collapse::Collapse;
+use fractal_slicer_4_d::color::{ColorMap, Palette};
+use fractal_slicer_4_d::dataset::Dataset;
+use fractal_slicer_4_d::deviation::{deviation, write_errors, Deviation};
+use fractal_slicer_4_d::diff::{compare, LatticeDiff, Source};
+use fractal_slicer_4_d::error::Result;
+use fractal_slicer_4_d::escape::Sampling;
+use fractal_slicer_4_d::explode::Explode;
+use fractal_slicer_4_d::export::{Coordinates, Domain, Gltf, Precision, Up};
+use fractal_slicer_4_d::growth::Grow;
+use fractal_slicer_4_d::image::{ImageStack, ImageValues};
+use fractal_slicer_4_d::import::{read_mesh, Import, Voxelizer};
+use fractal_slicer_4_d::infill::Infill;
+use fractal_slicer_4_d::job::{Job, JobReport};
+use fractal_slicer_4_d::lattice::Boundary;
+use fractal_slicer_4_d::materials::MaterialRule;
+use fractal_slicer_4_d::mesh::{Normals, Simplify};
+use fractal_slicer_4_d::monitor::Monitor;
+use fractal_slicer_4_d::morphology::{Element, Morphology, Operation};
+use fractal_slicer_4_d::orientation::Orient;
+#[cfg(feature = "plots")]
+use fractal_slicer_4_d::plot::Chart;
+use fractal_slicer_4_d::preset::Presets;
+use fractal_slicer_4_d::printability::Printability;
+use fractal_slicer_4_d::provenance::Provenance;
+use fractal_slicer_4_d::ranks::{finalize_mpi, gather, Ranks};
+use fractal_slicer_4_d::removal::RemovalReport;
+use fractal_slicer_4_d::render::{Pass, Scene, StereoMode};
+use fractal_slicer_4_d::report::Summary;
+use fractal_slicer_4_d::rule::{AxisRule, Combination, Rule, RuleCombinator};
+use fractal_slicer_4_d::rule_test::{RuleTest, RuleTestReport};
+use fractal_slicer_4_d::schematic::Schematic;
+use fractal_slicer_4_d::script::ScriptRule;
+use fractal_slicer_4_d::sdf::EstimatorParams;
+use fractal_slicer_4_d::server::{Access, Server, ServerConfig};
+use fractal_slicer_4_d::shader::{self, ShaderLanguage};
+use fractal_slicer_4_d::slice::{Bookmark, PlaneCut, Section};
+use fractal_slicer_4_d::sonify::Sonify;
+use fractal_slicer_4_d::symmetry::SymmetryReport;
+use fractal_slicer_4_d::texture::Texture;
+use fractal_slicer_4_d::tiling::Tiling;
+use fractal_slicer_4_d::transform::{Axis, Transform};
+use fractal_slicer_4_d::volume::MappedVolume;
+use fractal_slicer_4_d::watch::Watch;
+use fractal_slicer_4_d::web_demo;
+use fractal_slicer_4_d::zarr::Zarr;
+
+#[derive(Parser)]
+#[command(
+    name = "fractal-slicer",
+    version,
+    about = "Slices generated 4D fractals into a set of 3D objects"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Print the run summary as JSON instead of a table.
+    #[arg(long, global = true)]
+    json: bool,
+    /// Log line format; span timings are logged when each stage ends.
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Presets file to use instead of `fractal-slicer/presets.json` in
+    /// the user's configuration directory.
+    #[arg(long, global = true)]
+    presets: Option<PathBuf>,
+    /// Run the command again whenever a file it reads changes: a rule,
+    /// program, script, model, scene or manifest; the viewer reloads its
+    /// scene instead.
+    #[arg(long, global = true)]
+    watch: bool,
+}
+
+#[derive(Subcommand)]
+enum RuleCommand {
+    /// Check a custom rule file: survivors per level, closed-form against
+    /// generated counts, self-similarity and any expected counts; fails
+    /// with a diff of what differs.
+    Test {
+        file: PathBuf,
+        /// Check this many levels instead of the file's depth.
+        #[arg(long, short = 'n')]
+        depth: Option<u32>,
+    },
+}
+
+/// Planning a run instead of running it, shared by `generate` and `batch`.
+#[derive(Args)]
+struct PlanArgs {
+    /// Print the plan: cells per level, memory, outputs and their sizes,
+    /// and a time estimate scaled up from timing a small calibration run
+    /// of at most 2^18 cells or 2^16 triangles, which is discarded.
+    /// Nothing is written.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+/// Which fractal to generate, shared by the commands that generate one.
+#[derive(Args)]
+struct FractalArgs {
+    /// Built-in rule (menger, jerusalem, mosely, vicsek, octahedron;
+    /// hybrid with `--combine`; custom with `--bases`; script with
+    /// `--script`), escape-time set
+    /// (julia, mandelbrot), distance-estimated fractal (mandelbulb,
+    /// mandelbox; 3D), surface (koch-surface, carpet, koch-curve; 3D),
+    /// implicit `--program`, or import or infill of `--model`.
+    /// Sampled fractals take `--depth` as iterations.
+    #[arg(long, default_value = "menger")]
+    fractal: String,
+    #[arg(long, default_value_t = 4)]
+    dims: usize,
+    #[arg(long, short = 'n')]
+    depth: u32,
+    /// Rules combined by `--fractal hybrid`, as `op:rule,rule,…` with op
+    /// alternate, intersection or union.
+    #[arg(long, value_parser = parse_combine)]
+    combine: Option<RuleCombinator>,
+    /// Parts per axis for `--fractal custom`, e.g. `3,3,5`; digits other
+    /// than the first and last are removed Menger-style. Also the parts
+    /// per axis of `--fractal script`.
+    #[arg(long, value_delimiter = ',')]
+    bases: Vec<u32>,
+    /// Program of `--fractal implicit`: statements over the cell centre
+    /// `x, y, z, w` ending in an expression at most 0 inside, or `@path`
+    /// of a file holding one.
+    #[arg(long, value_parser = parse_program)]
+    program: Option<Program>,
+    /// Cells along each axis for escape-time, distance-estimated and
+    /// implicit fractals.
+    #[arg(long)]
+    resolution: Option<usize>,
+    /// Mandelbulb exponent.
+    #[arg(long)]
+    power: Option<f64>,
+    /// Mandelbox scale.
+    #[arg(long)]
+    box_scale: Option<f64>,
+    /// STL or OBJ model voxelized by `--fractal import` or filled by
+    /// `--fractal infill`.
+    #[arg(long)]
+    model: Option<PathBuf>,
+    /// How the model's inside is found: `parity` for watertight models,
+    /// `fill` for ones with small holes.
+    #[arg(long, value_enum, default_value_t)]
+    voxelizer: Voxelizer,
+    /// Rule filling the model.
+    #[arg(long, default_value = "menger")]
+    infill_rule: String,
+    /// Model units per cell of an imported or infilled model.
+    #[arg(long, default_value_t = 1.0)]
+    cell_size: f64,
+    /// Cells of solid skin kept under the model's surface.
+    #[arg(long, default_value_t = 1)]
+    shell: u32,
+    /// Rhai script defining `keep(cell, level, digits)` for `--fractal
+    /// script`; needs the `scripting` feature.
+    #[arg(long)]
+    script: Option<PathBuf>,
+    /// Operations one call of the script's `keep` may take.
+    #[arg(long, default_value_t = 100_000)]
+    max_operations: u64,
+}
+
+/// An implicit program, and the file it was read from.
+#[derive(Clone)]
+struct Program {
+    source: String,
+    file: Option<PathBuf>,
+}
+
+impl FractalArgs {
+    /// The files the fractal is read from.
+    fn inputs(&self) -> Vec<PathBuf> {
+        let program = self.program.as_ref().and_then(|p| p.file.clone());
+        [program, self.model.clone(), self.script.clone()]
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// A job generating the fractal, with no outputs.
+    fn into_job(self) -> Job {
+        let FractalArgs {
+            fractal,
+            dims,
+            depth,
+            combine,
+            bases,
+            resolution,
+            power,
+            box_scale,
+            program,
+            model,
+            voxelizer,
+            infill_rule,
+            cell_size,
+            shell,
+            script,
+            max_operations,
+        } = self;
+        let (import, model) = match fractal.as_str() {
+            "import" => (model, None),
+            _ => (None, model),
+        };
+        let (bases, script) = match script {
+            Some(file) => (
+                Vec::new(),
+                Some(ScriptRule {
+                    file,
+                    bases,
+                    max_operations,
+                }),
+            ),
+            None => (bases, None),
+        };
+        Job {
+            name: None,
+            fractal,
+            lsystem: None,
+            combine,
+            custom: (!bases.is_empty()).then(|| AxisRule {
+                bases,
+                remove: Vec::new(),
+                min_removed: 2,
+            }),
+            script,
+            infill: model.map(|model| Infill {
+                model,
+                rule: infill_rule,
+                cell_size,
+                shell,
+                voxelizer,
+            }),
+            import: import.map(|model| Import {
+                model,
+                cell_size,
+                voxelizer,
+            }),
+            implicit: program.map(|program| program.source),
+            sampling: resolution.map(|resolution| Sampling {
+                resolution,
+                ..Sampling::default()
+            }),
+            estimator: (power.is_some() || box_scale.is_some()).then(|| {
+                let defaults = EstimatorParams::default();
+                EstimatorParams {
+                    power: power.unwrap_or(defaults.power),
+                    scale: box_scale.unwrap_or(defaults.scale),
+                }
+            }),
+            dims,
+            depth,
+            slices: Vec::new(),
+            slab: None,
+            transforms: Vec::new(),
+            tiling: Tiling::default(),
+            normals: Normals::None,
+            precision: Precision::F32,
+            coordinates: Coordinates::F64,
+            normalize: false,
+            domain: Domain::Positive,
+            simplify: Simplify::default(),
+            repair: false,
+            boundary: Boundary::Open,
+            morphology: Vec::new(),
+            printability: None,
+            orient: None,
+            offset: None,
+            images: ImageStack::default(),
+            schematic: Schematic::default(),
+            gltf: Gltf::default(),
+            color: None,
+            materials: Vec::new(),
+            texture: Texture::default(),
+            zarr: Zarr::default(),
+            monitor: None,
+            out_of_core: false,
+            symmetric: false,
+            numa: false,
+            ranks: None,
+            section: None,
+            cut: None,
+            explode: None,
+            grow: None,
+            sonify: None,
+            complex: None,
+            plugins: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+}
+
+// Parsed once at startup, so the size of `Generate` does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Command {
+    /// Generate a fractal and write its slices.
+    Generate {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// w index to slice at; repeat for several, omit for all.
+        #[arg(long = "slice")]
+        slices: Vec<usize>,
+        /// Project the slab of w indices `w0:w1`, both included, into one
+        /// 3D lattice instead of slicing at single w.
+        #[arg(long, value_parser = parse_slab, conflicts_with = "slices")]
+        slab: Option<[usize; 2]>,
+        /// Output path; `{w}` is replaced by the slice index and `{i}` by
+        /// the layer of a `.png` or `.tiff` image stack.
+        #[arg(long, short, required = true)]
+        output: Vec<PathBuf>,
+        /// Export settings for a common workflow, such as `print-mm`,
+        /// `web-glb` or `paraview`, filling in those not given and the
+        /// format of outputs without an extension; see `presets`.
+        #[arg(long)]
+        preset: Option<String>,
+        /// Normals to write to formats that support them.
+        #[arg(long, value_enum, default_value_t = Normals::None)]
+        normals: Normals,
+        /// Coordinates of `.glb` and `.inst` outputs: `f16` halves and
+        /// `q16` quantizes them to the bounding box.
+        #[arg(long, value_enum, default_value_t = Precision::F32)]
+        precision: Precision,
+        /// Build and transform meshes in `f32`, halving their memory, for
+        /// outputs that are only viewed.
+        #[arg(long, value_enum, default_value_t = Coordinates::F64)]
+        coordinates: Coordinates,
+        /// Scale outputs into the unit cube whatever the depth, so they
+        /// overlay without per-depth scaling.
+        #[arg(long)]
+        normalize: bool,
+        /// Where outputs sit: from the origin, or `centered` on it from
+        /// minus to plus half the lattice's side.
+        #[arg(long, value_enum, default_value_t = Domain::Positive)]
+        domain: Domain,
+        /// Copies along x,y,z and optionally w, e.g. `3,3,1`.
+        #[arg(long, value_parser = parse_tile, default_value = "1,1,1")]
+        tile: [usize; 4],
+        /// Gap between tiled copies, in cells.
+        #[arg(long, default_value_t = 0.0)]
+        spacing: f64,
+        /// Simplify each mesh to at most this many triangles.
+        #[arg(long)]
+        max_triangles: Option<usize>,
+        /// Largest distance simplification may move the surface.
+        #[arg(long)]
+        max_error: Option<f64>,
+        /// Fix winding and remove degenerate faces before writing.
+        #[arg(long)]
+        repair: bool,
+        /// How the outer walls are meshed: open closes them, periodic and
+        /// mirrored leave them open where the domain continues.
+        #[arg(long, value_enum, default_value_t = Boundary::Open)]
+        boundary: Boundary,
+        /// Reshape each lattice before meshing, as `op[:element][:radius]`
+        /// with op dilate, erode, open or close and element ball, cube or
+        /// cross; repeat to apply several in order.
+        #[arg(long = "morph", value_parser = parse_morphology)]
+        morphology: Vec<Morphology>,
+        /// Size of one cell in the output, e.g. in millimetres.
+        #[arg(long)]
+        scale: Option<f64>,
+        /// Report struts and walls thinner than this, in output units.
+        #[arg(long)]
+        min_thickness: Option<f64>,
+        /// Thicken the struts and walls `--min-thickness` finds.
+        #[arg(long, requires = "min_thickness")]
+        fix_thin: bool,
+        /// Rotate each output to the orientation needing the least support.
+        #[arg(long)]
+        orient: bool,
+        /// Steepest overhang printable without support, in degrees from
+        /// vertical, for `--orient`.
+        #[arg(long, default_value_t = 45.0)]
+        max_overhang: f64,
+        /// Write a smooth surface this many cells outside the cubes:
+        /// positive dilates the solid, negative erodes it.
+        #[arg(long, allow_hyphen_values = true)]
+        offset: Option<f64>,
+        /// Axis `.png` and `.tiff` image stacks are layered along.
+        #[arg(long, value_enum, default_value = "z")]
+        image_axis: Axis,
+        /// Pixel values of image stacks.
+        #[arg(long, value_enum, default_value_t)]
+        image_values: ImageValues,
+        /// Minecraft block filled cells become in `.schem` outputs.
+        #[arg(long, default_value = "minecraft:stone")]
+        block: String,
+        /// Longest side of one `.schem` file; larger lattices are split.
+        #[arg(long, default_value_t = 256)]
+        schematic_size: usize,
+        /// Up axis of `.glb` outputs; game engines expect `y`.
+        #[arg(long, value_enum, default_value_t)]
+        gltf_up: Up,
+        /// Metres per output unit in `.glb` outputs, e.g. 0.001 for
+        /// millimetres.
+        #[arg(long, default_value_t = 1.0)]
+        gltf_unit_scale: f64,
+        /// Write vertex tangents to `.glb` outputs; needs `--normals smooth`.
+        #[arg(long)]
+        tangents: bool,
+        /// Compress `.glb` outputs with `EXT_meshopt_compression`.
+        #[arg(long)]
+        meshopt: bool,
+        /// Bits, 1 to 16, of the grid `--precision q16` snaps `.glb`
+        /// positions to.
+        #[arg(long, default_value_t = 16)]
+        position_bits: u32,
+        /// Bits, 8 or 16, of `--precision q16` normals and tangents in
+        /// `.glb` outputs.
+        #[arg(long, default_value_t = 16)]
+        normal_bits: u32,
+        /// Colour `.glb` vertices as `attribute[:palette]`: attribute
+        /// depth, w, occlusion or component, palette viridis, magma or
+        /// comma-separated `#rrggbb` stops.
+        #[arg(long)]
+        color: Option<ColorMap>,
+        /// JSON file of material rules to colour `.glb` vertices by, and
+        /// to split outputs with `{material}` in their path by.
+        #[arg(long)]
+        materials: Option<PathBuf>,
+        /// BC4-compress `.dds` occupancy textures.
+        #[arg(long)]
+        bc4: bool,
+        /// Side of the cubic chunks of `.zarr` outputs, in cells.
+        #[arg(long, default_value_t = 64)]
+        zarr_chunk: usize,
+        /// Write `.vti` snapshots of the generation for ParaView; `{i}` is
+        /// replaced by the snapshot number.
+        #[arg(long)]
+        monitor: Option<PathBuf>,
+        /// Seconds between `--monitor` snapshots.
+        #[arg(long, default_value_t = 10.0)]
+        monitor_interval: f64,
+        /// Mesh `.obj` and `.stl` outputs a slab of layers at a time
+        /// without holding the lattice, for rule fractals too deep for
+        /// memory.
+        #[arg(long)]
+        out_of_core: bool,
+        /// Test only the cells of one fundamental domain of the rule's
+        /// reflections and axis swaps, a 48th of the Menger sponge, and
+        /// mirror them into the rest.
+        #[arg(long)]
+        symmetric: bool,
+        /// Generate on every core, splitting the rule's top-level
+        /// branches between NUMA nodes and pinning each node's threads to
+        /// it; needs the `numa` feature.
+        #[arg(long, conflicts_with = "symmetric")]
+        numa: bool,
+        /// Generate and write only one rank's rows of chunks of the `.zarr`
+        /// outputs, `rank/size` or `env` for the rank `mpirun` or `srun`
+        /// set; `gather` totals the ranks' counts once all have finished.
+        /// With the `mpi` feature, `mpi` joins the MPI world instead, and
+        /// the ranks sum their counts over it, rank 0 logging the totals.
+        #[arg(long, value_parser = parse_rank)]
+        rank: Option<Ranks>,
+        /// Cut a 4D rule fractal exactly along the hyperplane
+        /// `nx,ny,nz,nw=p/q`, the points whose coordinates, 0 to 1 across
+        /// the hypercube, have that dot product with the normal; writes
+        /// the polytopes left of its cells as one mesh.
+        #[arg(long, value_parser = parse_section)]
+        section: Option<Section>,
+        /// Cut a 3D rule fractal exactly along the plane `nx,ny,nz=p/q`,
+        /// keeping what lies behind it and capping the cells it crosses.
+        #[arg(long, value_parser = parse_cut)]
+        cut: Option<PlaneCut>,
+        /// Push the level blocks of a 3D rule fractal apart as
+        /// `level:factor[:frames]`, e.g. `1:0.5`; with frames, outputs are
+        /// written per frame, at `{frame}` in their paths.
+        #[arg(long)]
+        explode: Option<Explode>,
+        /// Animate a 3D rule fractal growing from one level short of
+        /// `--depth` into it over this many frames, the cells the last
+        /// level removes shrinking away; outputs are written per frame, at
+        /// `{frame}` in their paths.
+        #[arg(long)]
+        grow: Option<u32>,
+        /// Play the w sweep's slices as sound into this `.wav` file, their
+        /// cells setting the pitch, their surface area the loudness and
+        /// their pieces the brightness.
+        #[arg(long)]
+        sonify: Option<PathBuf>,
+        /// Slices played a second by `--sonify`, the sweep animation's
+        /// frame rate.
+        #[arg(long, default_value_t = 24.0)]
+        sonify_fps: f64,
+        /// Also write the kept 4D cells with their 16 vertices and 8 cube
+        /// facets, as JSON for a `.json` path and binary otherwise.
+        #[arg(long)]
+        complex: Option<PathBuf>,
+        /// Load exporters for further formats from this shared library;
+        /// repeat for several. Needs the `plugins` feature.
+        #[arg(long = "plugin")]
+        plugins: Vec<PathBuf>,
+        /// Scale `.glb` outputs so their longest side is this many metres,
+        /// centred and standing on the floor.
+        #[arg(long)]
+        gltf_fit: Option<f64>,
+        /// Preset for Blender's glTF importer: y up and face normals.
+        #[arg(long, conflicts_with_all = ["gltf_up", "normals"])]
+        blender: bool,
+        /// Preset for OpenXR viewers: y up, face normals, and a metre
+        /// across standing on the floor unless `--gltf-fit` says otherwise.
+        #[arg(long, conflicts_with_all = ["gltf_up", "normals", "blender", "gltf_unit_scale"])]
+        xr: bool,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+    /// Measure porosity, pore sizes and percolation of a fractal.
+    Analyze {
+        #[command(flatten)]
+        fractal: FractalArgs,
+    },
+    /// List the reflections and axis permutations a rule fractal is
+    /// symmetric under.
+    Symmetry {
+        #[command(flatten)]
+        fractal: FractalArgs,
+    },
+    /// Tools for rule authors.
+    Rule {
+        #[command(subcommand)]
+        command: RuleCommand,
+    },
+    /// Count the cells each clause of a rule removes per level, and
+    /// optionally dump which subcells die where.
+    Removal {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// Write the removal tree as JSON lines: each kept cell with the
+        /// subcells its split removes.
+        #[arg(long)]
+        tree: Option<PathBuf>,
+    },
+    /// Measure the pore space of a `.nii` volume written by `generate`,
+    /// memory-mapped so that only the cells analyzed are read.
+    AnalyzeVolume {
+        volume: PathBuf,
+        /// Analyze only the box `x0,y0,z0:x1,y1,z1`, upper bounds
+        /// exclusive, for volumes too large to analyze whole.
+        #[arg(long, value_parser = parse_region)]
+        region: Option<([usize; 3], [usize; 3])>,
+    },
+    /// Measure how far a mesh strays from a reference, such as a
+    /// decimated or marching-cubes mesh from the exact cube mesh: the
+    /// Hausdorff and Chamfer distances between their surfaces.
+    Deviation {
+        /// The `.stl` or `.obj` mesh measured against.
+        reference: PathBuf,
+        /// The `.stl` or `.obj` mesh measured.
+        measured: PathBuf,
+        /// Points drawn over each mesh's area, besides its vertices.
+        #[arg(long, default_value_t = 10_000)]
+        samples: usize,
+        /// Write the measured mesh with each vertex's distance to the
+        /// reference, as colours of a `.glb` or scalars of a `.vtk`.
+        #[arg(long)]
+        errors: Option<PathBuf>,
+        /// Palette of `--errors` colours: viridis, magma or `#rrggbb`
+        /// stops.
+        #[arg(long, default_value = "viridis")]
+        palette: Palette,
+    },
+    /// Compare two lattices, each a `.json` job or a `.nii` volume: their
+    /// overlap, the distance between their surfaces and the blocks of each
+    /// level they disagree on.
+    Diff {
+        first: PathBuf,
+        second: PathBuf,
+        /// w slice of 4D jobs to compare; two 4D jobs are compared whole
+        /// when omitted, others at the middle slice.
+        #[arg(long)]
+        slice: Option<usize>,
+        /// Write a VTK image (`.vti`) labelling each cell 1 where both are
+        /// filled, 2 where only the first is and 3 where only the second is.
+        #[arg(long)]
+        voxels: Option<PathBuf>,
+    },
+    /// Render a fractal to a PNG image by ray casting its cells.
+    Render {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// JSON scene file: camera, lights, background and a material per
+        /// depth level; defaults for anything left out.
+        #[arg(long)]
+        scene: Option<PathBuf>,
+        /// w slice of a 4D fractal to render; the middle one when omitted.
+        #[arg(long)]
+        slice: Option<usize>,
+        /// Path trace with this many samples per pixel instead of ray
+        /// casting, keeping the scene's other path tracing settings.
+        #[arg(long)]
+        samples: Option<usize>,
+        /// Render a view for each eye, keeping the scene's eye separation.
+        #[arg(long, value_enum)]
+        stereo: Option<StereoMode>,
+        /// Auxiliary passes to write, comma separated, replacing the
+        /// scene's: channels of an `.exr` output, or PNGs beside a `.png`
+        /// one.
+        #[arg(long, value_enum, value_delimiter = ',')]
+        passes: Vec<Pass>,
+        /// Colour faces as `attribute[:palette]` instead of by material,
+        /// replacing the scene's colour map; see `generate --color`.
+        #[arg(long)]
+        color: Option<ColorMap>,
+        /// JSON file of material rules to colour faces by when no colour
+        /// map is given; see `generate --materials`.
+        #[arg(long)]
+        materials: Option<PathBuf>,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Render the six axis views and an isometric one, orthographically,
+    /// into one labelled PNG for a quick look at a rule or slice.
+    ContactSheet {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// JSON scene file whose lights, background and materials are
+        /// used; its camera is replaced by the sheet's views.
+        #[arg(long)]
+        scene: Option<PathBuf>,
+        /// w slice of a 4D fractal; the middle one when omitted.
+        #[arg(long)]
+        slice: Option<usize>,
+        /// Side of each view's square tile, in pixels.
+        #[arg(long, default_value_t = 256)]
+        size: usize,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Drop the cells of a lattice onto the ground as rigid cubes and
+    /// write their motion as a USD point cache (`.usda`), for collapse
+    /// renders in Blender.
+    #[cfg(feature = "rapier")]
+    Collapse {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// w slice of a 4D fractal; the middle one when omitted.
+        #[arg(long)]
+        slice: Option<usize>,
+        #[arg(long, default_value_t = 120)]
+        frames: u32,
+        #[arg(long, default_value_t = 24.0)]
+        fps: f64,
+        /// Side of each cube, in metres.
+        #[arg(long, default_value_t = 0.1)]
+        cube_size: f64,
+        /// Drop height of the lattice's underside, in metres.
+        #[arg(long, default_value_t = 0.0)]
+        height: f64,
+        /// Random starting speed of each cube, up to this many metres a
+        /// second.
+        #[arg(long, default_value_t = 0.0)]
+        scatter: f64,
+        /// Seed of the starting velocities.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Chart a fractal's box-counting dimension, the cells of its w
+    /// slices or its pore sizes as an `.svg` or `.png` figure.
+    #[cfg(feature = "plots")]
+    Plot {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        #[arg(long, value_enum)]
+        chart: Chart,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Render a slice bookmarked in the viewer, through its camera.
+    RenderBookmark {
+        bookmark: PathBuf,
+        /// JSON scene file whose lights, background, materials and passes
+        /// are used; the bookmark's camera replaces its own.
+        #[arg(long)]
+        scene: Option<PathBuf>,
+        /// Image size as `WIDTHxHEIGHT`, in place of the bookmark's.
+        #[arg(long, value_parser = parse_size)]
+        size: Option<[usize; 2]>,
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Run every job in a JSON manifest.
+    Batch {
+        manifest: PathBuf,
+        /// Number of jobs to run at once.
+        #[arg(long, short, default_value_t = 1)]
+        jobs: usize,
+        /// Write a manifest of every artifact with its size, SHA-256 and
+        /// job parameters, for `verify` and dataset publication.
+        #[arg(long)]
+        artifacts: Option<PathBuf>,
+        #[command(flatten)]
+        plan: PlanArgs,
+    },
+    /// Explore slices of a 4D rule in a window, with live controls for the
+    /// rule, depth and the slicing hyperplane's offset and rotation.
+    #[cfg(feature = "viewer")]
+    View {
+        /// A built-in rule, sliced in 4D.
+        #[arg(long, default_value = "menger")]
+        fractal: String,
+        #[arg(long, default_value_t = 2)]
+        depth: u32,
+        /// Cells along each side of the sampled slice.
+        #[arg(long, default_value_t = 81)]
+        resolution: usize,
+        /// JSON scene file whose camera starts the view and whose lights,
+        /// background and materials are used.
+        #[arg(long)]
+        scene: Option<PathBuf>,
+        /// Start from a bookmark saved in the viewer, in place of the
+        /// slice options and the scene's camera.
+        #[arg(long)]
+        bookmark: Option<PathBuf>,
+        /// Size of screenshots taken with F12, as `WIDTHxHEIGHT`.
+        #[arg(long, value_parser = parse_size, default_value = "1920x1080")]
+        screenshot_size: [usize; 2],
+    },
+    /// Edit and run a job in the terminal, watching its stages, progress,
+    /// memory use and log; for remote machines without a window.
+    #[cfg(feature = "tui")]
+    Tui {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// Output paths, editable before the job starts.
+        #[arg(long, short)]
+        output: Vec<PathBuf>,
+    },
+    /// Render randomized samples for 3D machine learning: a depth image, a
+    /// signed distance volume and labels each, split into train and val.
+    Dataset {
+        /// Directory the samples and their labels are written to.
+        #[arg(long, short)]
+        output: PathBuf,
+        #[arg(long, default_value_t = 100)]
+        samples: usize,
+        /// Rules to draw from, comma separated; all built-in rules when
+        /// omitted.
+        #[arg(long, value_delimiter = ',')]
+        fractals: Vec<String>,
+        #[arg(long, default_value_t = 1)]
+        min_depth: u32,
+        #[arg(long, default_value_t = 3)]
+        max_depth: u32,
+        /// Side of every sample's grid, in cells.
+        #[arg(long, default_value_t = 64)]
+        resolution: usize,
+        /// Fraction of the samples held out for validation.
+        #[arg(long, default_value_t = 0.1)]
+        val_fraction: f64,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Number of samples rendered at once.
+        #[arg(long, short, default_value_t = 1)]
+        jobs: usize,
+    },
+    /// Time the generation backends on a standard matrix of depths, to
+    /// pick the fastest on this machine or to attach to a performance
+    /// report with `--json`.
+    Bench {
+        /// A built-in rule.
+        #[arg(long, default_value = "menger")]
+        fractal: String,
+        /// Dimensions to run the rule in, comma separated.
+        #[arg(long, value_delimiter = ',', default_value = "3,4")]
+        dims: Vec<usize>,
+        /// Run every depth whose lattice has at most this many cells.
+        #[arg(long, default_value_t = 50_000_000)]
+        max_cells: u64,
+        /// Backends to time, comma separated; all this build has when
+        /// omitted.
+        #[arg(long, value_enum, value_delimiter = ',')]
+        backends: Vec<Backend>,
+        /// Runs of each, of which the fastest counts.
+        #[arg(long, default_value_t = 3)]
+        repeat: usize,
+    },
+    /// Total the counts the ranks of a `generate --rank` run left in a
+    /// Zarr array, failing while some have not finished.
+    Gather { array: PathBuf },
+    /// List the export presets, built-in and from the presets file.
+    Presets,
+    /// Re-check the artifacts listed in a `batch --artifacts` manifest.
+    Verify { artifacts: PathBuf },
+    /// Write a WGSL or GLSL `fs_is_solid` function testing whether a cell
+    /// is filled, for use in your own shaders.
+    Shader {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// Shading language of the source.
+        #[arg(long, value_enum, default_value_t = ShaderLanguage::Wgsl)]
+        language: ShaderLanguage,
+        /// Path of the shader source; printed when omitted.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Write a folder with a WebAssembly build, JS glue and a WebGL page
+    /// exploring a 4D rule's slices with a w-slider, for GitHub Pages or
+    /// any static host.
+    WebDemo {
+        #[command(flatten)]
+        fractal: FractalArgs,
+        /// Folder to write the demo into.
+        #[arg(long, short)]
+        output: PathBuf,
+        /// A `fractal_slicer_web.wasm` built before; by default the module
+        /// is built from the slicer's sources, which needs the
+        /// `wasm32-unknown-unknown` target. Required when the slicer was
+        /// installed, e.g. with `cargo install`, since its sources are gone.
+        #[arg(long)]
+        wasm: Option<PathBuf>,
+    },
+    /// Write a Blender add-on that generates fractals from a panel.
+    BlenderAddon {
+        /// Path of the add-on's `.py` file; printed when omitted.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print a completion script for `shell`, e.g. to save as
+    /// `~/.local/share/bash-completion/completions/fractal-slicer`.
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Write the man pages, from the same definitions as `--help`.
+    Manpage {
+        /// Directory to write `fractal-slicer.1` and a page per subcommand
+        /// to; the main page is printed when omitted.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Serve slices over HTTP, with Prometheus metrics at /metrics.
+    Serve {
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// Deepest fractal a request may ask for.
+        #[arg(long, default_value_t = 4)]
+        max_depth: u32,
+        /// Number of generated hypersponges kept between requests.
+        #[arg(long, default_value_t = 4)]
+        cache: usize,
+        /// Seconds a `/slice` request may run before it is abandoned; 0
+        /// disables.
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+        /// Queued jobs run at once.
+        #[arg(long, default_value_t = 2)]
+        workers: usize,
+        /// Jobs that may wait for a worker before submissions are refused.
+        #[arg(long, default_value_t = 64)]
+        max_queued: usize,
+        /// Connections handled at once; more wait to be accepted.
+        #[arg(long, default_value_t = 64)]
+        connections: usize,
+        /// Seconds a connection may take to send its request, or to take
+        /// each part of the response; 0 disables.
+        #[arg(long, default_value_t = 10)]
+        io_timeout: u64,
+        /// JSON file of the clients' bearer tokens and quotas, and the
+        /// quota of requests without a token; without it anyone may use
+        /// the server.
+        #[arg(long)]
+        access: Option<PathBuf>,
+        /// Also write each queued job's output to this directory, or to an
+        /// `s3://bucket/prefix` or `gs://bucket/prefix` with the
+        /// `object-store` feature, as `job-<id>.<format>`.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+impl Command {
+    /// The files the command reads, which `--watch` reruns it on.
+    fn inputs(&self) -> Vec<PathBuf> {
+        match self {
+            Command::Generate {
+                fractal, plugins, ..
+            } => fractal
+                .inputs()
+                .into_iter()
+                .chain(plugins.clone())
+                .collect(),
+            Command::Analyze { fractal }
+            | Command::Symmetry { fractal }
+            | Command::Removal { fractal, .. }
+            | Command::Shader { fractal, .. }
+            | Command::WebDemo { fractal, .. } => fractal.inputs(),
+            Command::Render { fractal, scene, .. }
+            | Command::ContactSheet { fractal, scene, .. } => {
+                fractal.inputs().into_iter().chain(scene.clone()).collect()
+            }
+            #[cfg(feature = "rapier")]
+            Command::Collapse { fractal, .. } => fractal.inputs(),
+            #[cfg(feature = "plots")]
+            Command::Plot { fractal, .. } => fractal.inputs(),
+            Command::RenderBookmark {
+                bookmark, scene, ..
+            } => std::iter::once(bookmark.clone())
+                .chain(scene.clone())
+                .collect(),
+            Command::Rule {
+                command: RuleCommand::Test { file, .. },
+            } => vec![file.clone()],
+            Command::AnalyzeVolume { volume, .. } => vec![volume.clone()],
+            Command::Diff { first, second, .. } => vec![first.clone(), second.clone()],
+            Command::Deviation {
+                reference,
+                measured,
+                ..
+            } => vec![reference.clone(), measured.clone()],
+            Command::Batch { manifest, .. } => {
+                // A manifest that does not load is still watched, to run
+                // once it is fixed.
+                let jobs = Manifest::load(manifest).map_or(Vec::new(), |m| m.jobs);
+                std::iter::once(manifest.clone())
+                    .chain(jobs.iter().flat_map(Job::inputs))
+                    .collect()
+            }
+            #[cfg(feature = "viewer")]
+            Command::View { scene, .. } => scene.iter().cloned().collect(),
+            Command::Verify { artifacts } => vec![artifacts.clone()],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether the command watches its inputs itself under `--watch`
+    /// rather than being run again.
+    fn watches_itself(&self) -> bool {
+        match self {
+            #[cfg(feature = "viewer")]
+            Command::View { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // The terminal interface shows the logs itself.
+    #[cfg(feature = "tui")]
+    if let Command::Tui { fractal, output } = cli.command {
+        let job = Job {
+            outputs: output,
+            ..fractal.into_job()
+        };
+        return match fractal_slicer_4_d::tui::run(job) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+    init_logging(cli.log_format);
+    let cancel = install_interrupt_handler();
+    if cli.watch && cli.command.inputs().is_empty() {
+        eprintln!("error: --watch: the command reads no files to watch");
+        return ExitCode::FAILURE;
+    }
+    let code = if cli.watch && !cli.command.watches_itself() {
+        watch(cli, &cancel)
+    } else {
+        run(cli, &cancel)
+    };
+    finalize_mpi(code != ExitCode::SUCCESS);
+    code
+}
+
+/// Runs the command, then again each time a file it reads changes, until
+/// interrupted.
+fn watch(mut cli: Cli, cancel: &CancelToken) -> ExitCode {
+    loop {
+        let mut watch = Watch::new(cli.command.inputs());
+        run(cli, cancel);
+        if cancel.is_cancelled() {
+            return ExitCode::from(130);
+        }
+        let files: Vec<_> = watch
+            .files()
+            .map(|path| path.display().to_string())
+            .collect();
+        tracing::info!("watching {} for changes", files.join(", "));
+        cli = loop {
+            let Ok(changed) = watch.wait(cancel) else {
+                return ExitCode::from(130);
+            };
+            let changed: Vec<_> = changed
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            tracing::info!("{} changed, running again", changed.join(", "));
+            // Parsed again, so that `@path` programs are read again.
+            match Cli::try_parse() {
+                Ok(cli) => break cli,
+                Err(e) => {
+                    let _ = e.print();
+                }
+            }
+        };
+    }
+}
+
+fn run(cli: Cli, cancel: &CancelToken) -> ExitCode {
+    let results = match cli.command {
+        Command::Generate {
+            fractal,
+            slices,
+            slab,
+            output,
+            preset,
+            normals,
+            precision,
+            coordinates,
+            normalize,
+            domain,
+            tile,
+            spacing,
+            max_triangles,
+            max_error,
+            repair,
+            boundary,
+            morphology,
+            scale,
+            min_thickness,
+            fix_thin,
+            orient,
+            max_overhang,
+            offset,
+            image_axis,
+            image_values,
+            block,
+            schematic_size,
+            gltf_up,
+            gltf_unit_scale,
+            tangents,
+            meshopt,
+            position_bits,
+            normal_bits,
+            color,
+            materials,
+            bc4,
+            zarr_chunk,
+            monitor,
+            monitor_interval,
+            out_of_core,
+            symmetric,
+            numa,
+            rank,
+            section,
+            cut,
+            explode,
+            grow,
+            sonify,
+            sonify_fps,
+            complex,
+            plugins,
+            gltf_fit,
+            blender,
+            xr,
+            plan: PlanArgs { dry_run },
+        } => {
+            let (normals, gltf_up) = if blender || xr {
+                (Normals::Face, Up::Y)
+            } else {
+                (normals, gltf_up)
+            };
+            let gltf_fit = if xr { gltf_fit.or(Some(1.0)) } else { gltf_fit };
+            let Some(materials) = load_materials(materials) else {
+                return ExitCode::FAILURE;
+            };
+            let job = Job {
+                slices,
+                slab,
+                tiling: Tiling {
+                    count: tile,
+                    spacing,
+                    ..Tiling::default()
+                },
+                normals,
+                precision,
+                coordinates,
+                normalize,
+                domain,
+                simplify: Simplify {
+                    max_triangles,
+                    max_error,
+                },
+                repair,
+                boundary,
+                morphology,
+                printability: min_thickness.map(|min_thickness| Printability {
+                    min_thickness,
+                    fix: fix_thin,
+                }),
+                orient: orient.then(|| Orient {
+                    max_overhang,
+                    ..Orient::default()
+                }),
+                transforms: scale.map(Transform::Scale).into_iter().collect(),
+                offset,
+                images: ImageStack {
+                    axis: image_axis,
+                    values: image_values,
+                },
+                schematic: Schematic {
+                    block,
+                    max_size: schematic_size,
+                },
+                gltf: Gltf {
+                    up: gltf_up,
+                    unit_scale: gltf_unit_scale,
+                    tangents,
+                    fit: gltf_fit,
+                    meshopt,
+                    position_bits,
+                    normal_bits,
+                },
+                color,
+                materials,
+                texture: Texture { bc4 },
+                zarr: Zarr { chunk: zarr_chunk },
+                monitor: monitor.map(|path| Monitor {
+                    path,
+                    interval: monitor_interval,
+                }),
+                out_of_core,
+                symmetric,
+                numa,
+                ranks: rank,
+                section,
+                cut,
+                explode,
+                grow: grow.map(|frames| Grow { frames }),
+                sonify: sonify.map(|path| Sonify {
+                    path,
+                    fps: sonify_fps,
+                    sample_rate: 44_100,
+                }),
+                complex,
+                plugins,
+                outputs: output,
+                ..fractal.into_job()
+            };
+            let job = match preset {
+                Some(name) => match Presets::load(cli.presets.as_deref())
+                    .and_then(|presets| presets.get(&name)?.apply(&job))
+                {
+                    Ok(job) => job,
+                    Err(e) => {
+                        eprintln!("error: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => job,
+            };
+            if dry_run {
+                return print_plans(&[job]);
+            }
+            vec![(job.display_name(), job.run_cancellable(cancel))]
+        }
+        Command::Analyze { fractal } => {
+            let job = fractal.into_job();
+            return print_analysis(&job.display_name(), job.analyze(cancel), cli.json);
+        }
+        Command::Symmetry { fractal } => {
+            let job = fractal.into_job();
+            return print_symmetry(&job.display_name(), job.symmetry(), cli.json);
+        }
+        Command::Rule {
+            command: RuleCommand::Test { file, depth },
+        } => {
+            let name = file.display().to_string();
+            let report = RuleTest::load(&file).and_then(|mut test| {
+                test.depth = depth.unwrap_or(test.depth);
+                test.run(&name, cancel)
+            });
+            return print_rule_test(&name, report, cli.json);
+        }
+        Command::Removal { fractal, tree } => {
+            let job = fractal.into_job();
+            if let Some(tree) = tree {
+                if let Err(e) = job.export_removal_tree(&tree, cancel) {
+                    eprintln!("error: {}: {e}", tree.display());
+                    return ExitCode::FAILURE;
+                }
+            }
+            return print_removal(&job.display_name(), job.removal(), cli.json);
+        }
+        Command::AnalyzeVolume { volume, region } => {
+            let analysis = MappedVolume::open(&volume)
+                .and_then(|mapped| analyze_volume(&mapped, region, cancel));
+            return print_analysis(&volume.display().to_string(), analysis, cli.json);
+        }
+        Command::Deviation {
+            reference,
+            measured,
+            samples,
+            errors,
+            palette,
+        } => {
+            let names = (
+                reference.display().to_string(),
+                measured.display().to_string(),
+            );
+            let name = format!("{} against {}", names.1, names.0);
+            let result = read_mesh(&reference).and_then(|reference_mesh| {
+                let measured_mesh = read_mesh(&measured)?;
+                let (mut report, vertex_errors) =
+                    deviation(names, &reference_mesh, &measured_mesh, samples, cancel)?;
+                if let Some(path) = &errors {
+                    report.errors = Some(write_errors(
+                        &measured_mesh,
+                        &vertex_errors,
+                        path,
+                        &palette,
+                        cancel,
+                    )?);
+                }
+                Ok(report)
+            });
+            return print_deviation(&name, result, cli.json);
+        }
+        Command::Diff {
+            first,
+            second,
+            slice,
+            voxels,
+        } => {
+            let name = format!("{} against {}", first.display(), second.display());
+            let diff = Source::load(&first).and_then(|first| {
+                let second = Source::load(&second)?;
+                compare(&first, &second, slice, voxels.as_deref(), cancel)
+            });
+            return print_diff(&name, diff, cli.json);
+        }
+        Command::Render {
+            fractal,
+            scene,
+            slice,
+            samples,
+            stereo,
+            passes,
+            color,
+            materials,
+            output,
+        } => {
+            let mut job = fractal.into_job();
+            let Some(mut scene) = load_scene(scene) else {
+                return ExitCode::FAILURE;
+            };
+            if let Some(samples) = samples {
+                scene
+                    .path_tracing
+                    .get_or_insert_with(Default::default)
+                    .samples = samples;
+            }
+            if let Some(mode) = stereo {
+                scene
+                    .camera
+                    .stereo
+                    .get_or_insert_with(Default::default)
+                    .mode = mode;
+            }
+            if !passes.is_empty() {
+                scene.passes = passes;
+            }
+            if color.is_some() {
+                scene.color = color;
+            }
+            let Some(materials) = load_materials(materials) else {
+                return ExitCode::FAILURE;
+            };
+            job.materials = materials;
+            return match job.render(&scene, slice, &output, cancel) {
+                Ok(artifacts) if cli.json => {
+                    let json = serde_json::to_string_pretty(&artifacts);
+                    println!("{}", json.expect("artifacts serialize"));
+                    ExitCode::SUCCESS
+                }
+                Ok(artifacts) => {
+                    for artifact in artifacts {
+                        println!("rendered {}", artifact.path.display());
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {e}", job.display_name());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::ContactSheet {
+            fractal,
+            scene,
+            slice,
+            size,
+            output,
+        } => {
+            let job = fractal.into_job();
+            let Some(scene) = load_scene(scene) else {
+                return ExitCode::FAILURE;
+            };
+            return match job.contact_sheet(&scene, slice, size, &output, cancel) {
+                Ok(artifact) if cli.json => {
+                    let json = serde_json::to_string_pretty(&artifact);
+                    println!("{}", json.expect("artifact serializes"));
+                    ExitCode::SUCCESS
+                }
+                Ok(artifact) => {
+                    println!("rendered {}", artifact.path.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {e}", job.display_name());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        #[cfg(feature = "rapier")]
+        Command::Collapse {
+            fractal,
+            slice,
+            frames,
+            fps,
+            cube_size,
+            height,
+            scatter,
+            seed,
+            output,
+        } => {
+            let job = fractal.into_job();
+            let collapse = Collapse {
+                frames,
+                fps,
+                cell_size: cube_size,
+                height,
+                scatter,
+                seed,
+                ..Collapse::default()
+            };
+            return match job.collapse(&collapse, slice, &output, cancel) {
+                Ok(artifact) if cli.json => {
+                    let json = serde_json::to_string_pretty(&artifact);
+                    println!("{}", json.expect("artifact serializes"));
+                    ExitCode::SUCCESS
+                }
+                Ok(artifact) => {
+                    println!("wrote {}", artifact.path.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {e}", job.display_name());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        #[cfg(feature = "plots")]
+        Command::Plot {
+            fractal,
+            chart,
+            output,
+        } => {
+            let job = fractal.into_job();
+            return match job.plot(chart, &output, cancel) {
+                Ok(artifact) if cli.json => {
+                    let json = serde_json::to_string_pretty(&artifact);
+                    println!("{}", json.expect("artifact serializes"));
+                    ExitCode::SUCCESS
+                }
+                Ok(artifact) => {
+                    println!("wrote {}", artifact.path.display());
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {e}", job.display_name());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::RenderBookmark {
+            bookmark: path,
+            scene,
+            size,
+            output,
+        } => {
+            let Some(scene) = load_scene(scene) else {
+                return ExitCode::FAILURE;
+            };
+            let rendered = Bookmark::load(&path).and_then(|mut bookmark| {
+                if let Some([width, height]) = size {
+                    bookmark.camera.width = width;
+                    bookmark.camera.height = height;
+                }
+                bookmark.render(&scene, &output, cancel)
+            });
+            return match rendered {
+                Ok(artifacts) if cli.json => {
+                    let json = serde_json::to_string_pretty(&artifacts);
+                    println!("{}", json.expect("artifacts serialize"));
+                    ExitCode::SUCCESS
+                }
+                Ok(artifacts) => {
+                    for artifact in artifacts {
+                        println!("rendered {}", artifact.path.display());
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {e}", path.display());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Batch {
+            manifest,
+            jobs,
+            artifacts,
+            plan: PlanArgs { dry_run },
+        } => match Manifest::load(&manifest) {
+            Ok(manifest) if dry_run => return print_plans(&manifest.jobs),
+            Ok(manifest) => {
+                let results = run_batch(&manifest.jobs, jobs, cancel);
+                let saved = match &artifacts {
+                    Some(path) => Provenance::new(&manifest.jobs, &results)
+                        .save(path)
+                        .map_err(|e| eprintln!("error: {}: {e}", path.display())),
+                    None => Ok(()),
+                };
+                let names = manifest.jobs.iter().map(Job::display_name);
+                let code = print_summary(names.zip(results).collect(), cli.json);
+                return if saved.is_ok() {
+                    code
+                } else {
+                    ExitCode::FAILURE
+                };
+            }
+            Err(e) => {
+                eprintln!("error: {}: {e}", manifest.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        #[cfg(feature = "tui")]
+        Command::Tui { .. } => unreachable!("run before logging starts"),
+        #[cfg(feature = "viewer")]
+        Command::View {
+            fractal,
+            depth,
+            resolution,
+            scene,
+            bookmark,
+            screenshot_size,
+        } => {
+            let watch = scene.clone().filter(|_| cli.watch);
+            let Some(mut scene) = load_scene(scene) else {
+                return ExitCode::FAILURE;
+            };
+            let explorer = match bookmark {
+                Some(path) => match Bookmark::load(&path) {
+                    Ok(bookmark) => {
+                        scene.camera = bookmark.camera;
+                        bookmark.slice
+                    }
+                    Err(e) => {
+                        eprintln!("error: {}: {e}", path.display());
+                        return ExitCode::FAILURE;
+                    }
+                },
+                None => fractal_slicer_4_d::slice::Explorer {
+                    fractal,
+                    depth,
+                    resolution,
+                    ..Default::default()
+                },
+            };
+            return match fractal_slicer_4_d::viewer::run(explorer, scene, screenshot_size, watch) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Dataset {
+            output,
+            samples,
+            fractals,
+            min_depth,
+            max_depth,
+            resolution,
+            val_fraction,
+            seed,
+            jobs,
+        } => {
+            let dataset = Dataset {
+                samples,
+                fractals: if fractals.is_empty() {
+                    Rule::NAMES.iter().map(|name| name.to_string()).collect()
+                } else {
+                    fractals
+                },
+                min_depth,
+                max_depth,
+                resolution,
+                val_fraction,
+                seed,
+            };
+            return match dataset.generate(&output, jobs, cancel) {
+                Ok(report) if cli.json => {
+                    let json = serde_json::to_string_pretty(&report);
+                    println!("{}", json.expect("report serializes"));
+                    ExitCode::SUCCESS
+                }
+                Ok(report) => {
+                    print!("{report}");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {e}", output.display());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Bench {
+            fractal,
+            dims,
+            max_cells,
+            backends,
+            repeat,
+        } => {
+            let bench = Bench {
+                fractal,
+                dims,
+                max_cells,
+                backends: if backends.is_empty() {
+                    Bench::default().backends
+                } else {
+                    backends
+                },
+                repeat,
+            };
+            return match bench.run(cancel) {
+                Ok(report) if cli.json => {
+                    let json = serde_json::to_string_pretty(&report);
+                    println!("{}", json.expect("report serializes"));
+                    ExitCode::SUCCESS
+                }
+                Ok(report) => {
+                    print!("{report}");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Gather { array } => {
+            return match gather(&array) {
+                Ok(gathered) => {
+                    if cli.json {
+                        let json = serde_json::to_string_pretty(&gathered);
+                        println!("{}", json.expect("gathered counts serialize"));
+                    } else {
+                        print!("{gathered}");
+                    }
+                    if gathered.missing.is_empty() {
+                        ExitCode::SUCCESS
+                    } else {
+                        ExitCode::FAILURE
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {e}", array.display());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Verify { artifacts } => {
+            let verification = match Provenance::load(&artifacts) {
+                Ok(provenance) => provenance.verify(),
+                Err(e) => {
+                    eprintln!("error: {}: {e}", artifacts.display());
+                    return ExitCode::FAILURE;
+                }
+            };
+            if cli.json {
+                let json = serde_json::to_string_pretty(&verification);
+                println!("{}", json.expect("verification serializes"));
+            } else {
+                print!("{verification}");
+            }
+            return if verification.failed == 0 {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            };
+        }
+        Command::Shader {
+            fractal,
+            language,
+            output,
+        } => {
+            let job = fractal.into_job();
+            let source = job
+                .validate()
+                .and_then(|()| job.rule())
+                .and_then(|rule| shader::source(&rule, job.depth, language));
+            let source = match source {
+                Ok(source) => source,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let Some(output) = output else {
+                print!("{source}");
+                return ExitCode::SUCCESS;
+            };
+            return match std::fs::write(&output, source) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {}: {e}", output.display());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Presets => {
+            return match Presets::load(cli.presets.as_deref()) {
+                Ok(presets) if cli.json => {
+                    let json = serde_json::to_string_pretty(&presets);
+                    println!("{}", json.expect("presets serialize"));
+                    ExitCode::SUCCESS
+                }
+                Ok(presets) => {
+                    print!("{presets}");
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::WebDemo {
+            fractal,
+            output,
+            wasm,
+        } => {
+            let job = fractal.into_job();
+            let written = job.validate().and_then(|()| job.rule()).and_then(|rule| {
+                let wasm = match wasm {
+                    Some(wasm) => wasm,
+                    None => web_demo::build_wasm()?,
+                };
+                web_demo::write(&rule, job.depth, &wasm, &output)
+            });
+            return match written {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::BlenderAddon { output } => {
+            let program = std::env::current_exe().unwrap_or_else(|_| "fractal-slicer".into());
+            let source = addon(&program);
+            let Some(output) = output else {
+                print!("{source}");
+                return ExitCode::SUCCESS;
+            };
+            return match std::fs::write(&output, source) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {}: {e}", output.display());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Completions { shell } => {
+            let mut command = Cli::command();
+            clap_complete::generate(
+                shell,
+                &mut command,
+                "fractal-slicer",
+                &mut std::io::stdout(),
+            );
+            return ExitCode::SUCCESS;
+        }
+        Command::Manpage { output } => {
+            let written = match &output {
+                Some(dir) => std::fs::create_dir_all(dir)
+                    .and_then(|()| clap_mangen::generate_to(Cli::command(), dir)),
+                None => clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout()),
+            };
+            return match written {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    let path = output.unwrap_or_else(|| "stdout".into());
+                    eprintln!("error: {}: {e}", path.display());
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        Command::Serve {
+            addr,
+            max_depth,
+            cache,
+            timeout,
+            workers,
+            max_queued,
+            connections,
+            io_timeout,
+            access,
+            output,
+        } => {
+            let access = match access.as_deref().map(Access::load).transpose() {
+                Ok(access) => access,
+                Err(e) => {
+                    eprintln!("error: {e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let config = ServerConfig {
+                max_depth,
+                cache_capacity: cache,
+                request_timeout: (timeout > 0).then(|| Duration::from_secs(timeout)),
+                workers,
+                max_queued,
+                connections,
+                io_timeout: (io_timeout > 0).then(|| Duration::from_secs(io_timeout)),
+                access,
+                output,
+            };
+            return match serve(&addr, config) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("error: {addr}: {e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+    };
+    if cancel.is_cancelled() {
+        print_summary(results, cli.json);
+        return ExitCode::from(130);
+    }
+    print_summary(results, cli.json)
+}
+
+/// The scene file at `path`, or the default scene without one; `None`
+/// after reporting a file that cannot be read.
+fn load_scene(path: Option<PathBuf>) -> Option<Scene> {
+    let Some(path) = path else {
+        return Some(Scene::default());
+    };
+    match Scene::load(&path) {
+        Ok(scene) => Some(scene),
+        Err(e) => {
+            eprintln!("error: {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// The material rules in the JSON file at `path`, or none without one;
+/// `None` after reporting a file that cannot be read.
+fn load_materials(path: Option<PathBuf>) -> Option<Vec<MaterialRule>> {
+    let Some(path) = path else {
+        return Some(Vec::new());
+    };
+    let rules = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()));
+    match rules {
+        Ok(rules) => Some(rules),
+        Err(e) => {
+            eprintln!("error: {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn print_plans(jobs: &[Job]) -> ExitCode {
+    let mut status = ExitCode::SUCCESS;
+    for job in jobs {
+        match job.plan() {
+            Ok(plan) => print!("{plan}"),
+            Err(e) => {
+                println!("job {}: {e}", job.display_name());
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+/// The first Ctrl-C cancels running work so it can stop cleanly; a second
+/// one exits immediately.
+fn install_interrupt_handler() -> CancelToken {
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    let result = ctrlc::set_handler(move || {
+        if token.is_cancelled() {
+            std::process::exit(130);
+        }
+        eprintln!("interrupted, stopping after the current step (Ctrl-C again to abort)");
+        token.cancel();
+    });
+    if let Err(e) = result {
+        tracing::warn!("cannot install Ctrl-C handler: {e}");
+    }
+    cancel
+}
+
+fn serve(addr: &str, config: ServerConfig) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    tracing::info!("listening on http://{}", listener.local_addr()?);
+    Arc::new(Server::new(config)).serve(listener)
+}
+
+/// Prints an analysis, or the error that stopped it.
+fn print_analysis(name: &str, analysis: Result<Analysis>, json: bool) -> ExitCode {
+    match analysis {
+        Ok(analysis) if json => {
+            let json = serde_json::to_string_pretty(&analysis);
+            println!("{}", json.expect("analysis serializes"));
+            ExitCode::SUCCESS
+        }
+        Ok(analysis) => {
+            print!("{analysis}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the distances between two meshes, or the error that stopped
+/// them being measured.
+fn print_deviation(name: &str, deviation: Result<Deviation>, json: bool) -> ExitCode {
+    match deviation {
+        Ok(deviation) if json => {
+            let json = serde_json::to_string_pretty(&deviation);
+            println!("{}", json.expect("deviation serializes"));
+            ExitCode::SUCCESS
+        }
+        Ok(deviation) => {
+            print!("{deviation}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints a comparison of two lattices, or the error that stopped it.
+fn print_diff(name: &str, diff: Result<LatticeDiff>, json: bool) -> ExitCode {
+    match diff {
+        Ok(diff) if json => {
+            let json = serde_json::to_string_pretty(&diff);
+            println!("{}", json.expect("diff serializes"));
+            ExitCode::SUCCESS
+        }
+        Ok(diff) => {
+            print!("{diff}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints a symmetry report, or the error that stopped it.
+fn print_symmetry(name: &str, report: Result<SymmetryReport>, json: bool) -> ExitCode {
+    match report {
+        Ok(report) => {
+            if json {
+                let json = serde_json::to_string_pretty(&report);
+                println!("{}", json.expect("symmetry report serializes"));
+            } else {
+                print!("{report}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_rule_test(name: &str, report: Result<RuleTestReport>, json: bool) -> ExitCode {
+    match report {
+        Ok(report) => {
+            if json {
+                let json = serde_json::to_string_pretty(&report);
+                println!("{}", json.expect("rule test report serializes"));
+            } else {
+                print!("{report}");
+            }
+            if report.passed() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_removal(name: &str, report: Result<RemovalReport>, json: bool) -> ExitCode {
+    match report {
+        Ok(report) if json => {
+            let json = serde_json::to_string_pretty(&report);
+            println!("{}", json.expect("removal report serializes"));
+            ExitCode::SUCCESS
+        }
+        Ok(report) => {
+            print!("{report}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {name}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parses `x,y,z` or `x,y,z,w` tile counts.
+fn parse_tile(text: &str) -> std::result::Result<[usize; 4], String> {
+    let counts = text
+        .split(',')
+        .map(|part| part.trim().parse::<usize>().map_err(|e| e.to_string()))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    match counts[..] {
+        [x, y, z] => Ok([x, y, z, 1]),
+        [x, y, z, w] => Ok([x, y, z, w]),
+        _ => Err("expected x,y,z or x,y,z,w".to_string()),
+    }
+}
+
+/// Parses `w0:w1` slabs, both ends included.
+fn parse_slab(text: &str) -> std::result::Result<[usize; 2], String> {
+    let (w0, w1) = text.split_once(':').ok_or("expected w0:w1")?;
+    let parse = |part: &str| part.trim().parse::<usize>().map_err(|e| e.to_string());
+    let (w0, w1) = (parse(w0)?, parse(w1)?);
+    if w0 > w1 {
+        return Err("the slab must not end before it starts".to_string());
+    }
+    Ok([w0, w1])
+}
+
+/// Parses `x0,y0,z0:x1,y1,z1` boxes, upper bounds exclusive.
+fn parse_region(text: &str) -> std::result::Result<([usize; 3], [usize; 3]), String> {
+    let (low, high) = text.split_once(':').ok_or("expected x0,y0,z0:x1,y1,z1")?;
+    let corner = |part: &str| {
+        let values = part
+            .split(',')
+            .map(|value| value.trim().parse::<usize>().map_err(|e| e.to_string()))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        <[usize; 3]>::try_from(values).map_err(|_| "expected x0,y0,z0:x1,y1,z1".to_string())
+    };
+    let (low, high) = (corner(low)?, corner(high)?);
+    if (0..3).any(|a| low[a] >= high[a]) {
+        return Err("each upper bound must exceed its lower one".to_string());
+    }
+    Ok((low, high))
+}
+
+/// Parses `nx,ny,nz,nw=p/q` hyperplanes, the offset a whole number or a
+/// fraction.
+fn parse_section(text: &str) -> std::result::Result<Section, String> {
+    let (normal, offset) = parse_plane(text)?;
+    Ok(Section { normal, offset })
+}
+
+/// Parses `nx,ny,nz=p/q` planes, as [`parse_section`].
+fn parse_cut(text: &str) -> std::result::Result<PlaneCut, String> {
+    let (normal, offset) = parse_plane(text)?;
+    Ok(PlaneCut { normal, offset })
+}
+
+fn parse_plane<const D: usize>(text: &str) -> std::result::Result<([i64; D], [i64; 2]), String> {
+    let (normal, offset) = text
+        .split_once('=')
+        .ok_or("expected a normal, `=` and an offset")?;
+    let parse = |part: &str| part.trim().parse::<i64>().map_err(|e| e.to_string());
+    let normal = normal
+        .split(',')
+        .map(parse)
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let normal =
+        <[i64; D]>::try_from(normal).map_err(|_| format!("expected {D} normal components"))?;
+    let offset = match offset.split_once('/') {
+        Some((p, q)) => [parse(p)?, parse(q)?],
+        None => [parse(offset)?, 1],
+    };
+    Ok((normal, offset))
+}
+
+/// Parses `WIDTHxHEIGHT` image sizes, e.g. `1920x1080`.
+fn parse_size(text: &str) -> std::result::Result<[usize; 2], String> {
+    let (width, height) = text.split_once('x').ok_or("expected WIDTHxHEIGHT")?;
+    let parse = |part: &str| part.trim().parse::<usize>().map_err(|e| e.to_string());
+    Ok([parse(width)?, parse(height)?])
+}
+
+/// Parses `rank/size`, `env` for the rank the launcher set, or `mpi`.
+fn parse_rank(text: &str) -> std::result::Result<Ranks, String> {
+    match text {
+        "env" => Ranks::from_env().ok_or_else(|| "no MPI or Slurm rank is set".to_string()),
+        "mpi" => Ranks::from_mpi().map_err(|e| e.to_string()),
+        _ => text.parse(),
+    }
+}
+
+fn parse_program(text: &str) -> std::result::Result<Program, String> {
+    match text.strip_prefix('@') {
+        Some(path) => Ok(Program {
+            source: std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?,
+            file: Some(path.into()),
+        }),
+        None => Ok(Program {
+            source: text.to_string(),
+            file: None,
+        }),
+    }
+}
+
+fn parse_combine(text: &str) -> std::result::Result<RuleCombinator, String> {
+    let (op, rules) = text.split_once(':').ok_or("expected op:rule,rule,…")?;
+    let op = match op {
+        "alternate" => Combination::Alternate,
+        "intersection" => Combination::Intersection,
+        "union" => Combination::Union,
+        _ => return Err(format!("unknown combination `{op}`")),
+    };
+    Ok(RuleCombinator {
+        op,
+        rules: rules
+            .split(',')
+            .map(|rule| rule.trim().to_string())
+            .collect(),
+    })
+}
+
+/// Parses `op[:element][:radius]`, e.g. `dilate`, `close:2` or
+/// `erode:cube:1`.
+fn parse_morphology(text: &str) -> std::result::Result<Morphology, String> {
+    let mut parts = text.split(':');
+    let op = match parts.next().unwrap_or_default() {
+        "dilate" => Operation::Dilate,
+        "erode" => Operation::Erode,
+        "open" => Operation::Open,
+        "close" => Operation::Close,
+        op => return Err(format!("unknown morphology `{op}`")),
+    };
+    let mut morphology = Morphology::new(op, Element::default(), 1);
+    for part in parts {
+        morphology.element = match part {
+            "ball" => Element::Ball,
+            "cube" => Element::Cube,
+            "cross" => Element::Cross,
+            radius => {
+                morphology.radius = radius
+                    .parse()
+                    .map_err(|_| format!("expected an element or radius, got `{radius}`"))?;
+                continue;
+            }
+        };
+    }
+    Ok(morphology)
+}
+
+/// Logs to stderr at `info` unless `RUST_LOG` says otherwise.
+fn init_logging(format: LogFormat) {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into()))
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+fn print_summary(results: Vec<(String, Result<JobReport>)>, json: bool) -> ExitCode {
+    let summary = Summary::new(results);
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&summary).expect("summary serializes")
+        );
+    } else {
+        let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+        print!("{}", summary.render(color));
+    }
+    if summary.failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
 }
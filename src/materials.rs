@@ -0,0 +1,180 @@
+//! Per-cell material rules: named materials assigned to a rule fractal's
+//! cells by the digits of their position at a level of the subdivision.
+//!
+//! A cell's level-`l` digit along an axis is the index, `0..base`, of the
+//! level-`l` block holding it among its siblings, so at depth 2 of the
+//! Menger sponge the level-2 digits `[0, 0, 0]` pick the low corner cube of
+//! every level-1 block:
+//!
+//! ```json
+//! "materials": [
+//!   { "name": "gold", "color": [1.0, 0.56, 0.1], "level": 2, "digits": [0, 0, 0] },
+//!   { "name": "glass", "color": [0.6, 0.8, 0.9] }
+//! ]
+//! ```
+//!
+//! Each cell takes the first rule it matches. Renders colour faces by
+//! their cells' materials, `.glb` outputs carry them as vertex colours, and
+//! an output path with `{material}` is written once per material with only
+//! its cells, as multi-material printers take their parts.
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::FaceColors;
+use crate::error::{Error, Result};
+use crate::lattice::Lattice3;
+
+/// A named material and the cells it takes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialRule {
+    pub name: String,
+    /// Linear RGB, as scene materials give it.
+    #[serde(default = "default_color")]
+    pub color: [f64; 3],
+    /// The level whose digits are read, 1 for the coarsest; without one
+    /// the rule takes every cell left.
+    #[serde(default)]
+    pub level: Option<u32>,
+    /// The digits along x, y and z a cell needs; `null` or a missing one
+    /// takes any.
+    #[serde(default)]
+    pub digits: Vec<Option<u32>>,
+}
+
+/// Material rules bound to a rule's bases and depth.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Materials {
+    rules: Vec<MaterialRule>,
+    bases: [u32; 3],
+    depth: u32,
+    /// The distinct materials, by name in order of first mention, and
+    /// `default` for cells no rule takes.
+    materials: Vec<(String, [f64; 3])>,
+    /// Per rule, its material.
+    indices: Vec<usize>,
+}
+
+impl Materials {
+    /// Binds `rules` to the depth-`depth` fractal of a rule split evenly
+    /// along axes of `bases`, checking their levels and digits.
+    pub fn new(rules: &[MaterialRule], bases: &[u32], depth: u32) -> Result<Self> {
+        let bases: [u32; 3] = bases
+            .get(..3)
+            .and_then(|bases| bases.try_into().ok())
+            .ok_or_else(|| Error::InvalidJob("material rules need three axes".into()))?;
+        let mut materials: Vec<(String, [f64; 3])> = Vec::new();
+        let mut indices = Vec::new();
+        for rule in rules {
+            if rule.name.is_empty() {
+                return Err(Error::InvalidJob("a material has no name".into()));
+            }
+            if !rule.color.iter().all(|c| c.is_finite() && *c >= 0.0) {
+                return Err(Error::InvalidJob(format!(
+                    "material `{}` has a negative or non-finite colour",
+                    rule.name
+                )));
+            }
+            if let Some(level) = rule.level {
+                if !(1..=depth).contains(&level) {
+                    return Err(Error::InvalidJob(format!(
+                        "material `{}` reads level {level}, outside 1 to the depth {depth}",
+                        rule.name
+                    )));
+                }
+            }
+            if rule.digits.len() > 3 {
+                return Err(Error::InvalidJob(format!(
+                    "material `{}` has more than three digits",
+                    rule.name
+                )));
+            }
+            if rule.level.is_none() && rule.digits.iter().any(Option::is_some) {
+                return Err(Error::InvalidJob(format!(
+                    "material `{}` has digits but no level to read them at",
+                    rule.name
+                )));
+            }
+            for (axis, digit) in rule.digits.iter().enumerate() {
+                if let Some(digit) = digit.filter(|&digit| digit >= bases[axis]) {
+                    return Err(Error::InvalidJob(format!(
+                        "material `{}` needs digit {digit} along an axis split into {}",
+                        rule.name, bases[axis]
+                    )));
+                }
+            }
+            indices.push(index_of(&mut materials, &rule.name, rule.color));
+        }
+        if rules.last().is_none_or(|rule| rule.level.is_some()) {
+            index_of(&mut materials, "default", default_color());
+        }
+        Ok(Materials {
+            rules: rules.to_vec(),
+            bases,
+            depth,
+            materials,
+            indices,
+        })
+    }
+
+    /// The materials' names, in the order [`Materials::of`] numbers them.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.materials.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The colour of material `material`.
+    pub fn color(&self, material: usize) -> [f64; 3] {
+        self.materials[material].1
+    }
+
+    /// The material of `cell`.
+    pub fn of(&self, cell: [usize; 3]) -> usize {
+        let rule = self.rules.iter().position(|rule| {
+            let Some(level) = rule.level else {
+                return true;
+            };
+            rule.digits.iter().enumerate().all(|(axis, digit)| {
+                let base = self.bases[axis] as usize;
+                let block = base.pow(self.depth - level);
+                digit.is_none_or(|digit| cell[axis] / block % base == digit as usize)
+            })
+        });
+        match rule {
+            Some(rule) => self.indices[rule],
+            // Rules that all read digits leave `default` last.
+            None => self.materials.len() - 1,
+        }
+    }
+
+    /// The cells of `lattice` of each material, in the order of
+    /// [`Materials::names`].
+    pub fn split(&self, lattice: &Lattice3) -> Vec<Lattice3> {
+        let mut parts = vec![Lattice3::new(lattice.shape()); self.materials.len()];
+        for cell in lattice.iter() {
+            parts[self.of(cell)].set(cell, true);
+        }
+        parts
+    }
+}
+
+impl FaceColors for Materials {
+    fn face(&self, cell: [usize; 3], _axis: usize, _positive: bool) -> [f64; 3] {
+        self.color(self.of(cell))
+    }
+}
+
+/// The index of the material `name` in `materials`, added with `color` if
+/// it is not there yet.
+fn index_of(materials: &mut Vec<(String, [f64; 3])>, name: &str, color: [f64; 3]) -> usize {
+    match materials.iter().position(|(known, _)| known == name) {
+        Some(index) => index,
+        None => {
+            materials.push((name.to_string(), color));
+            materials.len() - 1
+        }
+    }
+}
+
+fn default_color() -> [f64; 3] {
+    [0.8; 3]
+}
@@ -0,0 +1,447 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::lattice::{Boundary, Lattice3};
+use crate::transform::Affine;
+
+mod contour;
+mod polyhedral;
+mod simplify;
+mod validation;
+
+pub use contour::contour;
+pub use polyhedral::PolyMesh;
+pub use simplify::{simplify, Simplify, SimplifyReport};
+pub use validation::{repair, validate, RepairReport, ValidationReport};
+
+/// One exposed face of a filled cell: the side of `cell` facing along
+/// `axis`, in the positive or negative direction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Face {
+    pub cell: [usize; 3],
+    pub axis: usize,
+    pub positive: bool,
+}
+
+impl Face {
+    /// The face's corners as lattice points, counter-clockwise when seen
+    /// from outside the cell.
+    pub fn lattice_corners(&self) -> [[usize; 3]; 4] {
+        let a = self.axis;
+        let (u, v) = ((a + 1) % 3, (a + 2) % 3);
+        let corner = |du: usize, dv: usize| {
+            let mut p = self.cell;
+            if self.positive {
+                p[a] += 1;
+            }
+            p[u] += du;
+            p[v] += dv;
+            p
+        };
+        if self.positive {
+            [corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1)]
+        } else {
+            [corner(0, 0), corner(0, 1), corner(1, 1), corner(1, 0)]
+        }
+    }
+
+    /// The face's corners in lattice units, counter-clockwise when seen from
+    /// outside the cell.
+    pub fn corners(&self) -> [[f64; 3]; 4] {
+        self.lattice_corners().map(|p| p.map(|c| c as f64))
+    }
+
+    /// Outward unit normal.
+    pub fn normal(&self) -> [f64; 3] {
+        let mut n = [0.0; 3];
+        n[self.axis] = if self.positive { 1.0 } else { -1.0 };
+        n
+    }
+}
+
+/// Collects the faces of filled cells that border an empty cell or the
+/// outside of the lattice; faces shared by two filled cells are culled.
+/// Outside cells are read under `boundary`, so a periodic lattice keeps
+/// only the faces its tiled copies would show, and a mirrored one has no
+/// faces on its outer walls.
+#[tracing::instrument(name = "mesh", skip_all, fields(cells = lattice.count()))]
+pub fn surface_faces(lattice: &Lattice3, boundary: Boundary) -> Vec<Face> {
+    let mut faces = Vec::new();
+    for cell in lattice.iter() {
+        let p = cell.map(|c| c as i64);
+        for axis in 0..3 {
+            for positive in [false, true] {
+                let mut q = p;
+                q[axis] += if positive { 1 } else { -1 };
+                if !lattice.get_bounded(q, boundary) {
+                    faces.push(Face {
+                        cell,
+                        axis,
+                        positive,
+                    });
+                }
+            }
+        }
+    }
+    faces
+}
+
+/// The faces of one chunk of a larger lattice, from a lattice holding the
+/// chunk inside a one-cell ghost border of its neighbours' cells, as
+/// [`Lattice::chunk_from_fn`](crate::lattice::Lattice::chunk_from_fn)
+/// builds. Faces are culled against the ghosts, so chunks that cover the
+/// lattice give exactly the faces of [`surface_faces`] under the boundary
+/// the ghosts were read with, in the same order within each chunk, and
+/// cells are placed at `low`, the chunk's first cell.
+pub fn chunk_faces(chunk: &Lattice3, low: [usize; 3]) -> impl Iterator<Item = Face> + '_ {
+    let shape = chunk.shape();
+    chunk
+        .iter()
+        .filter(move |p| (0..3).all(|a| (1..shape[a] - 1).contains(&p[a])))
+        .flat_map(move |p| {
+            (0..6).filter_map(move |side| {
+                let (axis, positive) = (side / 2, side % 2 == 1);
+                let mut q = p;
+                q[axis] = if positive { q[axis] + 1 } else { q[axis] - 1 };
+                (!chunk.get(q)).then(|| Face {
+                    cell: std::array::from_fn(|a| low[a] + p[a] - 1),
+                    axis,
+                    positive,
+                })
+            })
+        })
+}
+
+/// Whether faces are kept as quads or split into triangles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaceKind {
+    Quads,
+    Triangles,
+}
+
+/// Face index lists of a [`Mesh`], counter-clockwise from outside.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Polygons {
+    Quads(Vec<[u32; 4]>),
+    Triangles(Vec<[u32; 3]>),
+}
+
+/// The type of a mesh's vertex coordinates: `f64`, or `f32` for meshes
+/// only ever drawn or written as single precision, at half the memory.
+///
+/// Lattice points are integers and `f32` holds every integer up to 2^24
+/// exactly, so the surfaces of lattices up to 16,777,216 cells a side are
+/// the same in either; only transformed vertices are rounded.
+pub trait Scalar: Copy + PartialEq + fmt::Debug + fmt::Display + Send + Sync + 'static {
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Scalar for f32 {
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+/// An indexed surface mesh, with `f64` coordinates unless `S` says
+/// otherwise. Simplification, repair and the other mesh stages work on
+/// `f64` meshes; see [`Mesh::cast`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh<S: Scalar = f64> {
+    pub vertices: Vec<[S; 3]>,
+    pub faces: Polygons,
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// The same mesh with coordinates of type `T`, rounded to the nearest
+    /// `T`; free when `T` is `S`.
+    pub fn cast<T: Scalar>(mut self) -> Mesh<T> {
+        let same: &mut dyn Any = &mut self.vertices;
+        if let Some(vertices) = same.downcast_mut::<Vec<[T; 3]>>() {
+            return Mesh {
+                vertices: std::mem::take(vertices),
+                faces: self.faces,
+            };
+        }
+        Mesh {
+            vertices: self
+                .vertices
+                .into_iter()
+                .map(|v| v.map(|c| T::from_f64(c.to_f64())))
+                .collect(),
+            faces: self.faces,
+        }
+    }
+
+    /// The vertex at `index` in `f64`.
+    pub fn vertex(&self, index: u32) -> [f64; 3] {
+        self.vertices[index as usize].map(S::to_f64)
+    }
+
+    pub fn face_count(&self) -> usize {
+        match &self.faces {
+            Polygons::Quads(quads) => quads.len(),
+            Polygons::Triangles(triangles) => triangles.len(),
+        }
+    }
+
+    /// The faces as triangles, splitting each quad along its 0-2 diagonal.
+    pub fn triangles(&self) -> Vec<[u32; 3]> {
+        match &self.faces {
+            Polygons::Quads(quads) => quads
+                .iter()
+                .flat_map(|q| [[q[0], q[1], q[2]], [q[0], q[2], q[3]]])
+                .collect(),
+            Polygons::Triangles(triangles) => triangles.clone(),
+        }
+    }
+
+    /// Moves every vertex by `affine`, reversing the winding if it mirrors
+    /// so faces keep pointing outwards.
+    pub fn transform(&mut self, affine: &Affine) {
+        for v in &mut self.vertices {
+            *v = affine.apply(v.map(S::to_f64)).map(S::from_f64);
+        }
+        if affine.is_mirroring() {
+            match &mut self.faces {
+                Polygons::Quads(quads) => quads.iter_mut().for_each(|q| q.reverse()),
+                Polygons::Triangles(triangles) => triangles.iter_mut().for_each(|t| t.reverse()),
+            }
+        }
+    }
+
+    /// Size of the bounding box along each axis.
+    pub fn extent(&self) -> [f64; 3] {
+        let mut min = [f64::INFINITY; 3];
+        let mut max = [f64::NEG_INFINITY; 3];
+        for v in &self.vertices {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(v[axis].to_f64());
+                max[axis] = max[axis].max(v[axis].to_f64());
+            }
+        }
+        std::array::from_fn(|axis| (max[axis] - min[axis]).max(0.0))
+    }
+
+    /// One transformed copy of the mesh per placement, merged into a single
+    /// mesh.
+    pub fn tile(&self, placements: &[Affine]) -> Mesh<S> {
+        let mut vertices = Vec::with_capacity(self.vertices.len() * placements.len());
+        let mut faces = match &self.faces {
+            Polygons::Quads(_) => Polygons::Quads(Vec::new()),
+            Polygons::Triangles(_) => Polygons::Triangles(Vec::new()),
+        };
+        for placement in placements {
+            let mut copy = self.clone();
+            copy.transform(placement);
+            let base = vertices.len() as u32;
+            vertices.extend(copy.vertices);
+            match (&mut faces, copy.faces) {
+                (Polygons::Quads(all), Polygons::Quads(quads)) => {
+                    all.extend(quads.into_iter().map(|q| q.map(|v| v + base)))
+                }
+                (Polygons::Triangles(all), Polygons::Triangles(triangles)) => {
+                    all.extend(triangles.into_iter().map(|t| t.map(|v| v + base)))
+                }
+                _ => unreachable!("copies keep the face kind"),
+            }
+        }
+        Mesh { vertices, faces }
+    }
+}
+
+/// Which normals to compute for a mesh.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Normals {
+    #[default]
+    None,
+    /// One flat normal per face; exact for cube faces.
+    Face,
+    /// Per-vertex normals averaged over adjacent faces, weighted by the
+    /// corner angle each face makes at the vertex.
+    Smooth,
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// The unit normal of every face, from its first three corners.
+    pub fn face_normals(&self) -> Vec<[f64; 3]> {
+        let normal = |a: u32, b: u32, c: u32| {
+            let [a, b, c] = [a, b, c].map(|v| self.vertex(v));
+            normalize(cross(sub(b, a), sub(c, a)))
+        };
+        match &self.faces {
+            Polygons::Quads(quads) => quads.iter().map(|q| normal(q[0], q[1], q[2])).collect(),
+            Polygons::Triangles(triangles) => {
+                triangles.iter().map(|t| normal(t[0], t[1], t[2])).collect()
+            }
+        }
+    }
+
+    /// Angle-weighted vertex normals: each face contributes its normal
+    /// scaled by its interior angle at the vertex, which keeps the result
+    /// independent of how faces are triangulated.
+    pub fn vertex_normals(&self) -> Vec<[f64; 3]> {
+        let mut normals = vec![[0.0; 3]; self.vertices.len()];
+        let mut accumulate = |polygon: &[u32], normal: [f64; 3]| {
+            let n = polygon.len();
+            for i in 0..n {
+                let [prev, here, next] =
+                    [polygon[(i + n - 1) % n], polygon[i], polygon[(i + 1) % n]]
+                        .map(|v| self.vertex(v));
+                let angle = angle_between(sub(prev, here), sub(next, here));
+                let sum = &mut normals[polygon[i] as usize];
+                for axis in 0..3 {
+                    sum[axis] += angle * normal[axis];
+                }
+            }
+        };
+        let face_normals = self.face_normals();
+        match &self.faces {
+            Polygons::Quads(quads) => {
+                for (q, &n) in quads.iter().zip(&face_normals) {
+                    accumulate(q, n);
+                }
+            }
+            Polygons::Triangles(triangles) => {
+                for (t, &n) in triangles.iter().zip(&face_normals) {
+                    accumulate(t, n);
+                }
+            }
+        }
+        normals.into_iter().map(normalize).collect()
+    }
+}
+
+pub(crate) fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// Scales `v` to unit length; the zero vector is returned unchanged.
+pub(crate) fn normalize(v: [f64; 3]) -> [f64; 3] {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        v
+    } else {
+        v.map(|c| c / len)
+    }
+}
+
+fn angle_between(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let denominator = (dot(a, a) * dot(b, b)).sqrt();
+    if denominator == 0.0 {
+        return 0.0;
+    }
+    (dot(a, b) / denominator).clamp(-1.0, 1.0).acos()
+}
+
+/// Builds an indexed mesh of the surface culled under `boundary`.
+///
+/// Vertices shared between faces are merged; indices are assigned in order
+/// of first use while walking the faces in lattice order, so the same
+/// lattice always yields the same numbering.
+pub fn build_indexed_mesh(lattice: &Lattice3, kind: FaceKind, boundary: Boundary) -> Mesh {
+    build_indexed_mesh_as(lattice, kind, boundary)
+}
+
+/// Like [`build_indexed_mesh`], with coordinates of type `S`.
+pub fn build_indexed_mesh_as<S: Scalar>(
+    lattice: &Lattice3,
+    kind: FaceKind,
+    boundary: Boundary,
+) -> Mesh<S> {
+    let mut vertices = Vec::new();
+    let mut lookup = HashMap::new();
+    let mut quads = Vec::new();
+    for face in surface_faces(lattice, boundary) {
+        quads.push(face.lattice_corners().map(|corner| {
+            *lookup.entry(corner).or_insert_with(|| {
+                vertices.push(corner.map(|c| S::from_f64(c as f64)));
+                (vertices.len() - 1) as u32
+            })
+        }));
+    }
+    let mut mesh = Mesh {
+        vertices,
+        faces: Polygons::Quads(quads),
+    };
+    if kind == FaceKind::Triangles {
+        mesh.faces = Polygons::Triangles(mesh.triangles());
+    }
+    mesh
+}
+
+/// An axis-aligned block of filled cells, `min` inclusive and `max` exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub min: [usize; 3],
+    pub max: [usize; 3],
+}
+
+/// Greedily covers the filled cells with disjoint boxes, growing each one
+/// along x, then y, then z while the swept cells are filled and unclaimed.
+pub fn greedy_blocks(lattice: &Lattice3) -> Vec<Block> {
+    let shape = lattice.shape();
+    let mut claimed = Lattice3::new(shape);
+    let free = |claimed: &Lattice3, p: [usize; 3]| lattice.get(p) && !claimed.get(p);
+    let mut blocks = Vec::new();
+    for start in lattice.iter() {
+        if claimed.get(start) {
+            continue;
+        }
+        let [x0, y0, z0] = start;
+        let mut x1 = x0 + 1;
+        while x1 < shape[0] && free(&claimed, [x1, y0, z0]) {
+            x1 += 1;
+        }
+        let mut y1 = y0 + 1;
+        while y1 < shape[1] && (x0..x1).all(|x| free(&claimed, [x, y1, z0])) {
+            y1 += 1;
+        }
+        let mut z1 = z0 + 1;
+        while z1 < shape[2] && (y0..y1).all(|y| (x0..x1).all(|x| free(&claimed, [x, y, z1]))) {
+            z1 += 1;
+        }
+        for z in z0..z1 {
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    claimed.set([x, y, z], true);
+                }
+            }
+        }
+        blocks.push(Block {
+            min: start,
+            max: [x1, y1, z1],
+        });
+    }
+    blocks
+}
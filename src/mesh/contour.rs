@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use super::{FaceKind, Mesh, Polygons};
+
+/// Extracts the surface where `field`, sampled at the centres of a grid of
+/// `shape` cells (x fastest), crosses `level`, with samples at or below
+/// `level` inside. Faces are wound outwards and vertices are in lattice
+/// units, so the surface lines up with the cube mesh of the same lattice.
+///
+/// Uses surface nets: one vertex per cube of eight neighbouring samples
+/// that the surface passes through, at the mean of the crossings on its
+/// edges, and one quad per crossed edge between samples. Outside the grid
+/// reads as just above `level`, so the surface closes at the walls, and
+/// infinite samples are read as half a cell from `level`.
+pub fn contour(field: &[f64], shape: [usize; 3], level: f64, kind: FaceKind) -> Mesh {
+    let padded = shape.map(|n| n + 2);
+    let sample = |p: [usize; 3]| -> f64 {
+        if !(0..3).all(|axis| (1..=shape[axis]).contains(&p[axis])) {
+            return level + 0.5;
+        }
+        let value = field[(p[0] - 1) + shape[0] * ((p[1] - 1) + shape[1] * (p[2] - 1))];
+        if value.is_infinite() {
+            level + 0.5 * value.signum()
+        } else {
+            value
+        }
+    };
+    let mut vertices = Vec::new();
+    let mut lookup: HashMap<[usize; 3], u32> = HashMap::new();
+    let mut quads = Vec::new();
+    for z in 0..padded[2] {
+        for y in 0..padded[1] {
+            for x in 0..padded[0] {
+                let p = [x, y, z];
+                let inside = sample(p) <= level;
+                for axis in 0..3 {
+                    if p[axis] + 1 == padded[axis] {
+                        continue;
+                    }
+                    let mut q = p;
+                    q[axis] += 1;
+                    if (sample(q) <= level) == inside {
+                        continue;
+                    }
+                    // The four cubes sharing the edge, counter-clockwise
+                    // about the axis.
+                    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+                    let mut quad = [(0, 0), (1, 0), (1, 1), (0, 1)].map(|(du, dv)| {
+                        let mut cube = p;
+                        cube[u] -= 1 - du;
+                        cube[v] -= 1 - dv;
+                        *lookup.entry(cube).or_insert_with(|| {
+                            vertices.push(cube_vertex(cube, level, &sample));
+                            (vertices.len() - 1) as u32
+                        })
+                    });
+                    if !inside {
+                        quad.reverse();
+                    }
+                    quads.push(quad);
+                }
+            }
+        }
+    }
+    let mut mesh = Mesh {
+        vertices,
+        faces: Polygons::Quads(quads),
+    };
+    if kind == FaceKind::Triangles {
+        mesh.faces = Polygons::Triangles(mesh.triangles());
+    }
+    mesh
+}
+
+/// The mean of the level crossings on the edges of the cube whose lowest
+/// sample is `cube`, in lattice units.
+fn cube_vertex(cube: [usize; 3], level: f64, sample: &impl Fn([usize; 3]) -> f64) -> [f64; 3] {
+    let mut sum = [0.0; 3];
+    let mut crossings = 0;
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for (du, dv) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let mut a = cube;
+            a[u] += du;
+            a[v] += dv;
+            let mut b = a;
+            b[axis] += 1;
+            let (fa, fb) = (sample(a), sample(b));
+            if (fa <= level) != (fb <= level) {
+                for (s, c) in sum.iter_mut().zip(a) {
+                    *s += c as f64;
+                }
+                sum[axis] += (level - fa) / (fb - fa);
+                crossings += 1;
+            }
+        }
+    }
+    // Sample `i` sits at the centre of cell `i - 1`.
+    sum.map(|s| s / crossings as f64 - 0.5)
+}
@@ -0,0 +1,120 @@
+use super::{cross, dot, sub, Mesh, Polygons};
+
+/// Convex polyhedral cells on shared vertices, such as the pieces exact
+/// cuts and sections leave of lattice cells. Each face is a loop of
+/// vertex indices, counter-clockwise seen from outside its cell, marked
+/// as on the surface or against a neighbouring cell.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PolyMesh {
+    pub vertices: Vec<[f64; 3]>,
+    /// The corners of every face, loop after loop.
+    corners: Vec<u32>,
+    /// Each face's end in `corners`, and whether it is on the surface.
+    faces: Vec<(usize, bool)>,
+    /// Each cell's end in `faces`.
+    cells: Vec<usize>,
+}
+
+impl PolyMesh {
+    /// Adds a convex cell bounded by `faces`, each a loop of vertex
+    /// indices with whether it is on the surface.
+    pub fn push_cell<'a>(&mut self, faces: impl IntoIterator<Item = (&'a [u32], bool)>) {
+        for (corners, surface) in faces {
+            self.corners.extend_from_slice(corners);
+            self.faces.push((self.corners.len(), surface));
+        }
+        self.cells.push(self.faces.len());
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// The faces of `cell`, each with whether it is on the surface.
+    pub fn cell_faces(&self, cell: usize) -> impl Iterator<Item = (&[u32], bool)> + '_ {
+        let first = match cell {
+            0 => 0,
+            _ => self.cells[cell - 1],
+        };
+        (first..self.cells[cell]).map(|face| self.face(face))
+    }
+
+    fn face(&self, face: usize) -> (&[u32], bool) {
+        let start = match face {
+            0 => 0,
+            _ => self.faces[face - 1].0,
+        };
+        let (end, surface) = self.faces[face];
+        (&self.corners[start..end], surface)
+    }
+
+    /// The volume of `cell`, from the tetrahedra its face fans make with
+    /// the origin.
+    pub fn cell_volume(&self, cell: usize) -> f64 {
+        self.cell_faces(cell)
+            .flat_map(|(corners, _)| self.fan(corners))
+            .map(|[a, b, c]| dot(a, cross(b, c)) / 6.0)
+            .sum()
+    }
+
+    pub fn volume(&self) -> f64 {
+        (0..self.cell_count())
+            .map(|cell| self.cell_volume(cell))
+            .sum()
+    }
+
+    /// The area of the faces on the surface.
+    pub fn area(&self) -> f64 {
+        (0..self.faces.len())
+            .map(|face| self.face(face))
+            .filter(|&(_, surface)| surface)
+            .flat_map(|(corners, _)| self.fan(corners))
+            .map(|[a, b, c]| {
+                let normal = cross(sub(b, a), sub(c, a));
+                dot(normal, normal).sqrt() / 2.0
+            })
+            .sum()
+    }
+
+    /// The triangles fanning out from a convex face's first corner.
+    fn fan<'a>(&'a self, corners: &'a [u32]) -> impl Iterator<Item = [[f64; 3]; 3]> + 'a {
+        let vertex = |i: u32| self.vertices[i as usize];
+        (1..corners.len().saturating_sub(1))
+            .map(move |i| [corners[0], corners[i], corners[i + 1]].map(vertex))
+    }
+
+    /// The surface faces as a triangle mesh, fanned from their first
+    /// corners, keeping only the vertices they use in their order here.
+    pub fn surface(&self) -> Mesh {
+        let mut index = vec![u32::MAX; self.vertices.len()];
+        let mut triangles = Vec::new();
+        for face in 0..self.faces.len() {
+            let (corners, surface) = self.face(face);
+            if !surface {
+                continue;
+            }
+            for &corner in corners {
+                index[corner as usize] = 0;
+            }
+            for i in 1..corners.len().saturating_sub(1) {
+                triangles.push([corners[0], corners[i], corners[i + 1]]);
+            }
+        }
+        let mut vertices = Vec::new();
+        for (old, new) in index.iter_mut().enumerate() {
+            if *new == 0 {
+                *new = vertices.len() as u32;
+                vertices.push(self.vertices[old]);
+            }
+        }
+        Mesh {
+            vertices,
+            faces: Polygons::Triangles(
+                triangles
+                    .into_iter()
+                    .map(|triangle| triangle.map(|corner| index[corner as usize]))
+                    .collect(),
+            ),
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{cross, dot, normalize, sub, Mesh, Polygons};
+
+/// Limits for [`simplify`]. Simplification stops at whichever is reached
+/// first; with neither set the mesh is left alone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Simplify {
+    /// Stop once the mesh has at most this many triangles.
+    #[serde(default)]
+    pub max_triangles: Option<usize>,
+    /// Never move the surface further than this from any plane of the
+    /// original faces, in lattice cells.
+    #[serde(default)]
+    pub max_error: Option<f64>,
+}
+
+/// What [`simplify`] did.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SimplifyReport {
+    pub triangles_before: usize,
+    pub triangles_after: usize,
+    /// Upper bound on the distance any vertex moved from the planes of the
+    /// faces it replaced.
+    pub error: f64,
+}
+
+/// Reduces the triangle count by quadric-error edge collapses (Garland and
+/// Heckbert), cheapest first. Quads are triangulated first.
+///
+/// Collapses that would fold a triangle over or pinch the surface into a
+/// non-manifold edge are skipped, so a watertight mesh stays watertight.
+/// Coplanar regions, which make up most of a voxel surface, collapse at
+/// zero error. Topology is preserved, so a sponge's tunnels put a floor
+/// under how far `max_triangles` can be met.
+pub fn simplify(mesh: &mut Mesh, limits: &Simplify) -> SimplifyReport {
+    let triangles = mesh.triangles();
+    let mut report = SimplifyReport {
+        triangles_before: triangles.len(),
+        triangles_after: triangles.len(),
+        error: 0.0,
+    };
+    if limits.max_triangles.is_none() && limits.max_error.is_none() {
+        return report;
+    }
+    let target = limits.max_triangles.unwrap_or(0);
+    // The quadric cost is a sum of squared plane distances, so bounding it
+    // by the squared error bounds the distance to every plane.
+    let max_cost = limits.max_error.map_or(f64::INFINITY, |e| e * e);
+
+    let mut state = Collapser::new(&mesh.vertices, triangles);
+    let mut max_used: f64 = 0.0;
+    while state.live > target {
+        let Some(Reverse(candidate)) = state.heap.pop() else {
+            break;
+        };
+        if candidate.cost > max_cost {
+            break;
+        }
+        if !state.is_current(&candidate) {
+            continue;
+        }
+        if state.collapse(&candidate) {
+            max_used = max_used.max(candidate.cost);
+        }
+    }
+    report.error = max_used.sqrt();
+    report.triangles_after = state.live;
+    *mesh = state.into_mesh();
+    report
+}
+
+/// A symmetric 4×4 matrix stored as its upper triangle.
+type Quadric = [f64; 10];
+
+fn plane_quadric([a, b, c]: [f64; 3], d: f64) -> Quadric {
+    [
+        a * a,
+        a * b,
+        a * c,
+        a * d,
+        b * b,
+        b * c,
+        b * d,
+        c * c,
+        c * d,
+        d * d,
+    ]
+}
+
+fn add(q: &Quadric, r: &Quadric) -> Quadric {
+    std::array::from_fn(|i| q[i] + r[i])
+}
+
+/// The sum of squared distances from `p` to the planes in `q`.
+fn evaluate(q: &Quadric, [x, y, z]: [f64; 3]) -> f64 {
+    let cost = q[0] * x * x
+        + 2.0 * q[1] * x * y
+        + 2.0 * q[2] * x * z
+        + 2.0 * q[3] * x
+        + q[4] * y * y
+        + 2.0 * q[5] * y * z
+        + 2.0 * q[6] * y
+        + q[7] * z * z
+        + 2.0 * q[8] * z
+        + q[9];
+    cost.max(0.0)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
+    cost: f64,
+    keep: u32,
+    remove: u32,
+    position: [f64; 3],
+    versions: (u32, u32),
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost
+            .total_cmp(&other.cost)
+            .then((self.keep, self.remove).cmp(&(other.keep, other.remove)))
+    }
+}
+
+struct Collapser {
+    positions: Vec<[f64; 3]>,
+    quadrics: Vec<Quadric>,
+    /// Bumped whenever a vertex moves or is removed, invalidating queued
+    /// candidates that mention it.
+    versions: Vec<u32>,
+    removed: Vec<bool>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    /// Triangles around each vertex; may still list dead triangles.
+    around: Vec<Vec<usize>>,
+    heap: BinaryHeap<Reverse<Candidate>>,
+    live: usize,
+}
+
+impl Collapser {
+    fn new(vertices: &[[f64; 3]], triangles: Vec<[u32; 3]>) -> Self {
+        let mut quadrics = vec![[0.0; 10]; vertices.len()];
+        let mut around = vec![Vec::new(); vertices.len()];
+        for (index, triangle) in triangles.iter().enumerate() {
+            let [a, b, c] = triangle.map(|v| vertices[v as usize]);
+            let normal = normalize(cross(sub(b, a), sub(c, a)));
+            let q = plane_quadric(normal, -dot(normal, a));
+            for &v in triangle {
+                quadrics[v as usize] = add(&quadrics[v as usize], &q);
+                around[v as usize].push(index);
+            }
+        }
+        let mut state = Collapser {
+            positions: vertices.to_vec(),
+            quadrics,
+            versions: vec![0; vertices.len()],
+            removed: vec![false; vertices.len()],
+            alive: vec![true; triangles.len()],
+            live: triangles.len(),
+            triangles,
+            around,
+            heap: BinaryHeap::new(),
+        };
+        let mut edges = HashSet::new();
+        for triangle in &state.triangles {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        for (a, b) in edges {
+            state.push(a, b);
+        }
+        state
+    }
+
+    /// Queues the collapse of edge `a`–`b` at its cheapest of the two
+    /// endpoints and the midpoint. Endpoints keep a voxel surface on its
+    /// lattice; the midpoint helps on curved input.
+    fn push(&mut self, a: u32, b: u32) {
+        let q = add(&self.quadrics[a as usize], &self.quadrics[b as usize]);
+        let (pa, pb) = (self.positions[a as usize], self.positions[b as usize]);
+        let mid = std::array::from_fn(|i| (pa[i] + pb[i]) / 2.0);
+        let best = [(a, b, pa), (b, a, pb), (a, b, mid)]
+            .into_iter()
+            .map(|(keep, remove, position)| (evaluate(&q, position), keep, remove, position))
+            .min_by(|x, y| x.0.total_cmp(&y.0))
+            .unwrap();
+        let (cost, keep, remove, position) = best;
+        self.heap.push(Reverse(Candidate {
+            cost,
+            keep,
+            remove,
+            position,
+            versions: (self.versions[keep as usize], self.versions[remove as usize]),
+        }));
+    }
+
+    fn is_current(&self, candidate: &Candidate) -> bool {
+        let (keep, remove) = (candidate.keep as usize, candidate.remove as usize);
+        !self.removed[keep]
+            && !self.removed[remove]
+            && candidate.versions == (self.versions[keep], self.versions[remove])
+    }
+
+    fn neighbours(&self, v: u32) -> HashSet<u32> {
+        self.around[v as usize]
+            .iter()
+            .filter(|&&t| self.alive[t])
+            .flat_map(|&t| self.triangles[t])
+            .filter(|&w| w != v)
+            .collect()
+    }
+
+    /// Collapses `remove` into `keep`, returning false if the collapse
+    /// would damage the surface.
+    fn collapse(&mut self, candidate: &Candidate) -> bool {
+        let Candidate {
+            keep,
+            remove,
+            position,
+            ..
+        } = *candidate;
+        let shared: Vec<usize> = self.around[remove as usize]
+            .iter()
+            .copied()
+            .filter(|&t| self.alive[t] && self.triangles[t].contains(&keep))
+            .collect();
+        // The link condition: the endpoints may only share the neighbours
+        // opposite the edge, or the collapse pinches the surface.
+        let common = self
+            .neighbours(keep)
+            .intersection(&self.neighbours(remove))
+            .count();
+        if shared.is_empty() || common != shared.len() {
+            return false;
+        }
+        for v in [keep, remove] {
+            for &t in &self.around[v as usize] {
+                if !self.alive[t] || shared.contains(&t) {
+                    continue;
+                }
+                if self.folds(self.triangles[t], v, position) {
+                    return false;
+                }
+            }
+        }
+
+        for t in shared {
+            self.alive[t] = false;
+            self.live -= 1;
+        }
+        let moved = std::mem::take(&mut self.around[remove as usize]);
+        for &t in &moved {
+            if self.alive[t] {
+                for v in &mut self.triangles[t] {
+                    if *v == remove {
+                        *v = keep;
+                    }
+                }
+                self.around[keep as usize].push(t);
+            }
+        }
+        let alive = &self.alive;
+        self.around[keep as usize].retain(|&t| alive[t]);
+        self.removed[remove as usize] = true;
+        self.versions[remove as usize] += 1;
+        self.versions[keep as usize] += 1;
+        self.positions[keep as usize] = position;
+        self.quadrics[keep as usize] = add(
+            &self.quadrics[keep as usize],
+            &self.quadrics[remove as usize],
+        );
+        for neighbour in self.neighbours(keep) {
+            self.push(keep, neighbour);
+        }
+        true
+    }
+
+    /// Whether moving `v` of `triangle` to `position` turns the triangle
+    /// over or collapses it to a line.
+    fn folds(&self, triangle: [u32; 3], v: u32, position: [f64; 3]) -> bool {
+        let corners = triangle.map(|w| self.positions[w as usize]);
+        let moved = triangle.map(|w| {
+            if w == v {
+                position
+            } else {
+                self.positions[w as usize]
+            }
+        });
+        let normal = |[a, b, c]: [[f64; 3]; 3]| cross(sub(b, a), sub(c, a));
+        let (before, after) = (normal(corners), normal(moved));
+        dot(after, after) == 0.0 || dot(normalize(before), normalize(after)) < 0.2
+    }
+
+    /// The surviving triangles over only the vertices they use.
+    fn into_mesh(self) -> Mesh {
+        let mut index = vec![u32::MAX; self.positions.len()];
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::with_capacity(self.live);
+        for (triangle, alive) in self.triangles.iter().zip(&self.alive) {
+            if !alive {
+                continue;
+            }
+            triangles.push(triangle.map(|v| {
+                let slot = &mut index[v as usize];
+                if *slot == u32::MAX {
+                    *slot = vertices.len() as u32;
+                    vertices.push(self.positions[v as usize]);
+                }
+                *slot
+            }));
+        }
+        Mesh {
+            vertices,
+            faces: Polygons::Triangles(triangles),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use serde::Serialize;
+
+use super::{cross, dot, sub, Mesh, Polygons};
+
+/// Problems found in a mesh by [`validate`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ValidationReport {
+    /// Faces that repeat a vertex or have zero area.
+    pub degenerate_faces: usize,
+    /// Faces using the same vertices as an earlier face.
+    pub duplicate_faces: usize,
+    /// Edges used by only one face.
+    pub boundary_edges: usize,
+    /// Connected loops of boundary edges.
+    pub holes: usize,
+    /// Edges shared by more than two faces.
+    pub non_manifold_edges: usize,
+    /// Manifold edges whose two faces traverse it in the same direction.
+    pub inconsistent_edges: usize,
+    /// Enclosed volume; negative when a closed mesh's faces point inwards.
+    pub signed_volume: f64,
+}
+
+impl ValidationReport {
+    /// Whether the mesh is a closed, consistently outward-wound manifold
+    /// that slicers will accept without repair.
+    pub fn is_watertight(&self) -> bool {
+        self.degenerate_faces == 0
+            && self.duplicate_faces == 0
+            && self.boundary_edges == 0
+            && self.non_manifold_edges == 0
+            && self.inconsistent_edges == 0
+            && self.signed_volume > 0.0
+    }
+}
+
+/// What [`repair`] changed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RepairReport {
+    pub removed_degenerate: usize,
+    pub removed_duplicates: usize,
+    pub flipped_faces: usize,
+}
+
+/// Checks a mesh for the defects that make slicers reject it.
+pub fn validate(mesh: &Mesh) -> ValidationReport {
+    match &mesh.faces {
+        Polygons::Quads(quads) => validate_faces(&mesh.vertices, quads),
+        Polygons::Triangles(triangles) => validate_faces(&mesh.vertices, triangles),
+    }
+}
+
+/// Removes degenerate and duplicate faces, then orients every connected
+/// patch consistently, outwards when the patch is closed. Holes and
+/// non-manifold edges are left for the caller to judge.
+pub fn repair(mesh: &mut Mesh) -> RepairReport {
+    match &mut mesh.faces {
+        Polygons::Quads(quads) => repair_faces(&mesh.vertices, quads),
+        Polygons::Triangles(triangles) => repair_faces(&mesh.vertices, triangles),
+    }
+}
+
+fn validate_faces<const N: usize>(vertices: &[[f64; 3]], faces: &[[u32; N]]) -> ValidationReport {
+    let mut report = ValidationReport {
+        degenerate_faces: faces.iter().filter(|f| is_degenerate(vertices, f)).count(),
+        signed_volume: faces.iter().map(|f| signed_volume(vertices, f)).sum(),
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    report.duplicate_faces = faces.iter().filter(|f| !seen.insert(sorted(f))).count();
+
+    let edges = edge_uses(faces);
+    let mut boundary = Vec::new();
+    for (&(a, b), uses) in &edges {
+        match uses.len() {
+            1 => boundary.push((a, b)),
+            2 if uses[0].1 == uses[1].1 => report.inconsistent_edges += 1,
+            2 => {}
+            _ => report.non_manifold_edges += 1,
+        }
+    }
+    report.boundary_edges = boundary.len();
+    report.holes = count_loops(&boundary);
+    report
+}
+
+fn repair_faces<const N: usize>(vertices: &[[f64; 3]], faces: &mut Vec<[u32; N]>) -> RepairReport {
+    let mut report = RepairReport::default();
+    let before = faces.len();
+    faces.retain(|f| !is_degenerate(vertices, f));
+    report.removed_degenerate = before - faces.len();
+
+    let before = faces.len();
+    let mut seen = HashSet::new();
+    faces.retain(|f| seen.insert(sorted(f)));
+    report.removed_duplicates = before - faces.len();
+
+    // Breadth-first over faces joined by manifold edges, flipping each
+    // neighbour that traverses the shared edge in the same direction.
+    let edges = edge_uses(faces);
+    let mut flipped = vec![false; faces.len()];
+    let mut visited = vec![false; faces.len()];
+    for seed in 0..faces.len() {
+        if visited[seed] {
+            continue;
+        }
+        visited[seed] = true;
+        let mut patch = vec![seed];
+        let mut queue = VecDeque::from([seed]);
+        while let Some(face) = queue.pop_front() {
+            for (key, forward) in face_edges(&faces[face]) {
+                let uses = &edges[&key];
+                if uses.len() != 2 {
+                    continue;
+                }
+                let &(other, other_forward) = uses.iter().find(|(f, _)| *f != face).unwrap();
+                if visited[other] {
+                    continue;
+                }
+                visited[other] = true;
+                // `other` is unvisited, so it has not been flipped yet.
+                let here = forward != flipped[face];
+                let there = other_forward;
+                flipped[other] = here == there;
+                patch.push(other);
+                queue.push_back(other);
+            }
+        }
+        let volume: f64 = patch
+            .iter()
+            .map(|&f| {
+                let v = signed_volume(vertices, &faces[f]);
+                if flipped[f] {
+                    -v
+                } else {
+                    v
+                }
+            })
+            .sum();
+        if volume < 0.0 {
+            for &f in &patch {
+                flipped[f] = !flipped[f];
+            }
+        }
+    }
+    for (face, flip) in faces.iter_mut().zip(flipped) {
+        if flip {
+            face.reverse();
+            report.flipped_faces += 1;
+        }
+    }
+    report
+}
+
+/// Every use of each undirected edge: the face using it and whether the
+/// face traverses it from the smaller vertex index to the larger.
+fn edge_uses<const N: usize>(faces: &[[u32; N]]) -> HashMap<(u32, u32), Vec<(usize, bool)>> {
+    let mut edges: HashMap<_, Vec<_>> = HashMap::new();
+    for (index, face) in faces.iter().enumerate() {
+        for (key, forward) in face_edges(face) {
+            edges.entry(key).or_default().push((index, forward));
+        }
+    }
+    edges
+}
+
+fn face_edges<const N: usize>(face: &[u32; N]) -> impl Iterator<Item = ((u32, u32), bool)> + '_ {
+    (0..N).map(move |i| {
+        let (a, b) = (face[i], face[(i + 1) % N]);
+        ((a.min(b), a.max(b)), a < b)
+    })
+}
+
+fn sorted<const N: usize>(face: &[u32; N]) -> [u32; N] {
+    let mut key = *face;
+    key.sort_unstable();
+    key
+}
+
+fn is_degenerate<const N: usize>(vertices: &[[f64; 3]], face: &[u32; N]) -> bool {
+    let key = sorted(face);
+    if key.windows(2).any(|w| w[0] == w[1]) {
+        return true;
+    }
+    let p = |i: usize| vertices[face[i] as usize];
+    let area: [f64; 3] = (1..N - 1)
+        .map(|i| cross(sub(p(i), p(0)), sub(p(i + 1), p(0))))
+        .fold([0.0; 3], |acc, c| {
+            [acc[0] + c[0], acc[1] + c[1], acc[2] + c[2]]
+        });
+    dot(area, area) == 0.0
+}
+
+/// Volume of the cone from the origin to the face, fan-triangulated.
+fn signed_volume<const N: usize>(vertices: &[[f64; 3]], face: &[u32; N]) -> f64 {
+    let p = |i: usize| vertices[face[i] as usize];
+    (1..N - 1)
+        .map(|i| dot(p(0), cross(p(i), p(i + 1))) / 6.0)
+        .sum()
+}
+
+/// Number of connected components formed by the given edges.
+fn count_loops(edges: &[(u32, u32)]) -> usize {
+    let mut parent: HashMap<u32, u32> = HashMap::new();
+    fn find(parent: &mut HashMap<u32, u32>, v: u32) -> u32 {
+        let p = *parent.entry(v).or_insert(v);
+        if p == v {
+            return v;
+        }
+        let root = find(parent, p);
+        parent.insert(v, root);
+        root
+    }
+    for &(a, b) in edges {
+        let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+        if ra != rb {
+            parent.insert(ra, rb);
+        }
+    }
+    let vertices: Vec<u32> = parent.keys().copied().collect();
+    vertices
+        .into_iter()
+        .filter(|&v| find(&mut parent, v) == v)
+        .count()
+}
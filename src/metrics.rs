@@ -0,0 +1,109 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// A monotonically increasing count.
+#[derive(Debug, Default)]
+pub struct Counter(AtomicU64);
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.0.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A distribution of observations over fixed cumulative buckets.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: Vec<f64>,
+    state: Mutex<HistogramState>,
+}
+
+#[derive(Debug)]
+struct HistogramState {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    /// A histogram with the given ascending upper bucket bounds; an implicit
+    /// `+Inf` bucket catches everything else.
+    pub fn new(bounds: &[f64]) -> Self {
+        Histogram {
+            bounds: bounds.to_vec(),
+            state: Mutex::new(HistogramState {
+                buckets: vec![0; bounds.len()],
+                sum: 0.0,
+                count: 0,
+            }),
+        }
+    }
+
+    pub fn observe(&self, value: f64) {
+        let mut state = self.state.lock().unwrap();
+        for (bucket, &bound) in state.buckets.iter_mut().zip(&self.bounds) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        state.sum += value;
+        state.count += 1;
+    }
+}
+
+/// Renders metrics in the Prometheus text exposition format.
+#[derive(Default)]
+pub struct Exposition(String);
+
+impl Exposition {
+    pub fn counter(&mut self, name: &str, help: &str, counter: &Counter) {
+        self.header(name, help, "counter");
+        let _ = writeln!(self.0, "{name} {}", counter.get());
+    }
+
+    pub fn gauge(&mut self, name: &str, help: &str, value: f64) {
+        self.header(name, help, "gauge");
+        let _ = writeln!(self.0, "{name} {value}");
+    }
+
+    pub fn histogram(&mut self, name: &str, help: &str, histogram: &Histogram) {
+        self.header(name, help, "histogram");
+        let state = histogram.state.lock().unwrap();
+        for (bound, count) in histogram.bounds.iter().zip(&state.buckets) {
+            let _ = writeln!(self.0, "{name}_bucket{{le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(self.0, "{name}_bucket{{le=\"+Inf\"}} {}", state.count);
+        let _ = writeln!(self.0, "{name}_sum {}", state.sum);
+        let _ = writeln!(self.0, "{name}_count {}", state.count);
+    }
+
+    pub fn finish(self) -> String {
+        self.0
+    }
+
+    fn header(&mut self, name: &str, help: &str, kind: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+}
+
+/// Resident set size of this process, where the platform exposes it. Read
+/// in kilobytes from `VmRSS`, as `statm` counts pages of a size that
+/// differs between platforms.
+pub fn resident_memory_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let rss = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?;
+    let kilobytes: u64 = rss.trim().strip_suffix("kB")?.trim_end().parse().ok()?;
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,153 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::image::layer_path;
+use crate::lattice::Lattice;
+use crate::store;
+
+/// Periodic `.vti` snapshots of a lattice while it is generated, so long
+/// generations can be watched in ParaView as they run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Monitor {
+    /// Where snapshots go. A `{i}` is replaced by the snapshot number,
+    /// otherwise it is appended to the file name; ParaView opens the
+    /// numbered files as one time series.
+    pub path: PathBuf,
+    /// Seconds between snapshots. The finished lattice is always written.
+    #[serde(default = "default_interval")]
+    pub interval: f64,
+}
+
+fn default_interval() -> f64 {
+    10.0
+}
+
+/// Snapshot numbers are padded to four digits, so a series sorts in order
+/// up to 10000 snapshots.
+const SERIES_LENGTH: usize = 10_000;
+
+impl Monitor {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.interval > 0.0 && self.interval.is_finite()) {
+            return Err(Error::InvalidJob(
+                "monitor interval must be positive".into(),
+            ));
+        }
+        match self.path.extension().and_then(|e| e.to_str()) {
+            Some("vti") => Ok(()),
+            _ => Err(Error::InvalidJob(format!(
+                "monitor snapshots are `.vti` files, not `{}`",
+                self.path.display()
+            ))),
+        }
+    }
+
+    /// Starts a series of snapshots of one generation.
+    pub fn start(&self) -> Snapshots<'_> {
+        Snapshots {
+            monitor: self,
+            interval: Duration::from_secs_f64(self.interval),
+            last: Instant::now(),
+            count: 0,
+        }
+    }
+}
+
+/// The snapshots of one generation so far.
+pub struct Snapshots<'a> {
+    monitor: &'a Monitor,
+    interval: Duration,
+    last: Instant,
+    count: usize,
+}
+
+impl Snapshots<'_> {
+    /// Writes a snapshot of `lattice`, of which the first `done` cells are
+    /// generated, once the interval has passed since the last snapshot or
+    /// when the lattice is complete.
+    pub fn observe<const D: usize>(&mut self, lattice: &Lattice<D>, done: usize) -> Result<()> {
+        if done < lattice.len() && self.last.elapsed() < self.interval {
+            return Ok(());
+        }
+        let path = layer_path(&self.monitor.path, self.count, SERIES_LENGTH);
+        write_snapshot(lattice, done, &path)?;
+        tracing::info!(path = %path.display(), done, cells = lattice.len(), "snapshot written");
+        self.count += 1;
+        self.last = Instant::now();
+        Ok(())
+    }
+}
+
+/// Writes the snapshot through [`store::create`], so ParaView never reads
+/// half a file.
+fn write_snapshot<const D: usize>(lattice: &Lattice<D>, done: usize, path: &Path) -> Result<()> {
+    let mut out = store::create(path)?;
+    match write_vti(lattice, done, &mut out) {
+        Ok(()) => out.finish(),
+        Err(e) => {
+            out.abort();
+            Err(e)
+        }
+    }
+}
+
+/// Writes a VTK XML image with one `occupancy` byte per cell, 1 where
+/// filled, and the fraction of cells generated as the `progress` field.
+/// 4D lattices show their cells filled in any w slice.
+pub fn write_vti<const D: usize>(
+    lattice: &Lattice<D>,
+    done: usize,
+    out: &mut impl Write,
+) -> Result<()> {
+    let shape = lattice.shape();
+    let extent = |axis: usize| shape.get(axis).copied().unwrap_or(1);
+    let [nx, ny, nz] = [0, 1, 2].map(extent);
+    let volume = nx * ny * nz;
+    // Flat indices run x fastest, so the later axes repeat the volume.
+    let mut occupancy = vec![0u8; volume];
+    for index in 0..lattice.len() {
+        if lattice.get(lattice.position(index)) {
+            occupancy[index % volume] = 1;
+        }
+    }
+    let progress = match lattice.len() {
+        0 => 1.0,
+        len => done as f64 / len as f64,
+    };
+    writeln!(out, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        out,
+        r#"<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" header_type="UInt64">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <ImageData WholeExtent="0 {nx} 0 {ny} 0 {nz}" Origin="0 0 0" Spacing="1 1 1">"#
+    )?;
+    writeln!(out, "    <FieldData>")?;
+    writeln!(
+        out,
+        r#"      <DataArray type="Float64" Name="progress" NumberOfTuples="1" format="ascii">{progress}</DataArray>"#
+    )?;
+    writeln!(out, "    </FieldData>")?;
+    writeln!(out, r#"    <Piece Extent="0 {nx} 0 {ny} 0 {nz}">"#)?;
+    writeln!(out, r#"      <CellData Scalars="occupancy">"#)?;
+    writeln!(
+        out,
+        r#"        <DataArray type="UInt8" Name="occupancy" format="appended" offset="0"/>"#
+    )?;
+    writeln!(out, "      </CellData>")?;
+    writeln!(out, "    </Piece>")?;
+    writeln!(out, "  </ImageData>")?;
+    // Raw appended data: an underscore, then the array's byte count.
+    write!(out, r#"  <AppendedData encoding="raw">_"#)?;
+    out.write_all(&(volume as u64).to_le_bytes())?;
+    out.write_all(&occupancy)?;
+    writeln!(out, "\n  </AppendedData>")?;
+    writeln!(out, "</VTKFile>")?;
+    Ok(())
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::{Boundary, Lattice};
+
+/// A morphological operation on a lattice's filled cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// Fills every cell the element reaches from a filled cell, thickening
+    /// struts and closing narrow gaps.
+    Dilate,
+    /// Keeps only the filled cells the element fits around, thinning
+    /// struts and removing ones thinner than it.
+    Erode,
+    /// Erodes then dilates: removes features smaller than the element and
+    /// rounds off convex corners.
+    Open,
+    /// Dilates then erodes: fills holes and cracks smaller than the element
+    /// and rounds off concave corners.
+    Close,
+}
+
+/// Shape of a structuring element, all centred on the cell operated on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Element {
+    /// The cells within `radius` face steps: an octahedron.
+    Cross,
+    /// The cells within `radius` along every axis.
+    Cube,
+    /// The cells whose centres lie within `radius`.
+    #[default]
+    Ball,
+}
+
+/// One step of morphology, applied to each lattice before it is meshed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Morphology {
+    pub op: Operation,
+    #[serde(default)]
+    pub element: Element,
+    /// Radius of the element, in cells.
+    #[serde(default = "default_radius")]
+    pub radius: u32,
+}
+
+fn default_radius() -> u32 {
+    1
+}
+
+/// Cells visited between cancellation checks.
+const CANCEL_INTERVAL: usize = 1 << 14;
+
+impl Morphology {
+    pub fn new(op: Operation, element: Element, radius: u32) -> Self {
+        Morphology {
+            op,
+            element,
+            radius,
+        }
+    }
+
+    /// Applies the operation, reading cells outside the lattice under
+    /// `boundary`. With an open boundary, solid on the outer walls erodes
+    /// and dilation is clipped to the lattice, though a closing never
+    /// loses a filled cell.
+    #[tracing::instrument(name = "morphology", skip_all, fields(op = ?self.op, element = ?self.element, radius = self.radius))]
+    pub fn apply<const D: usize>(
+        &self,
+        lattice: &Lattice<D>,
+        boundary: Boundary,
+        cancel: &CancelToken,
+    ) -> Result<Lattice<D>> {
+        if self.radius == 0 {
+            return Err(Error::InvalidJob(
+                "structuring element radius must be at least 1".into(),
+            ));
+        }
+        let element = self.offsets::<D>();
+        match self.op {
+            Operation::Dilate => dilate(lattice, &element, boundary, cancel),
+            Operation::Erode => erode(lattice, &element, boundary, cancel),
+            Operation::Open => dilate(
+                &erode(lattice, &element, boundary, cancel)?,
+                &element,
+                boundary,
+                cancel,
+            ),
+            Operation::Close if boundary == Boundary::Open => {
+                // Close with room around the lattice, so the erosion does
+                // not eat into walls that the clipped dilation left bare.
+                let margin = self.radius as usize;
+                let padded =
+                    Lattice::from_fn(lattice.shape().map(|n| n + 2 * margin), cancel, |p| {
+                        lattice.get_signed(p.map(|c| c as i64 - margin as i64))
+                    })?;
+                let closed = erode(
+                    &dilate(&padded, &element, boundary, cancel)?,
+                    &element,
+                    boundary,
+                    cancel,
+                )?;
+                Lattice::from_fn(lattice.shape(), cancel, |p| {
+                    closed.get(p.map(|c| c + margin))
+                })
+            }
+            Operation::Close => erode(
+                &dilate(lattice, &element, boundary, cancel)?,
+                &element,
+                boundary,
+                cancel,
+            ),
+        }
+    }
+
+    /// The element's cells relative to its centre.
+    pub fn offsets<const D: usize>(&self) -> Vec<[i64; D]> {
+        let r = self.radius as i64;
+        let width = 2 * r + 1;
+        (0..width.pow(D as u32))
+            .map(|mut index| {
+                std::array::from_fn(|_| {
+                    let c = index % width - r;
+                    index /= width;
+                    c
+                })
+            })
+            .filter(|offset: &[i64; D]| match self.element {
+                Element::Cross => offset.iter().map(|c| c.abs()).sum::<i64>() <= r,
+                Element::Cube => true,
+                Element::Ball => offset.iter().map(|c| c * c).sum::<i64>() <= r * r,
+            })
+            .collect()
+    }
+}
+
+/// Sets every cell that `element` placed on a filled cell covers.
+pub fn dilate<const D: usize>(
+    lattice: &Lattice<D>,
+    element: &[[i64; D]],
+    boundary: Boundary,
+    cancel: &CancelToken,
+) -> Result<Lattice<D>> {
+    let mut out = Lattice::new(lattice.shape());
+    for (i, p) in lattice.iter().enumerate() {
+        if i % CANCEL_INTERVAL == 0 {
+            cancel.check()?;
+        }
+        let p = p.map(|c| c as i64);
+        for offset in element {
+            let q = std::array::from_fn(|axis| p[axis] + offset[axis]);
+            if let Some(q) = lattice.wrap(q, boundary) {
+                out.set(q, true);
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Keeps the filled cells on which every cell of `element` is filled.
+pub fn erode<const D: usize>(
+    lattice: &Lattice<D>,
+    element: &[[i64; D]],
+    boundary: Boundary,
+    cancel: &CancelToken,
+) -> Result<Lattice<D>> {
+    Lattice::from_fn(lattice.shape(), cancel, |p| {
+        if !lattice.get(p) {
+            return false;
+        }
+        let p = p.map(|c| c as i64);
+        element.iter().all(|offset| {
+            let q = std::array::from_fn(|axis| p[axis] + offset[axis]);
+            lattice.get_bounded(q, boundary)
+        })
+    })
+}
@@ -0,0 +1,188 @@
+//! Rich display in Rust Jupyter notebooks run by
+//! [evcxr](https://github.com/evcxr/evcxr): a lattice or mesh left as a
+//! cell's value shows an inline render beside a summary table, and an
+//! analysis a table of its measures.
+//!
+//! evcxr calls a value's `evcxr_display` method, if it has one, and shows
+//! what it prints between `EVCXR_BEGIN_CONTENT <mime type>` and
+//! `EVCXR_END_CONTENT`; without the `evcxr` feature the values fall back to
+//! their `Debug` output.
+//!
+//! ```text
+//! :dep fractal_slicer_4_d = { path = "…", features = ["evcxr"] }
+//! use fractal_slicer_4_d::{lattice::Lattice4, rule::Rule};
+//! let sponge = Lattice4::generate(&Rule::menger(4), 2);
+//! sponge.slice_w(4)
+//! ```
+
+use std::fmt::Write;
+
+use crate::analysis::Analysis;
+use crate::cancel::CancelToken;
+use crate::lattice::{Lattice3, Lattice4};
+use crate::mesh::{Mesh, Polygons, Scalar};
+use crate::render::{render, render_mesh, Image, Scene};
+
+/// Size of inline renders, in pixels; small enough to keep notebooks light.
+const WIDTH: usize = 480;
+const HEIGHT: usize = 360;
+
+impl Lattice3 {
+    /// Shows the lattice in an evcxr notebook: a render and its shape and
+    /// filled cells.
+    pub fn evcxr_display(&self) {
+        let [x, y, z] = self.shape();
+        show(
+            render(self, None, &scene(), &CancelToken::new()).ok(),
+            &[
+                ("shape", format!("{x} × {y} × {z}")),
+                ("filled cells", self.count().to_string()),
+                ("fill", fraction(self.count(), self.len())),
+            ],
+        );
+    }
+}
+
+impl Lattice4 {
+    /// Shows the hypersponge in an evcxr notebook: a render of its middle
+    /// w slice and its shape and filled cells.
+    pub fn evcxr_display(&self) {
+        let [x, y, z, w] = self.shape();
+        let slice = (w > 0).then(|| self.slice_w(w / 2));
+        show(
+            slice
+                .as_ref()
+                .and_then(|slice| render(slice, None, &scene(), &CancelToken::new()).ok()),
+            &[
+                ("shape", format!("{x} × {y} × {z} × {w}")),
+                ("filled cells", self.count().to_string()),
+                ("fill", fraction(self.count(), self.len())),
+                ("rendered", format!("slice w = {}", w / 2)),
+            ],
+        );
+    }
+}
+
+impl<S: Scalar> Mesh<S> {
+    /// Shows the mesh in an evcxr notebook: a render and its size.
+    pub fn evcxr_display(&self) {
+        let [x, y, z] = self.extent();
+        let faces = match self.faces {
+            Polygons::Quads(_) => "quads",
+            Polygons::Triangles(_) => "triangles",
+        };
+        let image = (self.face_count() > 0)
+            .then(|| self.clone().cast::<f64>())
+            .and_then(|mesh| render_mesh(&mesh, &scene(), &CancelToken::new()).ok());
+        show(
+            image,
+            &[
+                ("vertices", self.vertices.len().to_string()),
+                (faces, self.face_count().to_string()),
+                ("extent", format!("{x} × {y} × {z}")),
+            ],
+        );
+    }
+}
+
+impl Analysis {
+    /// Shows the analysis in an evcxr notebook as a table.
+    pub fn evcxr_display(&self) {
+        let cells = self.shape.iter().product();
+        let percolates: Vec<_> = ["x", "y", "z", "w"]
+            .iter()
+            .zip(&self.percolates)
+            .filter(|(_, &percolates)| percolates)
+            .map(|(axis, _)| *axis)
+            .collect();
+        show(
+            None,
+            &[
+                ("job", self.job.clone()),
+                ("shape", format!("{:?}", self.shape)),
+                ("porosity", format!("{:.4}", self.porosity)),
+                ("empty cells", fraction(self.empty_cells, cells)),
+                ("pores", self.pores.to_string()),
+                (
+                    "largest pore radius",
+                    format!("{:.2}", self.max_pore_radius),
+                ),
+                (
+                    "percolates along",
+                    if percolates.is_empty() {
+                        "none".into()
+                    } else {
+                        percolates.join(", ")
+                    },
+                ),
+            ],
+        );
+    }
+}
+
+/// The default scene at the notebook's image size.
+fn scene() -> Scene {
+    let mut scene = Scene::default();
+    scene.camera.width = WIDTH;
+    scene.camera.height = HEIGHT;
+    scene
+}
+
+fn fraction(count: usize, of: usize) -> String {
+    match of {
+        0 => count.to_string(),
+        _ => format!("{count} ({:.2}%)", 100.0 * count as f64 / of as f64),
+    }
+}
+
+/// Prints `rows` as an HTML table, beside `image` when there is one, for
+/// evcxr to show.
+fn show(image: Option<Image>, rows: &[(&str, String)]) {
+    let mut html = String::from("<div style=\"display: flex; gap: 1em; align-items: flex-start\">");
+    let mut png = Vec::new();
+    if let Some(image) = image.filter(|image| image.write_png(&mut png).is_ok()) {
+        let _ = write!(
+            html,
+            "<img width=\"{}\" height=\"{}\" src=\"data:image/png;base64,{}\">",
+            image.width,
+            image.height,
+            base64(&png)
+        );
+    }
+    html.push_str("<table>");
+    for (name, value) in rows {
+        let _ = write!(
+            html,
+            "<tr><th style=\"text-align: left\">{}</th><td>{}</td></tr>",
+            escape(name),
+            escape(value)
+        );
+    }
+    html.push_str("</table></div>");
+    println!("EVCXR_BEGIN_CONTENT text/html\n{html}\nEVCXR_END_CONTENT");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = chunk.iter().enumerate().fold(0u32, |word, (i, &byte)| {
+            word | (byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            out.push(if i <= chunk.len() {
+                ALPHABET[(word >> (18 - 6 * i) & 63) as usize] as char
+            } else {
+                '='
+            });
+        }
+    }
+    out
+}
@@ -0,0 +1,127 @@
+//! NUMA-aware generation for multi-socket machines, behind the `numa`
+//! feature: the lattice is split along its last axis by the rule's
+//! top-level subdivision branches, each NUMA node takes a run of branches
+//! in proportion to its cores, and threads pinned to the node fill them.
+//! The pages of a node's part of the lattice are first written, and so
+//! allocated, on that node, and its threads never reach across sockets.
+//!
+//! Nodes come from Linux's `/sys/devices/system/node`; elsewhere the
+//! machine is one node of all its cores.
+
+use std::ops::Range;
+
+use crate::error::Result;
+
+/// A NUMA node and the cores on it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub id: usize,
+    pub cpus: Vec<usize>,
+}
+
+/// The machine's NUMA nodes with cores.
+pub fn nodes() -> Result<Vec<Node>> {
+    check()?;
+    let mut nodes: Vec<Node> = std::fs::read_dir("/sys/devices/system/node")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let id = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("node")?
+                .parse()
+                .ok()?;
+            let cpus = parse_cpulist(&std::fs::read_to_string(entry.path().join("cpulist")).ok()?)?;
+            Some(Node { id, cpus })
+        })
+        .filter(|node| !node.cpus.is_empty())
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+    if nodes.is_empty() {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        nodes.push(Node {
+            id: 0,
+            cpus: (0..cores).collect(),
+        });
+    }
+    Ok(nodes)
+}
+
+/// Parses lists of cores such as `0-3,8-11`.
+fn parse_cpulist(text: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in text.trim().split(',').filter(|part| !part.is_empty()) {
+        match part.split_once('-') {
+            Some((first, last)) => cpus.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+/// Splits `words` words, `branches` equal runs of them, between `nodes`
+/// in proportion to their cores and then evenly between each node's
+/// cores: the words of each thread, and the node it runs on.
+pub fn partition(words: usize, branches: usize, nodes: &[Node]) -> Vec<(Range<usize>, usize)> {
+    let cores: usize = nodes.iter().map(|node| node.cpus.len()).sum();
+    let branch_start = |branch: usize| words * branch / branches.max(1);
+    let mut parts = Vec::new();
+    let mut cores_before = 0;
+    for (n, node) in nodes.iter().enumerate() {
+        let first = branches * cores_before / cores;
+        cores_before += node.cpus.len();
+        let last = branches * cores_before / cores;
+        let (start, end) = (branch_start(first), branch_start(last));
+        let threads = node.cpus.len().min(end - start);
+        for t in 0..threads {
+            let run =
+                start + (end - start) * t / threads..start + (end - start) * (t + 1) / threads;
+            parts.push((run, n));
+        }
+    }
+    parts
+}
+
+#[cfg(not(feature = "numa"))]
+fn check() -> Result<()> {
+    Err(crate::error::Error::InvalidJob(
+        "NUMA-aware generation needs the `numa` feature".into(),
+    ))
+}
+
+#[cfg(feature = "numa")]
+fn check() -> Result<()> {
+    Ok(())
+}
+
+/// Pins the calling thread to `node`'s cores, where the system allows.
+#[cfg(not(all(feature = "numa", target_os = "linux")))]
+pub(crate) fn pin(_node: &Node) {}
+
+/// Pins the calling thread to `node`'s cores, where the system allows.
+#[cfg(all(feature = "numa", target_os = "linux"))]
+pub(crate) fn pin(node: &Node) {
+    // SAFETY: the set is zeroed plain data, only written through libc's
+    // own macros, and outlives the call.
+    let pinned = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        // The set has room for the first `CPU_SETSIZE` cores only.
+        for &cpu in node
+            .cpus
+            .iter()
+            .filter(|&&cpu| cpu < libc::CPU_SETSIZE as usize)
+        {
+            libc::CPU_SET(cpu, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if pinned != 0 {
+        tracing::debug!(
+            node = node.id,
+            "cannot pin to the node: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+}
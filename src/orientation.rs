@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::mesh::{cross, dot, sub, Mesh};
+use crate::transform::{Affine, Axis, Transform};
+
+/// Settings for searching the print orientation that needs the least
+/// support.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Orient {
+    /// Steepest overhang printable without support, in degrees from
+    /// vertical.
+    #[serde(default = "default_max_overhang")]
+    pub max_overhang: f64,
+    /// Directions tried, spread evenly over the sphere, besides the
+    /// current up and straight down the largest faces.
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+}
+
+fn default_max_overhang() -> f64 {
+    45.0
+}
+
+fn default_samples() -> usize {
+    1000
+}
+
+impl Default for Orient {
+    fn default() -> Self {
+        Orient {
+            max_overhang: default_max_overhang(),
+            samples: default_samples(),
+        }
+    }
+}
+
+/// The orientation [`Orient::search`] chose for one mesh.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Orientation {
+    /// The w index of the slice oriented, for 4D jobs.
+    pub slice: Option<usize>,
+    /// The direction, before rotating, that ends up pointing along +z.
+    pub up: [f64; 3],
+    /// Rotations taking `up` to +z.
+    pub rotations: Vec<Transform>,
+    /// Area of faces that would need support.
+    pub overhang_area: f64,
+    /// The same, as the mesh was oriented before.
+    pub original_overhang_area: f64,
+    /// Area of faces resting on the build plate.
+    pub bed_area: f64,
+    pub total_area: f64,
+}
+
+impl Orientation {
+    /// The rotations as one transform.
+    pub fn affine(&self) -> Affine {
+        Affine::from_transforms(&self.rotations)
+    }
+}
+
+/// Faces sharing one normal.
+struct NormalGroup {
+    normal: [f64; 3],
+    area: f64,
+    triangles: Vec<[u32; 3]>,
+}
+
+/// Normals closer than this are grouped, and faces within this of the
+/// lowest point rest on the plate.
+const EPSILON: f64 = 1e-6;
+
+/// How many of the largest normals are tried as the down direction.
+const FACE_DOWN_CANDIDATES: usize = 64;
+
+impl Orient {
+    /// Tries every candidate up direction and keeps the one leaving the
+    /// least overhang area, then the most area on the plate, then the
+    /// one whose steepest face off the plate is furthest from
+    /// overhanging. A face overhangs when it faces down at more than
+    /// `max_overhang` from vertical and is not on the plate.
+    #[tracing::instrument(name = "orient", skip_all, fields(triangles = mesh.face_count()))]
+    pub fn search(
+        &self,
+        mesh: &Mesh,
+        slice: Option<usize>,
+        cancel: &CancelToken,
+    ) -> Result<Orientation> {
+        if !(0.0..90.0).contains(&self.max_overhang) {
+            return Err(Error::InvalidJob(
+                "max overhang must be at least 0 and under 90 degrees".into(),
+            ));
+        }
+        let groups = normal_groups(mesh);
+        let total_area = groups.iter().map(|g| g.area).sum();
+        let threshold = self.max_overhang.to_radians().sin();
+        let score = |up: [f64; 3]| {
+            let mut overhang = 0.0;
+            let mut bed = 0.0;
+            let mut steepest = f64::NEG_INFINITY;
+            for group in &groups {
+                let down = -dot(group.normal, up);
+                let resting = if down > 1.0 - EPSILON {
+                    resting_area(mesh, group, up)
+                } else {
+                    0.0
+                };
+                if resting < group.area {
+                    steepest = steepest.max(down);
+                }
+                if down > threshold {
+                    overhang += group.area - resting;
+                    bed += resting;
+                }
+            }
+            (overhang, bed, steepest)
+        };
+        let mut largest: Vec<&NormalGroup> = groups.iter().collect();
+        largest.sort_by(|a, b| b.area.total_cmp(&a.area));
+        largest.truncate(FACE_DOWN_CANDIDATES);
+        let candidates = largest
+            .iter()
+            .map(|g| g.normal.map(|c| -c))
+            .chain(fibonacci_sphere(self.samples));
+        let tolerance = EPSILON * f64::max(total_area, 1.0);
+        let mut best = [0.0, 0.0, 1.0];
+        let mut best_score = score(best);
+        let original = best_score.0;
+        for (i, up) in candidates.enumerate() {
+            if i % 64 == 0 {
+                cancel.check()?;
+            }
+            let candidate = score(up);
+            let (overhang, bed, steepest) = candidate;
+            let better = if (overhang - best_score.0).abs() > tolerance {
+                overhang < best_score.0
+            } else if (bed - best_score.1).abs() > tolerance {
+                bed > best_score.1
+            } else {
+                steepest < best_score.2 - EPSILON
+            };
+            if better {
+                best = up;
+                best_score = candidate;
+            }
+        }
+        Ok(Orientation {
+            slice,
+            up: best,
+            rotations: rotations_to_z(best),
+            overhang_area: best_score.0,
+            original_overhang_area: original,
+            bed_area: best_score.1,
+            total_area,
+        })
+    }
+}
+
+/// The mesh's triangles grouped by normal, with their areas.
+fn normal_groups(mesh: &Mesh) -> Vec<NormalGroup> {
+    let mut groups: Vec<NormalGroup> = Vec::new();
+    let mut lookup = HashMap::new();
+    for triangle in mesh.triangles() {
+        let [a, b, c] = triangle.map(|v| mesh.vertices[v as usize]);
+        let n = cross(sub(b, a), sub(c, a));
+        let length = dot(n, n).sqrt();
+        if length == 0.0 {
+            continue;
+        }
+        let normal = n.map(|c| c / length);
+        let key = normal.map(|c| (c / EPSILON).round() as i64);
+        let index = *lookup.entry(key).or_insert_with(|| {
+            groups.push(NormalGroup {
+                normal,
+                area: 0.0,
+                triangles: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        groups[index].area += length / 2.0;
+        groups[index].triangles.push(triangle);
+    }
+    groups
+}
+
+/// Area of the faces in `group` lying in the lowest plane along `up`.
+fn resting_area(mesh: &Mesh, group: &NormalGroup, up: [f64; 3]) -> f64 {
+    let lowest = mesh
+        .vertices
+        .iter()
+        .map(|&v| dot(v, up))
+        .fold(f64::INFINITY, f64::min);
+    group
+        .triangles
+        .iter()
+        .filter(|t| {
+            t.iter()
+                .all(|&v| dot(mesh.vertices[v as usize], up) <= lowest + EPSILON)
+        })
+        .map(|t| {
+            let [a, b, c] = t.map(|v| mesh.vertices[v as usize]);
+            let n = cross(sub(b, a), sub(c, a));
+            dot(n, n).sqrt() / 2.0
+        })
+        .sum()
+}
+
+/// `n` directions spread evenly over the unit sphere.
+fn fibonacci_sphere(n: usize) -> impl Iterator<Item = [f64; 3]> {
+    let golden = std::f64::consts::PI * (3.0 - 5f64.sqrt());
+    (0..n).map(move |i| {
+        let z = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+        let r = (1.0 - z * z).sqrt();
+        let (sin, cos) = (golden * i as f64).sin_cos();
+        [r * cos, r * sin, z]
+    })
+}
+
+/// A turn about z into the xz plane, then about y onto +z.
+fn rotations_to_z(up: [f64; 3]) -> Vec<Transform> {
+    let azimuth = up[1].atan2(up[0]).to_degrees();
+    let tilt = up[2].clamp(-1.0, 1.0).acos().to_degrees();
+    let mut rotations = Vec::new();
+    if tilt.abs() > EPSILON && azimuth.abs() > EPSILON {
+        rotations.push(Transform::Rotate {
+            axis: Axis::Z,
+            degrees: -azimuth,
+        });
+    }
+    if tilt.abs() > EPSILON {
+        rotations.push(Transform::Rotate {
+            axis: Axis::Y,
+            degrees: -tilt,
+        });
+    }
+    rotations
+}
@@ -0,0 +1,542 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::cancel::CancelToken;
+use crate::complex::{is_json, CellComplex};
+use crate::error::Result;
+use crate::export::{slab_layers, streaming_bytes, Format, Precision};
+use crate::import::{Import, ModelGrid};
+use crate::infill::Infill;
+use crate::job::{slice_path, Job};
+use crate::lattice::{Lattice3, Lattice4};
+use crate::lsystem::Surface;
+use crate::rule::{expected_cells, Rule};
+use crate::transform::Transform;
+
+/// What a job would do, worked out without generating it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Plan {
+    pub job: String,
+    pub rule: String,
+    /// Whether the job builds a surface fractal, whose levels and outputs
+    /// count triangles rather than cells.
+    pub surface: bool,
+    pub dims: usize,
+    pub depth: u32,
+    /// Lattice extent along each axis; empty for surfaces.
+    pub shape: Vec<usize>,
+    /// Cells surviving after each subdivision level, from level 1.
+    pub levels: Vec<u64>,
+    /// Size of the generated lattice's bitset or, out of core, of the
+    /// layers and vertex planes held at once; none for a section.
+    pub lattice_bytes: u64,
+    pub estimated_time: Duration,
+    pub transforms: Vec<Transform>,
+    pub outputs: Vec<PlannedOutput>,
+}
+
+/// One file the job would write.
+#[derive(Clone, Debug, Serialize)]
+pub struct PlannedOutput {
+    pub path: PathBuf,
+    pub slice: Option<usize>,
+    /// Cells written: exact for rules without morphology, otherwise an
+    /// upper bound.
+    pub cells: u64,
+    /// Upper bound on the file size, assuming every cell face is exposed;
+    /// unknown for a plugin's format.
+    pub max_bytes: Option<u64>,
+}
+
+impl Job {
+    /// Plans the job: per-level survivor counts from the rule, memory from
+    /// the lattice size, and a time estimate extrapolated from generating a
+    /// small instance of the same rule.
+    pub fn plan(&self) -> Result<Plan> {
+        self.validate()?;
+        if let Some(surface) = self.surface()? {
+            return self.plan_surface(&surface);
+        }
+        if let Some(import) = self.import()? {
+            return self.plan_model(import, "import".to_string(), |coarse, grid, cancel| {
+                coarse.voxelize(grid, cancel).map(drop)
+            });
+        }
+        if let Some(infill) = self.infill()? {
+            let rule = format!("infill({})", infill.rule);
+            return self.plan_model(&infill.import(), rule, |coarse, grid, cancel| {
+                let coarse = Infill {
+                    cell_size: coarse.cell_size,
+                    ..infill.clone()
+                };
+                coarse.generate(grid, self.depth, cancel).map(drop)
+            });
+        }
+        if let Some(set) = self.escape_time() {
+            return self.plan_sampled(set.sampling.resolution, |resolution, cancel| {
+                let mut coarse = set.clone();
+                coarse.sampling.resolution = resolution;
+                Ok(match self.dims {
+                    3 => coarse.voxelize::<3>(self.depth, cancel)?.len(),
+                    _ => coarse.voxelize::<4>(self.depth, cancel)?.len(),
+                } as u64)
+            });
+        }
+        if let Some(field) = self.distance_field()? {
+            return self.plan_sampled(field.sampling.resolution, |resolution, cancel| {
+                let mut coarse = field.clone();
+                coarse.sampling.resolution = resolution;
+                Ok(coarse.voxelize::<3>(self.depth, cancel)?.len() as u64)
+            });
+        }
+        if let Some(implicit) = self.implicit()? {
+            return self.plan_sampled(implicit.sampling.resolution, |resolution, cancel| {
+                let mut coarse = implicit.clone();
+                coarse.sampling.resolution = resolution;
+                Ok(match self.dims {
+                    3 => coarse.voxelize::<3>(self.depth, cancel)?.len(),
+                    _ => coarse.voxelize::<4>(self.depth, cancel)?.len(),
+                } as u64)
+            });
+        }
+        if let Some(script) = self.script()? {
+            let bases = script.bases(self.dims)?;
+            let max_base = *bases.iter().max().unwrap() as usize;
+            let side = max_base.saturating_pow(self.depth);
+            return self.plan_sampled(side, |coarse, cancel| {
+                // The deepest level whose lattice fits in the coarse one.
+                let depth = (0..=self.depth)
+                    .take_while(|&d| max_base.saturating_pow(d) <= coarse)
+                    .last()
+                    .unwrap_or(0);
+                Ok(match self.dims {
+                    3 => script.generate::<3>(depth, cancel)?.len(),
+                    _ => script.generate::<4>(depth, cancel)?.len(),
+                } as u64)
+            });
+        }
+        let rule = self.rule()?;
+        let side = rule.side(self.depth);
+        let total = rule.volume(self.depth);
+        let copies = self.tiling.instance_count() as u64;
+        let shape: [u64; 3] = std::array::from_fn(|axis| rule.side_along(axis, self.depth) as u64);
+        let volume: u64 = shape.iter().product();
+        // Morphology may fill the whole slice, and a dilated surface may
+        // enclose every cell of it.
+        let cells = |cells: u64| {
+            if self.morphology.is_empty() {
+                cells
+            } else {
+                volume
+            }
+        };
+        let faces = |cells: u64| match self.offset {
+            Some(offset) if offset > 0.0 => volume.saturating_mul(6),
+            _ => cells * 6,
+        };
+        let mut outputs = Vec::new();
+        if let Some(section) = &self.section {
+            let sides = std::array::from_fn(|axis| rule.side_along(axis, self.depth));
+            let cells = section.max_cells(sides);
+            for path in &self.outputs {
+                // Each of a cell's eight facets cuts to at most a hexagon,
+                // two quads' worth of triangles.
+                outputs.push(self.planned(path.clone(), None, cells, cells * 16, shape, copies)?);
+            }
+        } else if self.cut.is_some() {
+            for path in &self.outputs {
+                // A cut cell keeps at most its six sides and a cap, each
+                // at most a hexagon.
+                let cells = expected_cells(&rule, self.depth);
+                outputs.push(self.planned(path.clone(), None, cells, cells * 28, shape, copies)?);
+            }
+        } else if self.dims == 3 {
+            for path in &self.outputs {
+                let cells = cells(expected_cells(&rule, self.depth));
+                outputs.push(self.planned(
+                    path.clone(),
+                    None,
+                    cells,
+                    faces(cells),
+                    shape,
+                    copies,
+                )?);
+            }
+        } else {
+            let slices = self.slice_indices(side)?;
+            for &w in &slices {
+                for path in &self.outputs {
+                    let path = slice_path(path, w, slices.len() > 1);
+                    let cells = cells(
+                        self.slab_ws(w)
+                            .map(|w| rule.slice_cells(w % side, self.depth))
+                            .fold(0, u64::saturating_add)
+                            .min(volume),
+                    );
+                    outputs.push(self.planned(
+                        path,
+                        Some(w),
+                        cells,
+                        faces(cells),
+                        shape,
+                        copies,
+                    )?);
+                }
+            }
+        }
+        outputs.extend(self.planned_complex(expected_cells(&rule, self.depth)));
+        Ok(Plan {
+            job: self.display_name(),
+            rule: rule.name().to_string(),
+            surface: false,
+            dims: self.dims,
+            depth: self.depth,
+            shape: (0..self.dims)
+                .map(|axis| rule.side_along(axis, self.depth))
+                .collect(),
+            levels: (1..=self.depth)
+                .map(|level| expected_cells(&rule, level))
+                .collect(),
+            lattice_bytes: if self.section.is_some() {
+                0
+            } else if self.out_of_core {
+                let shape = shape.map(|side| side as usize);
+                streaming_bytes(shape, slab_layers(shape))
+            } else {
+                total.div_ceil(8)
+            },
+            estimated_time: estimate_time(&rule, self.depth),
+            transforms: self.transforms.clone(),
+            outputs,
+        })
+    }
+}
+
+impl Job {
+    /// Plans a surface fractal job from its triangle counts, timing a build
+    /// at the deepest level of at most 2^16 triangles.
+    fn plan_surface(&self, surface: &Surface) -> Result<Plan> {
+        let triangles = surface.triangles(self.depth);
+        let calibration = (0..=self.depth)
+            .take_while(|&d| surface.triangles(d) <= 1 << 16)
+            .last()
+            .unwrap_or(0);
+        let start = Instant::now();
+        drop(surface.build(calibration, &CancelToken::new())?);
+        let per_triangle =
+            start.elapsed().as_secs_f64() / surface.triangles(calibration).max(1) as f64;
+        let copies = self.tiling.instance_count() as u64;
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|path| self.planned(path.clone(), None, triangles, triangles / 2, [0; 3], copies))
+            .collect::<Result<_>>()?;
+        Ok(Plan {
+            job: self.display_name(),
+            rule: surface.name().to_string(),
+            surface: true,
+            dims: 3,
+            depth: self.depth,
+            shape: Vec::new(),
+            levels: (1..=self.depth)
+                .map(|level| surface.triangles(level))
+                .collect(),
+            lattice_bytes: 0,
+            estimated_time: Duration::from_secs_f64(per_triangle * triangles as f64),
+            transforms: self.transforms.clone(),
+            outputs,
+        })
+    }
+}
+
+impl Job {
+    /// Plans an import or infill job from the model's grid. Outputs are
+    /// bounded by the whole grid and the time is scaled from running `fill`
+    /// with cells coarse enough for 16 along the model's longest side.
+    fn plan_model(
+        &self,
+        model: &Import,
+        rule: String,
+        fill: impl Fn(&Import, &ModelGrid, &CancelToken) -> Result<()>,
+    ) -> Result<Plan> {
+        let grid = model.grid()?;
+        let shape = grid.shape.map(|n| n as u64);
+        let volume: u64 = shape.iter().product();
+        let copies = self.tiling.instance_count() as u64;
+        let outputs = self
+            .outputs
+            .iter()
+            .map(|path| self.planned(path.clone(), None, volume, volume * 6, shape, copies))
+            .collect::<Result<_>>()?;
+        let longest = grid.shape.iter().copied().max().unwrap_or(1);
+        let coarse = Import {
+            cell_size: model.cell_size * (longest as f64 / 16.0).max(1.0),
+            ..model.clone()
+        };
+        let coarse_grid = coarse.grid()?;
+        let start = Instant::now();
+        fill(&coarse, &coarse_grid, &CancelToken::new())?;
+        let sampled: usize = coarse_grid.shape.iter().product();
+        let per_cell = start.elapsed().as_secs_f64() / sampled as f64;
+        Ok(Plan {
+            job: self.display_name(),
+            rule,
+            surface: false,
+            dims: 3,
+            depth: self.depth,
+            shape: grid.shape.to_vec(),
+            levels: Vec::new(),
+            lattice_bytes: volume.div_ceil(8),
+            estimated_time: Duration::from_secs_f64(per_cell * volume as f64),
+            transforms: self.transforms.clone(),
+            outputs,
+        })
+    }
+
+    /// Plans a sampled or scripted job. Which cells survive is not known
+    /// without generating, so outputs are bounded by a full slice and the
+    /// time is scaled from `voxelize` at a side of at most 16, which
+    /// returns the number of cells it generated.
+    fn plan_sampled(
+        &self,
+        side: usize,
+        voxelize: impl Fn(usize, &CancelToken) -> Result<u64>,
+    ) -> Result<Plan> {
+        let slice = (side as u64).saturating_pow(3);
+        let total = (side as u64).saturating_pow(self.dims as u32);
+        let copies = self.tiling.instance_count() as u64;
+        let mut outputs = Vec::new();
+        let slices = if self.dims == 3 {
+            vec![None]
+        } else {
+            let slices = self.slice_indices(side)?;
+            slices.iter().map(|&w| Some(w)).collect()
+        };
+        for &w in &slices {
+            for path in &self.outputs {
+                let path = match w {
+                    Some(w) => slice_path(path, w, slices.len() > 1),
+                    None => path.clone(),
+                };
+                outputs.push(self.planned(path, w, slice, slice * 6, [side as u64; 3], copies)?);
+            }
+        }
+        outputs.extend(self.planned_complex(total));
+        let coarse = side.min(16);
+        let start = Instant::now();
+        let sampled = voxelize(coarse, &CancelToken::new())? as f64;
+        let per_cell = start.elapsed().as_secs_f64() / sampled;
+        Ok(Plan {
+            job: self.display_name(),
+            rule: self.fractal.clone(),
+            surface: false,
+            dims: self.dims,
+            depth: self.depth,
+            shape: vec![side; self.dims],
+            levels: Vec::new(),
+            lattice_bytes: total.div_ceil(8),
+            estimated_time: Duration::from_secs_f64(per_cell * total as f64),
+            transforms: self.transforms.clone(),
+            outputs,
+        })
+    }
+}
+
+/// Times recursive generation at the largest depth with at most 2^18
+/// cells and scales by the lattice volume, which bounds both the cells
+/// copied and the bitset cleared.
+fn estimate_time(rule: &Rule, depth: u32) -> Duration {
+    let cells_at = |d: u32| rule.volume(d) as f64;
+    let calibration = (1..=depth)
+        .take_while(|&d| cells_at(d) <= (1 << 18) as f64)
+        .last()
+        .unwrap_or(1);
+    let cancel = CancelToken::new();
+    let start = Instant::now();
+    match rule.dims() {
+        3 => drop(Lattice3::generate_recursive(rule, calibration, &cancel)),
+        _ => drop(Lattice4::generate_recursive(rule, calibration, &cancel)),
+    }
+    let per_cell = start.elapsed().as_secs_f64() / cells_at(calibration);
+    Duration::from_secs_f64(per_cell * cells_at(depth))
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = if self.surface { "triangles" } else { "cells" };
+        if self.surface {
+            writeln!(
+                f,
+                "job {}: {} surface, depth {}",
+                self.job, self.rule, self.depth
+            )?;
+        } else {
+            let shape = match self.shape.as_slice() {
+                [side, rest @ ..] if rest.iter().all(|s| s == side) => {
+                    format!("{side}^{}", self.dims)
+                }
+                shape => shape
+                    .iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join("×"),
+            };
+            writeln!(
+                f,
+                "job {}: {} rule, {}D, depth {}, {shape} cells",
+                self.job, self.rule, self.dims, self.depth
+            )?;
+        }
+        if !self.levels.is_empty() {
+            writeln!(f, "  {:>5} {:>16}", "level", unit)?;
+            for (level, cells) in self.levels.iter().enumerate() {
+                writeln!(f, "  {:>5} {:>16}", level + 1, cells)?;
+            }
+        }
+        if !self.surface {
+            writeln!(f, "  lattice memory: {}", human_bytes(self.lattice_bytes))?;
+        }
+        writeln!(
+            f,
+            "  estimated generation time: {:.2?}",
+            self.estimated_time
+        )?;
+        if !self.transforms.is_empty() {
+            writeln!(f, "  transforms: {:?}", self.transforms)?;
+        }
+        writeln!(f, "  outputs:")?;
+        for output in &self.outputs {
+            let slice = output.slice.map(|w| format!("w={w}")).unwrap_or_default();
+            writeln!(
+                f,
+                "    {} {} {} {unit}, {}",
+                output.path.display(),
+                slice,
+                output.cells,
+                match output.max_bytes {
+                    Some(bytes) => format!("up to {}", human_bytes(bytes)),
+                    None => "size unknown".into(),
+                }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats a byte count with a binary unit prefix.
+pub fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+impl Job {
+    /// The cell complex of a 4D job, if it writes one, of at most `cells`
+    /// cells.
+    fn planned_complex(&self, cells: u64) -> Option<PlannedOutput> {
+        self.complex.as_ref().map(|path| PlannedOutput {
+            path: path.clone(),
+            slice: None,
+            cells,
+            max_bytes: Some(CellComplex::max_bytes(cells, is_json(path))),
+        })
+    }
+
+    /// Plans one file of `copies` tiles of `cells` cells each, with at most
+    /// `faces` quads, or pairs of triangles, per tile, from a slice of
+    /// `shape` cells.
+    fn planned(
+        &self,
+        path: PathBuf,
+        slice: Option<usize>,
+        cells: u64,
+        faces: u64,
+        shape: [u64; 3],
+        copies: u64,
+    ) -> Result<PlannedOutput> {
+        let format = match Format::from_path(&path) {
+            Ok(format) => format,
+            // Checked for an exporter when the job runs.
+            Err(_) if !self.plugins.is_empty() => {
+                return Ok(PlannedOutput {
+                    path,
+                    slice,
+                    cells: cells.saturating_mul(copies),
+                    max_bytes: None,
+                })
+            }
+            Err(e) => return Err(e),
+        };
+        let volume: u64 = shape.iter().product();
+        let layers = shape.iter().copied().max().unwrap_or(0);
+        let max_bytes = match format {
+            // Four vertex lines and a face line of up to ~24 bytes each.
+            Format::Obj => faces.saturating_mul(120).saturating_mul(copies),
+            Format::Stl => faces
+                .saturating_mul(100)
+                .saturating_mul(copies)
+                .saturating_add(84),
+            // Four positions, six indices and four normals, stored once, at
+            // 12 or quantized 8 bytes a vector; the header and one node per
+            // copy fit in a kilobyte or so each.
+            Format::Glb => faces
+                .saturating_mul(match self.precision {
+                    Precision::Q16 => 88,
+                    _ => 120,
+                })
+                .saturating_add(copies.saturating_mul(1024)),
+            // A float per cell of the slice, plus a short header; never tiled.
+            Format::Vtk => volume.saturating_mul(4).saturating_add(256),
+            // A byte or float per cell after a fixed header.
+            Format::Nifti => volume.saturating_mul(4).saturating_add(352),
+            // A byte a block, before gzip's framing and a small header per
+            // piece, which stay under another byte a block for pieces more
+            // than a few blocks across.
+            Format::Schem => volume.saturating_mul(2).saturating_add(1024),
+            // At most a float per cell after the DX10 header.
+            Format::Dds => volume.saturating_mul(4).saturating_add(148),
+            // Every chunk of a byte or float per cell, padded to whole chunks,
+            // in gzip's fixed codes of at most nine bits a byte, plus framing.
+            Format::Zarr => {
+                let chunk = self.zarr.chunk.max(1) as u64;
+                let chunks: u64 = shape.iter().map(|n| n.div_ceil(chunk)).product();
+                let padded = chunks.saturating_mul(chunk.saturating_pow(3));
+                (padded.saturating_mul(36) / 8)
+                    .saturating_add(chunks.saturating_mul(64))
+                    .saturating_add(1024)
+            }
+            // A 16-byte header, the quantization's offsets and steps, and
+            // four values of four or two bytes per cell and copy.
+            Format::Instances => cells
+                .saturating_mul(copies)
+                .saturating_mul(match self.precision {
+                    Precision::F32 => 16,
+                    Precision::F16 | Precision::Q16 => 8,
+                })
+                .saturating_add(48),
+            // Summed over the stack: at most two bytes a pixel and one a row,
+            // plus a kilobyte of headers per image along the longest axis.
+            Format::Png | Format::Tiff => volume
+                .saturating_mul(3)
+                .saturating_add(layers.saturating_mul(1024)),
+        };
+        Ok(PlannedOutput {
+            path,
+            slice,
+            cells: cells.saturating_mul(copies),
+            max_bytes: Some(max_bytes),
+        })
+    }
+}
@@ -0,0 +1,224 @@
+//! Charts of a fractal's measures, drawn with plotters to `.svg` or `.png`
+//! for papers and reports: the box-counting fit of its dimension, the
+//! cells of each w slice of a 4D sweep, and the distribution of its pore
+//! radii.
+
+use std::io;
+use std::path::Path;
+
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::BoxCounting;
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, Artifact};
+use crate::image::write_rgb_png;
+use crate::job::Job;
+
+/// Width and height of a chart, in pixels.
+const SIZE: (u32, u32) = (800, 600);
+
+/// What a chart shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Chart {
+    /// Occupied boxes against box size on log-log axes, with the fitted
+    /// line whose slope is the box-counting dimension.
+    BoxCounting,
+    /// Filled cells of each w slice of a 4D fractal.
+    CrossSections,
+    /// Histogram of the empty cells' distances to the nearest filled one.
+    PoreSizes,
+}
+
+impl Job {
+    /// Measures the job's fractal for `chart` and draws it to `path`, an
+    /// SVG or PNG file by its extension.
+    pub fn plot(&self, chart: Chart, path: &Path, cancel: &CancelToken) -> Result<Artifact> {
+        let svg = match path.extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("svg") => true,
+            Some(e) if e.eq_ignore_ascii_case("png") => false,
+            _ => return Err(Error::UnknownFormat(path.to_path_buf())),
+        };
+        let title = self.display_name();
+        let measures = match chart {
+            Chart::BoxCounting => Measures::BoxCounting(self.box_counting(cancel)?),
+            Chart::CrossSections => Measures::CrossSections(self.cross_sections(cancel)?),
+            Chart::PoreSizes => Measures::PoreSizes(self.analyze(cancel)?.pore_radii),
+        };
+        if svg {
+            let mut text = String::new();
+            measures.draw(
+                &title,
+                SVGBackend::with_string(&mut text, SIZE).into_drawing_area(),
+            )?;
+            write_file_atomically(path, |out| Ok(io::Write::write_all(out, text.as_bytes())?))
+        } else {
+            let (width, height) = (SIZE.0 as usize, SIZE.1 as usize);
+            let mut buffer = vec![0; width * height * 3];
+            measures.draw(
+                &title,
+                BitMapBackend::with_buffer(&mut buffer, SIZE).into_drawing_area(),
+            )?;
+            let pixels: Vec<[u8; 3]> = buffer.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect();
+            write_file_atomically(path, |out| write_rgb_png(width, height, &pixels, out))
+        }
+    }
+}
+
+/// What a chart is drawn from.
+enum Measures {
+    BoxCounting(BoxCounting),
+    CrossSections(Vec<usize>),
+    PoreSizes(Vec<u64>),
+}
+
+impl Measures {
+    fn draw<B: DrawingBackend>(&self, title: &str, area: DrawingArea<B, Shift>) -> Result<()> {
+        match self {
+            Measures::BoxCounting(counting) => {
+                let points: Vec<(f64, f64)> = counting
+                    .sides
+                    .iter()
+                    .zip(&counting.boxes)
+                    .filter(|(_, &boxes)| boxes > 0)
+                    .map(|(&side, &boxes)| (-(side as f64).ln(), (boxes as f64).ln()))
+                    .collect();
+                let fit = |x: f64| counting.intercept + counting.dimension * x;
+                draw_box_counting(&area, title, &points, counting.dimension, fit)
+            }
+            Measures::CrossSections(cells) => draw_cross_sections(&area, title, cells),
+            Measures::PoreSizes(radii) => draw_pore_sizes(&area, title, radii),
+        }
+    }
+}
+
+/// Log box counts against log inverse box side, with the fitted line.
+fn draw_box_counting<B: DrawingBackend>(
+    area: &DrawingArea<B, Shift>,
+    title: &str,
+    points: &[(f64, f64)],
+    dimension: f64,
+    fit: impl Fn(f64) -> f64,
+) -> Result<()> {
+    let (x, y) = bounds(points);
+    area.fill(&WHITE).map_err(plot_error)?;
+    let mut chart = ChartBuilder::on(area)
+        .caption(format!("{title}: box counting"), ("sans-serif", 24))
+        .margin(16)
+        .x_label_area_size(48)
+        .y_label_area_size(64)
+        .build_cartesian_2d(x.clone(), y)
+        .map_err(plot_error)?;
+    chart
+        .configure_mesh()
+        .x_desc("ln(1 / box side)")
+        .y_desc("ln(occupied boxes)")
+        .draw()
+        .map_err(plot_error)?;
+    chart
+        .draw_series(points.iter().map(|&p| Circle::new(p, 4, BLUE.filled())))
+        .map_err(plot_error)?;
+    chart
+        .draw_series(LineSeries::new([x.start, x.end].map(|x| (x, fit(x))), &RED))
+        .map_err(plot_error)?
+        .label(format!("dimension {dimension:.4}"))
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], RED));
+    chart
+        .configure_series_labels()
+        .background_style(WHITE)
+        .border_style(BLACK)
+        .draw()
+        .map_err(plot_error)?;
+    area.present().map_err(plot_error)
+}
+
+/// Filled cells of every w slice.
+fn draw_cross_sections<B: DrawingBackend>(
+    area: &DrawingArea<B, Shift>,
+    title: &str,
+    cells: &[usize],
+) -> Result<()> {
+    let points: Vec<(f64, f64)> = cells
+        .iter()
+        .enumerate()
+        .map(|(w, &cells)| (w as f64, cells as f64))
+        .collect();
+    let (x, y) = bounds(&points);
+    area.fill(&WHITE).map_err(plot_error)?;
+    let mut chart = ChartBuilder::on(area)
+        .caption(format!("{title}: cross-sections"), ("sans-serif", 24))
+        .margin(16)
+        .x_label_area_size(48)
+        .y_label_area_size(64)
+        .build_cartesian_2d(x, y)
+        .map_err(plot_error)?;
+    chart
+        .configure_mesh()
+        .x_desc("w")
+        .y_desc("filled cells")
+        .draw()
+        .map_err(plot_error)?;
+    chart
+        .draw_series(LineSeries::new(points, &BLUE))
+        .map_err(plot_error)?;
+    area.present().map_err(plot_error)
+}
+
+/// Histogram of `radii`, entry `r` the empty cells between `r` and `r + 1`
+/// cells from the nearest filled one.
+fn draw_pore_sizes<B: DrawingBackend>(
+    area: &DrawingArea<B, Shift>,
+    title: &str,
+    radii: &[u64],
+) -> Result<()> {
+    let top = radii.iter().copied().max().unwrap_or(0).max(1);
+    let top = top + top / 20;
+    area.fill(&WHITE).map_err(plot_error)?;
+    let mut chart = ChartBuilder::on(area)
+        .caption(format!("{title}: pore sizes"), ("sans-serif", 24))
+        .margin(16)
+        .x_label_area_size(48)
+        .y_label_area_size(64)
+        .build_cartesian_2d((0..radii.len().max(1) as u32).into_segmented(), 0..top)
+        .map_err(plot_error)?;
+    chart
+        .configure_mesh()
+        .x_desc("distance to nearest filled cell, cells")
+        .y_desc("empty cells")
+        .draw()
+        .map_err(plot_error)?;
+    chart
+        .draw_series(
+            Histogram::vertical(&chart)
+                .style(BLUE.filled())
+                .margin(2)
+                .data(radii.iter().enumerate().map(|(r, &n)| (r as u32, n))),
+        )
+        .map_err(plot_error)?;
+    area.present().map_err(plot_error)
+}
+
+/// Ranges holding every point, padded so none sits on an axis.
+fn bounds(points: &[(f64, f64)]) -> (std::ops::Range<f64>, std::ops::Range<f64>) {
+    let range = |values: &mut dyn Iterator<Item = f64>| {
+        let (low, high) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), v| {
+            (low.min(v), high.max(v))
+        });
+        if low > high {
+            return 0.0..1.0;
+        }
+        let pad = ((high - low) * 0.05).max(0.5);
+        low - pad..high + pad
+    };
+    (
+        range(&mut points.iter().map(|p| p.0)),
+        range(&mut points.iter().map(|p| p.1)),
+    )
+}
+
+fn plot_error(error: impl std::error::Error) -> Error {
+    Error::Io(io::Error::other(error.to_string()))
+}
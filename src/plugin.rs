@@ -0,0 +1,443 @@
+//! Exporters for formats the crate does not write itself, such as
+//! proprietary CAD formats, added without forking it.
+//!
+//! An [`Exporter`] claims some file extensions and writes lattices or
+//! meshes for them. [`Exporters`] holds those a job may use; the export
+//! functions in [`crate::export`] hand them any path none of the built-in
+//! [`Format`]s claims. Programs embedding the crate add their own with
+//! [`Exporters::register`]; with the `plugins` feature,
+//! [`Exporters::load`] adds one from a shared library, which is what a
+//! job's `plugins` list does.
+//!
+//! A library exports a C function named by [`abi::ENTRY_POINT`] that
+//! returns a pointer to an [`abi::ExporterV1`] living as long as the
+//! library. Only C types cross the boundary, so plugins may be written in
+//! any language, or in Rust with another compiler than the crate's:
+//!
+//! ```text
+//! use fractal_slicer_4_d::plugin::abi::{ExporterV1, LatticeV1, SinkV1, VERSION};
+//!
+//! unsafe extern "C" fn write_lattice(lattice: *const LatticeV1, sink: *mut SinkV1) -> i32 {
+//!     let (lattice, sink) = (&*lattice, &mut *sink);
+//!     let header = format!("{:?}\n", lattice.shape);
+//!     (sink.write)(sink.context, header.as_ptr(), header.len())
+//! }
+//!
+//! static EXPORTER: ExporterV1 = ExporterV1 {
+//!     version: VERSION,
+//!     name: c"shape".as_ptr(),
+//!     extensions: c"shape".as_ptr(),
+//!     write_lattice: Some(write_lattice),
+//!     write_mesh: None,
+//! };
+//!
+//! #[no_mangle]
+//! pub extern "C" fn fractal_slicer_exporter_v1() -> *const ExporterV1 {
+//!     &EXPORTER
+//! }
+//! ```
+//!
+//! Loading a library runs its code with the process's rights, so only
+//! load plugins you trust.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::Format;
+use crate::lattice::Lattice3;
+use crate::mesh::Mesh;
+use crate::seekable;
+
+/// Writes a format for the extensions it claims.
+///
+/// Given a lattice, an exporter that [writes lattices](Self::writes_lattices)
+/// gets its cells; any other gets its surface as a triangle mesh, built,
+/// simplified, repaired, tiled and transformed as for STL. Surface
+/// fractals and exact cuts are always written as meshes.
+pub trait Exporter: Send + Sync {
+    /// Names the exporter in logs and errors.
+    fn name(&self) -> &str;
+
+    /// The extensions, without a dot, of the paths it writes.
+    fn extensions(&self) -> Vec<String>;
+
+    /// Whether lattices are written as cells rather than as a surface.
+    fn writes_lattices(&self) -> bool {
+        false
+    }
+
+    /// Writes the cells of `lattice`.
+    fn write_lattice(
+        &self,
+        _lattice: &Lattice3,
+        _out: &mut dyn Write,
+        _cancel: &CancelToken,
+    ) -> Result<()> {
+        Err(Error::InvalidJob(format!(
+            "the `{}` exporter does not write lattices",
+            self.name()
+        )))
+    }
+
+    /// Writes a mesh already in output units.
+    fn write_mesh(&self, _mesh: &Mesh, _out: &mut dyn Write, _cancel: &CancelToken) -> Result<()> {
+        Err(Error::InvalidJob(format!(
+            "the `{}` exporter does not write meshes",
+            self.name()
+        )))
+    }
+}
+
+/// The exporters a job may use, by extension.
+#[derive(Clone, Default)]
+pub struct Exporters {
+    by_extension: Vec<(String, Arc<dyn Exporter>)>,
+}
+
+impl fmt::Debug for Exporters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: BTreeSet<&str> = self.by_extension.iter().map(|(_, e)| e.name()).collect();
+        f.debug_list().entries(names).finish()
+    }
+}
+
+impl Exporters {
+    /// Adds `exporter` for its extensions, which neither a built-in format
+    /// nor another exporter may already claim.
+    pub fn register(&mut self, exporter: Arc<dyn Exporter>) -> Result<()> {
+        let extensions = exporter.extensions();
+        if extensions.is_empty() {
+            return Err(Error::InvalidJob(format!(
+                "the `{}` exporter claims no extensions",
+                exporter.name()
+            )));
+        }
+        for extension in &extensions {
+            let extension = extension.to_ascii_lowercase();
+            if extension.is_empty() || extension.contains('.') {
+                return Err(Error::InvalidJob(format!(
+                    "the `{}` exporter claims an invalid extension `{extension}`",
+                    exporter.name()
+                )));
+            }
+            if Format::from_extension(&extension).is_some() {
+                return Err(Error::InvalidJob(format!(
+                    "the `{}` exporter claims `.{extension}`, a built-in format",
+                    exporter.name()
+                )));
+            }
+            if let Some(other) = self.get(&extension) {
+                return Err(Error::InvalidJob(format!(
+                    "the `{}` and `{}` exporters both claim `.{extension}`",
+                    other.name(),
+                    exporter.name()
+                )));
+            }
+            self.by_extension.push((extension, exporter.clone()));
+        }
+        tracing::debug!(name = exporter.name(), ?extensions, "exporter registered");
+        Ok(())
+    }
+
+    /// Loads the exporter of the shared library `library` and registers it.
+    pub fn load(&mut self, library: &Path) -> Result<()> {
+        self.register(open(library)?)
+    }
+
+    /// The exporter of `path`'s extension, or for a compressed `.zst` path
+    /// the extension before it.
+    pub fn find(&self, path: &Path) -> Option<&dyn Exporter> {
+        let path = seekable::inner_path(path);
+        let extension = path.extension()?.to_str()?;
+        self.get(&extension.to_ascii_lowercase())
+    }
+
+    fn get(&self, extension: &str) -> Option<&dyn Exporter> {
+        self.by_extension
+            .iter()
+            .find(|(e, _)| e == extension)
+            .map(|(_, exporter)| exporter.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_extension.is_empty()
+    }
+}
+
+/// The C interface between the crate and plugin libraries. A new version
+/// gets new types and a new entry point, so plugins built against this
+/// one keep loading.
+pub mod abi {
+    use std::ffi::{c_char, c_void};
+
+    /// The ABI version [`ExporterV1::version`] must hold.
+    pub const VERSION: u32 = 1;
+
+    /// The NUL-terminated name of the function a plugin exports, taking
+    /// nothing and returning a `*const ExporterV1`.
+    pub const ENTRY_POINT: &[u8] = b"fractal_slicer_exporter_v1\0";
+
+    /// A plugin's description of its exporter. It and the strings it points
+    /// to must live as long as the library.
+    #[repr(C)]
+    pub struct ExporterV1 {
+        /// [`VERSION`].
+        pub version: u32,
+        /// NUL-terminated UTF-8 name of the exporter.
+        pub name: *const c_char,
+        /// NUL-terminated, comma-separated extensions without dots, such
+        /// as `"3dm,3dmz"`.
+        pub extensions: *const c_char,
+        /// Writes the cells of a lattice, returning 0 on success; null to
+        /// be given lattices' surfaces as meshes instead.
+        pub write_lattice:
+            Option<unsafe extern "C" fn(lattice: *const LatticeV1, sink: *mut SinkV1) -> i32>,
+        /// Writes a triangle mesh, returning 0 on success; null if the
+        /// format holds no meshes.
+        pub write_mesh: Option<unsafe extern "C" fn(mesh: *const MeshV1, sink: *mut SinkV1) -> i32>,
+    }
+
+    // Only ever read, and by its contract pointing at data that outlives it.
+    unsafe impl Sync for ExporterV1 {}
+
+    /// A lattice lent to a plugin for one call.
+    #[repr(C)]
+    pub struct LatticeV1 {
+        /// Cells along x, y and z.
+        pub shape: [usize; 3],
+        /// A byte per cell, 1 if filled and 0 if not, x fastest and z
+        /// slowest.
+        pub cells: *const u8,
+    }
+
+    /// A triangle mesh lent to a plugin for one call.
+    #[repr(C)]
+    pub struct MeshV1 {
+        /// Three doubles, x, y and z, per vertex.
+        pub vertices: *const [f64; 3],
+        pub vertex_count: usize,
+        /// Three vertex indices per triangle, wound counter-clockwise seen
+        /// from outside.
+        pub triangles: *const [u32; 3],
+        pub triangle_count: usize,
+    }
+
+    /// Where a plugin writes its file.
+    #[repr(C)]
+    pub struct SinkV1 {
+        /// Passed back to `write` unchanged.
+        pub context: *mut c_void,
+        /// Writes all of `len` bytes, returning 0 on success. Non-zero means
+        /// the output failed or the job was cancelled; the plugin should
+        /// stop and return that value.
+        pub write: unsafe extern "C" fn(context: *mut c_void, bytes: *const u8, len: usize) -> i32,
+    }
+}
+
+#[cfg(not(feature = "plugins"))]
+fn open(library: &Path) -> Result<Arc<dyn Exporter>> {
+    Err(Error::InvalidJob(format!(
+        "loading `{}` needs the `plugins` feature",
+        library.display()
+    )))
+}
+
+#[cfg(feature = "plugins")]
+use library::open;
+
+#[cfg(feature = "plugins")]
+mod library {
+    use std::ffi::{c_char, c_void, CStr};
+    use std::io::Write;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use libloading::Library;
+
+    use super::abi::{ExporterV1, LatticeV1, MeshV1, SinkV1, ENTRY_POINT, VERSION};
+    use super::Exporter;
+    use crate::cancel::CancelToken;
+    use crate::error::{Error, Result};
+    use crate::lattice::Lattice3;
+    use crate::mesh::Mesh;
+
+    type WriteLattice = unsafe extern "C" fn(*const LatticeV1, *mut SinkV1) -> i32;
+    type WriteMesh = unsafe extern "C" fn(*const MeshV1, *mut SinkV1) -> i32;
+
+    /// An exporter of a loaded library, which it keeps loaded.
+    struct Plugin {
+        name: String,
+        extensions: Vec<String>,
+        write_lattice: Option<WriteLattice>,
+        write_mesh: Option<WriteMesh>,
+        _library: Library,
+    }
+
+    /// Loads `path` and reads its exporter's description.
+    pub(super) fn open(path: &Path) -> Result<Arc<dyn Exporter>> {
+        let invalid = |reason: String| Error::InvalidJob(format!("{}: {reason}", path.display()));
+        // SAFETY: loading runs the library's initialisers, which the user
+        // vouched for by naming it; the entry point's signature is the
+        // ABI's.
+        let library = unsafe { Library::new(path) }.map_err(|e| invalid(e.to_string()))?;
+        let entry =
+            unsafe { library.get::<unsafe extern "C" fn() -> *const ExporterV1>(ENTRY_POINT) }
+                .map_err(|e| invalid(e.to_string()))?;
+        // SAFETY: by the ABI the pointer is null or valid while the library
+        // is loaded, and we copy what we need out of it now.
+        let exporter = unsafe { entry().as_ref() }
+            .ok_or_else(|| invalid("the entry point returned no exporter".into()))?;
+        if exporter.version != VERSION {
+            return Err(invalid(format!(
+                "exporter ABI version {}, expected {VERSION}",
+                exporter.version
+            )));
+        }
+        let string = |ptr: *const c_char, what: &str| {
+            if ptr.is_null() {
+                return Err(invalid(format!("the exporter has no {what}")));
+            }
+            // SAFETY: non-null strings are NUL-terminated by the ABI.
+            unsafe { CStr::from_ptr(ptr) }
+                .to_str()
+                .map(str::to_owned)
+                .map_err(|_| invalid(format!("the exporter's {what} is not UTF-8")))
+        };
+        let plugin = Plugin {
+            name: string(exporter.name, "name")?,
+            extensions: string(exporter.extensions, "extensions")?
+                .split(',')
+                .map(|e| e.trim().to_owned())
+                .collect(),
+            write_lattice: exporter.write_lattice,
+            write_mesh: exporter.write_mesh,
+            _library: library,
+        };
+        if plugin.write_lattice.is_none() && plugin.write_mesh.is_none() {
+            return Err(invalid(
+                "the exporter writes neither lattices nor meshes".into(),
+            ));
+        }
+        tracing::info!(library = %path.display(), name = plugin.name, "plugin loaded");
+        Ok(Arc::new(plugin))
+    }
+
+    /// The state behind a [`SinkV1`].
+    struct Sink<'a> {
+        out: &'a mut dyn Write,
+        cancel: &'a CancelToken,
+        error: Option<Error>,
+    }
+
+    unsafe extern "C" fn write_sink(context: *mut c_void, bytes: *const u8, len: usize) -> i32 {
+        // SAFETY: `context` is the `Sink` of the call in progress.
+        let sink = unsafe { &mut *context.cast::<Sink>() };
+        let bytes = match len {
+            0 => &[][..],
+            // SAFETY: the plugin passes `len` readable bytes.
+            _ => unsafe { std::slice::from_raw_parts(bytes, len) },
+        };
+        let written = sink
+            .cancel
+            .check()
+            .and_then(|()| Ok(sink.out.write_all(bytes)?));
+        match written {
+            Ok(()) => 0,
+            Err(e) => {
+                sink.error = Some(e);
+                1
+            }
+        }
+    }
+
+    impl Plugin {
+        /// Runs `call` with a sink onto `out`, preferring the sink's own
+        /// error to the plugin's status.
+        fn call(
+            &self,
+            out: &mut dyn Write,
+            cancel: &CancelToken,
+            call: impl FnOnce(*mut SinkV1) -> i32,
+        ) -> Result<()> {
+            let mut sink = Sink {
+                out,
+                cancel,
+                error: None,
+            };
+            let mut abi = SinkV1 {
+                context: (&mut sink as *mut Sink).cast(),
+                write: write_sink,
+            };
+            let status = call(&mut abi);
+            match (sink.error, status) {
+                (Some(e), _) => Err(e),
+                (None, 0) => Ok(()),
+                (None, status) => Err(Error::InvalidJob(format!(
+                    "the `{}` exporter failed with status {status}",
+                    self.name
+                ))),
+            }
+        }
+    }
+
+    impl Exporter for Plugin {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn extensions(&self) -> Vec<String> {
+            self.extensions.clone()
+        }
+
+        fn writes_lattices(&self) -> bool {
+            self.write_lattice.is_some()
+        }
+
+        fn write_lattice(
+            &self,
+            lattice: &Lattice3,
+            out: &mut dyn Write,
+            cancel: &CancelToken,
+        ) -> Result<()> {
+            let Some(write) = self.write_lattice else {
+                return Err(Error::InvalidJob(format!(
+                    "the `{}` exporter does not write lattices",
+                    self.name
+                )));
+            };
+            let mut cells = vec![0u8; lattice.len()];
+            for p in lattice.iter() {
+                cells[lattice.index(p)] = 1;
+            }
+            let abi = LatticeV1 {
+                shape: lattice.shape(),
+                cells: cells.as_ptr(),
+            };
+            // SAFETY: `abi` and `cells` outlive the call.
+            self.call(out, cancel, |sink| unsafe { write(&abi, sink) })
+        }
+
+        fn write_mesh(&self, mesh: &Mesh, out: &mut dyn Write, cancel: &CancelToken) -> Result<()> {
+            let Some(write) = self.write_mesh else {
+                return Err(Error::InvalidJob(format!(
+                    "the `{}` exporter does not write meshes",
+                    self.name
+                )));
+            };
+            let triangles = mesh.triangles();
+            let abi = MeshV1 {
+                vertices: mesh.vertices.as_ptr(),
+                vertex_count: mesh.vertices.len(),
+                triangles: triangles.as_ptr(),
+                triangle_count: triangles.len(),
+            };
+            // SAFETY: `abi` and what it points to outlive the call.
+            self.call(out, cancel, |sink| unsafe { write(&abi, sink) })
+        }
+    }
+}
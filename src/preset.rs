@@ -0,0 +1,213 @@
+//! Named bundles of export settings for common workflows, so
+//! `--preset web-glb` stands in for half a dozen flags.
+//!
+//! A preset gives outputs without an extension its format and lays its
+//! settings over those the job leaves at their defaults; transforms are
+//! applied after the job's own. Besides the built-in presets, users keep
+//! their own, or replace built-in ones, in a JSON file mapping names to
+//! presets:
+//!
+//! ```text
+//! {
+//!   "print-cm": {
+//!     "description": "STL in centimetres, a cell a millimetre across",
+//!     "format": "stl",
+//!     "settings": { "repair": true, "transforms": [{ "scale": 0.1 }] }
+//!   }
+//! }
+//! ```
+//!
+//! The file is `fractal-slicer/presets.json` in the user's configuration
+//! directory, `$XDG_CONFIG_HOME` or `~/.config`, unless named otherwise.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::{Error, Result};
+use crate::job::Job;
+use crate::seekable;
+
+/// The settings a preset may set: those of the exported files rather
+/// than of the fractal.
+pub const SETTINGS: [&str; 16] = [
+    "transforms",
+    "tiling",
+    "normals",
+    "precision",
+    "coordinates",
+    "normalize",
+    "domain",
+    "simplify",
+    "repair",
+    "boundary",
+    "offset",
+    "images",
+    "schematic",
+    "gltf",
+    "texture",
+    "zarr",
+];
+
+const BUILT_IN: &str = r#"{
+  "print-mm": {
+    "description": "Repaired binary STL, a cell a millimetre across, for slicers",
+    "format": "stl",
+    "settings": { "repair": true, "boundary": "open" }
+  },
+  "web-glb": {
+    "description": "Quantized, meshopt-compressed glTF a metre across, y up, for web viewers",
+    "format": "glb",
+    "settings": {
+      "normals": "face",
+      "precision": "q16",
+      "gltf": { "up": "y", "fit": 1.0, "meshopt": true }
+    }
+  },
+  "unity": {
+    "description": "glTF in metres, y up, with smooth normals and tangents for game engines",
+    "format": "glb",
+    "settings": {
+      "normals": "smooth",
+      "gltf": { "up": "y", "tangents": true }
+    }
+  },
+  "paraview": {
+    "description": "VTK signed distance field for ParaView and other volume tools",
+    "format": "vtk"
+  },
+  "archive": {
+    "description": "Seekable zstd-compressed NIfTI volume of the signed distance",
+    "format": "nii.zst",
+    "settings": { "images": { "values": "distance" } }
+  }
+}"#;
+
+/// A named bundle of export settings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    #[serde(default)]
+    pub description: String,
+    /// Extension given to outputs without one, such as `stl`, or
+    /// `nii.zst` for compressed files.
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Job settings, by their names in job files; see [`SETTINGS`].
+    #[serde(default)]
+    pub settings: Map<String, Value>,
+}
+
+impl Preset {
+    /// `job` with the preset applied.
+    pub fn apply(&self, job: &Job) -> Result<Job> {
+        let mut value = serde_json::to_value(job)?;
+        let defaults = serde_json::to_value(serde_json::from_value::<Job>(serde_json::json!({
+            "depth": job.depth,
+            "outputs": [],
+        }))?)?;
+        for (key, setting) in &self.settings {
+            if !SETTINGS.contains(&key.as_str()) {
+                return Err(Error::InvalidJob(format!(
+                    "`{key}` is not an export setting; expected one of {}",
+                    SETTINGS.join(", ")
+                )));
+            }
+            match (&mut value[key], setting) {
+                (Value::Array(transforms), Value::Array(more)) if key == "transforms" => {
+                    transforms.extend(more.iter().cloned())
+                }
+                (field, _) => overlay(field, &defaults[key], setting),
+            }
+        }
+        let mut job: Job = serde_json::from_value(value)?;
+        if let Some(format) = &self.format {
+            for output in &mut job.outputs {
+                if seekable::inner_path(output).extension().is_none() {
+                    output.as_mut_os_string().push(format!(".{format}"));
+                }
+            }
+        }
+        Ok(job)
+    }
+}
+
+/// Sets `field` to `setting` if it still has its `default` value, field by
+/// field for objects such as `gltf`.
+fn overlay(field: &mut Value, default: &Value, setting: &Value) {
+    match (field, setting) {
+        (Value::Object(fields), Value::Object(settings)) => {
+            for (key, setting) in settings {
+                let field = fields.entry(key.clone()).or_insert(Value::Null);
+                overlay(field, &default[key], setting);
+            }
+        }
+        (field, _) if field == default => *field = setting.clone(),
+        _ => {}
+    }
+}
+
+/// The presets there are, by name.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Presets(pub BTreeMap<String, Preset>);
+
+impl Presets {
+    pub fn built_in() -> Self {
+        serde_json::from_str(BUILT_IN).expect("built-in presets are valid")
+    }
+
+    /// The built-in presets, with those of the file at `path` added or
+    /// replacing them, or of the user's presets file when `path` is None
+    /// and there is one.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut presets = Presets::built_in();
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => match user_file() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(presets),
+            },
+        };
+        let user = std::fs::read_to_string(&path)
+            .map_err(Error::from)
+            .and_then(|text| Ok(serde_json::from_str::<Presets>(&text)?))
+            .map_err(|e| Error::InvalidJob(format!("{}: {e}", path.display())))?;
+        presets.0.extend(user.0);
+        Ok(presets)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Preset> {
+        self.0.get(name).ok_or_else(|| {
+            let names: Vec<&str> = self.0.keys().map(String::as_str).collect();
+            Error::InvalidJob(format!(
+                "unknown preset `{name}`; expected one of {}",
+                names.join(", ")
+            ))
+        })
+    }
+}
+
+impl fmt::Display for Presets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.0.keys().map(String::len).max().unwrap_or(0);
+        for (name, preset) in &self.0 {
+            let format = preset.format.as_deref().unwrap_or("-");
+            let line = format!("{name:<width$}  {format:<8}  {}", preset.description);
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// `fractal-slicer/presets.json` in the user's configuration directory.
+pub fn user_file() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?;
+    Some(config.join("fractal-slicer").join("presets.json"))
+}
@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::{Boundary, Lattice3};
+use crate::morphology::{dilate, Element, Morphology, Operation};
+
+/// The thinnest strut or wall a printer can reproduce.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Printability {
+    /// Minimum thickness in output units, usually millimetres, after the
+    /// job's transforms have scaled the cells.
+    pub min_thickness: f64,
+    /// Thicken features found too thin, rather than only reporting them.
+    #[serde(default)]
+    pub fix: bool,
+}
+
+/// What [`Printability::check`] found in one lattice.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ThinFeatures {
+    /// The w index of the slice checked, for 4D jobs.
+    pub slice: Option<usize>,
+    /// The minimum thickness in cells.
+    pub min_cells: f64,
+    /// Filled cells lying in features thinner than the minimum.
+    pub thin_cells: usize,
+    /// Face-connected groups of thin cells.
+    pub regions: usize,
+    /// Cells filled by the fix; zero unless fixing.
+    pub added_cells: usize,
+    /// Thin cells left after fixing, where a fix was clipped by the
+    /// lattice's walls.
+    pub remaining_cells: usize,
+}
+
+impl Printability {
+    /// Checks `lattice` with cells `cell_size` units wide, returning the
+    /// lattice to print, thickened around thin features when fixing.
+    ///
+    /// A cell is thin if no cube of cells at least the minimum thickness
+    /// across, lying wholly in the solid, covers it: it is filled but not
+    /// left by an opening with that cube. Cubes rather than balls keep the
+    /// square corners of subdivision fractals from counting as thin. The
+    /// fix fills the same cube around every thin cell, so a strut one cell
+    /// thick grows to the minimum. Cubes span an odd number of cells, so
+    /// the minimum is rounded up to the next odd number of cells.
+    #[tracing::instrument(name = "printability", skip_all, fields(min_thickness = self.min_thickness))]
+    pub fn check(
+        &self,
+        lattice: Lattice3,
+        cell_size: f64,
+        boundary: Boundary,
+        slice: Option<usize>,
+        cancel: &CancelToken,
+    ) -> Result<(Lattice3, ThinFeatures)> {
+        if !(self.min_thickness > 0.0 && self.min_thickness.is_finite()) {
+            return Err(Error::InvalidJob(
+                "minimum thickness must be positive".into(),
+            ));
+        }
+        if !(cell_size > 0.0 && cell_size.is_finite()) {
+            return Err(Error::InvalidJob(format!(
+                "cannot check thickness with cells {cell_size} units wide"
+            )));
+        }
+        let min_cells = self.min_thickness / cell_size;
+        // An element of radius r spans 2r + 1 cells.
+        let radius = ((min_cells - 1.0) / 2.0).ceil().max(0.0) as u32;
+        let mut features = ThinFeatures {
+            slice,
+            min_cells,
+            thin_cells: 0,
+            regions: 0,
+            added_cells: 0,
+            remaining_cells: 0,
+        };
+        if radius == 0 {
+            return Ok((lattice, features));
+        }
+        let thin = thin_cells(&lattice, radius, boundary, cancel)?;
+        features.thin_cells = thin.count();
+        features.regions = thin.components(boundary).sizes.len();
+        if !self.fix || features.thin_cells == 0 {
+            if features.thin_cells > 0 {
+                tracing::warn!(
+                    thin_cells = features.thin_cells,
+                    regions = features.regions,
+                    min_cells,
+                    "features thinner than the minimum"
+                );
+            }
+            return Ok((lattice, features));
+        }
+        let cube = Morphology::new(Operation::Dilate, Element::Cube, radius).offsets::<3>();
+        let grown = dilate(&thin, &cube, boundary, cancel)?;
+        let fixed = Lattice3::from_fn(lattice.shape(), cancel, |p| lattice.get(p) || grown.get(p))?;
+        features.added_cells = fixed.count() - lattice.count();
+        features.remaining_cells = thin_cells(&fixed, radius, boundary, cancel)?.count();
+        tracing::info!(
+            thin_cells = features.thin_cells,
+            added_cells = features.added_cells,
+            remaining_cells = features.remaining_cells,
+            "thin features thickened"
+        );
+        Ok((fixed, features))
+    }
+}
+
+/// The filled cells an opening with a cube of `radius` removes.
+fn thin_cells(
+    lattice: &Lattice3,
+    radius: u32,
+    boundary: Boundary,
+    cancel: &CancelToken,
+) -> Result<Lattice3> {
+    let opened =
+        Morphology::new(Operation::Open, Element::Cube, radius).apply(lattice, boundary, cancel)?;
+    Lattice3::from_fn(lattice.shape(), cancel, |p| {
+        lattice.get(p) && !opened.get(p)
+    })
+}
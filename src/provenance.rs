@@ -0,0 +1,204 @@
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::export::{Artifact, HashingWriter};
+use crate::job::{Job, JobReport};
+use crate::store::object_url;
+
+/// Every artifact a batch wrote, with the job that wrote it, so a published
+/// dataset can be checked byte for byte and regenerated from its
+/// parameters.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Provenance {
+    /// The fractal-slicer version that wrote the artifacts.
+    pub version: String,
+    pub jobs: Vec<JobArtifacts>,
+}
+
+/// The artifacts of one successful job.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JobArtifacts {
+    pub job: Job,
+    pub artifacts: Vec<Artifact>,
+}
+
+impl Provenance {
+    /// Collects the artifacts of the jobs that succeeded, in batch order.
+    pub fn new(jobs: &[Job], results: &[Result<JobReport>]) -> Self {
+        let jobs = jobs
+            .iter()
+            .zip(results)
+            .filter_map(|(job, result)| {
+                let report = result.as_ref().ok()?;
+                Some(JobArtifacts {
+                    job: job.clone(),
+                    artifacts: report.artifacts.clone(),
+                })
+            })
+            .collect();
+        Provenance {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            jobs,
+        }
+    }
+
+    /// Writes the manifest as JSON. Paths under its directory are stored
+    /// relative to it, so the dataset can be moved or published with it.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let dir = absolute_dir(path)?;
+        let mut manifest = self.clone();
+        manifest.map_paths(|p| match std::path::absolute(&*p) {
+            Ok(absolute) if object_url(p).is_none() => {
+                if let Ok(relative) = absolute.strip_prefix(&dir) {
+                    *p = relative.to_path_buf();
+                }
+            }
+            _ => {}
+        });
+        let json = serde_json::to_string_pretty(&manifest)?;
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+
+    /// Reads a manifest written by [`Provenance::save`], resolving relative
+    /// paths against its directory.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let mut manifest: Provenance = serde_json::from_str(&text)?;
+        if let Some(dir) = path.parent() {
+            manifest.map_paths(|p| {
+                if p.is_relative() && object_url(p).is_none() {
+                    *p = dir.join(&*p);
+                }
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Re-reads every local artifact and compares its size and SHA-256
+    /// with the manifest. Objects in cloud storage are not read back and
+    /// are reported as skipped.
+    pub fn verify(&self) -> Verification {
+        let checks: Vec<ArtifactCheck> = self
+            .jobs
+            .iter()
+            .flat_map(|job| &job.artifacts)
+            .map(check)
+            .collect();
+        Verification {
+            verified: checks
+                .iter()
+                .filter(|c| matches!(c.status, CheckStatus::Ok))
+                .count(),
+            failed: checks.iter().filter(|c| !c.is_ok()).count(),
+            artifacts: checks,
+        }
+    }
+
+    fn map_paths(&mut self, mut f: impl FnMut(&mut PathBuf)) {
+        for job in &mut self.jobs {
+            job.job.outputs.iter_mut().for_each(&mut f);
+            for artifact in &mut job.artifacts {
+                f(&mut artifact.path);
+            }
+        }
+    }
+}
+
+fn absolute_dir(path: &Path) -> Result<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(std::path::absolute(dir)?)
+}
+
+fn check(artifact: &Artifact) -> ArtifactCheck {
+    let status = if object_url(&artifact.path).is_some() {
+        CheckStatus::Skipped
+    } else {
+        match hash_file(&artifact.path) {
+            Err(e) => CheckStatus::Unreadable {
+                error: e.to_string(),
+            },
+            Ok((bytes, sha256)) if bytes == artifact.bytes && sha256 == artifact.sha256 => {
+                CheckStatus::Ok
+            }
+            Ok((bytes, sha256)) => CheckStatus::Changed { bytes, sha256 },
+        }
+    };
+    ArtifactCheck {
+        path: artifact.path.clone(),
+        status,
+    }
+}
+
+/// Size and hex-encoded SHA-256 of a file's contents.
+fn hash_file(path: &Path) -> std::io::Result<(u64, String)> {
+    let mut out = HashingWriter::new(std::io::sink());
+    std::io::copy(&mut File::open(path)?, &mut out)?;
+    Ok((out.bytes(), out.hex_digest()))
+}
+
+/// The outcome of [`Provenance::verify`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Verification {
+    pub verified: usize,
+    pub failed: usize,
+    pub artifacts: Vec<ArtifactCheck>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ArtifactCheck {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub status: CheckStatus,
+}
+
+impl ArtifactCheck {
+    /// Whether the artifact matched or was not checked.
+    pub fn is_ok(&self) -> bool {
+        matches!(self.status, CheckStatus::Ok | CheckStatus::Skipped)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    /// Cloud objects are not read back.
+    Skipped,
+    Unreadable {
+        error: String,
+    },
+    /// The file's size and SHA-256 as found.
+    Changed {
+        bytes: u64,
+        sha256: String,
+    },
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.artifacts {
+            let path = check.path.display();
+            match &check.status {
+                CheckStatus::Ok => writeln!(f, "{:<10} {path}", "ok")?,
+                CheckStatus::Skipped => writeln!(f, "{:<10} {path}", "skipped")?,
+                CheckStatus::Unreadable { error } => {
+                    writeln!(f, "{:<10} {path}: {error}", "unreadable")?
+                }
+                CheckStatus::Changed { bytes, sha256 } => writeln!(
+                    f,
+                    "{:<10} {path}: now {bytes} bytes, sha256 {}",
+                    "changed",
+                    &sha256[..16]
+                )?,
+            }
+        }
+        writeln!(f, "{} verified, {} failed", self.verified, self.failed)
+    }
+}
@@ -0,0 +1,31 @@
+/// SplitMix64, enough to draw dataset parameters and render samples
+/// reproducibly without a random number crate.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    /// Stream `stream` of the generator seeded with `seed`.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        Rng(mix(seed ^ mix(stream)))
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    /// A number in `0..n`; the bias is negligible for small `n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A number in `[0, 1)`.
+    pub fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
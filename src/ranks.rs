@@ -0,0 +1,236 @@
+//! Splitting one job between the processes of a parallel launch on a
+//! cluster, such as `mpirun -n 64` or `srun`: each rank generates only
+//! its run of the lattice along z, whole rows of chunks, and writes only
+//! those chunks of the job's Zarr outputs, rank 0 the arrays' metadata as
+//! well. Together the ranks write each array without passing messages.
+//!
+//! Every rank also leaves its counts in the array's `ranks/` directory;
+//! [`gather`] reduces them to the job's totals once all have finished,
+//! and names the ranks that have not.
+//!
+//! Ranks are read from the environment the launcher sets, Open MPI's,
+//! MPICH's or Slurm's, so no MPI library is linked. With the `mpi`
+//! feature, ranks can instead join the MPI world through rsmpi
+//! ([`Ranks::from_mpi`]): they then also sum their counts with an
+//! all-reduce once every part is written ([`all_reduce`]), so rank 0
+//! reports the job's totals without a `gather`.
+
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "mpi")]
+use std::sync::Mutex;
+
+#[cfg(feature = "mpi")]
+use mpi::collective::SystemOperation;
+#[cfg(feature = "mpi")]
+use mpi::topology::SimpleCommunicator;
+#[cfg(feature = "mpi")]
+use mpi::traits::{Communicator, CommunicatorCollectives};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// One process of a parallel launch and how many there are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ranks {
+    pub rank: usize,
+    pub size: usize,
+}
+
+/// Environment variables holding the rank and the number of ranks, by
+/// launcher.
+const LAUNCHERS: [(&str, &str); 4] = [
+    ("OMPI_COMM_WORLD_RANK", "OMPI_COMM_WORLD_SIZE"),
+    ("PMI_RANK", "PMI_SIZE"),
+    ("PMIX_RANK", "PMIX_SIZE"),
+    ("SLURM_PROCID", "SLURM_NTASKS"),
+];
+
+/// MPI, from the first [`Ranks::from_mpi`] until [`finalize_mpi`].
+#[cfg(feature = "mpi")]
+static UNIVERSE: Mutex<Option<mpi::environment::Universe>> = Mutex::new(None);
+
+impl Ranks {
+    /// This process's rank, as its launcher set it.
+    pub fn from_env() -> Option<Self> {
+        LAUNCHERS.iter().find_map(|(rank, size)| {
+            Some(Ranks {
+                rank: std::env::var(rank).ok()?.parse().ok()?,
+                size: std::env::var(size).ok()?.parse().ok()?,
+            })
+        })
+    }
+
+    /// This process's rank in the MPI world, initializing MPI the first
+    /// time. Only the calling thread makes MPI calls.
+    #[cfg(feature = "mpi")]
+    pub fn from_mpi() -> Result<Self> {
+        let mut universe = UNIVERSE.lock().unwrap();
+        if universe.is_none() {
+            let (started, _) = mpi::initialize_with_threading(mpi::Threading::Funneled)
+                .ok_or_else(|| Error::InvalidJob("MPI was initialized elsewhere".into()))?;
+            *universe = Some(started);
+        }
+        let world = SimpleCommunicator::world();
+        Ok(Ranks {
+            rank: world.rank() as usize,
+            size: world.size() as usize,
+        })
+    }
+
+    #[cfg(not(feature = "mpi"))]
+    pub fn from_mpi() -> Result<Self> {
+        Err(Error::InvalidJob(
+            "joining the MPI world needs the `mpi` feature".into(),
+        ))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.rank >= self.size {
+            return Err(Error::InvalidJob(format!(
+                "rank {} is not one of {} ranks",
+                self.rank, self.size
+            )));
+        }
+        Ok(())
+    }
+
+    /// This rank's share of `items`: a run of them, the first ranks
+    /// taking one more when they do not divide evenly.
+    pub fn share(&self, items: usize) -> Range<usize> {
+        let (each, extra) = (items / self.size, items % self.size);
+        let start = self.rank * each + self.rank.min(extra);
+        start..start + each + usize::from(self.rank < extra)
+    }
+}
+
+impl FromStr for Ranks {
+    type Err = String;
+
+    /// Parses `rank/size`, e.g. `3/16`.
+    fn from_str(text: &str) -> std::result::Result<Self, String> {
+        let (rank, size) = text.split_once('/').ok_or("expected rank/size")?;
+        let parse = |part: &str| part.trim().parse::<usize>().map_err(|e| e.to_string());
+        Ok(Ranks {
+            rank: parse(rank)?,
+            size: parse(size)?,
+        })
+    }
+}
+
+/// The counts one rank left in an array's `ranks/` directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RankReport {
+    pub rank: usize,
+    pub size: usize,
+    /// Filled cells in the rank's part of the array.
+    pub cells: u64,
+    pub chunks: usize,
+}
+
+/// The totals of every rank's counts for one array.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Gathered {
+    pub ranks: usize,
+    pub cells: u64,
+    pub chunks: usize,
+    /// Ranks that left no counts, still running or failed.
+    pub missing: Vec<usize>,
+}
+
+/// Sums every rank's `cells` and `chunks` over MPI, returning the totals
+/// on every rank once all have finished; `None` unless
+/// [`Ranks::from_mpi`] started MPI.
+#[cfg(feature = "mpi")]
+pub fn all_reduce(cells: u64, chunks: usize) -> Option<Gathered> {
+    let universe = UNIVERSE.lock().unwrap();
+    let world = universe.as_ref()?.world();
+    let mut totals = [0u64; 2];
+    world.all_reduce_into(
+        &[cells, chunks as u64][..],
+        &mut totals[..],
+        SystemOperation::sum(),
+    );
+    Some(Gathered {
+        ranks: world.size() as usize,
+        cells: totals[0],
+        chunks: totals[1] as usize,
+        missing: Vec::new(),
+    })
+}
+
+#[cfg(not(feature = "mpi"))]
+pub fn all_reduce(_cells: u64, _chunks: usize) -> Option<Gathered> {
+    None
+}
+
+/// Shuts down MPI if [`Ranks::from_mpi`] started it, or aborts every rank
+/// when this one failed, since the others would wait for it forever.
+#[cfg(feature = "mpi")]
+pub fn finalize_mpi(failed: bool) {
+    if let Some(universe) = UNIVERSE.lock().unwrap().take() {
+        if failed {
+            universe.world().abort(1);
+        }
+    }
+}
+
+#[cfg(not(feature = "mpi"))]
+pub fn finalize_mpi(_failed: bool) {}
+
+/// Reduces the counts the ranks left in the Zarr array at `array`.
+pub fn gather(array: &Path) -> Result<Gathered> {
+    let mut reports = Vec::new();
+    for entry in std::fs::read_dir(array.join("ranks"))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json") {
+            let text = std::fs::read_to_string(&path)?;
+            let report: RankReport = serde_json::from_str(&text)
+                .map_err(|e| Error::InvalidJob(format!("{}: {e}", path.display())))?;
+            reports.push(report);
+        }
+    }
+    let Some(size) = reports.first().map(|report| report.size) else {
+        return Err(Error::InvalidJob(format!(
+            "no rank has written to `{}`",
+            array.display()
+        )));
+    };
+    if reports.iter().any(|report| report.size != size) {
+        return Err(Error::InvalidJob(format!(
+            "`{}` holds parts of runs with different numbers of ranks",
+            array.display()
+        )));
+    }
+    Ok(Gathered {
+        ranks: size,
+        cells: reports.iter().map(|report| report.cells).sum(),
+        chunks: reports.iter().map(|report| report.chunks).sum(),
+        missing: (0..size)
+            .filter(|&rank| reports.iter().all(|report| report.rank != rank))
+            .collect(),
+    })
+}
+
+impl fmt::Display for Gathered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} ranks: {} cells in {} chunks",
+            self.ranks - self.missing.len(),
+            self.ranks,
+            self.cells,
+            self.chunks
+        )?;
+        if !self.missing.is_empty() {
+            let missing: Vec<String> = self.missing.iter().map(usize::to_string).collect();
+            writeln!(f, "missing ranks: {}", missing.join(", "))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+//! Which part of a rule removes which subcells: per-level removal counts
+//! broken down by clause, and the full removal tree for debugging custom
+//! and combined rules.
+//!
+//! A clause names why a subcell is removed. A hybrid's clauses are its
+//! rules: under intersection the first rule that drops the subcell, under
+//! alternation the rule of the level, and under union the combination as
+//! a whole, since every rule must drop it. Other rules are grouped by the
+//! axes along which the subcell's digit is a removed one, as a custom
+//! rule counts them: `x+y` for a Menger tunnel along z, `none` for the
+//! corners Mosely drops.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, Artifact};
+use crate::job::Job;
+use crate::rule::{decompose, expected_cells, AxisRule, Combination, Rule, Split};
+
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
+
+/// Cells each clause of a job's rule removes, level by level.
+#[derive(Clone, Debug, Serialize)]
+pub struct RemovalReport {
+    pub job: String,
+    pub rule: String,
+    pub depth: u32,
+    /// Every clause that removes a subcell at some level.
+    pub clauses: Vec<String>,
+    pub levels: Vec<LevelRemoval>,
+}
+
+/// The cells one subdivision level keeps and removes.
+#[derive(Clone, Debug, Serialize)]
+pub struct LevelRemoval {
+    pub level: u32,
+    pub kept: u64,
+    pub removed: u64,
+    /// Clauses removing anything at the level, in the order of
+    /// [`RemovalReport::clauses`].
+    pub by_clause: Vec<ClauseRemoval>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ClauseRemoval {
+    pub clause: String,
+    /// Subcells of each cell the clause removes.
+    pub subcells: usize,
+    /// Cells removed at the level's resolution.
+    pub cells: u64,
+}
+
+/// The clause of every subcell over one period of a rule's masks.
+struct Clauses {
+    names: Vec<String>,
+    /// Per level of the period, per subcell, the clause removing it.
+    of: Vec<Vec<Option<usize>>>,
+}
+
+impl Clauses {
+    fn at(&self, level: u32) -> &[Option<usize>] {
+        &self.of[(level as usize - 1) % self.of.len()]
+    }
+}
+
+/// One line of the removal tree: the subcells removed when `cell`, at
+/// level `level - 1`, is split.
+#[derive(Serialize)]
+struct TreeNode<'a> {
+    level: u32,
+    /// Coordinates in cells of the parent's size.
+    cell: &'a [usize],
+    /// Flat subcell indices, x fastest, with the index of their clause.
+    removed: &'a [(usize, usize)],
+}
+
+impl Job {
+    /// Counts the cells each clause of the job's rule removes per level.
+    pub fn removal(&self) -> Result<RemovalReport> {
+        let (rule, clauses) = self.clauses()?;
+        let levels = (1..=self.depth)
+            .map(|level| {
+                let of = clauses.at(level);
+                let parents = expected_cells(&rule, level - 1);
+                let by_clause = (0..clauses.names.len())
+                    .filter_map(|clause| {
+                        let subcells = of.iter().filter(|&&c| c == Some(clause)).count();
+                        (subcells > 0).then(|| ClauseRemoval {
+                            clause: clauses.names[clause].clone(),
+                            subcells,
+                            cells: parents.saturating_mul(subcells as u64),
+                        })
+                    })
+                    .collect();
+                LevelRemoval {
+                    level,
+                    kept: expected_cells(&rule, level),
+                    removed: rule.removed(level),
+                    by_clause,
+                }
+            })
+            .collect();
+        Ok(RemovalReport {
+            job: self.display_name(),
+            rule: rule.name().to_string(),
+            depth: self.depth,
+            clauses: clauses.names,
+            levels,
+        })
+    }
+
+    /// Writes the removal tree to `path` as JSON lines: a header with the
+    /// rule, its bases and its clauses, then for every cell kept above
+    /// the last level, parents before their children, the subcells its
+    /// split removes and the clause removing each.
+    #[tracing::instrument(skip_all, fields(path = %path.display()))]
+    pub fn export_removal_tree(&self, path: &Path, cancel: &CancelToken) -> Result<Artifact> {
+        let (rule, clauses) = self.clauses()?;
+        write_file_atomically(path, |out| {
+            #[derive(Serialize)]
+            struct Header<'a> {
+                rule: &'a str,
+                bases: &'a [u32],
+                depth: u32,
+                clauses: &'a [String],
+            }
+            let header = Header {
+                rule: rule.name(),
+                bases: rule.bases(),
+                depth: self.depth,
+                clauses: &clauses.names,
+            };
+            serde_json::to_writer(&mut *out, &header)?;
+            out.write_all(b"\n")?;
+            let mut cell = vec![0; rule.dims()];
+            write_tree(&rule, &clauses, self.depth, 1, &mut cell, out, cancel)
+        })
+    }
+
+    /// The job's rule and the clause of each subcell it removes.
+    fn clauses(&self) -> Result<(Rule, Clauses)> {
+        self.validate()?;
+        if !self.is_rule()? {
+            return Err(Error::InvalidJob(format!(
+                "`{}` is not a subdivision rule fractal",
+                self.fractal
+            )));
+        }
+        let rule = self.rule()?;
+        if rule.split() != Split::Uniform {
+            return Err(Error::InvalidJob(format!(
+                "`{}` does not split uniformly, so its levels do not nest",
+                rule.name()
+            )));
+        }
+        let axes = self.custom.clone().unwrap_or_else(|| AxisRule {
+            bases: rule.bases().to_vec(),
+            remove: Vec::new(),
+            min_removed: 2,
+        });
+        let combined = match &self.combine {
+            Some(combine) => Some((combine.op, combine.rules(self.dims)?)),
+            None => None,
+        };
+        let clause = |level: u32, index: usize, digits: &[u32]| match &combined {
+            Some((Combination::Alternate, rules)) => {
+                rules[(level as usize - 1) % rules.len()].name().to_string()
+            }
+            Some((Combination::Intersection, rules)) => rules
+                .iter()
+                .find(|r| !r.mask(level)[index])
+                .map_or_else(|| rule.name().to_string(), |r| r.name().to_string()),
+            Some((Combination::Union, _)) => rule.name().to_string(),
+            None => {
+                let removed: Vec<&str> = (0..digits.len())
+                    .filter(|&axis| axes.is_removed(axis, digits[axis]))
+                    .map(|axis| AXES[axis])
+                    .collect();
+                if removed.is_empty() {
+                    "none".to_string()
+                } else {
+                    removed.join("+")
+                }
+            }
+        };
+        let mut names: Vec<String> = Vec::new();
+        let mut digits = vec![0; rule.dims()];
+        let of = (1..=rule.period() as u32)
+            .map(|level| {
+                let mask = rule.mask(level);
+                (0..mask.len())
+                    .map(|index| {
+                        if mask[index] {
+                            return None;
+                        }
+                        decompose(index, rule.bases(), &mut digits);
+                        let name = clause(level, index, &digits);
+                        Some(match names.iter().position(|n| *n == name) {
+                            Some(clause) => clause,
+                            None => {
+                                names.push(name);
+                                names.len() - 1
+                            }
+                        })
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok((rule, Clauses { names, of }))
+    }
+}
+
+/// Writes the nodes of the tree below the cell at `level - 1` whose
+/// coordinates `cell` holds, depth first.
+fn write_tree(
+    rule: &Rule,
+    clauses: &Clauses,
+    depth: u32,
+    level: u32,
+    cell: &mut Vec<usize>,
+    out: &mut dyn Write,
+    cancel: &CancelToken,
+) -> Result<()> {
+    if level > depth {
+        return Ok(());
+    }
+    if level + 1 == depth {
+        cancel.check()?;
+    }
+    let of = clauses.at(level);
+    let removed: Vec<(usize, usize)> = of
+        .iter()
+        .enumerate()
+        .filter_map(|(index, clause)| clause.map(|clause| (index, clause)))
+        .collect();
+    let node = TreeNode {
+        level,
+        cell,
+        removed: &removed,
+    };
+    serde_json::to_writer(&mut *out, &node)?;
+    out.write_all(b"\n")?;
+    let parent = cell.clone();
+    let mut digits = vec![0; rule.dims()];
+    for index in (0..of.len()).filter(|&index| of[index].is_none()) {
+        decompose(index, rule.bases(), &mut digits);
+        for (axis, c) in cell.iter_mut().enumerate() {
+            *c = parent[axis] * rule.bases()[axis] as usize + digits[axis] as usize;
+        }
+        write_tree(rule, clauses, depth, level + 1, cell, out, cancel)?;
+    }
+    cell.copy_from_slice(&parent);
+    Ok(())
+}
+
+impl fmt::Display for RemovalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "removal {}: {} rule, {} clause(s)",
+            self.job,
+            self.rule,
+            self.clauses.len()
+        )?;
+        writeln!(
+            f,
+            "  {:>5} {:>12} {:>16} {:>16} {:>9}",
+            "level", "clause", "kept", "removed", "subcells"
+        )?;
+        for level in &self.levels {
+            writeln!(
+                f,
+                "  {:>5} {:>12} {:>16} {:>16}",
+                level.level, "", level.kept, level.removed
+            )?;
+            for clause in &level.by_clause {
+                writeln!(
+                    f,
+                    "  {:>5} {:>12} {:>16} {:>16} {:>9}",
+                    "", clause.clause, "", clause.cells, clause.subcells
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,893 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::bvh::{Bvh, Ray};
+use crate::cancel::CancelToken;
+use crate::color::{Coloring, Colors, FaceColors};
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, Artifact};
+use crate::image::{write_exr, write_png, write_rgb_png, ExrChannel, ExrSamples, Layer};
+use crate::job::Job;
+use crate::lattice::{Boundary, Lattice3};
+use crate::materials::Materials;
+use crate::mesh::{cross, dot, normalize, sub, Mesh};
+use crate::rule::Split;
+
+use octree::Octree;
+
+mod clip;
+mod octree;
+mod path;
+mod scene;
+mod sheet;
+
+pub use scene::{
+    Camera, ClipPlane, Clipping, Cutaway, Light, Material, Pass, PathTracing, Scene, Stereo,
+    StereoMode,
+};
+
+/// A rendered image in linear RGB, row by row from the top.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f64; 3]>,
+    /// What each pixel's centre ray first meets, when the scene asks for
+    /// passes; empty otherwise.
+    pub surfaces: Vec<Option<Surface>>,
+}
+
+/// The face a pixel's centre ray first meets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Surface {
+    /// Distance from the camera along its view direction.
+    pub depth: f64,
+    /// Unit normal in world coordinates, towards the camera.
+    pub normal: [f64; 3],
+    /// The cell's index in the lattice, or the triangle's in the mesh.
+    pub id: u64,
+    /// The face's subdivision level, as materials count them.
+    pub level: u32,
+}
+
+impl Image {
+    /// The pixels in 8-bit sRGB.
+    pub fn srgb(&self) -> Vec<[u8; 3]> {
+        self.pixels.iter().map(|p| p.map(srgb)).collect()
+    }
+
+    /// Writes the image as an 8-bit sRGB PNG.
+    pub fn write_png(&self, out: &mut impl Write) -> Result<()> {
+        write_rgb_png(self.width, self.height, &self.srgb(), out)
+    }
+
+    /// Writes a pass as a PNG: depth as 16-bit grey from the nearest face,
+    /// black, to the farthest and misses, white; normals as 8-bit RGB
+    /// mapping -1..1 to 0..255; cell IDs as 24-bit big-endian RGB, wrapping
+    /// beyond 16 million cells; levels as 8-bit grey.
+    pub fn write_pass_png(&self, pass: Pass, out: &mut impl Write) -> Result<()> {
+        let layer = |bits: u8, samples: Vec<u16>| Layer {
+            width: self.width,
+            height: self.height,
+            bits,
+            samples,
+        };
+        match pass {
+            Pass::Depth => {
+                let depths = self.surfaces.iter().flatten().map(|s| s.depth);
+                let near = depths.clone().fold(f64::INFINITY, f64::min);
+                let range = depths.fold(f64::NEG_INFINITY, f64::max) - near;
+                let samples = self
+                    .surfaces
+                    .iter()
+                    .map(|surface| match surface {
+                        Some(s) if range > 0.0 => ((s.depth - near) / range * 65534.0) as u16,
+                        Some(_) => 0,
+                        None => u16::MAX,
+                    })
+                    .collect();
+                write_png(&layer(16, samples), out)
+            }
+            Pass::Normal => {
+                let pixels: Vec<[u8; 3]> = self
+                    .surfaces
+                    .iter()
+                    .map(|surface| match surface {
+                        Some(s) => s.normal.map(|c| ((c + 1.0) * 127.5).round() as u8),
+                        None => [0; 3],
+                    })
+                    .collect();
+                write_rgb_png(self.width, self.height, &pixels, out)
+            }
+            Pass::CellId => {
+                let pixels: Vec<[u8; 3]> = self
+                    .surfaces
+                    .iter()
+                    .map(|surface| {
+                        let [_, _, _, _, _, r, g, b] =
+                            surface.map_or(0, |s| s.id + 1).to_be_bytes();
+                        [r, g, b]
+                    })
+                    .collect();
+                write_rgb_png(self.width, self.height, &pixels, out)
+            }
+            Pass::Level => {
+                let samples = self
+                    .surfaces
+                    .iter()
+                    .map(|surface| surface.map_or(0, |s| (s.level + 1).min(255) as u16))
+                    .collect();
+                write_png(&layer(8, samples), out)
+            }
+        }
+    }
+
+    /// Writes the image as OpenEXR: linear `R`, `G` and `B` as 32-bit
+    /// floats, with depth as `Z` (infinite for misses), normals as `N.X`,
+    /// `N.Y` and `N.Z`, and cell IDs and levels as unsigned integers `id`
+    /// and `level`.
+    pub fn write_exr(&self, passes: &[Pass], out: &mut impl Write) -> Result<()> {
+        let float = |name: &str, value: &dyn Fn(usize) -> f64| ExrChannel {
+            name: name.into(),
+            samples: ExrSamples::Float((0..self.pixels.len()).map(|i| value(i) as f32).collect()),
+        };
+        let uint = |name: &str, value: &dyn Fn(&Surface) -> u64| ExrChannel {
+            name: name.into(),
+            samples: ExrSamples::Uint(
+                self.surfaces
+                    .iter()
+                    .map(|surface| surface.as_ref().map_or(0, value) as u32)
+                    .collect(),
+            ),
+        };
+        let mut channels: Vec<ExrChannel> = ["R", "G", "B"]
+            .iter()
+            .enumerate()
+            .map(|(c, name)| float(name, &|i| self.pixels[i][c]))
+            .collect();
+        for pass in passes {
+            match pass {
+                Pass::Depth => channels.push(float("Z", &|i| {
+                    self.surfaces[i].map_or(f64::INFINITY, |s| s.depth)
+                })),
+                Pass::Normal => {
+                    for (c, name) in ["N.X", "N.Y", "N.Z"].iter().enumerate() {
+                        channels.push(float(name, &|i| {
+                            self.surfaces[i].map_or(0.0, |s| s.normal[c])
+                        }));
+                    }
+                }
+                Pass::CellId => channels.push(uint("id", &|s| s.id + 1)),
+                Pass::Level => channels.push(uint("level", &|s| s.level as u64 + 1)),
+            }
+        }
+        write_exr(self.width, self.height, &channels, out)
+    }
+
+    /// Writes the image to `path`, as OpenEXR holding `passes` when its
+    /// extension is `.exr`, otherwise as PNG with a PNG per pass beside it.
+    pub(crate) fn write(&self, passes: &[Pass], path: &Path) -> Result<Vec<Artifact>> {
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("exr"))
+        {
+            let artifact = write_file_atomically(path, |out| self.write_exr(passes, out))?;
+            return Ok(vec![artifact]);
+        }
+        let mut artifacts = vec![write_file_atomically(path, |out| self.write_png(out))?];
+        for &pass in passes {
+            artifacts.push(write_file_atomically(&pass_path(path, pass), |out| {
+                self.write_pass_png(pass, out)
+            })?);
+        }
+        Ok(artifacts)
+    }
+}
+
+/// The file a pass of the PNG render at `path` is written to: the pass's
+/// name before the extension, as in `render.depth.png`.
+pub fn pass_path(path: &Path, pass: Pass) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{stem}.{}.png", pass.name()))
+}
+
+/// The subdivision whose grid decides which material a face gets: a face
+/// on a plane first cut at level `l` of `depth` splits into `base` parts
+/// takes material `l`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Levels {
+    pub base: usize,
+    pub depth: u32,
+}
+
+impl Levels {
+    /// The coarsest level whose grid contains the plane `plane` cells from
+    /// the lattice's edge.
+    pub fn of(&self, mut plane: usize) -> u32 {
+        let mut level = self.depth;
+        while level > 0 && plane.is_multiple_of(self.base) {
+            plane /= self.base;
+            level -= 1;
+        }
+        level
+    }
+}
+
+impl Job {
+    /// Renders the job's lattice or surface as `scene` describes to a PNG
+    /// or OpenEXR image at `path`, with the scene's passes. 4D fractals are
+    /// rendered at their w slice `w`, the middle one when none is given.
+    /// Faces are coloured by depth for rules split evenly along every axis,
+    /// or by the job's colour map or materials when the scene has no
+    /// colour map.
+    pub fn render(
+        &self,
+        scene: &Scene,
+        w: Option<usize>,
+        path: &Path,
+        cancel: &CancelToken,
+    ) -> Result<Vec<Artifact>> {
+        self.validate()?;
+        scene.validate()?;
+        let mut scene = scene.clone();
+        if scene.color.is_none() {
+            scene.color = self.color.clone();
+        }
+        let image = self.subject(w, cancel)?.draw(&scene, cancel)?;
+        image.write(&scene.passes, path)
+    }
+
+    /// The lattice or surface to render, with the levels and materials
+    /// faces of a lattice are coloured by and its place along w.
+    fn subject(&self, w: Option<usize>, cancel: &CancelToken) -> Result<Subject> {
+        if let Some(surface) = self.surface()? {
+            return Ok(Subject::Surface(Triangles::new(
+                &surface.build(self.depth, cancel)?,
+            )));
+        }
+        let (lattice, along) = match self.dims {
+            3 => (self.generate::<3>(cancel)?, 0.0),
+            _ => {
+                let lattice = self.generate::<4>(cancel)?;
+                let side = lattice.shape()[3];
+                let w = w.unwrap_or(side / 2);
+                if w >= side {
+                    return Err(Error::InvalidJob(format!(
+                        "slice w={w} is outside the lattice (side {side})"
+                    )));
+                }
+                (lattice.slice_w(w), w as f64 / (side - 1).max(1) as f64)
+            }
+        };
+        Ok(Subject::Cells(
+            lattice,
+            self.levels()?,
+            along,
+            self.materials()?,
+        ))
+    }
+
+    /// The levels faces are coloured by, for rules split evenly along
+    /// every axis.
+    pub(crate) fn levels(&self) -> Result<Option<Levels>> {
+        if !self.is_rule()? {
+            return Ok(None);
+        }
+        let rule = self.rule()?;
+        Ok(
+            (rule.split() == Split::Uniform && rule.is_isotropic()).then(|| Levels {
+                base: rule.bases()[0] as usize,
+                depth: self.depth,
+            }),
+        )
+    }
+}
+
+/// What a job renders.
+enum Subject {
+    Cells(Lattice3, Option<Levels>, f64, Option<Materials>),
+    Surface(Triangles),
+}
+
+impl Subject {
+    fn draw(&self, scene: &Scene, cancel: &CancelToken) -> Result<Image> {
+        match self {
+            Subject::Cells(lattice, levels, _, Some(materials)) if scene.color.is_none() => {
+                render_colored(lattice, *levels, Some(materials), scene, cancel)
+            }
+            Subject::Cells(lattice, levels, w, _) => render_at(lattice, *levels, *w, scene, cancel),
+            Subject::Surface(triangles) => draw(triangles, scene, cancel),
+        }
+    }
+}
+
+/// Draws `lattice` as `scene` describes: ray cast, with diffuse faces lit
+/// by the scene's lights and hard shadows from directional ones, or path
+/// traced when the scene asks for it. Rays step through the cells
+/// directly.
+pub fn render(
+    lattice: &Lattice3,
+    levels: Option<Levels>,
+    scene: &Scene,
+    cancel: &CancelToken,
+) -> Result<Image> {
+    render_at(lattice, levels, 0.0, scene, cancel)
+}
+
+/// Like [`render`], for a slice `w` of the way along its 4D fractal's w
+/// axis, which the scene's colour map may colour by.
+pub fn render_at(
+    lattice: &Lattice3,
+    levels: Option<Levels>,
+    w: f64,
+    scene: &Scene,
+    cancel: &CancelToken,
+) -> Result<Image> {
+    let coloring = scene.color.clone().map(|map| {
+        let colors = Colors { map, levels, w };
+        Coloring::new(colors, lattice, Boundary::Open)
+    });
+    let faces = coloring
+        .as_ref()
+        .map(|coloring| coloring as &dyn FaceColors);
+    render_colored(lattice, levels, faces, scene, cancel)
+}
+
+/// Like [`render`], with faces coloured by `faces`, such as a job's
+/// [`Materials`], rather than by the scene's materials.
+#[tracing::instrument(name = "render", skip_all, fields(cells = lattice.len()))]
+pub fn render_colored(
+    lattice: &Lattice3,
+    levels: Option<Levels>,
+    faces: Option<&dyn FaceColors>,
+    scene: &Scene,
+    cancel: &CancelToken,
+) -> Result<Image> {
+    draw(
+        &Grid::new(lattice, levels, scene.clipping.as_ref(), faces),
+        scene,
+        cancel,
+    )
+}
+
+/// Like [`render`], for a mesh such as a surface fractal, placed in the
+/// same world coordinates as a lattice and intersected through a [`Bvh`].
+/// Every face takes the first material.
+#[tracing::instrument(name = "render", skip_all, fields(faces = mesh.face_count()))]
+pub fn render_mesh(mesh: &Mesh, scene: &Scene, cancel: &CancelToken) -> Result<Image> {
+    draw(&Triangles::new(mesh), scene, cancel)
+}
+
+fn draw(geometry: &dyn Geometry, scene: &Scene, cancel: &CancelToken) -> Result<Image> {
+    let camera = &scene.camera;
+    let Some(stereo) = &camera.stereo else {
+        return draw_view(geometry, &View::new(camera, 0.0), scene, cancel);
+    };
+    let eye = |side: f64| View::new(camera, side * stereo.separation / 2.0);
+    let left = draw_view(geometry, &eye(-1.0), scene, cancel)?;
+    let right = draw_view(geometry, &eye(1.0), scene, cancel)?;
+    Ok(match stereo.mode {
+        StereoMode::SideBySide => Image {
+            width: 2 * left.width,
+            height: left.height,
+            pixels: side_by_side(&left.pixels, &right.pixels, left.width),
+            surfaces: side_by_side(&left.surfaces, &right.surfaces, left.width),
+        },
+        // Passes follow the left eye.
+        StereoMode::Anaglyph => Image {
+            pixels: left
+                .pixels
+                .iter()
+                .zip(&right.pixels)
+                .map(|(l, r)| [l[0], r[1], r[2]])
+                .collect(),
+            ..left
+        },
+    })
+}
+
+/// Rows of `width` from `left` followed by the same rows of `right`.
+fn side_by_side<T: Copy>(left: &[T], right: &[T], width: usize) -> Vec<T> {
+    left.chunks(width.max(1))
+        .zip(right.chunks(width.max(1)))
+        .flat_map(|(l, r)| l.iter().chain(r).copied())
+        .collect()
+}
+
+/// The image one view sees.
+fn draw_view(
+    geometry: &dyn Geometry,
+    view: &View,
+    scene: &Scene,
+    cancel: &CancelToken,
+) -> Result<Image> {
+    let pixels = match &scene.path_tracing {
+        Some(settings) => path::trace(geometry, view, scene, settings, cancel)?,
+        None => cast(geometry, view, scene, cancel)?,
+    };
+    let surfaces = if scene.passes.is_empty() {
+        Vec::new()
+    } else {
+        surfaces(geometry, view, cancel)?
+    };
+    Ok(Image {
+        width: view.width,
+        height: view.height,
+        pixels,
+        surfaces,
+    })
+}
+
+/// What the ray through each pixel's centre first meets.
+fn surfaces(
+    geometry: &dyn Geometry,
+    view: &View,
+    cancel: &CancelToken,
+) -> Result<Vec<Option<Surface>>> {
+    let mut surfaces = Vec::with_capacity(view.width * view.height);
+    for row in 0..view.height {
+        cancel.check()?;
+        for column in 0..view.width {
+            let (origin, direction) = view.ray(column as f64 + 0.5, row as f64 + 0.5);
+            surfaces.push(geometry.hit(origin, direction).map(|hit| Surface {
+                depth: hit.t * dot(direction, view.forward),
+                normal: hit.normal,
+                id: hit.id,
+                level: hit.level,
+            }));
+        }
+    }
+    Ok(surfaces)
+}
+
+/// What rays are traced against, in world coordinates.
+trait Geometry: Sync {
+    /// The first face along the unit `direction` from `origin`.
+    fn hit(&self, origin: [f64; 3], direction: [f64; 3]) -> Option<Hit>;
+
+    /// Whether anything lies along the unit `direction` from `origin`.
+    fn blocked(&self, origin: [f64; 3], direction: [f64; 3]) -> bool {
+        self.hit(origin, direction).is_some()
+    }
+}
+
+/// Where a ray first meets a face.
+struct Hit {
+    /// Distance along the ray.
+    t: f64,
+    /// The face's unit normal, on the side the ray came from.
+    normal: [f64; 3],
+    /// The material the face takes.
+    level: u32,
+    /// The colour the scene's colour map gives the face, in place of its
+    /// material's.
+    color: Option<[f64; 3]>,
+    /// The cell's or triangle's index.
+    id: u64,
+    /// Whether the face is a cut through a cell, in the cap colour.
+    cap: bool,
+}
+
+impl Hit {
+    /// The linear RGB albedo of the face.
+    fn albedo(&self, scene: &Scene) -> [f64; 3] {
+        match (&scene.clipping, self.cap) {
+            (Some(clipping), true) => clipping.cap,
+            _ => self
+                .color
+                .unwrap_or_else(|| scene.material(self.level).color),
+        }
+    }
+}
+
+fn cast(
+    geometry: &dyn Geometry,
+    view: &View,
+    scene: &Scene,
+    cancel: &CancelToken,
+) -> Result<Vec<[f64; 3]>> {
+    let mut pixels = Vec::with_capacity(view.width * view.height);
+    for row in 0..view.height {
+        cancel.check()?;
+        for column in 0..view.width {
+            let (origin, direction) = view.ray(column as f64 + 0.5, row as f64 + 0.5);
+            let color = match geometry.hit(origin, direction) {
+                Some(hit) => shade(geometry, &hit, origin, direction, scene),
+                None => scene.background,
+            };
+            pixels.push(color);
+        }
+    }
+    Ok(pixels)
+}
+
+/// The camera's rays.
+struct View {
+    origin: [f64; 3],
+    forward: [f64; 3],
+    right: [f64; 3],
+    up: [f64; 3],
+    /// Horizontal offset of the image's centre, in the same units as
+    /// `half_width`, keeping an eye's view converged on the target.
+    shift: f64,
+    /// Whether rays run parallel from points across the image, whose
+    /// half sides are then in world units rather than slopes.
+    orthographic: bool,
+    half_width: f64,
+    half_height: f64,
+    width: usize,
+    height: usize,
+}
+
+impl View {
+    /// The camera's view from `eye` world units to its right, looking
+    /// parallel to it and converging on its target.
+    fn new(camera: &Camera, eye: f64) -> Self {
+        let towards = sub(camera.target, camera.position);
+        let forward = normalize(towards);
+        let right = normalize(cross(forward, camera.up));
+        let half_height = match camera.orthographic {
+            Some(height) => height / 2.0,
+            None => (camera.fov.to_radians() / 2.0).tan(),
+        };
+        View {
+            origin: std::array::from_fn(|a| camera.position[a] + eye * right[a]),
+            forward,
+            right,
+            up: cross(right, forward),
+            shift: -eye / dot(towards, towards).sqrt(),
+            orthographic: camera.orthographic.is_some(),
+            half_width: half_height * camera.width as f64 / camera.height as f64,
+            half_height,
+            width: camera.width,
+            height: camera.height,
+        }
+    }
+
+    /// The origin and unit direction of the ray through the point `x`
+    /// pixels from the image's left edge and `y` from its top.
+    fn ray(&self, x: f64, y: f64) -> ([f64; 3], [f64; 3]) {
+        let x = (2.0 * x / self.width as f64 - 1.0) * self.half_width + self.shift;
+        let y = (1.0 - 2.0 * y / self.height as f64) * self.half_height;
+        if self.orthographic {
+            let origin =
+                std::array::from_fn(|a| self.origin[a] + x * self.right[a] + y * self.up[a]);
+            return (origin, self.forward);
+        }
+        let direction =
+            std::array::from_fn(|a| self.forward[a] + x * self.right[a] + y * self.up[a]);
+        (self.origin, normalize(direction))
+    }
+}
+
+fn shade(
+    geometry: &dyn Geometry,
+    hit: &Hit,
+    origin: [f64; 3],
+    direction: [f64; 3],
+    scene: &Scene,
+) -> [f64; 3] {
+    let normal = hit.normal;
+    let albedo = hit.albedo(scene);
+    // Lift the point off the face so shadow rays start in empty space.
+    let point: [f64; 3] =
+        std::array::from_fn(|a| origin[a] + direction[a] * hit.t + normal[a] * 1e-6);
+    let mut light = [0.0; 3];
+    for source in &scene.lights {
+        let (color, intensity, cosine) = match source {
+            Light::Ambient { color, intensity } => (color, intensity, 1.0),
+            Light::Directional {
+                direction,
+                color,
+                intensity,
+                ..
+            } => {
+                let towards = normalize(direction.map(|c| -c));
+                let cosine = dot(normal, towards);
+                if cosine <= 0.0 || geometry.blocked(point, towards) {
+                    continue;
+                }
+                (color, intensity, cosine)
+            }
+        };
+        for a in 0..3 {
+            light[a] += color[a] * intensity * cosine;
+        }
+    }
+    std::array::from_fn(|a| albedo[a] * light[a])
+}
+
+/// The lattice placed in world coordinates: centred on the origin, its
+/// longest side spanning -1 to 1.
+struct Grid<'a> {
+    lattice: &'a Lattice3,
+    /// Lets rays skip the empty stretches around and within deep fractals.
+    octree: Octree,
+    levels: Option<Levels>,
+    clipping: Option<&'a Clipping>,
+    faces: Option<&'a dyn FaceColors>,
+    corner: [f64; 3],
+    cell: f64,
+}
+
+/// Where a ray first meets a filled cell.
+struct Crossing {
+    /// Distance along the ray, in cells.
+    t: f64,
+    cell: [usize; 3],
+    /// The axis of the face the ray entered through.
+    axis: usize,
+    /// Whether that face looks along the axis rather than against it.
+    positive: bool,
+    /// Whether the ray entered the cell through a cut rather than a face.
+    cut: bool,
+}
+
+impl Crossing {
+    fn normal(&self) -> [f64; 3] {
+        let mut normal = [0.0; 3];
+        normal[self.axis] = if self.positive { 1.0 } else { -1.0 };
+        normal
+    }
+
+    /// The face's plane, in cells from the lattice's low edge along its
+    /// axis.
+    fn plane(&self) -> usize {
+        self.cell[self.axis] + self.positive as usize
+    }
+}
+
+impl<'a> Grid<'a> {
+    fn new(
+        lattice: &'a Lattice3,
+        levels: Option<Levels>,
+        clipping: Option<&'a Clipping>,
+        faces: Option<&'a dyn FaceColors>,
+    ) -> Self {
+        let shape = lattice.shape();
+        let cell = 2.0 / shape.into_iter().max().unwrap_or(1).max(1) as f64;
+        Grid {
+            lattice,
+            octree: Octree::new(lattice),
+            levels,
+            clipping,
+            faces,
+            corner: shape.map(|n| -(n as f64) * cell / 2.0),
+            cell,
+        }
+    }
+
+    /// A world point in cells from the lattice's low corner.
+    fn to_cells(&self, p: [f64; 3]) -> [f64; 3] {
+        std::array::from_fn(|a| (p[a] - self.corner[a]) / self.cell)
+    }
+
+    /// Steps a ray from `origin`, in cells, along the unit `direction`
+    /// through the cells it crosses until one is filled, crossing empty
+    /// blocks of the octree whole. Only the stretch from `start` to `end`,
+    /// in cells along the ray, is looked at.
+    fn march(
+        &self,
+        origin: [f64; 3],
+        direction: [f64; 3],
+        start: f64,
+        end: f64,
+    ) -> Option<Crossing> {
+        let shape = self.lattice.shape();
+        // Clip the ray to the lattice's box.
+        let (mut near, mut far, mut axis) = (0.0_f64, end, 0);
+        for a in 0..3 {
+            let extent = shape[a] as f64;
+            if direction[a] == 0.0 {
+                if origin[a] < 0.0 || origin[a] >= extent {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (0.0 - origin[a]) / direction[a];
+            let t1 = (extent - origin[a]) / direction[a];
+            let (enter, exit) = (t0.min(t1), t0.max(t1));
+            if enter > near {
+                near = enter;
+                axis = a;
+            }
+            far = far.min(exit);
+        }
+        let mut cut = start > near;
+        near = near.max(start);
+        if near > far {
+            return None;
+        }
+        let mut cell = [0usize; 3];
+        let mut step = [0i64; 3];
+        let mut next = [f64::INFINITY; 3];
+        let mut delta = [f64::INFINITY; 3];
+        for a in 0..3 {
+            let p = origin[a] + direction[a] * near;
+            cell[a] = (p.floor().max(0.0) as usize).min(shape[a].saturating_sub(1));
+            if direction[a] > 0.0 {
+                step[a] = 1;
+                next[a] = (cell[a] as f64 + 1.0 - origin[a]) / direction[a];
+            } else if direction[a] < 0.0 {
+                step[a] = -1;
+                next[a] = (cell[a] as f64 - origin[a]) / direction[a];
+            }
+            delta[a] = 1.0 / direction[a].abs();
+        }
+        let mut t = near;
+        loop {
+            if t > far {
+                return None;
+            }
+            if self.lattice.get(cell) {
+                return Some(Crossing {
+                    t,
+                    cell,
+                    axis,
+                    positive: step[axis] < 0,
+                    cut,
+                });
+            }
+            cut = false;
+            if let Some(side) = self.octree.empty_block(cell) {
+                // Leave the block, cut off at the lattice's edge, through the
+                // face the ray meets first, and resume in the cell beyond it.
+                let low = cell.map(|c| c / side * side);
+                let high: [usize; 3] = std::array::from_fn(|a| (low[a] + side).min(shape[a]));
+                let exits: [f64; 3] = std::array::from_fn(|a| match step[a] {
+                    1 => (high[a] as f64 - origin[a]) / direction[a],
+                    -1 => (low[a] as f64 - origin[a]) / direction[a],
+                    _ => f64::INFINITY,
+                });
+                axis = (0..3)
+                    .min_by(|&a, &b| exits[a].total_cmp(&exits[b]))
+                    .expect("three axes");
+                t = exits[axis];
+                let moved = match step[axis] {
+                    1 => high[axis] as i64,
+                    _ => low[axis] as i64 - 1,
+                };
+                if moved < 0 || moved >= shape[axis] as i64 {
+                    return None;
+                }
+                for a in 0..3 {
+                    cell[a] = if a == axis {
+                        moved as usize
+                    } else {
+                        let p = origin[a] + direction[a] * t;
+                        (p.floor().max(0.0) as usize).clamp(low[a], high[a] - 1)
+                    };
+                    next[a] = match step[a] {
+                        1 => (cell[a] as f64 + 1.0 - origin[a]) / direction[a],
+                        -1 => (cell[a] as f64 - origin[a]) / direction[a],
+                        _ => f64::INFINITY,
+                    };
+                }
+                continue;
+            }
+            axis = (0..3)
+                .min_by(|&a, &b| next[a].total_cmp(&next[b]))
+                .expect("three axes");
+            t = next[axis];
+            let moved = cell[axis] as i64 + step[axis];
+            if moved < 0 || moved >= shape[axis] as i64 {
+                return None;
+            }
+            cell[axis] = moved as usize;
+            next[axis] += delta[axis];
+        }
+    }
+}
+
+impl Geometry for Grid<'_> {
+    fn hit(&self, origin: [f64; 3], direction: [f64; 3]) -> Option<Hit> {
+        let cells = self.to_cells(origin);
+        let Some(clipping) = self.clipping else {
+            let crossing = self.march(cells, direction, 0.0, f64::INFINITY)?;
+            return Some(self.hit_at(&crossing, None));
+        };
+        clipping
+            .spans(origin, direction)
+            .into_iter()
+            .find_map(|span| {
+                let (start, end) = (span.start / self.cell, span.end / self.cell);
+                let crossing = self.march(cells, direction, start, end)?;
+                Some(self.hit_at(&crossing, span.cut))
+            })
+    }
+}
+
+impl Grid<'_> {
+    /// The hit at `crossing`, on the cut with unit normal `cut` when the
+    /// ray entered through one.
+    fn hit_at(&self, crossing: &Crossing, cut: Option<[f64; 3]>) -> Hit {
+        let id = self.lattice.index(crossing.cell) as u64;
+        match cut.filter(|_| crossing.cut) {
+            Some(normal) => Hit {
+                t: crossing.t * self.cell,
+                normal,
+                level: 0,
+                color: None,
+                id,
+                cap: true,
+            },
+            None => Hit {
+                t: crossing.t * self.cell,
+                normal: crossing.normal(),
+                level: self.levels.map_or(0, |levels| levels.of(crossing.plane())),
+                color: self
+                    .faces
+                    .map(|faces| faces.face(crossing.cell, crossing.axis, crossing.positive)),
+                id,
+                cap: false,
+            },
+        }
+    }
+}
+
+/// A mesh already placed in world coordinates.
+struct Triangles(Bvh);
+
+impl Triangles {
+    /// Places `mesh` centred on the origin, its longest side spanning -1
+    /// to 1.
+    fn new(mesh: &Mesh) -> Self {
+        let mut min = [f64::INFINITY; 3];
+        let mut max = [f64::NEG_INFINITY; 3];
+        for v in &mesh.vertices {
+            for a in 0..3 {
+                min[a] = min[a].min(v[a]);
+                max[a] = max[a].max(v[a]);
+            }
+        }
+        let longest = mesh.extent().into_iter().fold(0.0, f64::max);
+        let scale = if longest > 0.0 { 2.0 / longest } else { 1.0 };
+        let mut mesh = mesh.clone();
+        for v in &mut mesh.vertices {
+            *v = std::array::from_fn(|a| (v[a] - (min[a] + max[a]) / 2.0) * scale);
+        }
+        Triangles(Bvh::build(&mesh))
+    }
+
+    fn ray(origin: [f64; 3], direction: [f64; 3]) -> Ray {
+        Ray {
+            origin,
+            direction,
+            max: f64::INFINITY,
+        }
+    }
+}
+
+impl Geometry for Triangles {
+    fn hit(&self, origin: [f64; 3], direction: [f64; 3]) -> Option<Hit> {
+        let hit = self.0.intersect(&Triangles::ray(origin, direction))?;
+        let normal = if dot(hit.normal, direction) > 0.0 {
+            hit.normal.map(|c| -c)
+        } else {
+            hit.normal
+        };
+        Some(Hit {
+            t: hit.t,
+            normal,
+            level: 0,
+            color: None,
+            id: hit.triangle as u64,
+            cap: false,
+        })
+    }
+
+    fn blocked(&self, origin: [f64; 3], direction: [f64; 3]) -> bool {
+        self.0.occluded(&Triangles::ray(origin, direction))
+    }
+}
+
+/// A linear channel in 8-bit sRGB.
+fn srgb(linear: f64) -> u8 {
+    let c = linear.clamp(0.0, 1.0);
+    let encoded = if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
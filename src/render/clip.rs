@@ -0,0 +1,84 @@
+use super::Clipping;
+use crate::mesh::{dot, normalize};
+
+/// A stretch of a ray that clipping keeps.
+pub(super) struct Span {
+    pub(super) start: f64,
+    pub(super) end: f64,
+    /// The unit normal, facing the ray, of the cut the span starts on;
+    /// `None` when it starts at the ray's origin.
+    pub(super) cut: Option<[f64; 3]>,
+}
+
+impl Clipping {
+    /// The stretches of the ray from `origin` along the unit `direction`
+    /// left by the planes and the cutaway, nearest first.
+    pub(super) fn spans(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Span> {
+        let mut kept = Span {
+            start: 0.0,
+            end: f64::INFINITY,
+            cut: None,
+        };
+        for plane in &self.planes {
+            let normal = normalize(plane.normal);
+            let along = dot(normal, direction);
+            let height = dot(normal, origin) - plane.offset;
+            if along == 0.0 {
+                if height > 0.0 {
+                    return Vec::new();
+                }
+                continue;
+            }
+            let t = -height / along;
+            if along > 0.0 {
+                kept.end = kept.end.min(t);
+            } else if t > kept.start {
+                kept.start = t;
+                kept.cut = Some(normal);
+            }
+        }
+        if kept.start >= kept.end {
+            return Vec::new();
+        }
+        let Some(cutaway) = &self.cutaway else {
+            return vec![kept];
+        };
+        // The stretch inside the box, and the wall the ray leaves through.
+        let (mut enter, mut leave, mut wall) = (f64::NEG_INFINITY, f64::INFINITY, [0.0; 3]);
+        for a in 0..3 {
+            if direction[a] == 0.0 {
+                if origin[a] <= cutaway.min[a] || origin[a] >= cutaway.max[a] {
+                    return vec![kept];
+                }
+                continue;
+            }
+            let t0 = (cutaway.min[a] - origin[a]) / direction[a];
+            let t1 = (cutaway.max[a] - origin[a]) / direction[a];
+            enter = enter.max(t0.min(t1));
+            if t0.max(t1) < leave {
+                leave = t0.max(t1);
+                wall = [0.0; 3];
+                wall[a] = -direction[a].signum();
+            }
+        }
+        if enter >= leave || leave <= kept.start || enter >= kept.end {
+            return vec![kept];
+        }
+        let mut spans = Vec::new();
+        if enter > kept.start {
+            spans.push(Span {
+                start: kept.start,
+                end: enter,
+                cut: kept.cut,
+            });
+        }
+        if leave < kept.end {
+            spans.push(Span {
+                start: leave,
+                end: kept.end,
+                cut: Some(wall),
+            });
+        }
+        spans
+    }
+}
@@ -0,0 +1,71 @@
+use crate::lattice::Lattice3;
+
+/// Which blocks of a lattice hold any filled cell, at every power of two
+/// from 2 cells a side up to one block over the whole lattice, so a ray
+/// can step over an empty block in one go instead of cell by cell.
+pub(super) struct Octree {
+    /// Level `k - 1` holds blocks of `2^k` cells a side.
+    levels: Vec<Level>,
+}
+
+struct Level {
+    shape: [usize; 3],
+    filled: Vec<bool>,
+}
+
+impl Level {
+    /// Blocks of two cells a side of a level `shape` cells across, marked
+    /// filled over each of `cells`.
+    fn over(shape: [usize; 3], cells: impl Iterator<Item = [usize; 3]>) -> Self {
+        let shape = shape.map(|n| n.div_ceil(2));
+        let mut level = Level {
+            shape,
+            filled: vec![false; shape.iter().product()],
+        };
+        for cell in cells {
+            let index = level.index(cell.map(|c| c / 2));
+            level.filled[index] = true;
+        }
+        level
+    }
+
+    fn index(&self, p: [usize; 3]) -> usize {
+        (p[2] * self.shape[1] + p[1]) * self.shape[0] + p[0]
+    }
+
+    fn filled(&self) -> impl Iterator<Item = [usize; 3]> + '_ {
+        let [nx, ny, _] = self.shape;
+        (0..self.filled.len())
+            .filter(|&i| self.filled[i])
+            .map(move |i| [i % nx, i / nx % ny, i / (nx * ny)])
+    }
+}
+
+impl Octree {
+    pub(super) fn new(lattice: &Lattice3) -> Self {
+        let mut levels: Vec<Level> = Vec::new();
+        let mut shape = lattice.shape();
+        while shape.iter().any(|&n| n > 1) {
+            let level = match levels.last() {
+                Some(below) => Level::over(shape, below.filled()),
+                None => Level::over(shape, lattice.iter()),
+            };
+            shape = level.shape;
+            levels.push(level);
+        }
+        Octree { levels }
+    }
+
+    /// The side, in cells, of the largest empty block holding `cell`, or
+    /// `None` when its smallest block holds a filled cell.
+    pub(super) fn empty_block(&self, cell: [usize; 3]) -> Option<usize> {
+        let mut side = None;
+        for (k, level) in self.levels.iter().enumerate() {
+            if level.filled[level.index(cell.map(|c| c >> (k + 1)))] {
+                break;
+            }
+            side = Some(2 << k);
+        }
+        side
+    }
+}
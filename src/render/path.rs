@@ -0,0 +1,162 @@
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::{Geometry, Light, PathTracing, Scene, View};
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::mesh::{cross, dot, normalize};
+use crate::random::Rng;
+
+/// Path traces the image, rows shared between the machine's cores.
+///
+/// Faces are Lambertian. Directional lights are sampled at every hit over
+/// their apparent disc, which softens shadows; the background is seen by
+/// the camera and lights the lattice from every direction, so ambient
+/// lights are left out.
+pub(super) fn trace(
+    geometry: &dyn Geometry,
+    view: &View,
+    scene: &Scene,
+    settings: &PathTracing,
+    cancel: &CancelToken,
+) -> Result<Vec<[f64; 3]>> {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .clamp(1, view.height);
+    let next = AtomicUsize::new(0);
+    let rows = Mutex::new((0..view.height).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let row = next.fetch_add(1, Ordering::Relaxed);
+                if row >= view.height {
+                    break;
+                }
+                let result = cancel.check().map(|()| {
+                    (0..view.width)
+                        .map(|column| pixel(geometry, view, scene, settings, column, row))
+                        .collect::<Vec<_>>()
+                });
+                rows.lock().unwrap()[row] = Some(result);
+            });
+        }
+    });
+    let rows = rows
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|row| row.expect("every row is traced"))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(rows.concat())
+}
+
+/// The mean radiance of the pixel's samples, each through a random point
+/// of the pixel. Every pixel draws from its own stream, so the image does
+/// not depend on how rows are shared out.
+fn pixel(
+    geometry: &dyn Geometry,
+    view: &View,
+    scene: &Scene,
+    settings: &PathTracing,
+    column: usize,
+    row: usize,
+) -> [f64; 3] {
+    let mut rng = Rng::new(settings.seed, (row * view.width + column) as u64);
+    let mut sum = [0.0; 3];
+    for _ in 0..settings.samples {
+        let (origin, direction) = view.ray(column as f64 + rng.unit(), row as f64 + rng.unit());
+        let radiance = radiance(geometry, origin, direction, scene, settings, &mut rng);
+        for a in 0..3 {
+            sum[a] += radiance[a];
+        }
+    }
+    sum.map(|c| c / settings.samples as f64)
+}
+
+/// The light arriving at `origin` from along `direction`.
+fn radiance(
+    geometry: &dyn Geometry,
+    mut origin: [f64; 3],
+    mut direction: [f64; 3],
+    scene: &Scene,
+    settings: &PathTracing,
+    rng: &mut Rng,
+) -> [f64; 3] {
+    let mut total = [0.0; 3];
+    let mut throughput = [1.0; 3];
+    for bounce in 0..=settings.bounces {
+        let Some(hit) = geometry.hit(origin, direction) else {
+            for a in 0..3 {
+                total[a] += throughput[a] * scene.background[a];
+            }
+            break;
+        };
+        let normal = hit.normal;
+        let albedo = hit.albedo(scene);
+        for a in 0..3 {
+            throughput[a] *= albedo[a];
+        }
+        origin = std::array::from_fn(|a| origin[a] + direction[a] * hit.t + normal[a] * 1e-6);
+        for light in &scene.lights {
+            let Light::Directional {
+                direction,
+                color,
+                intensity,
+                angle,
+            } = light
+            else {
+                continue;
+            };
+            let towards = cone(
+                normalize(direction.map(|c| -c)),
+                angle.to_radians() / 2.0,
+                rng,
+            );
+            let cosine = dot(normal, towards);
+            if cosine <= 0.0 || geometry.blocked(origin, towards) {
+                continue;
+            }
+            for a in 0..3 {
+                total[a] += throughput[a] * color[a] * intensity * cosine;
+            }
+        }
+        if bounce == settings.bounces {
+            break;
+        }
+        direction = hemisphere(normal, rng);
+    }
+    total
+}
+
+/// A direction within `half_angle` radians of `axis`, uniform over the
+/// solid angle.
+fn cone(axis: [f64; 3], half_angle: f64, rng: &mut Rng) -> [f64; 3] {
+    if half_angle == 0.0 {
+        return axis;
+    }
+    let z = 1.0 - rng.unit() * (1.0 - half_angle.cos());
+    around(axis, z, 2.0 * PI * rng.unit())
+}
+
+/// A direction away from the face with unit `normal`, cosine weighted as
+/// a Lambertian face scatters light.
+fn hemisphere(normal: [f64; 3], rng: &mut Rng) -> [f64; 3] {
+    let z = (1.0 - rng.unit()).sqrt();
+    around(normal, z, 2.0 * PI * rng.unit())
+}
+
+/// The unit direction at cosine `z` from `axis`, turned `phi` radians
+/// about it.
+fn around(axis: [f64; 3], z: f64, phi: f64) -> [f64; 3] {
+    let helper = if axis[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let tangent = normalize(cross(helper, axis));
+    let bitangent = cross(axis, tangent);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let (sin, cos) = phi.sin_cos();
+    std::array::from_fn(|a| z * axis[a] + r * (cos * tangent[a] + sin * bitangent[a]))
+}
@@ -0,0 +1,431 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::color::ColorMap;
+use crate::error::{Error, Result};
+use crate::mesh::{cross, dot, sub};
+
+/// Everything a render needs besides the lattice, kept in a file so a
+/// figure can be reproduced exactly.
+///
+/// World coordinates put the lattice's centre at the origin with its
+/// longest side spanning -1 to 1, z up.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default)]
+    pub camera: Camera,
+    #[serde(default = "default_lights")]
+    pub lights: Vec<Light>,
+    /// Linear RGB seen where rays miss the lattice.
+    #[serde(default = "default_background")]
+    pub background: [f64; 3],
+    /// Materials by subdivision level: the first for faces on the outer
+    /// grid, the next for faces first cut at depth 1, and so on. The last
+    /// one is reused for deeper levels, and fractals without a single base
+    /// use the first for every face.
+    #[serde(default = "default_materials")]
+    pub materials: Vec<Material>,
+    /// Path trace rather than ray cast: slower, with light bouncing
+    /// between faces, soft shadows and the background lighting the
+    /// lattice from every direction.
+    #[serde(default)]
+    pub path_tracing: Option<PathTracing>,
+    /// Auxiliary images of the geometry to write beside the render.
+    #[serde(default)]
+    pub passes: Vec<Pass>,
+    /// Cut the lattice open to show its inside. Surface fractals are drawn
+    /// whole.
+    #[serde(default)]
+    pub clipping: Option<Clipping>,
+    /// Colour the lattice's faces from a palette keyed to an attribute of
+    /// their cells rather than by material. Surface fractals keep the
+    /// first material.
+    #[serde(default)]
+    pub color: Option<ColorMap>,
+}
+
+/// A pinhole camera.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Camera {
+    #[serde(default = "default_position")]
+    pub position: [f64; 3],
+    #[serde(default)]
+    pub target: [f64; 3],
+    #[serde(default = "default_up")]
+    pub up: [f64; 3],
+    /// Vertical field of view, in degrees.
+    #[serde(default = "default_fov")]
+    pub fov: f64,
+    /// Project in parallel, showing this many world units from the
+    /// image's bottom to its top, rather than in perspective with `fov`.
+    #[serde(default)]
+    pub orthographic: Option<f64>,
+    #[serde(default = "default_width")]
+    pub width: usize,
+    #[serde(default = "default_height")]
+    pub height: usize,
+    /// Render a view for each eye; `width` and `height` are per eye.
+    #[serde(default)]
+    pub stereo: Option<Stereo>,
+}
+
+/// Two views a little apart, converging on the camera's target, so faces
+/// there appear at the screen and nearer ones in front of it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stereo {
+    #[serde(default)]
+    pub mode: StereoMode,
+    /// Distance between the eyes, in world units.
+    #[serde(default = "default_separation")]
+    pub separation: f64,
+}
+
+/// How the two views are combined into one image.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum StereoMode {
+    /// The left eye's view beside the right's, for VR headsets and
+    /// parallel free viewing.
+    #[default]
+    SideBySide,
+    /// Red from the left eye and green and blue from the right, for
+    /// red-cyan glasses.
+    Anaglyph,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Stereo {
+            mode: StereoMode::default(),
+            separation: default_separation(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Light {
+    /// Light from every direction, unshadowed.
+    Ambient {
+        #[serde(default = "default_white")]
+        color: [f64; 3],
+        intensity: f64,
+    },
+    /// Parallel light travelling along `direction`, casting shadows.
+    Directional {
+        direction: [f64; 3],
+        #[serde(default = "default_white")]
+        color: [f64; 3],
+        intensity: f64,
+        /// Apparent diameter of the light, in degrees, over which path
+        /// tracing softens its shadows; ray casting keeps them hard.
+        #[serde(default)]
+        angle: f64,
+    },
+}
+
+/// Settings of the path tracer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathTracing {
+    /// Rays per pixel; noise falls with its square root.
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+    /// Diffuse bounces followed after the first hit.
+    #[serde(default = "default_bounces")]
+    pub bounces: usize,
+    /// The same seed gives the same noise.
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl Default for PathTracing {
+    fn default() -> Self {
+        PathTracing {
+            samples: default_samples(),
+            bounces: default_bounces(),
+            seed: 0,
+        }
+    }
+}
+
+/// An auxiliary image of what each pixel's centre ray first meets, for
+/// compositing and ground truth. Misses are 0 in every pass but depth.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pass {
+    /// Distance from the camera along its view direction, in world units.
+    Depth,
+    /// The face's unit normal in world coordinates.
+    Normal,
+    /// One more than the cell's index in the lattice, or than the
+    /// triangle's index in a surface mesh.
+    CellId,
+    /// One more than the face's subdivision level, as materials count
+    /// them.
+    Level,
+}
+
+impl Pass {
+    pub fn name(self) -> &'static str {
+        match self {
+            Pass::Depth => "depth",
+            Pass::Normal => "normal",
+            Pass::CellId => "cell-id",
+            Pass::Level => "level",
+        }
+    }
+}
+
+/// Planes and a box cutting cells away, in world coordinates. Cells cut
+/// through are capped flat where the cut meets them, in the cap colour.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Clipping {
+    #[serde(default)]
+    pub planes: Vec<ClipPlane>,
+    #[serde(default)]
+    pub cutaway: Option<Cutaway>,
+    /// Linear RGB of the caps.
+    #[serde(default = "default_cap")]
+    pub cap: [f64; 3],
+}
+
+/// A plane removing everything on the side its normal points to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClipPlane {
+    pub normal: [f64; 3],
+    /// Distance of the plane from the origin along the unit normal.
+    #[serde(default)]
+    pub offset: f64,
+}
+
+/// An axis-aligned box removed from the lattice.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Cutaway {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl Default for Clipping {
+    fn default() -> Self {
+        Clipping {
+            planes: Vec::new(),
+            cutaway: None,
+            cap: default_cap(),
+        }
+    }
+}
+
+/// A diffuse surface.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Material {
+    /// Linear RGB albedo.
+    pub color: [f64; 3],
+}
+
+fn default_samples() -> usize {
+    64
+}
+
+fn default_bounces() -> usize {
+    4
+}
+
+fn default_position() -> [f64; 3] {
+    [2.4, -3.2, 2.2]
+}
+
+fn default_up() -> [f64; 3] {
+    [0.0, 0.0, 1.0]
+}
+
+fn default_fov() -> f64 {
+    40.0
+}
+
+fn default_width() -> usize {
+    800
+}
+
+fn default_height() -> usize {
+    600
+}
+
+fn default_separation() -> f64 {
+    0.12
+}
+
+fn default_white() -> [f64; 3] {
+    [1.0; 3]
+}
+
+fn default_lights() -> Vec<Light> {
+    vec![
+        Light::Ambient {
+            color: default_white(),
+            intensity: 0.25,
+        },
+        Light::Directional {
+            direction: [-0.4, 0.6, -1.0],
+            color: default_white(),
+            intensity: 0.9,
+            angle: 0.0,
+        },
+    ]
+}
+
+fn default_cap() -> [f64; 3] {
+    [0.9, 0.75, 0.1]
+}
+
+fn default_background() -> [f64; 3] {
+    [0.9; 3]
+}
+
+/// Blue at the outer faces shading to red at the deepest cuts.
+fn default_materials() -> Vec<Material> {
+    [
+        [0.1, 0.25, 0.8],
+        [0.1, 0.55, 0.7],
+        [0.15, 0.6, 0.25],
+        [0.8, 0.65, 0.1],
+        [0.8, 0.3, 0.08],
+        [0.7, 0.07, 0.07],
+    ]
+    .into_iter()
+    .map(|color| Material { color })
+    .collect()
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Scene {
+            camera: Camera::default(),
+            lights: default_lights(),
+            background: default_background(),
+            materials: default_materials(),
+            path_tracing: None,
+            passes: Vec::new(),
+            clipping: None,
+            color: None,
+        }
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: default_position(),
+            target: [0.0; 3],
+            up: default_up(),
+            fov: default_fov(),
+            orthographic: None,
+            width: default_width(),
+            height: default_height(),
+            stereo: None,
+        }
+    }
+}
+
+impl Scene {
+    /// Reads a JSON scene file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let camera = &self.camera;
+        if camera.width == 0 || camera.height == 0 {
+            return Err(Error::InvalidJob("the image needs pixels".into()));
+        }
+        if !(camera.fov > 0.0 && camera.fov < 180.0) {
+            return Err(Error::InvalidJob(
+                "field of view must be between 0 and 180 degrees".into(),
+            ));
+        }
+        if camera
+            .orthographic
+            .is_some_and(|height| !(height > 0.0 && height.is_finite()))
+        {
+            return Err(Error::InvalidJob(
+                "an orthographic view must be positive in height".into(),
+            ));
+        }
+        if camera.orthographic.is_some() && camera.stereo.is_some() {
+            return Err(Error::InvalidJob(
+                "stereo needs a perspective camera".into(),
+            ));
+        }
+        let view = sub(camera.target, camera.position);
+        if dot(view, view) == 0.0 || cross(view, camera.up) == [0.0; 3] {
+            return Err(Error::InvalidJob(
+                "the camera must look somewhere other than up".into(),
+            ));
+        }
+        if camera
+            .stereo
+            .as_ref()
+            .is_some_and(|s| !(s.separation > 0.0 && s.separation.is_finite()))
+        {
+            return Err(Error::InvalidJob(
+                "stereo eye separation must be positive".into(),
+            ));
+        }
+        if self.materials.is_empty() {
+            return Err(Error::InvalidJob("the scene needs a material".into()));
+        }
+        for light in &self.lights {
+            if let Light::Directional {
+                direction, angle, ..
+            } = light
+            {
+                if dot(*direction, *direction) == 0.0 {
+                    return Err(Error::InvalidJob("a light has no direction".into()));
+                }
+                if !(0.0..180.0).contains(angle) {
+                    return Err(Error::InvalidJob(
+                        "a light's angle must be between 0 and 180 degrees".into(),
+                    ));
+                }
+            }
+        }
+        if self.path_tracing.as_ref().is_some_and(|p| p.samples == 0) {
+            return Err(Error::InvalidJob(
+                "path tracing needs at least 1 sample per pixel".into(),
+            ));
+        }
+        if let Some(clipping) = &self.clipping {
+            if clipping
+                .planes
+                .iter()
+                .any(|p| dot(p.normal, p.normal) == 0.0)
+            {
+                return Err(Error::InvalidJob("a clipping plane has no normal".into()));
+            }
+            if clipping.cutaway.as_ref().is_some_and(|c| {
+                (0..3).any(|a| c.min[a].partial_cmp(&c.max[a]) != Some(Ordering::Less))
+            }) {
+                return Err(Error::InvalidJob(
+                    "a cutaway's min must be below its max on every axis".into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The material of faces first cut at `level`.
+    pub fn material(&self, level: u32) -> &Material {
+        let last = self.materials.len() - 1;
+        &self.materials[(level as usize).min(last)]
+    }
+}
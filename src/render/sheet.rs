@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use super::{Image, Scene};
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, Artifact};
+use crate::job::Job;
+
+/// The views of a contact sheet: a label, the direction the camera looks
+/// from, its up, and the height of world the view shows.
+const VIEWS: [(&str, [f64; 3], [f64; 3], f64); 7] = [
+    ("+X", [1.0, 0.0, 0.0], [0.0, 0.0, 1.0], AXIS_HEIGHT),
+    ("-X", [-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], AXIS_HEIGHT),
+    ("+Y", [0.0, 1.0, 0.0], [0.0, 0.0, 1.0], AXIS_HEIGHT),
+    ("-Y", [0.0, -1.0, 0.0], [0.0, 0.0, 1.0], AXIS_HEIGHT),
+    ("+Z", [0.0, 0.0, 1.0], [0.0, 1.0, 0.0], AXIS_HEIGHT),
+    ("-Z", [0.0, 0.0, -1.0], [0.0, 1.0, 0.0], AXIS_HEIGHT),
+    ("ISO", [1.0, -1.0, 1.0], [0.0, 0.0, 1.0], ISO_HEIGHT),
+];
+/// A little more than the lattice's longest side, 2 world units.
+const AXIS_HEIGHT: f64 = 2.4;
+/// A little more than a cube's height seen along its diagonal, 4/√6
+/// times its side.
+const ISO_HEIGHT: f64 = 3.4;
+/// Camera distance from the origin, clear of the lattice's corners.
+const DISTANCE: f64 = 4.0;
+const COLUMNS: usize = 4;
+/// Pixels between tiles.
+const GAP: usize = 4;
+const GAP_COLOR: [f64; 3] = [0.2; 3];
+const LABEL_COLOR: [f64; 3] = [0.01; 3];
+
+impl Job {
+    /// Renders the job from the six axis directions and an isometric
+    /// corner, orthographically, into one labelled PNG of `size`-pixel
+    /// square tiles: +X, -X, +Y and -Y above, +Z, -Z and ISO below. The
+    /// scene's camera is replaced; its lights and materials are kept.
+    pub fn contact_sheet(
+        &self,
+        scene: &Scene,
+        w: Option<usize>,
+        size: usize,
+        path: &Path,
+        cancel: &CancelToken,
+    ) -> Result<Artifact> {
+        if size == 0 {
+            return Err(Error::InvalidJob("contact sheet tiles need pixels".into()));
+        }
+        self.validate()?;
+        let subject = self.subject(w, cancel)?;
+        let rows = VIEWS.len().div_ceil(COLUMNS);
+        let width = COLUMNS * size + (COLUMNS + 1) * GAP;
+        let height = rows * size + (rows + 1) * GAP;
+        let mut sheet = Image {
+            width,
+            height,
+            pixels: vec![GAP_COLOR; width * height],
+            surfaces: Vec::new(),
+        };
+        for (i, (label, from, up, view_height)) in VIEWS.into_iter().enumerate() {
+            let mut view = scene.clone();
+            let length = from.iter().map(|c| c * c).sum::<f64>().sqrt();
+            view.camera.position = from.map(|c| c / length * DISTANCE);
+            view.camera.target = [0.0; 3];
+            view.camera.up = up;
+            view.camera.orthographic = Some(view_height);
+            view.camera.width = size;
+            view.camera.height = size;
+            view.camera.stereo = None;
+            view.passes.clear();
+            view.validate()?;
+            let tile = subject.draw(&view, cancel)?;
+            let left = GAP + (i % COLUMNS) * (size + GAP);
+            let top = GAP + (i / COLUMNS) * (size + GAP);
+            for (y, row) in tile.pixels.chunks(size).enumerate() {
+                let start = (top + y) * width + left;
+                sheet.pixels[start..start + size].copy_from_slice(row);
+            }
+            let scale = (size / 80).max(1);
+            sheet.label(label, left + 2 * scale, top + 2 * scale, scale);
+        }
+        write_file_atomically(path, |out| sheet.write_png(out))
+    }
+}
+
+impl Image {
+    /// Draws `text` with its top left corner at pixel (`x`, `y`), each
+    /// font pixel `scale` pixels square.
+    fn label(&mut self, text: &str, x: usize, y: usize, scale: usize) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + i * 4 * scale;
+            for (row, bits) in glyph(c).into_iter().enumerate() {
+                for column in 0..3 {
+                    if bits >> (2 - column) & 1 == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let (px, py) = (left + column * scale + dx, y + row * scale + dy);
+                            if px < self.width && py < self.height {
+                                self.pixels[py * self.width + px] = LABEL_COLOR;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of a 3×5 pixel font, top first, for the characters of view
+/// labels.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        _ => [0; 5],
+    }
+}
@@ -0,0 +1,197 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::job::JobReport;
+use crate::plan::human_bytes;
+
+/// The outcome of a run of one or more jobs, for display or as JSON.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub jobs: Vec<JobOutcome>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobOutcome {
+    Ok(JobReport),
+    Failed { name: String, error: String },
+}
+
+impl Summary {
+    /// Collects `(job name, result)` pairs in run order.
+    pub fn new(results: Vec<(String, Result<JobReport>)>) -> Self {
+        let jobs: Vec<_> = results
+            .into_iter()
+            .map(|(name, result)| match result {
+                Ok(report) => JobOutcome::Ok(report),
+                Err(e) => JobOutcome::Failed {
+                    name,
+                    error: e.to_string(),
+                },
+            })
+            .collect();
+        let failed = jobs
+            .iter()
+            .filter(|job| matches!(job, JobOutcome::Failed { .. }))
+            .count();
+        Summary {
+            succeeded: jobs.len() - failed,
+            failed,
+            jobs,
+        }
+    }
+
+    /// Renders the summary as aligned tables, with ANSI colours if `color`.
+    pub fn render(&self, color: bool) -> String {
+        let style = Style { color };
+        let mut out = String::new();
+        for job in &self.jobs {
+            match job {
+                JobOutcome::Ok(report) => render_report(&mut out, report, &style),
+                JobOutcome::Failed { name, error } => {
+                    let _ = writeln!(
+                        out,
+                        "{} {}  {error}",
+                        style.paint(RED, "✘"),
+                        style.paint(BOLD, name)
+                    );
+                }
+            }
+        }
+        let failed = format!("{} failed", self.failed);
+        let failed = if self.failed > 0 {
+            style.paint(RED, &failed)
+        } else {
+            failed
+        };
+        let _ = writeln!(out, "{} succeeded, {failed}", self.succeeded);
+        out
+    }
+}
+
+fn render_report(out: &mut String, report: &JobReport, style: &Style) {
+    let _ = writeln!(
+        out,
+        "{} {}  {} {}, {} slice(s), {:.2} s",
+        style.paint(GREEN, "✔"),
+        style.paint(BOLD, &report.name),
+        report.cells,
+        if report.surface { "triangles" } else { "cells" },
+        report.slices,
+        report.elapsed.as_secs_f64()
+    );
+    if !report.levels.is_empty() {
+        let _ = writeln!(
+            out,
+            "  {}",
+            style.paint(
+                DIM,
+                &format!("{:>5} {:>16} {:>16}", "level", "kept", "removed")
+            )
+        );
+    }
+    for level in &report.levels {
+        let _ = writeln!(
+            out,
+            "  {:>5} {:>16} {:>16}",
+            level.level, level.kept, level.removed
+        );
+    }
+    let counted = report.stages.iter().any(|s| s.allocations.is_some());
+    let mut header = format!("{:<10} {:>10}", "stage", "seconds");
+    if counted {
+        header += &format!(" {:>12} {:>10} {:>10}", "allocations", "allocated", "peak");
+    }
+    let _ = writeln!(out, "  {}", style.paint(DIM, &header));
+    for stage in &report.stages {
+        let mut line = format!("{:<10} {:>10.3}", stage.stage, stage.elapsed.as_secs_f64());
+        if let Some(allocations) = stage.allocations {
+            line += &format!(
+                " {:>12} {:>10} {:>10}",
+                allocations.count,
+                human_bytes(allocations.bytes),
+                human_bytes(allocations.peak_bytes)
+            );
+        }
+        let _ = writeln!(out, "  {line}");
+    }
+    if let Some(peak) = report.peak_rss {
+        let _ = writeln!(out, "  peak resident memory {}", human_bytes(peak));
+    }
+    for thin in &report.thin_features {
+        let slice = thin.slice.map(|w| format!(" at w={w}")).unwrap_or_default();
+        let mut line = format!(
+            "  {} thin cells in {} regions{slice} (minimum {:.2} cells)",
+            thin.thin_cells, thin.regions, thin.min_cells
+        );
+        if thin.added_cells > 0 {
+            line += &format!(
+                ", thickened by {} cells, {} still thin",
+                thin.added_cells, thin.remaining_cells
+            );
+        }
+        let _ = writeln!(out, "{line}");
+    }
+    for orientation in &report.orientations {
+        let slice = orientation
+            .slice
+            .map(|w| format!(" at w={w}"))
+            .unwrap_or_default();
+        let [x, y, z] = orientation.up;
+        let _ = writeln!(
+            out,
+            "  oriented{slice} with ({x:.3}, {y:.3}, {z:.3}) up: overhang {:.1} of {:.1}, was {:.1}",
+            orientation.overhang_area, orientation.total_area, orientation.original_overhang_area
+        );
+    }
+    if report.artifacts.is_empty() {
+        return;
+    }
+    let width = report
+        .artifacts
+        .iter()
+        .map(|a| a.path.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("artifact".len());
+    let _ = writeln!(
+        out,
+        "  {}",
+        style.paint(
+            DIM,
+            &format!("{:<width$} {:>10}  {}", "artifact", "size", "sha256")
+        )
+    );
+    for artifact in &report.artifacts {
+        let _ = writeln!(
+            out,
+            "  {:<width$} {:>10}  {}",
+            artifact.path.display().to_string(),
+            human_bytes(artifact.bytes),
+            style.paint(DIM, &artifact.sha256[..16])
+        );
+    }
+}
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+const RED: &str = "31";
+const GREEN: &str = "32";
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+}
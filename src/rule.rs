@@ -0,0 +1,728 @@
+use serde::{Deserialize, Serialize};
+
+pub use fractal_slicer_core::{compose, decompose, pell, pell_digit, Split, MAX_DIMS};
+
+use crate::error::{Error, Result};
+
+/// Most subcells a custom rule may split a cell into, bounding the
+/// keep-mask built from it.
+pub const MAX_SUBCELLS: usize = 1 << 20;
+
+/// A self-similar subdivision rule.
+///
+/// Every cell is split into `bases[i]` parts along axis `i` and the rule
+/// decides, from the subcell's digit tuple, whether it survives. Applying
+/// the rule recursively `depth` times produces the fractal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rule {
+    name: String,
+    bases: Vec<u32>,
+    dims: usize,
+    /// Keep-masks by subdivision level, cycled: level `k`, counted from 1
+    /// at the coarsest, uses mask `(k - 1) % len`. Only uniform splits
+    /// have more than one.
+    masks: Vec<Vec<bool>>,
+    split: Split,
+}
+
+impl Rule {
+    /// Builds a rule by evaluating `keep` on every digit tuple in `[0, base)^dims`.
+    pub fn from_fn(name: &str, base: u32, dims: usize, keep: impl Fn(&[u32]) -> bool) -> Self {
+        Rule::from_fn_bases(name, &vec![base; dims], keep)
+    }
+
+    /// Like [`Rule::from_fn`] with `bases[i]` parts along axis `i`, for
+    /// stretched and layered variants.
+    pub fn from_fn_bases(name: &str, bases: &[u32], keep: impl Fn(&[u32]) -> bool) -> Self {
+        assert!(
+            bases.iter().all(|&b| b >= 2),
+            "subdivision base must be at least 2"
+        );
+        assert!(!bases.is_empty(), "a rule needs at least one axis");
+        assert!(
+            bases.len() <= MAX_DIMS,
+            "a rule has at most {MAX_DIMS} axes"
+        );
+        let subcells = bases.iter().map(|&b| b as usize).product();
+        let mut digits = vec![0; bases.len()];
+        let keep = (0..subcells)
+            .map(|index| {
+                decompose(index, bases, &mut digits);
+                keep(&digits)
+            })
+            .collect();
+        Rule {
+            name: name.to_string(),
+            bases: bases.to_vec(),
+            dims: bases.len(),
+            masks: vec![keep],
+            split: Split::Uniform,
+        }
+    }
+
+    /// Whether every axis is split into the same number of parts.
+    pub fn is_isotropic(&self) -> bool {
+        self.bases.iter().all(|&b| b == self.bases[0])
+    }
+
+    /// Switches the rule to the [`Split::Pell`] subdivision. Needs base 3
+    /// and a single mask.
+    pub fn with_pell_split(mut self) -> Self {
+        assert!(
+            self.bases.iter().all(|&b| b == 3),
+            "the Pell split has three parts per axis"
+        );
+        assert_eq!(self.masks.len(), 1, "the Pell split uses one mask");
+        self.split = Split::Pell;
+        self
+    }
+
+    /// The Menger rule in `dims` dimensions: a subcell is removed when two or
+    /// more of its digits are the centre digit. Gives the Menger sponge for
+    /// `dims == 3` and its 48/81 hypersponge analogue for `dims == 4`.
+    pub fn menger(dims: usize) -> Self {
+        Rule::from_fn("menger", 3, dims, |digits| {
+            digits.iter().filter(|&&d| d == 1).count() < 2
+        })
+    }
+
+    /// Switches the rule to the [`Split::Flake`] subdivision. Needs base 3
+    /// and a single mask.
+    pub fn with_flake_split(mut self) -> Self {
+        assert!(
+            self.bases.iter().all(|&b| b == 3),
+            "the flake split has three offsets per axis"
+        );
+        assert_eq!(self.masks.len(), 1, "the flake split uses one mask");
+        self.split = Split::Flake;
+        self
+    }
+
+    /// The Jerusalem cube in `dims` dimensions: corner copies scaled by
+    /// `√2 - 1` and a copy scaled by its square on every edge, from the
+    /// Menger mask on a [`Split::Pell`].
+    pub fn jerusalem(dims: usize) -> Self {
+        let mut rule = Rule::menger(dims).with_pell_split();
+        rule.name = "jerusalem".to_string();
+        rule
+    }
+
+    /// The Mosely snowflake in `dims` dimensions: the corners and the centre
+    /// are removed, keeping 18 of 27 subcells in 3D.
+    pub fn mosely(dims: usize) -> Self {
+        Rule::from_fn("mosely", 3, dims, |digits| {
+            let corner = digits.iter().all(|&d| d != 1);
+            let centre = digits.iter().all(|&d| d == 1);
+            !corner && !centre
+        })
+    }
+
+    /// The Vicsek fractal in `dims` dimensions: the centre and the subcells
+    /// next to its faces survive, a plus sign of `2 * dims + 1` subcells.
+    pub fn vicsek(dims: usize) -> Self {
+        Rule::from_fn("vicsek", 3, dims, |digits| {
+            digits.iter().filter(|&&d| d != 1).count() <= 1
+        })
+    }
+
+    /// The Sierpinski octahedron flake in `dims` dimensions: `2 * dims`
+    /// half-size copies on the vertices of the cross-polytope, on a
+    /// [`Split::Flake`].
+    pub fn octahedron(dims: usize) -> Self {
+        Rule::from_fn("octahedron", 3, dims, |digits| {
+            digits.iter().filter(|&&d| d != 1).count() == 1
+        })
+        .with_flake_split()
+    }
+
+    /// Looks up a built-in rule by name.
+    pub fn by_name(name: &str, dims: usize) -> Option<Self> {
+        match name {
+            "menger" => Some(Rule::menger(dims)),
+            "jerusalem" => Some(Rule::jerusalem(dims)),
+            "mosely" => Some(Rule::mosely(dims)),
+            "vicsek" => Some(Rule::vicsek(dims)),
+            "octahedron" => Some(Rule::octahedron(dims)),
+            _ => None,
+        }
+    }
+
+    /// Names accepted by [`Rule::by_name`].
+    pub const NAMES: &'static [&'static str] =
+        &["menger", "jerusalem", "mosely", "vicsek", "octahedron"];
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Parts each axis is split into.
+    pub fn bases(&self) -> &[u32] {
+        &self.bases
+    }
+
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    pub fn split(&self) -> Split {
+        self.split
+    }
+
+    /// Number of subcells a single cell is split into.
+    pub fn subcells(&self) -> usize {
+        self.masks[0].len()
+    }
+
+    /// Number of subcells that survive the first subdivision step.
+    pub fn survivors(&self) -> usize {
+        self.survivors_at(1)
+    }
+
+    /// Number of subcells that survive subdivision step `level`, from 1.
+    pub fn survivors_at(&self, level: u32) -> usize {
+        self.mask(level).iter().filter(|&&k| k).count()
+    }
+
+    /// The subcells surviving subdivision step `level`, from 1, out of
+    /// those a cell is split into: 20 of 27 for the Menger sponge and 48
+    /// of 81 for its 4D analogue.
+    pub fn survivor_fraction(&self, level: u32) -> (usize, usize) {
+        (self.survivors_at(level), self.subcells())
+    }
+
+    /// Number of levels after which the masks repeat.
+    pub fn period(&self) -> usize {
+        self.masks.len()
+    }
+
+    /// The keep-mask of subdivision step `level`, from 1.
+    pub(crate) fn mask(&self, level: u32) -> &[bool] {
+        &self.masks[(level.max(1) as usize - 1) % self.masks.len()]
+    }
+
+    /// Number of cells left after `level` subdivision steps.
+    #[deprecated(note = "use `rule::expected_cells`")]
+    pub fn cells(&self, level: u32) -> u64 {
+        expected_cells(self, level)
+    }
+
+    /// Number of cells removed by subdivision step `level`, counted at that
+    /// level's resolution. Pell and flake levels do not nest, so for them
+    /// this is the cells cut away around the level's outermost copies.
+    pub fn removed(&self, level: u32) -> u64 {
+        if level == 0 {
+            return 0;
+        }
+        match self.split {
+            Split::Uniform => {
+                let per_cell = (self.subcells() - self.survivors_at(level)) as u64;
+                expected_cells(self, level - 1).saturating_mul(per_cell)
+            }
+            Split::Pell => {
+                let (outer, inner) = self.pell_survivors();
+                let volume = |depth: Option<u32>| depth.map_or(0, |d| self.volume(d));
+                volume(Some(level))
+                    .saturating_sub((outer as u64).saturating_mul(volume(Some(level - 1))))
+                    .saturating_sub((inner as u64).saturating_mul(volume(level.checked_sub(2))))
+            }
+            Split::Flake => {
+                let ball = |depth: u32| l1_ball(self.dims, (1u64 << depth) - 1);
+                ball(level)
+                    .saturating_sub((self.survivors() as u64).saturating_mul(ball(level - 1)))
+            }
+        }
+    }
+
+    /// Directions, each component in `-1..=1`, of the copies a flake level
+    /// places around its centre.
+    fn flake_offsets(&self) -> Vec<Vec<i64>> {
+        let mut digits = vec![0; self.dims];
+        (0..self.masks[0].len())
+            .filter(|&i| self.masks[0][i])
+            .map(|index| {
+                decompose(index, &self.bases, &mut digits);
+                digits.iter().map(|&d| d as i64 - 1).collect()
+            })
+            .collect()
+    }
+
+    /// [`Rule::slice_cells`] for the flake split, with `last` relative to
+    /// the lattice centre.
+    fn flake_slice_cells(&self, offsets: &[Vec<i64>], last: i64, depth: u32) -> u64 {
+        let radius = (1i64 << depth) - 1;
+        if last.abs() > radius {
+            return 0;
+        }
+        if depth == 0 {
+            return 1;
+        }
+        let half = 1i64 << (depth - 1);
+        // Copies sharing an offset along the final axis cut the slice alike.
+        [-1, 0, 1]
+            .into_iter()
+            .map(|t| {
+                let copies = offsets.iter().filter(|o| o[self.dims - 1] == t).count() as u64;
+                if copies == 0 {
+                    return 0;
+                }
+                copies.saturating_mul(self.flake_slice_cells(offsets, last - t * half, depth - 1))
+            })
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Where the copy held by the subcell with `digits` sits in the
+    /// depth-`depth` lattice: how many levels down it is and its offset,
+    /// in cells, along each axis.
+    pub(crate) fn copy_placement(&self, digits: &[u32], depth: u32, offset: &mut [usize]) -> u32 {
+        let down = match self.split {
+            Split::Pell if digits.contains(&1) => 2,
+            _ => 1,
+        };
+        for (axis, (o, &digit)) in offset.iter_mut().zip(digits).enumerate() {
+            *o = match self.split {
+                Split::Uniform => digit as usize * self.side_along(axis, depth - 1),
+                // Outer digits push the copy against their face.
+                Split::Pell => match digit {
+                    0 => 0,
+                    1 => pell(depth as i64),
+                    _ => pell(depth as i64 + 1) - pell(depth as i64 + 1 - down as i64),
+                },
+                Split::Flake => (digit as usize) << (depth - 1),
+            };
+        }
+        down
+    }
+
+    /// Kept Pell subcells holding copies one and two levels down.
+    fn pell_survivors(&self) -> (usize, usize) {
+        let mut digits = vec![0; self.dims];
+        let (mut outer, mut inner) = (0, 0);
+        for index in (0..self.masks[0].len()).filter(|&i| self.masks[0][i]) {
+            decompose(index, &self.bases, &mut digits);
+            if digits.contains(&1) {
+                inner += 1;
+            } else {
+                outer += 1;
+            }
+        }
+        (outer, inner)
+    }
+
+    /// Number of cells in the cross-section at index `last` of the final
+    /// axis, computed from the digits of `last` without generating anything.
+    pub fn slice_cells(&self, last: usize, depth: u32) -> u64 {
+        match self.split {
+            Split::Uniform => {}
+            Split::Pell => return self.pell_slice_cells(last, depth as i64),
+            Split::Flake => {
+                let centre = (1i64 << depth) - 1;
+                return self.flake_slice_cells(&self.flake_offsets(), last as i64 - centre, depth);
+            }
+        }
+        let base = self.bases[self.dims - 1] as usize;
+        let stride = self.subcells() / base;
+        let mut cells = 1u64;
+        let mut rest = last;
+        // Digits run from the finest level up.
+        for level in (1..=depth).rev() {
+            let digit = rest % base;
+            let mask = self.mask(level);
+            let kept = (0..stride).filter(|&i| mask[i + digit * stride]).count();
+            cells = cells.saturating_mul(kept as u64);
+            rest /= base;
+        }
+        cells
+    }
+
+    /// Whether the subcell with the given digit tuple survives the first
+    /// subdivision step.
+    pub fn keeps(&self, digits: &[u32]) -> bool {
+        self.masks[0][compose(digits, &self.bases)]
+    }
+
+    /// Whether the subcell with the given flat index survives the first
+    /// subdivision step.
+    pub fn keeps_index(&self, index: usize) -> bool {
+        self.masks[0][index]
+    }
+
+    /// Side length, in cells, of the lattice produced at `depth` along its
+    /// final axis, the one slices are cut across.
+    pub fn side(&self, depth: u32) -> usize {
+        self.side_along(self.dims - 1, depth)
+    }
+
+    /// Side length, in cells, of the lattice produced at `depth` along
+    /// `axis`, saturating at `usize::MAX` for depths no lattice reaches.
+    pub fn side_along(&self, axis: usize, depth: u32) -> usize {
+        match self.split {
+            Split::Uniform => (self.bases[axis] as usize).saturating_pow(depth),
+            Split::Pell => pell(depth as i64 + 1),
+            Split::Flake => 2usize.saturating_pow(depth.saturating_add(1)) - 1,
+        }
+    }
+
+    /// The cell one level coarser that the cell at `coords` was split
+    /// from, for rules split evenly: the same cell in the lattice of one
+    /// depth less.
+    pub fn parent(&self, coords: &[usize]) -> Vec<usize> {
+        debug_assert_eq!(self.split, Split::Uniform, "uneven splits have no grid");
+        coords
+            .iter()
+            .zip(&self.bases)
+            .map(|(&c, &base)| c / base as usize)
+            .collect()
+    }
+
+    /// The cells of the lattice of one depth more that the cell at
+    /// `coords` is split into, kept or not, in flat subcell order, for
+    /// rules split evenly.
+    pub fn children<'a>(&'a self, coords: &'a [usize]) -> impl Iterator<Item = Vec<usize>> + 'a {
+        debug_assert_eq!(self.split, Split::Uniform, "uneven splits have no grid");
+        let mut digits = vec![0; self.dims];
+        (0..self.subcells()).map(move |index| {
+            decompose(index, &self.bases, &mut digits);
+            coords
+                .iter()
+                .zip(&self.bases)
+                .zip(&digits)
+                .map(|((&c, &base), &digit)| c * base as usize + digit as usize)
+                .collect()
+        })
+    }
+
+    /// The cell the centred domain puts at the origin, the middle one
+    /// along each axis, or the upper of the two along even sides; see
+    /// [`Rule::is_solid_centered`].
+    pub fn center(&self, depth: u32) -> Vec<usize> {
+        (0..self.dims)
+            .map(|axis| self.side_along(axis, depth) / 2)
+            .collect()
+    }
+
+    /// Membership test for the cell at signed `coords` in the centred
+    /// domain, which numbers the depth-`depth` lattice's cells from
+    /// [`Rule::center`], at 0, outwards; cells outside the lattice are
+    /// empty.
+    ///
+    /// With a uniform split of odd bases the coordinates are taken apart
+    /// into balanced digits, from `-(b - 1) / 2` to `(b - 1) / 2` in base
+    /// `b`, each the rule's own digit less `(b - 1) / 2`, so the middle
+    /// subcell of every level has digit 0 and a rule symmetric under
+    /// reflection gives a fractal symmetric about the origin. Other rules
+    /// have no middle subcell, and are tested by [`Rule::is_solid`] of the
+    /// cell shifted by the centre.
+    pub fn is_solid_centered(&self, coords: &[i64], depth: u32) -> bool {
+        debug_assert_eq!(coords.len(), self.dims);
+        if self.split != Split::Uniform || self.bases.iter().any(|base| base % 2 == 0) {
+            let shifted: Option<Vec<usize>> = coords
+                .iter()
+                .zip(self.center(depth))
+                .enumerate()
+                .map(|(axis, (&c, center))| {
+                    usize::try_from(c.checked_add(center as i64)?)
+                        .ok()
+                        .filter(|&p| p < self.side_along(axis, depth))
+                })
+                .collect();
+            return shifted.is_some_and(|p| self.is_solid(&p, depth));
+        }
+        let mut rest = [0; MAX_DIMS];
+        rest[..coords.len()].copy_from_slice(coords);
+        for level in (1..=depth).rev() {
+            let mut index = 0;
+            let mut stride = 1;
+            for (c, &base) in rest.iter_mut().zip(&self.bases) {
+                let (base, half) = (i64::from(base), i64::from(base / 2));
+                let (quotient, remainder) = (c.div_euclid(base), c.rem_euclid(base));
+                // The balanced digit is the remainder less the base above
+                // the middle, carrying one into the next digit.
+                let (digit, carry) = if remainder > half {
+                    (remainder - base, 1)
+                } else {
+                    (remainder, 0)
+                };
+                *c = quotient + carry;
+                index += (digit + half) as usize * stride;
+                stride *= base as usize;
+            }
+            if !self.mask(level)[index] {
+                return false;
+            }
+        }
+        // Digits left over lie beyond the lattice.
+        rest.iter().all(|&c| c == 0)
+    }
+
+    /// Number of cells in the lattice produced at `depth`, solid or not.
+    pub fn volume(&self, depth: u32) -> u64 {
+        (0..self.dims)
+            .map(|axis| self.side_along(axis, depth) as u64)
+            .fold(1, u64::saturating_mul)
+    }
+
+    fn pell_slice_cells(&self, last: usize, depth: i64) -> u64 {
+        if depth < 0 {
+            return 0;
+        }
+        if depth == 0 {
+            return 1;
+        }
+        // Group the kept subcells by the copy the slice passes through.
+        let mut digits = vec![0; self.dims];
+        let mut groups: Vec<((i64, usize), u64)> = Vec::new();
+        for index in (0..self.masks[0].len()).filter(|&i| self.masks[0][i]) {
+            decompose(index, &self.bases, &mut digits);
+            let inner = digits.contains(&1);
+            let (digit, piece) = pell_digit(last, depth, inner);
+            let Some(piece) = piece.filter(|_| digit == digits[self.dims - 1]) else {
+                continue;
+            };
+            // Every cell of the copy's cross-section lies inside the slab.
+            match groups.iter_mut().find(|(key, _)| *key == piece) {
+                Some((_, count)) => *count += 1,
+                None => groups.push((piece, 1)),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|((d, local), count)| count.saturating_mul(self.pell_slice_cells(local, d)))
+            .fold(0, u64::saturating_add)
+    }
+
+    /// Membership test for the cell at `coords` in the depth-`depth` lattice,
+    /// by the `no_std` [`fractal_slicer_core::is_solid`].
+    pub fn is_solid(&self, coords: &[usize], depth: u32) -> bool {
+        fractal_slicer_core::is_solid(&self.bases, self.split, coords, depth, |level, index| {
+            self.mask(level)[index]
+        })
+    }
+}
+
+/// The exact number of cells the depth-`n` fractal of `rule` keeps,
+/// without generating it: the `volume(n)` cells of its lattice thinned by
+/// every level's [`Rule::survivor_fraction`], so `27^n * (20/27)^n = 20^n`
+/// for the Menger sponge. Pell and flake levels do not nest, and follow
+/// their own recurrences instead. Saturates at `u64::MAX`.
+///
+/// Tests check generators against it, `--dry-run` plans size outputs by
+/// it, and generation reports its progress in it.
+pub fn expected_cells(rule: &Rule, n: u32) -> u64 {
+    match rule.split {
+        Split::Uniform => (1..=n)
+            .map(|k| rule.survivors_at(k) as u64)
+            .fold(1, u64::saturating_mul),
+        Split::Flake => (rule.survivors() as u64).saturating_pow(n),
+        Split::Pell => {
+            let (outer, inner) = rule.pell_survivors();
+            // Cells at depths n - 1 and n, from depth -1 (empty).
+            let (mut previous, mut current) = (0u64, 1u64);
+            for _ in 0..n {
+                let next = (outer as u64)
+                    .saturating_mul(current)
+                    .saturating_add((inner as u64).saturating_mul(previous));
+                (previous, current) = (current, next);
+            }
+            current
+        }
+    }
+}
+
+/// A Menger-style rule with its own number of parts and removed digits per
+/// axis, for stretched and layered variants such as a 3×3×5 sponge.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AxisRule {
+    /// Parts each axis is split into.
+    pub bases: Vec<u32>,
+    /// Digits along each axis that count as removed; by default every
+    /// digit but the first and last, so base 3 removes the centre.
+    #[serde(default)]
+    pub remove: Vec<Vec<u32>>,
+    /// A subcell is removed once this many of its digits are removed ones:
+    /// 2 gives Menger-style tunnels, 1 Cantor dust.
+    #[serde(default = "default_min_removed")]
+    pub min_removed: usize,
+}
+
+fn default_min_removed() -> usize {
+    2
+}
+
+impl AxisRule {
+    /// The rule in `dims` dimensions, which must match the axes given.
+    pub fn rule(&self, dims: usize) -> Result<Rule> {
+        if self.bases.len() != dims {
+            return Err(Error::InvalidJob(format!(
+                "custom rule has {} bases for {dims} dimensions",
+                self.bases.len()
+            )));
+        }
+        if self.bases.is_empty() || self.bases.len() > MAX_DIMS {
+            return Err(Error::InvalidJob(format!(
+                "custom rule needs bases for 1 to {MAX_DIMS} axes"
+            )));
+        }
+        if let Some(base) = self.bases.iter().find(|&&b| b < 2) {
+            return Err(Error::InvalidJob(format!(
+                "subdivision base must be at least 2, got {base}"
+            )));
+        }
+        let subcells = self
+            .bases
+            .iter()
+            .try_fold(1usize, |n, &b| n.checked_mul(b as usize))
+            .filter(|&n| n <= MAX_SUBCELLS);
+        if subcells.is_none() {
+            return Err(Error::InvalidJob(format!(
+                "custom rule splits a cell into more than {MAX_SUBCELLS} subcells"
+            )));
+        }
+        if !self.remove.is_empty() && self.remove.len() != dims {
+            return Err(Error::InvalidJob(format!(
+                "custom rule has {} removal masks for {dims} dimensions",
+                self.remove.len()
+            )));
+        }
+        Ok(Rule::from_fn_bases("custom", &self.bases, |digits| {
+            let count = (0..dims)
+                .filter(|&axis| self.is_removed(axis, digits[axis]))
+                .count();
+            count < self.min_removed
+        }))
+    }
+
+    /// Whether `digit` along `axis` counts as a removed one.
+    pub fn is_removed(&self, axis: usize, digit: u32) -> bool {
+        match self.remove.get(axis) {
+            Some(digits) => digits.contains(&digit),
+            None => digit != 0 && digit != self.bases[axis] - 1,
+        }
+    }
+}
+
+/// Builds one rule out of several built-in ones.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleCombinator {
+    pub op: Combination,
+    /// Names of the rules to combine.
+    pub rules: Vec<String>,
+}
+
+/// How a [`RuleCombinator`] merges its rules' keep-masks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Combination {
+    /// The rules take turns by level: step `k` uses rule `(k - 1) % n`, so
+    /// `[menger, vicsek]` is Menger on odd levels and Vicsek on even ones.
+    Alternate,
+    /// A subcell survives where every rule keeps it.
+    Intersection,
+    /// A subcell survives where any rule keeps it.
+    Union,
+}
+
+impl RuleCombinator {
+    /// Looks up the named rules in `dims` dimensions and combines them.
+    pub fn rule(&self, dims: usize) -> Result<Rule> {
+        self.op.apply(&self.rules(dims)?)
+    }
+
+    /// The named rules in `dims` dimensions.
+    pub fn rules(&self, dims: usize) -> Result<Vec<Rule>> {
+        self.rules
+            .iter()
+            .map(|name| {
+                Rule::by_name(name, dims).ok_or_else(|| Error::UnknownFractal(name.clone()))
+            })
+            .collect()
+    }
+}
+
+impl Combination {
+    pub fn name(self) -> &'static str {
+        match self {
+            Combination::Alternate => "alternate",
+            Combination::Intersection => "intersection",
+            Combination::Union => "union",
+        }
+    }
+
+    /// Combines `rules`, which must share bases and split. Only
+    /// uniform splits can alternate.
+    pub fn apply(self, rules: &[Rule]) -> Result<Rule> {
+        let Some(first) = rules.first() else {
+            return Err(Error::InvalidJob(
+                "a combination needs at least one rule".into(),
+            ));
+        };
+        if let Some(other) = rules
+            .iter()
+            .find(|r| (&r.bases, r.split) != (&first.bases, first.split))
+        {
+            return Err(Error::InvalidJob(format!(
+                "cannot combine `{}` with `{}`: bases and split must match",
+                first.name, other.name
+            )));
+        }
+        let period = rules.iter().map(Rule::period).fold(1, lcm);
+        let masks: Vec<Vec<bool>> = match self {
+            Combination::Alternate => {
+                if first.split != Split::Uniform && rules.len() > 1 {
+                    return Err(Error::InvalidJob(format!(
+                        "`{}` does not split uniformly, so it cannot alternate",
+                        first.name
+                    )));
+                }
+                (1..=(period * rules.len()) as u32)
+                    .map(|k| rules[(k as usize - 1) % rules.len()].mask(k).to_vec())
+                    .collect()
+            }
+            Combination::Intersection | Combination::Union => (1..=period as u32)
+                .map(|k| {
+                    (0..first.subcells())
+                        .map(|i| match self {
+                            Combination::Union => rules.iter().any(|r| r.mask(k)[i]),
+                            _ => rules.iter().all(|r| r.mask(k)[i]),
+                        })
+                        .collect()
+                })
+                .collect(),
+        };
+        let names: Vec<&str> = rules.iter().map(Rule::name).collect();
+        Ok(Rule {
+            name: format!("{}({})", self.name(), names.join(",")),
+            bases: first.bases.clone(),
+            dims: first.dims,
+            masks,
+            split: first.split,
+        })
+    }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    let gcd = |mut a: usize, mut b: usize| {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    };
+    a / gcd(a, b) * b
+}
+
+/// Number of points of the integer lattice within L1 distance `radius` of
+/// the origin in `dims` dimensions.
+fn l1_ball(dims: usize, radius: u64) -> u64 {
+    // Choose the k nonzero axes, their signs, and a composition of at most
+    // `radius` into k positive parts.
+    let choose = |n: u64, k: u64| (0..k).fold(1u64, |acc, i| acc.saturating_mul(n - i) / (i + 1));
+    (0..=dims as u64)
+        .filter(|&k| k <= radius || k == 0)
+        .map(|k| {
+            (1u64 << k)
+                .saturating_mul(choose(dims as u64, k))
+                .saturating_mul(choose(radius, k))
+        })
+        .fold(0, u64::saturating_add)
+}
@@ -0,0 +1,274 @@
+//! Sanity checks for custom rules, so rule authors get fast feedback: how
+//! much of each level survives, whether the generated lattices agree with
+//! the closed-form counts, whether the fractal is made of copies of
+//! itself, and whether it matches the counts the author expects.
+//!
+//! A rule test file holds the custom rule as a job would, the depth to
+//! check and, optionally, the expected counts:
+//!
+//! ```json
+//! {
+//!   "custom": {"bases": [3, 3, 5], "remove": [[1], [1], [1, 2, 3]]},
+//!   "depth": 3,
+//!   "expect": {"survivors": [28], "cells": [28, 784, 21952]}
+//! }
+//! ```
+
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::Lattice;
+use crate::rule::{decompose, expected_cells, AxisRule, Rule};
+
+/// A custom rule with the counts it should produce.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleTest {
+    pub custom: AxisRule,
+    /// Levels to generate and check.
+    #[serde(default = "default_depth")]
+    pub depth: u32,
+    #[serde(default)]
+    pub expect: Expectations,
+}
+
+fn default_depth() -> u32 {
+    3
+}
+
+/// Counts the author expects, per level from 1; empty checks nothing.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    /// Subcells of a cell each level keeps.
+    #[serde(default)]
+    pub survivors: Vec<usize>,
+    /// Cells left after each level.
+    #[serde(default)]
+    pub cells: Vec<u64>,
+}
+
+/// The outcome of every check of a rule test.
+#[derive(Clone, Debug, Serialize)]
+pub struct RuleTestReport {
+    pub name: String,
+    pub depth: u32,
+    pub checks: Vec<Check>,
+}
+
+/// One check, with what it compared: `- ` lines are what was expected and
+/// `+ ` lines what the rule gave, where they differ; plain lines are notes.
+#[derive(Clone, Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub lines: Vec<String>,
+}
+
+impl RuleTestReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+}
+
+impl RuleTest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Runs every check, generating the lattice of each level up to the
+    /// test's depth.
+    #[tracing::instrument(name = "rule_test", skip_all, fields(depth = self.depth))]
+    pub fn run(&self, name: &str, cancel: &CancelToken) -> Result<RuleTestReport> {
+        if self.depth == 0 {
+            return Err(Error::InvalidJob(
+                "a rule test needs a depth of at least 1".into(),
+            ));
+        }
+        let dims = self.custom.bases.len();
+        let rule = self.custom.rule(dims)?;
+        let generated = match dims {
+            2 => generated::<2>(&rule, self.depth, cancel)?,
+            3 => generated::<3>(&rule, self.depth, cancel)?,
+            4 => generated::<4>(&rule, self.depth, cancel)?,
+            _ => {
+                return Err(Error::InvalidJob(format!(
+                    "rule tests generate 2D to 4D rules, not {dims}D"
+                )))
+            }
+        };
+        let mut checks = vec![survivors(&rule, self.depth), counts(&rule, &generated)];
+        checks.push(Check {
+            name: "self-similarity",
+            passed: generated.copies.is_empty(),
+            lines: generated.copies,
+        });
+        if !self.expect.survivors.is_empty() || !self.expect.cells.is_empty() {
+            checks.push(expected(&self.expect, &rule, &generated.cells));
+        }
+        Ok(RuleTestReport {
+            name: name.to_string(),
+            depth: self.depth,
+            checks,
+        })
+    }
+}
+
+/// What generating each level found.
+struct Generated {
+    /// Cells of the lattice at each level from 1.
+    cells: Vec<u64>,
+    /// Mismatches between the copies in each lattice and the lattice one
+    /// level down.
+    copies: Vec<String>,
+}
+
+fn generated<const D: usize>(rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<Generated> {
+    let lattices = (0..=depth)
+        .map(|level| Lattice::<D>::generate_cancellable(rule, level, cancel))
+        .collect::<Result<Vec<_>>>()?;
+    let mut copies = Vec::new();
+    let mut digits = [0; D];
+    for level in 1..=depth as usize {
+        let (whole, part) = (&lattices[level], &lattices[level - 1]);
+        let side = part.shape();
+        // Each subcell of the first split holds the lattice one level down
+        // when the rule keeps it, and nothing when it does not.
+        for index in 0..rule.subcells() {
+            cancel.check()?;
+            decompose(index, rule.bases(), &mut digits);
+            let kept = rule.mask(1)[index];
+            let offset: [usize; D] = std::array::from_fn(|axis| digits[axis] as usize * side[axis]);
+            let wrong = (0..part.len())
+                .map(|i| part.position(i))
+                .filter(|&p| {
+                    let q = std::array::from_fn(|axis| offset[axis] + p[axis]);
+                    whole.get(q) != (kept && part.get(p))
+                })
+                .count();
+            if wrong > 0 {
+                copies.push(format!(
+                    "- level {level}, subcell {digits:?}: {}",
+                    if kept {
+                        format!("a copy of level {}", level - 1)
+                    } else {
+                        "empty".to_string()
+                    }
+                ));
+                copies.push(format!(
+                    "+ level {level}, subcell {digits:?}: {wrong} cells differ"
+                ));
+            }
+        }
+    }
+    Ok(Generated {
+        cells: lattices[1..].iter().map(|l| l.count() as u64).collect(),
+        copies,
+    })
+}
+
+/// Each level must keep some subcells and remove others, or the fractal
+/// vanishes or never changes.
+fn survivors(rule: &Rule, depth: u32) -> Check {
+    let subcells = rule.subcells();
+    let mut passed = true;
+    let lines = (1..=depth.min(rule.period() as u32))
+        .map(|level| {
+            let kept = rule.survivors_at(level);
+            let fraction = kept as f64 / subcells as f64;
+            let line = format!("level {level} keeps {kept} of {subcells} ({fraction:.4})");
+            match kept {
+                0 => {
+                    passed = false;
+                    format!("+ {line}, so nothing is left")
+                }
+                _ if kept == subcells => {
+                    passed = false;
+                    format!("+ {line}, so nothing is removed")
+                }
+                _ => line,
+            }
+        })
+        .collect();
+    Check {
+        name: "survivors",
+        passed,
+        lines,
+    }
+}
+
+/// The closed-form counts against the generated lattices.
+fn counts(rule: &Rule, generated: &Generated) -> Check {
+    let mut lines = Vec::new();
+    for (level, &cells) in (1..).zip(&generated.cells) {
+        let closed = expected_cells(rule, level);
+        if closed != cells {
+            lines.push(format!("- level {level}: {closed} cells (closed form)"));
+            lines.push(format!("+ level {level}: {cells} cells (generated)"));
+        }
+    }
+    let passed = lines.is_empty();
+    if passed {
+        let cells: Vec<String> = generated.cells.iter().map(u64::to_string).collect();
+        lines.push(format!("generated as closed: {} cells", cells.join(", ")));
+    }
+    Check {
+        name: "counts",
+        passed,
+        lines,
+    }
+}
+
+/// The author's expected counts against the rule's.
+fn expected(expect: &Expectations, rule: &Rule, cells: &[u64]) -> Check {
+    let mut lines = Vec::new();
+    for (level, &want) in (1..).zip(&expect.survivors) {
+        let got = rule.survivors_at(level);
+        if got != want {
+            lines.push(format!("- level {level}: keeps {want} subcells"));
+            lines.push(format!("+ level {level}: keeps {got} subcells"));
+        }
+    }
+    for (level, &want) in (1..).zip(&expect.cells) {
+        let got = match cells.get(level as usize - 1) {
+            Some(&got) => got,
+            None => expected_cells(rule, level),
+        };
+        if got != want {
+            lines.push(format!("- level {level}: {want} cells"));
+            lines.push(format!("+ level {level}: {got} cells"));
+        }
+    }
+    Check {
+        name: "expected",
+        passed: lines.is_empty(),
+        lines,
+    }
+}
+
+impl fmt::Display for RuleTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.checks.iter().filter(|check| !check.passed).count();
+        writeln!(
+            f,
+            "rule test {}: depth {}, {} checks, {} failed",
+            self.name,
+            self.depth,
+            self.checks.len(),
+            failed
+        )?;
+        for check in &self.checks {
+            let status = if check.passed { "ok" } else { "FAIL" };
+            writeln!(f, "  {status:<5} {}", check.name)?;
+            for line in &check.lines {
+                writeln!(f, "        {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::compress::gzip;
+use crate::error::{Error, Result};
+use crate::lattice::Lattice3;
+
+/// How `.schem` outputs turn cells into Minecraft blocks.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Schematic {
+    /// The block filled cells become, such as `minecraft:stone` or
+    /// `minecraft:white_concrete`; empty cells become air.
+    #[serde(default = "default_block")]
+    pub block: String,
+    /// Longest side of one schematic, in blocks. Larger lattices are split
+    /// into pieces, each recording its offset so they paste back together.
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+fn default_block() -> String {
+    "minecraft:stone".to_string()
+}
+
+fn default_max_size() -> usize {
+    256
+}
+
+impl Default for Schematic {
+    fn default() -> Self {
+        Schematic {
+            block: default_block(),
+            max_size: default_max_size(),
+        }
+    }
+}
+
+/// One schematic's share of a lattice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Piece {
+    /// Position among the pieces along each lattice axis.
+    pub index: [usize; 3],
+    /// The lattice cell at the piece's corner.
+    pub origin: [usize; 3],
+    pub shape: [usize; 3],
+}
+
+/// Sponge schematic version 2, read by WorldEdit and most editors.
+const VERSION: i32 = 2;
+
+/// The world data version blocks are named for: Minecraft 1.20.1.
+const DATA_VERSION: i32 = 3465;
+
+impl Schematic {
+    /// The block's namespaced id, checked and with `minecraft:` added when
+    /// no namespace is given.
+    pub fn block_id(&self) -> Result<String> {
+        let valid = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || "_-./:".contains(c);
+        let id = if self.block.contains(':') {
+            self.block.clone()
+        } else {
+            format!("minecraft:{}", self.block)
+        };
+        if self.block.is_empty() || !self.block.chars().all(valid) || id == "minecraft:air" {
+            return Err(Error::InvalidJob(format!(
+                "`{}` is not a solid Minecraft block id",
+                self.block
+            )));
+        }
+        Ok(id)
+    }
+
+    /// Splits a lattice of `shape` into pieces no longer than `max_size`
+    /// along any axis.
+    pub fn pieces(&self, shape: [usize; 3]) -> Result<Vec<Piece>> {
+        // Schematic sides are stored as 16-bit integers.
+        if !(1..=u16::MAX as usize).contains(&self.max_size) {
+            return Err(Error::InvalidJob(format!(
+                "schematic size must be between 1 and {}",
+                u16::MAX
+            )));
+        }
+        let counts = shape.map(|n| n.div_ceil(self.max_size).max(1));
+        let mut pieces = Vec::new();
+        for z in 0..counts[2] {
+            for y in 0..counts[1] {
+                for x in 0..counts[0] {
+                    let index = [x, y, z];
+                    let origin = std::array::from_fn(|a| index[a] * self.max_size);
+                    let shape = std::array::from_fn(|a| self.max_size.min(shape[a] - origin[a]));
+                    pieces.push(Piece {
+                        index,
+                        origin,
+                        shape,
+                    });
+                }
+            }
+        }
+        Ok(pieces)
+    }
+
+    /// Writes one piece of `lattice` as a gzipped Sponge schematic. The
+    /// lattice's z axis becomes Minecraft's vertical y and its y axis
+    /// points north, so models stand the way they are meshed, unmirrored.
+    pub fn write(
+        &self,
+        lattice: &Lattice3,
+        piece: &Piece,
+        out: &mut impl Write,
+        cancel: &CancelToken,
+    ) -> Result<()> {
+        let block = self.block_id()?;
+        let [width, length, height] = piece.shape;
+        // The piece's northmost row, in Minecraft's southward z.
+        let north = lattice.shape()[1] - piece.origin[1] - length;
+        // Blocks in Minecraft order, x fastest, then z, then y, each a
+        // varint palette index; both indices fit in one byte.
+        let mut blocks = Vec::with_capacity(width * length * height);
+        for y in 0..height {
+            cancel.check()?;
+            for z in 0..length {
+                for x in 0..width {
+                    let cell = [
+                        piece.origin[0] + x,
+                        piece.origin[1] + length - 1 - z,
+                        piece.origin[2] + y,
+                    ];
+                    blocks.push(lattice.get(cell) as u8);
+                }
+            }
+        }
+        let mut nbt = Nbt::default();
+        nbt.compound("Schematic");
+        nbt.int("Version", VERSION);
+        nbt.int("DataVersion", DATA_VERSION);
+        nbt.short("Width", width as u16);
+        nbt.short("Height", height as u16);
+        nbt.short("Length", length as u16);
+        let offset = [piece.origin[0], piece.origin[2], north];
+        nbt.int_array("Offset", &offset.map(|c| c as i32));
+        nbt.int("PaletteMax", 2);
+        nbt.compound("Palette");
+        nbt.int("minecraft:air", 0);
+        nbt.int(&block, 1);
+        nbt.end();
+        nbt.byte_array("BlockData", &blocks);
+        nbt.end();
+        out.write_all(&gzip(&nbt.0))?;
+        Ok(())
+    }
+}
+
+/// The file `piece` is written to: `path` itself for a lattice in one
+/// piece, otherwise with the piece's positions along the lattice's x, y and
+/// z appended to the file name.
+pub fn piece_path(path: &Path, piece: &Piece, pieces: usize) -> PathBuf {
+    if pieces == 1 {
+        return path.to_path_buf();
+    }
+    let [x, y, z] = piece.index;
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{stem}_{x}_{y}_{z}");
+    if let Some(extension) = path.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// A big-endian NBT document under construction.
+#[derive(Default)]
+struct Nbt(Vec<u8>);
+
+impl Nbt {
+    fn tag(&mut self, kind: u8, name: &str) {
+        self.0.push(kind);
+        self.0.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        self.0.extend_from_slice(name.as_bytes());
+    }
+
+    fn short(&mut self, name: &str, value: u16) {
+        self.tag(2, name);
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn int(&mut self, name: &str, value: i32) {
+        self.tag(3, name);
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn byte_array(&mut self, name: &str, values: &[u8]) {
+        self.tag(7, name);
+        self.0
+            .extend_from_slice(&(values.len() as i32).to_be_bytes());
+        self.0.extend_from_slice(values);
+    }
+
+    fn int_array(&mut self, name: &str, values: &[i32]) {
+        self.tag(11, name);
+        self.0
+            .extend_from_slice(&(values.len() as i32).to_be_bytes());
+        for value in values {
+            self.0.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    /// Opens a compound, closed by [`Nbt::end`].
+    fn compound(&mut self, name: &str) {
+        self.tag(10, name);
+    }
+
+    fn end(&mut self) {
+        self.0.push(0);
+    }
+}
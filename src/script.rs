@@ -0,0 +1,207 @@
+//! Subdivision rules written as Rhai scripts, for removal that depends on
+//! where a cell lies or how deep, which per-level keep-masks cannot say.
+//!
+//! A script defines `keep(cell, level, digits)`. At subdivision step
+//! `level`, counted from 1, every kept cell of the previous step's lattice
+//! is split along each axis into its base's parts; `cell` holds the
+//! coordinates of the cell being split and `digits` those of one part
+//! within it, and the part is kept when `keep` returns true. A Menger
+//! sponge whose tunnels only open from the third level on is
+//!
+//! ```text
+//! fn keep(cell, level, digits) {
+//!     level < 3 || digits.filter(|d| d == 1).len() < 2
+//! }
+//! ```
+//!
+//! Scripts run in an embedded engine, with the `scripting` feature, that
+//! cannot read files or import modules; each call of `keep` is stopped
+//! after `max_operations` steps, so a runaway loop fails the job instead
+//! of hanging it.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::Lattice;
+use crate::rule::{decompose, MAX_DIMS, MAX_SUBCELLS};
+
+/// A scripted rule: fractal `script`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScriptRule {
+    /// The `.rhai` script defining `keep`.
+    pub file: PathBuf,
+    /// Parts each axis is split into; 3 along every axis when empty.
+    #[serde(default)]
+    pub bases: Vec<u32>,
+    /// Operations one call of `keep` may take.
+    #[serde(default = "default_max_operations")]
+    pub max_operations: u64,
+}
+
+fn default_max_operations() -> u64 {
+    100_000
+}
+
+/// A loaded script's `keep`.
+trait Keep {
+    fn keep(&mut self, cell: &[usize], level: u32, digits: &[u32]) -> Result<bool>;
+}
+
+#[cfg(not(feature = "scripting"))]
+fn load(rule: &ScriptRule) -> Result<Box<dyn Keep>> {
+    Err(Error::InvalidJob(format!(
+        "running `{}` needs the `scripting` feature",
+        rule.file.display()
+    )))
+}
+
+#[cfg(feature = "scripting")]
+use engine::load;
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use rhai::module_resolvers::DummyModuleResolver;
+    use rhai::{Array, CallFnOptions, Dynamic, Engine, Scope, AST, INT};
+
+    use super::{Keep, ScriptRule};
+    use crate::error::{Error, Result};
+
+    struct Script {
+        engine: Engine,
+        ast: AST,
+        scope: Scope<'static>,
+        name: String,
+    }
+
+    /// Compiles the script in an engine without file or module access.
+    pub(super) fn load(rule: &ScriptRule) -> Result<Box<dyn Keep>> {
+        let name = rule.file.display().to_string();
+        let mut engine = Engine::new();
+        engine
+            .set_module_resolver(DummyModuleResolver::new())
+            .disable_symbol("eval")
+            .set_max_operations(rule.max_operations)
+            .set_max_call_levels(64)
+            .set_max_expr_depths(64, 64)
+            .set_max_string_size(1 << 16)
+            .set_max_array_size(1 << 16)
+            .set_max_map_size(1 << 12)
+            .on_print(|text| tracing::info!(target: "script", "{text}"))
+            .on_debug(|text, _, _| tracing::debug!(target: "script", "{text}"));
+        let source = std::fs::read_to_string(&rule.file)?;
+        let ast = engine
+            .compile(source)
+            .map_err(|e| Error::InvalidJob(format!("{name}: {e}")))?;
+        let defines_keep = ast
+            .iter_functions()
+            .any(|f| f.name == "keep" && f.params.len() == 3);
+        if !defines_keep {
+            return Err(Error::InvalidJob(format!(
+                "{name}: no `keep(cell, level, digits)` function"
+            )));
+        }
+        Ok(Box::new(Script {
+            engine,
+            ast,
+            scope: Scope::new(),
+            name,
+        }))
+    }
+
+    impl Keep for Script {
+        fn keep(&mut self, cell: &[usize], level: u32, digits: &[u32]) -> Result<bool> {
+            let cell: Array = cell.iter().map(|&c| Dynamic::from(c as INT)).collect();
+            let digits: Array = digits.iter().map(|&d| Dynamic::from(d as INT)).collect();
+            // Only `keep` runs, not the script's top level.
+            let options = CallFnOptions::new().eval_ast(false);
+            self.engine
+                .call_fn_with_options(
+                    options,
+                    &mut self.scope,
+                    &self.ast,
+                    "keep",
+                    (cell, level as INT, digits),
+                )
+                .map_err(|e| Error::InvalidJob(format!("{}: {e}", self.name)))
+        }
+    }
+}
+
+impl ScriptRule {
+    /// The parts each of `dims` axes is split into.
+    pub fn bases(&self, dims: usize) -> Result<Vec<u32>> {
+        let bases = if self.bases.is_empty() {
+            vec![3; dims]
+        } else {
+            self.bases.clone()
+        };
+        if bases.len() != dims || dims > MAX_DIMS {
+            return Err(Error::InvalidJob(format!(
+                "script rule has {} bases for {dims} dimensions",
+                bases.len()
+            )));
+        }
+        if let Some(base) = bases.iter().find(|&&b| b < 2) {
+            return Err(Error::InvalidJob(format!(
+                "subdivision base must be at least 2, got {base}"
+            )));
+        }
+        let subcells = bases
+            .iter()
+            .try_fold(1usize, |n, &b| n.checked_mul(b as usize))
+            .filter(|&n| n <= MAX_SUBCELLS);
+        if subcells.is_none() {
+            return Err(Error::InvalidJob(format!(
+                "script rule splits a cell into more than {MAX_SUBCELLS} subcells"
+            )));
+        }
+        if self.max_operations == 0 {
+            return Err(Error::InvalidJob(
+                "script operation budget must be positive".into(),
+            ));
+        }
+        Ok(bases)
+    }
+
+    /// Builds the lattice level by level, asking the script about every
+    /// part of every cell kept so far.
+    #[tracing::instrument(name = "generate", skip_all, fields(script = %self.file.display(), depth = depth))]
+    pub fn generate<const D: usize>(&self, depth: u32, cancel: &CancelToken) -> Result<Lattice<D>> {
+        let bases = self.bases(D)?;
+        let mut shape = [1usize; D];
+        for (side, &base) in shape.iter_mut().zip(&bases) {
+            *side = (base as usize).checked_pow(depth).ok_or_else(|| {
+                Error::InvalidJob(format!("a depth-{depth} script lattice is too large"))
+            })?;
+        }
+        let mut script = load(self)?;
+        let subcells: usize = bases.iter().map(|&b| b as usize).product();
+        let mut digits = [0; D];
+        let mut kept = vec![[0usize; D]];
+        for level in 1..=depth {
+            let mut next = Vec::new();
+            for cell in &kept {
+                cancel.check()?;
+                for index in 0..subcells {
+                    decompose(index, &bases, &mut digits);
+                    if script.keep(cell, level, &digits)? {
+                        next.push(std::array::from_fn(|axis| {
+                            cell[axis] * bases[axis] as usize + digits[axis] as usize
+                        }));
+                    }
+                }
+            }
+            kept = next;
+            tracing::debug!(done = level, total = depth, "levels built");
+        }
+        let mut lattice = Lattice::new(shape);
+        for p in kept {
+            lattice.set(p, true);
+        }
+        Ok(lattice)
+    }
+}
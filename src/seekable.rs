@@ -0,0 +1,212 @@
+//! zstd compression in the seekable format: the output cut into frames of
+//! [`FRAME_SIZE`] bytes, compressed independently, followed by a table of
+//! their sizes in a skippable frame. Any zstd tool decompresses the whole
+//! file, while [`SeekableReader`] reads a single slice or chunk of a large
+//! volume by decompressing only the frames it overlaps.
+//!
+//! Any output whose name ends in `.zst`, such as `sponge.nii.zst`, is
+//! written this way in the format of the name before it.
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use ruzstd::decoding::StreamingDecoder;
+use ruzstd::encoding::{compress_to_vec, CompressionLevel};
+
+use crate::error::Result;
+
+/// Uncompressed bytes per frame, the most a partial read decompresses
+/// beyond what it asked for.
+pub const FRAME_SIZE: usize = 1 << 20;
+
+const SKIPPABLE_MAGIC: u32 = 0x184d_2a5e;
+const SEEKABLE_MAGIC: u32 = 0x8f92_eab1;
+/// Frame count, descriptor and magic closing the seek table.
+const FOOTER: usize = 9;
+
+/// Whether `path` names a zstd-compressed output.
+pub fn is_compressed(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("zst"))
+}
+
+/// The path `path` compresses, which names its format: `sponge.nii` for
+/// `sponge.nii.zst`, and `path` itself when it is not compressed.
+pub fn inner_path(path: &Path) -> PathBuf {
+    if is_compressed(path) {
+        path.with_extension("")
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Compresses everything written through it, a frame at a time.
+pub struct SeekableWriter<W: Write> {
+    inner: W,
+    frame: Vec<u8>,
+    /// Compressed and uncompressed size of every frame written.
+    frames: Vec<(u32, u32)>,
+}
+
+impl<W: Write> SeekableWriter<W> {
+    pub fn new(inner: W) -> Self {
+        SeekableWriter {
+            inner,
+            frame: Vec::with_capacity(FRAME_SIZE),
+            frames: Vec::new(),
+        }
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        let compressed = compress_to_vec(self.frame.as_slice(), CompressionLevel::Fastest);
+        self.inner.write_all(&compressed)?;
+        self.frames
+            .push((compressed.len() as u32, self.frame.len() as u32));
+        self.frame.clear();
+        Ok(())
+    }
+
+    /// Compresses the last frame and appends the seek table.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.frame.is_empty() {
+            self.write_frame()?;
+        }
+        let mut table = Vec::with_capacity(8 + self.frames.len() * 8 + FOOTER);
+        table.extend_from_slice(&SKIPPABLE_MAGIC.to_le_bytes());
+        table.extend_from_slice(&((self.frames.len() * 8 + FOOTER) as u32).to_le_bytes());
+        for (compressed, uncompressed) in &self.frames {
+            table.extend_from_slice(&compressed.to_le_bytes());
+            table.extend_from_slice(&uncompressed.to_le_bytes());
+        }
+        table.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        // No checksums; zstd's own frames carry them.
+        table.push(0);
+        table.extend_from_slice(&SEEKABLE_MAGIC.to_le_bytes());
+        self.inner.write_all(&table)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for SeekableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(FRAME_SIZE - self.frame.len());
+        self.frame.extend_from_slice(&buf[..n]);
+        if self.frame.len() == FRAME_SIZE {
+            self.write_frame()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads a seekable zstd file as its uncompressed bytes, decompressing a
+/// frame only when a read reaches it.
+pub struct SeekableReader<R> {
+    inner: R,
+    /// Compressed and uncompressed offset at which every frame starts,
+    /// then the ends of the last.
+    starts: Vec<(u64, u64)>,
+    position: u64,
+    /// The most recently decompressed frame.
+    cached: Option<(usize, Vec<u8>)>,
+}
+
+impl<R: Read + Seek> SeekableReader<R> {
+    /// Reads the seek table at the end of `inner`.
+    pub fn new(mut inner: R) -> Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "not a seekable zstd file");
+        let mut footer = [0; FOOTER];
+        inner
+            .seek(SeekFrom::End(-(FOOTER as i64)))
+            .map_err(|_| invalid())?;
+        inner.read_exact(&mut footer)?;
+        let word = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+        if word(&footer[5..]) != SEEKABLE_MAGIC {
+            return Err(invalid().into());
+        }
+        let count = word(&footer[..4]) as usize;
+        let entry = if footer[4] & 0x80 != 0 { 12 } else { 8 };
+        let mut table = vec![0; 8 + count * entry];
+        inner
+            .seek(SeekFrom::End(-((table.len() + FOOTER) as i64)))
+            .map_err(|_| invalid())?;
+        inner.read_exact(&mut table)?;
+        if word(&table[..4]) != SKIPPABLE_MAGIC {
+            return Err(invalid().into());
+        }
+        let mut starts = vec![(0, 0)];
+        for sizes in table[8..].chunks_exact(entry) {
+            let (compressed, uncompressed) = starts[starts.len() - 1];
+            starts.push((
+                compressed + word(&sizes[..4]) as u64,
+                uncompressed + word(&sizes[4..8]) as u64,
+            ));
+        }
+        Ok(SeekableReader {
+            inner,
+            starts,
+            position: 0,
+            cached: None,
+        })
+    }
+
+    /// The uncompressed length.
+    pub fn len(&self) -> u64 {
+        self.starts[self.starts.len() - 1].1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The decompressed frame `i`.
+    fn frame(&mut self, i: usize) -> io::Result<&[u8]> {
+        if self.cached.as_ref().is_none_or(|(cached, _)| *cached != i) {
+            let (start, end) = (self.starts[i].0, self.starts[i + 1].0);
+            let mut compressed = vec![0; (end - start) as usize];
+            self.inner.seek(SeekFrom::Start(start))?;
+            self.inner.read_exact(&mut compressed)?;
+            let mut decoder = StreamingDecoder::new(compressed.as_slice())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            let mut frame = Vec::new();
+            decoder.read_to_end(&mut frame)?;
+            self.cached = Some((i, frame));
+        }
+        Ok(&self.cached.as_ref().unwrap().1)
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len() || buf.is_empty() {
+            return Ok(0);
+        }
+        // The last frame starting at or before the position.
+        let i = self
+            .starts
+            .partition_point(|&(_, start)| start <= self.position)
+            - 1;
+        let offset = (self.position - self.starts[i].1) as usize;
+        let frame = self.frame(i)?;
+        let n = buf.len().min(frame.len() - offset);
+        buf[..n].copy_from_slice(&frame[offset..offset + n]);
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "seek before the start"))?;
+        Ok(self.position)
+    }
+}
@@ -0,0 +1,661 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::{self, write_file_atomically, Artifact, ExportOptions, Format};
+use crate::lattice::Lattice4;
+use crate::metrics::{resident_memory_bytes, Counter, Exposition, Histogram};
+use crate::rule::Rule;
+
+mod access;
+mod queue;
+
+use access::Limiter;
+pub use access::{Access, Client, Quota};
+pub use queue::{JobState, JobStatus};
+use queue::{Queue, Refusal};
+
+/// Most bytes of request line and headers read from a connection before
+/// it is answered with a 431.
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+
+/// Limits and sizes for server mode.
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    /// Deepest fractal a request may ask for.
+    pub max_depth: u32,
+    /// Number of generated hypersponges kept for reuse between requests.
+    pub cache_capacity: usize,
+    /// How long a `/slice` request may spend generating and meshing before
+    /// it is abandoned with a 503. Queued jobs run until done or cancelled.
+    pub request_timeout: Option<Duration>,
+    /// Queued jobs run at once.
+    pub workers: usize,
+    /// Jobs that may wait for a worker before submissions are refused
+    /// with a 503.
+    pub max_queued: usize,
+    /// Connections handled at once; further ones wait to be accepted.
+    pub connections: usize,
+    /// How long a connection may take to send its request, or to take
+    /// each part of the response, before it is dropped.
+    pub io_timeout: Option<Duration>,
+    /// The clients served and their quotas; without, every request is
+    /// served within the limits above.
+    pub access: Option<Access>,
+    /// A directory, or with the `object-store` feature an `s3://` or
+    /// `gs://` prefix, that each queued job's output is also written to
+    /// as `job-<id>.<format>`; see [`crate::store::create`].
+    pub output: Option<PathBuf>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            max_depth: 4,
+            cache_capacity: 4,
+            request_timeout: Some(Duration::from_secs(60)),
+            workers: 2,
+            max_queued: 64,
+            connections: 64,
+            io_timeout: Some(Duration::from_secs(10)),
+            access: None,
+            output: None,
+        }
+    }
+}
+
+/// A minimal HTTP server handing out slices of generated hypersponges.
+///
+/// Routes:
+/// * `GET /slice?fractal=menger&depth=2&w=0&format=obj` — one w-slice as a mesh
+/// * `POST /jobs?fractal=menger&depth=4&w=0&format=obj&priority=1` — queues
+///   the slice as a job and returns its status, with its id
+/// * `GET /jobs` — the status of every job held
+/// * `GET /jobs/{id}` — one job's status and progress
+/// * `GET /jobs/{id}/result` — a done job's mesh, or a 409 with its status;
+///   with [`ServerConfig::output`] set, its status names where it was
+///   also written
+/// * `DELETE /jobs/{id}` — cancels a job
+/// * `GET /metrics` — Prometheus metrics
+///
+/// With [`ServerConfig::access`] set, every request is made by a client
+/// and within its [`Quota`]: others are refused with a 401, and requests
+/// beyond a client's rate or jobs with a 429. Clients see only their own
+/// jobs.
+///
+/// Connections are handled by a fixed pool of
+/// [`ServerConfig::connections`] threads, each dropping a connection that
+/// stalls past [`ServerConfig::io_timeout`] and answering one whose
+/// request line and headers run past 16 KiB with a 431.
+pub struct Server {
+    config: ServerConfig,
+    cache: Mutex<VecDeque<(CacheKey, Arc<Lattice4>)>>,
+    jobs: Queue,
+    limiter: Limiter,
+    metrics: ServerMetrics,
+}
+
+/// Who made a request and what they may ask for.
+struct Caller {
+    /// The client's name, empty when the server has no access list.
+    name: String,
+    max_depth: u32,
+    max_jobs: usize,
+}
+
+type CacheKey = (String, u32);
+
+#[derive(Debug)]
+struct ServerMetrics {
+    requests: Counter,
+    slices_served: Counter,
+    cache_hits: Counter,
+    cache_misses: Counter,
+    jobs_finished: Counter,
+    refused: Counter,
+    generation_seconds: Histogram,
+}
+
+/// A slice to make, as `/slice` and `POST /jobs` ask for it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SliceRequest {
+    pub fractal: String,
+    pub depth: u32,
+    pub w: usize,
+    /// The output format's extension.
+    pub format: String,
+}
+
+impl SliceRequest {
+    fn parse(request: &Request) -> Result<Self> {
+        Ok(SliceRequest {
+            fractal: request.param("fractal").unwrap_or("menger").to_string(),
+            depth: parse_param(request, "depth", 2)?,
+            w: parse_param(request, "w", 0)?,
+            format: request.param("format").unwrap_or("obj").to_string(),
+        })
+    }
+
+    /// The output format, one a single slice can be written in.
+    fn format(&self) -> Result<Format> {
+        Format::from_extension(&self.format)
+            .filter(|format| !format.is_image_stack() && *format != Format::Zarr)
+            .ok_or_else(|| Error::InvalidJob(format!("unknown format `{}`", self.format)))
+    }
+}
+
+/// A parsed request line, with the headers and address it came with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: Vec<(String, String)>,
+    pub headers: Vec<(String, String)>,
+    pub peer: Option<IpAddr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: u16, body: impl Into<String>) -> Self {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into().into_bytes(),
+        }
+    }
+
+    fn json(status: u16, value: &impl Serialize) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body: serde_json::to_vec_pretty(value).expect("statuses serialize"),
+        }
+    }
+}
+
+impl Request {
+    /// Parses an HTTP request line such as `GET /slice?w=1 HTTP/1.1`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?;
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let query = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (key.to_string(), value.to_string())
+            })
+            .collect();
+        Some(Request {
+            method,
+            path: path.to_string(),
+            query,
+            headers: Vec::new(),
+            peer: None,
+        })
+    }
+
+    /// Parses a header line such as `Authorization: Bearer 3f9c` into
+    /// the request's headers.
+    pub fn add_header(&mut self, line: &str) {
+        if let Some((name, value)) = line.split_once(':') {
+            self.headers
+                .push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The bearer token the request is authorized with.
+    pub fn token(&self) -> Option<&str> {
+        let (scheme, token) = self.header("authorization")?.split_once(' ')?;
+        scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+    }
+
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl Server {
+    pub fn new(config: ServerConfig) -> Self {
+        Server {
+            jobs: Queue::new(config.max_queued),
+            config,
+            cache: Mutex::new(VecDeque::new()),
+            limiter: Limiter::default(),
+            metrics: ServerMetrics {
+                requests: Counter::default(),
+                slices_served: Counter::default(),
+                cache_hits: Counter::default(),
+                cache_misses: Counter::default(),
+                jobs_finished: Counter::default(),
+                refused: Counter::default(),
+                generation_seconds: Histogram::new(&[0.001, 0.01, 0.1, 1.0, 10.0, 60.0]),
+            },
+        }
+    }
+
+    /// Starts the job workers and a fixed pool of connection handlers,
+    /// and accepts connections forever, each waiting for a free handler.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        for _ in 0..self.config.workers.max(1) {
+            let server = Arc::clone(&self);
+            std::thread::spawn(move || server.work());
+        }
+        let (accepted, waiting) = mpsc::sync_channel::<TcpStream>(0);
+        let waiting = Arc::new(Mutex::new(waiting));
+        for _ in 0..self.config.connections.max(1) {
+            let server = Arc::clone(&self);
+            let waiting = Arc::clone(&waiting);
+            std::thread::spawn(move || loop {
+                let Ok(stream) = waiting.lock().unwrap().recv() else {
+                    return;
+                };
+                if let Err(e) = server.handle_connection(stream) {
+                    tracing::warn!("connection failed: {e}");
+                }
+            });
+        }
+        for stream in listener.incoming() {
+            if accepted.send(stream?).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(self.config.io_timeout)?;
+        stream.set_write_timeout(self.config.io_timeout)?;
+        let mut reader = BufReader::new((&stream).take(MAX_HEADER_BYTES));
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut request = Request::parse(&line);
+        // Read the headers; no route needs a body.
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            if let Some(request) = &mut request {
+                request.add_header(&header);
+            }
+            header.clear();
+        }
+        let response = if reader.get_ref().limit() == 0 {
+            Response::text(431, "request headers too large\n")
+        } else {
+            match request {
+                Some(mut request) => {
+                    request.peer = stream.peer_addr().ok().map(|addr| addr.ip());
+                    self.handle(&request)
+                }
+                None => Response::text(400, "malformed request line\n"),
+            }
+        };
+        write_response(&stream, &response)
+    }
+
+    /// Routes a request to its handler.
+    pub fn handle(&self, request: &Request) -> Response {
+        self.metrics.requests.inc();
+        tracing::info!(method = %request.method, path = %request.path, "request");
+        let caller = match self.caller(request) {
+            Ok(caller) => caller,
+            Err(response) => {
+                self.metrics.refused.inc();
+                return response;
+            }
+        };
+        let path: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), path.as_slice()) {
+            ("GET", ["slice"]) => match self.slice(request, &caller) {
+                Ok(response) => response,
+                Err(e) => error_response(e),
+            },
+            ("GET", ["metrics"]) => Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4",
+                body: self.render_metrics().into_bytes(),
+            },
+            ("POST", ["jobs"]) => match self.submit(request, &caller) {
+                Ok(response) => response,
+                Err(e) => error_response(e),
+            },
+            ("GET", ["jobs"]) => Response::json(200, &self.jobs.list(&caller.name)),
+            ("GET", ["jobs", id]) => {
+                match parse_id(id).and_then(|id| self.jobs.status(id, &caller.name)) {
+                    Some(status) => Response::json(200, &status),
+                    None => Response::text(404, "no such job\n"),
+                }
+            }
+            ("GET", ["jobs", id, "result"]) => {
+                match parse_id(id).and_then(|id| self.jobs.result(id, &caller.name)) {
+                    Some((status, Some(body))) => Response {
+                        status: 200,
+                        content_type: status.request.format().map_or("", Format::mime_type),
+                        body,
+                    },
+                    Some((status, None)) => Response::json(409, &status),
+                    None => Response::text(404, "no such job\n"),
+                }
+            }
+            ("DELETE", ["jobs", id]) => {
+                match parse_id(id).and_then(|id| self.jobs.cancel(id, &caller.name)) {
+                    Some(status) => Response::json(200, &status),
+                    None => Response::text(404, "no such job\n"),
+                }
+            }
+            (_, ["slice" | "metrics" | "jobs"] | ["jobs", _] | ["jobs", _, "result"]) => {
+                Response::text(405, "method not allowed\n")
+            }
+            _ => Response::text(404, "not found\n"),
+        }
+    }
+
+    /// Identifies the client making a request and takes one of its
+    /// requests, or refuses it.
+    fn caller(&self, request: &Request) -> std::result::Result<Caller, Response> {
+        let Some(access) = &self.config.access else {
+            return Ok(Caller {
+                name: String::new(),
+                max_depth: self.config.max_depth,
+                max_jobs: usize::MAX,
+            });
+        };
+        let Some((name, quota)) = access.identify(request.token(), request.peer) else {
+            return Err(Response::text(401, "a valid bearer token is required\n"));
+        };
+        if !self.limiter.admit(&name, quota.requests_per_minute) {
+            tracing::info!(client = %name, "rate limited");
+            return Err(Response::text(
+                429,
+                "too many requests; try again shortly\n",
+            ));
+        }
+        Ok(Caller {
+            name,
+            max_depth: quota.max_depth.min(self.config.max_depth),
+            max_jobs: quota.max_jobs,
+        })
+    }
+
+    fn slice(&self, request: &Request, caller: &Caller) -> Result<Response> {
+        let slice = SliceRequest::parse(request)?;
+        self.validate(&slice, caller.max_depth)?;
+        let allowed = caller
+            .max_jobs
+            .saturating_sub(self.jobs.active(&caller.name));
+        let Some(_slicing) = self.limiter.start(&caller.name, allowed) else {
+            return Ok(too_many_jobs(caller));
+        };
+        let mut cancel = CancelToken::new();
+        if let Some(timeout) = self.config.request_timeout {
+            cancel = cancel.with_deadline(Instant::now() + timeout);
+        }
+        let body = self.make(&slice, &cancel, |_| {})?;
+        Ok(Response {
+            status: 200,
+            content_type: slice.format()?.mime_type(),
+            body,
+        })
+    }
+
+    /// Queues a slice as a job, at the request's `priority`.
+    fn submit(&self, request: &Request, caller: &Caller) -> Result<Response> {
+        let slice = SliceRequest::parse(request)?;
+        let priority: i32 = parse_param(request, "priority", 0)?;
+        self.validate(&slice, caller.max_depth)?;
+        let allowed = caller
+            .max_jobs
+            .saturating_sub(self.limiter.slicing(&caller.name));
+        Ok(
+            match self.jobs.submit(&caller.name, slice, priority, allowed) {
+                Ok(status) => Response::json(202, &status),
+                Err(Refusal::Quota) => too_many_jobs(caller),
+                Err(Refusal::Full) => Response::text(503, "the job queue is full\n"),
+            },
+        )
+    }
+
+    /// Runs queued jobs, one at a time, forever.
+    fn work(&self) {
+        loop {
+            let (id, slice, cancel) = self.jobs.next();
+            tracing::info!(id, fractal = %slice.fractal, depth = slice.depth, "job started");
+            let result = self
+                .make(&slice, &cancel, |stage| self.jobs.advance(id, stage))
+                .and_then(|body| {
+                    let artifact = self.store(id, &slice, &body)?;
+                    Ok((body, artifact))
+                });
+            if let Err(e) = &result {
+                tracing::info!(id, "job stopped: {e}");
+            }
+            self.jobs.finish(id, result);
+            self.metrics.jobs_finished.inc();
+        }
+    }
+
+    /// Writes a job's output to the server's output location, if it has
+    /// one.
+    fn store(&self, id: u64, slice: &SliceRequest, body: &[u8]) -> Result<Option<Artifact>> {
+        let Some(output) = &self.config.output else {
+            return Ok(None);
+        };
+        let path = output.join(format!("job-{id}.{}", slice.format));
+        let artifact = write_file_atomically(&path, |out| Ok(out.write_all(body)?))?;
+        tracing::info!(id, path = %path.display(), "job stored");
+        Ok(Some(artifact))
+    }
+
+    /// Checks a slice against the server's limits and `max_depth`,
+    /// returning its rule and format.
+    fn validate(&self, slice: &SliceRequest, max_depth: u32) -> Result<(Rule, Format)> {
+        let format = slice.format()?;
+        if slice.depth > max_depth {
+            return Err(Error::InvalidJob(format!(
+                "depth {} exceeds the limit of {max_depth}",
+                slice.depth
+            )));
+        }
+        let rule = Rule::by_name(&slice.fractal, 4)
+            .ok_or_else(|| Error::UnknownFractal(slice.fractal.clone()))?;
+        let side = rule.side(slice.depth);
+        if slice.w >= side {
+            return Err(Error::InvalidJob(format!(
+                "w={} is outside the lattice (side {side})",
+                slice.w
+            )));
+        }
+        Ok((rule, format))
+    }
+
+    /// Generates a slice and writes it, telling `stage` when writing
+    /// starts.
+    fn make(
+        &self,
+        slice: &SliceRequest,
+        cancel: &CancelToken,
+        stage: impl Fn(JobState),
+    ) -> Result<Vec<u8>> {
+        let (rule, format) = self.validate(slice, self.config.max_depth)?;
+        let lattice = self.lattice(&rule, slice.depth, cancel)?;
+        stage(JobState::Writing);
+        let mut body = Vec::new();
+        export::write(
+            &lattice.slice_w(slice.w),
+            format,
+            &mut body,
+            &ExportOptions::default(),
+            cancel,
+        )?;
+        self.metrics.slices_served.inc();
+        Ok(body)
+    }
+
+    /// Fetches a hypersponge from the cache or generates and caches it.
+    fn lattice(&self, rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<Arc<Lattice4>> {
+        let key = (rule.name().to_string(), depth);
+        if let Some((_, lattice)) = self.cache.lock().unwrap().iter().find(|(k, _)| *k == key) {
+            self.metrics.cache_hits.inc();
+            return Ok(Arc::clone(lattice));
+        }
+        self.metrics.cache_misses.inc();
+        let start = Instant::now();
+        let lattice = Arc::new(Lattice4::generate_recursive(rule, depth, cancel)?);
+        self.metrics
+            .generation_seconds
+            .observe(start.elapsed().as_secs_f64());
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.config.cache_capacity {
+            cache.pop_front();
+        }
+        if self.config.cache_capacity > 0 {
+            cache.push_back((key, Arc::clone(&lattice)));
+        }
+        Ok(lattice)
+    }
+
+    pub fn render_metrics(&self) -> String {
+        let m = &self.metrics;
+        let mut out = Exposition::default();
+        out.counter(
+            "fractal_slicer_http_requests_total",
+            "HTTP requests received.",
+            &m.requests,
+        );
+        out.counter(
+            "fractal_slicer_slices_served_total",
+            "Slices returned to clients.",
+            &m.slices_served,
+        );
+        out.counter(
+            "fractal_slicer_cache_hits_total",
+            "Requests served from the lattice cache.",
+            &m.cache_hits,
+        );
+        out.counter(
+            "fractal_slicer_cache_misses_total",
+            "Requests that had to generate a lattice.",
+            &m.cache_misses,
+        );
+        out.counter(
+            "fractal_slicer_requests_refused_total",
+            "Requests refused for want of a token or beyond a client's rate.",
+            &m.refused,
+        );
+        out.counter(
+            "fractal_slicer_jobs_finished_total",
+            "Queued jobs done, failed or cancelled while running.",
+            &m.jobs_finished,
+        );
+        let (queued, running) = self.jobs.load();
+        out.gauge(
+            "fractal_slicer_jobs_queued",
+            "Jobs waiting for a worker.",
+            queued as f64,
+        );
+        out.gauge(
+            "fractal_slicer_jobs_running",
+            "Jobs being generated or written.",
+            running as f64,
+        );
+        out.histogram(
+            "fractal_slicer_generation_seconds",
+            "Time spent generating hypersponges.",
+            &m.generation_seconds,
+        );
+        if let Some(bytes) = resident_memory_bytes() {
+            out.gauge(
+                "process_resident_memory_bytes",
+                "Resident memory size in bytes.",
+                bytes as f64,
+            );
+        }
+        out.finish()
+    }
+}
+
+fn parse_param<T: std::str::FromStr>(request: &Request, key: &str, default: T) -> Result<T> {
+    match request.param(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| Error::InvalidJob(format!("invalid {key} `{value}`"))),
+        None => Ok(default),
+    }
+}
+
+fn too_many_jobs(caller: &Caller) -> Response {
+    Response::text(
+        429,
+        format!(
+            "at most {} jobs may be queued or running at once\n",
+            caller.max_jobs
+        ),
+    )
+}
+
+/// A 400 for requests the server cannot serve, a 503 for those that ran
+/// out of time and a 500 for failures of the server itself.
+fn error_response(e: Error) -> Response {
+    match e {
+        Error::Cancelled => Response::text(503, "request timed out\n"),
+        e @ (Error::Io(_) | Error::Json(_)) => {
+            tracing::error!("request failed: {e}");
+            Response::text(500, "internal error\n")
+        }
+        e => Response::text(400, format!("{e}\n")),
+    }
+}
+
+fn parse_id(id: &str) -> Option<u64> {
+    id.parse().ok()
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
@@ -0,0 +1,220 @@
+//! Who may use the server and how much, so it can be exposed publicly:
+//! clients present bearer tokens naming them, each with a quota of depth,
+//! jobs at once and requests per minute, and requests without a token get
+//! the anonymous quota, counted per address, or are refused.
+//!
+//! ```json
+//! {
+//!   "anonymous": {"max_depth": 3, "max_jobs": 1, "requests_per_minute": 30},
+//!   "clients": [
+//!     {"name": "site", "token": "3f9c…", "quota": {"max_depth": 5, "max_jobs": 4}}
+//!   ]
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// Rate buckets held before full ones, whose clients have been quiet for a
+/// minute, are forgotten.
+const BUCKETS: usize = 4096;
+
+/// The clients a server accepts.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Access {
+    /// The quota of requests without a token, each address counted apart;
+    /// without one they are refused.
+    #[serde(default)]
+    pub anonymous: Option<Quota>,
+    #[serde(default)]
+    pub clients: Vec<Client>,
+}
+
+/// A client and the token it presents as `Authorization: Bearer <token>`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Client {
+    pub name: String,
+    pub token: String,
+    #[serde(default)]
+    pub quota: Quota,
+}
+
+/// What one client may ask of the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Quota {
+    /// Deepest fractal the client may ask for, within the server's own
+    /// limit.
+    pub max_depth: u32,
+    /// Jobs the client may have queued or running at once.
+    pub max_jobs: usize,
+    /// Requests the client may make a minute, in bursts of up to as many;
+    /// 0 for no limit.
+    pub requests_per_minute: u32,
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Quota {
+            max_depth: 4,
+            max_jobs: 2,
+            requests_per_minute: 60,
+        }
+    }
+}
+
+impl Access {
+    pub fn load(path: &Path) -> Result<Self> {
+        let access: Access = std::fs::read_to_string(path)
+            .map_err(Error::from)
+            .and_then(|text| Ok(serde_json::from_str(&text)?))
+            .map_err(|e| Error::InvalidJob(format!("{}: {e}", path.display())))?;
+        access.validate()?;
+        Ok(access)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (i, client) in self.clients.iter().enumerate() {
+            if client.token.is_empty() {
+                return Err(Error::InvalidJob(format!(
+                    "client `{}` has an empty token",
+                    client.name
+                )));
+            }
+            if let Some(other) = self.clients[..i]
+                .iter()
+                .find(|other| other.name == client.name || other.token == client.token)
+            {
+                return Err(Error::InvalidJob(format!(
+                    "clients `{}` and `{}` share a name or token",
+                    other.name, client.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The name and quota of the client presenting `token`, or of the
+    /// anonymous client at `peer` without one; None if neither is
+    /// accepted.
+    pub(crate) fn identify(
+        &self,
+        token: Option<&str>,
+        peer: Option<IpAddr>,
+    ) -> Option<(String, Quota)> {
+        match token {
+            Some(token) => self
+                .clients
+                .iter()
+                // Every token is compared in full, so timing reveals
+                // nothing of them.
+                .fold(None, |found, client| {
+                    let matches = same(client.token.as_bytes(), token.as_bytes());
+                    found.or(matches.then_some(client))
+                })
+                .map(|client| (client.name.clone(), client.quota)),
+            None => self.anonymous.map(|quota| {
+                let peer = peer.map_or("unknown".into(), |peer| peer.to_string());
+                (format!("anonymous@{peer}"), quota)
+            }),
+        }
+    }
+}
+
+/// Compares byte strings in time depending only on their lengths.
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// A client's allowance of requests, refilled continuously.
+struct Bucket {
+    requests: f64,
+    updated: Instant,
+}
+
+/// Per-client token buckets for the requests-per-minute quotas, and the
+/// `/slice` requests each client has running, which count as jobs.
+#[derive(Default)]
+pub(crate) struct Limiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+    slicing: Mutex<HashMap<String, usize>>,
+}
+
+impl Limiter {
+    /// Takes one of the client's requests, or returns false if it has used
+    /// them all.
+    pub(crate) fn admit(&self, client: &str, per_minute: u32) -> bool {
+        if per_minute == 0 {
+            return true;
+        }
+        let burst = per_minute as f64;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= BUCKETS {
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated).as_secs() < 60);
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            requests: burst,
+            updated: now,
+        });
+        let refilled = now.duration_since(bucket.updated).as_secs_f64() * burst / 60.0;
+        bucket.requests = (bucket.requests + refilled).min(burst);
+        bucket.updated = now;
+        if bucket.requests < 1.0 {
+            return false;
+        }
+        bucket.requests -= 1.0;
+        true
+    }
+
+    /// The client's `/slice` requests running.
+    pub(crate) fn slicing(&self, client: &str) -> usize {
+        self.slicing
+            .lock()
+            .unwrap()
+            .get(client)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Counts a `/slice` request of the client's while the guard lives,
+    /// unless it already has `allowed` running.
+    pub(crate) fn start(&self, client: &str, allowed: usize) -> Option<Slicing<'_>> {
+        let mut slicing = self.slicing.lock().unwrap();
+        if slicing.get(client).copied().unwrap_or(0) >= allowed {
+            return None;
+        }
+        *slicing.entry(client.to_string()).or_default() += 1;
+        Some(Slicing {
+            limiter: self,
+            client: client.to_string(),
+        })
+    }
+}
+
+/// A running `/slice` request, counted until dropped.
+pub(crate) struct Slicing<'a> {
+    limiter: &'a Limiter,
+    client: String,
+}
+
+impl Drop for Slicing<'_> {
+    fn drop(&mut self) {
+        let mut slicing = self.limiter.slicing.lock().unwrap();
+        if let Some(running) = slicing.get_mut(&self.client) {
+            *running -= 1;
+            if *running == 0 {
+                slicing.remove(&self.client);
+            }
+        }
+    }
+}
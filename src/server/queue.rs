@@ -0,0 +1,313 @@
+//! The server's job queue: slices submitted to run in the background on a
+//! fixed number of workers, most urgent first, which clients poll, fetch
+//! and cancel by id instead of holding a connection open while they are
+//! generated.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use super::SliceRequest;
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::Artifact;
+
+/// Finished jobs kept for their clients to fetch before the oldest are
+/// forgotten.
+const RETAINED: usize = 256;
+
+/// Where a job is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Generating,
+    Writing,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            JobState::Done | JobState::Failed | JobState::Cancelled
+        )
+    }
+
+    /// A rough share of a job's work done once it reaches the state;
+    /// generating takes most of it.
+    fn progress(self) -> f64 {
+        match self {
+            JobState::Queued => 0.0,
+            JobState::Generating => 0.1,
+            JobState::Writing => 0.8,
+            JobState::Done | JobState::Failed | JobState::Cancelled => 1.0,
+        }
+    }
+}
+
+/// What a client sees of a job.
+#[derive(Clone, Debug, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    /// Higher runs first; equal priorities run in the order submitted.
+    pub priority: i32,
+    pub state: JobState,
+    pub progress: f64,
+    /// Jobs that will start before this one, while it waits.
+    pub ahead: Option<usize>,
+    /// Time since the job was submitted.
+    pub seconds: f64,
+    pub error: Option<String>,
+    pub request: SliceRequest,
+    /// Where the output was written, with the server's output location.
+    pub artifact: Option<Artifact>,
+}
+
+struct Job {
+    /// The client that submitted the job, the only one that sees it.
+    owner: String,
+    priority: i32,
+    request: SliceRequest,
+    state: JobState,
+    submitted: Instant,
+    cancel: CancelToken,
+    result: Option<Vec<u8>>,
+    artifact: Option<Artifact>,
+    error: Option<String>,
+}
+
+impl Job {
+    /// The order jobs start in: highest priority, then lowest id.
+    fn urgency(&self, id: u64) -> (i32, std::cmp::Reverse<u64>) {
+        (self.priority, std::cmp::Reverse(id))
+    }
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    jobs: BTreeMap<u64, Job>,
+    /// Finished jobs, oldest first, dropped beyond the retained number.
+    finished: VecDeque<u64>,
+}
+
+impl State {
+    /// The job, if `owner` submitted it.
+    fn job(&self, id: u64, owner: &str) -> Option<&Job> {
+        self.jobs.get(&id).filter(|job| job.owner == owner)
+    }
+
+    fn status(&self, id: u64) -> Option<JobStatus> {
+        let job = self.jobs.get(&id)?;
+        let ahead = (job.state == JobState::Queued).then(|| {
+            self.jobs
+                .iter()
+                .filter(|(&other, queued)| {
+                    queued.state == JobState::Queued && queued.urgency(other) > job.urgency(id)
+                })
+                .count()
+        });
+        Some(JobStatus {
+            id,
+            priority: job.priority,
+            state: job.state,
+            progress: job.state.progress(),
+            ahead,
+            seconds: job.submitted.elapsed().as_secs_f64(),
+            error: job.error.clone(),
+            request: job.request.clone(),
+            artifact: job.artifact.clone(),
+        })
+    }
+
+    /// `owner`'s jobs queued or running.
+    fn active(&self, owner: &str) -> usize {
+        let jobs = self.jobs.values();
+        jobs.filter(|job| job.owner == owner && !job.state.is_finished())
+            .count()
+    }
+
+    fn count(&self, state: JobState) -> usize {
+        self.jobs.values().filter(|job| job.state == state).count()
+    }
+
+    /// Moves the job to a finished state and forgets the oldest finished
+    /// jobs beyond those retained.
+    fn finish(&mut self, id: u64, state: JobState) {
+        let Some(job) = self.jobs.get_mut(&id) else {
+            return;
+        };
+        job.state = state;
+        self.finished.push_back(id);
+        while self.finished.len() > RETAINED {
+            if let Some(old) = self.finished.pop_front() {
+                self.jobs.remove(&old);
+            }
+        }
+    }
+}
+
+/// Why a job was not queued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Refusal {
+    /// As many jobs as may wait already are.
+    Full,
+    /// The owner has as many jobs queued or running as it may.
+    Quota,
+}
+
+pub(crate) struct Queue {
+    state: Mutex<State>,
+    queued: Condvar,
+    max_queued: usize,
+}
+
+impl Queue {
+    pub(crate) fn new(max_queued: usize) -> Self {
+        Queue {
+            state: Mutex::new(State::default()),
+            queued: Condvar::new(),
+            max_queued,
+        }
+    }
+
+    /// Queues a job, unless `max_queued` are already waiting or `owner`
+    /// has `allowed` jobs queued or running, counted under the same lock
+    /// so concurrent submissions cannot overrun it.
+    pub(crate) fn submit(
+        &self,
+        owner: &str,
+        request: SliceRequest,
+        priority: i32,
+        allowed: usize,
+    ) -> std::result::Result<JobStatus, Refusal> {
+        let mut state = self.state.lock().unwrap();
+        if state.active(owner) >= allowed {
+            return Err(Refusal::Quota);
+        }
+        if state.count(JobState::Queued) >= self.max_queued {
+            return Err(Refusal::Full);
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.jobs.insert(
+            id,
+            Job {
+                owner: owner.to_string(),
+                priority,
+                request,
+                state: JobState::Queued,
+                submitted: Instant::now(),
+                cancel: CancelToken::new(),
+                result: None,
+                artifact: None,
+                error: None,
+            },
+        );
+        self.queued.notify_one();
+        Ok(state.status(id).expect("the job was just queued"))
+    }
+
+    pub(crate) fn status(&self, id: u64, owner: &str) -> Option<JobStatus> {
+        let state = self.state.lock().unwrap();
+        state.job(id, owner)?;
+        state.status(id)
+    }
+
+    /// Every job of `owner`'s still held, oldest first.
+    pub(crate) fn list(&self, owner: &str) -> Vec<JobStatus> {
+        let state = self.state.lock().unwrap();
+        let ids = state.jobs.iter().filter(|(_, job)| job.owner == owner);
+        ids.filter_map(|(&id, _)| state.status(id)).collect()
+    }
+
+    /// `owner`'s jobs queued or running.
+    pub(crate) fn active(&self, owner: &str) -> usize {
+        self.state.lock().unwrap().active(owner)
+    }
+
+    /// A job's status, with its output once it is done.
+    pub(crate) fn result(&self, id: u64, owner: &str) -> Option<(JobStatus, Option<Vec<u8>>)> {
+        let state = self.state.lock().unwrap();
+        let body = state.job(id, owner)?.result.clone();
+        Some((state.status(id)?, body))
+    }
+
+    /// Cancels a job: one waiting never starts, and a running one stops at
+    /// its next check. Finished jobs are left as they are.
+    pub(crate) fn cancel(&self, id: u64, owner: &str) -> Option<JobStatus> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.job(id, owner)?;
+        match job.state {
+            JobState::Queued => state.finish(id, JobState::Cancelled),
+            JobState::Generating | JobState::Writing => job.cancel.cancel(),
+            JobState::Done | JobState::Failed | JobState::Cancelled => {}
+        }
+        state.status(id)
+    }
+
+    /// Waits for a queued job and starts the most urgent.
+    pub(crate) fn next(&self) -> (u64, SliceRequest, CancelToken) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let next = state
+                .jobs
+                .iter()
+                .filter(|(_, job)| job.state == JobState::Queued)
+                .max_by_key(|(&id, job)| job.urgency(id))
+                .map(|(&id, _)| id);
+            if let Some(id) = next {
+                let job = state.jobs.get_mut(&id).expect("job is queued");
+                job.state = JobState::Generating;
+                return (id, job.request.clone(), job.cancel.clone());
+            }
+            state = self.queued.wait(state).unwrap();
+        }
+    }
+
+    /// Records that a running job has reached `stage`.
+    pub(crate) fn advance(&self, id: u64, stage: JobState) {
+        if let Some(job) = self.state.lock().unwrap().jobs.get_mut(&id) {
+            job.state = stage;
+        }
+    }
+
+    /// Records a running job's output, and where it was written, or why
+    /// it has none.
+    pub(crate) fn finish(&self, id: u64, result: Result<(Vec<u8>, Option<Artifact>)>) {
+        let mut state = self.state.lock().unwrap();
+        let Some(job) = state.jobs.get_mut(&id) else {
+            return;
+        };
+        let finished = match result {
+            Ok((body, artifact)) => {
+                job.result = Some(body);
+                job.artifact = artifact;
+                JobState::Done
+            }
+            Err(Error::Cancelled) => JobState::Cancelled,
+            Err(e) => {
+                job.error = Some(e.to_string());
+                JobState::Failed
+            }
+        };
+        state.finish(id, finished);
+    }
+
+    /// Jobs waiting and jobs running.
+    pub(crate) fn load(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        let queued = state.count(JobState::Queued);
+        let running = state
+            .jobs
+            .values()
+            .filter(|job| !job.state.is_finished())
+            .count();
+        (queued, running - queued)
+    }
+}
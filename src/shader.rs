@@ -0,0 +1,405 @@
+//! Shader source for a rule's membership test, so the exact fractal can be
+//! raymarched or sampled in a user's own WGSL or GLSL renderer.
+//!
+//! The source defines `fs_is_solid(cell, depth)`, true when `cell`, with
+//! coordinates from 0 to the lattice side minus 1, is filled in the
+//! depth-`depth` lattice, and `FS_DEPTH`, the depth it was generated for.
+//! It mirrors [`crate::rule::Rule::is_solid`]: keep-masks are baked in as
+//! bitsets, and the flake split's kept copies as offsets. Every name it
+//! defines starts with `fs_` or `FS_`.
+
+use crate::error::{Error, Result};
+use crate::rule::{decompose, Rule, Split};
+
+/// The shading language to write.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ShaderLanguage {
+    /// WebGPU's shading language.
+    #[default]
+    Wgsl,
+    /// GLSL 4.50 or GLSL ES 3.10 and later.
+    Glsl,
+}
+
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
+
+/// The language's spelling of the types and declarations the source
+/// needs, for a rule of `dims` axes.
+struct Syntax {
+    language: ShaderLanguage,
+    dims: usize,
+}
+
+impl Syntax {
+    fn uint(&self) -> &'static str {
+        match self.language {
+            ShaderLanguage::Wgsl => "u32",
+            ShaderLanguage::Glsl => "uint",
+        }
+    }
+
+    fn uvec(&self) -> String {
+        match self.language {
+            ShaderLanguage::Wgsl => format!("vec{}<u32>", self.dims),
+            ShaderLanguage::Glsl => format!("uvec{}", self.dims),
+        }
+    }
+
+    fn ivec(&self) -> String {
+        match self.language {
+            ShaderLanguage::Wgsl => format!("vec{}<i32>", self.dims),
+            ShaderLanguage::Glsl => format!("ivec{}", self.dims),
+        }
+    }
+
+    /// A constant scalar.
+    fn constant(&self, name: &str, ty: &str, value: &str) -> String {
+        match self.language {
+            ShaderLanguage::Wgsl => format!("const {name}: {ty} = {value};\n"),
+            ShaderLanguage::Glsl => format!("const {ty} {name} = {value};\n"),
+        }
+    }
+
+    /// A constant array of `values`, each already of type `ty`, six to a
+    /// line and without the trailing comma GLSL rejects.
+    fn array(&self, name: &str, ty: &str, values: &[String]) -> String {
+        let n = values.len();
+        let lines: Vec<String> = values.chunks(6).map(|chunk| chunk.join(", ")).collect();
+        let lines = format!("    {}\n", lines.join(",\n    "));
+        match self.language {
+            ShaderLanguage::Wgsl => {
+                format!("var<private> {name}: array<{ty}, {n}> = array<{ty}, {n}>(\n{lines});\n")
+            }
+            ShaderLanguage::Glsl => format!("const {ty} {name}[{n}] = {ty}[{n}](\n{lines});\n"),
+        }
+    }
+
+    /// The flat subcell index of the digits in the vector `digits`, as
+    /// [`crate::rule::compose`] computes it.
+    fn compose(&self, digits: &str, bases: &[u32]) -> String {
+        let mut index = format!("{digits}.{}", AXES[self.dims - 1]);
+        for axis in (0..self.dims - 1).rev() {
+            index = format!("{digits}.{} + {}u * ({index})", AXES[axis], bases[axis]);
+        }
+        index
+    }
+
+    /// The sum of the components of the vector `v`.
+    fn sum(&self, v: &str) -> String {
+        AXES[..self.dims]
+            .iter()
+            .map(|axis| format!("{v}.{axis}"))
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+}
+
+/// The source of `fs_is_solid` for `rule` in `language`, with `FS_DEPTH`
+/// set to `depth`.
+pub fn source(rule: &Rule, depth: u32, language: ShaderLanguage) -> Result<String> {
+    let dims = rule.dims();
+    if !(2..=4).contains(&dims) {
+        return Err(Error::InvalidJob(format!(
+            "shaders have 2 to 4 component vectors; the rule has {dims} axes"
+        )));
+    }
+    let side = (0..dims).map(|axis| rule.side_along(axis, depth)).max();
+    // The flake split counts from the centre in signed integers.
+    if side.unwrap_or(0) > i32::MAX as usize {
+        return Err(Error::InvalidJob(format!(
+            "the depth-{depth} lattice is too wide for 32-bit shader integers"
+        )));
+    }
+    let syntax = Syntax { language, dims };
+    let sides: Vec<String> = (0..dims)
+        .map(|axis| rule.side_along(axis, depth).to_string())
+        .collect();
+    let mut out = format!(
+        "// Generated by fractal-slicer {}: the `{}` rule in {dims}D, bases {:?}.\n\
+         // fs_is_solid(cell, depth) is true when `cell` is filled in the\n\
+         // depth-`depth` lattice; at FS_DEPTH it is {} cells.\n\n",
+        env!("CARGO_PKG_VERSION"),
+        rule.name(),
+        rule.bases(),
+        sides.join("x"),
+    );
+    out.push_str(&syntax.constant("FS_DEPTH", syntax.uint(), &format!("{depth}u")));
+    match rule.split() {
+        Split::Uniform | Split::Pell => {
+            out.push_str(&masks(rule, &syntax));
+            out.push('\n');
+            out.push_str(&match rule.split() {
+                Split::Uniform => uniform(rule, &syntax),
+                _ => pell(rule, &syntax),
+            });
+        }
+        Split::Flake => {
+            out.push_str(&copies(rule, &syntax));
+            out.push('\n');
+            out.push_str(&flake(&syntax));
+        }
+    }
+    Ok(out)
+}
+
+/// The keep-masks of one period as a bitset, and `fs_keeps(level, index)`
+/// to read them.
+fn masks(rule: &Rule, syntax: &Syntax) -> String {
+    let subcells = rule.subcells();
+    let period = rule.period();
+    let mut words = vec![0u32; (subcells * period).div_ceil(32)];
+    for level in 1..=period {
+        for (index, &kept) in rule.mask(level as u32).iter().enumerate() {
+            let bit = (level - 1) * subcells + index;
+            words[bit / 32] |= (kept as u32) << (bit % 32);
+        }
+    }
+    let words: Vec<String> = words.iter().map(|w| format!("{w:#010x}u")).collect();
+    let uint = syntax.uint();
+    let mut out = syntax.constant("FS_SUBCELLS", uint, &format!("{subcells}u"));
+    out.push_str(&syntax.constant("FS_PERIOD", uint, &format!("{period}u")));
+    out.push_str(&syntax.array("FS_MASKS", uint, &words));
+    out.push('\n');
+    let body = "    let bit = ((level - 1u) % FS_PERIOD) * FS_SUBCELLS + index;\n    \
+                return ((FS_MASKS[bit / 32u] >> (bit % 32u)) & 1u) != 0u;\n";
+    out.push_str(&match syntax.language {
+        ShaderLanguage::Wgsl => {
+            format!("fn fs_keeps(level: u32, index: u32) -> bool {{\n{body}}}\n")
+        }
+        ShaderLanguage::Glsl => format!(
+            "bool fs_keeps(uint level, uint index) {{\n{}}}\n",
+            body.replace("let bit", "uint bit")
+        ),
+    });
+    out
+}
+
+/// Reads one digit per level along every axis, finest first, and rejects
+/// the cell at the first level whose mask drops its subcell.
+fn uniform(rule: &Rule, syntax: &Syntax) -> String {
+    let uvec = syntax.uvec();
+    let bases: Vec<String> = rule.bases().iter().map(|b| format!("{b}u")).collect();
+    let bases = format!("{uvec}({})", bases.join(", "));
+    let index = syntax.compose("digit", rule.bases());
+    match syntax.language {
+        ShaderLanguage::Wgsl => format!(
+            "fn fs_is_solid(cell: {uvec}, depth: u32) -> bool {{
+    let bases = {bases};
+    var c = cell;
+    for (var level = depth; level > 0u; level -= 1u) {{
+        let digit = c % bases;
+        c = c / bases;
+        if (!fs_keeps(level, {index})) {{
+            return false;
+        }}
+    }}
+    return true;
+}}
+"
+        ),
+        ShaderLanguage::Glsl => format!(
+            "bool fs_is_solid({uvec} cell, uint depth) {{
+    {uvec} bases = {bases};
+    {uvec} c = cell;
+    for (uint level = depth; level > 0u; level--) {{
+        {uvec} digit = c % bases;
+        c = c / bases;
+        if (!fs_keeps(level, {index})) {{
+            return false;
+        }}
+    }}
+    return true;
+}}
+"
+        ),
+    }
+}
+
+/// Descends into the Pell copy holding the cell, one or two levels down,
+/// until it reaches a single cell or falls in a gap.
+fn pell(rule: &Rule, syntax: &Syntax) -> String {
+    let uvec = syntax.uvec();
+    let dims = syntax.dims;
+    let index = syntax.compose("digit", rule.bases());
+    match syntax.language {
+        ShaderLanguage::Wgsl => format!(
+            "fn fs_pell(n: i32) -> u32 {{
+    var a = 0u;
+    var b = 1u;
+    for (var i = 0; i < n; i += 1) {{
+        let next = 2u * b + a;
+        a = b;
+        b = next;
+    }}
+    return a;
+}}
+
+fn fs_is_solid(cell: {uvec}, depth: u32) -> bool {{
+    var local = cell;
+    var digit = {uvec}(0u);
+    var d = i32(depth);
+    while (d > 0) {{
+        let outer = fs_pell(d);
+        let middle = outer + fs_pell(d - 1);
+        for (var i = 0; i < {dims}; i += 1) {{
+            digit[i] = select(select(2u, 1u, local[i] < middle), 0u, local[i] < outer);
+        }}
+        if (!fs_keeps(1u, {index})) {{
+            return false;
+        }}
+        // Subcells off the corners hold a copy two levels down, pushed
+        // against the outer faces.
+        let inner = any(digit == {uvec}(1u));
+        let side = fs_pell(d + 1);
+        let piece = select(outer, fs_pell(d - 1), inner);
+        for (var i = 0; i < {dims}; i += 1) {{
+            if (digit[i] == 0u) {{
+                if (local[i] >= piece) {{
+                    return false;
+                }}
+            }} else if (digit[i] == 1u) {{
+                local[i] -= outer;
+            }} else {{
+                if (local[i] < side - piece) {{
+                    return false;
+                }}
+                local[i] -= side - piece;
+            }}
+        }}
+        d -= select(1, 2, inner);
+    }}
+    return d == 0;
+}}
+"
+        ),
+        ShaderLanguage::Glsl => format!(
+            "uint fs_pell(int n) {{
+    uint a = 0u;
+    uint b = 1u;
+    for (int i = 0; i < n; i++) {{
+        uint next = 2u * b + a;
+        a = b;
+        b = next;
+    }}
+    return a;
+}}
+
+bool fs_is_solid({uvec} cell, uint depth) {{
+    {uvec} local = cell;
+    {uvec} digit = {uvec}(0u);
+    int d = int(depth);
+    while (d > 0) {{
+        uint outer = fs_pell(d);
+        uint middle = outer + fs_pell(d - 1);
+        for (int i = 0; i < {dims}; i++) {{
+            digit[i] = local[i] < outer ? 0u : (local[i] < middle ? 1u : 2u);
+        }}
+        if (!fs_keeps(1u, {index})) {{
+            return false;
+        }}
+        // Subcells off the corners hold a copy two levels down, pushed
+        // against the outer faces.
+        bool inner = any(equal(digit, {uvec}(1u)));
+        uint side = fs_pell(d + 1);
+        uint piece = inner ? fs_pell(d - 1) : outer;
+        for (int i = 0; i < {dims}; i++) {{
+            if (digit[i] == 0u) {{
+                if (local[i] >= piece) {{
+                    return false;
+                }}
+            }} else if (digit[i] == 1u) {{
+                local[i] -= outer;
+            }} else {{
+                if (local[i] < side - piece) {{
+                    return false;
+                }}
+                local[i] -= side - piece;
+            }}
+        }}
+        d -= inner ? 2 : 1;
+    }}
+    return d == 0;
+}}
+"
+        ),
+    }
+}
+
+/// The offsets, in half sides, of the flake split's kept copies.
+fn copies(rule: &Rule, syntax: &Syntax) -> String {
+    let ivec = syntax.ivec();
+    let mut digits = vec![0; syntax.dims];
+    let offsets: Vec<String> = (0..rule.subcells())
+        .filter(|&index| rule.keeps_index(index))
+        .map(|index| {
+            decompose(index, rule.bases(), &mut digits);
+            let offset: Vec<String> = digits.iter().map(|&d| (d as i32 - 1).to_string()).collect();
+            format!("{ivec}({})", offset.join(", "))
+        })
+        .collect();
+    let mut out = syntax.constant(
+        "FS_COPY_COUNT",
+        match syntax.language {
+            ShaderLanguage::Wgsl => "i32",
+            ShaderLanguage::Glsl => "int",
+        },
+        &offsets.len().to_string(),
+    );
+    out.push_str(&syntax.array("FS_COPIES", &ivec, &offsets));
+    out
+}
+
+/// Descends into the one copy whose L1 ball holds the cell until it
+/// reaches the centre of a depth-0 copy or falls between copies.
+fn flake(syntax: &Syntax) -> String {
+    let (uvec, ivec) = (syntax.uvec(), syntax.ivec());
+    let distance = syntax.sum("a");
+    match syntax.language {
+        ShaderLanguage::Wgsl => format!(
+            "fn fs_is_solid(cell: {uvec}, depth: u32) -> bool {{
+    var r = {ivec}(cell) - {ivec}((1 << depth) - 1);
+    for (var level = depth; level > 0u; level -= 1u) {{
+        let reach = 1 << (level - 1u);
+        var found = false;
+        for (var k = 0; k < FS_COPY_COUNT; k += 1) {{
+            let o = FS_COPIES[k] * reach;
+            let a = abs(r - o);
+            if ({distance} < reach) {{
+                r -= o;
+                found = true;
+                break;
+            }}
+        }}
+        if (!found) {{
+            return false;
+        }}
+    }}
+    return all(r == {ivec}(0));
+}}
+"
+        ),
+        ShaderLanguage::Glsl => format!(
+            "bool fs_is_solid({uvec} cell, uint depth) {{
+    {ivec} r = {ivec}(cell) - {ivec}((1 << depth) - 1);
+    for (uint level = depth; level > 0u; level--) {{
+        int reach = 1 << (level - 1u);
+        bool found = false;
+        for (int k = 0; k < FS_COPY_COUNT; k++) {{
+            {ivec} o = FS_COPIES[k] * reach;
+            {ivec} a = abs(r - o);
+            if ({distance} < reach) {{
+                r -= o;
+                found = true;
+                break;
+            }}
+        }}
+        if (!found) {{
+            return false;
+        }}
+    }}
+    return all(equal(r, {ivec}(0)));
+}}
+"
+        ),
+    }
+}
@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+
+use std::path::Path;
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::Artifact;
+use crate::lattice::Lattice3;
+use crate::render::{render, Camera, Scene};
+use crate::rule::Rule;
+
+mod section;
+
+pub use section::{PlaneCut, Section};
+
+/// A 3D cross-section of the unit hypercube, turned out of the w axis, so
+/// slices are not limited to the lattice's own w layers.
+///
+/// The section holds the points whose w, after turning, is `offset`; with
+/// every angle zero it is the layer at that fraction of the w side.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hyperplane {
+    /// Position along the turned w axis, 0 to 1 across the hypercube.
+    #[serde(default = "default_offset")]
+    pub offset: f64,
+    /// Rotations in the xw, yw and zw planes, in degrees, applied in that
+    /// order.
+    #[serde(default)]
+    pub angles: [f64; 3],
+}
+
+fn default_offset() -> f64 {
+    0.5
+}
+
+impl Default for Hyperplane {
+    fn default() -> Self {
+        Hyperplane {
+            offset: default_offset(),
+            angles: [0.0; 3],
+        }
+    }
+}
+
+impl Hyperplane {
+    /// Samples the 4D `rule` at `depth` over the section, on a grid of
+    /// `resolution` cells a side spanning the hypercube's width. Grid cells
+    /// whose centre falls outside the hypercube are empty.
+    pub fn sample(
+        &self,
+        rule: &Rule,
+        depth: u32,
+        resolution: usize,
+        cancel: &CancelToken,
+    ) -> Result<Lattice3> {
+        if rule.dims() != 4 {
+            return Err(Error::InvalidJob(
+                "a hyperplane slices 4D rules only".into(),
+            ));
+        }
+        if resolution == 0 {
+            return Err(Error::InvalidJob("the slice needs cells".into()));
+        }
+        let sides: [usize; 4] = std::array::from_fn(|axis| rule.side_along(axis, depth));
+        let rotation = self.rotation();
+        let n = resolution as f64;
+        Lattice3::from_fn([resolution; 3], cancel, |p| {
+            let local = [
+                (p[0] as f64 + 0.5) / n - 0.5,
+                (p[1] as f64 + 0.5) / n - 0.5,
+                (p[2] as f64 + 0.5) / n - 0.5,
+                self.offset - 0.5,
+            ];
+            let mut coords = [0; 4];
+            for (axis, row) in rotation.iter().enumerate() {
+                let u: f64 = row.iter().zip(local).map(|(r, c)| r * c).sum::<f64>() + 0.5;
+                if !(0.0..1.0).contains(&u) {
+                    return false;
+                }
+                coords[axis] = ((u * sides[axis] as f64) as usize).min(sides[axis] - 1);
+            }
+            rule.is_solid(&coords, depth)
+        })
+    }
+
+    /// The matrix taking the section's coordinates, w last, to the
+    /// hypercube's.
+    pub fn rotation(&self) -> [[f64; 4]; 4] {
+        let mut matrix = [[0.0; 4]; 4];
+        for (axis, row) in matrix.iter_mut().enumerate() {
+            row[axis] = 1.0;
+        }
+        for (axis, degrees) in self.angles.into_iter().enumerate() {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            // Turn the axis towards w: left multiply by the plane rotation.
+            let (rows, w) = matrix.split_at_mut(3);
+            for (a, w) in rows[axis].iter_mut().zip(&mut w[0]) {
+                (*a, *w) = (cos * *a - sin * *w, sin * *a + cos * *w);
+            }
+        }
+        matrix
+    }
+}
+
+/// The parameters of an exploration: a built-in 4D rule and the turned
+/// hyperplane it is sliced along.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Explorer {
+    /// One of [`Rule::NAMES`], in 4D.
+    #[serde(default = "default_fractal")]
+    pub fractal: String,
+    #[serde(default = "default_depth")]
+    pub depth: u32,
+    /// Cells along each side of the sampled slice.
+    #[serde(default = "default_resolution")]
+    pub resolution: usize,
+    #[serde(default)]
+    pub hyperplane: Hyperplane,
+}
+
+fn default_fractal() -> String {
+    "menger".into()
+}
+
+fn default_depth() -> u32 {
+    2
+}
+
+fn default_resolution() -> usize {
+    81
+}
+
+impl Default for Explorer {
+    fn default() -> Self {
+        Explorer {
+            fractal: default_fractal(),
+            depth: default_depth(),
+            resolution: default_resolution(),
+            hyperplane: Hyperplane::default(),
+        }
+    }
+}
+
+impl Explorer {
+    /// The slice the parameters describe.
+    pub fn lattice(&self, cancel: &CancelToken) -> Result<Lattice3> {
+        let rule = Rule::by_name(&self.fractal, 4)
+            .ok_or_else(|| Error::UnknownFractal(self.fractal.clone()))?;
+        self.hyperplane
+            .sample(&rule, self.depth, self.resolution, cancel)
+    }
+}
+
+/// A view of a slice worth keeping: the exploration and the camera on it,
+/// saved as JSON by the viewer and rendered again by `render-bookmark`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bookmark {
+    #[serde(default)]
+    pub slice: Explorer,
+    #[serde(default)]
+    pub camera: Camera,
+}
+
+impl Bookmark {
+    /// Reads a JSON bookmark file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json + "\n")?;
+        Ok(())
+    }
+
+    /// Renders the slice to `path` through the bookmarked camera, lit,
+    /// coloured and with the passes `scene` gives; its camera is unused.
+    pub fn render(
+        &self,
+        scene: &Scene,
+        path: &Path,
+        cancel: &CancelToken,
+    ) -> Result<Vec<Artifact>> {
+        let scene = Scene {
+            camera: self.camera.clone(),
+            ..scene.clone()
+        };
+        scene.validate()?;
+        let lattice = self.slice.lattice(cancel)?;
+        render(&lattice, None, &scene, cancel)?.write(&scene.passes, path)
+    }
+}
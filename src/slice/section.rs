@@ -0,0 +1,421 @@
+//! Exact cuts of rule fractals by planes across their cells: a 3D lattice
+//! cut by a plane, keeping the side behind it, and a 4D lattice sectioned
+//! by a hyperplane. Each cell the plane crosses is clipped to the convex
+//! polyhedron it leaves, in rational arithmetic, rather than sampled into
+//! voxels as [`Hyperplane::sample`](super::Hyperplane::sample) does.
+//!
+//! Corners are tested by the sign of a whole-number form, with corners on
+//! the plane counted as behind it, as if it were nudged forward by an
+//! infinitesimal. Every crossing is then a proper one, a hyperplane at a
+//! whole w picks the layer above it as a w slice does, and neighbouring
+//! pieces share their corners exactly, so the surface is closed.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::Lattice3;
+use crate::mesh::{cross, dot, sub, PolyMesh};
+use crate::rule::Rule;
+
+/// The hyperplane `normal · u = offset` through the unit hypercube, `u`
+/// running from 0 to 1 along each axis, with a whole-number normal and a
+/// rational offset so that its section is exact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Section {
+    /// x to w; `[0, 0, 0, 1]` cuts at a fixed w.
+    pub normal: [i64; 4],
+    /// Numerator and denominator.
+    pub offset: [i64; 2],
+}
+
+/// The plane `normal · u = offset` through the unit cube, as for a
+/// [`Section`], keeping what lies behind it, away from the normal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaneCut {
+    pub normal: [i64; 3],
+    /// Numerator and denominator.
+    pub offset: [i64; 2],
+}
+
+impl Section {
+    pub fn validate(&self) -> Result<()> {
+        validate_plane(&self.normal, self.offset)
+    }
+
+    /// The axis the form leans on most, along which each column of cells
+    /// crosses the hyperplane only a few times; w when tied.
+    fn steepest(a: [i128; 4]) -> usize {
+        (0..4).rev().max_by_key(|&k| a[k].abs()).expect("four axes")
+    }
+
+    /// Upper bound on the cells of a lattice with `sides` the hyperplane
+    /// crosses.
+    pub fn max_cells(&self, sides: [usize; 4]) -> u64 {
+        let (a, _) = form(self.normal, self.offset, sides);
+        let k = Section::steepest(a);
+        let per_column = a.iter().map(|a| a.abs()).sum::<i128>() / a[k].abs().max(1) + 1;
+        let columns: u64 = (0..4)
+            .filter(|&m| m != k)
+            .map(|m| sides[m] as u64)
+            .product();
+        columns.saturating_mul(per_column as u64)
+    }
+
+    /// The section of the depth-`depth` fractal of the 4D `rule`, in
+    /// cells: the polyhedra the hyperplane leaves of its filled cells,
+    /// seen in an orthonormal frame of the hyperplane. A hyperplane normal
+    /// to w has the frame of a w slice.
+    #[tracing::instrument(name = "section", skip_all, fields(rule = %rule.name(), depth = depth))]
+    pub fn cells(&self, rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<PolyMesh> {
+        self.validate()?;
+        if rule.dims() != 4 {
+            return Err(Error::InvalidJob(
+                "a hyperplane slices 4D rules only".into(),
+            ));
+        }
+        let sides: [usize; 4] = std::array::from_fn(|axis| rule.side_along(axis, depth));
+        let (a, c) = form(self.normal, self.offset, sides);
+        let k = Section::steepest(a);
+        let frame = frame(a, k);
+        let value = |v: [i64; 4]| (0..4).map(|m| a[m] * v[m] as i128).sum::<i128>() - c;
+        let solid = |v: [i64; 4]| {
+            (0..4).all(|m| (0..sides[m] as i64).contains(&v[m]))
+                && rule.is_solid(&v.map(|c| c as usize), depth)
+        };
+        let low: i128 = a.iter().map(|&a| a.min(0)).sum();
+        let high: i128 = a.iter().map(|&a| a.max(0)).sum();
+        let others: Vec<usize> = (0..4).filter(|&m| m != k).collect();
+        let mut points = Points::new(|cell: [f64; 4]| {
+            frame.map(|row| (0..4).map(|m| row[m] * cell[m]).sum::<f64>())
+        });
+        let mut cells = PolyMesh::default();
+        for i in 0..sides[others[0]] as i64 {
+            cancel.check()?;
+            for j in 0..sides[others[1]] as i64 {
+                for l in 0..sides[others[2]] as i64 {
+                    let mut v = [0; 4];
+                    (v[others[0]], v[others[1]], v[others[2]]) = (i, j, l);
+                    // The cell is crossed when its lowest corner is at or
+                    // below the hyperplane and its highest above: the
+                    // range of v[k] where both hold.
+                    let rest = value(v);
+                    let (first, last) = if a[k] > 0 {
+                        (
+                            (-(rest + high)).div_euclid(a[k]) + 1,
+                            (-(rest + low)).div_euclid(a[k]),
+                        )
+                    } else {
+                        (
+                            ceil_div(rest + low, -a[k]),
+                            ceil_div(rest + high, -a[k]) - 1,
+                        )
+                    };
+                    let first = first.max(0) as i64;
+                    let last = last.min(sides[k] as i128 - 1) as i64;
+                    for x in first..=last {
+                        v[k] = x;
+                        if !solid(v) {
+                            continue;
+                        }
+                        // Each facet the hyperplane crosses leaves a face
+                        // of the cell's polyhedron.
+                        let mut faces = Vec::new();
+                        for m in 0..4 {
+                            let axes: Vec<usize> = (0..4).filter(|&axis| axis != m).collect();
+                            for positive in [false, true] {
+                                let corner = |bits: usize| {
+                                    let mut corner = v;
+                                    corner[m] += positive as i64;
+                                    for (i, &axis) in axes.iter().enumerate() {
+                                        corner[axis] += (bits >> i & 1) as i64;
+                                    }
+                                    corner
+                                };
+                                let values = std::array::from_fn(|bits| value(corner(bits)));
+                                let mut ring: Vec<u32> = cube_ring(&values)
+                                    .into_iter()
+                                    .map(|(bits, i)| {
+                                        let to = values[bits | 1 << i];
+                                        points.crossing(corner(bits), axes[i], values[bits], to)
+                                    })
+                                    .collect();
+                                let sign = if positive { 1.0 } else { -1.0 };
+                                let outward = frame.map(|row| sign * row[m]);
+                                if orient(&mut ring, &points.vertices, outward) {
+                                    let mut next = v;
+                                    next[m] += if positive { 1 } else { -1 };
+                                    faces.push((ring, !solid(next)));
+                                }
+                            }
+                        }
+                        if faces.len() >= 4 {
+                            cells.push_cell(
+                                faces.iter().map(|(ring, surface)| (&ring[..], *surface)),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        cells.vertices = points.vertices;
+        Ok(cells)
+    }
+}
+
+impl PlaneCut {
+    pub fn validate(&self) -> Result<()> {
+        validate_plane(&self.normal, self.offset)
+    }
+
+    /// The depth-`depth` fractal of the 3D `rule` behind the plane, in
+    /// cells: whole cells where they lie behind it and the convex pieces
+    /// it leaves of the cells it crosses, capped where it cuts them.
+    #[tracing::instrument(name = "cut", skip_all, fields(rule = %rule.name(), depth = depth))]
+    pub fn cells(&self, rule: &Rule, depth: u32, cancel: &CancelToken) -> Result<PolyMesh> {
+        self.validate()?;
+        if rule.dims() != 3 {
+            return Err(Error::InvalidJob("a plane cuts 3D rules only".into()));
+        }
+        let lattice = Lattice3::generate_cancellable(rule, depth, cancel)?;
+        let (a, c) = form(self.normal, self.offset, lattice.shape());
+        let value = |v: [i64; 3]| (0..3).map(|m| a[m] * v[m] as i128).sum::<i128>() - c;
+        let mut points = Points::new(|cell: [f64; 3]| cell);
+        let mut cells = PolyMesh::default();
+        for p in lattice.iter() {
+            if p[0] == 0 && p[1] == 0 {
+                cancel.check()?;
+            }
+            let v = p.map(|c| c as i64);
+            let corner = |bits: usize| std::array::from_fn(|m| v[m] + (bits >> m & 1) as i64);
+            let values: [i128; 8] = std::array::from_fn(|bits| value(corner(bits)));
+            if values.iter().all(|&value| value > 0) {
+                continue;
+            }
+            // Each side of the cube clipped to the part behind the plane,
+            // corner by corner around it.
+            let mut faces = Vec::new();
+            for m in 0..3 {
+                let (u, w) = ((m + 1) % 3, (m + 2) % 3);
+                for positive in [false, true] {
+                    let base = (positive as usize) << m;
+                    let around = [0, 1 << u, 1 << u | 1 << w, 1 << w].map(|bits| base | bits);
+                    let mut ring = Vec::new();
+                    for (i, &from) in around.iter().enumerate() {
+                        let to = around[(i + 1) % 4];
+                        if values[from] <= 0 {
+                            ring.push(points.crossing(corner(from), 0, 0, 1));
+                        }
+                        if (values[from] > 0) != (values[to] > 0) {
+                            let (low, high) = (from.min(to), from.max(to));
+                            let axis = (high ^ low).trailing_zeros() as usize;
+                            ring.push(points.crossing(
+                                corner(low),
+                                axis,
+                                values[low],
+                                values[high],
+                            ));
+                        }
+                    }
+                    let mut outward = [0.0; 3];
+                    outward[m] = if positive { 1.0 } else { -1.0 };
+                    if orient(&mut ring, &points.vertices, outward) {
+                        let mut next = v;
+                        next[m] += if positive { 1 } else { -1 };
+                        faces.push((ring, !lattice.get_signed(next)));
+                    }
+                }
+            }
+            // The cap where the plane crosses the cube.
+            let mut cap: Vec<u32> = cube_ring(&values)
+                .into_iter()
+                .map(|(bits, i)| {
+                    points.crossing(corner(bits), i, values[bits], values[bits | 1 << i])
+                })
+                .collect();
+            if orient(&mut cap, &points.vertices, a.map(|a| a as f64)) {
+                faces.push((cap, true));
+            }
+            if faces.len() >= 4 {
+                cells.push_cell(faces.iter().map(|(ring, surface)| (&ring[..], *surface)));
+            }
+        }
+        cells.vertices = points.vertices;
+        Ok(cells)
+    }
+}
+
+fn validate_plane(normal: &[i64], [_, denominator]: [i64; 2]) -> Result<()> {
+    if normal.iter().all(|&n| n == 0) {
+        return Err(Error::InvalidJob(
+            "a cutting plane needs a nonzero normal".into(),
+        ));
+    }
+    if denominator == 0 {
+        return Err(Error::InvalidJob(
+            "a cutting plane's offset has a zero denominator".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The plane `normal · u = offset` of the unit box as `a · x = c` in the
+/// cells of a lattice with `sides`, scaled to whole numbers.
+fn form<const D: usize>(
+    normal: [i64; D],
+    [mut p, mut q]: [i64; 2],
+    sides: [usize; D],
+) -> ([i128; D], i128) {
+    if q < 0 {
+        (p, q) = (-p, -q);
+    }
+    let scale = sides.iter().fold(1, |l, &side| lcm(l, side as i128));
+    let a = std::array::from_fn(|k| normal[k] as i128 * q as i128 * (scale / sides[k] as i128));
+    (a, p as i128 * scale)
+}
+
+/// The edges of a cube a plane crosses, in order around the polygon it
+/// cuts, given the form's value at each corner, corner `bits` having bit
+/// `i` set when it is at the far end along the cube's axis `i`. An edge is
+/// its near corner and its axis.
+///
+/// Each square of the cube the plane cuts is crossed on exactly two of its
+/// edges, as a plane cannot split a square's diagonals, so the walk goes
+/// on through the other crossed edge of the square not just left.
+fn cube_ring(values: &[i128; 8]) -> Vec<(usize, usize)> {
+    let crosses = |(bits, i): (usize, usize)| (values[bits] > 0) != (values[bits | 1 << i] > 0);
+    let edges: Vec<(usize, usize)> = (0..8)
+        .flat_map(|bits| (0..3).map(move |i| (bits, i)))
+        .filter(|&(bits, i)| bits >> i & 1 == 0)
+        .collect();
+    let Some(&start) = edges.iter().find(|&&edge| crosses(edge)) else {
+        return Vec::new();
+    };
+    let mut ring = Vec::new();
+    let (mut edge, mut square) = (start, None);
+    loop {
+        ring.push(edge);
+        let (bits, i) = edge;
+        let here = (0..3)
+            .filter(|&j| j != i)
+            .map(|j| (j, bits >> j & 1))
+            .find(|&s| Some(s) != square)
+            .expect("an edge lies in two squares");
+        let (j, side) = here;
+        edge = *edges
+            .iter()
+            .find(|&&(b, d)| (b, d) != edge && d != j && b >> j & 1 == side && crosses((b, d)))
+            .expect("a cut square is crossed twice");
+        square = Some(here);
+        if edge == start {
+            return ring;
+        }
+    }
+}
+
+/// Drops repeated corners from a face and winds it to face `outward`;
+/// false when fewer than three corners are left.
+fn orient(ring: &mut Vec<u32>, vertices: &[[f64; 3]], outward: [f64; 3]) -> bool {
+    ring.dedup();
+    if ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+    if ring.len() < 3 {
+        return false;
+    }
+    let corner = |i: usize| vertices[ring[i] as usize];
+    let mut normal = [0.0; 3];
+    for i in 1..ring.len() - 1 {
+        let n = cross(sub(corner(i), corner(0)), sub(corner(i + 1), corner(0)));
+        normal = std::array::from_fn(|j| normal[j] + n[j]);
+    }
+    if dot(normal, outward) < 0.0 {
+        ring.reverse();
+    }
+    true
+}
+
+/// Numbers exact points once, each placed in 3D by `place`.
+struct Points<const D: usize, F> {
+    /// Points with rational coordinates `coords / denominator`, in lowest
+    /// terms with a positive denominator, so equal points have equal keys.
+    indices: HashMap<([i128; D], i128), u32>,
+    place: F,
+    vertices: Vec<[f64; 3]>,
+}
+
+impl<const D: usize, F: Fn([f64; D]) -> [f64; 3]> Points<D, F> {
+    fn new(place: F) -> Self {
+        Points {
+            indices: HashMap::new(),
+            place,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// The index of the point where the plane crosses the edge from
+    /// `corner` along `axis`, whose ends have form values `from` and `to`;
+    /// the corner itself when `from` is zero.
+    fn crossing(&mut self, corner: [i64; D], axis: usize, from: i128, to: i128) -> u32 {
+        // Along the edge by `from / (from - to)`.
+        let (mut t, mut denominator) = (from, from - to);
+        if denominator < 0 {
+            (t, denominator) = (-t, -denominator);
+        }
+        let mut coords = corner.map(|c| c as i128 * denominator);
+        coords[axis] += t;
+        let divisor = coords.iter().fold(denominator, |g, &c| gcd(g, c));
+        let point = (coords.map(|c| c / divisor), denominator / divisor);
+        let count = self.indices.len() as u32;
+        let index = *self.indices.entry(point).or_insert(count);
+        if index == count {
+            let cell = point.0.map(|c| c as f64 / point.1 as f64);
+            self.vertices.push((self.place)(cell));
+        }
+        index
+    }
+}
+
+/// An orthonormal frame of the hyperplane with normal `a`: the axes other
+/// than `k`, projected into the hyperplane and made orthonormal in turn.
+fn frame(a: [i128; 4], k: usize) -> [[f64; 4]; 3] {
+    let a = a.map(|a| a as f64);
+    let norm = a.iter().map(|a| a * a).sum::<f64>();
+    let mut frame = [[0.0; 4]; 3];
+    for (j, axis) in (0..4).filter(|&m| m != k).enumerate() {
+        let mut e: [f64; 4] = std::array::from_fn(|m| (m == axis) as u8 as f64);
+        let along = a[axis] / norm;
+        for m in 0..4 {
+            e[m] -= along * a[m];
+        }
+        for row in &frame[..j] {
+            let along: f64 = (0..4).map(|m| row[m] * e[m]).sum();
+            for m in 0..4 {
+                e[m] -= along * row[m];
+            }
+        }
+        let length = e.iter().map(|e| e * e).sum::<f64>().sqrt();
+        frame[j] = e.map(|e| e / length);
+    }
+    frame
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    -(-a).div_euclid(b)
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    (a, b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lcm(a: i128, b: i128) -> i128 {
+    a / gcd(a, b) * b
+}
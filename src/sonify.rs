@@ -0,0 +1,171 @@
+//! Sonification: the cross-sections of a w sweep played as sound, one
+//! slice per frame of the animation the sweep's outputs make, written as
+//! a WAV file to lay under it.
+//!
+//! Each slice sets a tone for its frame: the filled cells its pitch, over
+//! three octaves up from 110 Hz, the exposed faces its loudness, and the
+//! face-connected components its brightness, one harmonic per component up
+//! to eight. Every measure is scaled between its smallest and largest over
+//! the sweep, and the tone glides from each slice's setting to the next so
+//! frames join without clicks.
+
+use std::f64::consts::TAU;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::export::{write_file_atomically, Artifact};
+use crate::lattice::{Boundary, Lattice3};
+use crate::mesh::surface_faces;
+
+/// Most harmonics a tone takes, however many components its slice has.
+const MAX_HARMONICS: usize = 8;
+
+/// Where and how a sweep is sonified.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Sonify {
+    /// The `.wav` file written.
+    pub path: PathBuf,
+    /// Slices played a second, the frame rate of the sweep's animation.
+    #[serde(default = "default_fps")]
+    pub fps: f64,
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+}
+
+fn default_fps() -> f64 {
+    24.0
+}
+
+fn default_sample_rate() -> u32 {
+    44_100
+}
+
+/// What a slice of a sweep sounds like.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct SliceStats {
+    pub w: usize,
+    /// Filled cells.
+    pub cells: usize,
+    /// Faces of filled cells open to empty ones, the surface area in
+    /// cell faces.
+    pub faces: usize,
+    /// Face-connected pieces.
+    pub components: usize,
+}
+
+impl SliceStats {
+    /// Measures the slice `w` of a sweep, its faces culled under
+    /// `boundary`.
+    pub fn measure(lattice: &Lattice3, w: usize, boundary: Boundary) -> Self {
+        SliceStats {
+            w,
+            cells: lattice.count(),
+            faces: surface_faces(lattice, boundary).len(),
+            components: lattice.components(boundary).sizes.len(),
+        }
+    }
+}
+
+impl Sonify {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.fps > 0.0 && self.fps.is_finite()) || self.sample_rate == 0 {
+            return Err(Error::InvalidJob(
+                "sonification needs a positive frame and sample rate".into(),
+            ));
+        }
+        if !is_wav(&self.path) {
+            return Err(Error::UnknownFormat(self.path.clone()));
+        }
+        Ok(())
+    }
+
+    /// The sweep `slices`, in order, as mono samples from -1 to 1.
+    pub fn samples(&self, slices: &[SliceStats]) -> Vec<f64> {
+        let scale = |measure: fn(&SliceStats) -> usize| -> Vec<f64> {
+            let values: Vec<f64> = slices.iter().map(|s| measure(s) as f64).collect();
+            let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            values
+                .iter()
+                .map(|v| {
+                    if high > low {
+                        (v - low) / (high - low)
+                    } else {
+                        0.5
+                    }
+                })
+                .collect()
+        };
+        let pitch = scale(|s| s.cells);
+        let loudness = scale(|s| s.faces);
+        let harmonics: Vec<f64> = slices
+            .iter()
+            .map(|s| s.components.clamp(1, MAX_HARMONICS) as f64)
+            .collect();
+        let per_frame = (self.sample_rate as f64 / self.fps).round().max(1.0) as usize;
+        let mut samples = Vec::with_capacity(per_frame * slices.len());
+        let mut phase = 0.0;
+        for frame in 0..slices.len() {
+            let next = (frame + 1).min(slices.len() - 1);
+            for i in 0..per_frame {
+                let t = i as f64 / per_frame as f64;
+                let glide = |values: &[f64]| values[frame] + (values[next] - values[frame]) * t;
+                let frequency = 110.0 * 2f64.powf(3.0 * glide(&pitch));
+                let amplitude = 0.2 + 0.8 * glide(&loudness);
+                let partials = glide(&harmonics);
+                phase = (phase + frequency / self.sample_rate as f64).fract();
+                // Harmonic k at 1/k, the last faded in as `partials`
+                // passes it.
+                let mut value = 0.0;
+                let mut norm = 0.0;
+                for k in 1..=partials.ceil() as usize {
+                    let weight = (partials - (k - 1) as f64).min(1.0) / k as f64;
+                    value += weight * (TAU * phase * k as f64).sin();
+                    norm += weight;
+                }
+                samples.push(amplitude * value / norm.max(1.0));
+            }
+        }
+        samples
+    }
+
+    /// Writes the sweep `slices` as a 16-bit mono WAV file to the
+    /// sonification's path.
+    pub fn write(&self, slices: &[SliceStats]) -> Result<Artifact> {
+        let samples = self.samples(slices);
+        write_file_atomically(&self.path, |out| write_wav(&samples, self.sample_rate, out))
+    }
+}
+
+/// Writes `samples`, from -1 to 1, as a 16-bit mono PCM WAV stream.
+pub fn write_wav(samples: &[f64], sample_rate: u32, out: &mut impl Write) -> Result<()> {
+    let data = samples.len() as u32 * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(36 + data).to_le_bytes())?;
+    out.write_all(b"WAVEfmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    // PCM, one channel.
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&sample_rate.to_le_bytes())?;
+    out.write_all(&(sample_rate * 2).to_le_bytes())?;
+    out.write_all(&2u16.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+    out.write_all(b"data")?;
+    out.write_all(&data.to_le_bytes())?;
+    for sample in samples {
+        let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f64).round() as i16;
+        out.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Whether `path` names a WAV file.
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+}
@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// A file or object being written, published only once finished, so a
+/// failed or cancelled export never leaves a truncated output behind.
+pub trait Sink: Write {
+    /// Publishes everything written.
+    fn finish(self: Box<Self>) -> Result<()>;
+    /// Discards everything written.
+    fn abort(self: Box<Self>);
+}
+
+/// Opens `path` for writing. Local files are written under a partial name
+/// and renamed into place. With the `object-store` feature, paths such as
+/// `s3://bucket/key` or `gs://bucket/key` name objects in S3 or Google
+/// Cloud Storage, uploaded in parts as they are written, with credentials
+/// and region taken from the usual `AWS_*` or `GOOGLE_*` variables.
+pub fn create(path: &Path) -> Result<Box<dyn Sink>> {
+    if let Some((scheme, location)) = object_url(path) {
+        let Some((bucket, key)) = location.split_once('/').filter(|(_, key)| !key.is_empty())
+        else {
+            return Err(Error::InvalidJob(format!(
+                "`{}` names no object in its bucket",
+                path.display()
+            )));
+        };
+        return create_object(scheme, bucket, key);
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = partial_path(path);
+    let file = File::create(&partial)?;
+    Ok(Box::new(LocalFile {
+        path: path.to_path_buf(),
+        partial,
+        out: BufWriter::new(file),
+    }))
+}
+
+/// The scheme and the rest of a path naming an object rather than a file.
+pub fn object_url(path: &Path) -> Option<(&str, &str)> {
+    let (scheme, location) = path.to_str()?.split_once("://")?;
+    matches!(scheme, "s3" | "gs").then_some((scheme, location))
+}
+
+/// The temporary name a file is written under: `.<name>.partial` in the
+/// same directory, so the final rename never crosses filesystems.
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.partial"))
+}
+
+struct LocalFile {
+    path: PathBuf,
+    partial: PathBuf,
+    out: BufWriter<File>,
+}
+
+impl Write for LocalFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl Sink for LocalFile {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        let result = self
+            .out
+            .flush()
+            .and_then(|()| std::fs::rename(&self.partial, &self.path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&self.partial);
+        }
+        Ok(result?)
+    }
+
+    fn abort(self: Box<Self>) {
+        let _ = std::fs::remove_file(&self.partial);
+    }
+}
+
+#[cfg(not(feature = "object-store"))]
+fn create_object(scheme: &str, bucket: &str, key: &str) -> Result<Box<dyn Sink>> {
+    Err(Error::InvalidJob(format!(
+        "writing `{scheme}://{bucket}/{key}` needs the `object-store` feature"
+    )))
+}
+
+#[cfg(feature = "object-store")]
+use object::create_object;
+
+#[cfg(feature = "object-store")]
+mod object {
+    use std::collections::HashMap;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex, OnceLock};
+
+    use object_store::aws::AmazonS3Builder;
+    use object_store::gcp::GoogleCloudStorageBuilder;
+    use object_store::path::Path;
+    use object_store::{MultipartUpload, ObjectStore, ObjectStoreExt, PutPayload};
+    use tokio::runtime::Runtime;
+
+    use super::Sink;
+    use crate::error::Result;
+
+    /// Bytes buffered per uploaded part. S3 needs at least 5 MiB in every
+    /// part but the last.
+    const PART_SIZE: usize = 8 << 20;
+
+    /// The runtime uploads are driven on, shared by every job.
+    fn runtime() -> &'static Runtime {
+        static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+        RUNTIME.get_or_init(|| {
+            tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(2)
+                .enable_all()
+                .build()
+                .expect("the upload runtime starts")
+        })
+    }
+
+    /// One client per bucket, kept for the rest of the run.
+    fn bucket(scheme: &str, bucket: &str) -> io::Result<Arc<dyn ObjectStore>> {
+        static STORES: OnceLock<Mutex<HashMap<String, Arc<dyn ObjectStore>>>> = OnceLock::new();
+        let url = format!("{scheme}://{bucket}");
+        let mut stores = STORES.get_or_init(Default::default).lock().unwrap();
+        if let Some(store) = stores.get(&url) {
+            return Ok(Arc::clone(store));
+        }
+        let store: Arc<dyn ObjectStore> = match scheme {
+            "s3" => Arc::new(
+                AmazonS3Builder::from_env()
+                    .with_url(&url)
+                    .build()
+                    .map_err(io::Error::other)?,
+            ),
+            _ => Arc::new(
+                GoogleCloudStorageBuilder::from_env()
+                    .with_url(&url)
+                    .build()
+                    .map_err(io::Error::other)?,
+            ),
+        };
+        stores.insert(url, Arc::clone(&store));
+        Ok(store)
+    }
+
+    pub(super) fn create_object(scheme: &str, name: &str, key: &str) -> Result<Box<dyn Sink>> {
+        let store = bucket(scheme, name)?;
+        let upload = runtime()
+            .block_on(store.put_multipart(&Path::from(key)))
+            .map_err(io::Error::other)?;
+        Ok(Box::new(Object {
+            upload,
+            buffer: Vec::with_capacity(PART_SIZE),
+            parts: 0,
+        }))
+    }
+
+    /// A multipart upload, one part per [`PART_SIZE`] bytes written.
+    struct Object {
+        upload: Box<dyn MultipartUpload>,
+        buffer: Vec<u8>,
+        parts: usize,
+    }
+
+    impl Object {
+        fn put_part(&mut self) -> io::Result<()> {
+            let part = PutPayload::from(std::mem::take(&mut self.buffer));
+            runtime()
+                .block_on(self.upload.put_part(part))
+                .map_err(io::Error::other)?;
+            self.parts += 1;
+            Ok(())
+        }
+    }
+
+    impl Write for Object {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.buffer.extend_from_slice(buf);
+            if self.buffer.len() >= PART_SIZE {
+                self.put_part()?;
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Sink for Object {
+        fn finish(mut self: Box<Self>) -> Result<()> {
+            // Even an empty object needs a part.
+            if !self.buffer.is_empty() || self.parts == 0 {
+                if let Err(e) = self.put_part() {
+                    self.abort();
+                    return Err(e.into());
+                }
+            }
+            let result = runtime().block_on(self.upload.complete());
+            if let Err(e) = result {
+                self.abort();
+                return Err(io::Error::other(e).into());
+            }
+            Ok(())
+        }
+
+        fn abort(mut self: Box<Self>) {
+            let _ = runtime().block_on(self.upload.abort());
+        }
+    }
+}
@@ -0,0 +1,184 @@
+//! Slicing a 4D rule fractal at many w without generating the hypercube,
+//! as an animation sweeping through w does.
+//!
+//! A uniform rule's keep-mask at each level splits by the subcell's w
+//! digit into 3D masks, so the slice at `w` is the 3D fractal whose level
+//! `k` keeps by the mask of `w`'s `k`th digit. Slices whose w share
+//! leading digits share their coarse levels, and digits with the same
+//! mask, such as Menger's 0 and 2, give the same slice; a sweep builds
+//! each distinct coarse lattice once, in parallel, and each slice only
+//! refines its finest level.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+use crate::lattice::Lattice3;
+use crate::rule::{Rule, Split};
+
+/// The coarse levels shared by a set of slices of one 4D rule.
+pub struct Sweep {
+    depth: u32,
+    bases: [usize; 3],
+    w_base: usize,
+    /// Per level from 1, the 3D keep-mask of each class of w digit with a
+    /// mask of its own.
+    masks: Vec<Vec<Vec<bool>>>,
+    /// Per level from 1, the class of every w digit.
+    classes: Vec<Vec<usize>>,
+    /// The lattices of depth `depth - 1`, by the classes of the digits
+    /// leading to them.
+    prefixes: HashMap<Vec<usize>, Lattice3>,
+    /// The last slice refined, reused while w keeps the same classes.
+    last: Option<(Vec<usize>, Lattice3)>,
+}
+
+impl Sweep {
+    /// Plans slices of the depth-`depth` fractal of `rule` at each of
+    /// `ws`, building the coarse lattices they need. None unless the rule
+    /// is a uniform 4D one; others are sliced from the generated lattice.
+    #[tracing::instrument(name = "sweep", skip_all, fields(rule = %rule.name(), slices = ws.len()))]
+    pub fn new(
+        rule: &Rule,
+        depth: u32,
+        ws: &[usize],
+        cancel: &CancelToken,
+    ) -> Result<Option<Self>> {
+        if rule.dims() != 4 || rule.split() != Split::Uniform {
+            return Ok(None);
+        }
+        let bases = rule.bases();
+        let subcells: usize = bases[..3].iter().map(|&b| b as usize).product();
+        let mut masks = Vec::new();
+        let mut classes = Vec::new();
+        for level in 1..=depth {
+            // The w digit is the slowest in a mask's index, so each digit's
+            // 3D mask is a contiguous run.
+            let mut distinct: Vec<Vec<bool>> = Vec::new();
+            let class = rule
+                .mask(level)
+                .chunks_exact(subcells)
+                .map(|mask| match distinct.iter().position(|m| m == mask) {
+                    Some(class) => class,
+                    None => {
+                        distinct.push(mask.to_vec());
+                        distinct.len() - 1
+                    }
+                })
+                .collect();
+            masks.push(distinct);
+            classes.push(class);
+        }
+        let mut sweep = Sweep {
+            depth,
+            bases: std::array::from_fn(|axis| bases[axis] as usize),
+            w_base: bases[3] as usize,
+            masks,
+            classes,
+            prefixes: HashMap::new(),
+            last: None,
+        };
+        let keys: Vec<Vec<usize>> = ws.iter().map(|&w| sweep.key(w)).collect();
+        let mut prefixes =
+            HashMap::from([(Vec::new(), Lattice3::from_fn([1; 3], cancel, |_| true)?)]);
+        for level in 1..depth as usize {
+            let needed: Vec<&[usize]> = keys
+                .iter()
+                .map(|key| &key[..level])
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect();
+            let built = parallel_map(&needed, |prefix| {
+                let (class, parent) = (prefix[level - 1], &prefixes[&prefix[..level - 1]]);
+                sweep.refine(parent, &sweep.masks[level - 1][class], cancel)
+            })?;
+            prefixes = needed
+                .into_iter()
+                .map(<[usize]>::to_vec)
+                .zip(built)
+                .collect();
+        }
+        sweep.prefixes = prefixes;
+        Ok(Some(sweep))
+    }
+
+    /// Cells along w in the hypercube.
+    pub fn side(&self) -> usize {
+        self.w_base.pow(self.depth)
+    }
+
+    /// The classes of `w`'s digits, coarsest first. Slices at w with the
+    /// same key are the same.
+    pub fn key(&self, w: usize) -> Vec<usize> {
+        (1..=self.depth)
+            .map(|level| {
+                let digit = w / self.w_base.pow(self.depth - level) % self.w_base;
+                self.classes[level as usize - 1][digit]
+            })
+            .collect()
+    }
+
+    /// The slice at `w`, one of the planned ones.
+    pub fn slice(&mut self, w: usize, cancel: &CancelToken) -> Result<Lattice3> {
+        let key = self.key(w);
+        if let Some((last, slice)) = &self.last {
+            if *last == key {
+                return Ok(slice.clone());
+            }
+        }
+        let slice = match key.split_last() {
+            Some((&class, prefix)) => {
+                let parent = self
+                    .prefixes
+                    .get(prefix)
+                    .expect("slices are sliced at a planned w");
+                self.refine(parent, &self.masks[self.depth as usize - 1][class], cancel)?
+            }
+            None => Lattice3::from_fn([1; 3], cancel, |_| true)?,
+        };
+        self.last = Some((key, slice.clone()));
+        Ok(slice)
+    }
+
+    /// The next level down from `parent`, keeping the subcells of its
+    /// filled cells that `mask` keeps.
+    fn refine(&self, parent: &Lattice3, mask: &[bool], cancel: &CancelToken) -> Result<Lattice3> {
+        let [bx, by, bz] = self.bases;
+        let shape = std::array::from_fn(|axis| parent.shape()[axis] * self.bases[axis]);
+        Lattice3::from_fn(shape, cancel, |[x, y, z]| {
+            mask[x % bx + (y % by + z % bz * by) * bx] && parent.get([x / bx, y / by, z / bz])
+        })
+    }
+}
+
+/// `f` of every item, shared out between the available cores.
+fn parallel_map<T: Sync, U: Send>(
+    items: &[T],
+    f: impl Fn(&T) -> Result<U> + Sync,
+) -> Result<Vec<U>> {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .clamp(1, items.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = f(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every item is mapped"))
+        .collect()
+}
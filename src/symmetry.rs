@@ -0,0 +1,298 @@
+//! Symmetries of subdivision rules under the signed permutations of the
+//! axes, the 48 symmetries of the cube in 3D and 384 of the tesseract in
+//! 4D, and the fundamental domain that symmetric generation fills before
+//! mirroring it into the rest of the lattice.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::job::Job;
+use crate::rule::{compose, decompose, Rule};
+
+const AXES: [&str; 4] = ["x", "y", "z", "w"];
+
+/// A signed permutation of the axes: coordinate `i` of the image is
+/// coordinate `perm[i]` of the source, reversed when `flip[i]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symmetry {
+    pub perm: Vec<usize>,
+    pub flip: Vec<bool>,
+}
+
+impl Symmetry {
+    /// Every signed permutation of `dims` axes, the identity first.
+    pub fn all(dims: usize) -> Vec<Symmetry> {
+        let mut perms = vec![Vec::new()];
+        for _ in 0..dims {
+            perms = perms
+                .into_iter()
+                .flat_map(|perm: Vec<usize>| {
+                    (0..dims)
+                        .filter(|a| !perm.contains(a))
+                        .map(|a| [perm.as_slice(), &[a]].concat())
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+        }
+        perms
+            .into_iter()
+            .flat_map(|perm| {
+                (0..1usize << dims).map(move |signs| Symmetry {
+                    perm: perm.clone(),
+                    flip: (0..dims).map(|axis| signs >> axis & 1 == 1).collect(),
+                })
+            })
+            .collect()
+    }
+
+    /// The image of `p` in a box `sides` cells across.
+    pub fn apply(&self, p: &[usize], sides: &[usize]) -> Vec<usize> {
+        self.perm
+            .iter()
+            .zip(&self.flip)
+            .map(|(&from, &flip)| {
+                if flip {
+                    sides[from] - 1 - p[from]
+                } else {
+                    p[from]
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the symmetry only reverses `axis`.
+    fn is_reflection(&self, axis: usize) -> bool {
+        self.perm.iter().enumerate().all(|(i, &from)| i == from)
+            && self
+                .flip
+                .iter()
+                .enumerate()
+                .all(|(i, &flip)| flip == (i == axis))
+    }
+
+    /// Whether the symmetry only swaps axes `a` and `b`.
+    fn is_transposition(&self, a: usize, b: usize) -> bool {
+        self.flip.iter().all(|&flip| !flip)
+            && self.perm.iter().enumerate().all(|(i, &from)| match i {
+                _ if i == a => from == b,
+                _ if i == b => from == a,
+                _ => from == i,
+            })
+    }
+}
+
+impl fmt::Display for Symmetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let axes: Vec<String> = self
+            .perm
+            .iter()
+            .zip(&self.flip)
+            .map(|(&from, &flip)| format!("{}{}", if flip { "-" } else { "" }, AXES[from]))
+            .collect();
+        write!(f, "({})", axes.join(", "))
+    }
+}
+
+impl Rule {
+    /// The signed axis permutations that map every keep-mask onto itself,
+    /// the identity first. Only axes split into the same number of parts
+    /// are swapped, so each symmetry of the masks is one of the rule's
+    /// lattices at every depth.
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        let bases = self.bases();
+        let mut digits = vec![0; self.dims()];
+        Symmetry::all(self.dims())
+            .into_iter()
+            .filter(|symmetry| {
+                symmetry
+                    .perm
+                    .iter()
+                    .enumerate()
+                    .all(|(i, &from)| bases[i] == bases[from])
+            })
+            .filter(|symmetry| {
+                let sides: Vec<usize> = bases.iter().map(|&b| b as usize).collect();
+                (1..=self.period() as u32).all(|level| {
+                    let mask = self.mask(level);
+                    (0..mask.len()).all(|index| {
+                        decompose(index, bases, &mut digits);
+                        let p: Vec<usize> = digits.iter().map(|&d| d as usize).collect();
+                        let image: Vec<u32> = symmetry
+                            .apply(&p, &sides)
+                            .into_iter()
+                            .map(|d| d as u32)
+                            .collect();
+                        mask[compose(&image, bases)] == mask[index]
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+/// The part of a symmetry group that symmetric generation exploits:
+/// reflections of single axes, folding the lattice in half along them,
+/// and swaps of axes, sorting the coordinates of the axes they connect.
+/// A cell's orbit under these has one member in the domain, where the
+/// reflected coordinates are in the lower half and the coordinates of
+/// each class of swappable axes ascend.
+#[derive(Clone, Copy, Debug)]
+pub struct Domain<const D: usize> {
+    reflected: [bool; D],
+    /// The first axis of the class of swappable axes each axis is in.
+    class: [usize; D],
+}
+
+impl<const D: usize> Domain<D> {
+    /// The domain of the symmetries in `group`.
+    pub fn new(group: &[Symmetry]) -> Self {
+        let reflected = std::array::from_fn(|axis| group.iter().any(|s| s.is_reflection(axis)));
+        let mut class: [usize; D] = std::array::from_fn(|axis| axis);
+        for b in 0..D {
+            if let Some(a) = (0..b).find(|&a| group.iter().any(|s| s.is_transposition(a, b))) {
+                class[b] = class[a];
+            }
+        }
+        Domain { reflected, class }
+    }
+
+    /// The number of cells each cell of the domain stands for in the
+    /// bulk of the lattice: the order of the exploited subgroup.
+    pub fn order(&self) -> usize {
+        let reflections = 1 << self.reflected.iter().filter(|&&r| r).count();
+        (0..D)
+            .map(|axis| self.class.iter().filter(|&&c| c == axis).count())
+            .map(|size| (1..=size).product::<usize>())
+            .product::<usize>()
+            * reflections
+    }
+
+    /// The shape of the box holding the domain in a lattice of `shape`.
+    pub fn folded(&self, shape: [usize; D]) -> [usize; D] {
+        std::array::from_fn(|axis| {
+            if self.reflected[axis] {
+                shape[axis].div_ceil(2)
+            } else {
+                shape[axis]
+            }
+        })
+    }
+
+    /// Whether the folded cell `q` is in the domain.
+    pub fn contains(&self, q: [usize; D]) -> bool {
+        (0..D).all(|b| (0..b).all(|a| self.class[a] != self.class[b] || q[a] <= q[b]))
+    }
+
+    /// The member of `p`'s orbit in the domain, in a lattice of `shape`.
+    pub fn canonical(&self, mut p: [usize; D], shape: [usize; D]) -> [usize; D] {
+        for axis in 0..D {
+            if self.reflected[axis] {
+                p[axis] = p[axis].min(shape[axis] - 1 - p[axis]);
+            }
+        }
+        for a in 0..D {
+            for b in a + 1..D {
+                if self.class[a] == self.class[b] && p[a] > p[b] {
+                    p.swap(a, b);
+                }
+            }
+        }
+        p
+    }
+}
+
+/// The symmetry group of a job's rule.
+#[derive(Clone, Debug, Serialize)]
+pub struct SymmetryReport {
+    pub job: String,
+    pub dims: usize,
+    /// Every symmetry of the rule's masks, as the image of the axes, such
+    /// as `(y, -x, z)`; the identity first.
+    pub symmetries: Vec<String>,
+    /// Axes whose reflection alone is a symmetry.
+    pub reflections: Vec<String>,
+    /// Classes of axes any two of which may be swapped alone.
+    pub swappable: Vec<Vec<String>>,
+    /// The cells of the lattice stood for by each cell of the fundamental
+    /// domain `--symmetric` generates.
+    pub domain_order: usize,
+}
+
+impl fmt::Display for SymmetryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let signed = (1..=self.dims).product::<usize>() << self.dims;
+        writeln!(
+            f,
+            "symmetry {}: {} of {} signed axis permutations",
+            self.job,
+            self.symmetries.len(),
+            signed
+        )?;
+        let list = |axes: &[String]| {
+            if axes.is_empty() {
+                "none".to_string()
+            } else {
+                axes.join(", ")
+            }
+        };
+        writeln!(f, "  reflections: {}", list(&self.reflections))?;
+        let swappable: Vec<String> = self.swappable.iter().map(|class| class.join("")).collect();
+        writeln!(f, "  swappable axes: {}", list(&swappable))?;
+        writeln!(
+            f,
+            "  fundamental domain: 1/{} of the cells",
+            self.domain_order
+        )?;
+        for symmetry in &self.symmetries {
+            writeln!(f, "  {symmetry}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Job {
+    /// Finds the symmetries of the job's rule from its masks.
+    pub fn symmetry(&self) -> Result<SymmetryReport> {
+        self.validate()?;
+        if !self.is_rule()? {
+            return Err(Error::InvalidJob(format!(
+                "`{}` is not a subdivision rule fractal",
+                self.fractal
+            )));
+        }
+        let rule = self.rule()?;
+        let group = rule.symmetries();
+        let (reflections, swappable, domain_order) = match self.dims {
+            3 => describe(&Domain::<3>::new(&group)),
+            _ => describe(&Domain::<4>::new(&group)),
+        };
+        Ok(SymmetryReport {
+            job: self.display_name(),
+            dims: self.dims,
+            symmetries: group.iter().map(Symmetry::to_string).collect(),
+            reflections,
+            swappable,
+            domain_order,
+        })
+    }
+}
+
+/// The reflected axes, classes of swappable axes and order of a domain.
+fn describe<const D: usize>(domain: &Domain<D>) -> (Vec<String>, Vec<Vec<String>>, usize) {
+    let reflections = (0..D)
+        .filter(|&axis| domain.reflected[axis])
+        .map(|axis| AXES[axis].to_string())
+        .collect();
+    let swappable = (0..D)
+        .map(|first| {
+            (0..D)
+                .filter(|&axis| domain.class[axis] == first)
+                .map(|axis| AXES[axis].to_string())
+                .collect::<Vec<_>>()
+        })
+        .filter(|class| class.len() > 1)
+        .collect();
+    (reflections, swappable, domain.order())
+}
@@ -0,0 +1,155 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cancel::CancelToken;
+use crate::distance::signed_distances;
+use crate::error::{Error, Result};
+use crate::image::ImageValues;
+use crate::lattice::Lattice3;
+
+/// Settings of `.dds` 3D texture outputs, raymarched by shaders.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Texture {
+    /// Compress occupancy textures to BC4, eight bytes per 4×4 block of
+    /// each layer, which GPUs sample directly. Exact for occupancy, whose
+    /// texels are only 0 and 255.
+    #[serde(default)]
+    pub bc4: bool,
+}
+
+/// DXGI formats of the texels.
+const DXGI_FORMAT_R32_FLOAT: u32 = 41;
+const DXGI_FORMAT_R8_UNORM: u32 = 61;
+const DXGI_FORMAT_BC4_UNORM: u32 = 80;
+
+/// How many layers are written between cancellation checks.
+const CANCEL_LAYERS: usize = 16;
+
+impl Texture {
+    /// Writes `lattice` as a DirectDraw Surface volume texture with the
+    /// DX10 header, x across, y down and z deep: `R8_UNORM` occupancy (255
+    /// filled), `BC4_UNORM` when compressing, or for
+    /// [`ImageValues::Distance`] `R32_FLOAT` signed distance in cells.
+    pub fn write_dds(
+        &self,
+        lattice: &Lattice3,
+        values: ImageValues,
+        out: &mut impl Write,
+        cancel: &CancelToken,
+    ) -> Result<()> {
+        let [width, height, depth] = lattice.shape();
+        let (format, layer_bytes) = match (values, self.bc4) {
+            (ImageValues::Occupancy, false) => (DXGI_FORMAT_R8_UNORM, width * height),
+            (ImageValues::Occupancy, true) => (
+                DXGI_FORMAT_BC4_UNORM,
+                width.div_ceil(4) * height.div_ceil(4) * 8,
+            ),
+            (ImageValues::Distance, false) => (DXGI_FORMAT_R32_FLOAT, width * height * 4),
+            (ImageValues::Distance, true) => {
+                return Err(Error::InvalidJob(
+                    "BC4 compresses occupancy textures only, not distances".into(),
+                ))
+            }
+        };
+        // Caps, height, width, pixel format and depth, with the pitch of a
+        // row, or the size of a layer when compressed.
+        let flags: u32 = 0x1 | 0x2 | 0x4 | 0x1000 | 0x80_0000;
+        let (flags, pitch) = if self.bc4 {
+            (flags | 0x8_0000, layer_bytes)
+        } else {
+            (flags | 0x8, layer_bytes / height)
+        };
+        let mut header = Vec::with_capacity(148);
+        header.extend_from_slice(b"DDS ");
+        for value in [
+            124,
+            flags,
+            height as u32,
+            width as u32,
+            pitch as u32,
+            depth as u32,
+            1,
+        ] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(&[0; 44]);
+        // The pixel format only points on to the DX10 header.
+        header.extend_from_slice(&32u32.to_le_bytes());
+        header.extend_from_slice(&4u32.to_le_bytes());
+        header.extend_from_slice(b"DX10");
+        header.extend_from_slice(&[0; 20]);
+        // A complex texture, a volume.
+        for value in [0x1008u32, 0x20_0000, 0, 0, 0] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        // The format, a 3D resource, no flags, one texture, straight alpha.
+        for value in [format, 4, 0, 1, 0] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        out.write_all(&header)?;
+
+        if values == ImageValues::Distance {
+            for (i, value) in signed_distances(lattice, cancel)?.iter().enumerate() {
+                if i % (width * height * CANCEL_LAYERS) == 0 {
+                    cancel.check()?;
+                }
+                out.write_all(&(*value as f32).to_le_bytes())?;
+            }
+            return Ok(());
+        }
+        for z in 0..depth {
+            if z % CANCEL_LAYERS == 0 {
+                cancel.check()?;
+            }
+            let texel = |x: usize, y: usize| if lattice.get([x, y, z]) { 255 } else { 0 };
+            let mut layer = Vec::with_capacity(layer_bytes);
+            if self.bc4 {
+                for by in (0..height).step_by(4) {
+                    for bx in (0..width).step_by(4) {
+                        // Blocks past the edge repeat the edge texels.
+                        let block = std::array::from_fn(|i| {
+                            texel((bx + i % 4).min(width - 1), (by + i / 4).min(height - 1))
+                        });
+                        layer.extend_from_slice(&bc4_block(&block));
+                    }
+                }
+            } else {
+                for y in 0..height {
+                    layer.extend((0..width).map(|x| texel(x, y)));
+                }
+            }
+            out.write_all(&layer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes 16 texels, row by row, as a BC4 block: the largest and smallest
+/// value as endpoints, six steps between them, and the nearest of the
+/// eight for each texel.
+fn bc4_block(texels: &[u8; 16]) -> [u8; 8] {
+    let high = *texels.iter().max().unwrap();
+    let low = *texels.iter().min().unwrap();
+    let mut block = [high, low, 0, 0, 0, 0, 0, 0];
+    if high == low {
+        return block;
+    }
+    // With the first endpoint larger, code 0 is `high`, 1 is `low`, and
+    // codes 2 to 7 step from `high` towards `low`.
+    let palette: [i32; 8] = std::array::from_fn(|code| match code {
+        0 => high as i32,
+        1 => low as i32,
+        c => ((8 - c as i32) * high as i32 + (c as i32 - 1) * low as i32) / 7,
+    });
+    let mut bits = 0u64;
+    for (i, &texel) in texels.iter().enumerate() {
+        let code = (0..8)
+            .min_by_key(|&c| (palette[c] - texel as i32).abs())
+            .unwrap() as u64;
+        bits |= code << (3 * i);
+    }
+    block[2..].copy_from_slice(&bits.to_le_bytes()[..6]);
+    block
+}
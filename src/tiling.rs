@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::transform::{Affine, Transform};
+
+/// Copies of the generated object laid out in a grid.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tiling {
+    /// Copies along x, y, z and w. Tiling a 4D fractal along w repeats it
+    /// in w, so slices beyond the first copy wrap around.
+    #[serde(default = "single")]
+    pub count: [usize; 4],
+    /// Gap between neighbouring copies, in lattice cells.
+    #[serde(default)]
+    pub spacing: f64,
+    /// Transforms applied to each copy about its own origin before it is
+    /// placed. Copy `i` uses entry `i` modulo the length, so one entry
+    /// applies to every copy.
+    #[serde(default)]
+    pub instance_transforms: Vec<Vec<Transform>>,
+}
+
+fn single() -> [usize; 4] {
+    [1; 4]
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Tiling {
+            count: single(),
+            spacing: 0.0,
+            instance_transforms: Vec::new(),
+        }
+    }
+}
+
+impl Tiling {
+    /// Whether the tiling leaves the 3D geometry untouched.
+    pub fn is_single(&self) -> bool {
+        self.count[..3] == [1; 3] && self.instance_transforms.is_empty()
+    }
+
+    /// Number of copies in each 3D slice.
+    pub fn instance_count(&self) -> usize {
+        self.count[..3].iter().product()
+    }
+
+    /// The placement of every copy of an object of size `extent`, x
+    /// varying fastest.
+    pub fn instances(&self, extent: [f64; 3]) -> Vec<Affine> {
+        let pitch = extent.map(|size| size + self.spacing);
+        let [nx, ny, nz, _] = self.count;
+        let mut instances = Vec::with_capacity(self.instance_count());
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    let local = match self.instance_transforms.as_slice() {
+                        [] => Affine::IDENTITY,
+                        all => Affine::from_transforms(&all[instances.len() % all.len()]),
+                    };
+                    let index = [x, y, z];
+                    let offset = std::array::from_fn(|axis| index[axis] as f64 * pitch[axis]);
+                    instances.push(Affine::from(Transform::Translate(offset)).then(&local));
+                }
+            }
+        }
+        instances
+    }
+}
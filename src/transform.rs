@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+
+/// A coordinate axis.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// A geometric transform applied to output geometry, in lattice units.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transform {
+    Scale(f64),
+    Translate([f64; 3]),
+    Rotate { axis: Axis, degrees: f64 },
+}
+
+/// A 3x4 affine matrix, row major, acting on column vectors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Affine([[f64; 4]; 3]);
+
+impl Affine {
+    pub const IDENTITY: Affine = Affine([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+    ]);
+
+    /// Composes a list of transforms, applied in order.
+    pub fn from_transforms(transforms: &[Transform]) -> Self {
+        transforms
+            .iter()
+            .fold(Affine::IDENTITY, |acc, t| Affine::from(*t).then(&acc))
+    }
+
+    /// The transform applying `inner` first and then `self`.
+    pub fn then(&self, inner: &Affine) -> Affine {
+        let (a, b) = (&self.0, &inner.0);
+        let mut m = [[0.0; 4]; 3];
+        for (r, row) in m.iter_mut().enumerate() {
+            for (c, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| a[r][k] * b[k][c]).sum();
+            }
+            row[3] += a[r][3];
+        }
+        Affine(m)
+    }
+
+    pub fn apply(&self, p: [f64; 3]) -> [f64; 3] {
+        self.0
+            .map(|row| row[0] * p[0] + row[1] * p[1] + row[2] * p[2] + row[3])
+    }
+
+    /// The matrix's rows.
+    pub fn rows(&self) -> [[f64; 4]; 3] {
+        self.0
+    }
+
+    /// The matrix as a column-major 4x4, as glTF stores node transforms.
+    pub fn to_column_major(&self) -> [f64; 16] {
+        let m = &self.0;
+        std::array::from_fn(|i| {
+            let (column, row) = (i / 4, i % 4);
+            match row {
+                3 if column == 3 => 1.0,
+                3 => 0.0,
+                _ => m[row][column],
+            }
+        })
+    }
+
+    /// Whether the transform mirrors geometry, which flips face winding.
+    pub fn is_mirroring(&self) -> bool {
+        self.determinant() < 0.0
+    }
+
+    /// The factor lengths are scaled by, which for the uniform scales,
+    /// rotations and translations of [`Transform`] is the same in every
+    /// direction.
+    pub fn scale_factor(&self) -> f64 {
+        self.determinant().abs().cbrt()
+    }
+
+    fn determinant(&self) -> f64 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
+
+impl Default for Affine {
+    fn default() -> Self {
+        Affine::IDENTITY
+    }
+}
+
+impl From<Transform> for Affine {
+    fn from(t: Transform) -> Self {
+        let mut m = Affine::IDENTITY.0;
+        match t {
+            Transform::Scale(s) => {
+                for (axis, row) in m.iter_mut().enumerate() {
+                    row[axis] = s;
+                }
+            }
+            Transform::Translate(offset) => {
+                for (row, d) in m.iter_mut().zip(offset) {
+                    row[3] = d;
+                }
+            }
+            Transform::Rotate { axis, degrees } => {
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                let a = axis.index();
+                let (u, v) = ((a + 1) % 3, (a + 2) % 3);
+                m[u][u] = cos;
+                m[u][v] = -sin;
+                m[v][u] = sin;
+                m[v][v] = cos;
+            }
+        }
+        Affine(m)
+    }
+}
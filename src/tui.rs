@@ -0,0 +1,504 @@
+//! A terminal front-end for running a job on a remote machine over SSH,
+//! where the viewer's window cannot open: a form to adjust the job, with
+//! its plan, before launch, then its stages, progress, memory use and log
+//! while it runs.
+//!
+//! The job's spans and events reach the screen through a tracing layer
+//! installed as the global subscriber, so nothing is written to stderr
+//! underneath the interface.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event as TracingEvent, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::alloc;
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::job::{Job, JobReport};
+use crate::plan::human_bytes;
+
+/// Log lines kept for the log pane.
+const LOG_LINES: usize = 500;
+/// Stages kept, oldest finished ones dropped first.
+const STAGES: usize = 200;
+
+/// Shows the form for `job`, runs it as often as asked, and returns once
+/// the user quits.
+pub fn run(job: Job) -> Result<()> {
+    let activity = Arc::new(Mutex::new(Activity::default()));
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "info,fractal_slicer_4_d=trace".into());
+    tracing_subscriber::registry()
+        .with(Recorder(activity.clone()).with_filter(filter))
+        .try_init()
+        .map_err(|e| Error::InvalidJob(format!("cannot capture logs: {e}")))?;
+    let mut terminal = ratatui::init();
+    let mut app = App::new(job, activity);
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    app.stop();
+    result
+}
+
+/// What the running job has done, as its spans and events tell.
+#[derive(Default)]
+struct Activity {
+    stages: VecDeque<Stage>,
+    progress: Option<Progress>,
+    log: VecDeque<String>,
+}
+
+/// One span: a stage of the job such as generation or an export.
+struct Stage {
+    id: u64,
+    name: &'static str,
+    fields: String,
+    nesting: usize,
+    started: Instant,
+    elapsed: Option<Duration>,
+}
+
+/// The last event reporting `done` of `total` steps, until the span it
+/// came from closes.
+struct Progress {
+    span: Option<u64>,
+    message: String,
+    done: u64,
+    total: u64,
+}
+
+/// Collects an event's or span's fields, its message apart.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    text: String,
+    done: Option<u64>,
+    total: Option<u64>,
+}
+
+impl Visit for Fields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "done" => self.done = Some(value),
+            "total" => self.total = Some(value),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        match u64::try_from(value) {
+            Ok(value) => self.record_u64(field, value),
+            Err(_) => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_owned(),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{value:?}"),
+            name => {
+                let _ = write!(self.text, " {name}={value:?}");
+            }
+        }
+    }
+}
+
+/// Feeds spans and events into the shared [`Activity`].
+struct Recorder(Arc<Mutex<Activity>>);
+
+impl Recorder {
+    fn activity(&self) -> MutexGuard<'_, Activity> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        let nesting = ctx.span(id).map_or(0, |span| span.scope().count() - 1);
+        let mut activity = self.activity();
+        if activity.stages.len() == STAGES {
+            match activity.stages.iter().position(|s| s.elapsed.is_some()) {
+                Some(oldest) => activity.stages.remove(oldest),
+                None => activity.stages.pop_front(),
+            };
+        }
+        activity.stages.push_back(Stage {
+            id: id.into_u64(),
+            name: attrs.metadata().name(),
+            fields: fields.text,
+            nesting,
+            started: Instant::now(),
+            elapsed: None,
+        });
+    }
+
+    fn on_close(&self, id: Id, _ctx: Context<'_, S>) {
+        let mut activity = self.activity();
+        let id = id.into_u64();
+        if let Some(stage) = activity.stages.iter_mut().rev().find(|s| s.id == id) {
+            stage.elapsed = Some(stage.started.elapsed());
+        }
+        if activity
+            .progress
+            .as_ref()
+            .is_some_and(|p| p.span == Some(id))
+        {
+            activity.progress = None;
+        }
+    }
+
+    fn on_event(&self, event: &TracingEvent<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let mut activity = self.activity();
+        if let (Some(done), Some(total)) = (fields.done, fields.total) {
+            activity.progress = Some(Progress {
+                span: ctx.event_span(event).map(|span| span.id().into_u64()),
+                message: fields.message,
+                done,
+                total,
+            });
+            return;
+        }
+        if activity.log.len() == LOG_LINES {
+            activity.log.pop_front();
+        }
+        let metadata = event.metadata();
+        activity.log.push_back(format!(
+            "{:>5} {}: {}{}",
+            metadata.level(),
+            metadata.target(),
+            fields.message,
+            fields.text
+        ));
+    }
+}
+
+/// A job's run in the background.
+struct Run {
+    started: Instant,
+    cancel: CancelToken,
+    thread: Option<JoinHandle<Result<JobReport>>>,
+    outcome: Option<Result<JobReport>>,
+}
+
+/// The text fields of the form, in order.
+const LABELS: [&str; 4] = ["fractal", "dimensions", "depth", "outputs"];
+
+struct App {
+    base: Job,
+    fields: [String; 4],
+    selected: usize,
+    /// The plan of the job the form describes, or why there is none.
+    plan: std::result::Result<String, String>,
+    run: Option<Run>,
+    activity: Arc<Mutex<Activity>>,
+}
+
+impl App {
+    fn new(job: Job, activity: Arc<Mutex<Activity>>) -> Self {
+        let outputs: Vec<String> = job
+            .outputs
+            .iter()
+            .map(|p| p.display().to_string())
+            .collect();
+        let mut app = App {
+            fields: [
+                job.fractal.clone(),
+                job.dims.to_string(),
+                job.depth.to_string(),
+                outputs.join(", "),
+            ],
+            base: job,
+            selected: 0,
+            plan: Err(String::new()),
+            run: None,
+            activity,
+        };
+        app.replan();
+        app
+    }
+
+    /// The job the form describes.
+    fn job(&self) -> std::result::Result<Job, String> {
+        let [fractal, dims, depth, outputs] = &self.fields;
+        let mut job = self.base.clone();
+        job.fractal = fractal.trim().to_owned();
+        job.dims = dims
+            .trim()
+            .parse()
+            .map_err(|_| format!("dimensions: `{dims}` is not a number"))?;
+        job.depth = depth
+            .trim()
+            .parse()
+            .map_err(|_| format!("depth: `{depth}` is not a number"))?;
+        job.outputs = outputs
+            .split(',')
+            .map(str::trim)
+            .filter(|output| !output.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        if job.outputs.is_empty() {
+            return Err("outputs: name at least one file".into());
+        }
+        Ok(job)
+    }
+
+    fn replan(&mut self) {
+        self.plan = self
+            .job()
+            .and_then(|job| job.plan().map_err(|e| e.to_string()))
+            .map(|plan| plan.to_string());
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            self.poll();
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let interrupt =
+                key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL;
+            match &self.run {
+                Some(run) if run.outcome.is_none() => {
+                    if interrupt || matches!(key.code, KeyCode::Esc | KeyCode::Char('q')) {
+                        run.cancel.cancel();
+                    }
+                }
+                Some(_) => match key.code {
+                    KeyCode::Char('e') | KeyCode::Enter => self.run = None,
+                    KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+                    _ if interrupt => return Ok(()),
+                    _ => {}
+                },
+                None => match key.code {
+                    _ if interrupt => return Ok(()),
+                    KeyCode::Esc => return Ok(()),
+                    KeyCode::Up | KeyCode::BackTab => {
+                        self.selected = (self.selected + LABELS.len() - 1) % LABELS.len()
+                    }
+                    KeyCode::Down | KeyCode::Tab => {
+                        self.selected = (self.selected + 1) % LABELS.len()
+                    }
+                    KeyCode::Backspace => {
+                        self.fields[self.selected].pop();
+                        self.replan();
+                    }
+                    KeyCode::Char(c) => {
+                        self.fields[self.selected].push(c);
+                        self.replan();
+                    }
+                    KeyCode::Enter => self.launch(),
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// Starts the job the form describes, if it is valid.
+    fn launch(&mut self) {
+        let Ok(job) = self.job() else { return };
+        if self.plan.is_err() {
+            return;
+        }
+        *self.activity.lock().unwrap_or_else(|p| p.into_inner()) = Activity::default();
+        let cancel = CancelToken::new();
+        let thread = {
+            let cancel = cancel.clone();
+            std::thread::spawn(move || job.run_cancellable(&cancel))
+        };
+        self.run = Some(Run {
+            started: Instant::now(),
+            cancel,
+            thread: Some(thread),
+            outcome: None,
+        });
+    }
+
+    /// Collects the job's outcome once its thread has finished.
+    fn poll(&mut self) {
+        let Some(run) = &mut self.run else { return };
+        if !run.thread.as_ref().is_some_and(JoinHandle::is_finished) {
+            return;
+        }
+        let thread = run.thread.take().expect("checked above");
+        run.outcome = Some(match thread.join() {
+            Ok(result) => result,
+            Err(_) => Err(Error::InvalidJob("the job panicked".into())),
+        });
+    }
+
+    /// Cancels a running job and waits for it.
+    fn stop(&mut self) {
+        if let Some(run) = &mut self.run {
+            run.cancel.cancel();
+            if let Some(thread) = run.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        match &self.run {
+            None => self.draw_form(frame),
+            Some(run) => self.draw_run(frame, run),
+        }
+    }
+
+    fn draw_form(&self, frame: &mut Frame) {
+        let [top, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [form, plan] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(top);
+        let items: Vec<ListItem> = LABELS
+            .iter()
+            .zip(&self.fields)
+            .map(|(label, value)| ListItem::new(format!("{label:>10}: {value}")))
+            .collect();
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(
+            List::new(items)
+                .block(Block::bordered().title(" job "))
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED)),
+            form,
+            &mut state,
+        );
+        let (title, text) = match &self.plan {
+            Ok(plan) => (" plan ", plan.as_str()),
+            Err(e) => (" cannot run ", e.as_str()),
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(title)),
+            plan,
+        );
+        frame.render_widget(
+            Line::from("up/down select   type to edit   enter run   esc quit"),
+            help,
+        );
+    }
+
+    fn draw_run(&self, frame: &mut Frame, run: &Run) {
+        let activity = self.activity.lock().unwrap_or_else(|p| p.into_inner());
+        let [status, gauge, middle, log, help] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Percentage(50),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let state = match &run.outcome {
+            None => format!("running for {:.1?}", run.started.elapsed()),
+            Some(Ok(report)) => format!(
+                "finished: {} {} and {} files in {:.2?}",
+                report.cells,
+                if report.surface { "triangles" } else { "cells" },
+                report.artifacts.len(),
+                report.elapsed
+            ),
+            Some(Err(Error::Cancelled)) => "cancelled".into(),
+            Some(Err(e)) => format!("failed: {e}"),
+        };
+        frame.render_widget(
+            Paragraph::new(format!("{state}   {}", memory()))
+                .block(Block::bordered().title(format!(" {} ", self.fields[0]))),
+            status,
+        );
+        let (label, ratio) = match (&activity.progress, &run.outcome) {
+            (_, Some(Ok(_))) => ("done".into(), 1.0),
+            (Some(p), None) if p.total > 0 => (
+                format!("{} {} of {}", p.message, p.done, p.total),
+                (p.done as f64 / p.total as f64).clamp(0.0, 1.0),
+            ),
+            _ => ("waiting for progress".into(), 0.0),
+        };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(" progress "))
+                .label(label)
+                .ratio(ratio),
+            gauge,
+        );
+        render_tail(
+            frame,
+            middle,
+            " stages ",
+            activity.stages.iter().map(|stage| {
+                let time = match stage.elapsed {
+                    Some(elapsed) => format!("{elapsed:.2?}"),
+                    None => format!("{:.1?}…", stage.started.elapsed()),
+                };
+                format!(
+                    "{:indent$}{} {time}{}",
+                    "",
+                    stage.name,
+                    stage.fields,
+                    indent = stage.nesting * 2
+                )
+            }),
+        );
+        render_tail(frame, log, " log ", activity.log.iter().cloned());
+        let keys = match run.outcome {
+            None => "esc or q cancel",
+            Some(_) => "e edit and rerun   q quit",
+        };
+        frame.render_widget(Line::from(keys), help);
+    }
+}
+
+/// Draws the last lines of `lines` that fit in `area`.
+fn render_tail(
+    frame: &mut Frame,
+    area: Rect,
+    title: &str,
+    lines: impl DoubleEndedIterator<Item = String>,
+) {
+    let rows = area.height.saturating_sub(2) as usize;
+    let mut tail: Vec<ListItem> = lines.rev().take(rows).map(ListItem::new).collect();
+    tail.reverse();
+    frame.render_widget(List::new(tail).block(Block::bordered().title(title)), area);
+}
+
+/// The process's resident and peak memory, where the system reports them.
+fn memory() -> String {
+    match alloc::resident() {
+        Some((rss, peak)) => format!("memory {} (peak {})", human_bytes(rss), human_bytes(peak)),
+        None => String::new(),
+    }
+}
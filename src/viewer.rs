@@ -0,0 +1,576 @@
+//! An interactive window for exploring slices of the 4D rules: a panel of
+//! live controls beside an orbiting render of the slice.
+//!
+//! Everything is drawn on the CPU, the slice by [`render`] and the panel by
+//! a small rasterizer for egui, so the viewer runs wherever a window opens.
+//!
+//! Other egui apps can embed the controls alone as a
+//! [`FractalExplorerWidget`], drawing the meshes it regenerates themselves.
+
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+use winit::application::ApplicationHandler;
+use winit::dpi::LogicalSize;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, EventLoop};
+use winit::window::{Window, WindowId};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::export::{export, ExportOptions};
+use crate::lattice::Lattice3;
+use crate::mesh::sub;
+use crate::render::{render, Camera, Scene};
+use crate::slice::{Bookmark, Explorer};
+use crate::watch::Watch;
+
+mod clipping;
+mod explorer;
+mod input;
+mod paint;
+mod stream;
+mod widget;
+
+use input::Input;
+use paint::Painter;
+use stream::Stream;
+pub use widget::{FractalExplorerWidget, MeshUpdate};
+
+/// Opens the viewer on `explorer`'s slice, seen from `scene`'s camera and
+/// lit and coloured as it describes, and returns once the window is
+/// closed. Screenshots are rendered `screenshot` pixels wide and high.
+///
+/// When `watch` names the scene's file, the scene is reloaded each time
+/// the file changes, keeping the camera the view has moved to.
+pub fn run(
+    explorer: Explorer,
+    scene: Scene,
+    screenshot: [usize; 2],
+    watch: Option<PathBuf>,
+) -> Result<()> {
+    let event_loop = EventLoop::with_user_event().build().map_err(other)?;
+    let proxy = event_loop.create_proxy();
+    let wake: Arc<dyn Fn() + Send + Sync> = Arc::new(move || {
+        // Fails only once the window is gone.
+        let _ = proxy.send_event(());
+    });
+    let stop = CancelToken::new();
+    let scene_file = watch.map(|path| {
+        let changes = watch_scene(&path, stop.clone(), wake.clone());
+        (path, changes)
+    });
+    let mut viewer = Viewer::new(explorer, scene, scene_file, screenshot, wake);
+    let ran = event_loop.run_app(&mut viewer).map_err(other);
+    stop.cancel();
+    ran?;
+    match viewer.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Watches the scene file at `path` from another thread, sending word of
+/// each change and waking the viewer, until `stop` is cancelled.
+fn watch_scene(path: &Path, stop: CancelToken, wake: Arc<dyn Fn() + Send + Sync>) -> Receiver<()> {
+    let (sender, changes) = mpsc::channel();
+    let mut watch = Watch::new([path.to_path_buf()]);
+    std::thread::spawn(move || {
+        while watch.wait(&stop).is_ok() && sender.send(()).is_ok() {
+            wake();
+        }
+    });
+    changes
+}
+
+/// A camera circling the origin, z up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Orbit {
+    /// Degrees about z from the x axis.
+    yaw: f64,
+    /// Degrees above the xy plane.
+    pitch: f64,
+    distance: f64,
+}
+
+impl Orbit {
+    /// The orbit through `camera`'s position, as seen from its target.
+    fn new(camera: &Camera) -> Self {
+        let [x, y, z] = sub(camera.position, camera.target);
+        let distance = (x * x + y * y + z * z).sqrt();
+        Orbit {
+            yaw: y.atan2(x).to_degrees(),
+            pitch: (z / distance).asin().to_degrees().clamp(-89.0, 89.0),
+            distance: distance.clamp(1.0, 40.0),
+        }
+    }
+
+    fn camera(&self, base: &Camera, width: usize, height: usize) -> Camera {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Camera {
+            position: [
+                self.distance * pitch.cos() * yaw.cos(),
+                self.distance * pitch.cos() * yaw.sin(),
+                self.distance * pitch.sin(),
+            ],
+            target: [0.0; 3],
+            up: [0.0, 0.0, 1.0],
+            width,
+            height,
+            stereo: None,
+            orthographic: None,
+            ..base.clone()
+        }
+    }
+}
+
+/// The rendered slice shown, and what it was rendered for.
+struct View {
+    texture: egui::TextureHandle,
+    size: [usize; 2],
+    orbit: Orbit,
+}
+
+struct Viewer {
+    explorer: Explorer,
+    scene: Scene,
+    /// The scene's file and word of its changes, when it is watched.
+    scene_file: Option<(PathBuf, Receiver<()>)>,
+    orbit: Orbit,
+    window: Option<Surface>,
+    egui: egui::Context,
+    input: Input,
+    painter: Painter,
+    /// The current slice, or why it could not be sampled.
+    lattice: std::result::Result<Lattice3, String>,
+    /// Deeper levels of the slice still being sampled.
+    stream: Option<Stream>,
+    /// Asks for a redraw from another thread.
+    wake: Arc<dyn Fn() + Send + Sync>,
+    view: Option<View>,
+    /// Stem of exported files; the format's extension is added.
+    export_stem: String,
+    /// Width and height of screenshots, and of bookmarked cameras.
+    screenshot: [usize; 2],
+    bookmark: String,
+    status: String,
+    error: Option<Error>,
+}
+
+struct Surface {
+    window: Rc<Window>,
+    surface: softbuffer::Surface<Rc<Window>, Rc<Window>>,
+}
+
+/// Formats the export buttons write, by extension.
+const EXPORTS: [(&str, &str); 4] = [
+    ("OBJ", "obj"),
+    ("STL", "stl"),
+    ("glTF", "glb"),
+    ("VTK", "vtk"),
+];
+
+impl Viewer {
+    fn new(
+        explorer: Explorer,
+        scene: Scene,
+        scene_file: Option<(PathBuf, Receiver<()>)>,
+        screenshot: [usize; 2],
+        wake: Arc<dyn Fn() + Send + Sync>,
+    ) -> Self {
+        let mut viewer = Viewer {
+            explorer,
+            orbit: Orbit::new(&scene.camera),
+            scene,
+            scene_file,
+            window: None,
+            egui: egui::Context::default(),
+            input: Input::new(),
+            painter: Painter::new(),
+            lattice: Err(String::new()),
+            stream: None,
+            wake,
+            view: None,
+            export_stem: "slice".into(),
+            screenshot,
+            bookmark: "bookmark.json".into(),
+            status: String::new(),
+            error: None,
+        };
+        viewer.resample();
+        viewer
+    }
+
+    /// Starts sampling the slice afresh, keeping the current one on show
+    /// until the first level arrives.
+    fn resample(&mut self) {
+        let wake = self.wake.clone();
+        self.stream = Some(Stream::start(&self.explorer, false, move || wake()));
+    }
+
+    /// Shows the deepest level sampled since the last frame.
+    fn refine(&mut self) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        let Some((depth, sample)) = stream.poll() else {
+            return;
+        };
+        if depth == stream.depth || sample.is_err() {
+            self.stream = None;
+        }
+        self.lattice = sample
+            .map(|sample| sample.lattice)
+            .map_err(|e| e.to_string());
+        self.view = None;
+    }
+
+    /// Reloads the scene once its file has changed, keeping the camera.
+    fn reload_scene(&mut self) {
+        let Some((path, changes)) = &self.scene_file else {
+            return;
+        };
+        if changes.try_iter().count() == 0 {
+            return;
+        }
+        self.status = match Scene::load(path) {
+            Ok(scene) => {
+                self.scene = Scene {
+                    camera: self.scene.camera.clone(),
+                    ..scene
+                };
+                self.view = None;
+                format!("reloaded {}", path.display())
+            }
+            Err(e) => format!("{}: {e}", path.display()),
+        };
+    }
+
+    fn ui(&mut self, ctx: &egui::Context) {
+        use egui::{Key, Modifiers};
+        self.refine();
+        self.reload_scene();
+        let sampled = self.stream.is_none();
+        let (shoot, save, load) = ctx.input_mut(|input| {
+            (
+                input.consume_key(Modifiers::NONE, Key::F12),
+                input.consume_key(Modifiers::COMMAND, Key::S),
+                input.consume_key(Modifiers::COMMAND, Key::O),
+            )
+        });
+        egui::SidePanel::left("controls")
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.heading("fractal-slicer");
+                ui.separator();
+                if self.explorer.ui(ui) {
+                    self.resample();
+                }
+                ui.separator();
+                ui.label("Clipping");
+                let clipping = self.scene.clipping.get_or_insert_with(Default::default);
+                if clipping.ui(ui) {
+                    self.view = None;
+                }
+                if clipping.planes.is_empty() && clipping.cutaway.is_none() {
+                    self.scene.clipping = None;
+                }
+                ui.separator();
+                ui.label("Export");
+                ui.horizontal(|ui| {
+                    ui.label("File");
+                    ui.text_edit_singleline(&mut self.export_stem);
+                });
+                // Exports and screenshots wait for the full depth.
+                ui.add_enabled_ui(sampled, |ui| {
+                    ui.horizontal(|ui| {
+                        for (label, extension) in EXPORTS {
+                            if ui.button(label).clicked() {
+                                self.export(extension);
+                            }
+                        }
+                    });
+                });
+                ui.separator();
+                ui.label("Screenshot");
+                ui.horizontal(|ui| {
+                    let [width, height] = &mut self.screenshot;
+                    ui.add(egui::DragValue::new(width).range(1..=16384));
+                    ui.label("×");
+                    ui.add(egui::DragValue::new(height).range(1..=16384));
+                    let button = ui.add_enabled(sampled, egui::Button::new("Save"));
+                    if (button.on_hover_text("F12").clicked() || shoot) && sampled {
+                        self.take_screenshot();
+                    }
+                });
+                ui.separator();
+                ui.label("Bookmark");
+                ui.horizontal(|ui| {
+                    ui.label("File");
+                    ui.text_edit_singleline(&mut self.bookmark);
+                });
+                ui.horizontal(|ui| {
+                    let shortcut = |key| {
+                        ctx.format_shortcut(&egui::KeyboardShortcut::new(Modifiers::COMMAND, key))
+                    };
+                    if ui.button("Save").on_hover_text(shortcut(Key::S)).clicked() || save {
+                        self.save_bookmark();
+                    }
+                    if ui.button("Load").on_hover_text(shortcut(Key::O)).clicked() || load {
+                        self.load_bookmark();
+                    }
+                });
+                ui.separator();
+                match &self.lattice {
+                    Ok(lattice) => ui.label(format!("{} filled cells", lattice.count())),
+                    Err(e) => ui.colored_label(ui.visuals().error_fg_color, e),
+                };
+                if let Some(stream) = &self.stream {
+                    ui.label(match stream.received {
+                        Some(depth) => format!("refining: depth {depth} of {}", stream.depth),
+                        None => "sampling…".into(),
+                    });
+                }
+                if !self.status.is_empty() {
+                    ui.label(&self.status);
+                }
+            });
+        egui::CentralPanel::default()
+            .frame(egui::Frame::NONE.fill(ctx.style().visuals.extreme_bg_color))
+            .show(ctx, |ui| self.slice_ui(ui));
+    }
+
+    /// The render of the slice, orbited by dragging and zoomed by
+    /// scrolling.
+    fn slice_ui(&mut self, ui: &mut egui::Ui) {
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), egui::Sense::drag());
+        let delta = response.drag_delta();
+        self.orbit.yaw -= delta.x as f64 * 0.4;
+        self.orbit.pitch = (self.orbit.pitch + delta.y as f64 * 0.4).clamp(-89.0, 89.0);
+        if response.hovered() {
+            let scroll = ui.input(|i| i.smooth_scroll_delta.y) as f64;
+            self.orbit.distance = (self.orbit.distance * (-scroll * 0.002).exp()).clamp(1.0, 40.0);
+        }
+        // Render at half resolution while dragging to keep up.
+        let scale = ui.ctx().pixels_per_point() / if response.dragged() { 2.0 } else { 1.0 };
+        let size = [
+            (rect.width() * scale).round().max(1.0) as usize,
+            (rect.height() * scale).round().max(1.0) as usize,
+        ];
+        let current = self
+            .view
+            .as_ref()
+            .is_some_and(|view| view.size == size && view.orbit == self.orbit);
+        if !current {
+            if let Ok(lattice) = &self.lattice {
+                let mut scene = self.scene(size);
+                scene.passes.clear();
+                match render(lattice, None, &scene, &CancelToken::new()) {
+                    Ok(image) => {
+                        let pixels = image.srgb();
+                        let image = egui::ColorImage::from_rgb(size, pixels.as_flattened());
+                        let options = egui::TextureOptions::LINEAR;
+                        let texture = match self.view.take() {
+                            Some(mut view) => {
+                                view.texture.set(image, options);
+                                view.texture
+                            }
+                            None => ui.ctx().load_texture("slice", image, options),
+                        };
+                        self.view = Some(View {
+                            texture,
+                            size,
+                            orbit: self.orbit,
+                        });
+                    }
+                    Err(e) => self.status = e.to_string(),
+                }
+            }
+        }
+        if let Some(view) = &self.view {
+            egui::Image::new((view.texture.id(), rect.size())).paint_at(ui, rect);
+        }
+    }
+
+    /// The scene seen from the orbit, `size` pixels wide and high.
+    fn scene(&self, [width, height]: [usize; 2]) -> Scene {
+        Scene {
+            camera: self.orbit.camera(&self.scene.camera, width, height),
+            ..self.scene.clone()
+        }
+    }
+
+    /// Renders the current view at the screenshot size to the first free
+    /// `screenshot-NNN.png`, with any passes the scene asks for.
+    fn take_screenshot(&mut self) {
+        let Ok(lattice) = &self.lattice else {
+            return;
+        };
+        let path = (1..)
+            .map(|n| PathBuf::from(format!("screenshot-{n:03}.png")))
+            .find(|path| !path.exists())
+            .expect("a free screenshot name");
+        let scene = self.scene(self.screenshot);
+        let cancel = CancelToken::new();
+        self.status = match render(lattice, None, &scene, &cancel)
+            .and_then(|image| image.write(&scene.passes, &path))
+        {
+            Ok(artifacts) => format!("wrote {}", artifacts[0].path.display()),
+            Err(e) => format!("{}: {e}", path.display()),
+        };
+    }
+
+    /// Saves the slice and the orbit's camera, at the screenshot size, so
+    /// `render-bookmark` reproduces the screenshot.
+    fn save_bookmark(&mut self) {
+        let bookmark = Bookmark {
+            slice: self.explorer.clone(),
+            camera: self.scene(self.screenshot).camera,
+        };
+        let path = Path::new(&self.bookmark);
+        self.status = match bookmark.save(path) {
+            Ok(()) => format!("saved {}", path.display()),
+            Err(e) => format!("{}: {e}", path.display()),
+        };
+    }
+
+    /// Restores a saved slice and camera; the camera's target is taken to
+    /// be the origin the viewer orbits.
+    fn load_bookmark(&mut self) {
+        let path = PathBuf::from(&self.bookmark);
+        match Bookmark::load(&path) {
+            Ok(bookmark) => {
+                self.orbit = Orbit::new(&bookmark.camera);
+                self.screenshot = [bookmark.camera.width, bookmark.camera.height];
+                self.scene.camera = bookmark.camera;
+                self.explorer = bookmark.slice;
+                self.resample();
+                self.status = format!("loaded {}", path.display());
+            }
+            Err(e) => self.status = format!("{}: {e}", path.display()),
+        }
+    }
+
+    fn export(&mut self, extension: &str) {
+        let Ok(lattice) = &self.lattice else {
+            return;
+        };
+        let path = PathBuf::from(format!("{}.{extension}", self.export_stem));
+        self.status = match export(
+            lattice,
+            &path,
+            &ExportOptions::default(),
+            &CancelToken::new(),
+        ) {
+            Ok(artifact) => format!(
+                "wrote {} ({} bytes)",
+                artifact.path.display(),
+                artifact.bytes
+            ),
+            Err(e) => format!("{}: {e}", path.display()),
+        };
+    }
+
+    fn redraw(&mut self) -> Result<()> {
+        let Some(surface) = &mut self.window else {
+            return Ok(());
+        };
+        let size = surface.window.inner_size();
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        else {
+            return Ok(());
+        };
+        let scale = surface.window.scale_factor() as f32;
+        let raw = self.input.take(size, scale);
+        let ctx = self.egui.clone();
+        let output = ctx.run(raw, |ctx| self.ui(ctx));
+        let Some(surface) = &mut self.window else {
+            return Ok(());
+        };
+        let primitives = ctx.tessellate(output.shapes, output.pixels_per_point);
+        self.painter.set_textures(&output.textures_delta);
+        surface.surface.resize(width, height).map_err(other)?;
+        let mut buffer = surface.surface.buffer_mut().map_err(other)?;
+        buffer.fill(0);
+        self.painter.paint(
+            &primitives,
+            output.pixels_per_point,
+            &mut buffer,
+            size.width as usize,
+        );
+        buffer.present().map_err(other)?;
+        self.painter.free_textures(&output.textures_delta);
+        let repaint = output
+            .viewport_output
+            .get(&egui::ViewportId::ROOT)
+            .is_some_and(|viewport| viewport.repaint_delay.is_zero());
+        if repaint {
+            surface.window.request_redraw();
+        }
+        Ok(())
+    }
+}
+
+/// A windowing error, as I/O; softbuffer's hold raw handles that cannot
+/// cross threads.
+fn other(e: impl std::fmt::Display) -> Error {
+    std::io::Error::other(e.to_string()).into()
+}
+
+impl ApplicationHandler for Viewer {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        let attributes = Window::default_attributes()
+            .with_title("fractal-slicer")
+            .with_inner_size(LogicalSize::new(1280.0, 800.0));
+        let opened = event_loop
+            .create_window(attributes)
+            .map_err(other)
+            .and_then(|window| {
+                let window = Rc::new(window);
+                let context = softbuffer::Context::new(window.clone()).map_err(other)?;
+                let surface = softbuffer::Surface::new(&context, window.clone()).map_err(other)?;
+                Ok(Surface { window, surface })
+            });
+        match opened {
+            Ok(surface) => self.window = Some(surface),
+            Err(e) => {
+                self.error = Some(e);
+                event_loop.exit();
+            }
+        }
+    }
+
+    /// A level of the slice has been sampled, or the scene file changed.
+    fn user_event(&mut self, _: &ActiveEventLoop, (): ()) {
+        if let Some(surface) = &self.window {
+            surface.window.request_redraw();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let Some(surface) = &self.window else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = self.redraw() {
+                    self.error = Some(e);
+                    event_loop.exit();
+                }
+            }
+            event => {
+                self.input
+                    .push(&event, surface.window.scale_factor() as f32);
+                surface.window.request_redraw();
+            }
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::render::{ClipPlane, Clipping, Cutaway};
+
+/// How far past the lattice, which spans -1 to 1, cuts may be moved.
+const REACH: f64 = 1.2;
+
+impl Clipping {
+    /// Controls for the planes and the cutaway, returning whether one
+    /// changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let before = self.clone();
+        let mut removed = None;
+        for (i, plane) in self.planes.iter_mut().enumerate() {
+            ui.push_id(i, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Normal");
+                    for c in &mut plane.normal {
+                        ui.add(egui::DragValue::new(c).speed(0.01).range(-1.0..=1.0));
+                    }
+                    if ui.button("Remove").clicked() {
+                        removed = Some(i);
+                    }
+                });
+                ui.add(egui::Slider::new(&mut plane.offset, -REACH..=REACH).text("Offset"));
+            });
+        }
+        if let Some(i) = removed {
+            self.planes.remove(i);
+        }
+        if ui.button("Add plane").clicked() {
+            self.planes.push(ClipPlane {
+                normal: [0.0, 0.0, 1.0],
+                offset: 0.0,
+            });
+        }
+        let mut cutaway = self.cutaway.is_some();
+        ui.checkbox(&mut cutaway, "Cutaway box");
+        match (cutaway, &mut self.cutaway) {
+            (true, Some(cutaway)) => {
+                for (a, axis) in ["x", "y", "z"].into_iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(axis);
+                        let [min, max] = [&mut cutaway.min[a], &mut cutaway.max[a]];
+                        ui.add(egui::DragValue::new(min).speed(0.01).range(-REACH..=*max));
+                        ui.add(egui::DragValue::new(max).speed(0.01).range(*min..=REACH));
+                    });
+                }
+            }
+            // The octant facing the default camera.
+            (true, None) => {
+                self.cutaway = Some(Cutaway {
+                    min: [0.0, -REACH, 0.0],
+                    max: [REACH, 0.0, REACH],
+                })
+            }
+            (false, _) => self.cutaway = None,
+        }
+        *self != before
+    }
+}
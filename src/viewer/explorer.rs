@@ -0,0 +1,42 @@
+use crate::rule::Rule;
+use crate::slice::Explorer;
+
+/// Deepest level the depth slider reaches; deeper sponges need more
+/// resolution than a live view can sample.
+const MAX_DEPTH: u32 = 5;
+const MAX_RESOLUTION: usize = 243;
+
+impl Explorer {
+    /// Controls for every parameter, returning whether one changed.
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let before = self.clone();
+        egui::ComboBox::from_label("Rule")
+            .selected_text(&self.fractal)
+            .show_ui(ui, |ui| {
+                for &name in Rule::NAMES {
+                    ui.selectable_value(&mut self.fractal, name.to_string(), name);
+                }
+            });
+        ui.add(egui::Slider::new(&mut self.depth, 0..=MAX_DEPTH).text("Depth"));
+        ui.add(
+            egui::Slider::new(&mut self.resolution, 8..=MAX_RESOLUTION)
+                .logarithmic(true)
+                .text("Resolution"),
+        );
+        ui.separator();
+        ui.label("Slice");
+        let hyperplane = &mut self.hyperplane;
+        ui.add(egui::Slider::new(&mut hyperplane.offset, 0.0..=1.0).text("w offset"));
+        for (angle, plane) in hyperplane.angles.iter_mut().zip(["xw", "yw", "zw"]) {
+            ui.add(
+                egui::Slider::new(angle, -180.0..=180.0)
+                    .suffix("°")
+                    .text(plane),
+            );
+        }
+        if ui.button("Reset rotation").clicked() {
+            hyperplane.angles = [0.0; 3];
+        }
+        *self != before
+    }
+}
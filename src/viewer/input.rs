@@ -0,0 +1,131 @@
+use std::time::Instant;
+
+use egui::{pos2, vec2, Event, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput};
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{Key, ModifiersState};
+
+/// Collects window events as egui input between frames.
+pub(super) struct Input {
+    events: Vec<Event>,
+    pointer: Pos2,
+    modifiers: Modifiers,
+    start: Instant,
+}
+
+impl Input {
+    pub(super) fn new() -> Self {
+        Input {
+            events: Vec::new(),
+            pointer: Pos2::ZERO,
+            modifiers: Modifiers::default(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Records `event`, with positions in points of `scale` pixels.
+    pub(super) fn push(&mut self, event: &WindowEvent, scale: f32) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer = pos2(position.x as f32 / scale, position.y as f32 / scale);
+                self.events.push(Event::PointerMoved(self.pointer));
+            }
+            WindowEvent::CursorLeft { .. } => self.events.push(Event::PointerGone),
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => PointerButton::Primary,
+                    MouseButton::Right => PointerButton::Secondary,
+                    MouseButton::Middle => PointerButton::Middle,
+                    MouseButton::Back => PointerButton::Extra1,
+                    MouseButton::Forward => PointerButton::Extra2,
+                    MouseButton::Other(_) => return,
+                };
+                self.events.push(Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (MouseWheelUnit::Line, vec2(*x, *y)),
+                    MouseScrollDelta::PixelDelta(p) => (
+                        MouseWheelUnit::Point,
+                        vec2(p.x as f32 / scale, p.y as f32 / scale),
+                    ),
+                };
+                self.events.push(Event::MouseWheel {
+                    unit,
+                    delta,
+                    modifiers: self.modifiers,
+                });
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                self.modifiers = Modifiers {
+                    alt: state.contains(ModifiersState::ALT),
+                    ctrl: state.contains(ModifiersState::CONTROL),
+                    shift: state.contains(ModifiersState::SHIFT),
+                    mac_cmd: cfg!(target_os = "macos") && state.contains(ModifiersState::SUPER),
+                    command: if cfg!(target_os = "macos") {
+                        state.contains(ModifiersState::SUPER)
+                    } else {
+                        state.contains(ModifiersState::CONTROL)
+                    },
+                };
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == ElementState::Pressed;
+                if let Some(key) = key(&event.logical_key) {
+                    self.events.push(Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed,
+                        repeat: false,
+                        modifiers: self.modifiers,
+                    });
+                }
+                // Shortcuts and control characters are not typed text.
+                let text = event.text.as_deref().filter(|text| {
+                    pressed && !self.modifiers.command && !text.chars().any(char::is_control)
+                });
+                if let Some(text) = text {
+                    self.events.push(Event::Text(text.to_string()));
+                }
+            }
+            WindowEvent::Focused(focused) => self.events.push(Event::WindowFocused(*focused)),
+            _ => {}
+        }
+    }
+
+    /// The input gathered since the last frame, for a window of `size`
+    /// pixels at `scale` pixels per point.
+    pub(super) fn take(&mut self, size: PhysicalSize<u32>, scale: f32) -> RawInput {
+        let mut raw = RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                Pos2::ZERO,
+                vec2(size.width as f32, size.height as f32) / scale,
+            )),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: true,
+            ..RawInput::default()
+        };
+        raw.viewports
+            .entry(raw.viewport_id)
+            .or_default()
+            .native_pixels_per_point = Some(scale);
+        raw
+    }
+}
+
+/// The egui key for a winit key, where egui has one.
+fn key(key: &Key) -> Option<egui::Key> {
+    match key {
+        Key::Named(named) => egui::Key::from_name(&format!("{named:?}")),
+        Key::Character(text) => egui::Key::from_name(text),
+        _ => None,
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use egui::epaint::{ClippedPrimitive, ImageData, Primitive, TextureId, Vertex};
+use egui::{Color32, TextureFilter, TexturesDelta};
+
+/// Rasterizes egui's triangles into a frame of `0x00RRGGBB` pixels, so the
+/// viewer needs no GPU.
+///
+/// Colors are blended as egui gives them, premultiplied in sRGB.
+pub(super) struct Painter {
+    textures: HashMap<TextureId, Texture>,
+}
+
+struct Texture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color32>,
+    filter: TextureFilter,
+}
+
+impl Painter {
+    pub(super) fn new() -> Self {
+        Painter {
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Uploads new textures and patches; call before painting.
+    pub(super) fn set_textures(&mut self, delta: &TexturesDelta) {
+        for (id, delta) in &delta.set {
+            let ImageData::Color(image) = &delta.image;
+            let [width, height] = image.size;
+            let filter = delta.options.magnification;
+            match delta.pos {
+                Some([left, top]) => {
+                    let Some(texture) = self.textures.get_mut(id) else {
+                        continue;
+                    };
+                    for (y, row) in image.pixels.chunks(width.max(1)).enumerate() {
+                        let start = (top + y) * texture.width + left;
+                        texture.pixels[start..start + row.len()].copy_from_slice(row);
+                    }
+                }
+                None => {
+                    let texture = Texture {
+                        width,
+                        height,
+                        pixels: image.pixels.clone(),
+                        filter,
+                    };
+                    self.textures.insert(*id, texture);
+                }
+            }
+        }
+    }
+
+    /// Drops textures egui no longer uses; call after painting.
+    pub(super) fn free_textures(&mut self, delta: &TexturesDelta) {
+        for id in &delta.free {
+            self.textures.remove(id);
+        }
+    }
+
+    /// Draws `primitives` over `frame`, `width` pixels wide, at
+    /// `pixels_per_point`.
+    pub(super) fn paint(
+        &self,
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+        frame: &mut [u32],
+        width: usize,
+    ) {
+        let height = frame.len() / width.max(1);
+        for primitive in primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            let Some(texture) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let clip = primitive.clip_rect * pixels_per_point;
+            let clip = [
+                clip.min.x.round().max(0.0) as usize,
+                clip.min.y.round().max(0.0) as usize,
+                (clip.max.x.round().max(0.0) as usize).min(width),
+                (clip.max.y.round().max(0.0) as usize).min(height),
+            ];
+            for triangle in mesh.indices.chunks_exact(3) {
+                let corners = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+                fill(&corners, texture, pixels_per_point, clip, frame, width);
+            }
+        }
+    }
+}
+
+/// Fills one triangle, sampling at pixel centres inside `clip`, given as
+/// left, top, right and bottom pixel bounds.
+fn fill(
+    corners: &[Vertex; 3],
+    texture: &Texture,
+    pixels_per_point: f32,
+    clip: [usize; 4],
+    frame: &mut [u32],
+    width: usize,
+) {
+    let p = corners.map(|v| [v.pos.x * pixels_per_point, v.pos.y * pixels_per_point]);
+    let edge = |a: [f32; 2], b: [f32; 2], x: f32, y: f32| {
+        (b[0] - a[0]) * (y - a[1]) - (b[1] - a[1]) * (x - a[0])
+    };
+    let area = edge(p[0], p[1], p[2][0], p[2][1]);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+    let low = |a: usize| p.iter().map(|q| q[a]).fold(f32::INFINITY, f32::min);
+    let high = |a: usize| p.iter().map(|q| q[a]).fold(f32::NEG_INFINITY, f32::max);
+    let left = (low(0).floor().max(0.0) as usize).max(clip[0]);
+    let top = (low(1).floor().max(0.0) as usize).max(clip[1]);
+    let right = (high(0).ceil().max(0.0) as usize).min(clip[2]);
+    let bottom = (high(1).ceil().max(0.0) as usize).min(clip[3]);
+    let colors = corners.map(|v| v.color.to_array().map(f32::from));
+    for y in top..bottom {
+        let cy = y as f32 + 0.5;
+        for x in left..right {
+            let cx = x as f32 + 0.5;
+            let weights = [
+                edge(p[1], p[2], cx, cy) / area,
+                edge(p[2], p[0], cx, cy) / area,
+                edge(p[0], p[1], cx, cy) / area,
+            ];
+            if weights.iter().any(|&w| w < 0.0) {
+                continue;
+            }
+            let blend = |f: &dyn Fn(usize) -> f32| (0..3).map(|i| weights[i] * f(i)).sum::<f32>();
+            let u = blend(&|i| corners[i].uv.x);
+            let v = blend(&|i| corners[i].uv.y);
+            let texel = texture.sample(u, v);
+            let src: [f32; 4] =
+                std::array::from_fn(|c| blend(&|i| colors[i][c]) * texel[c] / 255.0);
+            let pixel = &mut frame[y * width + x];
+            let dst = [(*pixel >> 16) & 0xff, (*pixel >> 8) & 0xff, *pixel & 0xff];
+            let keep = 1.0 - src[3] / 255.0;
+            let out: [u32; 3] =
+                std::array::from_fn(|c| (src[c] + dst[c] as f32 * keep).round().min(255.0) as u32);
+            *pixel = out[0] << 16 | out[1] << 8 | out[2];
+        }
+    }
+}
+
+impl Texture {
+    /// The premultiplied color at normalized coordinates (`u`, `v`).
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let texel = |x: i64, y: i64| {
+            let x = x.clamp(0, self.width as i64 - 1) as usize;
+            let y = y.clamp(0, self.height as i64 - 1) as usize;
+            self.pixels[y * self.width + x].to_array().map(f32::from)
+        };
+        if self.filter == TextureFilter::Nearest {
+            return texel(x.round() as i64, y.round() as i64);
+        }
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let [a, b, c, d] = [
+            texel(x0, y0),
+            texel(x0 + 1, y0),
+            texel(x0, y0 + 1),
+            texel(x0 + 1, y0 + 1),
+        ];
+        std::array::from_fn(|i| {
+            let top = a[i] + (b[i] - a[i]) * fx;
+            let bottom = c[i] + (d[i] - c[i]) * fx;
+            top + (bottom - top) * fy
+        })
+    }
+}
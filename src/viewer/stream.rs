@@ -0,0 +1,100 @@
+use std::sync::mpsc::{channel, Receiver};
+
+use crate::cancel::CancelToken;
+use crate::error::{Error, Result};
+use crate::lattice::{Boundary, Lattice3};
+use crate::mesh::{build_indexed_mesh, FaceKind, Mesh};
+use crate::rule::Rule;
+use crate::slice::Explorer;
+
+/// One level of a slice, and its surface when the stream makes them.
+pub(super) struct Sample {
+    pub(super) lattice: Lattice3,
+    pub(super) mesh: Option<Mesh>,
+}
+
+/// Samples an exploration's slice on a background thread one depth at a
+/// time, coarsest first, so the viewer has something to show at once and
+/// refines it as deeper levels finish.
+pub(super) struct Stream {
+    receiver: Receiver<(u32, Result<Sample>)>,
+    cancel: CancelToken,
+    /// The depth the exploration asks for, the last to arrive.
+    pub(super) depth: u32,
+    /// The deepest level polled so far.
+    pub(super) received: Option<u32>,
+}
+
+impl Stream {
+    /// Starts sampling `explorer`'s slice, and meshing each level's
+    /// surface if `meshes` is set, calling `wake` from the thread after
+    /// each level.
+    pub(super) fn start(
+        explorer: &Explorer,
+        meshes: bool,
+        wake: impl Fn() + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = channel();
+        let cancel = CancelToken::new();
+        let explorer = explorer.clone();
+        let depth = explorer.depth;
+        let token = cancel.clone();
+        std::thread::spawn(move || {
+            for level in depth.min(1)..=depth {
+                let sample = preview(&explorer, level, &token).map(|lattice| Sample {
+                    mesh: meshes
+                        .then(|| build_indexed_mesh(&lattice, FaceKind::Triangles, Boundary::Open)),
+                    lattice,
+                });
+                let failed = sample.is_err();
+                if token.is_cancelled() || sender.send((level, sample)).is_err() {
+                    return;
+                }
+                wake();
+                if failed {
+                    return;
+                }
+            }
+        });
+        Stream {
+            receiver,
+            cancel,
+            depth,
+            received: None,
+        }
+    }
+
+    /// The deepest level finished since the last call, if any.
+    pub(super) fn poll(&mut self) -> Option<(u32, Result<Sample>)> {
+        let level = self.receiver.try_iter().last()?;
+        self.received = Some(level.0);
+        Some(level)
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+/// The slice `explorer` describes cut off at `depth`, sampled no finer
+/// than that depth's cells, which coarse levels need far fewer of.
+fn preview(explorer: &Explorer, depth: u32, cancel: &CancelToken) -> Result<Lattice3> {
+    let rule = Rule::by_name(&explorer.fractal, 4)
+        .ok_or_else(|| Error::UnknownFractal(explorer.fractal.clone()))?;
+    let side = (0..4)
+        .map(|axis| rule.side_along(axis, depth))
+        .max()
+        .unwrap_or(1);
+    let explorer = Explorer {
+        depth,
+        resolution: if depth == explorer.depth {
+            explorer.resolution
+        } else {
+            explorer.resolution.min(side)
+        },
+        ..explorer.clone()
+    };
+    explorer.lattice(cancel)
+}
@@ -0,0 +1,147 @@
+use crate::mesh::Mesh;
+use crate::slice::Explorer;
+
+use super::stream::Stream;
+
+/// A slice's surface, regenerated after its parameters changed.
+#[derive(Clone, Debug)]
+pub struct MeshUpdate {
+    /// The depth sampled; coarser levels arrive first.
+    pub depth: u32,
+    /// Whether this is the depth asked for, after which no more arrive
+    /// until the parameters change again.
+    pub complete: bool,
+    /// Filled cells of the slice.
+    pub cells: usize,
+    /// Triangles in cell units, the slice's lattice from the origin to its
+    /// resolution along each axis.
+    pub mesh: Mesh,
+}
+
+/// The viewer's exploration controls as a widget for other egui apps:
+/// rule, depth, resolution and the slicing hyperplane, with a status line.
+/// Changing them regenerates the slice on a background thread, coarsest
+/// level first, and each level's mesh is handed to the
+/// [`FractalExplorerWidget::on_mesh`] callbacks as it arrives.
+///
+/// ```no_run
+/// # use fractal_slicer_4_d::slice::Explorer;
+/// # use fractal_slicer_4_d::viewer::FractalExplorerWidget;
+/// # fn app(ui: &mut egui::Ui, upload: impl Fn(&fractal_slicer_4_d::mesh::Mesh) + 'static) {
+/// let mut explorer = FractalExplorerWidget::new(Explorer::default())
+///     .on_mesh(move |update| upload(&update.mesh));
+/// ui.add(&mut explorer);
+/// # }
+/// ```
+pub struct FractalExplorerWidget {
+    explorer: Explorer,
+    stream: Option<Stream>,
+    /// Whether the parameters changed since the stream started, or no
+    /// stream has been started yet.
+    stale: bool,
+    /// Filled cells of the latest level, or why it could not be sampled.
+    latest: Result<usize, String>,
+    callbacks: Vec<Callback>,
+}
+
+type Callback = Box<dyn FnMut(&MeshUpdate)>;
+
+impl FractalExplorerWidget {
+    pub fn new(explorer: Explorer) -> Self {
+        FractalExplorerWidget {
+            explorer,
+            stream: None,
+            stale: true,
+            latest: Err(String::new()),
+            callbacks: Vec::new(),
+        }
+    }
+
+    /// Adds a callback called on the UI thread with each regenerated mesh.
+    pub fn on_mesh(mut self, callback: impl FnMut(&MeshUpdate) + 'static) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    pub fn explorer(&self) -> &Explorer {
+        &self.explorer
+    }
+
+    /// Replaces the parameters, regenerating the slice at the next frame.
+    pub fn set_explorer(&mut self, explorer: Explorer) {
+        self.explorer = explorer;
+        self.stale = true;
+    }
+
+    /// Whether deeper levels of the slice are still being sampled.
+    pub fn is_refining(&self) -> bool {
+        self.stale || self.stream.is_some()
+    }
+
+    /// Draws the controls and hands on any level sampled since the last
+    /// frame, returning whether a parameter changed.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> bool {
+        self.deliver();
+        let changed = self.explorer.ui(ui);
+        if changed || self.stale {
+            let ctx = ui.ctx().clone();
+            self.stream = Some(Stream::start(&self.explorer, true, move || {
+                ctx.request_repaint()
+            }));
+            self.stale = false;
+        }
+        ui.separator();
+        match &self.latest {
+            Ok(cells) => ui.label(format!("{cells} filled cells")),
+            Err(e) => ui.colored_label(ui.visuals().error_fg_color, e),
+        };
+        if let Some(stream) = &self.stream {
+            ui.label(match stream.received {
+                Some(depth) => format!("refining: depth {depth} of {}", stream.depth),
+                None => "sampling…".into(),
+            });
+        }
+        changed
+    }
+
+    /// Passes the deepest level sampled since the last frame to the
+    /// callbacks.
+    fn deliver(&mut self) {
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        let Some((depth, sample)) = stream.poll() else {
+            return;
+        };
+        let complete = depth == stream.depth;
+        if complete || sample.is_err() {
+            self.stream = None;
+        }
+        match sample {
+            Ok(sample) => {
+                let update = MeshUpdate {
+                    depth,
+                    complete,
+                    cells: sample.lattice.count(),
+                    mesh: sample.mesh.expect("the widget's stream makes meshes"),
+                };
+                self.latest = Ok(update.cells);
+                for callback in &mut self.callbacks {
+                    callback(&update);
+                }
+            }
+            Err(e) => self.latest = Err(e.to_string()),
+        }
+    }
+}
+
+impl egui::Widget for &mut FractalExplorerWidget {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let inner = ui.vertical(|ui| self.show(ui));
+        let mut response = inner.response;
+        if inner.inner {
+            response.mark_changed();
+        }
+        response
+    }
+}
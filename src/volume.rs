@@ -0,0 +1,155 @@
+//! Memory-mapped reading of NIfTI volumes written by `--output x.nii`, for
+//! analyzing volumes larger than memory: the file is mapped rather than
+//! read, and the operating system pages in only the layers a query or
+//! region touches.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use memmap2::Advice;
+use memmap2::Mmap;
+
+use crate::error::{Error, Result};
+use crate::lattice::Lattice3;
+
+/// How a mapped volume stores its cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Values {
+    /// A byte per cell, nonzero when filled.
+    Occupancy,
+    /// An `f32` signed distance per cell, filled at or below zero.
+    Distance,
+}
+
+impl Values {
+    fn width(self) -> usize {
+        match self {
+            Values::Occupancy => 1,
+            Values::Distance => 4,
+        }
+    }
+}
+
+/// A NIfTI-1 volume mapped into memory, x fastest.
+pub struct MappedVolume {
+    path: PathBuf,
+    map: Mmap,
+    shape: [usize; 3],
+    values: Values,
+    /// Byte offset of the first cell.
+    offset: usize,
+}
+
+impl MappedVolume {
+    /// Maps the single-file NIfTI volume at `path`, holding occupancy
+    /// bytes or `f32` distances.
+    pub fn open(path: &Path) -> Result<Self> {
+        let invalid =
+            |reason: &str| Error::InvalidJob(format!("cannot read `{}`: {reason}", path.display()));
+        let file = File::open(path)?;
+        // SAFETY: the map is only read, and a file truncated or changed
+        // while mapped is outside what this type promises, as for any
+        // memory-mapped reader.
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < 352 || map[..4] != 348i32.to_le_bytes() || &map[344..348] != b"n+1\0" {
+            return Err(invalid("not a single-file NIfTI-1 volume"));
+        }
+        let short = |offset: usize| i16::from_le_bytes([map[offset], map[offset + 1]]);
+        if !(3..=4).contains(&short(40))
+            || short(40) == 4 && short(48) != 1
+            || [42, 44, 46].iter().any(|&offset| short(offset) < 0)
+        {
+            return Err(invalid("expected a 3D volume"));
+        }
+        let shape = [42, 44, 46].map(|offset| short(offset) as usize);
+        let values = match short(70) {
+            2 => Values::Occupancy,
+            16 => Values::Distance,
+            _ => return Err(invalid("expected uint8 occupancy or float32 distances")),
+        };
+        let offset = f32::from_le_bytes(map[108..112].try_into().unwrap()) as usize;
+        let len = shape.iter().product::<usize>() * values.width();
+        if offset < 352 || offset.checked_add(len).is_none_or(|end| map.len() < end) {
+            return Err(invalid("file is shorter than its header says"));
+        }
+        Ok(MappedVolume {
+            path: path.to_path_buf(),
+            map,
+            shape,
+            values,
+            offset,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn shape(&self) -> [usize; 3] {
+        self.shape
+    }
+
+    pub fn values(&self) -> Values {
+        self.values
+    }
+
+    /// Whether cell `p` is filled; cells outside the volume are empty.
+    pub fn get(&self, [x, y, z]: [usize; 3]) -> bool {
+        let [nx, ny, nz] = self.shape;
+        if x >= nx || y >= ny || z >= nz {
+            return false;
+        }
+        self.filled(self.cells((z * ny + y) * nx + x, 1))
+    }
+
+    /// The bytes of `count` cells from flat index `start`.
+    fn cells(&self, start: usize, count: usize) -> &[u8] {
+        let width = self.values.width();
+        &self.map[self.offset + start * width..self.offset + (start + count) * width]
+    }
+
+    fn filled(&self, cell: &[u8]) -> bool {
+        match self.values {
+            Values::Occupancy => cell[0] != 0,
+            Values::Distance => f32::from_le_bytes(cell.try_into().unwrap()) <= 0.0,
+        }
+    }
+
+    /// The cells from `low` up to but not including `high`, clamped to
+    /// the volume, copied into a lattice. Only the rows of the region are
+    /// paged in.
+    pub fn region(&self, low: [usize; 3], high: [usize; 3]) -> Lattice3 {
+        let [nx, ny, _] = self.shape;
+        let high: [usize; 3] = std::array::from_fn(|a| high[a].min(self.shape[a]));
+        let low: [usize; 3] = std::array::from_fn(|a| low[a].min(high[a]));
+        let mut lattice = Lattice3::new(std::array::from_fn(|a| high[a] - low[a]));
+        let width = self.values.width();
+        for z in low[2]..high[2] {
+            for y in low[1]..high[1] {
+                let row = self.cells((z * ny + y) * nx + low[0], high[0] - low[0]);
+                for (i, cell) in row.chunks_exact(width).enumerate() {
+                    if self.filled(cell) {
+                        lattice.set([i, y - low[1], z - low[2]], true);
+                    }
+                }
+            }
+        }
+        lattice
+    }
+
+    /// Layer `z`, one cell deep.
+    pub fn layer(&self, z: usize) -> Lattice3 {
+        let [nx, ny, _] = self.shape;
+        self.region([0, 0, z], [nx, ny, z + 1])
+    }
+
+    /// The whole volume as a lattice, an eighth of a byte per cell, read
+    /// front to back.
+    pub fn to_lattice(&self) -> Lattice3 {
+        // Only a hint; a kernel that ignores it pages in the same cells.
+        #[cfg(unix)]
+        let _ = self.map.advise(Advice::Sequential);
+        self.region([0; 3], self.shape)
+    }
+}
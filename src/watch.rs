@@ -0,0 +1,88 @@
+//! Waiting for files to change, so commands can rerun as their rules,
+//! scripts, scenes and models are edited.
+//!
+//! Files are polled for their modification time and size rather than
+//! watched through the operating system, which misses changes on network
+//! and container mounts and to files editors replace rather than rewrite.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::cancel::CancelToken;
+use crate::error::Result;
+
+/// How often files are polled.
+const INTERVAL: Duration = Duration::from_millis(200);
+
+/// A file's modification time and size, or None while it does not exist.
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Files watched for changes since they were last seen.
+#[derive(Clone, Debug, Default)]
+pub struct Watch {
+    files: Vec<(PathBuf, Stamp)>,
+}
+
+impl Watch {
+    /// Watches `files` for changes from how they are now.
+    pub fn new(files: impl IntoIterator<Item = PathBuf>) -> Self {
+        let mut files: Vec<PathBuf> = files.into_iter().collect();
+        files.sort();
+        files.dedup();
+        Watch {
+            files: files
+                .into_iter()
+                .map(|path| {
+                    let stamp = stamp(&path);
+                    (path, stamp)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The files that changed, were created or were removed since they
+    /// were last seen, marking them seen.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for (path, seen) in &mut self.files {
+            let now = stamp(path);
+            if now != *seen {
+                *seen = now;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    /// Blocks until some files change, and returns them once they have
+    /// stopped changing for a poll, so a file still being saved is not
+    /// read half written; [`Error::Cancelled`](crate::error::Error::Cancelled)
+    /// when `cancel` is cancelled first.
+    pub fn wait(&mut self, cancel: &CancelToken) -> Result<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+        loop {
+            cancel.check()?;
+            std::thread::sleep(INTERVAL);
+            let more = self.changed();
+            if more.is_empty() && !changed.is_empty() {
+                changed.sort();
+                changed.dedup();
+                return Ok(changed);
+            }
+            changed.extend(more);
+        }
+    }
+}
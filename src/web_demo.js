@@ -0,0 +1,33 @@
+// Glue for fractal-slicer web demos, written by `fractal-slicer web-demo`:
+// loads fractal_slicer_web.wasm and the bundle's rule.bin, and meshes the
+// rule's 3D slices for the page.
+
+/** Loads the module and rule from `base`, the bundle's folder. */
+export async function load(base = '.') {
+  const [{ instance }, rule] = await Promise.all([
+    WebAssembly.instantiateStreaming(fetch(`${base}/fractal_slicer_web.wasm`)),
+    fetch(`${base}/rule.bin`).then((response) => response.arrayBuffer()),
+  ]);
+  const fs = instance.exports;
+  const bytes = new Uint8Array(rule);
+  new Uint8Array(fs.memory.buffer, fs.fs_rule_buffer(bytes.length), bytes.length).set(bytes);
+  if (fs.fs_load_rule() !== 0) {
+    throw new Error('rule.bin is not a rule this module reads');
+  }
+  return {
+    /** The depth-`depth` lattice's side along `axis`, 0 to 3. */
+    side: (axis, depth) => fs.fs_side(axis, depth),
+    /** The triangles of the slice at `w`: positions and normals, three floats a vertex. */
+    slice(depth, w) {
+      const vertices = fs.fs_slice(depth, w);
+      if (vertices < 0) {
+        throw new Error(`no slice at w=${w} of depth ${depth}`);
+      }
+      // Views are taken after slicing, which may have grown the memory,
+      // and copied before the next slice replaces them.
+      const floats = (pointer) =>
+        new Float32Array(fs.memory.buffer, pointer, vertices * 3).slice();
+      return { positions: floats(fs.fs_positions()), normals: floats(fs.fs_normals()) };
+    },
+  };
+}
@@ -0,0 +1,136 @@
+//! Static web demo bundles: a folder with the slicer's WebAssembly module,
+//! the rule it reads, JavaScript glue and a page with a WebGL viewer and
+//! a w-slider, so an interactive explorer of a custom rule's 4D slices
+//! can be published on any static host, such as GitHub Pages. Everything
+//! the page loads is in the folder, so it also works offline.
+//!
+//! The module is the workspace's `web` crate built for
+//! `wasm32-unknown-unknown`. [`build_wasm`] builds it from the sources the
+//! slicer was built from, which needs that target installed and those
+//! sources still in place, so an installed slicer is given a module built
+//! before; a module built once can be reused for every rule.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{Error, Result};
+use crate::rule::{Rule, Split};
+
+/// The page, with `@…@` placeholders filled by [`write`].
+const PAGE: &str = include_str!("web_demo.html");
+
+/// The glue between the page and the module.
+const GLUE: &str = include_str!("web_demo.js");
+
+/// The page's WebGL viewer.
+const VIEWER: &str = include_str!("web_demo_viewer.js");
+
+/// The module's file name in the bundle and in Cargo's output.
+const WASM: &str = "fractal_slicer_web.wasm";
+
+/// Most cells a slice may have, as the module limits them.
+const MAX_SLICE_CELLS: u64 = 1 << 22;
+
+/// The rule as the module reads it: little-endian words of the magic
+/// `FSR1`, the number of axes, the split, the number of keep-masks, the
+/// bases and each keep-mask as a bitset.
+pub fn rule_bytes(rule: &Rule) -> Vec<u8> {
+    let split = match rule.split() {
+        Split::Uniform => 0,
+        Split::Pell => 1,
+        Split::Flake => 2,
+    };
+    let mut words = vec![
+        u32::from_le_bytes(*b"FSR1"),
+        rule.dims() as u32,
+        split,
+        rule.period() as u32,
+    ];
+    words.extend(rule.bases());
+    for level in 1..=rule.period() as u32 {
+        let mask = rule.mask(level);
+        let mut bits = vec![0u32; mask.len().div_ceil(32)];
+        for (i, _) in mask.iter().enumerate().filter(|(_, &keep)| keep) {
+            bits[i / 32] |= 1 << (i % 32);
+        }
+        words.extend(bits);
+    }
+    words.into_iter().flat_map(u32::to_le_bytes).collect()
+}
+
+/// Builds the module with Cargo from the slicer's sources, returning the
+/// path of the `.wasm` file.
+pub fn build_wasm() -> Result<PathBuf> {
+    let sources = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let manifest = sources.join("web").join("Cargo.toml");
+    if !manifest.exists() {
+        return Err(Error::InvalidJob(format!(
+            "the web module's sources are not at `{}`; pass a built module with --wasm",
+            manifest.display()
+        )));
+    }
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(["build", "--release", "--target", "wasm32-unknown-unknown"])
+        .arg("--manifest-path")
+        .arg(&manifest)
+        .status()?;
+    if !status.success() {
+        return Err(Error::InvalidJob(
+            "building the web module failed; it needs \
+             `rustup target add wasm32-unknown-unknown`"
+                .into(),
+        ));
+    }
+    // Cargo puts the module in the workspace's target directory.
+    let target =
+        std::env::var_os("CARGO_TARGET_DIR").map_or_else(|| sources.join("target"), PathBuf::from);
+    Ok(target
+        .join("wasm32-unknown-unknown")
+        .join("release")
+        .join(WASM))
+}
+
+/// Writes a bundle exploring the slices of the 4D `rule` up to `depth`
+/// into `dir`, with the module at `wasm`.
+pub fn write(rule: &Rule, depth: u32, wasm: &Path, dir: &Path) -> Result<()> {
+    if rule.dims() != 4 {
+        return Err(Error::InvalidJob(format!(
+            "web demos explore 4D rules; the rule has {} axes",
+            rule.dims()
+        )));
+    }
+    let cells = (0..3)
+        .map(|axis| rule.side_along(axis, depth) as u64)
+        .fold(1, u64::saturating_mul);
+    if cells > MAX_SLICE_CELLS {
+        return Err(Error::InvalidJob(format!(
+            "depth-{depth} slices have {cells} cells, more than the {MAX_SLICE_CELLS} \
+             a browser is given"
+        )));
+    }
+    let settings = serde_json::json!({
+        "name": rule.name(),
+        "bases": rule.bases(),
+        "depth": depth,
+        "version": env!("CARGO_PKG_VERSION"),
+    });
+    let page = PAGE
+        .replace("@TITLE@", &html_escape(rule.name()))
+        // Escaped so no rule name can close the page's script.
+        .replace("@SETTINGS@", &settings.to_string().replace('<', "\\u003c"));
+    std::fs::create_dir_all(dir)?;
+    std::fs::copy(wasm, dir.join(WASM))
+        .map_err(|e| Error::InvalidJob(format!("{}: {e}", wasm.display())))?;
+    std::fs::write(dir.join("rule.bin"), rule_bytes(rule))?;
+    std::fs::write(dir.join("fractal-slicer.js"), GLUE)?;
+    std::fs::write(dir.join("viewer.js"), VIEWER)?;
+    std::fs::write(dir.join("index.html"), page)?;
+    Ok(())
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
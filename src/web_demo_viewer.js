@@ -0,0 +1,202 @@
+// The viewer of fractal-slicer web demos, written by `fractal-slicer
+// web-demo`: plain WebGL drawing one lit mesh under a camera that orbits
+// the origin, so the bundle loads nothing from other hosts.
+
+const VERTEX = `
+  attribute vec3 position;
+  attribute vec3 normal;
+  uniform mat4 projection;
+  uniform mat4 view;
+  uniform vec3 offset;
+  uniform float scale;
+  varying vec3 vNormal;
+  void main() {
+    vNormal = normal;
+    gl_Position = projection * view * vec4((position + offset) * scale, 1.0);
+  }
+`;
+
+// A hemisphere light, white sky over a blue-grey ground, and a sun.
+const FRAGMENT = `
+  precision mediump float;
+  uniform vec3 color;
+  uniform vec3 sun;
+  varying vec3 vNormal;
+  void main() {
+    vec3 n = normalize(vNormal);
+    vec3 sky = mix(vec3(0.2, 0.27, 0.33), vec3(1.0), 0.5 + 0.5 * n.y);
+    float direct = max(dot(n, sun), 0.0);
+    gl_FragColor = vec4(color * (0.55 * sky + 0.75 * direct), 1.0);
+  }
+`;
+
+function compile(gl, type, source) {
+  const shader = gl.createShader(type);
+  gl.shaderSource(shader, source);
+  gl.compileShader(shader);
+  if (!gl.getShaderParameter(shader, gl.COMPILE_STATUS)) {
+    throw new Error(gl.getShaderInfoLog(shader));
+  }
+  return shader;
+}
+
+function normalize([x, y, z]) {
+  const length = Math.hypot(x, y, z);
+  return [x / length, y / length, z / length];
+}
+
+function cross([ax, ay, az], [bx, by, bz]) {
+  return [ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx];
+}
+
+function dot(a, b) {
+  return a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+}
+
+/** A column-major perspective projection, `fov` the vertical angle in degrees. */
+function perspective(fov, aspect, near, far) {
+  const f = 1 / Math.tan((fov * Math.PI) / 360);
+  const depth = 1 / (near - far);
+  return new Float32Array([
+    f / aspect, 0, 0, 0,
+    0, f, 0, 0,
+    0, 0, (near + far) * depth, -1,
+    0, 0, 2 * near * far * depth, 0,
+  ]);
+}
+
+/** The view from `eye` towards the origin, y up. */
+function lookAtOrigin(eye) {
+  const z = normalize(eye);
+  const x = normalize(cross([0, 1, 0], z));
+  const y = cross(z, x);
+  return new Float32Array([
+    x[0], y[0], z[0], 0,
+    x[1], y[1], z[1], 0,
+    x[2], y[2], z[2], 0,
+    -dot(x, eye), -dot(y, eye), -dot(z, eye), 1,
+  ]);
+}
+
+/**
+ * Draws into a canvas filling the window. Dragging orbits the camera
+ * about the origin and the wheel moves it closer or further.
+ */
+export function viewer() {
+  const canvas = document.createElement('canvas');
+  document.body.appendChild(canvas);
+  const gl = canvas.getContext('webgl', { antialias: true });
+  if (!gl) {
+    throw new Error('this browser has no WebGL');
+  }
+  const program = gl.createProgram();
+  gl.attachShader(program, compile(gl, gl.VERTEX_SHADER, VERTEX));
+  gl.attachShader(program, compile(gl, gl.FRAGMENT_SHADER, FRAGMENT));
+  gl.linkProgram(program);
+  if (!gl.getProgramParameter(program, gl.LINK_STATUS)) {
+    throw new Error(gl.getProgramInfoLog(program));
+  }
+  gl.useProgram(program);
+  const uniform = (name) => gl.getUniformLocation(program, name);
+  gl.uniform3f(uniform('color'), 0xd8 / 255, 0xb2 / 255, 0x6e / 255);
+  gl.uniform3fv(uniform('sun'), normalize([2, 3, 4]));
+  gl.enable(gl.DEPTH_TEST);
+  gl.enable(gl.CULL_FACE);
+  gl.clearColor(0x11 / 255, 0x11 / 255, 0x11 / 255, 1);
+
+  const buffers = ['position', 'normal'].map((name) => {
+    const location = gl.getAttribLocation(program, name);
+    const buffer = gl.createBuffer();
+    gl.bindBuffer(gl.ARRAY_BUFFER, buffer);
+    gl.enableVertexAttribArray(location);
+    gl.vertexAttribPointer(location, 3, gl.FLOAT, false, 0, 0);
+    return buffer;
+  });
+  let vertices = 0;
+
+  // The camera on a sphere about the origin: its radius, the angle about
+  // the y axis and the angle down from it.
+  let radius = Math.hypot(1.4, 1.1, 1.8);
+  let around = Math.atan2(1.4, 1.8);
+  let down = Math.acos(1.1 / radius);
+
+  let pending = false;
+  function draw() {
+    if (pending) {
+      return;
+    }
+    pending = true;
+    requestAnimationFrame(() => {
+      pending = false;
+      const eye = [
+        radius * Math.sin(down) * Math.sin(around),
+        radius * Math.cos(down),
+        radius * Math.sin(down) * Math.cos(around),
+      ];
+      gl.uniformMatrix4fv(uniform('view'), false, lookAtOrigin(eye));
+      gl.clear(gl.COLOR_BUFFER_BIT | gl.DEPTH_BUFFER_BIT);
+      gl.drawArrays(gl.TRIANGLES, 0, vertices);
+    });
+  }
+
+  function resize() {
+    const ratio = window.devicePixelRatio;
+    canvas.style.width = `${window.innerWidth}px`;
+    canvas.style.height = `${window.innerHeight}px`;
+    canvas.width = Math.round(window.innerWidth * ratio);
+    canvas.height = Math.round(window.innerHeight * ratio);
+    gl.viewport(0, 0, canvas.width, canvas.height);
+    const aspect = window.innerWidth / window.innerHeight;
+    gl.uniformMatrix4fv(uniform('projection'), false, perspective(45, aspect, 0.01, 100));
+    draw();
+  }
+  window.addEventListener('resize', resize);
+  resize();
+
+  let dragged = null;
+  canvas.style.touchAction = 'none';
+  canvas.addEventListener('pointerdown', (event) => {
+    dragged = [event.clientX, event.clientY];
+    canvas.setPointerCapture(event.pointerId);
+  });
+  canvas.addEventListener('pointermove', (event) => {
+    if (!dragged) {
+      return;
+    }
+    // A drag across the window's height turns the camera once around.
+    const turn = (2 * Math.PI) / window.innerHeight;
+    around -= (event.clientX - dragged[0]) * turn;
+    down = Math.min(Math.max(down - (event.clientY - dragged[1]) * turn, 0.01), Math.PI - 0.01);
+    dragged = [event.clientX, event.clientY];
+    draw();
+  });
+  canvas.addEventListener('pointerup', () => {
+    dragged = null;
+  });
+  canvas.addEventListener(
+    'wheel',
+    (event) => {
+      event.preventDefault();
+      radius = Math.min(Math.max(radius * (event.deltaY > 0 ? 1 / 0.95 : 0.95), 0.05), 50);
+      draw();
+    },
+    { passive: false },
+  );
+
+  return {
+    /**
+     * Shows triangles, three floats a vertex for `positions` and
+     * `normals`, moved by `offset` and then scaled by `scale`.
+     */
+    show(positions, normals, offset, scale) {
+      for (const [buffer, data] of [[buffers[0], positions], [buffers[1], normals]]) {
+        gl.bindBuffer(gl.ARRAY_BUFFER, buffer);
+        gl.bufferData(gl.ARRAY_BUFFER, data, gl.STATIC_DRAW);
+      }
+      gl.uniform3fv(uniform('offset'), offset);
+      gl.uniform1f(uniform('scale'), scale);
+      vertices = positions.length / 3;
+      draw();
+    },
+  };
+}
@@ -0,0 +1,168 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::cancel::CancelToken;
+use crate::compress::gzip;
+use crate::distance::signed_distances;
+use crate::error::{Error, Result};
+use crate::image::ImageValues;
+use crate::lattice::Lattice3;
+
+/// Layout of `.zarr` outputs: Zarr v3 arrays stored as a directory of
+/// gzipped chunks, read lazily by zarr-python, dask and xarray.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Zarr {
+    /// Side of the cubic chunks, in cells.
+    #[serde(default = "default_chunk")]
+    pub chunk: usize,
+}
+
+fn default_chunk() -> usize {
+    64
+}
+
+impl Default for Zarr {
+    fn default() -> Self {
+        Zarr {
+            chunk: default_chunk(),
+        }
+    }
+}
+
+/// One chunk's position in the chunk grid, in the array's z, y, x order.
+pub type ChunkIndex = [usize; 3];
+
+impl Zarr {
+    pub fn validate(&self) -> Result<()> {
+        if self.chunk == 0 {
+            return Err(Error::InvalidJob(
+                "Zarr chunks must be at least 1 cell".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The chunks covering a lattice of `shape`, z slowest.
+    pub fn chunks(&self, shape: [usize; 3]) -> Vec<ChunkIndex> {
+        let [nx, ny, nz] = shape.map(|n| n.div_ceil(self.chunk));
+        let mut chunks = Vec::with_capacity(nx * ny * nz);
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    chunks.push([z, y, x]);
+                }
+            }
+        }
+        chunks
+    }
+
+    /// The `zarr.json` of an array of `shape` cells. Dimensions run z, y,
+    /// x, so x varies fastest as in the lattice; occupancy is 1 for filled
+    /// cells, and [`ImageValues::Distance`] stores the signed distance in
+    /// cells.
+    pub fn metadata(&self, shape: [usize; 3], values: ImageValues) -> String {
+        let [nx, ny, nz] = shape;
+        let (data_type, fill_value, description) = match values {
+            ImageValues::Occupancy => ("uint8", json!(0), "fractal occupancy"),
+            ImageValues::Distance => (
+                "float32",
+                json!(0.0),
+                "signed distance to the fractal surface, in cells",
+            ),
+        };
+        let chunk_shape = [self.chunk; 3];
+        let metadata = json!({
+            "zarr_format": 3,
+            "node_type": "array",
+            "shape": [nz, ny, nx],
+            "data_type": data_type,
+            "chunk_grid": {
+                "name": "regular",
+                "configuration": { "chunk_shape": chunk_shape },
+            },
+            "chunk_key_encoding": {
+                "name": "default",
+                "configuration": { "separator": "/" },
+            },
+            "fill_value": fill_value,
+            "codecs": [
+                { "name": "bytes", "configuration": { "endian": "little" } },
+                { "name": "gzip", "configuration": { "level": 1 } },
+            ],
+            "dimension_names": ["z", "y", "x"],
+            "attributes": { "description": description },
+        });
+        serde_json::to_string_pretty(&metadata).expect("metadata serializes")
+    }
+
+    /// Writes one chunk, padded with the fill value past the lattice's
+    /// edges as Zarr requires. The lattice is the part of the array from
+    /// cell `origin` on, holding the whole chunk unless it is at the
+    /// array's edge. `distances` are the lattice's signed distances when
+    /// storing [`ImageValues::Distance`].
+    pub fn write_chunk(
+        &self,
+        lattice: &Lattice3,
+        origin: [usize; 3],
+        distances: Option<&[f64]>,
+        chunk: ChunkIndex,
+        out: &mut impl Write,
+        cancel: &CancelToken,
+    ) -> Result<()> {
+        cancel.check()?;
+        let shape = lattice.shape();
+        let side = self.chunk;
+        let value_bytes = match distances {
+            Some(_) => 4,
+            None => 1,
+        };
+        let mut raw = Vec::with_capacity(side.pow(3) * value_bytes);
+        for dz in 0..side {
+            for dy in 0..side {
+                for dx in 0..side {
+                    let p = [
+                        chunk[2] * side + dx - origin[0],
+                        chunk[1] * side + dy - origin[1],
+                        chunk[0] * side + dz - origin[2],
+                    ];
+                    let inside = (0..3).all(|a| p[a] < shape[a]);
+                    match distances {
+                        Some(distances) => {
+                            let value = if inside {
+                                distances[lattice.index(p)] as f32
+                            } else {
+                                0.0
+                            };
+                            raw.extend_from_slice(&value.to_le_bytes());
+                        }
+                        None => raw.push((inside && lattice.get(p)) as u8),
+                    }
+                }
+            }
+        }
+        out.write_all(&gzip(&raw))?;
+        Ok(())
+    }
+}
+
+/// The signed distances a store of `values` needs, computed once for all
+/// its chunks.
+pub fn chunk_distances(
+    lattice: &Lattice3,
+    values: ImageValues,
+    cancel: &CancelToken,
+) -> Result<Option<Vec<f64>>> {
+    match values {
+        ImageValues::Occupancy => Ok(None),
+        ImageValues::Distance => Ok(Some(signed_distances(lattice, cancel)?)),
+    }
+}
+
+/// The key of `chunk` under the store's root.
+pub fn chunk_key(chunk: ChunkIndex) -> String {
+    let [z, y, x] = chunk;
+    format!("c/{z}/{y}/{x}")
+}
@@ -0,0 +1,105 @@
+//! Batch manifests: paths resolved against the manifest's directory, and
+//! results in manifest order whichever order the jobs finish in.
+
+mod common;
+
+use fractal_slicer_4_d::batch::{run_batch, Manifest};
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::error::Error;
+use fractal_slicer_4_d::job::Job;
+
+use common::scratch;
+
+/// A 3D Menger job writing an STL to `output`.
+fn job(depth: u32, output: &str) -> serde_json::Value {
+    serde_json::json!({"fractal": "menger", "dims": 3, "depth": depth, "outputs": [output]})
+}
+
+fn load(dir: &std::path::Path, jobs: Vec<serde_json::Value>) -> Vec<Job> {
+    let path = dir.join("manifest.json");
+    std::fs::write(&path, serde_json::json!({ "jobs": jobs }).to_string()).unwrap();
+    Manifest::load(&path).unwrap().jobs
+}
+
+#[test]
+fn relative_paths_are_resolved_against_the_manifest() {
+    let dir = scratch("paths");
+    let elsewhere = std::env::temp_dir().join("elsewhere.stl");
+    let mut absolute = job(1, "sub/a.stl");
+    absolute["outputs"]
+        .as_array_mut()
+        .unwrap()
+        .push(elsewhere.to_str().unwrap().into());
+    absolute["infill"] = serde_json::json!({"model": "models/infill.stl", "cell_size": 1.0});
+    absolute["import"] = serde_json::json!({"model": "models/import.obj", "cell_size": 1.0});
+    absolute["monitor"] = serde_json::json!({"path": "snapshots/{i}.vti"});
+    absolute["complex"] = "cells/complex.json".into();
+    absolute["script"] = serde_json::json!({"file": "rules/keep.rhai"});
+    absolute["plugins"] = serde_json::json!(["lib/exporter.so"]);
+    absolute["sonify"] = serde_json::json!({"path": "sweep.wav"});
+    let jobs = load(&dir, vec![absolute]);
+    let job = &jobs[0];
+    assert_eq!(job.outputs, [dir.join("sub/a.stl"), elsewhere]);
+    let infill = job.infill.as_ref().unwrap();
+    assert_eq!(infill.model, dir.join("models/infill.stl"));
+    let import = job.import.as_ref().unwrap();
+    assert_eq!(import.model, dir.join("models/import.obj"));
+    let monitor = job.monitor.as_ref().unwrap();
+    assert_eq!(monitor.path, dir.join("snapshots/{i}.vti"));
+    assert_eq!(job.complex, Some(dir.join("cells/complex.json")));
+    let script = job.script.as_ref().unwrap();
+    assert_eq!(script.file, dir.join("rules/keep.rhai"));
+    assert_eq!(job.plugins, [dir.join("lib/exporter.so")]);
+    assert!(job.inputs().contains(&dir.join("lib/exporter.so")));
+    let sonify = job.sonify.as_ref().unwrap();
+    assert_eq!(sonify.path, dir.join("sweep.wav"));
+}
+
+#[test]
+fn results_come_back_in_manifest_order() {
+    let dir = scratch("order");
+    // Deep jobs first, so the shallow ones behind them finish earlier.
+    let depths = [3, 1, 0, 2, 1, 0];
+    let mut jobs: Vec<_> = depths
+        .iter()
+        .enumerate()
+        .map(|(i, &depth)| job(depth, &format!("job-{i}.stl")))
+        .collect();
+    jobs.insert(
+        3,
+        serde_json::json!({"fractal": "nonesuch", "depth": 1, "outputs": []}),
+    );
+    let jobs = load(&dir, jobs);
+    for workers in [1, 3, 8] {
+        let results = run_batch(&jobs, workers, &CancelToken::new());
+        assert_eq!(results.len(), jobs.len());
+        for (job, result) in jobs.iter().zip(&results) {
+            if job.fractal == "nonesuch" {
+                assert!(
+                    matches!(result, Err(Error::UnknownFractal(_))),
+                    "{result:?}"
+                );
+                continue;
+            }
+            let report = result.as_ref().unwrap();
+            assert_eq!(report.artifacts[0].path, job.outputs[0]);
+        }
+    }
+}
+
+#[test]
+fn a_cancelled_batch_reports_every_job_cancelled() {
+    let dir = scratch("cancelled");
+    let jobs = load(
+        &dir,
+        (0..4).map(|i| job(2, &format!("job-{i}.stl"))).collect(),
+    );
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    let results = run_batch(&jobs, 2, &cancel);
+    assert_eq!(results.len(), 4);
+    for result in results {
+        assert!(matches!(result, Err(Error::Cancelled)), "{result:?}");
+    }
+    assert!(!dir.join("job-0.stl").exists());
+}
@@ -0,0 +1,154 @@
+//! Membership by balanced digits about the origin, and the outputs of the
+//! centred domain that use it.
+
+mod common;
+
+use std::path::Path;
+
+use fractal_slicer_4_d::job::Job;
+use fractal_slicer_4_d::rule::{expected_cells, Rule};
+
+use common::scratch;
+
+/// Every centred coordinate of the depth-`depth` lattice, and one more
+/// cell on each side.
+fn around(rule: &Rule, depth: u32) -> Vec<Vec<i64>> {
+    let mut cells = vec![Vec::new()];
+    for axis in 0..rule.dims() {
+        let side = rule.side_along(axis, depth) as i64;
+        let center = rule.center(depth)[axis] as i64;
+        cells = cells
+            .into_iter()
+            .flat_map(|cell| {
+                (-center - 1..=side - center).map(move |c| {
+                    let mut cell = cell.clone();
+                    cell.push(c);
+                    cell
+                })
+            })
+            .collect();
+    }
+    cells
+}
+
+/// Checks the centred test against the lattice's own, shifted by the
+/// centre, over the lattice and a cell beyond it.
+fn assert_agrees_with_is_solid(rule: &Rule, depth: u32) {
+    let center = rule.center(depth);
+    let mut filled = 0;
+    for c in around(rule, depth) {
+        let shifted: Option<Vec<usize>> = c
+            .iter()
+            .zip(&center)
+            .enumerate()
+            .map(|(axis, (&c, &center))| {
+                usize::try_from(c + center as i64)
+                    .ok()
+                    .filter(|&p| p < rule.side_along(axis, depth))
+            })
+            .collect();
+        let expected = shifted.is_some_and(|p| rule.is_solid(&p, depth));
+        assert_eq!(rule.is_solid_centered(&c, depth), expected, "{c:?}");
+        filled += usize::from(expected);
+    }
+    assert_eq!(filled as u64, expected_cells(rule, depth));
+}
+
+#[test]
+fn menger_is_symmetric_about_the_origin() {
+    for (dims, depths) in [(3, 1..=3), (4, 1..=2)] {
+        let rule = Rule::menger(dims);
+        for depth in depths {
+            assert_eq!(rule.center(depth), vec![(3usize.pow(depth) - 1) / 2; dims]);
+            assert!(
+                !rule.is_solid_centered(&vec![0; dims], depth),
+                "the middle is a hole"
+            );
+            for c in around(&rule, depth) {
+                let solid = rule.is_solid_centered(&c, depth);
+                let negated: Vec<i64> = c.iter().map(|&c| -c).collect();
+                assert_eq!(rule.is_solid_centered(&negated, depth), solid, "{c:?}");
+                for axis in 0..dims {
+                    let mut mirrored = c.clone();
+                    mirrored[axis] = -mirrored[axis];
+                    assert_eq!(rule.is_solid_centered(&mirrored, depth), solid, "{c:?}");
+                }
+            }
+            assert_agrees_with_is_solid(&rule, depth);
+        }
+    }
+}
+
+#[test]
+fn cells_beyond_the_lattice_are_empty() {
+    let rule = Rule::menger(3);
+    // A full corner cell, and the same cell a lattice further out.
+    assert!(rule.is_solid_centered(&[13, 13, 13], 3));
+    assert!(!rule.is_solid_centered(&[13 + 27, 13, 13], 3));
+    assert!(!rule.is_solid_centered(&[i64::MAX, i64::MIN, 0], 3));
+}
+
+#[test]
+fn other_bases_and_splits_agree_with_the_shifted_lattice() {
+    let stretched = Rule::from_fn_bases("stretched", &[3, 5, 3], |d| {
+        d.iter().filter(|&&d| d == 1).count() < 2 && d[1] != 3
+    });
+    let even = Rule::from_fn("even", 4, 3, |d| d.iter().any(|&d| d == 0 || d == 3));
+    for rule in [stretched, even, Rule::vicsek(3), Rule::jerusalem(3)] {
+        for depth in 0..=2 {
+            assert_agrees_with_is_solid(&rule, depth);
+        }
+    }
+}
+
+fn job(dir: &Path, json: &str) -> Job {
+    let mut job: Job = serde_json::from_str(json).expect("valid job");
+    job.outputs = job.outputs.iter().map(|output| dir.join(output)).collect();
+    job
+}
+
+#[test]
+fn centred_volumes_sit_about_the_origin() {
+    let dir = scratch("vtk");
+    job(
+        &dir,
+        r#"{"fractal": "menger", "dims": 3, "depth": 2, "domain": "centered", "outputs": ["sponge.vtk"]}"#,
+    )
+    .run()
+    .unwrap();
+    let vtk = std::fs::read(dir.join("sponge.vtk")).unwrap();
+    let header = String::from_utf8_lossy(&vtk[..200]);
+    assert!(header.contains("\nORIGIN -4 -4 -4\n"), "{header}");
+}
+
+#[test]
+fn ranks_of_a_centred_job_write_the_same_chunks() {
+    let dir = scratch("ranks");
+    for domain in ["positive", "centered"] {
+        job(
+            &dir,
+            &format!(
+                r#"{{"fractal": "menger", "dims": 3, "depth": 4, "domain": "{domain}",
+                    "ranks": {{"rank": 0, "size": 1}}, "outputs": ["{domain}.zarr"]}}"#
+            ),
+        )
+        .run()
+        .unwrap();
+    }
+    let chunks = |domain: &str| {
+        let mut chunks: Vec<_> = std::fs::read_dir(dir.join(format!("{domain}.zarr/c/0/0")))
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                (
+                    path.file_name().unwrap().to_owned(),
+                    std::fs::read(&path).unwrap(),
+                )
+            })
+            .collect();
+        chunks.sort();
+        chunks
+    };
+    assert!(!chunks("positive").is_empty());
+    assert_eq!(chunks("centered"), chunks("positive"));
+}
@@ -0,0 +1,130 @@
+//! Resuming a job from the checkpoint an interrupted run left beside its
+//! outputs.
+
+mod common;
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::checkpoint::Checkpoint;
+use fractal_slicer_4_d::error::Error;
+use fractal_slicer_4_d::export::Artifact;
+use fractal_slicer_4_d::job::Job;
+
+use common::scratch;
+
+/// A 4D job writing two slices into `dir`.
+fn job(dir: &Path) -> Job {
+    let mut job: Job = serde_json::from_str(
+        r#"{"fractal": "menger", "dims": 4, "depth": 1, "slices": [0, 1],
+            "outputs": ["slice_{w}.obj"]}"#,
+    )
+    .expect("valid job");
+    job.outputs = vec![dir.join("slice_{w}.obj")];
+    job
+}
+
+fn slice_artifacts(artifacts: &[Artifact], w: usize) -> Vec<Artifact> {
+    let name = format!("slice_{w}.obj");
+    artifacts
+        .iter()
+        .filter(|artifact| artifact.path.ends_with(&name))
+        .cloned()
+        .collect()
+}
+
+fn modified(path: &Path) -> SystemTime {
+    std::fs::metadata(path).unwrap().modified().unwrap()
+}
+
+#[test]
+fn a_rerun_reuses_unchanged_slices_and_rewrites_the_rest() {
+    let dir = scratch("resume");
+    let job = job(&dir);
+    let checkpoint_path = Checkpoint::path_for(&job.outputs[0]).unwrap();
+    let first = job.run().unwrap();
+    assert!(
+        !checkpoint_path.exists(),
+        "a finished run keeps no checkpoint"
+    );
+
+    // Record both slices as an interrupted run would have, then age the
+    // first and damage the second.
+    let checkpoint = Checkpoint::open(checkpoint_path.clone(), &job).unwrap();
+    for w in [0, 1] {
+        let done = slice_artifacts(&first.artifacts, w);
+        checkpoint.unit(&format!("w{w}"), || Ok(done)).unwrap();
+    }
+    checkpoint.save().unwrap();
+    let old = SystemTime::now() - Duration::from_secs(3600);
+    let kept = dir.join("slice_0.obj");
+    std::fs::File::options()
+        .append(true)
+        .open(&kept)
+        .unwrap()
+        .set_modified(old)
+        .unwrap();
+    std::fs::write(dir.join("slice_1.obj"), "damaged").unwrap();
+
+    let second = job.run().unwrap();
+    assert_eq!(second.artifacts, first.artifacts);
+    assert_eq!(modified(&kept), old, "the unchanged slice was reused");
+    assert!(!checkpoint_path.exists());
+}
+
+#[test]
+fn a_checkpoint_of_another_job_is_ignored() {
+    let dir = scratch("other");
+    let job = job(&dir);
+    let path = dir.join("checkpoint.json");
+    let checkpoint = Checkpoint::open(path.clone(), &job).unwrap();
+    let report = job.run().unwrap();
+    checkpoint
+        .unit("w0", || Ok(slice_artifacts(&report.artifacts, 0)))
+        .unwrap();
+    checkpoint.save().unwrap();
+
+    let mut deeper = job.clone();
+    deeper.depth = 2;
+    let mut ran = false;
+    Checkpoint::open(path.clone(), &deeper)
+        .unwrap()
+        .unit("w0", || {
+            ran = true;
+            Ok(Vec::new())
+        })
+        .unwrap();
+    assert!(ran);
+
+    let mut ran = false;
+    Checkpoint::open(path, &job)
+        .unwrap()
+        .unit("w0", || {
+            ran = true;
+            Ok(Vec::new())
+        })
+        .unwrap();
+    assert!(!ran);
+}
+
+#[test]
+fn a_cancelled_run_leaves_a_checkpoint_and_no_partial_files() {
+    let dir = scratch("cancelled");
+    let job = job(&dir);
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert!(matches!(
+        job.run_cancellable(&cancel),
+        Err(Error::Cancelled)
+    ));
+    let checkpoint = Checkpoint::path_for(&job.outputs[0]).unwrap();
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    files.sort();
+    assert_eq!(files, vec![checkpoint.clone()]);
+    job.run().unwrap();
+    assert!(!checkpoint.exists());
+}
@@ -0,0 +1,132 @@
+//! The chunked, out-of-core meshing path against the monolithic one: the
+//! same faces from chunks with ghost borders, and the same files from
+//! slabs of any depth.
+
+mod common;
+
+use std::collections::HashSet;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::export::{export, export_streaming};
+use fractal_slicer_4_d::lattice::{Boundary, Lattice3};
+use fractal_slicer_4_d::mesh::{chunk_faces, surface_faces, Face};
+use fractal_slicer_4_d::rule::Rule;
+
+use common::{lattices, options, scratch};
+
+const BOUNDARIES: [Boundary; 3] = [Boundary::Open, Boundary::Periodic, Boundary::Mirrored];
+
+#[test]
+fn chunks_cull_exactly_the_faces_of_the_whole_lattice() {
+    let cancel = CancelToken::new();
+    for lattice in lattices() {
+        let shape = lattice.shape();
+        for boundary in BOUNDARIES {
+            let whole = surface_faces(&lattice, boundary);
+            // Bricks that do not divide the lattice evenly, so some are cut
+            // short at the far walls.
+            let size = [2, 4, 3];
+            let mut chunked = Vec::new();
+            for z in (0..shape[2]).step_by(size[2]) {
+                for y in (0..shape[1]).step_by(size[1]) {
+                    for x in (0..shape[0]).step_by(size[0]) {
+                        let low = [x, y, z];
+                        let size = std::array::from_fn(|a| size[a].min(shape[a] - low[a]));
+                        let chunk =
+                            Lattice3::chunk_from_fn(shape, low, size, boundary, &cancel, |p| {
+                                lattice.get(p)
+                            })
+                            .unwrap();
+                        chunked.extend(chunk_faces(&chunk, low));
+                    }
+                }
+            }
+            assert_eq!(chunked.len(), whole.len(), "{shape:?} {boundary:?}");
+            let whole: HashSet<Face> = whole.into_iter().collect();
+            assert!(chunked.iter().all(|face| whole.contains(face)));
+        }
+    }
+}
+
+#[test]
+fn slabs_keep_the_order_of_the_whole_lattice() {
+    let cancel = CancelToken::new();
+    let lattice = Lattice3::generate(&Rule::menger(3), 2);
+    let [nx, ny, nz] = lattice.shape();
+    for boundary in BOUNDARIES {
+        let mut slabs = Vec::new();
+        for z in (0..nz).step_by(4) {
+            let depth = 4.min(nz - z);
+            let slab = Lattice3::chunk_from_fn(
+                lattice.shape(),
+                [0, 0, z],
+                [nx, ny, depth],
+                boundary,
+                &cancel,
+                |p| lattice.get(p),
+            )
+            .unwrap();
+            slabs.extend(chunk_faces(&slab, [0, 0, z]));
+        }
+        assert_eq!(slabs, surface_faces(&lattice, boundary));
+    }
+}
+
+#[test]
+fn streamed_files_match_whole_lattice_exports() {
+    let cancel = CancelToken::new();
+    let dir = scratch("files");
+    let settings = [
+        "",
+        r#", "boundary": "periodic""#,
+        r#", "boundary": "mirrored", "normals": "face""#,
+        r#", "normals": "face", "transforms": [{"scale": -0.5}]"#,
+    ];
+    for (l, lattice) in lattices().iter().enumerate() {
+        for (s, extra) in settings.iter().enumerate() {
+            let options = options(extra);
+            for extension in ["obj", "stl"] {
+                let whole = dir.join(format!("whole-{l}-{s}.{extension}"));
+                export(lattice, &whole, &options, &cancel).unwrap();
+                let expected = std::fs::read(&whole).unwrap();
+                for layers in [1, 2, 5, 100] {
+                    let path = dir.join(format!("streamed-{l}-{s}-{layers}.{extension}"));
+                    export_streaming(
+                        lattice.shape(),
+                        |p| lattice.get(p),
+                        layers,
+                        &path,
+                        &options,
+                        &cancel,
+                    )
+                    .unwrap();
+                    let streamed = std::fs::read(&path).unwrap();
+                    match extension {
+                        // Streamed OBJ writes each vertex and normal just
+                        // before its first face, so compare each kind of
+                        // line in order.
+                        "obj" => {
+                            for kind in ["v ", "vn ", "f "] {
+                                let lines = |bytes: &[u8]| -> Vec<String> {
+                                    String::from_utf8_lossy(bytes)
+                                        .lines()
+                                        .filter(|line| line.starts_with(kind))
+                                        .map(str::to_string)
+                                        .collect()
+                                };
+                                assert_eq!(
+                                    lines(&streamed),
+                                    lines(&expected),
+                                    "{} {kind}",
+                                    path.display()
+                                );
+                            }
+                        }
+                        _ => assert!(streamed == expected, "{}", path.display()),
+                    }
+                }
+            }
+        }
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
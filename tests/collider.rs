@@ -0,0 +1,35 @@
+//! Colliders for the smallest lattices: empty ones and a single cell.
+
+#![cfg(feature = "rapier")]
+
+use fractal_slicer_4_d::collider::{build_collider, build_collider_set, ColliderShape};
+use fractal_slicer_4_d::lattice::Lattice3;
+use fractal_slicer_4_d::rule::Rule;
+use rapier3d::prelude::{Point, TriMeshBuilderError};
+
+const SHAPES: [ColliderShape; 2] = [ColliderShape::Cuboids, ColliderShape::TriMesh];
+
+#[test]
+fn empty_lattices_have_no_collider() {
+    for lattice in [Lattice3::new([0; 3]), Lattice3::new([3; 3])] {
+        for shape in SHAPES {
+            assert!(matches!(
+                build_collider(&lattice, shape, 1.0),
+                Err(TriMeshBuilderError::EmptyIndices)
+            ));
+            assert!(build_collider_set(&lattice, shape, 1.0).unwrap().is_empty());
+        }
+    }
+}
+
+#[test]
+fn a_depth_0_lattice_collides_as_one_cell() {
+    let lattice = Lattice3::generate(&Rule::menger(3), 0);
+    assert_eq!(lattice.count(), 1);
+    for shape in SHAPES {
+        let aabb = build_collider(&lattice, shape, 2.0).unwrap().compute_aabb();
+        assert_eq!(aabb.mins, Point::new(0.0, 0.0, 0.0), "{shape:?}");
+        assert_eq!(aabb.maxs, Point::new(2.0, 2.0, 2.0), "{shape:?}");
+        assert_eq!(build_collider_set(&lattice, shape, 2.0).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,71 @@
+//! Helpers shared by the integration tests; each test crate uses some.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use fractal_slicer_4_d::export::ExportOptions;
+use fractal_slicer_4_d::job::Job;
+use fractal_slicer_4_d::lattice::Lattice3;
+use fractal_slicer_4_d::rule::{AxisRule, Combination, Rule};
+
+/// An empty directory for the calling test crate's test `name`, cleared
+/// of anything an earlier run left.
+pub fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "fractal-slicer-{}-{name}-{}",
+        env!("CARGO_CRATE_NAME"),
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// 3D lattices with holes of several shapes, and one with bases that
+/// differ per axis.
+pub fn lattices() -> Vec<Lattice3> {
+    vec![
+        Lattice3::generate(&Rule::menger(3), 3),
+        Lattice3::generate(&Rule::jerusalem(3), 2),
+        Lattice3::generate(&Rule::vicsek(3), 2),
+        Lattice3::generate(&Rule::from_fn_bases("slab", &[3, 2, 5], |d| d[2] != 2), 2),
+    ]
+}
+
+/// Every built-in rule, alternations and unions of the uniform ones, and
+/// anisotropic custom rules, in `dims` dimensions.
+pub fn rules(dims: usize) -> Vec<Rule> {
+    let mut rules: Vec<Rule> = Rule::NAMES
+        .iter()
+        .map(|name| Rule::by_name(name, dims).unwrap())
+        .collect();
+    for (op, names) in [
+        (Combination::Alternate, ["menger", "vicsek"]),
+        (Combination::Alternate, ["vicsek", "mosely"]),
+        (Combination::Union, ["vicsek", "mosely"]),
+    ] {
+        let parts: Vec<Rule> = names
+            .iter()
+            .map(|name| Rule::by_name(name, dims).unwrap())
+            .collect();
+        rules.push(op.apply(&parts).unwrap());
+    }
+    for (bases, min_removed) in [(vec![3, 2, 5, 4], 2), (vec![2, 4, 3, 3], 1)] {
+        let custom = AxisRule {
+            bases: bases[..dims].to_vec(),
+            remove: Vec::new(),
+            min_removed,
+        };
+        rules.push(custom.rule(dims).unwrap());
+    }
+    rules
+}
+
+/// The export options of a 3D job with `extra` fields, such as
+/// `"boundary": "periodic"`.
+pub fn options(extra: &str) -> ExportOptions {
+    let json = format!(r#"{{"fractal": "menger", "dims": 3, "depth": 1, "outputs": [] {extra}}}"#);
+    let job: Job = serde_json::from_str(&json).expect("valid job");
+    job.export_options()
+}
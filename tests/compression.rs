@@ -0,0 +1,226 @@
+//! The deflate streams in written files inflated by another
+//! implementation, over inputs large and repetitive enough for long
+//! matches and far distances.
+
+mod common;
+
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::Crc;
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::image::{write_png, Layer};
+use fractal_slicer_4_d::job::Job;
+use fractal_slicer_4_d::lattice::Lattice3;
+use fractal_slicer_4_d::rule::Rule;
+use fractal_slicer_4_d::schematic::{Piece, Schematic};
+
+use common::scratch;
+
+fn run(dir: &Path, json: &str) {
+    let mut job: Job = serde_json::from_str(json).expect("valid job");
+    job.outputs = job.outputs.iter().map(|output| dir.join(output)).collect();
+    job.run().unwrap();
+}
+
+fn gunzip(bytes: &[u8]) -> Vec<u8> {
+    let mut data = Vec::new();
+    GzDecoder::new(bytes).read_to_end(&mut data).unwrap();
+    data
+}
+
+/// The width, height, bit depth and inflated, unfiltered rows of a
+/// grayscale PNG, checking every chunk's CRC.
+fn decode_png(png: &[u8]) -> (u32, u32, u8, Vec<u8>) {
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut rest = &png[8..];
+    let mut header = Vec::new();
+    let mut compressed = Vec::new();
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+        let mut crc = Crc::new();
+        crc.update(&rest[4..8 + len]);
+        assert_eq!(crc.sum().to_be_bytes(), rest[8 + len..12 + len]);
+        match kind {
+            b"IHDR" => header = data.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(data),
+            _ => {}
+        }
+        rest = &rest[12 + len..];
+    }
+    let width = u32::from_be_bytes(header[..4].try_into().unwrap());
+    let height = u32::from_be_bytes(header[4..8].try_into().unwrap());
+    let mut raw = Vec::new();
+    ZlibDecoder::new(&compressed[..])
+        .read_to_end(&mut raw)
+        .unwrap();
+    let row = raw.len() / height as usize;
+    let mut pixels = Vec::new();
+    for line in raw.chunks(row) {
+        assert_eq!(line[0], 0, "unfiltered rows");
+        pixels.extend_from_slice(&line[1..]);
+    }
+    (width, height, header[8], pixels)
+}
+
+/// The shorts, ints and byte arrays of an NBT document by name, walking
+/// the tags a schematic uses and checking nothing is left over.
+fn read_nbt(mut nbt: &[u8]) -> (Vec<(String, i32)>, Vec<u8>) {
+    fn take<'a>(nbt: &mut &'a [u8], n: usize) -> &'a [u8] {
+        let (head, rest) = nbt.split_at(n);
+        *nbt = rest;
+        head
+    }
+    fn int(nbt: &mut &[u8]) -> i32 {
+        i32::from_be_bytes(take(nbt, 4).try_into().unwrap())
+    }
+    let (mut numbers, mut bytes, mut depth) = (Vec::new(), Vec::new(), 0);
+    loop {
+        let kind = take(&mut nbt, 1)[0];
+        if kind == 0 {
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+            continue;
+        }
+        let len = u16::from_be_bytes(take(&mut nbt, 2).try_into().unwrap());
+        let name = String::from_utf8(take(&mut nbt, len.into()).to_vec()).unwrap();
+        match kind {
+            2 => {
+                let value = u16::from_be_bytes(take(&mut nbt, 2).try_into().unwrap());
+                numbers.push((name, value.into()));
+            }
+            3 => numbers.push((name, int(&mut nbt))),
+            7 => {
+                let len = int(&mut nbt) as usize;
+                bytes = take(&mut nbt, len).to_vec();
+            }
+            11 => {
+                let len = int(&mut nbt) as usize;
+                take(&mut nbt, 4 * len);
+            }
+            10 => depth += 1,
+            _ => panic!("unexpected tag {kind}"),
+        }
+    }
+    assert!(nbt.is_empty(), "{} bytes left over", nbt.len());
+    (numbers, bytes)
+}
+
+#[test]
+fn zarr_chunks_inflate_to_the_lattice() {
+    let dir = scratch("zarr");
+    run(
+        &dir,
+        r#"{"fractal": "menger", "dims": 3, "depth": 4, "zarr": {"chunk": 81},
+            "outputs": ["sponge.zarr"]}"#,
+    );
+    let lattice = Lattice3::generate(&Rule::menger(3), 4);
+    let chunk = std::fs::read(dir.join("sponge.zarr/c/0/0/0")).unwrap();
+    let data = gunzip(&chunk);
+    assert_eq!(data.len(), 81 * 81 * 81);
+    assert!(chunk.len() * 20 < data.len(), "{} bytes", chunk.len());
+    for (i, &value) in data.iter().enumerate() {
+        let p = [i % 81, i / 81 % 81, i / (81 * 81)];
+        assert_eq!(value, u8::from(lattice.get(p)), "{p:?}");
+    }
+
+    // Distances, whose bytes repeat far less.
+    run(
+        &dir,
+        r#"{"fractal": "menger", "dims": 3, "depth": 3, "zarr": {"chunk": 27},
+            "images": {"values": "distance"}, "outputs": ["distance.zarr"]}"#,
+    );
+    let data = gunzip(&std::fs::read(dir.join("distance.zarr/c/0/0/0")).unwrap());
+    let distances: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect();
+    let lattice = Lattice3::generate(&Rule::menger(3), 3);
+    assert_eq!(distances.len(), 27 * 27 * 27);
+    for (i, &distance) in distances.iter().enumerate() {
+        let p = [i % 27, i / 27 % 27, i / (27 * 27)];
+        assert_eq!(distance < 0.0, lattice.get(p), "{p:?}: {distance}");
+    }
+}
+
+#[test]
+fn png_layers_inflate_to_their_samples() {
+    // A Sierpinski carpet of 729 rows, and 16-bit samples that repeat far
+    // less.
+    let carpet = (0..729 * 729)
+        .map(|i: usize| {
+            let (mut x, mut y) = (i % 729, i / 729);
+            while x > 0 || y > 0 {
+                if x % 3 == 1 && y % 3 == 1 {
+                    return 0;
+                }
+                (x, y) = (x / 3, y / 3);
+            }
+            255
+        })
+        .collect();
+    let ramp = (0..300 * 200u32)
+        .map(|i| (i * i / 7 % 65536) as u16)
+        .collect();
+    for layer in [
+        Layer {
+            width: 729,
+            height: 729,
+            bits: 8,
+            samples: carpet,
+        },
+        Layer {
+            width: 300,
+            height: 200,
+            bits: 16,
+            samples: ramp,
+        },
+    ] {
+        let mut png = Vec::new();
+        write_png(&layer, &mut png).unwrap();
+        let (width, height, bits, pixels) = decode_png(&png);
+        assert_eq!(
+            (width as usize, height as usize),
+            (layer.width, layer.height)
+        );
+        assert_eq!(bits, layer.bits);
+        let samples: Vec<u16> = match bits {
+            8 => pixels.iter().map(|&p| p.into()).collect(),
+            _ => pixels
+                .chunks_exact(2)
+                .map(|p| u16::from_be_bytes([p[0], p[1]]))
+                .collect(),
+        };
+        assert!(samples == layer.samples, "{bits}-bit samples differ");
+    }
+}
+
+#[test]
+fn schematics_inflate_to_the_lattice() {
+    let lattice = Lattice3::generate(&Rule::menger(3), 4);
+    let piece = Piece {
+        index: [0; 3],
+        origin: [0; 3],
+        shape: [81; 3],
+    };
+    let mut schem = Vec::new();
+    Schematic::default()
+        .write(&lattice, &piece, &mut schem, &CancelToken::new())
+        .unwrap();
+    let (numbers, blocks) = read_nbt(&gunzip(&schem));
+    for side in ["Width", "Height", "Length"] {
+        assert!(numbers.contains(&(side.to_string(), 81)), "{side}");
+    }
+    assert!(numbers.contains(&("minecraft:stone".to_string(), 1)));
+    assert_eq!(blocks.len(), 81 * 81 * 81);
+    assert!(schem.len() * 20 < blocks.len(), "{} bytes", schem.len());
+    // Minecraft's x, then its southward z, then its vertical y.
+    for (i, &block) in blocks.iter().enumerate() {
+        let p = [i % 81, 80 - i / 81 % 81, i / (81 * 81)];
+        assert_eq!(block, u8::from(lattice.get(p)), "{p:?}");
+    }
+}
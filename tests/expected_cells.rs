@@ -0,0 +1,44 @@
+//! The closed-form cell counts against the survivor fractions they come
+//! from and against generated lattices of every split.
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::lattice::Lattice;
+use fractal_slicer_4_d::rule::{expected_cells, Combination, Rule};
+
+#[test]
+fn menger_keeps_its_survivor_fraction_every_level() {
+    let menger3 = Rule::menger(3);
+    let menger4 = Rule::menger(4);
+    assert_eq!(menger3.survivor_fraction(1), (20, 27));
+    assert_eq!(menger4.survivor_fraction(1), (48, 81));
+    for n in 0..=6 {
+        assert_eq!(expected_cells(&menger3, n), 20u64.pow(n));
+        assert_eq!(expected_cells(&menger4, n), 48u64.pow(n));
+    }
+    assert_eq!(expected_cells(&menger3, 100), u64::MAX);
+}
+
+#[test]
+fn expected_cells_match_generated_lattices() {
+    let cancel = CancelToken::new();
+    let alternating = Combination::Alternate
+        .apply(&[Rule::menger(3), Rule::vicsek(3)])
+        .unwrap();
+    let rules = [
+        Rule::jerusalem(3),
+        Rule::mosely(3),
+        Rule::octahedron(3),
+        alternating,
+    ];
+    for rule in rules {
+        for n in 0..=3 {
+            let lattice: Lattice<3> = Lattice::generate_cancellable(&rule, n, &cancel).unwrap();
+            assert_eq!(
+                lattice.count() as u64,
+                expected_cells(&rule, n),
+                "{} at depth {n}",
+                rule.name()
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! The `f32` coordinate pipeline against the `f64` one: the same surfaces
+//! and files for untransformed lattices, and transformed vertices within
+//! half an `f32` step of the `f64` ones.
+
+mod common;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::export::{write, Format};
+use fractal_slicer_4_d::lattice::{Boundary, Lattice3};
+use fractal_slicer_4_d::mesh::{build_indexed_mesh, build_indexed_mesh_as, FaceKind, Mesh};
+use fractal_slicer_4_d::rule::Rule;
+use fractal_slicer_4_d::transform::Affine;
+
+use common::{lattices, options};
+
+fn transform() -> Affine {
+    let json = r#"[
+        {"rotate": {"axis": "z", "degrees": 30}},
+        {"rotate": {"axis": "x", "degrees": -47}},
+        {"scale": 0.37},
+        {"translate": [1000.25, -3.5, 0.1]}
+    ]"#;
+    Affine::from_transforms(&serde_json::from_str::<Vec<_>>(json).unwrap())
+}
+
+#[test]
+fn untransformed_surfaces_are_exact() {
+    for lattice in lattices() {
+        for boundary in [Boundary::Open, Boundary::Periodic, Boundary::Mirrored] {
+            for kind in [FaceKind::Quads, FaceKind::Triangles] {
+                let narrow: Mesh<f32> = build_indexed_mesh_as(&lattice, kind, boundary);
+                assert_eq!(narrow.cast(), build_indexed_mesh(&lattice, kind, boundary));
+            }
+        }
+    }
+}
+
+#[test]
+fn files_match_the_f64_path_without_transforms() {
+    let cancel = CancelToken::new();
+    let settings = [
+        "",
+        r#", "normals": "face""#,
+        r#", "normals": "smooth", "tiling": {"count": [2, 1, 3, 1], "spacing": 1.0}"#,
+    ];
+    for lattice in lattices() {
+        for extra in settings {
+            for format in [Format::Obj, Format::Stl, Format::Glb] {
+                let [wide, narrow] = ["", r#", "coordinates": "f32""#].map(|coordinates| {
+                    let mut out = Vec::new();
+                    let options = options(&format!("{extra}{coordinates}"));
+                    write(&lattice, format, &mut out, &options, &cancel).unwrap();
+                    out
+                });
+                assert!(wide == narrow, "{format:?} {extra}");
+            }
+        }
+    }
+}
+
+#[test]
+fn transformed_vertices_are_within_half_an_f32_step() {
+    let affine = transform();
+    for lattice in lattices() {
+        let mut wide = build_indexed_mesh(&lattice, FaceKind::Quads, Boundary::Open);
+        let mut narrow: Mesh<f32> =
+            build_indexed_mesh_as(&lattice, FaceKind::Quads, Boundary::Open);
+        wide.transform(&affine);
+        narrow.transform(&affine);
+        assert_eq!(wide.faces, narrow.faces);
+        for (w, n) in wide.vertices.iter().zip(&narrow.vertices) {
+            for axis in 0..3 {
+                let bound = w[axis].abs() * f32::EPSILON as f64 / 2.0;
+                let error = (n[axis] as f64 - w[axis]).abs();
+                assert!(error <= bound, "{w:?} {n:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn transformed_stl_normals_stay_close() {
+    let cancel = CancelToken::new();
+    let transform =
+        r#", "transforms": [{"rotate": {"axis": "y", "degrees": 12.5}}, {"scale": 2.0}]"#;
+    let lattice = Lattice3::generate(&Rule::menger(3), 3);
+    let [wide, narrow] = ["", r#", "coordinates": "f32""#].map(|coordinates| {
+        let mut out = Vec::new();
+        let options = options(&format!("{transform}{coordinates}"));
+        write(&lattice, Format::Stl, &mut out, &options, &cancel).unwrap();
+        out
+    });
+    assert_eq!(wide.len(), narrow.len());
+    // After the header, 50-byte records of a normal, three corners and an
+    // attribute word.
+    for (w, n) in wide[84..].chunks(50).zip(narrow[84..].chunks(50)) {
+        let floats = |record: &[u8]| -> Vec<f32> {
+            record[..48]
+                .chunks(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                .collect()
+        };
+        let (w, n) = (floats(w), floats(n));
+        // Corners were rounded to f32 once on either path.
+        assert_eq!(w[3..], n[3..]);
+        // Normals come from the rounded corners, 2 apart and up to 60 from
+        // the origin, so they turn by up to 60 / 2 of an f32 step.
+        let bound = 30.0 * f32::EPSILON;
+        for axis in 0..3 {
+            assert!((w[axis] - n[axis]).abs() <= bound, "{w:?} {n:?}");
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Every export format against checked-in golden files, so a refactor of
+//! an exporter cannot change its output unnoticed. Numbers in text files
+//! and the coordinates of binary STL compare with a small tolerance; all
+//! other bytes must match exactly.
+//!
+//! After an intended change to a format, regenerate the files with
+//! `UPDATE_GOLDEN=1 cargo test --test golden_exports` and review the diff.
+
+use std::path::{Path, PathBuf};
+
+use fractal_slicer_4_d::job::Job;
+
+/// Relative tolerance for numbers that went through float formatting.
+const TOLERANCE: f64 = 1e-5;
+
+/// Small jobs that between them write every format.
+const CASES: [(&str, &str); 3] = [
+    (
+        "menger-3d",
+        r#"{"fractal": "menger", "dims": 3, "depth": 1, "outputs": [
+            "sponge.obj", "sponge.stl", "sponge.glb", "sponge.vtk", "sponge.nii",
+            "sponge.schem", "sponge.dds", "sponge.zarr", "sponge.inst",
+            "layers.png", "layers.tif"
+        ]}"#,
+    ),
+    (
+        "jerusalem-3d-transformed",
+        r#"{"fractal": "jerusalem", "dims": 3, "depth": 1,
+            "transforms": [{"scale": 0.25}, {"rotate": {"axis": "z", "degrees": 30}}],
+            "outputs": ["cross.obj", "cross.stl", "cross.glb", "cross.inst", "cross.obj.zst"]}"#,
+    ),
+    (
+        "menger-4d-slices",
+        r#"{"fractal": "menger", "dims": 4, "depth": 1, "slices": [0, 1],
+            "complex": "cells.json",
+            "outputs": ["slice_{w}.obj", "slice_{w}.stl"]}"#,
+    ),
+];
+
+fn golden_dir(case: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(case)
+}
+
+/// Runs the case's job in a fresh directory and returns it with the
+/// files written, relative to it.
+fn run(case: &str, json: &str) -> (PathBuf, Vec<PathBuf>) {
+    let dir = std::env::temp_dir().join(format!(
+        "fractal-slicer-golden-{case}-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut job: Job = serde_json::from_str(json).expect("valid job");
+    job.outputs = job.outputs.iter().map(|path| dir.join(path)).collect();
+    job.complex = job.complex.map(|path| dir.join(path));
+    let report = job.run().unwrap();
+    let mut files: Vec<PathBuf> = report
+        .artifacts
+        .iter()
+        .map(|artifact| artifact.path.strip_prefix(&dir).unwrap().to_path_buf())
+        .collect();
+    files.sort();
+    (dir, files)
+}
+
+/// Files under `dir`, relative to it.
+fn listing(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(next) = pending.pop() {
+        for entry in std::fs::read_dir(&next).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path.strip_prefix(dir).unwrap().to_path_buf());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() <= TOLERANCE * a.abs().max(b.abs()).max(1.0)
+}
+
+/// Where `actual` first differs from `expected`, if it does.
+fn difference(file: &Path, expected: &[u8], actual: &[u8]) -> Option<String> {
+    let is_stl = file.extension().is_some_and(|e| e == "stl");
+    match (std::str::from_utf8(expected), std::str::from_utf8(actual)) {
+        (Ok(expected), Ok(actual)) => text_difference(expected, actual),
+        _ if is_stl && expected.len() == actual.len() && expected.len() >= 84 => {
+            stl_difference(expected, actual)
+        }
+        _ => bytes_difference(expected, actual),
+    }
+}
+
+/// Compares text token by token, numbers within the tolerance.
+fn text_difference(expected: &str, actual: &str) -> Option<String> {
+    let split = |c: char| c.is_whitespace() || ",[]{}:\"/".contains(c);
+    let lines = expected.lines().zip(actual.lines()).enumerate();
+    for (number, (e, a)) in lines {
+        let (mut es, mut as_) = (e.split(split), a.split(split));
+        loop {
+            let (e, a) = match (es.next(), as_.next()) {
+                (None, None) => break,
+                (Some(e), Some(a)) if e == a => continue,
+                (Some(e), Some(a)) => match (e.parse::<f64>(), a.parse::<f64>()) {
+                    (Ok(x), Ok(y)) if close(x, y) => continue,
+                    _ => (e, a),
+                },
+                (e, a) => (e.unwrap_or("end of line"), a.unwrap_or("end of line")),
+            };
+            return Some(format!("line {}:\n- {e}\n+ {a}", number + 1));
+        }
+    }
+    let (e, a) = (expected.lines().count(), actual.lines().count());
+    (e != a).then(|| format!("{e} lines expected, {a} written"))
+}
+
+/// Compares binary STL with its coordinates within the tolerance.
+fn stl_difference(expected: &[u8], actual: &[u8]) -> Option<String> {
+    if expected[..84] != actual[..84] {
+        return bytes_difference(&expected[..84], &actual[..84]);
+    }
+    let records = expected[84..].chunks(50).zip(actual[84..].chunks(50));
+    for (i, (e, a)) in records.enumerate() {
+        let float =
+            |r: &[u8], k: usize| f32::from_le_bytes(r[k * 4..k * 4 + 4].try_into().unwrap());
+        if let Some(k) = (0..12).find(|&k| !close(float(e, k) as f64, float(a, k) as f64)) {
+            return Some(format!(
+                "triangle {i}, float {k}: expected {}, wrote {}",
+                float(e, k),
+                float(a, k)
+            ));
+        }
+        if e[48..] != a[48..] {
+            return Some(format!("triangle {i}: attribute bytes differ"));
+        }
+    }
+    None
+}
+
+fn bytes_difference(expected: &[u8], actual: &[u8]) -> Option<String> {
+    match expected.iter().zip(actual).position(|(e, a)| e != a) {
+        Some(offset) => Some(format!(
+            "byte {offset}: expected {:#04x}, wrote {:#04x}",
+            expected[offset], actual[offset]
+        )),
+        None if expected.len() != actual.len() => Some(format!(
+            "{} bytes expected, {} written",
+            expected.len(),
+            actual.len()
+        )),
+        None => None,
+    }
+}
+
+#[test]
+fn exports_match_their_golden_files() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+    for (case, json) in CASES {
+        let (dir, files) = run(case, json);
+        let golden = golden_dir(case);
+        if update {
+            let _ = std::fs::remove_dir_all(&golden);
+            for file in &files {
+                let path = golden.join(file);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::copy(dir.join(file), path).unwrap();
+            }
+            continue;
+        }
+        assert!(
+            golden.is_dir(),
+            "no golden files for {case}; run with UPDATE_GOLDEN=1 to write them"
+        );
+        let expected = listing(&golden);
+        if expected != files {
+            failures.push(format!(
+                "{case}: expected files {expected:?}, wrote {files:?}"
+            ));
+            continue;
+        }
+        for file in &files {
+            let expected = std::fs::read(golden.join(file)).unwrap();
+            let actual = std::fs::read(dir.join(file)).unwrap();
+            if let Some(difference) = difference(file, &expected, &actual) {
+                failures.push(format!("{case}/{}: {difference}", file.display()));
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn the_comparison_tolerates_float_noise_but_not_changes() {
+    let file = Path::new("a.obj");
+    assert_eq!(
+        difference(
+            file,
+            b"v 0.5 1 2\nf 1/1 2 3\n",
+            b"v 0.5000001 1 2\nf 1/1 2 3\n"
+        ),
+        None
+    );
+    assert!(difference(file, b"v 0.5 1 2\n", b"v 0.6 1 2\n").is_some());
+    assert!(difference(file, b"f 1 2 3\n", b"f 1 2 4\n").is_some());
+    assert!(difference(file, b"v 0 0 0\n", b"v 0 0 0\nv 1 1 1\n").is_some());
+    assert!(difference(Path::new("a.glb"), &[0, 159, 1], &[0, 159, 2]).is_some());
+}
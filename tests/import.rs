@@ -0,0 +1,188 @@
+//! Reading STL and OBJ models and voxelizing them, and the grids that are
+//! refused before anything is allocated.
+
+mod common;
+
+use std::path::Path;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::error::Error;
+use fractal_slicer_4_d::import::{
+    parse_obj, parse_stl, voxelize, voxelize_fill, Import, Voxelizer,
+};
+use fractal_slicer_4_d::mesh::Mesh;
+
+use common::scratch;
+
+/// An OBJ cube `side` units wide at the origin, its faces quads wound
+/// outwards.
+fn cube_obj(side: f64) -> String {
+    let mut obj = String::new();
+    for [x, y, z] in [
+        [0, 0, 0],
+        [1, 0, 0],
+        [1, 1, 0],
+        [0, 1, 0],
+        [0, 0, 1],
+        [1, 0, 1],
+        [1, 1, 1],
+        [0, 1, 1],
+    ] {
+        let [x, y, z] = [x, y, z].map(|c| c as f64 * side);
+        obj += &format!("v {x} {y} {z}\n");
+    }
+    obj += "f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 3 4 8 7\nf 1 5 8 4\nf 2 3 7 6\n";
+    obj
+}
+
+fn ascii_stl(mesh: &Mesh) -> String {
+    let mut stl = "solid cube\n".to_string();
+    for triangle in mesh.triangles() {
+        stl += "  facet normal 0 0 0\n    outer loop\n";
+        for v in triangle {
+            let [x, y, z] = mesh.vertices[v as usize];
+            stl += &format!("      vertex {x} {y} {z}\n");
+        }
+        stl += "    endloop\n  endfacet\n";
+    }
+    stl + "endsolid cube\n"
+}
+
+fn binary_stl(mesh: &Mesh) -> Vec<u8> {
+    let triangles = mesh.triangles();
+    let mut stl = vec![0; 80];
+    stl.extend((triangles.len() as u32).to_le_bytes());
+    for triangle in triangles {
+        stl.extend([0u8; 12]);
+        for v in triangle {
+            for c in mesh.vertices[v as usize] {
+                stl.extend((c as f32).to_le_bytes());
+            }
+        }
+        stl.extend([0u8; 2]);
+    }
+    stl
+}
+
+fn model(dir: &Path, name: &str, text: &str, cell_size: f64) -> Import {
+    let model = dir.join(name);
+    std::fs::write(&model, text).unwrap();
+    Import {
+        model,
+        cell_size,
+        voxelizer: Voxelizer::Parity,
+    }
+}
+
+#[test]
+fn obj_polygons_are_fanned_into_welded_triangles() {
+    let mesh = parse_obj(&cube_obj(1.0), "cube.obj").unwrap();
+    assert_eq!(mesh.vertices.len(), 8);
+    assert_eq!(mesh.triangles().len(), 12);
+
+    // Negative indices count back from the latest vertex; texture and
+    // normal indices are skipped.
+    let relative = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nf -3/1 -2/1/1 -1//1\n";
+    let triangle = parse_obj(relative, "relative.obj").unwrap();
+    assert_eq!(
+        triangle.vertices,
+        [[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+    );
+    assert_eq!(triangle.triangles(), [[0, 1, 2]]);
+
+    for (text, reason) in [
+        ("v 0 0\n", "line 1: malformed vertex"),
+        (
+            "v 0 0 0\nf 1 2 3\n",
+            "line 2: face refers to a missing vertex",
+        ),
+        (
+            "v 0 0 0\nv 1 0 0\nf 1 2\n",
+            "line 3: faces must have at least three",
+        ),
+        ("v 0 0 0\nf 1 x 1\n", "line 2: malformed face"),
+    ] {
+        let error = parse_obj(text, "bad.obj").unwrap_err().to_string();
+        assert!(error.contains(reason), "{error}");
+    }
+}
+
+#[test]
+fn ascii_and_binary_stl_read_as_the_obj() {
+    let mesh = parse_obj(&cube_obj(2.0), "cube.obj").unwrap();
+    assert_eq!(
+        parse_stl(ascii_stl(&mesh).as_bytes(), "cube.stl").unwrap(),
+        mesh
+    );
+    assert_eq!(parse_stl(&binary_stl(&mesh), "cube.stl").unwrap(), mesh);
+
+    for (bytes, reason) in [
+        (&b"not a model"[..], "not an STL file"),
+        (b"solid x\nvertex 0 0\n", "malformed vertex"),
+        (b"solid x\nvertex 0 0 0\nvertex 1 0 0\n", "three vertices"),
+    ] {
+        let error = parse_stl(bytes, "bad.stl").unwrap_err().to_string();
+        assert!(error.contains(reason), "{error}");
+    }
+}
+
+#[test]
+fn both_voxelizers_fill_a_grid_aligned_cube() {
+    let cancel = CancelToken::new();
+    let mesh = parse_obj(&cube_obj(3.0), "cube.obj").unwrap();
+    let inside = voxelize(&mesh, [7, 7, 7], [-2.0; 3], 1.0, &cancel).unwrap();
+    assert_eq!(inside.count(), 27);
+    for p in [[2, 2, 2], [4, 4, 4], [3, 2, 4]] {
+        assert!(inside.get(p), "{p:?}");
+    }
+    for p in [[1, 3, 3], [5, 3, 3], [3, 3, 1]] {
+        assert!(!inside.get(p), "{p:?}");
+    }
+    // Filling keeps the cells the faces touch too, on either side of
+    // faces on cell boundaries, but nothing further out.
+    let filled = voxelize_fill(&mesh, [7, 7, 7], [-2.0; 3], 1.0, &cancel).unwrap();
+    for x in 0..7 {
+        for y in 0..7 {
+            for z in 0..7 {
+                let p = [x, y, z];
+                if inside.get(p) {
+                    assert!(filled.get(p), "{p:?}");
+                }
+                if p.iter().any(|&c| c == 0 || c == 6) {
+                    assert!(!filled.get(p), "{p:?}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn imports_lay_their_grid_over_the_model() {
+    let dir = scratch("grid");
+    let import = model(&dir, "cube.obj", &cube_obj(3.0), 0.5);
+    let grid = import.grid().unwrap();
+    assert_eq!(grid.shape, [6, 6, 6]);
+    assert_eq!(grid.origin, [0.0; 3]);
+    let lattice = import.voxelize(&grid, &CancelToken::new()).unwrap();
+    assert_eq!(lattice.count(), 216);
+}
+
+#[test]
+fn unbounded_grids_are_refused() {
+    let dir = scratch("unbounded");
+    let tiny = model(&dir, "cube.obj", &cube_obj(1.0), 1e-9);
+    let error = tiny.grid().err().unwrap();
+    assert!(matches!(error, Error::InvalidJob(_)), "{error}");
+    assert!(error.to_string().contains("too large"), "{error}");
+
+    for (name, vertex) in [
+        ("nan", "nan 0 0"),
+        ("inf", "0 inf 0"),
+        ("huge", "0 0 1e308"),
+    ] {
+        let text = cube_obj(1.0).replacen("v 0 0 0", &format!("v {vertex}"), 1);
+        let import = model(&dir, &format!("{name}.obj"), &text, 1.0);
+        let error = import.grid().err().unwrap();
+        assert!(matches!(error, Error::InvalidJob(_)), "{name}: {error}");
+    }
+}
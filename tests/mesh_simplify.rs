@@ -0,0 +1,124 @@
+//! Quadric simplification of a sponge keeps it watertight and within its
+//! error bound.
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::lattice::{Boundary, Lattice3};
+use fractal_slicer_4_d::mesh::{build_indexed_mesh, simplify, validate, FaceKind, Mesh, Simplify};
+use fractal_slicer_4_d::rule::Rule;
+
+/// The depth-2 Menger sponge, 2112 triangles.
+fn sponge() -> Mesh {
+    let lattice = Lattice3::generate_recursive(&Rule::menger(3), 2, &CancelToken::new()).unwrap();
+    build_indexed_mesh(&lattice, FaceKind::Triangles, Boundary::Open)
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| a[i] - b[i])
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (0..3).map(|i| a[i] * b[i]).sum()
+}
+
+/// Distance from `p` to the nearest point of the axis-aligned triangle
+/// `t`, as every triangle of a voxel surface is: clamped to the
+/// triangle's bounding square, which the triangle fills on its side of
+/// the diagonal.
+fn distance_to(p: [f64; 3], [a, b, c]: [[f64; 3]; 3]) -> f64 {
+    let (lo, hi): (Vec<f64>, Vec<f64>) = (0..3)
+        .map(|i| (a[i].min(b[i]).min(c[i]), a[i].max(b[i]).max(c[i])))
+        .unzip();
+    let mut q: [f64; 3] = std::array::from_fn(|i| p[i].clamp(lo[i], hi[i]));
+    // Pull `q` back across the hypotenuse if it left the triangle.
+    let (corner, far) = if dot(sub(b, a), sub(c, a)) == 0.0 {
+        (a, [b, c])
+    } else if dot(sub(a, b), sub(c, b)) == 0.0 {
+        (b, [a, c])
+    } else {
+        (c, [a, b])
+    };
+    let edge = sub(far[1], far[0]);
+    let normal = sub(sub(corner, far[0]), {
+        let t = dot(sub(corner, far[0]), edge) / dot(edge, edge);
+        edge.map(|e| e * t)
+    });
+    let beyond = dot(sub(q, far[0]), normal);
+    if beyond < 0.0 {
+        let scale = beyond / dot(normal, normal);
+        q = std::array::from_fn(|i| q[i] - normal[i] * scale);
+    }
+    dot(sub(p, q), sub(p, q)).sqrt()
+}
+
+/// Distance from `p` to the nearest triangle of `mesh`.
+fn distance_to_mesh(p: [f64; 3], mesh: &Mesh) -> f64 {
+    mesh.triangles()
+        .iter()
+        .map(|t| distance_to(p, t.map(|v| mesh.vertices[v as usize])))
+        .fold(f64::INFINITY, f64::min)
+}
+
+#[test]
+fn zero_error_merges_only_coplanar_triangles() {
+    let original = sponge();
+    let mut mesh = original.clone();
+    let limits = Simplify {
+        max_triangles: None,
+        max_error: Some(0.0),
+    };
+    let report = simplify(&mut mesh, &limits);
+    assert_eq!(report.triangles_before, 2112);
+    assert!(report.triangles_after < report.triangles_before);
+    assert_eq!(report.error, 0.0);
+    let validation = validate(&mesh);
+    assert!(validation.is_watertight(), "{validation:?}");
+    assert!((validation.signed_volume - 400.0).abs() < 1e-9);
+    for &v in &mesh.vertices {
+        assert!(original.vertices.contains(&v), "{v:?} moved");
+    }
+}
+
+#[test]
+fn max_error_bounds_the_distance_to_the_original_surface() {
+    let original = sponge();
+    for max_error in [0.5, 1.0, 2.0] {
+        let mut mesh = original.clone();
+        let limits = Simplify {
+            max_triangles: Some(0),
+            max_error: Some(max_error),
+        };
+        let report = simplify(&mut mesh, &limits);
+        assert!(report.error <= max_error, "{report:?}");
+        let validation = validate(&mesh);
+        assert!(validation.is_watertight(), "{validation:?}");
+        for &v in &mesh.vertices {
+            let distance = distance_to_mesh(v, &original);
+            assert!(distance <= max_error + 1e-9, "{v:?} is {distance} away");
+        }
+    }
+}
+
+#[test]
+fn max_triangles_is_met_while_topology_allows() {
+    for max_triangles in [1500, 1000, 800] {
+        let mut mesh = sponge();
+        let limits = Simplify {
+            max_triangles: Some(max_triangles),
+            max_error: None,
+        };
+        let report = simplify(&mut mesh, &limits);
+        assert!(report.triangles_after <= max_triangles, "{report:?}");
+        assert_eq!(mesh.triangles().len(), report.triangles_after);
+        let validation = validate(&mesh);
+        assert!(validation.is_watertight(), "{validation:?}");
+    }
+    // The sponge's tunnels stop collapses short of an empty target.
+    let mut mesh = sponge();
+    let limits = Simplify {
+        max_triangles: Some(0),
+        max_error: None,
+    };
+    let report = simplify(&mut mesh, &limits);
+    assert!(report.triangles_after > 0);
+    assert!(validate(&mesh).is_watertight());
+}
@@ -0,0 +1,150 @@
+//! The defects `validate` counts, each introduced on its own, and `repair`
+//! undoing those it can.
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::lattice::{Boundary, Lattice3};
+use fractal_slicer_4_d::mesh::{
+    build_indexed_mesh, repair, validate, FaceKind, Mesh, Polygons, RepairReport, ValidationReport,
+};
+use fractal_slicer_4_d::rule::Rule;
+
+/// A unit cube of six outward quads.
+fn cube() -> Mesh {
+    let vertices = (0..8)
+        .map(|i| [(i & 1) as f64, (i >> 1 & 1) as f64, (i >> 2) as f64])
+        .collect();
+    Mesh {
+        vertices,
+        faces: Polygons::Quads(vec![
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ]),
+    }
+}
+
+fn quads(mesh: &mut Mesh) -> &mut Vec<[u32; 4]> {
+    match &mut mesh.faces {
+        Polygons::Quads(quads) => quads,
+        Polygons::Triangles(_) => unreachable!("built as quads"),
+    }
+}
+
+fn sponge() -> Mesh {
+    let lattice = Lattice3::generate_recursive(&Rule::menger(3), 2, &CancelToken::new()).unwrap();
+    build_indexed_mesh(&lattice, FaceKind::Quads, Boundary::Open)
+}
+
+#[test]
+fn a_cube_is_watertight() {
+    let report = validate(&cube());
+    assert_eq!(
+        report,
+        ValidationReport {
+            signed_volume: 1.0,
+            ..Default::default()
+        }
+    );
+    assert!(report.is_watertight());
+}
+
+#[test]
+fn a_flipped_face_makes_its_four_edges_inconsistent() {
+    let mut mesh = cube();
+    quads(&mut mesh)[1].reverse();
+    let report = validate(&mesh);
+    assert_eq!(report.inconsistent_edges, 4);
+    assert_eq!(report.boundary_edges, 0);
+    assert_eq!(report.non_manifold_edges, 0);
+    assert!(!report.is_watertight());
+    assert_eq!(
+        repair(&mut mesh),
+        RepairReport {
+            flipped_faces: 1,
+            ..Default::default()
+        }
+    );
+    assert!(validate(&mesh).is_watertight());
+}
+
+#[test]
+fn a_duplicate_face_is_counted_and_removed() {
+    let mut mesh = cube();
+    let copy = quads(&mut mesh)[2];
+    quads(&mut mesh).push(copy);
+    let report = validate(&mesh);
+    assert_eq!(report.duplicate_faces, 1);
+    // The copy's edges are each used by three faces.
+    assert_eq!(report.non_manifold_edges, 4);
+    assert!(!report.is_watertight());
+    assert_eq!(repair(&mut mesh).removed_duplicates, 1);
+    assert!(validate(&mesh).is_watertight());
+}
+
+#[test]
+fn a_missing_face_leaves_one_hole_of_four_edges() {
+    let mut mesh = cube();
+    quads(&mut mesh).remove(3);
+    let report = validate(&mesh);
+    assert_eq!(report.boundary_edges, 4);
+    assert_eq!(report.holes, 1);
+    assert_eq!(report.non_manifold_edges, 0);
+    assert_eq!(report.inconsistent_edges, 0);
+    assert!(!report.is_watertight());
+    // Holes are reported, not filled.
+    repair(&mut mesh);
+    assert_eq!(validate(&mesh).holes, 1);
+}
+
+#[test]
+fn a_fin_on_an_edge_is_non_manifold() {
+    let mut mesh = cube();
+    // A quad sticking out of the edge from vertex 0 to vertex 1.
+    mesh.vertices.extend([[0.0, -1.0, -1.0], [1.0, -1.0, -1.0]]);
+    quads(&mut mesh).push([0, 1, 9, 8]);
+    let report = validate(&mesh);
+    assert_eq!(report.non_manifold_edges, 1);
+    assert_eq!(report.boundary_edges, 3);
+    assert_eq!(report.holes, 1);
+    assert!(!report.is_watertight());
+}
+
+#[test]
+fn a_degenerate_face_is_counted_and_removed() {
+    let mut mesh = cube();
+    quads(&mut mesh).push([0, 1, 1, 0]);
+    assert_eq!(validate(&mesh).degenerate_faces, 1);
+    assert_eq!(repair(&mut mesh).removed_degenerate, 1);
+    assert!(validate(&mesh).is_watertight());
+}
+
+#[test]
+fn repair_restores_a_damaged_sponge() {
+    let mut mesh = sponge();
+    let original = validate(&mesh);
+    let faces = quads(&mut mesh);
+    let count = faces.len();
+    for face in faces.iter_mut().step_by(7) {
+        face.reverse();
+    }
+    faces.extend_from_within(..count / 10);
+    faces.push([0, 0, 1, 1]);
+    // Turn the whole surface inside out too.
+    for face in faces.iter_mut() {
+        face.reverse();
+    }
+    let damaged = validate(&mesh);
+    assert!(damaged.inconsistent_edges > 0);
+    assert_eq!(damaged.duplicate_faces, count / 10);
+    assert_eq!(damaged.degenerate_faces, 1);
+
+    let report = repair(&mut mesh);
+    assert_eq!(report.removed_duplicates, count / 10);
+    assert_eq!(report.removed_degenerate, 1);
+    let repaired = validate(&mesh);
+    assert_eq!(repaired, original);
+    assert!(repaired.is_watertight(), "{repaired:?}");
+}
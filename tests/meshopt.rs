@@ -0,0 +1,313 @@
+//! `EXT_meshopt_compression` streams decoded back by a port of
+//! meshoptimizer's reference decoders, `decodeVertexBuffer` (version 0)
+//! and `decodeIndexBuffer` (version 1).
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::export::meshopt::{encode_triangles, encode_vertices};
+use fractal_slicer_4_d::lattice::{Boundary, Lattice3};
+use fractal_slicer_4_d::mesh::{build_indexed_mesh, FaceKind};
+use fractal_slicer_4_d::rule::Rule;
+
+/// A deterministic xorshift stream, so failures reproduce.
+struct Noise(u64);
+
+impl Noise {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        (self.next() % u64::from(n)) as u32
+    }
+}
+
+fn decode_vertices(encoded: &[u8], count: usize, stride: usize) -> Vec<u8> {
+    assert_eq!(encoded[0], 0xa0, "version 0 header");
+    let tail = stride.max(32);
+    assert!(encoded.len() > tail, "a header and the tail");
+    let mut last = encoded[encoded.len() - stride..].to_vec();
+    let block = ((8192 / stride) & !15).min(256);
+    let mut data = &encoded[1..];
+    let mut out = vec![0; count * stride];
+    let mut buffer = [0u8; 256];
+    for start in (0..count).step_by(block) {
+        let vertices = block.min(count - start);
+        for (k, previous) in last.iter_mut().enumerate() {
+            data = decode_bytes(data, &mut buffer[..vertices.next_multiple_of(16)]);
+            for (i, &delta) in buffer[..vertices].iter().enumerate() {
+                let v = (delta >> 1 ^ (delta & 1).wrapping_neg()).wrapping_add(*previous);
+                out[(start + i) * stride + k] = v;
+                *previous = v;
+            }
+        }
+    }
+    assert_eq!(data.len(), tail, "the blocks end where the tail starts");
+    out
+}
+
+fn decode_bytes<'a>(data: &'a [u8], buffer: &mut [u8]) -> &'a [u8] {
+    let groups = buffer.len() / 16;
+    let (header, mut data) = data.split_at(groups.div_ceil(4));
+    for (i, group) in buffer.chunks_exact_mut(16).enumerate() {
+        let bits = 1 << (header[i / 4] >> (i % 4 * 2) & 3);
+        data = match bits {
+            1 => {
+                group.fill(0);
+                data
+            }
+            8 => {
+                group.copy_from_slice(&data[..16]);
+                &data[16..]
+            }
+            _ => {
+                let sentinel = (1u8 << bits) - 1;
+                let (packed, mut escaped) = data.split_at(16 * bits / 8);
+                for (i, value) in group.iter_mut().enumerate() {
+                    let shift = 8 - bits - (i * bits) % 8;
+                    *value = packed[i * bits / 8] >> shift & sentinel;
+                    if *value == sentinel {
+                        *value = escaped[0];
+                        escaped = &escaped[1..];
+                    }
+                }
+                escaped
+            }
+        };
+    }
+    data
+}
+
+/// The decoder's recent edges and vertices.
+struct Fifo {
+    edges: [[u32; 2]; 16],
+    edge_offset: usize,
+    vertices: [u32; 16],
+    vertex_offset: usize,
+}
+
+impl Fifo {
+    fn edge(&self, age: u8) -> [u32; 2] {
+        self.edges[(self.edge_offset + 15 - usize::from(age)) & 15]
+    }
+
+    /// The vertex pushed `back` pushes ago, counting from one.
+    fn vertex(&self, back: u8) -> u32 {
+        self.vertices[(self.vertex_offset + 16 - usize::from(back)) & 15]
+    }
+
+    fn push_edge(&mut self, a: u32, b: u32) {
+        self.edges[self.edge_offset] = [a, b];
+        self.edge_offset = (self.edge_offset + 1) & 15;
+    }
+
+    fn push_vertex(&mut self, v: u32, push: bool) {
+        if push {
+            self.vertices[self.vertex_offset] = v;
+            self.vertex_offset = (self.vertex_offset + 1) & 15;
+        }
+    }
+}
+
+fn decode_triangles(encoded: &[u8], index_count: usize) -> Vec<[u32; 3]> {
+    assert_eq!(encoded[0], 0xe1, "version 1 header");
+    let triangles = index_count / 3;
+    let codes = &encoded[1..1 + triangles];
+    let aux_table = &encoded[encoded.len() - 16..];
+    let mut data = &encoded[1 + triangles..encoded.len() - 16];
+    let mut fifo = Fifo {
+        edges: [[u32::MAX; 2]; 16],
+        edge_offset: 0,
+        vertices: [u32::MAX; 16],
+        vertex_offset: 0,
+    };
+    let mut next = 0u32;
+    let mut last = 0u32;
+    let mut out = Vec::with_capacity(triangles);
+    for &code in codes {
+        if code < 0xf0 {
+            let [a, b] = fifo.edge(code >> 4);
+            let fec = code & 15;
+            let c = match fec {
+                0 => {
+                    next += 1;
+                    next - 1
+                }
+                1..13 => fifo.vertex(fec + 1),
+                13 => last.wrapping_sub(1),
+                14 => last.wrapping_add(1),
+                _ => decode_free(&mut data, last),
+            };
+            if fec >= 13 {
+                last = c;
+            }
+            fifo.push_vertex(c, fec == 0 || fec >= 13);
+            fifo.push_edge(c, b);
+            fifo.push_edge(a, c);
+            out.push([a, b, c]);
+        } else {
+            let (fea, aux) = if code < 0xfe {
+                (0, aux_table[usize::from(code & 15)])
+            } else {
+                let aux = data[0];
+                data = &data[1..];
+                (if code == 0xfe { 0 } else { 15 }, aux)
+            };
+            let codes = [fea, aux >> 4, aux & 15];
+            let mut triangle = codes.map(|fe| match fe {
+                0 => {
+                    next += 1;
+                    next - 1
+                }
+                15 => 0,
+                _ => fifo.vertex(fe),
+            });
+            for (v, fe) in triangle.iter_mut().zip(codes) {
+                if fe == 15 {
+                    *v = decode_free(&mut data, last);
+                    last = *v;
+                }
+            }
+            for (v, fe) in triangle.into_iter().zip(codes) {
+                fifo.push_vertex(v, fe == 0 || fe == 15);
+            }
+            let [a, b, c] = triangle;
+            fifo.push_edge(b, a);
+            fifo.push_edge(c, b);
+            fifo.push_edge(a, c);
+            out.push(triangle);
+        }
+    }
+    assert!(data.is_empty(), "{} bytes left over", data.len());
+    out
+}
+
+fn decode_free(data: &mut &[u8], last: u32) -> u32 {
+    let mut v = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = data[0];
+        *data = &data[1..];
+        v |= u32::from(byte & 127) << shift;
+        if byte < 128 {
+            break;
+        }
+    }
+    last.wrapping_add((v >> 1) ^ (v & 1).wrapping_neg())
+}
+
+/// `triangle` turned to start at its smallest index, which keeps the
+/// winding the decoder must preserve but not the rotation it may change.
+fn canonical(triangle: [u32; 3]) -> [u32; 3] {
+    let start = (0..3).min_by_key(|&i| triangle[i]).unwrap();
+    std::array::from_fn(|i| triangle[(start + i) % 3])
+}
+
+fn assert_triangles_round_trip(triangles: &[[u32; 3]]) {
+    let encoded = encode_triangles(triangles.concat().as_slice());
+    let decoded = decode_triangles(&encoded, triangles.len() * 3);
+    assert_eq!(decoded.len(), triangles.len());
+    for (i, (&got, &want)) in decoded.iter().zip(triangles).enumerate() {
+        assert_eq!(canonical(got), canonical(want), "triangle {i}");
+    }
+}
+
+#[test]
+fn vertices_round_trip_at_every_stride() {
+    let mut noise = Noise(0x9e37_79b9_7f4a_7c15);
+    for stride in [4, 8, 12, 16] {
+        for count in [0, 1, 15, 16, 17, 255, 256, 257, 600, 1000] {
+            // Small steps, which pack into narrow groups with escapes, and
+            // noise, which packs into whole bytes.
+            let mut smooth = vec![0u8; stride];
+            let mut data = Vec::with_capacity(count * stride);
+            for i in 0..count {
+                for byte in &mut smooth {
+                    *byte = byte.wrapping_add((noise.below(7) as u8).wrapping_sub(3));
+                }
+                if i % 3 == 0 {
+                    smooth[noise.below(stride as u32) as usize] = noise.next() as u8;
+                }
+                data.extend_from_slice(&smooth);
+            }
+            let encoded = encode_vertices(&data, stride);
+            assert_eq!(
+                decode_vertices(&encoded, count, stride),
+                data,
+                "{count} smooth vertices of {stride} bytes"
+            );
+
+            let data: Vec<u8> = (0..count * stride).map(|_| noise.next() as u8).collect();
+            let encoded = encode_vertices(&data, stride);
+            assert_eq!(
+                decode_vertices(&encoded, count, stride),
+                data,
+                "{count} noisy vertices of {stride} bytes"
+            );
+        }
+    }
+}
+
+#[test]
+fn constant_vertices_compress_to_zero_bit_groups() {
+    let data = [1, 2, 3, 4, 5, 6, 7, 8].repeat(1000);
+    let encoded = encode_vertices(&data, 8);
+    assert_eq!(decode_vertices(&encoded, 1000, 8), data);
+    // Four blocks of 256, each byte of each block only a header of sixteen
+    // zero-bit groups, then the tail.
+    assert_eq!(encoded.len(), 1 + 4 * 8 * 4 + 32);
+}
+
+#[test]
+fn empty_streams_hold_only_their_headers_and_tails() {
+    for stride in [4, 8, 12, 16] {
+        let encoded = encode_vertices(&[], stride);
+        assert_eq!(encoded.len(), 1 + 32);
+        assert!(decode_vertices(&encoded, 0, stride).is_empty());
+    }
+    let encoded = encode_triangles(&[]);
+    assert_eq!(encoded.len(), 1 + 16);
+    assert!(decode_triangles(&encoded, 0).is_empty());
+}
+
+#[test]
+fn sponge_triangles_round_trip() {
+    let lattice = Lattice3::generate_recursive(&Rule::menger(3), 2, &CancelToken::new()).unwrap();
+    let mesh = build_indexed_mesh(&lattice, FaceKind::Triangles, Boundary::Open);
+    let triangles = mesh.triangles();
+    assert!(mesh.vertices.len() > 256);
+    assert_triangles_round_trip(&triangles);
+}
+
+#[test]
+fn scattered_and_shared_triangles_round_trip() {
+    let mut noise = Noise(0x2545_f491_4f6c_dd1d);
+    // Unrelated triangles, whose indices are all free.
+    let scattered: Vec<[u32; 3]> = (0..500)
+        .map(|_| std::array::from_fn(|_| noise.below(100_000)))
+        .collect();
+    assert_triangles_round_trip(&scattered);
+
+    // A strip over a grid, reusing edges and vertices out of order, with
+    // triangles mixed in that share an edge but whose third vertex is one
+    // either side of the last free index, or anywhere.
+    let mut triangles = Vec::new();
+    let width = 40;
+    for row in 0..30u32 {
+        for column in 0..width - 1 {
+            let v = row * width + column;
+            triangles.push([v, v + 1, v + width]);
+            triangles.push([v + 1, v + width + 1, v + width]);
+            match noise.below(6) {
+                0 => triangles.push([v + width, v + width + 1, 90_000 + v]),
+                1 => triangles.push([v + width + 1, v + width, 90_001 + v]),
+                2 => triangles.push([v, v + width, 89_999 + v]),
+                _ => {}
+            }
+        }
+    }
+    assert_triangles_round_trip(&triangles);
+    // The same strip started halfway, so early indices are not `next`.
+    assert_triangles_round_trip(&triangles[triangles.len() / 2..]);
+}
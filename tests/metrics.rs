@@ -0,0 +1,15 @@
+//! Process metrics read from the platform.
+
+#![cfg(target_os = "linux")]
+
+use fractal_slicer_4_d::metrics::resident_memory_bytes;
+
+#[test]
+fn resident_memory_grows_with_touched_pages() {
+    let before = resident_memory_bytes().expect("Linux exposes VmRSS");
+    assert!(before > 1 << 20, "{before} bytes");
+    let touched = vec![1u8; 64 << 20];
+    let after = resident_memory_bytes().unwrap();
+    assert!(after >= before + (48 << 20), "{before} then {after} bytes");
+    drop(std::hint::black_box(touched));
+}
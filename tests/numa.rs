@@ -0,0 +1,95 @@
+//! Sharing a lattice's words out between NUMA nodes and their cores: runs
+//! that tile the lattice, meet only at branch boundaries between nodes,
+//! and fill the same cells as a single thread.
+
+use std::ops::Range;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::lattice::Lattice;
+use fractal_slicer_4_d::numa::{partition, Node};
+use fractal_slicer_4_d::rule::Rule;
+
+/// Nodes with the given numbers of cores, numbered on from 0.
+fn nodes(cores: &[usize]) -> Vec<Node> {
+    let mut next = 0;
+    cores
+        .iter()
+        .enumerate()
+        .map(|(id, &n)| {
+            next += n;
+            Node {
+                id,
+                cpus: (next - n..next).collect(),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn runs_tile_the_words_and_nodes_split_at_branches() {
+    for (words, branches, cores) in [
+        (1000, 9, vec![4, 4]),
+        (1000, 9, vec![3, 5]),
+        (12345, 27, vec![1, 2, 5]),
+        // Fewer branches than cores, and than nodes.
+        (100, 3, vec![8, 8]),
+        (700, 1, vec![4, 4, 4]),
+        // Fewer words than cores.
+        (5, 9, vec![2, 6]),
+    ] {
+        let case = format!("{words} words, {branches} branches, cores {cores:?}");
+        let nodes = nodes(&cores);
+        let parts = partition(words, branches, &nodes);
+        let mut end = 0;
+        for (run, _) in &parts {
+            assert_eq!(run.start, end, "{case}: {parts:?}");
+            assert!(run.start < run.end, "{case}: {parts:?}");
+            end = run.end;
+        }
+        assert_eq!(end, words, "{case}: {parts:?}");
+
+        let boundaries: Vec<usize> = (0..=branches).map(|b| words * b / branches).collect();
+        for (n, node) in nodes.iter().enumerate() {
+            let runs: Vec<&Range<usize>> = parts
+                .iter()
+                .filter(|&&(_, on)| on == n)
+                .map(|(run, _)| run)
+                .collect();
+            assert!(runs.len() <= node.cpus.len(), "{case}: node {n}");
+            if let (Some(first), Some(last)) = (runs.first(), runs.last()) {
+                assert!(boundaries.contains(&first.start), "{case}: node {n}");
+                assert!(boundaries.contains(&last.end), "{case}: node {n}");
+            }
+        }
+        let on: Vec<usize> = parts.iter().map(|&(_, n)| n).collect();
+        assert!(on.is_sorted(), "{case}: {on:?}");
+    }
+}
+
+fn assert_same_cells<const D: usize>(rule: &Rule, depth: u32) {
+    let cancel = CancelToken::new();
+    let shape = std::array::from_fn(|axis| rule.side_along(axis, depth));
+    let solid = |p: [usize; D]| rule.is_solid(&p, depth);
+    let whole = Lattice::from_fn(shape, &cancel, solid).unwrap();
+    // Cores past what a CPU set holds are left out of the pinning.
+    for cores in [vec![1], vec![3, 1], vec![2, 5, 2], vec![1100, 4]] {
+        for branches in [1, 3, 9, 27] {
+            let parted =
+                Lattice::from_fn_partitioned(shape, branches, &nodes(&cores), &cancel, solid)
+                    .unwrap();
+            assert!(
+                parted == whole,
+                "{} in {D}D, {branches} branches on cores {cores:?}",
+                rule.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn partitioned_generation_matches_a_single_thread() {
+    assert_same_cells::<3>(&Rule::menger(3), 3);
+    assert_same_cells::<3>(&Rule::jerusalem(3), 2);
+    assert_same_cells::<4>(&Rule::vicsek(4), 2);
+    assert_same_cells::<2>(&Rule::from_fn_bases("slab", &[5, 2], |d| d[1] != 1), 3);
+}
@@ -0,0 +1,65 @@
+//! The recursive generator against the brute-force filter: exactly the
+//! same cells for every built-in rule, and for hybrid and custom ones,
+//! at every depth up to 3.
+
+mod common;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::lattice::Lattice;
+use fractal_slicer_4_d::rule::Rule;
+
+use common::rules;
+
+const MAX_DEPTH: u32 = 3;
+
+#[allow(deprecated)]
+fn assert_same_cells<const D: usize>() {
+    let cancel = CancelToken::new();
+    for rule in rules(D) {
+        for depth in 0..=MAX_DEPTH {
+            let brute = Lattice::<D>::generate(&rule, depth);
+            let recursive = Lattice::<D>::generate_recursive(&rule, depth, &cancel).unwrap();
+            assert_eq!(
+                recursive.shape(),
+                brute.shape(),
+                "{} in {D}D at depth {depth}",
+                rule.name()
+            );
+            let differing = brute
+                .iter()
+                .filter(|&p| !recursive.get(p))
+                .chain(recursive.iter().filter(|&p| !brute.get(p)))
+                .collect::<Vec<_>>();
+            assert!(
+                differing.is_empty(),
+                "{} in {D}D at depth {depth}: {} cells differ, first {:?}",
+                rule.name(),
+                differing.len(),
+                differing[0]
+            );
+            assert_eq!(recursive.count() as u64, rule.cells(depth));
+        }
+    }
+}
+
+#[test]
+fn recursive_generation_matches_brute_force_in_2d() {
+    assert_same_cells::<2>();
+}
+
+#[test]
+fn recursive_generation_matches_brute_force_in_3d() {
+    assert_same_cells::<3>();
+}
+
+#[test]
+fn recursive_generation_matches_brute_force_in_4d() {
+    assert_same_cells::<4>();
+}
+
+#[test]
+fn recursive_generation_stops_when_cancelled() {
+    let cancel = CancelToken::new();
+    cancel.cancel();
+    assert!(Lattice::<4>::generate_recursive(&Rule::menger(4), 2, &cancel).is_err());
+}
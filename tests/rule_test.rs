@@ -0,0 +1,58 @@
+//! The rule test harness on sound, mistaken and degenerate custom rules.
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::rule_test::{RuleTest, RuleTestReport};
+
+fn run(json: &str) -> RuleTestReport {
+    let test: RuleTest = serde_json::from_str(json).expect("valid rule test");
+    test.run("test", &CancelToken::new()).unwrap()
+}
+
+fn check<'a>(report: &'a RuleTestReport, name: &str) -> &'a [String] {
+    let check = report
+        .checks
+        .iter()
+        .find(|check| check.name == name)
+        .unwrap_or_else(|| panic!("no {name} check"));
+    assert!(!check.passed, "{name} passed:\n{report}");
+    &check.lines
+}
+
+#[test]
+fn sound_rules_pass_every_check() {
+    for json in [
+        r#"{"custom": {"bases": [3, 3, 3]}, "expect": {"survivors": [20], "cells": [20, 400, 8000]}}"#,
+        r#"{"custom": {"bases": [3, 3, 5], "remove": [[1], [1], [1, 2, 3]]}, "depth": 2}"#,
+        r#"{"custom": {"bases": [3, 3, 3, 3], "min_removed": 3}, "depth": 2}"#,
+        r#"{"custom": {"bases": [4, 5], "min_removed": 1}}"#,
+    ] {
+        let report = run(json);
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.checks.len(), 3 + json.contains("expect") as usize);
+    }
+}
+
+#[test]
+fn wrong_expectations_fail_with_a_diff() {
+    let report = run(
+        r#"{"custom": {"bases": [3, 3, 3]}, "depth": 2, "expect": {"survivors": [20], "cells": [20, 401]}}"#,
+    );
+    assert!(!report.passed());
+    assert_eq!(
+        check(&report, "expected"),
+        ["- level 2: 401 cells", "+ level 2: 400 cells"]
+    );
+    assert!(report
+        .checks
+        .iter()
+        .filter(|check| check.name != "expected")
+        .all(|check| check.passed));
+}
+
+#[test]
+fn rules_that_keep_all_or_nothing_fail_the_survivor_check() {
+    let nothing = run(r#"{"custom": {"bases": [3, 3, 3], "min_removed": 0}, "depth": 1}"#);
+    assert!(check(&nothing, "survivors")[0].ends_with("so nothing is left"));
+    let everything = run(r#"{"custom": {"bases": [2, 2, 2], "remove": [[], [], []]}, "depth": 1}"#);
+    assert!(check(&everything, "survivors")[0].ends_with("so nothing is removed"));
+}
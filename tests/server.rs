@@ -0,0 +1,162 @@
+//! Server mode over real connections.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use fractal_slicer_4_d::server::{Access, Quota, Server, ServerConfig};
+
+use common::scratch;
+
+/// Serves `config` on a free local port in the background.
+fn start(config: ServerConfig) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Arc::new(Server::new(config));
+    std::thread::spawn(move || server.serve(listener));
+    addr
+}
+
+/// Sends `request` and returns the status code and body of the response.
+fn send(addr: SocketAddr, request: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let response = String::from_utf8_lossy(&response);
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").map_or("", |(_, body)| body);
+    (status, body.to_string())
+}
+
+fn get(addr: SocketAddr, path: &str) -> (u16, String) {
+    send(addr, &format!("GET {path} HTTP/1.1\r\n\r\n"))
+}
+
+fn post(addr: SocketAddr, path: &str) -> (u16, String) {
+    send(addr, &format!("POST {path} HTTP/1.1\r\n\r\n"))
+}
+
+fn json(body: &str) -> serde_json::Value {
+    serde_json::from_str(body).unwrap()
+}
+
+/// Polls a job until it finishes and returns its status.
+fn wait(addr: SocketAddr, id: u64) -> serde_json::Value {
+    let deadline = Instant::now() + Duration::from_secs(30);
+    loop {
+        let status = json(&get(addr, &format!("/jobs/{id}")).1);
+        if status["state"] != "queued"
+            && status["state"] != "generating"
+            && status["state"] != "writing"
+        {
+            return status;
+        }
+        assert!(Instant::now() < deadline, "job {id} never finished");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn queued_jobs_are_written_to_the_output_location() {
+    let output = scratch("output");
+    let addr = start(ServerConfig {
+        output: Some(output.clone()),
+        ..ServerConfig::default()
+    });
+    let (status, body) = post(addr, "/jobs?depth=1&w=0&format=stl");
+    assert_eq!(status, 202, "{body}");
+    let id = json(&body)["id"].as_u64().unwrap();
+    let status = wait(addr, id);
+    assert_eq!(status["state"], "done", "{status}");
+
+    let path = output.join(format!("job-{id}.stl"));
+    assert_eq!(status["artifact"]["path"], path.to_str().unwrap());
+    let written = std::fs::read(&path).unwrap();
+    assert_eq!(status["artifact"]["bytes"], written.len());
+    let (code, _) = get(addr, &format!("/jobs/{id}/result"));
+    assert_eq!(code, 200);
+}
+
+#[test]
+fn oversized_headers_get_a_431() {
+    let addr = start(ServerConfig::default());
+    // Exactly the limit, with no end to the headers in sight, so the
+    // server reads everything sent before answering.
+    let mut request = "GET /jobs HTTP/1.1\r\nX-Padding: ".to_string();
+    request.extend(std::iter::repeat_n('a', 16 * 1024 - request.len()));
+    let (status, body) = send(addr, &request);
+    assert_eq!(status, 431, "{body}");
+    let (status, _) = get(addr, "/jobs");
+    assert_eq!(status, 200);
+}
+
+#[test]
+fn stalled_connections_are_dropped() {
+    let addr = start(ServerConfig {
+        io_timeout: Some(Duration::from_millis(200)),
+        ..ServerConfig::default()
+    });
+    let start = Instant::now();
+    let mut idle = TcpStream::connect(addr).unwrap();
+    let mut response = Vec::new();
+    idle.read_to_end(&mut response).unwrap();
+    assert!(response.is_empty());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn connections_wait_for_a_free_handler() {
+    let addr = start(ServerConfig {
+        connections: 1,
+        io_timeout: Some(Duration::from_millis(500)),
+        ..ServerConfig::default()
+    });
+    let _idle = TcpStream::connect(addr).unwrap();
+    let start = Instant::now();
+    let (status, _) = get(addr, "/jobs");
+    assert_eq!(status, 200);
+    assert!(
+        start.elapsed() >= Duration::from_millis(400),
+        "served beside the stalled connection"
+    );
+}
+
+#[test]
+fn concurrent_submissions_stay_within_the_quota() {
+    let addr = start(ServerConfig {
+        workers: 1,
+        access: Some(Access {
+            anonymous: Some(Quota {
+                max_depth: 4,
+                max_jobs: 2,
+                requests_per_minute: 0,
+            }),
+            clients: Vec::new(),
+        }),
+        ..ServerConfig::default()
+    });
+    // Deep enough that none finishes while the others are submitted.
+    let submissions: Vec<_> = (0..16)
+        .map(|_| std::thread::spawn(move || post(addr, "/jobs?depth=4&w=0&format=stl")))
+        .collect();
+    let responses: Vec<_> = submissions
+        .into_iter()
+        .map(|submission| submission.join().unwrap())
+        .collect();
+    let accepted: Vec<u64> = responses
+        .iter()
+        .filter(|(status, _)| *status == 202)
+        .map(|(_, body)| json(body)["id"].as_u64().unwrap())
+        .collect();
+    assert_eq!(accepted.len(), 2, "{responses:?}");
+    assert!(responses
+        .iter()
+        .all(|(status, _)| *status == 202 || *status == 429));
+    for id in accepted {
+        send(addr, &format!("DELETE /jobs/{id} HTTP/1.1\r\n\r\n"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Shader sources checked by naga's WGSL and GLSL front ends and
+//! validator, and their baked keep-masks read back against the rule.
+
+use fractal_slicer_4_d::error::Error;
+use fractal_slicer_4_d::rule::{Combination, Rule, Split};
+use fractal_slicer_4_d::shader::{source, ShaderLanguage};
+
+/// Every built-in rule, an alternation and an anisotropic rule, in 2D to
+/// 4D.
+fn rules() -> Vec<Rule> {
+    let mut rules = Vec::new();
+    for dims in 2..=4 {
+        for name in Rule::NAMES {
+            rules.push(Rule::by_name(name, dims).unwrap());
+        }
+        let alternate = [Rule::menger(dims), Rule::vicsek(dims)];
+        rules.push(Combination::Alternate.apply(&alternate).unwrap());
+        let bases = &[2, 3, 4, 5][..dims];
+        rules.push(Rule::from_fn_bases("stretched", bases, |d| {
+            d.iter().sum::<u32>() % 3 != 1
+        }));
+    }
+    rules
+}
+
+fn validate(module: &naga::Module) {
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(module)
+    .unwrap();
+}
+
+#[test]
+fn sources_validate_in_both_languages() {
+    for rule in rules() {
+        let name = format!("{} in {}D", rule.name(), rule.dims());
+        let wgsl = source(&rule, 3, ShaderLanguage::Wgsl).unwrap();
+        let module = naga::front::wgsl::parse_str(&wgsl)
+            .unwrap_or_else(|error| panic!("{name}: {}\n{wgsl}", error.emit_to_string(&wgsl)));
+        assert!(module
+            .functions
+            .iter()
+            .any(|(_, f)| f.name.as_deref() == Some("fs_is_solid")));
+        validate(&module);
+
+        // GLSL needs a version and an entry point around the functions.
+        let glsl = source(&rule, 3, ShaderLanguage::Glsl).unwrap();
+        let shader = format!(
+            "#version 450\n{glsl}\nlayout(local_size_x = 1) in;\n\
+             void main() {{ fs_is_solid(uvec{}(0u), FS_DEPTH); }}\n",
+            rule.dims()
+        );
+        let options = naga::front::glsl::Options::from(naga::ShaderStage::Compute);
+        let module = naga::front::glsl::Frontend::default()
+            .parse(&options, &shader)
+            .unwrap_or_else(|error| panic!("{name}: {}\n{shader}", error.emit_to_string(&shader)));
+        validate(&module);
+    }
+}
+
+/// The words of the `FS_MASKS` array in a source.
+fn masks(source: &str) -> Vec<u32> {
+    let start = source.find("FS_MASKS").expect("masks");
+    let body = &source[start..];
+    let body = &body[body.find("(\n").unwrap() + 2..body.find(");").unwrap()];
+    body.split(',')
+        .map(|word| {
+            let word = word.trim().trim_start_matches("0x").trim_end_matches('u');
+            u32::from_str_radix(word, 16).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn baked_masks_reproduce_uniform_rules() {
+    for rule in rules()
+        .into_iter()
+        .filter(|rule| rule.split() == Split::Uniform)
+    {
+        let words = masks(&source(&rule, 2, ShaderLanguage::Wgsl).unwrap());
+        let (subcells, period) = (rule.subcells(), rule.period());
+        assert_eq!(words.len(), (subcells * period).div_ceil(32));
+        let keeps = |level: usize, index: usize| {
+            let bit = (level - 1) % period * subcells + index;
+            words[bit / 32] >> (bit % 32) & 1 != 0
+        };
+        // The shader's loop: digits finest first, at levels counting down.
+        let depth = 2.max(period as u32);
+        let sides: Vec<usize> = (0..rule.dims())
+            .map(|axis| rule.side_along(axis, depth))
+            .collect();
+        let cells: usize = sides.iter().product();
+        let mut cell = vec![0; rule.dims()];
+        for flat in 0..cells {
+            let mut rest = flat;
+            for (c, side) in cell.iter_mut().zip(&sides) {
+                *c = rest % side;
+                rest /= side;
+            }
+            let mut c = cell.clone();
+            let solid = (1..=depth as usize).rev().all(|level| {
+                let digits: Vec<usize> = c
+                    .iter_mut()
+                    .zip(rule.bases())
+                    .map(|(c, &base)| {
+                        let digit = *c % base as usize;
+                        *c /= base as usize;
+                        digit
+                    })
+                    .collect();
+                // The first axis varies fastest, as `rule::compose` has it.
+                let index = digits
+                    .iter()
+                    .zip(rule.bases())
+                    .rev()
+                    .fold(0, |index, (&digit, &base)| index * base as usize + digit);
+                keeps(level, index)
+            });
+            assert_eq!(
+                solid,
+                rule.is_solid(&cell, depth),
+                "{} {cell:?}",
+                rule.name()
+            );
+        }
+    }
+}
+
+#[test]
+fn unsupported_rules_are_refused() {
+    for rule in [
+        Rule::from_fn("line", 3, 1, |d| d[0] != 1),
+        Rule::from_fn("hyper", 2, 5, |d| d[0] == 0),
+    ] {
+        let error = source(&rule, 1, ShaderLanguage::Wgsl).unwrap_err();
+        assert!(matches!(error, Error::InvalidJob(_)), "{error}");
+        assert!(error.to_string().contains("2 to 4 component"), "{error}");
+    }
+    // The flake split counts in signed 32-bit integers.
+    let flake = Rule::octahedron(3);
+    assert!(source(&flake, 30, ShaderLanguage::Glsl).is_ok());
+    let error = source(&flake, 31, ShaderLanguage::Glsl).unwrap_err();
+    assert!(error.to_string().contains("too wide"), "{error}");
+}
@@ -0,0 +1,59 @@
+//! Sweeps against slicing the generated hypercube: the same slice at every
+//! w, whether built from a shared prefix or reused from the last slice.
+
+mod common;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::lattice::Lattice;
+use fractal_slicer_4_d::rule::{Rule, Split};
+use fractal_slicer_4_d::sweep::Sweep;
+
+use common::rules;
+
+#[test]
+fn sweeps_match_slices_of_the_hypercube() {
+    let cancel = CancelToken::new();
+    for rule in rules(4) {
+        for depth in 0..=2 {
+            let hypercube = Lattice::<4>::generate(&rule, depth);
+            let side = hypercube.shape()[3];
+            // Every w forwards, then backwards with each repeated, so runs
+            // of the same key come from the last slice.
+            let ws: Vec<usize> = (0..side)
+                .chain((0..side).rev().flat_map(|w| [w, w]))
+                .collect();
+            let Some(mut sweep) = Sweep::new(&rule, depth, &ws, &cancel).unwrap() else {
+                assert_ne!(rule.split(), Split::Uniform, "{}", rule.name());
+                continue;
+            };
+            assert_eq!(sweep.side(), side);
+            for &w in &ws {
+                assert_eq!(
+                    sweep.slice(w, &cancel).unwrap(),
+                    hypercube.slice_w(w),
+                    "{} at depth {depth}, w {w}",
+                    rule.name()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn alike_digits_share_prefixes() {
+    let cancel = CancelToken::new();
+    let rule = Rule::menger(4);
+    let hypercube = Lattice::<4>::generate(&rule, 3);
+    let ws = [4, 13, 26];
+    let mut sweep = Sweep::new(&rule, 3, &ws, &cancel).unwrap().unwrap();
+    // Menger's w digits 0 and 2 keep alike, so 26 and 0 share a key, and
+    // 8 and 18 the prefix planned for 26.
+    assert_eq!(sweep.key(26), sweep.key(0));
+    for w in ws.into_iter().chain([0, 8, 18]) {
+        assert_eq!(
+            sweep.slice(w, &cancel).unwrap(),
+            hypercube.slice_w(w),
+            "w {w}"
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! The symmetric generator against the brute-force filter: mirroring one
+//! fundamental domain of a rule's symmetries gives exactly the same cells
+//! for every built-in rule, and for hybrid and custom ones, at every depth
+//! up to 3.
+
+mod common;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::lattice::Lattice;
+
+use common::rules;
+
+const MAX_DEPTH: u32 = 3;
+
+fn assert_same_cells<const D: usize>() {
+    let cancel = CancelToken::new();
+    for rule in rules(D) {
+        for depth in 0..=MAX_DEPTH {
+            let brute = Lattice::<D>::generate(&rule, depth);
+            // The mirrored lattice has every symmetry of the masks, so
+            // matching it means the brute-force one has them too.
+            let symmetric = Lattice::<D>::generate_symmetric(&rule, depth, &cancel).unwrap();
+            assert!(
+                symmetric == brute,
+                "{} in {D}D at depth {depth}: {} cells instead of {}",
+                rule.name(),
+                symmetric.count(),
+                brute.count()
+            );
+        }
+    }
+}
+
+#[test]
+fn symmetric_generation_matches_brute_force_in_2d() {
+    assert_same_cells::<2>();
+}
+
+#[test]
+fn symmetric_generation_matches_brute_force_in_3d() {
+    assert_same_cells::<3>();
+}
+
+#[test]
+fn symmetric_generation_matches_brute_force_in_4d() {
+    assert_same_cells::<4>();
+}
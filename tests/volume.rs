@@ -0,0 +1,149 @@
+//! NIfTI volumes written by the exporter read back through a memory map,
+//! and headers that are refused rather than trusted.
+
+mod common;
+
+use std::path::Path;
+
+use fractal_slicer_4_d::cancel::CancelToken;
+use fractal_slicer_4_d::error::Error;
+use fractal_slicer_4_d::export::write_nifti;
+use fractal_slicer_4_d::lattice::Lattice3;
+use fractal_slicer_4_d::rule::Rule;
+use fractal_slicer_4_d::volume::{MappedVolume, Values};
+
+use common::{lattices, options, scratch};
+
+fn nifti(lattice: &Lattice3, extra: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    write_nifti(lattice, &mut bytes, &options(extra), &CancelToken::new()).unwrap();
+    bytes
+}
+
+fn open(dir: &Path, name: &str, bytes: &[u8]) -> Result<MappedVolume, Error> {
+    let path = dir.join(name);
+    std::fs::write(&path, bytes).unwrap();
+    MappedVolume::open(&path)
+}
+
+#[test]
+fn written_volumes_map_back_to_their_lattice() {
+    let dir = scratch("round-trip");
+    for (i, lattice) in lattices().iter().enumerate() {
+        for (extra, values) in [
+            ("", Values::Occupancy),
+            (r#", "images": {"values": "distance"}"#, Values::Distance),
+        ] {
+            let volume = open(&dir, &format!("{i}.nii"), &nifti(lattice, extra)).unwrap();
+            assert_eq!(volume.values(), values);
+            assert_eq!(volume.shape(), lattice.shape());
+            assert_eq!(volume.to_lattice(), *lattice, "{i} {values:?}");
+            let [nx, ny, nz] = lattice.shape();
+            let layer = volume.layer(nz / 2);
+            for x in 0..nx {
+                for y in 0..ny {
+                    assert_eq!(layer.get([x, y, 0]), lattice.get([x, y, nz / 2]));
+                }
+            }
+            assert!(!volume.get([nx, 0, 0]));
+        }
+    }
+}
+
+#[test]
+fn regions_are_clamped_to_the_volume() {
+    let dir = scratch("regions");
+    let lattice = Lattice3::generate(&Rule::menger(3), 2);
+    let volume = open(&dir, "sponge.nii", &nifti(&lattice, "")).unwrap();
+    let region = volume.region([3, 4, 5], [20, 30, 40]);
+    assert_eq!(region.shape(), [6, 5, 4]);
+    for x in 0..6 {
+        for y in 0..5 {
+            for z in 0..4 {
+                assert_eq!(region.get([x, y, z]), lattice.get([x + 3, y + 4, z + 5]));
+            }
+        }
+    }
+    assert_eq!(volume.region([30; 3], [40; 3]).shape(), [0; 3]);
+}
+
+#[test]
+fn malformed_headers_are_refused() {
+    let dir = scratch("malformed");
+    let good = nifti(&Lattice3::generate(&Rule::menger(3), 2), "");
+    // Four dimensions are read when the fourth has a single step.
+    let single = {
+        let mut nifti = good.clone();
+        nifti[40..42].copy_from_slice(&4i16.to_le_bytes());
+        nifti
+    };
+    assert!(open(&dir, "single.nii", &single).is_ok());
+    let patched = |offset: usize, bytes: &[u8]| {
+        let mut nifti = good.clone();
+        nifti[offset..offset + bytes.len()].copy_from_slice(bytes);
+        nifti
+    };
+    for (name, bytes, reason) in [
+        ("short", good[..300].to_vec(), "not a single-file NIfTI-1"),
+        (
+            "size",
+            patched(0, &540i32.to_le_bytes()),
+            "not a single-file NIfTI-1",
+        ),
+        ("pair", patched(344, b"ni1\0"), "not a single-file NIfTI-1"),
+        (
+            "2d",
+            patched(40, &2i16.to_le_bytes()),
+            "expected a 3D volume",
+        ),
+        (
+            "series",
+            {
+                let mut series = patched(40, &4i16.to_le_bytes());
+                series[48..50].copy_from_slice(&2i16.to_le_bytes());
+                series
+            },
+            "expected a 3D volume",
+        ),
+        (
+            "negative",
+            patched(44, &(-9i16).to_le_bytes()),
+            "expected a 3D volume",
+        ),
+        ("int16", patched(70, &4i16.to_le_bytes()), "expected uint8"),
+        (
+            "truncated",
+            good[..good.len() - 1].to_vec(),
+            "shorter than its header",
+        ),
+        (
+            "tall",
+            patched(46, &28i16.to_le_bytes()),
+            "shorter than its header",
+        ),
+        (
+            "inside",
+            patched(108, &100f32.to_le_bytes()),
+            "shorter than its header",
+        ),
+        (
+            "far",
+            patched(108, &1e30f32.to_le_bytes()),
+            "shorter than its header",
+        ),
+        (
+            "infinite",
+            patched(108, &f32::INFINITY.to_le_bytes()),
+            "shorter than its header",
+        ),
+        (
+            "nan",
+            patched(108, &f32::NAN.to_le_bytes()),
+            "shorter than its header",
+        ),
+    ] {
+        let error = open(&dir, &format!("{name}.nii"), &bytes).err().unwrap();
+        assert!(matches!(error, Error::InvalidJob(_)), "{name}: {error}");
+        assert!(error.to_string().contains(reason), "{name}: {error}");
+    }
+}
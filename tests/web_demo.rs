@@ -0,0 +1,36 @@
+//! Web demo bundles hold everything their page loads.
+
+mod common;
+
+use fractal_slicer_4_d::rule::Rule;
+use fractal_slicer_4_d::web_demo;
+
+use common::scratch;
+
+#[test]
+fn bundles_load_nothing_from_other_hosts() {
+    let dir = scratch("offline");
+    let wasm = dir.join("module.wasm");
+    std::fs::write(&wasm, b"\0asm").unwrap();
+    let bundle = dir.join("site");
+    web_demo::write(&Rule::menger(4), 2, &wasm, &bundle).unwrap();
+
+    for file in ["index.html", "fractal-slicer.js", "viewer.js"] {
+        let text = std::fs::read_to_string(bundle.join(file)).unwrap();
+        assert!(
+            !text.contains("http://") && !text.contains("https://"),
+            "{file} refers to another host"
+        );
+        // Every module the file imports is in the bundle.
+        for import in text.split("from '").skip(1) {
+            let path = &import[..import.find('\'').unwrap()];
+            let path = path.strip_prefix("./").expect("a relative import");
+            assert!(bundle.join(path).is_file(), "{file} imports {path}");
+        }
+    }
+    assert_eq!(
+        std::fs::read(bundle.join("fractal_slicer_web.wasm")).unwrap(),
+        b"\0asm"
+    );
+    assert!(bundle.join("rule.bin").is_file());
+}
@@ -0,0 +1,246 @@
+//! The WebAssembly module of `fractal-slicer web-demo` bundles: it reads a
+//! rule the slicer wrote and meshes the 3D slices of its 4D fractal in the
+//! browser, so a demo page needs nothing but static files.
+//!
+//! Its exports are plain functions over the module's memory, called from
+//! the bundle's JavaScript without generated bindings:
+//! * `fs_rule_buffer(len)` — room for a rule of `len` bytes, into which
+//!   the page copies `rule.bin`
+//! * `fs_load_rule()` — reads the rule copied in, 0 on success
+//! * `fs_side(axis, depth)` — the depth-`depth` lattice's side along `axis`
+//! * `fs_slice(depth, w)` — meshes the slice at `w`, returning its vertex
+//!   count, or -1 without a rule or for slices out of range or too large
+//! * `fs_positions()`, `fs_normals()` — the slice's triangles, three
+//!   floats a vertex, valid until the next slice
+//!
+//! A rule is little-endian words: the magic `FSR1`, the number of axes,
+//! the split (0 uniform, 1 Pell, 2 flake), the number of keep-masks, the
+//! bases, then each keep-mask as a bitset of its subcells.
+
+use std::sync::Mutex;
+
+use fractal_slicer_core::{pell, Split};
+
+const MAGIC: u32 = u32::from_le_bytes(*b"FSR1");
+
+/// Most cells a slice may have, bounding the module's memory.
+const MAX_SLICE_CELLS: usize = 1 << 22;
+
+/// The corners of a unit cell's faces, counter-clockwise seen from
+/// outside, and the faces' normals: +x, -x, +y, -y, +z, -z.
+const FACES: [([[u8; 3]; 4], [f32; 3]); 6] = [
+    (
+        [[1, 0, 0], [1, 1, 0], [1, 1, 1], [1, 0, 1]],
+        [1.0, 0.0, 0.0],
+    ),
+    (
+        [[0, 0, 0], [0, 0, 1], [0, 1, 1], [0, 1, 0]],
+        [-1.0, 0.0, 0.0],
+    ),
+    (
+        [[0, 1, 0], [0, 1, 1], [1, 1, 1], [1, 1, 0]],
+        [0.0, 1.0, 0.0],
+    ),
+    (
+        [[0, 0, 0], [1, 0, 0], [1, 0, 1], [0, 0, 1]],
+        [0.0, -1.0, 0.0],
+    ),
+    (
+        [[0, 0, 1], [1, 0, 1], [1, 1, 1], [0, 1, 1]],
+        [0.0, 0.0, 1.0],
+    ),
+    (
+        [[0, 0, 0], [0, 1, 0], [1, 1, 0], [1, 0, 0]],
+        [0.0, 0.0, -1.0],
+    ),
+];
+
+struct Rule {
+    bases: Vec<u32>,
+    split: Split,
+    /// Keep-masks by level, cycled as the slicer's are.
+    masks: Vec<Vec<bool>>,
+}
+
+impl Rule {
+    fn parse(bytes: &[u8]) -> Option<Rule> {
+        let mut words = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()));
+        if words.next()? != MAGIC {
+            return None;
+        }
+        let dims = words.next()? as usize;
+        let split = match words.next()? {
+            0 => Split::Uniform,
+            1 => Split::Pell,
+            2 => Split::Flake,
+            _ => return None,
+        };
+        let period = words.next()? as usize;
+        if dims != 4 || period == 0 {
+            return None;
+        }
+        let bases: Vec<u32> = words.by_ref().take(dims).collect();
+        if bases.len() != dims || bases.iter().any(|&base| base < 2) {
+            return None;
+        }
+        let subcells = bases
+            .iter()
+            .try_fold(1usize, |n, &base| n.checked_mul(base as usize))?;
+        let mut masks = Vec::with_capacity(period);
+        for _ in 0..period {
+            let bits: Vec<u32> = words.by_ref().take(subcells.div_ceil(32)).collect();
+            if bits.len() != subcells.div_ceil(32) {
+                return None;
+            }
+            masks.push(
+                (0..subcells)
+                    .map(|i| bits[i / 32] >> (i % 32) & 1 == 1)
+                    .collect(),
+            );
+        }
+        Some(Rule {
+            bases,
+            split,
+            masks,
+        })
+    }
+
+    /// The lattice's side along `axis`, as the slicer's `Rule::side_along`.
+    fn side(&self, axis: usize, depth: u32) -> usize {
+        match self.split {
+            Split::Uniform => (self.bases[axis] as usize).saturating_pow(depth),
+            Split::Pell => pell(depth as i64 + 1),
+            Split::Flake => 2usize.saturating_pow(depth.saturating_add(1)) - 1,
+        }
+    }
+
+    fn is_solid(&self, coords: &[usize], depth: u32) -> bool {
+        fractal_slicer_core::is_solid(&self.bases, self.split, coords, depth, |level, index| {
+            self.masks[(level.max(1) as usize - 1) % self.masks.len()][index]
+        })
+    }
+
+    /// The triangles of the surface of the slice at `w`: positions and
+    /// normals, three floats a vertex.
+    fn slice(&self, depth: u32, w: usize) -> Option<(Vec<f32>, Vec<f32>)> {
+        let [nx, ny, nz] = [0, 1, 2].map(|axis| self.side(axis, depth));
+        let cells = nx.checked_mul(ny)?.checked_mul(nz)?;
+        if w >= self.side(3, depth) || cells > MAX_SLICE_CELLS {
+            return None;
+        }
+        let mut solid = vec![false; cells];
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    solid[x + nx * (y + ny * z)] = self.is_solid(&[x, y, z, w], depth);
+                }
+            }
+        }
+        let filled = |x: usize, y: usize, z: usize| {
+            x < nx && y < ny && z < nz && solid[x + nx * (y + ny * z)]
+        };
+        let (mut positions, mut normals) = (Vec::new(), Vec::new());
+        for z in 0..nz {
+            for y in 0..ny {
+                for x in 0..nx {
+                    if !filled(x, y, z) {
+                        continue;
+                    }
+                    // Neighbours below 0 wrap to usize::MAX, outside the
+                    // lattice.
+                    let neighbours = [
+                        (x + 1, y, z),
+                        (x.wrapping_sub(1), y, z),
+                        (x, y + 1, z),
+                        (x, y.wrapping_sub(1), z),
+                        (x, y, z + 1),
+                        (x, y, z.wrapping_sub(1)),
+                    ];
+                    for ((corners, normal), (px, py, pz)) in FACES.iter().zip(neighbours) {
+                        if filled(px, py, pz) {
+                            continue;
+                        }
+                        for corner in [0, 1, 2, 0, 2, 3] {
+                            let [cx, cy, cz] = corners[corner];
+                            positions.extend([
+                                (x + cx as usize) as f32,
+                                (y + cy as usize) as f32,
+                                (z + cz as usize) as f32,
+                            ]);
+                            normals.extend(normal);
+                        }
+                    }
+                }
+            }
+        }
+        Some((positions, normals))
+    }
+}
+
+struct State {
+    buffer: Vec<u8>,
+    rule: Option<Rule>,
+    positions: Vec<f32>,
+    normals: Vec<f32>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    buffer: Vec::new(),
+    rule: None,
+    positions: Vec::new(),
+    normals: Vec::new(),
+});
+
+#[no_mangle]
+pub extern "C" fn fs_rule_buffer(len: u32) -> *mut u8 {
+    let mut state = STATE.lock().unwrap();
+    state.buffer = vec![0; len as usize];
+    state.buffer.as_mut_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn fs_load_rule() -> i32 {
+    let mut state = STATE.lock().unwrap();
+    state.rule = Rule::parse(&state.buffer);
+    match state.rule {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn fs_side(axis: u32, depth: u32) -> u32 {
+    let state = STATE.lock().unwrap();
+    let side = match &state.rule {
+        Some(rule) if axis < 4 => rule.side(axis as usize, depth),
+        _ => 0,
+    };
+    u32::try_from(side).unwrap_or(u32::MAX)
+}
+
+#[no_mangle]
+pub extern "C" fn fs_slice(depth: u32, w: u32) -> i32 {
+    let mut state = STATE.lock().unwrap();
+    let Some((positions, normals)) = state
+        .rule
+        .as_ref()
+        .and_then(|rule| rule.slice(depth, w as usize))
+    else {
+        return -1;
+    };
+    let vertices = positions.len() / 3;
+    (state.positions, state.normals) = (positions, normals);
+    i32::try_from(vertices).unwrap_or(-1)
+}
+
+#[no_mangle]
+pub extern "C" fn fs_positions() -> *const f32 {
+    STATE.lock().unwrap().positions.as_ptr()
+}
+
+#[no_mangle]
+pub extern "C" fn fs_normals() -> *const f32 {
+    STATE.lock().unwrap().normals.as_ptr()
+}